@@ -0,0 +1,186 @@
+//! Bracket pairs colored by how deeply they are nested, and guides along the
+//! lines between the brackets of a pair spanning several. Brackets in
+//! strings and comments, as the grammar highlights them, are left out so
+//! they can't unbalance the pairs around them, as are long lines.
+
+use crate::highlight::{self, Grammar, Kind, State};
+use crate::segments::LONG_LINE;
+use std::cmp::Ordering;
+use unicode_segmentation::UnicodeSegmentation;
+
+const PAIRS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
+
+/// A bracket at a zero based line and grapheme column.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bracket {
+  pub line: usize,
+  pub column: usize,
+  /// How many pairs it is nested in, `None` if it has no partner.
+  pub depth: Option<usize>,
+}
+
+/// A vertical line through the lines between the brackets of a pair.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Guide {
+  /// The lines of the opening and the closing bracket.
+  pub open: usize,
+  pub close: usize,
+  /// The indentation of the opening line, in graphemes.
+  pub column: usize,
+  pub depth: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Brackets {
+  /// Sorted by position.
+  pub brackets: Vec<Bracket>,
+  pub guides: Vec<Guide>,
+}
+
+impl Brackets {
+  /// The brackets of `line`, sorted by column.
+  pub fn on_line(&self, line: usize) -> &[Bracket] {
+    // never equal, so the searches end where the line starts and ends
+    let start = self
+      .brackets
+      .binary_search_by(|bracket| {
+        bracket.line.cmp(&line).then(Ordering::Greater)
+      })
+      .unwrap_err();
+    let end = self
+      .brackets
+      .binary_search_by(|bracket| bracket.line.cmp(&line).then(Ordering::Less))
+      .unwrap_err();
+    &self.brackets[start..end]
+  }
+}
+
+/// The brackets of `lines` and the guides of the pairs spanning more than
+/// two lines.
+pub fn find(lines: &[String], grammar: &Grammar) -> Brackets {
+  let mut found = Brackets::default();
+  // indices of the open brackets and the closers they wait for
+  let mut open: Vec<(usize, &str)> = vec![];
+  let mut state = State::Normal;
+  for (row, line) in lines.iter().enumerate() {
+    // too long to go through whenever the text changes
+    if line.len() > LONG_LINE {
+      continue;
+    }
+    let (spans, next) = highlight::highlight_line(line, state, grammar);
+    state = next;
+    let quoted = |column| {
+      spans.iter().any(|span| {
+        matches!(span.kind, Kind::String | Kind::Comment)
+          && (span.start..span.end).contains(&column)
+      })
+    };
+
+    for (column, grapheme) in line.graphemes(true).enumerate() {
+      let opener = PAIRS.iter().find(|(opener, _)| *opener == grapheme);
+      let closes = PAIRS.iter().any(|(_, closer)| *closer == grapheme);
+      if (opener.is_none() && !closes) || quoted(column) {
+        continue;
+      }
+      let depth = match opener {
+        Some((_, closer)) => {
+          open.push((found.brackets.len(), closer));
+          Some(open.len() - 1)
+        }
+        // the brackets left open inside the pair it closes have no partner
+        None => {
+          match open.iter().rposition(|(_, closer)| *closer == grapheme) {
+            Some(at) => {
+              for (i, _) in open.drain(at + 1..) {
+                found.brackets[i].depth = None;
+              }
+              let (i, _) = open.pop().unwrap();
+              let opening = found.brackets[i];
+              if row > opening.line + 1 {
+                let indent = lines[opening.line]
+                  .graphemes(true)
+                  .take_while(|grapheme| grapheme.trim().is_empty())
+                  .count();
+                found.guides.push(Guide {
+                  open: opening.line,
+                  close: row,
+                  column: indent,
+                  depth: open.len(),
+                });
+              }
+              opening.depth
+            }
+            None => None,
+          }
+        }
+      };
+      found.brackets.push(Bracket {
+        line: row,
+        column,
+        depth,
+      });
+    }
+  }
+  for (i, _) in open {
+    found.brackets[i].depth = None;
+  }
+  found
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(text: &str) -> Vec<String> {
+    text.lines().map(String::from).collect()
+  }
+
+  #[test]
+  fn nested_pairs() {
+    let text = lines(
+      "fn main() {
+  let x = [(1), \"(\"]; // )
+  if x {
+    y(]
+  }
+}
+(",
+    );
+    let found = find(&text, &Grammar::generic());
+    let depths = |line| {
+      found
+        .on_line(line)
+        .iter()
+        .map(|bracket| (bracket.column, bracket.depth))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(depths(0), vec![(7, Some(0)), (8, Some(0)), (10, Some(0))]);
+    // the brackets in the string and the comment don't count
+    assert_eq!(
+      depths(1),
+      vec![(10, Some(1)), (11, Some(2)), (13, Some(2)), (19, Some(1))]
+    );
+    // an opener closed by the pair around it and a closer of nothing
+    assert_eq!(depths(3), vec![(5, None), (6, None)]);
+    assert_eq!(depths(4), vec![(2, Some(1))]);
+    assert_eq!(depths(6), vec![(0, None)]);
+
+    assert_eq!(
+      found.guides,
+      vec![
+        Guide {
+          open: 2,
+          close: 4,
+          column: 2,
+          depth: 1,
+        },
+        Guide {
+          open: 0,
+          close: 5,
+          column: 0,
+          depth: 0,
+        },
+      ]
+    );
+  }
+}
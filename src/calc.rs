@@ -0,0 +1,535 @@
+//! Evaluation of the quick calculations typed into the command palette, e.g.
+//! `3*(17+4)`, `0xff & 0b1010 to bin` or `12 km to mi`.
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Token<'a> {
+  Number(f64),
+  Ident(&'a str),
+  Op(&'static str),
+}
+
+const OPERATORS: &[&str] = &[
+  "**", "<<", ">>", "+", "-", "*", "/", "%", "^", "&", "|", "~", "(", ")",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token<'_>>, anyhow::Error> {
+  let mut tokens = vec![];
+  let mut rest = input.trim_start();
+
+  while let Some(ch) = rest.chars().next() {
+    if ch.is_ascii_digit() || ch == '.' {
+      let (number, len) = parse_number(rest)?;
+      tokens.push(Token::Number(number));
+      rest = &rest[len..];
+    } else if ch.is_alphabetic() || ch == '_' {
+      let len = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+      tokens.push(Token::Ident(&rest[..len]));
+      rest = &rest[len..];
+    } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+      tokens.push(Token::Op(op));
+      rest = &rest[op.len()..];
+    } else {
+      anyhow::bail!("unexpected character '{}'", ch);
+    }
+    rest = rest.trim_start();
+  }
+
+  Ok(tokens)
+}
+
+fn parse_number(input: &str) -> Result<(f64, usize), anyhow::Error> {
+  let radix = match input.get(..2) {
+    Some("0x") | Some("0X") => 16,
+    Some("0b") | Some("0B") => 2,
+    Some("0o") | Some("0O") => 8,
+    _ => 10,
+  };
+
+  if radix != 10 {
+    let digits = &input[2..];
+    let len = digits
+      .find(|c: char| !(c.is_digit(radix) || c == '_'))
+      .unwrap_or(digits.len());
+    let value = i64::from_str_radix(&digits[..len].replace('_', ""), radix)
+      .map_err(|_| anyhow::anyhow!("invalid number '{}'", &input[..len + 2]))?;
+    return Ok((value as f64, len + 2));
+  }
+
+  let mut len = input
+    .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '_'))
+    .unwrap_or(input.len());
+  // exponent, but only if it is followed by digits so `2e` isn't swallowed
+  if let Some(exponent) = input[len..]
+    .strip_prefix('e')
+    .or_else(|| input[len..].strip_prefix('E'))
+  {
+    let exponent = exponent
+      .strip_prefix('-')
+      .or_else(|| exponent.strip_prefix('+'))
+      .unwrap_or(exponent);
+    let digits = exponent
+      .find(|c: char| !c.is_ascii_digit())
+      .unwrap_or(exponent.len());
+    if digits != 0 {
+      len = input.len() - exponent.len() + digits;
+    }
+  }
+
+  let number = input[..len]
+    .replace('_', "")
+    .parse::<f64>()
+    .map_err(|_| anyhow::anyhow!("invalid number '{}'", &input[..len]))?;
+  Ok((number, len))
+}
+
+struct Parser<'a> {
+  tokens: Vec<Token<'a>>,
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn peek(&self) -> Option<Token<'a>> {
+    self.tokens.get(self.pos).copied()
+  }
+
+  fn eat_op(&mut self, ops: &[&str]) -> Option<&'static str> {
+    match self.peek() {
+      Some(Token::Op(op)) if ops.contains(&op) => {
+        self.pos += 1;
+        Some(op)
+      }
+      _ => None,
+    }
+  }
+
+  fn eat_ident(&mut self, idents: &[&str]) -> bool {
+    match self.peek() {
+      Some(Token::Ident(ident)) if idents.contains(&ident) => {
+        self.pos += 1;
+        true
+      }
+      _ => false,
+    }
+  }
+
+  fn expr(&mut self) -> Result<f64, anyhow::Error> {
+    let mut value = self.bit_xor()?;
+    while self.eat_op(&["|"]).is_some() {
+      value = (integer(value)? | integer(self.bit_xor()?)?) as f64;
+    }
+    Ok(value)
+  }
+
+  fn bit_xor(&mut self) -> Result<f64, anyhow::Error> {
+    let mut value = self.bit_and()?;
+    while self.eat_ident(&["xor"]) {
+      value = (integer(value)? ^ integer(self.bit_and()?)?) as f64;
+    }
+    Ok(value)
+  }
+
+  fn bit_and(&mut self) -> Result<f64, anyhow::Error> {
+    let mut value = self.shift()?;
+    while self.eat_op(&["&"]).is_some() {
+      value = (integer(value)? & integer(self.shift()?)?) as f64;
+    }
+    Ok(value)
+  }
+
+  fn shift(&mut self) -> Result<f64, anyhow::Error> {
+    let mut value = self.additive()?;
+    while let Some(op) = self.eat_op(&["<<", ">>"]) {
+      let rhs = integer(self.additive()?)?;
+      if !(0..64).contains(&rhs) {
+        anyhow::bail!("shift amount out of range");
+      }
+      value = if op == "<<" {
+        integer(value)? << rhs
+      } else {
+        integer(value)? >> rhs
+      } as f64;
+    }
+    Ok(value)
+  }
+
+  fn additive(&mut self) -> Result<f64, anyhow::Error> {
+    let mut value = self.term()?;
+    while let Some(op) = self.eat_op(&["+", "-"]) {
+      let rhs = self.term()?;
+      value = if op == "+" { value + rhs } else { value - rhs };
+    }
+    Ok(value)
+  }
+
+  fn term(&mut self) -> Result<f64, anyhow::Error> {
+    let mut value = self.unary()?;
+    while let Some(op) = self.eat_op(&["*", "/", "%"]) {
+      let rhs = self.unary()?;
+      value = match op {
+        "*" => value * rhs,
+        _ if rhs == 0.0 => anyhow::bail!("division by zero"),
+        "/" => value / rhs,
+        _ => value % rhs,
+      };
+    }
+    Ok(value)
+  }
+
+  fn unary(&mut self) -> Result<f64, anyhow::Error> {
+    match self.eat_op(&["-", "+", "~"]) {
+      Some("-") => Ok(-self.unary()?),
+      Some("~") => Ok(!integer(self.unary()?)? as f64),
+      Some(_) => self.unary(),
+      None => self.power(),
+    }
+  }
+
+  fn power(&mut self) -> Result<f64, anyhow::Error> {
+    let base = self.primary()?;
+    if self.eat_op(&["^", "**"]).is_some() {
+      Ok(base.powf(self.unary()?))
+    } else {
+      Ok(base)
+    }
+  }
+
+  fn primary(&mut self) -> Result<f64, anyhow::Error> {
+    match self.peek() {
+      Some(Token::Number(number)) => {
+        self.pos += 1;
+        Ok(number)
+      }
+      Some(Token::Op("(")) => {
+        self.pos += 1;
+        let value = self.expr()?;
+        self
+          .eat_op(&[")"])
+          .ok_or_else(|| anyhow::anyhow!("missing ')'"))?;
+        Ok(value)
+      }
+      Some(Token::Ident(ident)) => {
+        self.pos += 1;
+        match ident {
+          "pi" => return Ok(std::f64::consts::PI),
+          "e" => return Ok(std::f64::consts::E),
+          _ => {}
+        }
+
+        let function: fn(f64) -> f64 = match ident {
+          "sqrt" => f64::sqrt,
+          "abs" => f64::abs,
+          "floor" => f64::floor,
+          "ceil" => f64::ceil,
+          "round" => f64::round,
+          "ln" => f64::ln,
+          "log2" => f64::log2,
+          "log10" => f64::log10,
+          "sin" => f64::sin,
+          "cos" => f64::cos,
+          "tan" => f64::tan,
+          _ => anyhow::bail!("unknown function '{}'", ident),
+        };
+        self
+          .eat_op(&["("])
+          .ok_or_else(|| anyhow::anyhow!("expected '(' after {}", ident))?;
+        let value = self.expr()?;
+        self
+          .eat_op(&[")"])
+          .ok_or_else(|| anyhow::anyhow!("missing ')'"))?;
+        Ok(function(value))
+      }
+      Some(Token::Op(op)) => anyhow::bail!("unexpected '{}'", op),
+      None => anyhow::bail!("unexpected end of expression"),
+    }
+  }
+}
+
+fn integer(value: f64) -> Result<i64, anyhow::Error> {
+  if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+    Ok(value as i64)
+  } else {
+    anyhow::bail!("{} is not an integer", format_number(value))
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Quantity {
+  Length,
+  Mass,
+  Data,
+  Time,
+  Temperature,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Unit {
+  names: &'static [&'static str],
+  quantity: Quantity,
+  /// Multiplier to get to the base unit of the quantity.
+  factor: f64,
+  /// Added after scaling, only needed for temperatures.
+  offset: f64,
+}
+
+macro_rules! unit {
+  ($quantity: ident, $factor: expr, $($name: literal),+) => {
+    Unit {
+      names: &[$($name),+],
+      quantity: Quantity::$quantity,
+      factor: $factor,
+      offset: 0.0,
+    }
+  };
+}
+
+const UNITS: &[Unit] = &[
+  unit!(Length, 0.001, "mm"),
+  unit!(Length, 0.01, "cm"),
+  unit!(Length, 1.0, "m"),
+  unit!(Length, 1000.0, "km"),
+  unit!(Length, 0.0254, "in", "inch", "inches"),
+  unit!(Length, 0.3048, "ft", "feet", "foot"),
+  unit!(Length, 0.9144, "yd", "yard", "yards"),
+  unit!(Length, 1609.344, "mi", "mile", "miles"),
+  unit!(Mass, 0.000001, "mg"),
+  unit!(Mass, 0.001, "g"),
+  unit!(Mass, 1.0, "kg"),
+  unit!(Mass, 0.028349523125, "oz"),
+  unit!(Mass, 0.45359237, "lb", "lbs"),
+  unit!(Data, 0.125, "bit", "bits"),
+  unit!(Data, 1.0, "b", "byte", "bytes"),
+  unit!(Data, 1e3, "kb"),
+  unit!(Data, 1e6, "mb"),
+  unit!(Data, 1e9, "gb"),
+  unit!(Data, 1e12, "tb"),
+  unit!(Data, 1024.0, "kib"),
+  unit!(Data, 1048576.0, "mib"),
+  unit!(Data, 1073741824.0, "gib"),
+  unit!(Data, 1099511627776.0, "tib"),
+  unit!(Time, 0.001, "ms"),
+  unit!(Time, 1.0, "s", "sec", "secs"),
+  unit!(Time, 60.0, "min", "mins"),
+  unit!(Time, 3600.0, "h", "hr", "hours"),
+  unit!(Time, 86400.0, "d", "day", "days"),
+  unit!(Temperature, 1.0, "k", "kelvin"),
+  Unit {
+    names: &["c", "celsius"],
+    quantity: Quantity::Temperature,
+    factor: 1.0,
+    offset: 273.15,
+  },
+  Unit {
+    names: &["f", "fahrenheit"],
+    quantity: Quantity::Temperature,
+    factor: 5.0 / 9.0,
+    offset: 273.15 - (32.0 * 5.0 / 9.0),
+  },
+];
+
+fn find_unit(name: &str) -> Option<&'static Unit> {
+  let name = name.to_lowercase();
+  UNITS
+    .iter()
+    .find(|unit| unit.names.contains(&name.as_str()))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Radix {
+  Decimal,
+  Hexadecimal,
+  Binary,
+  Octal,
+}
+
+impl Radix {
+  fn from_name(name: &str) -> Option<Self> {
+    match name.to_lowercase().as_str() {
+      "dec" | "decimal" => Some(Radix::Decimal),
+      "hex" | "hexadecimal" => Some(Radix::Hexadecimal),
+      "bin" | "binary" => Some(Radix::Binary),
+      "oct" | "octal" => Some(Radix::Octal),
+      _ => None,
+    }
+  }
+
+  fn format(self, value: i64) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let value = i128::from(value).abs();
+    match self {
+      Radix::Decimal => format!("{}{}", sign, value),
+      Radix::Hexadecimal => format!("{}{:#x}", sign, value),
+      Radix::Binary => format!("{}{:#b}", sign, value),
+      Radix::Octal => format!("{}{:#o}", sign, value),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Answer {
+  pub value: f64,
+  pub unit: Option<&'static Unit>,
+  pub radix: Radix,
+}
+
+impl Answer {
+  /// The result formatted the way it was asked for.
+  pub fn display(&self) -> String {
+    let number = match integer(self.value) {
+      Ok(value) => self.radix.format(value),
+      Err(_) => format_number(self.value),
+    };
+    match self.unit {
+      Some(unit) => format!("{} {}", number, unit.names[0]),
+      None => number,
+    }
+  }
+
+  /// Other representations of an integer result, for programmer math.
+  pub fn alternatives(&self) -> Vec<String> {
+    match (self.unit, integer(self.value)) {
+      (None, Ok(value)) => [Radix::Decimal, Radix::Hexadecimal, Radix::Binary]
+        .iter()
+        .filter(|radix| **radix != self.radix)
+        .map(|radix| radix.format(value))
+        .collect(),
+      _ => vec![],
+    }
+  }
+}
+
+fn format_number(value: f64) -> String {
+  if value.fract() == 0.0 && value.abs() < 1e15 {
+    format!("{}", value as i64)
+  } else if value != 0.0 && (value.abs() >= 1e15 || value.abs() < 1e-6) {
+    format!("{:e}", value)
+  } else {
+    let formatted = format!("{:.10}", value);
+    formatted
+      .trim_end_matches('0')
+      .trim_end_matches('.')
+      .to_string()
+  }
+}
+
+/// Evaluates an expression, optionally followed by a unit and a conversion
+/// such as `to mi` or `in hex`.
+pub fn evaluate(input: &str) -> Result<Answer, anyhow::Error> {
+  let mut parser = Parser {
+    tokens: tokenize(input)?,
+    pos: 0,
+  };
+  let mut value = parser.expr()?;
+
+  let rest = &parser.tokens[parser.pos..];
+  let is_conversion = |tokens: &[Token<'_>]| {
+    matches!(
+      tokens,
+      [Token::Ident("to"), Token::Ident(_)]
+        | [Token::Ident("in"), Token::Ident(_)]
+        | [Token::Ident("as"), Token::Ident(_)]
+    )
+  };
+
+  // `5 in to cm` has a unit, `0xff in bin` has not
+  let unit = match rest.first() {
+    Some(Token::Ident(name)) if !is_conversion(rest) => {
+      parser.pos += 1;
+      Some(
+        find_unit(name)
+          .ok_or_else(|| anyhow::anyhow!("unknown unit '{}'", name))?,
+      )
+    }
+    _ => None,
+  };
+
+  let mut answer = Answer {
+    value,
+    unit,
+    radix: Radix::Decimal,
+  };
+
+  match &parser.tokens[parser.pos..] {
+    [] => {}
+    rest @ [_, Token::Ident(target)] if is_conversion(rest) => {
+      if let Some(radix) = Radix::from_name(target) {
+        answer.radix = radix;
+      } else {
+        let to = find_unit(target)
+          .ok_or_else(|| anyhow::anyhow!("unknown unit '{}'", target))?;
+        let from = unit.ok_or_else(|| {
+          anyhow::anyhow!("nothing to convert to {}", to.names[0])
+        })?;
+        if from.quantity != to.quantity {
+          anyhow::bail!("can't convert {} to {}", from.names[0], to.names[0]);
+        }
+        value = ((value * from.factor + from.offset) - to.offset) / to.factor;
+        answer = Answer {
+          value,
+          unit: Some(to),
+          radix: Radix::Decimal,
+        };
+      }
+    }
+    [Token::Op(op), ..] => anyhow::bail!("unexpected '{}'", op),
+    [token, ..] => anyhow::bail!("unexpected {:?}", token),
+  }
+
+  Ok(answer)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn display(input: &str) -> String {
+    evaluate(input).unwrap().display()
+  }
+
+  #[test]
+  fn arithmetic() {
+    assert_eq!(display("3*(17+4)"), "63");
+    assert_eq!(display("-2^2"), "-4");
+    assert_eq!(display("2^3^2"), "512");
+    assert_eq!(display("7 / 2"), "3.5");
+    assert_eq!(display("0.1 + 0.2"), "0.3");
+    assert_eq!(display("1.5e3 % 7"), "2");
+    assert_eq!(display("sqrt(16) + pi - pi"), "4");
+  }
+
+  #[test]
+  fn programmer_math() {
+    assert_eq!(display("0xff & 0b1010"), "10");
+    assert_eq!(display("1 << 10"), "1024");
+    assert_eq!(display("0xf0 | 0x0f to hex"), "0xff");
+    assert_eq!(display("6 xor 3 in bin"), "0b101");
+    assert_eq!(display("-255 as hex"), "-0xff");
+    assert_eq!(
+      evaluate("255").unwrap().alternatives(),
+      vec!["0xff", "0b11111111"]
+    );
+    assert!(evaluate("1.5 | 1").is_err());
+  }
+
+  #[test]
+  fn units() {
+    assert_eq!(display("10 km"), "10 km");
+    assert_eq!(display("1 mi to km"), "1.609344 km");
+    assert_eq!(display("12 in to ft"), "1 ft");
+    assert_eq!(display("100 c to f"), "212 f");
+    assert_eq!(display("2 gib in mib"), "2048 mib");
+    assert!(evaluate("1 kg to m").is_err());
+    assert!(evaluate("5 to km").is_err());
+  }
+
+  #[test]
+  fn errors() {
+    assert!(evaluate("").is_err());
+    assert!(evaluate("1 +").is_err());
+    assert!(evaluate("(1 + 2").is_err());
+    assert!(evaluate("1 / 0").is_err());
+    assert!(evaluate("foo(1)").is_err());
+    assert!(evaluate("1 $ 2").is_err());
+  }
+}
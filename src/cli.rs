@@ -0,0 +1,291 @@
+use crate::lsp;
+use crate::text;
+use std::path::{Path, PathBuf};
+
+/// Links other programs use to open files, see [`FileArg::from_link`].
+pub const LINK_SCHEME: &str = "devcode://";
+
+pub const USAGE: &str = "\
+usage: devcode [options] <file[:line[:column]]>...
+       devcode [options] -d <left> <right>
+       devcode --register
+
+  -                  read the text to show from stdin
+  -d, --diff         compare two files
+  -w, --wait         exit once all opened files are closed
+  --open             open the files in a running instance whose workspace
+                     contains them, or in a new one for their repository
+  --register         add devcode to the \"Open With\" menu of the OS
+  --language <name>  language of text read from stdin
+  --font <name>      name of an installed font to use
+  -h, --help         show this message
+
+files can also be given as file:// URIs or as
+devcode://open?file=<path>&line=<line>&column=<column> links";
+
+/// A file given on the command line, optionally with a position to open
+/// at. Line and column are zero based.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileArg {
+  pub path: PathBuf,
+  pub position: Option<(usize, usize)>,
+}
+
+impl FileArg {
+  /// Splits a `:line` or `:line:column` suffix off `arg`, unless a file
+  /// with the full name exists.
+  fn parse(arg: &str) -> Self {
+    let mut path = arg;
+    let mut numbers = vec![];
+    if !Path::new(arg).exists() {
+      while numbers.len() < 2 {
+        let (rest, number) = match path.rfind(':') {
+          Some(i) => (&path[..i], &path[i + 1..]),
+          None => break,
+        };
+        match number.parse::<usize>() {
+          Ok(number) => numbers.insert(0, number.saturating_sub(1)),
+          Err(_) => break,
+        }
+        path = rest;
+      }
+    }
+
+    Self {
+      path: PathBuf::from(path),
+      position: match numbers[..] {
+        [line] => Some((line, 0)),
+        [line, column] => Some((line, column)),
+        _ => None,
+      },
+    }
+  }
+
+  /// Parses a `devcode://open?file=<path>&line=<line>&column=<column>`
+  /// link, where the path is percent encoded and line and column are one
+  /// based and optional.
+  fn from_link(link: &str) -> Result<Self, anyhow::Error> {
+    let rest = link.strip_prefix(LINK_SCHEME).unwrap_or(link);
+    let (action, query) = match rest.find('?') {
+      Some(i) => (&rest[..i], &rest[i + 1..]),
+      None => (rest, ""),
+    };
+    if action.trim_end_matches('/') != "open" {
+      anyhow::bail!("unsupported link '{}'", link);
+    }
+
+    let mut path = None;
+    let mut line = None;
+    let mut column = None;
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+      let (key, value) = match pair.find('=') {
+        Some(i) => (&pair[..i], &pair[i + 1..]),
+        None => (pair, ""),
+      };
+      let value = text::percent_decode(&value.replace('+', " "))
+        .and_then(|value| String::from_utf8(value).ok())
+        .ok_or_else(|| anyhow::anyhow!("invalid encoding in '{}'", link))?;
+      match key {
+        "file" => path = Some(PathBuf::from(value)),
+        "line" => line = Some(value.parse::<usize>()?),
+        "column" => column = Some(value.parse::<usize>()?),
+        _ => {}
+      }
+    }
+
+    Ok(Self {
+      path: path
+        .ok_or_else(|| anyhow::anyhow!("link '{}' names no file", link))?,
+      position: line.map(|line| {
+        (
+          line.saturating_sub(1),
+          column.unwrap_or(1).saturating_sub(1),
+        )
+      }),
+    })
+  }
+
+  pub fn is_stdin(&self) -> bool {
+    self.path == Path::new("-")
+  }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+  pub files: Vec<FileArg>,
+  /// Two files to compare instead of opening `files`.
+  pub diff: Option<(PathBuf, PathBuf)>,
+  pub wait: bool,
+  /// Whether the files are handed to a running instance, as done when
+  /// opening them from the OS file manager.
+  pub open: bool,
+  /// Whether to register devcode with the OS instead of opening files.
+  pub register: bool,
+  /// Whether to print the usage instead of opening files.
+  pub help: bool,
+  pub language: Option<String>,
+  pub font: Option<String>,
+}
+
+impl Args {
+  pub fn parse(
+    mut raw_args: impl Iterator<Item = String>,
+  ) -> Result<Self, anyhow::Error> {
+    let mut args = Self::default();
+    let mut diff = false;
+    let mut positional = vec![];
+
+    while let Some(arg) = raw_args.next() {
+      let mut value = |name: &str| {
+        raw_args
+          .next()
+          .ok_or_else(|| anyhow::anyhow!("{} requires a value", name))
+      };
+
+      match arg.as_str() {
+        "--language" => args.language = Some(value("--language")?),
+        "--font" => args.font = Some(value("--font")?),
+        "-d" | "--diff" => diff = true,
+        "-w" | "--wait" => args.wait = true,
+        "--open" => args.open = true,
+        "--register" => args.register = true,
+        "-h" | "--help" => {
+          args.help = true;
+          return Ok(args);
+        }
+        _ => {
+          if let Some(language) = arg.strip_prefix("--language=") {
+            args.language = Some(language.to_string());
+          } else if let Some(font) = arg.strip_prefix("--font=") {
+            args.font = Some(font.to_string());
+          } else if arg.starts_with('-') && arg != "-" {
+            anyhow::bail!("unknown option '{}'\n\n{}", arg, USAGE);
+          } else {
+            positional.push(arg);
+          }
+        }
+      }
+    }
+
+    if args.register {
+      return Ok(args);
+    }
+    // the files are handed off, so there is nothing to wait for
+    if args.open && args.wait {
+      anyhow::bail!("--open can't be combined with --wait\n\n{}", USAGE);
+    }
+    if diff {
+      if positional.len() != 2 {
+        anyhow::bail!("--diff requires exactly two files\n\n{}", USAGE);
+      }
+      let right = PathBuf::from(positional.pop().unwrap());
+      let left = PathBuf::from(positional.pop().unwrap());
+      args.diff = Some((left, right));
+    } else if positional.is_empty() {
+      anyhow::bail!("no file provided\n\n{}", USAGE);
+    } else {
+      args.files = positional
+        .iter()
+        .map(|arg| {
+          if arg.starts_with(LINK_SCHEME) {
+            FileArg::from_link(arg)
+          } else if let Some(path) = lsp::uri_to_path(arg) {
+            Ok(FileArg {
+              path,
+              position: None,
+            })
+          } else {
+            Ok(FileArg::parse(arg))
+          }
+        })
+        .collect::<Result<_, _>>()?;
+    }
+
+    Ok(args)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &[&str]) -> Result<Args, anyhow::Error> {
+    Args::parse(args.iter().map(|arg| arg.to_string()))
+  }
+
+  #[test]
+  fn file_positions() {
+    assert_eq!(
+      FileArg::parse("does/not/exist.rs:120:8"),
+      FileArg {
+        path: PathBuf::from("does/not/exist.rs"),
+        position: Some((119, 7)),
+      }
+    );
+    assert_eq!(
+      FileArg::parse("does/not/exist.rs:120"),
+      FileArg {
+        path: PathBuf::from("does/not/exist.rs"),
+        position: Some((119, 0)),
+      }
+    );
+    assert_eq!(
+      FileArg::parse("does/not/exist.rs:a"),
+      FileArg {
+        path: PathBuf::from("does/not/exist.rs:a"),
+        position: None,
+      }
+    );
+  }
+
+  #[test]
+  fn links() {
+    assert_eq!(
+      FileArg::from_link("devcode://open?file=/a%20b/c.rs&line=3&column=7")
+        .unwrap(),
+      FileArg {
+        path: PathBuf::from("/a b/c.rs"),
+        position: Some((2, 6)),
+      }
+    );
+    assert_eq!(
+      FileArg::from_link("devcode://open/?file=c.rs").unwrap(),
+      FileArg {
+        path: PathBuf::from("c.rs"),
+        position: None,
+      }
+    );
+    assert!(FileArg::from_link("devcode://open?line=3").is_err());
+    assert!(FileArg::from_link("devcode://delete?file=c.rs").is_err());
+    assert!(FileArg::from_link("devcode://open?file=c.rs&line=x").is_err());
+
+    let args = parse(&["file:///a%20b.rs", "devcode://open?file=c.rs"]);
+    assert_eq!(args.unwrap().files[0].path, PathBuf::from("/a b.rs"));
+  }
+
+  #[test]
+  fn parse_args() {
+    let args = parse(&["-w", "a.rs", "b.rs:3", "--font", "Mono"]).unwrap();
+    assert!(args.wait);
+    assert_eq!(args.font.as_deref(), Some("Mono"));
+    assert_eq!(args.files.len(), 2);
+    assert_eq!(args.files[1].position, Some((2, 0)));
+
+    let args = parse(&["-d", "a.rs", "b.rs"]).unwrap();
+    assert_eq!(
+      args.diff,
+      Some((PathBuf::from("a.rs"), PathBuf::from("b.rs")))
+    );
+
+    assert!(parse(&[]).is_err());
+    assert!(parse(&["-d", "a.rs"]).is_err());
+    assert!(parse(&["--unknown", "a.rs"]).is_err());
+    assert!(parse(&["-"]).unwrap().files[0].is_stdin());
+    assert!(parse(&["--open", "a.rs"]).unwrap().open);
+    assert!(parse(&["--open", "-w", "a.rs"]).is_err());
+    assert!(parse(&["--unknown", "--help"]).is_err());
+    assert!(parse(&["--help", "--unknown"]).unwrap().help);
+    assert!(parse(&["-h"]).unwrap().help);
+    assert!(parse(&["--register"]).unwrap().register);
+  }
+}
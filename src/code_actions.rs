@@ -0,0 +1,136 @@
+//! Code actions a language server offers for the text at the cursor, like
+//! quick fixes, refactorings and organizing the imports.
+
+use serde_json::{json, Value};
+
+/// The kind of the actions organizing the imports of a file.
+pub const ORGANIZE_IMPORTS: &str = "source.organizeImports";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeAction {
+  pub title: String,
+  pub kind: Option<String>,
+  /// The `WorkspaceEdit` it makes, `None` if it has to be resolved first or
+  /// it only runs a command.
+  pub edit: Option<Value>,
+  /// Run by the server after the edit is applied.
+  pub command: Option<Value>,
+  /// The action as the server sent it, to resolve it with.
+  pub raw: Value,
+}
+
+impl CodeAction {
+  /// Whether the server has to fill in its edit first.
+  pub fn needs_resolve(&self) -> bool {
+    self.edit.is_none() && self.raw.get("data").is_some()
+  }
+}
+
+/// Parses a `CodeAction`, or a bare `Command` which is run as it is.
+/// Actions the server disabled are left out.
+pub fn parse_action(value: &Value) -> Option<CodeAction> {
+  let title = value["title"].as_str()?.to_string();
+  if value.get("disabled").is_some() {
+    return None;
+  }
+  // a command has the name of the command where an action has its own
+  if value["command"].is_string() {
+    return Some(CodeAction {
+      title,
+      kind: None,
+      edit: None,
+      command: Some(value.clone()),
+      raw: value.clone(),
+    });
+  }
+  Some(CodeAction {
+    title,
+    kind: value["kind"].as_str().map(String::from),
+    edit: value.get("edit").cloned(),
+    command: value.get("command").cloned(),
+    raw: value.clone(),
+  })
+}
+
+/// The actions of a `textDocument/codeAction` response, the preferred ones
+/// first.
+pub fn parse_actions(result: &Value) -> Vec<CodeAction> {
+  let mut actions = result
+    .as_array()
+    .map(|actions| {
+      actions
+        .iter()
+        .filter_map(|action| {
+          let preferred = action["isPreferred"].as_bool().unwrap_or(false);
+          Some((!preferred, parse_action(action)?))
+        })
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  actions.sort_by_key(|(not_preferred, _)| *not_preferred);
+  actions.into_iter().map(|(_, action)| action).collect()
+}
+
+/// The parameters asking for the actions of `line` from the UTF-16 column
+/// `start` to `end`, only those of kind `only` if given.
+pub fn request_params(
+  uri: &str,
+  line: usize,
+  start: usize,
+  end: usize,
+  only: Option<&str>,
+) -> Value {
+  let mut context = json!({ "diagnostics": [] });
+  if let Some(only) = only {
+    context["only"] = json!([only]);
+  }
+  json!({
+    "textDocument": { "uri": uri },
+    "range": {
+      "start": { "line": line, "character": start },
+      "end": { "line": line, "character": end },
+    },
+    "context": context,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_actions() {
+    let edit = json!({ "changes": {} });
+    let result = json!([
+      { "title": "Run", "command": "run", "arguments": [1] },
+      { "title": "Extract", "kind": "refactor.extract", "data": 7 },
+      {
+        "title": "Import Foo",
+        "kind": "quickfix",
+        "isPreferred": true,
+        "edit": edit,
+      },
+      { "title": "Inline", "disabled": { "reason": "not here" } },
+    ]);
+    let actions = parse_actions(&result);
+    let titles = actions
+      .iter()
+      .map(|action| action.title.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(titles, vec!["Import Foo", "Run", "Extract"]);
+    assert_eq!(actions[0].edit, Some(edit));
+    assert_eq!(actions[0].kind.as_deref(), Some("quickfix"));
+    assert_eq!(
+      actions[1].command.as_ref().unwrap()["arguments"],
+      json!([1])
+    );
+    assert!(!actions[1].needs_resolve());
+    assert!(actions[2].needs_resolve());
+    assert!(parse_actions(&Value::Null).is_empty());
+
+    let params =
+      request_params("file:///a.rs", 2, 0, 4, Some(ORGANIZE_IMPORTS));
+    assert_eq!(params["context"]["only"], json!([ORGANIZE_IMPORTS]));
+    assert_eq!(params["range"]["end"]["character"], 4);
+  }
+}
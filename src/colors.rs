@@ -0,0 +1,136 @@
+//! Color literals in source text, such as `#1e1e1e` or `rgb(30, 30, 30)`.
+
+use crate::text::is_word;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+  /// `#rrggbb` or `#rgb`.
+  Hex,
+  /// `rgb(r, g, b)` with channels from 0 to 255.
+  Rgb,
+}
+
+/// A color written in a line, spanning the graphemes `start..end`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorLiteral {
+  pub start: usize,
+  pub end: usize,
+  /// The sRGB channels.
+  pub rgb: [u8; 3],
+  pub format: Format,
+}
+
+/// Writes `rgb` in `format`, always with two digits per hex channel.
+pub fn format(rgb: [u8; 3], format: Format) -> String {
+  match format {
+    Format::Hex => format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]),
+    Format::Rgb => format!("rgb({}, {}, {})", rgb[0], rgb[1], rgb[2]),
+  }
+}
+
+fn parse_hex(digits: &str) -> Option<[u8; 3]> {
+  let channel = |i: usize, width: usize| {
+    let value =
+      u8::from_str_radix(digits.get(i * width..(i + 1) * width)?, 16).ok()?;
+    // #abc is short for #aabbcc
+    Some(if width == 1 { value * 17 } else { value })
+  };
+  let width = match digits.len() {
+    3 => 1,
+    6 => 2,
+    _ => return None,
+  };
+  Some([channel(0, width)?, channel(1, width)?, channel(2, width)?])
+}
+
+fn parse_rgb(arguments: &str) -> Option<[u8; 3]> {
+  let channels = arguments
+    .split(',')
+    .map(|channel| channel.trim().parse::<u8>().ok())
+    .collect::<Option<Vec<_>>>()?;
+  match channels.as_slice() {
+    [r, g, b] => Some([*r, *g, *b]),
+    _ => None,
+  }
+}
+
+/// The color literals in `line`. Hex literals have to stand on their own, so
+/// `a#fff` or `#fffff` aren't colors.
+pub fn find(line: &str) -> Vec<ColorLiteral> {
+  let graphemes = line.graphemes(true).collect::<Vec<_>>();
+  let word_before = |i: usize| i > 0 && is_word(graphemes[i - 1]);
+  let mut colors = vec![];
+  let mut i = 0;
+  while i < graphemes.len() {
+    if graphemes[i] == "#" && !word_before(i) {
+      let end = (i + 1..graphemes.len())
+        .find(|j| !is_word(graphemes[*j]))
+        .unwrap_or(graphemes.len());
+      if let Some(rgb) = parse_hex(&graphemes[i + 1..end].concat()) {
+        colors.push(ColorLiteral {
+          start: i,
+          end,
+          rgb,
+          format: Format::Hex,
+        });
+      }
+      i = end;
+    } else if graphemes[i..].starts_with(&["r", "g", "b", "("])
+      && !word_before(i)
+    {
+      let close = graphemes[i..].iter().position(|g| *g == ")");
+      let literal = close.and_then(|close| {
+        let rgb = parse_rgb(&graphemes[i + 4..i + close].concat())?;
+        Some(ColorLiteral {
+          start: i,
+          end: i + close + 1,
+          rgb,
+          format: Format::Rgb,
+        })
+      });
+      match literal {
+        Some(literal) => {
+          i = literal.end;
+          colors.push(literal);
+        }
+        None => i += 4,
+      }
+    } else {
+      i += 1;
+    }
+  }
+  colors
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn found(line: &str) -> Vec<(usize, usize, [u8; 3])> {
+    find(line)
+      .into_iter()
+      .map(|color| (color.start, color.end, color.rgb))
+      .collect()
+  }
+
+  #[test]
+  fn literals() {
+    assert_eq!(
+      found("color: #1e90FF; border: 1px solid #fff"),
+      vec![(7, 14, [0x1e, 0x90, 0xff]), (34, 38, [255, 255, 255])]
+    );
+    assert_eq!(
+      found("fill = rgb(10,20, 255) + rgb(1, 2)"),
+      vec![(7, 22, [10, 20, 255])]
+    );
+    assert_eq!(found("a#fff #abcd #ggg foo(rgb(300, 0, 0))"), vec![]);
+    assert_eq!(found("// \u{1f600} #000"), vec![(5, 9, [0, 0, 0])]);
+  }
+
+  #[test]
+  fn formats() {
+    assert_eq!(format([0x1e, 0x90, 0xff], Format::Hex), "#1e90ff");
+    assert_eq!(format([1, 2, 3], Format::Rgb), "rgb(1, 2, 3)");
+  }
+}
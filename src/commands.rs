@@ -0,0 +1,108 @@
+macro_rules! commands {
+  ($($command: ident => $id: literal, $name: literal;)*) => {
+    /// Everything that can be triggered from a keybinding or the command
+    /// palette.
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub enum Command {
+      $($command,)*
+    }
+
+    impl Command {
+      pub const ALL: &'static [Command] = &[$(Command::$command,)*];
+
+      pub fn name(self) -> &'static str {
+        match self {
+          $(Command::$command => $name,)*
+        }
+      }
+
+      /// Looks up a command by the identifier used in the config file.
+      pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+          $($id => Some(Command::$command),)*
+          _ => None,
+        }
+      }
+    }
+  };
+}
+
+commands! {
+  Save => "save", "Save";
+  SaveAs => "save-as", "Save As";
+  NewFile => "new-file", "New File";
+  ChangeLanguage => "change-language", "Change Language";
+  CloseTab => "close-tab", "Close Tab";
+  NewWindow => "new-window", "New Window";
+  OpenFolder => "open-folder", "Open Folder";
+  MoveTabToNewWindow => "move-tab-to-new-window", "Move Tab to New Window";
+  PinTab => "pin-tab", "Keep Tab Open";
+  ToggleBookmark => "toggle-bookmark", "Toggle Bookmark";
+  NextBookmark => "next-bookmark", "Go to Next Bookmark";
+  PreviousBookmark => "previous-bookmark", "Go to Previous Bookmark";
+  NextProblem => "next-problem", "Go to Next Problem";
+  PreviousProblem => "previous-problem", "Go to Previous Problem";
+  NextChange => "next-change", "Go to Next Change";
+  PreviousChange => "previous-change", "Go to Previous Change";
+  NextMatch => "next-match", "Go to Next Match";
+  PreviousMatch => "previous-match", "Go to Previous Match";
+  ExpandSelection => "expand-selection", "Expand Selection";
+  ShrinkSelection => "shrink-selection", "Shrink Selection";
+  Undo => "undo", "Undo";
+  Redo => "redo", "Redo";
+  Paste => "paste", "Paste";
+  Copy => "copy", "Copy";
+  Cut => "cut", "Cut";
+  SelectAll => "select-all", "Select All";
+  SelectLeft => "select-left", "Extend Selection Left";
+  SelectRight => "select-right", "Extend Selection Right";
+  CursorWordLeft => "cursor-word-left", "Move Cursor to Previous Word";
+  CursorWordRight => "cursor-word-right", "Move Cursor to Next Word";
+  SelectWordLeft => "select-word-left", "Select to Previous Word";
+  SelectWordRight => "select-word-right", "Select to Next Word";
+  AddCursorAbove => "add-cursor-above", "Add Cursor Above";
+  AddCursorBelow => "add-cursor-below", "Add Cursor Below";
+  ToggleOverwrite => "toggle-overwrite", "Toggle Overwrite Mode";
+  ToggleTypewriter => "toggle-typewriter", "Toggle Typewriter Mode";
+  SortLinesAscending => "sort-lines-ascending", "Sort Lines Ascending";
+  SortLinesDescending => "sort-lines-descending", "Sort Lines Descending";
+  SortLinesUnique => "sort-lines-unique", "Sort Lines and Remove Duplicates";
+  ReverseLines => "reverse-lines", "Reverse Lines";
+  TransformToUppercase => "transform-to-uppercase", "Transform to Uppercase";
+  TransformToLowercase => "transform-to-lowercase", "Transform to Lowercase";
+  TransformToTitleCase => "transform-to-title-case", "Transform to Title Case";
+  Base64Encode => "base64-encode", "Encode as Base64";
+  Base64Decode => "base64-decode", "Decode Base64";
+  UrlEncode => "url-encode", "Encode for URL";
+  UrlDecode => "url-decode", "Decode URL";
+  ShowCommandPalette => "show-command-palette", "Show Command Palette";
+  QuickOpen => "quick-open", "Go to Buffer or File";
+  SwitchBuffer => "switch-buffer", "Switch to Recent Buffer";
+  ToggleDebugOverlay => "toggle-debug-overlay", "Toggle Debug Overlay";
+  ResizeMode => "resize-mode", "Resize Panels with Arrow Keys";
+  GrowPanel => "grow-panel", "Grow Focused Panel";
+  ShrinkPanel => "shrink-panel", "Shrink Focused Panel";
+  EqualizePanels => "equalize-panels", "Reset Panel Sizes";
+  ToggleSidebar => "toggle-sidebar", "Toggle Sidebar";
+  ShowExplorer => "show-explorer", "Show Explorer";
+  ShowOutline => "show-outline", "Show Outline";
+  ShowSearch => "show-search", "Show Search";
+  ShowSourceControl => "show-source-control", "Show Source Control";
+  ShowDebug => "show-debug", "Show Debug";
+  ShowExtensions => "show-extensions", "Show Extensions";
+  InstallPlugin => "install-plugin", "Install Plugin";
+  ReloadPlugins => "reload-plugins", "Reload Plugins";
+  RestartLanguageServer => "restart-language-server", "Restart Language Server";
+  OpenServerLog => "open-server-log", "Open Language Server Log";
+  ToggleMaximizePanel => "toggle-maximize-panel", "Maximize/Restore Focused Panel";
+  ToggleZenMode => "toggle-zen-mode", "Toggle Zen Mode";
+  ToggleFullscreen => "toggle-fullscreen", "Toggle Fullscreen";
+  ToggleScrollLock => "toggle-scroll-lock", "Toggle Scroll Lock";
+  RenameSymbol => "rename-symbol", "Rename Symbol";
+  ShowCodeActions => "show-code-actions", "Show Code Actions";
+  OrganizeImports => "organize-imports", "Organize Imports";
+  ReplaceInFiles => "replace-in-files", "Replace in Files";
+  ToggleOutput => "toggle-output", "Toggle Output Panel";
+  RunLastTask => "run-last-task", "Run Last Task";
+  StopTask => "stop-task", "Stop Task";
+}
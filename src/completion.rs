@@ -0,0 +1,234 @@
+use crate::fuzzy;
+use crate::text::{is_word, is_word_char};
+use serde::Deserialize;
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How completion items are ordered.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sorting {
+  /// By how well the label matches what was typed.
+  Fuzzy,
+  /// By the order the source of the items asked for, `sortText` in LSP.
+  SortText,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+  pub label: String,
+  /// Text to insert, in LSP snippet syntax if `is_snippet` is set.
+  pub insert_text: String,
+  pub is_snippet: bool,
+  pub sort_text: Option<String>,
+  pub documentation: Option<String>,
+}
+
+/// The items matching `query`, best first.
+pub fn filter_and_sort(
+  items: Vec<Item>,
+  query: &str,
+  sorting: Sorting,
+) -> Vec<Item> {
+  let mut items = items
+    .into_iter()
+    .filter_map(|item| Some((fuzzy::score(query, &item.label)?, item)))
+    .collect::<Vec<_>>();
+  match sorting {
+    Sorting::Fuzzy => items.sort_by(|(a_score, a), (b_score, b)| {
+      b_score
+        .cmp(a_score)
+        .then(a.label.len().cmp(&b.label.len()))
+        .then(a.label.cmp(&b.label))
+    }),
+    Sorting::SortText => items.sort_by(|(_, a), (_, b)| {
+      let a_key = a.sort_text.as_ref().unwrap_or(&a.label);
+      let b_key = b.sort_text.as_ref().unwrap_or(&b.label);
+      a_key.cmp(b_key)
+    }),
+  }
+  items.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Turns an LSP snippet into plain text by replacing placeholders with their
+/// default text and dropping tabstops.
+pub fn expand_snippet(snippet: &str) -> String {
+  let mut text = String::new();
+  let mut chars = snippet.chars().peekable();
+  while let Some(ch) = chars.next() {
+    match ch {
+      '\\' => text.extend(chars.next()),
+      '$' if chars.peek() == Some(&'{') => {
+        chars.next();
+        while matches!(chars.peek(), Some(ch) if ch.is_ascii_digit()) {
+          chars.next();
+        }
+        if chars.peek() == Some(&':') {
+          chars.next();
+        }
+        // nested placeholders are kept as they are
+        let mut depth = 0;
+        for ch in &mut chars {
+          match ch {
+            '{' => depth += 1,
+            '}' if depth == 0 => break,
+            '}' => depth -= 1,
+            _ => {}
+          }
+          text.push(ch);
+        }
+      }
+      '$' if matches!(chars.peek(), Some(ch) if ch.is_ascii_digit()) => {
+        while matches!(chars.peek(), Some(ch) if ch.is_ascii_digit()) {
+          chars.next();
+        }
+      }
+      _ => text.push(ch),
+    }
+  }
+  text
+}
+
+/// The word characters directly in front of the grapheme `column` in `line`.
+pub fn word_before(line: &str, column: usize) -> &str {
+  let end = line
+    .grapheme_indices(true)
+    .nth(column)
+    .map_or(line.len(), |(i, _)| i);
+  let start = line[..end]
+    .char_indices()
+    .rev()
+    .take_while(|(_, ch)| is_word_char(*ch))
+    .last()
+    .map_or(end, |(i, _)| i);
+  &line[start..end]
+}
+
+/// The word around the grapheme `column` in `line`.
+pub fn word_at(line: &str, column: usize) -> &str {
+  let end = line
+    .grapheme_indices(true)
+    .nth(column)
+    .map_or(line.len(), |(i, _)| i);
+  let start = end - word_before(line, column).len();
+  let after = line[end..]
+    .char_indices()
+    .find(|(_, ch)| !is_word_char(*ch))
+    .map_or(line.len() - end, |(i, _)| i);
+  &line[start..end + after]
+}
+
+/// The grapheme column of the start of the word before `column` in `line`,
+/// past anything between them, so moving by words stops where a
+/// completion would start.
+pub fn word_start(line: &str, column: usize) -> usize {
+  let graphemes = line.graphemes(true).take(column).collect::<Vec<_>>();
+  let gap = graphemes.iter().rev().take_while(|g| !is_word(g)).count();
+  let word = graphemes[..graphemes.len() - gap]
+    .iter()
+    .rev()
+    .take_while(|g| is_word(g))
+    .count();
+  graphemes.len() - gap - word
+}
+
+/// The grapheme column of the end of the word after `column` in `line`,
+/// past anything between them.
+pub fn word_end(line: &str, column: usize) -> usize {
+  let graphemes = line.graphemes(true).skip(column).collect::<Vec<_>>();
+  let gap = graphemes.iter().take_while(|g| !is_word(g)).count();
+  let word = graphemes[gap..].iter().take_while(|g| is_word(g)).count();
+  column + gap + word
+}
+
+/// Every word of the buffer as an item, used when no better source of
+/// completions is available.
+pub fn buffer_words(lines: &[String]) -> Vec<Item> {
+  let mut counts = HashMap::<&str, usize>::new();
+  for line in lines {
+    for word in line.split(|ch| !is_word_char(ch)) {
+      if word.chars().count() > 1 {
+        *counts.entry(word).or_default() += 1;
+      }
+    }
+  }
+
+  counts
+    .into_iter()
+    .map(|(word, count)| Item {
+      label: word.to_string(),
+      insert_text: word.to_string(),
+      is_snippet: false,
+      // more frequent words first
+      sort_text: Some(format!("{:08}{}", usize::MAX / 2 - count, word)),
+      documentation: Some(format!("Used {} times in this buffer", count)),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn item(label: &str, sort_text: &str) -> Item {
+    Item {
+      label: label.to_string(),
+      insert_text: label.to_string(),
+      is_snippet: false,
+      sort_text: Some(sort_text.to_string()),
+      documentation: None,
+    }
+  }
+
+  #[test]
+  fn sorting() {
+    let items = vec![item("foo_bar", "2"), item("fbar", "1"), item("xyz", "0")];
+    let labels = |items: Vec<Item>| {
+      items.into_iter().map(|item| item.label).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      labels(filter_and_sort(items.clone(), "foo", Sorting::Fuzzy)),
+      vec!["foo_bar"]
+    );
+    assert_eq!(
+      labels(filter_and_sort(items.clone(), "fb", Sorting::Fuzzy)),
+      vec!["fbar", "foo_bar"]
+    );
+    assert_eq!(
+      labels(filter_and_sort(items, "", Sorting::SortText)),
+      vec!["xyz", "fbar", "foo_bar"]
+    );
+  }
+
+  #[test]
+  fn snippets() {
+    assert_eq!(expand_snippet("fn ${1:name}($2) {$0}"), "fn name() {}");
+    assert_eq!(expand_snippet("${1:a ${2:b}}"), "a ${2:b}");
+    assert_eq!(expand_snippet("\\$1 costs $$1"), "$1 costs $");
+  }
+
+  #[test]
+  fn words() {
+    assert_eq!(word_before("let foo_b = 1", 9), "foo_b");
+    assert_eq!(word_before("let foo_b = 1", 10), "");
+    assert_eq!(word_before("äb", 2), "äb");
+    assert_eq!(word_at("let foo_b = 1", 5), "foo_b");
+    assert_eq!(word_at("let foo_b = 1", 4), "foo_b");
+    assert_eq!(word_at("let foo_b = 1", 3), "let");
+    assert_eq!(word_at("a + b", 2), "");
+    assert_eq!(word_start("let foo_b = 1", 9), 4);
+    assert_eq!(word_start("let foo_b = 1", 11), 4);
+    assert_eq!(word_start("  a", 2), 0);
+    assert_eq!(word_end("let foo_b = 1", 3), 9);
+    assert_eq!(word_end("äb + c", 0), 2);
+    assert_eq!(word_end("a += ", 1), 5);
+
+    let mut words = buffer_words(&["a foo foo bar".to_string()])
+      .into_iter()
+      .map(|item| item.label)
+      .collect::<Vec<_>>();
+    words.sort();
+    assert_eq!(words, vec!["bar", "foo"]);
+  }
+}
@@ -0,0 +1,696 @@
+use crate::completion::Sorting;
+use crate::viewport::Overscroll;
+use crate::wrap::WrapConfig;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether glyphs are blended with what's behind them in linear space, set
+/// once at startup from the text config.
+static GAMMA_CORRECT: AtomicBool = AtomicBool::new(true);
+
+/// Sets whether colors are blended in linear space, which takes an sRGB
+/// surface, or in sRGB space on a plain one.
+pub fn set_gamma_correct(gamma_correct: bool) {
+  GAMMA_CORRECT.store(gamma_correct, Ordering::Relaxed);
+}
+
+pub fn gamma_correct() -> bool {
+  GAMMA_CORRECT.load(Ordering::Relaxed)
+}
+
+/// A color in linear space, deserialized from an sRGB hex string such as
+/// `"#1e1e1e"`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Color(pub [f32; 3]);
+
+impl Color {
+  /// The channels as drawn, encoded to sRGB if blending isn't gamma
+  /// correct as the surface then takes them as they are.
+  pub fn rgb(self) -> [f32; 3] {
+    if gamma_correct() {
+      self.0
+    } else {
+      let [r, g, b] = self.0;
+      [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)]
+    }
+  }
+
+  pub fn rgba(self) -> [f32; 4] {
+    let [r, g, b] = self.rgb();
+    [r, g, b, 1.0]
+  }
+
+  /// `amount` of the way from this color to `other`.
+  pub fn mix(self, other: Color, amount: f32) -> Self {
+    let mut color = self.0;
+    for (channel, other) in color.iter_mut().zip(other.0.iter()) {
+      *channel += (other - *channel) * amount;
+    }
+    Color(color)
+  }
+
+  /// Converts 8 bit sRGB channels.
+  pub fn from_srgb(rgb: [u8; 3]) -> Self {
+    let mut color = [0.0; 3];
+    for (channel, value) in color.iter_mut().zip(rgb.iter()) {
+      *channel = srgb_to_linear(*value as f32 / 255.0);
+    }
+    Color(color)
+  }
+
+  fn from_hex(hex: &str) -> Result<Self, anyhow::Error> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    // the digits are sliced by byte, which could split other characters
+    if digits.len() != 6 || !digits.is_ascii() {
+      anyhow::bail!("invalid color '{}', expected #rrggbb", hex);
+    }
+
+    let mut rgb = [0; 3];
+    for (i, channel) in rgb.iter_mut().enumerate() {
+      *channel = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+        .map_err(|_| anyhow::anyhow!("invalid color '{}'", hex))?;
+    }
+    Ok(Color::from_srgb(rgb))
+  }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+  if value <= 0.04045 {
+    value / 12.92
+  } else {
+    ((value + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+  if value <= 0.0031308 {
+    value * 12.92
+  } else {
+    1.055 * value.powf(1.0 / 2.4) - 0.055
+  }
+}
+
+impl<'de> Deserialize<'de> for Color {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let hex = String::deserialize(deserializer)?;
+    Color::from_hex(&hex).map_err(serde::de::Error::custom)
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Theme {
+  pub background: Color,
+  pub text: Color,
+  pub cursor: Color,
+  pub gutter: Color,
+  pub gutter_text: Color,
+  /// Marks bookmarked lines in the gutter.
+  pub bookmark: Color,
+  /// The vertical lines at the ruler columns.
+  pub ruler: Color,
+  pub tab_bar: Color,
+  pub tab: Color,
+  pub tab_text: Color,
+  pub sidebar: Color,
+  pub sidebar_text: Color,
+  pub activity_bar: Color,
+  pub activity_bar_text: Color,
+  pub status_bar: Color,
+  pub status_bar_text: Color,
+  pub overlay: Color,
+  pub overlay_text: Color,
+  pub selection: Color,
+  pub error: Color,
+  pub warning: Color,
+  pub info: Color,
+  /// The words added in a diff, its added lines a fainter shade of it.
+  pub added: Color,
+  /// The words removed in a diff, its removed lines a fainter shade of it.
+  pub removed: Color,
+  pub keyword: Color,
+  pub string: Color,
+  pub comment: Color,
+  pub number: Color,
+  /// Nested bracket pairs take these colors in turn.
+  pub brackets: [Color; 3],
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    Self {
+      background: Color([0.01, 0.01, 0.01]),
+      text: Color([0.9, 0.9, 0.9]),
+      cursor: Color([0.7, 0.0, 0.0]),
+      gutter: Color([0.5, 0.05, 0.05]),
+      gutter_text: Color([0.9, 0.9, 0.9]),
+      bookmark: Color([0.3, 0.55, 0.9]),
+      ruler: Color([0.25, 0.25, 0.25]),
+      tab_bar: Color([0.12, 0.2, 0.89]),
+      tab: Color([0.04, 0.12, 0.81]),
+      tab_text: Color([0.9, 0.9, 0.9]),
+      sidebar: Color([0.04, 0.04, 0.04]),
+      sidebar_text: Color([0.0, 0.9, 0.0]),
+      activity_bar: Color([0.02, 0.02, 0.02]),
+      activity_bar_text: Color([0.4, 0.4, 0.4]),
+      status_bar: Color([0.12, 0.2, 0.89]),
+      status_bar_text: Color([0.9, 0.9, 0.9]),
+      overlay: Color([0.15, 0.15, 0.15]),
+      overlay_text: Color([0.9, 0.9, 0.9]),
+      selection: Color([0.04, 0.12, 0.81]),
+      error: Color([0.9, 0.4, 0.4]),
+      warning: Color([0.9, 0.7, 0.3]),
+      info: Color([0.3, 0.55, 0.9]),
+      added: Color([0.05, 0.3, 0.05]),
+      removed: Color([0.4, 0.03, 0.03]),
+      keyword: Color([0.8, 0.47, 0.87]),
+      string: Color([0.6, 0.76, 0.47]),
+      comment: Color([0.45, 0.5, 0.55]),
+      number: Color([0.82, 0.6, 0.4]),
+      brackets: [
+        Color([1.0, 0.68, 0.0]),
+        Color([0.7, 0.16, 0.67]),
+        Color([0.01, 0.35, 1.0]),
+      ],
+    }
+  }
+}
+
+/// How to start a language server and which files it handles.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LanguageServerConfig {
+  pub command: String,
+  #[serde(default)]
+  pub args: Vec<String>,
+  /// File extensions without the leading dot.
+  #[serde(default)]
+  pub extensions: Vec<String>,
+  /// Ids of the languages handled, besides the files with `extensions`.
+  #[serde(default)]
+  pub languages: Vec<String>,
+}
+
+/// Settings which apply to the files of a single language, keyed by its id
+/// like `rust`. The files are told apart as described in
+/// [`crate::languages`], the lists here add to those known already.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LanguageConfig {
+  /// File extensions without the leading dot.
+  pub extensions: Vec<String>,
+  /// Whole file names such as `Makefile`.
+  pub file_names: Vec<String>,
+  /// Programs run by the shebang, such as `python` for `#!/bin/python3`.
+  pub interpreters: Vec<String>,
+  /// Replaces the global `[wrap]` settings.
+  pub wrap: Option<WrapConfig>,
+  /// Replaces the global rulers.
+  pub rulers: Option<Vec<usize>>,
+  /// Replaces the global `tab-width`.
+  pub tab_width: Option<usize>,
+  /// Replaces the global `insert-spaces`.
+  pub insert_spaces: Option<bool>,
+  /// Replaces the global `format-on-save`.
+  pub format_on_save: Option<bool>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CompletionConfig {
+  /// Milliseconds to wait after typing before completions are shown.
+  pub trigger_delay: u64,
+  /// Whether the only completion starting with the typed word is inserted
+  /// without asking.
+  pub auto_insert_single: bool,
+  /// Whether enter accepts a completion, otherwise only tab does.
+  pub accept_on_enter: bool,
+  /// Whether snippets are expanded on accept, otherwise only their label is
+  /// inserted.
+  pub expand_snippets: bool,
+  /// Whether the documentation of the selected completion is shown next to
+  /// the list.
+  pub show_documentation: bool,
+  pub sort: Sorting,
+}
+
+impl Default for CompletionConfig {
+  fn default() -> Self {
+    Self {
+      trigger_delay: 100,
+      auto_insert_single: false,
+      accept_on_enter: true,
+      expand_snippets: true,
+      show_documentation: true,
+      sort: Sorting::Fuzzy,
+    }
+  }
+}
+
+/// When files with unsaved changes are saved without asking.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AutoSave {
+  Off,
+  /// Once they didn't change for the delay.
+  AfterDelay,
+  /// When the window loses focus.
+  OnFocusLoss,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct AutoSaveConfig {
+  pub mode: AutoSave,
+  /// Milliseconds without changes before saving `after-delay`.
+  pub delay: u64,
+}
+
+impl Default for AutoSaveConfig {
+  fn default() -> Self {
+    Self {
+      mode: AutoSave::Off,
+      delay: 1000,
+    }
+  }
+}
+
+/// How the glyphs of text are drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TextConfig {
+  /// Whether the edges of glyphs are blended in linear space, otherwise in
+  /// sRGB space like most native text. Applies after a restart.
+  pub gamma_correct: bool,
+  /// Whether glyphs of the code are placed on whole pixels, which keeps
+  /// small text from blurring.
+  pub hinting: bool,
+}
+
+impl Default for TextConfig {
+  fn default() -> Self {
+    Self {
+      gamma_correct: true,
+      hinting: true,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TabsConfig {
+  /// Whether files opened from the explorer share a single preview tab until
+  /// they are edited or pinned.
+  pub preview: bool,
+  /// Most tabs to keep open, the least recently used tabs which are neither
+  /// modified nor pinned are closed beyond it.
+  pub limit: Option<usize>,
+}
+
+impl Default for TabsConfig {
+  fn default() -> Self {
+    Self {
+      preview: true,
+      limit: None,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct WindowConfig {
+  /// Opacity of the backgrounds from 0 to 1, the text stays opaque. Below 1
+  /// the window is created transparent, so going below 1 applies after a
+  /// restart. It only shows through where the graphics backend and the
+  /// compositor support transparent windows.
+  pub opacity: f32,
+  /// Most frames drawn per second while running on battery, unlimited if
+  /// not set.
+  pub battery_max_fps: Option<u32>,
+}
+
+impl Default for WindowConfig {
+  fn default() -> Self {
+    Self {
+      opacity: 1.0,
+      battery_max_fps: None,
+    }
+  }
+}
+
+impl WindowConfig {
+  pub fn opacity(&self) -> f32 {
+    self.opacity.clamp(0.0, 1.0)
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct BracketsConfig {
+  /// Whether bracket pairs are colored by how deeply they are nested.
+  pub colorize: bool,
+  /// Whether a line is drawn along the lines between a pair of brackets.
+  pub guides: bool,
+}
+
+impl Default for BracketsConfig {
+  fn default() -> Self {
+    Self {
+      colorize: true,
+      guides: false,
+    }
+  }
+}
+
+/// A column of the gutter.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GutterComponent {
+  LineNumbers,
+  /// Marks the added and removed lines of a diff.
+  Changes,
+  /// Marks the lines the last task reported a problem on, and the line of
+  /// the cursor when there are code actions for it.
+  Diagnostics,
+  /// Chevrons of the regions which can be folded.
+  Folding,
+  Bookmarks,
+  Breakpoints,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GutterConfig {
+  /// The columns shown, from left to right. The gutter is as wide as they
+  /// are.
+  pub components: Vec<GutterComponent>,
+}
+
+impl Default for GutterConfig {
+  fn default() -> Self {
+    Self {
+      components: vec![
+        GutterComponent::Diagnostics,
+        GutterComponent::Breakpoints,
+        GutterComponent::Bookmarks,
+        GutterComponent::LineNumbers,
+        GutterComponent::Changes,
+        GutterComponent::Folding,
+      ],
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ZenConfig {
+  /// Most columns of code shown in zen mode, centered in the window.
+  pub max_width: usize,
+}
+
+impl Default for ZenConfig {
+  fn default() -> Self {
+    Self { max_width: 100 }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+  /// Name of an installed font, the bundled JetBrains Mono is used if unset.
+  pub font: Option<String>,
+  /// Font size in points.
+  pub font_size: f32,
+  /// Whether to draw the ligatures of the font, like `=>` as an arrow.
+  pub ligatures: bool,
+  pub text: TextConfig,
+  pub theme: Theme,
+  pub tab_width: usize,
+  /// Whether pressing tab inserts spaces instead of a tab character.
+  pub insert_spaces: bool,
+  /// Whether files are formatted by their language server when saved.
+  pub format_on_save: bool,
+  /// Maps key combinations like `"ctrl+shift+p"` to command ids.
+  pub keybindings: HashMap<String, String>,
+  /// Keyboard layout the keys of shortcuts are told by, a built in one like
+  /// `"azerty"` or one of `keyboard-layouts`. The keys the platform reports
+  /// are used if unset.
+  pub keyboard_layout: Option<String>,
+  /// Keyboard layouts by name, as the characters typed by the rows of keys
+  /// from the digits down.
+  pub keyboard_layouts: HashMap<String, Vec<String>>,
+  /// Language servers by name.
+  pub language_servers: HashMap<String, LanguageServerConfig>,
+  pub completion: CompletionConfig,
+  pub wrap: WrapConfig,
+  /// How far the code can be scrolled past its last line.
+  pub overscroll: Overscroll,
+  /// Whether the cursor is kept in the middle of the view while typing and
+  /// moving it.
+  pub typewriter: bool,
+  /// Columns to draw a vertical line at.
+  pub rulers: Vec<usize>,
+  pub brackets: BracketsConfig,
+  pub gutter: GutterConfig,
+  /// Per language settings by language name.
+  pub languages: HashMap<String, LanguageConfig>,
+  pub tabs: TabsConfig,
+  pub auto_save: AutoSaveConfig,
+  pub zen: ZenConfig,
+  pub window: WindowConfig,
+  /// Where to download grammars for file types without one, `{extension}`
+  /// is replaced by the file extension. Nothing is downloaded if unset.
+  pub grammar_url: Option<String>,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      font: None,
+      font_size: 10.0,
+      ligatures: true,
+      text: TextConfig::default(),
+      theme: Theme::default(),
+      tab_width: 4,
+      insert_spaces: true,
+      format_on_save: false,
+      keybindings: HashMap::new(),
+      keyboard_layout: None,
+      keyboard_layouts: HashMap::new(),
+      language_servers: HashMap::new(),
+      completion: CompletionConfig::default(),
+      wrap: WrapConfig::default(),
+      overscroll: Overscroll::default(),
+      typewriter: false,
+      rulers: vec![],
+      brackets: BracketsConfig::default(),
+      gutter: GutterConfig::default(),
+      languages: HashMap::new(),
+      tabs: TabsConfig::default(),
+      auto_save: AutoSaveConfig::default(),
+      zen: ZenConfig::default(),
+      window: WindowConfig::default(),
+      grammar_url: None,
+    }
+  }
+}
+
+impl Config {
+  pub fn path() -> PathBuf {
+    PathBuf::from(
+      shellexpand::tilde("~/.config/devcode/config.toml").to_string(),
+    )
+  }
+
+  /// Loads the config file, falling back to the defaults if there is none.
+  pub fn load() -> Result<Self, anyhow::Error> {
+    match std::fs::read_to_string(Self::path()) {
+      Ok(text) => Self::parse(&text),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+        Ok(Self::default())
+      }
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  /// The settings of the language with the id `language`, if there are
+  /// any.
+  fn language(&self, language: Option<&str>) -> Option<&LanguageConfig> {
+    self.languages.get(language?)
+  }
+
+  /// The wrap settings for files in `language`.
+  pub fn wrap_for(&self, language: Option<&str>) -> &WrapConfig {
+    self
+      .language(language)
+      .and_then(|language| language.wrap.as_ref())
+      .unwrap_or(&self.wrap)
+  }
+
+  /// The ruler columns for files in `language`.
+  pub fn rulers_for(&self, language: Option<&str>) -> &[usize] {
+    self
+      .language(language)
+      .and_then(|language| language.rulers.as_deref())
+      .unwrap_or(&self.rulers)
+  }
+
+  /// How many columns a level of indentation is in `language`.
+  pub fn tab_width_for(&self, language: Option<&str>) -> usize {
+    self
+      .language(language)
+      .and_then(|language| language.tab_width)
+      .unwrap_or(self.tab_width)
+  }
+
+  /// Whether tab inserts spaces in `language`.
+  pub fn insert_spaces_for(&self, language: Option<&str>) -> bool {
+    self
+      .language(language)
+      .and_then(|language| language.insert_spaces)
+      .unwrap_or(self.insert_spaces)
+  }
+
+  /// Whether files in `language` are formatted when saved.
+  pub fn format_on_save_for(&self, language: Option<&str>) -> bool {
+    self
+      .language(language)
+      .and_then(|language| language.format_on_save)
+      .unwrap_or(self.format_on_save)
+  }
+
+  fn parse(text: &str) -> Result<Self, anyhow::Error> {
+    Ok(toml::from_str(text)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_config() {
+    let config = Config::parse(
+      r##"
+      font-size = 12
+      ligatures = false
+      tab-width = 2
+      rulers = [80, 100]
+      overscroll = "none"
+      typewriter = true
+      keyboard-layout = "azerty"
+
+      [text]
+      hinting = false
+
+      [theme]
+      text = "#ffffff"
+      background = "#000000"
+      brackets = ["#ffffff", "#000000", "#ffffff"]
+
+      [keybindings]
+      "ctrl+p" = "show-command-palette"
+
+      [language-servers.rust-analyzer]
+      command = "rust-analyzer"
+      extensions = ["rs"]
+
+      [completion]
+      accept-on-enter = false
+      sort = "sort-text"
+
+      [wrap]
+      soft-wrap = true
+
+      [languages.markdown]
+      extensions = ["md"]
+      wrap = { indent = "hang", hang = 4 }
+      rulers = []
+
+      [languages.go]
+      tab-width = 8
+      insert-spaces = false
+      format-on-save = true
+
+      [tabs]
+      limit = 8
+
+      [auto-save]
+      mode = "after-delay"
+
+      [zen]
+      max-width = 80
+
+      [brackets]
+      guides = true
+
+      [gutter]
+      components = ["line-numbers", "bookmarks"]
+
+      [window]
+      opacity = 1.5
+      "##,
+    )
+    .unwrap();
+
+    assert_eq!(config.font_size, 12.0);
+    assert!(!config.ligatures);
+    assert!(!config.text.hinting);
+    assert!(config.text.gamma_correct);
+    assert_eq!(config.tab_width, 2);
+    assert_eq!(config.overscroll, Overscroll::None);
+    assert!(config.typewriter);
+    assert_eq!(config.keyboard_layout.as_deref(), Some("azerty"));
+    assert!(config.insert_spaces);
+    assert_eq!(config.theme.text, Color([1.0, 1.0, 1.0]));
+    assert_eq!(config.theme.background, Color([0.0, 0.0, 0.0]));
+    assert_eq!(config.theme.cursor, Theme::default().cursor);
+    assert_eq!(config.theme.brackets[1], Color([0.0, 0.0, 0.0]));
+    assert!(config.brackets.colorize && config.brackets.guides);
+    assert_eq!(
+      config.gutter.components,
+      vec![GutterComponent::LineNumbers, GutterComponent::Bookmarks]
+    );
+    assert_eq!(config.keybindings["ctrl+p"], "show-command-palette");
+    assert_eq!(
+      config.language_servers["rust-analyzer"].extensions,
+      vec!["rs"]
+    );
+    assert!(!config.completion.accept_on_enter);
+    assert_eq!(config.completion.sort, Sorting::SortText);
+    assert_eq!(config.completion.trigger_delay, 100);
+
+    assert!(config.wrap_for(None).soft_wrap);
+    assert!(config.wrap_for(Some("rust")).soft_wrap);
+    let markdown = config.wrap_for(Some("markdown"));
+    assert!(!markdown.soft_wrap);
+    assert_eq!(markdown.indent, crate::wrap::WrapIndent::Hang);
+    assert_eq!(markdown.hang, 4);
+    assert_eq!(config.rulers_for(None), &[80, 100]);
+    assert!(config.rulers_for(Some("markdown")).is_empty());
+    assert_eq!(config.tab_width_for(Some("go")), 8);
+    assert_eq!(config.tab_width_for(Some("markdown")), 2);
+    assert!(!config.insert_spaces_for(Some("go")));
+    assert!(config.format_on_save_for(Some("go")));
+    assert!(!config.format_on_save_for(None));
+
+    assert!(config.tabs.preview);
+    assert_eq!(config.tabs.limit, Some(8));
+    assert_eq!(config.auto_save.mode, AutoSave::AfterDelay);
+    assert_eq!(config.auto_save.delay, 1000);
+    assert_eq!(config.zen.max_width, 80);
+    assert_eq!(config.window.opacity(), 1.0);
+  }
+
+  #[test]
+  fn invalid_color() {
+    assert!(Config::parse("[theme]\ntext = \"#fff\"").is_err());
+    assert!(Config::parse("[theme]\ntext = \"#gggggg\"").is_err());
+    assert!(Config::parse("[theme]\ntext = \"#a€bc\"").is_err());
+  }
+}
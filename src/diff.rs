@@ -0,0 +1,319 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A line of a line based diff.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Line<'a> {
+  Same(&'a str),
+  Removed(&'a str),
+  Added(&'a str),
+}
+
+/// Where an element of a diff comes from.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Step {
+  Both,
+  Left,
+  Right,
+}
+
+/// The steps turning `left` into `right`, from the longest common
+/// subsequence of their elements.
+fn steps<T: PartialEq>(left: &[T], right: &[T]) -> Vec<Step> {
+  // lengths[i][j] is the length of the LCS of left[i..] and right[j..]
+  let mut lengths = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+  for i in (0..left.len()).rev() {
+    for j in (0..right.len()).rev() {
+      lengths[i][j] = if left[i] == right[j] {
+        lengths[i + 1][j + 1] + 1
+      } else {
+        lengths[i + 1][j].max(lengths[i][j + 1])
+      };
+    }
+  }
+
+  let mut steps = vec![];
+  let (mut i, mut j) = (0, 0);
+  while i < left.len() && j < right.len() {
+    if left[i] == right[j] {
+      steps.push(Step::Both);
+      i += 1;
+      j += 1;
+    } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+      steps.push(Step::Left);
+      i += 1;
+    } else {
+      steps.push(Step::Right);
+      j += 1;
+    }
+  }
+  steps.extend((i..left.len()).map(|_| Step::Left));
+  steps.extend((j..right.len()).map(|_| Step::Right));
+  steps
+}
+
+/// Computes the changes needed to turn `left` into `right` from the longest
+/// common subsequence of their lines.
+pub fn lines<'a>(left: &'a str, right: &'a str) -> Vec<Line<'a>> {
+  let left = left.lines().collect::<Vec<_>>();
+  let right = right.lines().collect::<Vec<_>>();
+  let (mut i, mut j) = (0, 0);
+  steps(&left, &right)
+    .into_iter()
+    .map(|step| match step {
+      Step::Both => {
+        i += 1;
+        j += 1;
+        Line::Same(left[i - 1])
+      }
+      Step::Left => {
+        i += 1;
+        Line::Removed(left[i - 1])
+      }
+      Step::Right => {
+        j += 1;
+        Line::Added(right[j - 1])
+      }
+    })
+    .collect()
+}
+
+/// Splits `line` into words, runs of whitespace and single other
+/// graphemes, with the graphemes they span.
+fn tokens(line: &str) -> Vec<(&str, Range<usize>)> {
+  #[derive(PartialEq)]
+  enum Class {
+    Word,
+    Space,
+    Other,
+  }
+  let class = |grapheme: &str| {
+    let ch = grapheme.chars().next().unwrap_or(' ');
+    if ch.is_alphanumeric() || ch == '_' {
+      Class::Word
+    } else if ch.is_whitespace() {
+      Class::Space
+    } else {
+      Class::Other
+    }
+  };
+
+  let mut tokens: Vec<(&str, Range<usize>)> = vec![];
+  let mut previous = None;
+  for (column, (offset, grapheme)) in line.grapheme_indices(true).enumerate() {
+    let current = class(grapheme);
+    match tokens.last_mut() {
+      Some((text, columns))
+        if previous.as_ref() == Some(&current) && current != Class::Other =>
+      {
+        *text = &line[offset - text.len()..offset + grapheme.len()];
+        columns.end = column + 1;
+      }
+      _ => tokens.push((grapheme, column..column + 1)),
+    }
+    previous = Some(current);
+  }
+  tokens
+}
+
+/// The graphemes of `left` and of `right` which differ, compared word by
+/// word. Nothing is highlighted when the lines only share whitespace and
+/// punctuation, as they were rewritten rather than changed.
+pub fn words(
+  left: &str,
+  right: &str,
+) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+  let left = tokens(left);
+  let right = tokens(right);
+  let texts = |tokens: &[(&str, Range<usize>)]| {
+    tokens
+      .iter()
+      .map(|(text, _)| text.to_string())
+      .collect::<Vec<_>>()
+  };
+  let steps = steps(&texts(&left), &texts(&right));
+
+  let (mut removed, mut added) = (vec![], vec![]);
+  let mut shared = false;
+  let (mut i, mut j) = (0, 0);
+  for step in steps {
+    let (ranges, token): (&mut Vec<Range<usize>>, _) = match step {
+      Step::Both => {
+        let (text, _) = &left[i];
+        shared |= text.chars().any(|ch| ch.is_alphanumeric());
+        i += 1;
+        j += 1;
+        continue;
+      }
+      Step::Left => {
+        i += 1;
+        (&mut removed, &left[i - 1])
+      }
+      Step::Right => {
+        j += 1;
+        (&mut added, &right[j - 1])
+      }
+    };
+    let (_, columns) = token;
+    match ranges.last_mut() {
+      Some(last) if last.end == columns.start => last.end = columns.end,
+      _ => ranges.push(columns.clone()),
+    }
+  }
+  if shared {
+    (removed, added)
+  } else {
+    (vec![], vec![])
+  }
+}
+
+/// A removed or added line of a unified diff.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Changed {
+  pub line: usize,
+  pub added: bool,
+  /// The graphemes which changed, if the line replaced another one.
+  pub words: Vec<Range<usize>>,
+}
+
+/// The removed and added lines of a unified diff like [`unified`] renders.
+/// Each run of removed lines is paired with the added lines right after
+/// it, line by line, to find the words which changed.
+pub fn changed_lines(lines: &[String]) -> Vec<Changed> {
+  let mut changed = vec![];
+  let mut line = 0;
+  while line < lines.len() {
+    let run = |start: usize, prefix: char| {
+      start
+        + lines[start..]
+          .iter()
+          .take_while(|line| line.starts_with(prefix))
+          .count()
+    };
+    let removed_end = run(line, '-');
+    let added_end = run(removed_end, '+');
+    if added_end == line {
+      line += 1;
+      continue;
+    }
+    let removed = (line..removed_end).collect::<Vec<_>>();
+    let added = (removed_end..added_end).collect::<Vec<_>>();
+    let mut words = vec![vec![]; added_end - line];
+    for (&left, &right) in removed.iter().zip(added.iter()) {
+      let (from, to) = words_of(&lines[left][1..], &lines[right][1..]);
+      words[left - line] = from;
+      words[right - line] = to;
+    }
+    for (i, words) in words.into_iter().enumerate() {
+      changed.push(Changed {
+        line: line + i,
+        added: line + i >= removed_end,
+        words,
+      });
+    }
+    line = added_end;
+  }
+  changed
+}
+
+/// [`words`] shifted past the `-` or `+` in front of the lines.
+fn words_of(left: &str, right: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+  let shift = |ranges: Vec<Range<usize>>| {
+    ranges
+      .into_iter()
+      .map(|range| range.start + 1..range.end + 1)
+      .collect()
+  };
+  let (left, right) = words(left, right);
+  (shift(left), shift(right))
+}
+
+/// Renders the diff with a `-`, `+` or space in front of every line.
+pub fn unified(left: &str, right: &str) -> String {
+  let mut text = String::new();
+  for line in lines(left, right) {
+    let (prefix, line) = match line {
+      Line::Same(line) => (' ', line),
+      Line::Removed(line) => ('-', line),
+      Line::Added(line) => ('+', line),
+    };
+    text.push(prefix);
+    text.push_str(line);
+    text.push('\n');
+  }
+  text
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn line_diff() {
+    assert_eq!(
+      lines("a\nb\nc\n", "a\nc\nd\n"),
+      vec![
+        Line::Same("a"),
+        Line::Removed("b"),
+        Line::Same("c"),
+        Line::Added("d"),
+      ]
+    );
+    assert_eq!(unified("a\nb", "b"), "-a\n b\n");
+    assert!(lines("", "").is_empty());
+  }
+
+  #[test]
+  #[allow(clippy::single_range_in_vec_init)]
+  fn word_diff() {
+    assert_eq!(
+      words("let x = foo(1);", "let y = foo(1, 2);"),
+      (vec![4..5], vec![4..5, 13..16])
+    );
+    // graphemes are counted, not bytes
+    assert_eq!(words("é == a", "é == b"), (vec![5..6], vec![5..6]));
+    // rewritten lines aren't broken into words
+    assert_eq!(words("abc", "xyz;"), (vec![], vec![]));
+    assert_eq!(words("same", "same"), (vec![], vec![]));
+  }
+
+  #[test]
+  #[allow(clippy::single_range_in_vec_init)]
+  fn pairs_changed_lines() {
+    let text = unified("a\nold one\nb\nc\n", "a\nnew one\nb\nd\ne\n")
+      .lines()
+      .map(String::from)
+      .collect::<Vec<_>>();
+    let changed = changed_lines(&text);
+    assert_eq!(
+      changed,
+      vec![
+        Changed {
+          line: 1,
+          added: false,
+          words: vec![1..4],
+        },
+        Changed {
+          line: 2,
+          added: true,
+          words: vec![1..4],
+        },
+        Changed {
+          line: 4,
+          added: false,
+          words: vec![],
+        },
+        Changed {
+          line: 5,
+          added: true,
+          words: vec![],
+        },
+        Changed {
+          line: 6,
+          added: true,
+          words: vec![],
+        },
+      ]
+    );
+  }
+}
@@ -0,0 +1,149 @@
+//! The text of open files, shared by the views showing them in any window so
+//! an edit in one shows up in all of them.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::time::SystemTime;
+
+pub fn split_lines(text: &str) -> Vec<String> {
+  let mut lines = text.lines().map(|s| s.to_string()).collect::<Vec<String>>();
+  if text.ends_with('\n') {
+    lines.push(String::from(""));
+  }
+  lines
+}
+
+pub fn modified(path: &Path) -> Option<SystemTime> {
+  std::fs::metadata(path)
+    .and_then(|metadata| metadata.modified())
+    .ok()
+}
+
+pub struct Document {
+  pub text: Rc<RefCell<Vec<String>>>,
+  /// Incremented whenever the text changes.
+  version: Cell<usize>,
+  /// The version last read from or written to disk.
+  saved_version: Cell<usize>,
+  /// When the file was last changed by the editor, to notice changes made by
+  /// other programs.
+  disk_modified: Cell<Option<SystemTime>>,
+}
+
+impl Document {
+  pub fn new(text: &str) -> Self {
+    Self {
+      text: Rc::new(RefCell::new(split_lines(text))),
+      version: Cell::new(0),
+      saved_version: Cell::new(0),
+      disk_modified: Cell::new(None),
+    }
+  }
+
+  pub fn version(&self) -> usize {
+    self.version.get()
+  }
+
+  /// Counts a change to the text, returns the new version.
+  pub fn changed(&self) -> usize {
+    self.version.set(self.version.get() + 1);
+    self.version.get()
+  }
+
+  /// Whether the text changed since it was last saved.
+  pub fn is_modified(&self) -> bool {
+    self.version.get() != self.saved_version.get()
+  }
+
+  /// Marks the text as in sync with the file at `path`.
+  pub fn saved(&self, path: &Path) {
+    self.saved_version.set(self.version.get());
+    self.disk_modified.set(modified(path));
+  }
+
+  pub fn disk_modified(&self) -> Option<SystemTime> {
+    self.disk_modified.get()
+  }
+
+  pub fn set_disk_modified(&self, time: Option<SystemTime>) {
+    self.disk_modified.set(time);
+  }
+}
+
+/// The documents of the files open in any window, by canonical path. Cloning
+/// it shares the documents.
+#[derive(Clone, Default)]
+pub struct Documents {
+  open: Rc<RefCell<HashMap<PathBuf, Weak<Document>>>>,
+}
+
+impl Documents {
+  /// The document of the file at `path`, read from disk unless it's open
+  /// already.
+  pub fn open(&self, path: &Path) -> Result<Rc<Document>, anyhow::Error> {
+    let key = path.canonicalize()?;
+    let mut open = self.open.borrow_mut();
+    if let Some(document) = open.get(&key).and_then(Weak::upgrade) {
+      return Ok(document);
+    }
+    // closed documents are only dropped here
+    open.retain(|_, document| document.strong_count() > 0);
+
+    let document = Document::new(&std::fs::read_to_string(path)?);
+    document.saved(path);
+    let document = Rc::new(document);
+    open.insert(key, Rc::downgrade(&document));
+    Ok(document)
+  }
+
+  /// Shares `document` as the one of the file at `path`, such as a new
+  /// buffer saved there for the first time.
+  pub fn add(
+    &self,
+    path: &Path,
+    document: &Rc<Document>,
+  ) -> Result<(), anyhow::Error> {
+    let key = path.canonicalize()?;
+    self.open.borrow_mut().insert(key, Rc::downgrade(document));
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn open_files_are_shared() {
+    let path = std::env::temp_dir()
+      .join(format!("devcode-documents-{}.txt", std::process::id()));
+    std::fs::write(&path, "a\nb\n").unwrap();
+
+    let documents = Documents::default();
+    let first = documents.open(&path).unwrap();
+    assert_eq!(*first.text.borrow(), vec!["a", "b", ""]);
+    let second = documents.clone().open(&path).unwrap();
+    assert!(Rc::ptr_eq(&first, &second));
+
+    second.text.borrow_mut()[0] = String::from("c");
+    second.changed();
+    assert!(first.is_modified());
+    first.saved(&path);
+    assert!(!second.is_modified());
+
+    let new_path = path.with_extension("new");
+    std::fs::write(&new_path, "").unwrap();
+    let unsaved = Rc::new(Document::new("e"));
+    documents.add(&new_path, &unsaved).unwrap();
+    assert!(Rc::ptr_eq(&documents.open(&new_path).unwrap(), &unsaved));
+    std::fs::remove_file(new_path).unwrap();
+
+    // closed documents are read again
+    drop((first, second));
+    std::fs::write(&path, "d").unwrap();
+    assert_eq!(*documents.open(&path).unwrap().text.borrow(), vec!["d"]);
+    std::fs::remove_file(path).unwrap();
+  }
+}
@@ -0,0 +1,930 @@
+//! The state of an editor and the messages changing it. Views draw it and
+//! turn input into messages, so editing can be recorded, replayed or driven
+//! from elsewhere, and tested without a window.
+
+use crate::completion;
+use crate::structure::{self, Position, Span};
+use std::cell::RefCell;
+use std::rc::Rc;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+  Up,
+  Down,
+  Left,
+  Right,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+  /// Moves the cursors a grapheme or a line, dropping the selection.
+  Move(Direction),
+  /// Moves the cursor, clamped to the text, keeping the selection. Other
+  /// cursors are dropped.
+  MoveTo(Position),
+  /// Selects the text from the anchor to the cursor.
+  Select {
+    anchor: Position,
+    cursor: Position,
+  },
+  ClearSelection,
+  /// Moves the main cursor a grapheme or a line, extending the selection.
+  /// Other cursors are dropped.
+  Extend(Direction),
+  /// Moves the main cursor to the start of the word before it or the end
+  /// of the word after it, extending the selection if `select`. Other
+  /// cursors are dropped.
+  MoveWord {
+    forward: bool,
+    select: bool,
+  },
+  SelectAll,
+  /// Grows the selection to the structure around it.
+  ExpandSelection,
+  /// Goes back to the selection last grown from.
+  ShrinkSelection,
+  /// Adds a cursor on the line above or below the others.
+  AddCursor(Direction),
+  /// Drops the cursors added to the main one.
+  ClearCursors,
+  /// Types a character at each cursor, in place of the selection.
+  Insert(char),
+  /// Splits the line at each cursor, in place of the selection.
+  Newline,
+  /// Inserts text at each cursor, in place of the selection. A large paste
+  /// comes in chunks, each one `continuing` the last as a single step to
+  /// undo.
+  Paste {
+    text: String,
+    continuing: bool,
+  },
+  /// Removes the selection, or the grapheme before each cursor.
+  Backspace,
+  Undo,
+  Redo,
+  /// Switches between replacing and inserting what is typed.
+  SetOverwrite(bool),
+  /// Replaces all of the text, keeping the cursor in place where possible.
+  Replace(Vec<String>),
+  /// Catches up with a change to the text made elsewhere, which can't be
+  /// undone here.
+  TextChanged,
+}
+
+/// What a message changed, for the views to catch up with.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Change {
+  Nothing,
+  /// The cursors, the selection or how they are drawn.
+  Cursor,
+  Text,
+}
+
+fn length(line: &str) -> usize {
+  line.graphemes(true).count()
+}
+
+/// Replaces `span` of `text` with `inserted`, returning where it ends.
+fn replace(text: &mut Vec<String>, span: Span, inserted: &str) -> Position {
+  let start = structure::byte_offset(&text[span.start.0], span.start.1);
+  let end = structure::byte_offset(&text[span.end.0], span.end.1);
+  let rest = text[span.end.0][end..].to_string();
+  let mut lines = inserted.split('\n').map(String::from).collect::<Vec<_>>();
+  lines[0].insert_str(0, &text[span.start.0][..start]);
+  let last = lines.len() - 1;
+  let end = (span.start.0 + last, length(&lines[last]));
+  lines[last] += &rest;
+  text.splice(span.start.0..=span.end.0, lines);
+  end
+}
+
+/// Where `position`, at or after `span`, ends up once the text of `span` was
+/// replaced by text ending at `end`.
+fn shift(position: Position, span: Span, end: Position) -> Position {
+  if position <= span.end {
+    end
+  } else if position.0 == span.end.0 {
+    (end.0, end.1 + position.1 - span.end.1)
+  } else {
+    (position.0 + end.0 - span.end.0, position.1)
+  }
+}
+
+/// Lines `first..first + len` replacing `old`, and where the cursors were
+/// before and after.
+#[derive(Clone, Debug)]
+struct Edit {
+  first: usize,
+  old: Vec<String>,
+  len: usize,
+  before: Vec<Position>,
+  after: Vec<Position>,
+}
+
+#[derive(Default)]
+struct History {
+  undo: Vec<Edit>,
+  redo: Vec<Edit>,
+  /// Whether a word is being typed, which the next character joins.
+  typing: bool,
+}
+
+impl History {
+  fn record(&mut self, edit: Edit, joins: bool) {
+    self.redo.clear();
+    if let Some(last) = self.undo.last_mut().filter(|_| joins) {
+      // the lines edited are among the ones the last edit left
+      if last.first <= edit.first
+        && edit.first + edit.old.len() <= last.first + last.len
+      {
+        last.len = last.len + edit.len - edit.old.len();
+        last.after = edit.after;
+        return;
+      }
+    }
+    self.undo.push(edit);
+  }
+}
+
+pub struct EditorState {
+  /// The lines of the text, shared with the document and other views.
+  pub text: Rc<RefCell<Vec<String>>>,
+  /// Row and grapheme column of the main cursor.
+  cursor: Position,
+  /// More cursors editing along with the main one, in order.
+  cursors: Vec<Position>,
+  /// Where the selection started, it ends at the main cursor.
+  anchor: Option<Position>,
+  /// The selections grown from, the last one first to shrink back to.
+  expansions: Vec<Span>,
+  /// Whether typing replaces the grapheme under the cursor.
+  overwrite: bool,
+  history: History,
+}
+
+impl EditorState {
+  pub fn new(text: Rc<RefCell<Vec<String>>>) -> Self {
+    Self {
+      text,
+      cursor: (0, 0),
+      cursors: vec![],
+      anchor: None,
+      expansions: vec![],
+      overwrite: false,
+      history: History::default(),
+    }
+  }
+
+  /// Zero based row and column of the main cursor.
+  pub fn cursor(&self) -> Position {
+    self.cursor
+  }
+
+  /// The cursors besides the main one.
+  pub fn cursors(&self) -> &[Position] {
+    &self.cursors
+  }
+
+  pub fn overwrite(&self) -> bool {
+    self.overwrite
+  }
+
+  /// The selected text, `None` if nothing is selected.
+  pub fn selection(&self) -> Option<Span> {
+    let span = Span::new(self.anchor?, self.cursor);
+    if span.is_empty() {
+      None
+    } else {
+      Some(span)
+    }
+  }
+
+  /// Applies `message`, returning what it changed.
+  pub fn update(&mut self, message: Message) -> Change {
+    let typing = std::mem::take(&mut self.history.typing);
+    match message {
+      Message::Move(direction) => {
+        self.clear_selection();
+        let carets = self
+          .carets()
+          .into_iter()
+          .map(|caret| self.moved(caret, direction))
+          .collect();
+        self.set_carets(carets);
+        Change::Cursor
+      }
+      Message::MoveTo(position) => {
+        self.cursors.clear();
+        self.cursor = self.clamp(position);
+        Change::Cursor
+      }
+      Message::Select { anchor, cursor } => {
+        self.cursors.clear();
+        self.anchor = Some(self.clamp(anchor));
+        self.cursor = self.clamp(cursor);
+        Change::Cursor
+      }
+      Message::ClearSelection => {
+        self.clear_selection();
+        Change::Cursor
+      }
+      Message::Extend(direction) => {
+        let position = self.moved(self.cursor, direction);
+        self.move_cursor(position, true)
+      }
+      Message::MoveWord { forward, select } => {
+        let position = self.word_boundary(forward);
+        self.move_cursor(position, select)
+      }
+      Message::SelectAll => {
+        let text = self.text.borrow();
+        let last = text.len().saturating_sub(1);
+        let end = (last, text.get(last).map_or(0, |line| length(line)));
+        drop(text);
+        self.cursors.clear();
+        self.anchor = Some((0, 0));
+        self.cursor = end;
+        Change::Cursor
+      }
+      Message::ExpandSelection => {
+        let current = self
+          .selection()
+          .unwrap_or_else(|| Span::new(self.cursor, self.cursor));
+        match structure::expand(&self.text.borrow(), current) {
+          Some(expanded) => {
+            self.cursors.clear();
+            self.expansions.push(current);
+            self.anchor = Some(expanded.start);
+            self.cursor = expanded.end;
+            Change::Cursor
+          }
+          None => Change::Nothing,
+        }
+      }
+      Message::ShrinkSelection => match self.expansions.pop() {
+        Some(previous) => {
+          self.anchor = Some(previous.start);
+          self.cursor = previous.end;
+          Change::Cursor
+        }
+        None => Change::Nothing,
+      },
+      Message::AddCursor(direction) => self.add_cursor(direction),
+      Message::ClearCursors if self.cursors.is_empty() => Change::Nothing,
+      Message::ClearCursors => {
+        self.cursors.clear();
+        Change::Cursor
+      }
+      Message::Insert(ch) => {
+        let overwrite = self.overwrite && !ch.is_control();
+        let text = self.text.borrow();
+        let spans = self.spans(|(row, column)| {
+          let end = if overwrite {
+            (column + 1).min(length(&text[row]))
+          } else {
+            column
+          };
+          Span::new((row, column), (row, end))
+        });
+        drop(text);
+        self.edit(spans, &ch.to_string(), typing);
+        // whitespace ends a word along with the step undoing it
+        self.history.typing = !ch.is_whitespace();
+        Change::Text
+      }
+      Message::Newline => {
+        let spans = self.spans(|caret| Span::new(caret, caret));
+        self.edit(spans, "\n", false);
+        Change::Text
+      }
+      Message::Paste { text, continuing } => {
+        let spans = self.spans(|caret| Span::new(caret, caret));
+        self.edit(spans, &text, continuing);
+        Change::Text
+      }
+      Message::Backspace => {
+        let text = self.text.borrow();
+        let spans = self.spans(|caret| match caret {
+          (0, 0) => Span::new(caret, caret),
+          (row, 0) => Span::new((row - 1, length(&text[row - 1])), caret),
+          (row, column) => Span::new((row, column - 1), caret),
+        });
+        drop(text);
+        if spans.iter().all(Span::is_empty) {
+          return Change::Nothing;
+        }
+        self.edit(spans, "", false);
+        Change::Text
+      }
+      Message::Undo => match self.history.undo.pop() {
+        Some(edit) => {
+          let redo = self.restore(edit);
+          self.history.redo.push(redo);
+          Change::Text
+        }
+        None => Change::Nothing,
+      },
+      Message::Redo => match self.history.redo.pop() {
+        Some(edit) => {
+          let undo = self.restore(edit);
+          self.history.undo.push(undo);
+          Change::Text
+        }
+        None => Change::Nothing,
+      },
+      Message::SetOverwrite(overwrite) => {
+        self.overwrite = overwrite;
+        Change::Cursor
+      }
+      Message::Replace(lines) => {
+        let before = self.carets();
+        let len = lines.len();
+        let old = std::mem::replace(&mut *self.text.borrow_mut(), lines);
+        self.clear_selection();
+        self
+          .set_carets(before.iter().map(|caret| self.clamp(*caret)).collect());
+        let edit = Edit {
+          first: 0,
+          old,
+          len,
+          before,
+          after: self.carets(),
+        };
+        self.history.record(edit, false);
+        Change::Text
+      }
+      Message::TextChanged => {
+        self.clear_selection();
+        self.history = History::default();
+        let carets = self.carets();
+        self
+          .set_carets(carets.iter().map(|caret| self.clamp(*caret)).collect());
+        Change::Text
+      }
+    }
+  }
+
+  fn clear_selection(&mut self) {
+    self.anchor = None;
+    self.expansions.clear();
+  }
+
+  /// The text of the selection, `None` if nothing is selected.
+  pub fn selected_text(&self) -> Option<String> {
+    let span = self.selection()?;
+    let text = self.text.borrow();
+    let start = structure::byte_offset(&text[span.start.0], span.start.1);
+    let end = structure::byte_offset(&text[span.end.0], span.end.1);
+    if span.start.0 == span.end.0 {
+      return Some(text[span.start.0][start..end].to_string());
+    }
+    let mut selected = text[span.start.0][start..].to_string();
+    for line in &text[span.start.0 + 1..span.end.0] {
+      selected.push('\n');
+      selected += line;
+    }
+    selected.push('\n');
+    selected += &text[span.end.0][..end];
+    Some(selected)
+  }
+
+  /// Moves the main cursor to `position`, dropping the other ones, and
+  /// extends the selection to it or drops it.
+  fn move_cursor(&mut self, position: Position, select: bool) -> Change {
+    self.cursors.clear();
+    if select {
+      self.anchor.get_or_insert(self.cursor);
+      self.expansions.clear();
+    } else {
+      self.clear_selection();
+    }
+    self.cursor = position;
+    Change::Cursor
+  }
+
+  /// Where moving the main cursor by a word takes it, to the next line at
+  /// the end of one.
+  fn word_boundary(&self, forward: bool) -> Position {
+    let text = self.text.borrow();
+    let (row, column) = self.cursor;
+    let line = &text[row];
+    match (forward, column) {
+      (true, column) if column >= length(line) && row + 1 < text.len() => {
+        (row + 1, 0)
+      }
+      (true, column) => (row, completion::word_end(line, column)),
+      (false, 0) if row > 0 => (row - 1, length(&text[row - 1])),
+      (false, column) => (row, completion::word_start(line, column)),
+    }
+  }
+
+  /// The main cursor followed by the others.
+  fn carets(&self) -> Vec<Position> {
+    std::iter::once(self.cursor)
+      .chain(self.cursors.iter().copied())
+      .collect()
+  }
+
+  /// Moves the cursors to `carets`, the first being the main one, merging
+  /// the ones which meet.
+  fn set_carets(&mut self, mut carets: Vec<Position>) {
+    self.cursor = carets.remove(0);
+    carets.sort_unstable();
+    carets.dedup();
+    carets.retain(|caret| *caret != self.cursor);
+    self.cursors = carets;
+  }
+
+  /// `position` moved into the text.
+  fn clamp(&self, (row, column): Position) -> Position {
+    let text = self.text.borrow();
+    if text.is_empty() {
+      return (0, 0);
+    }
+    let row = row.min(text.len() - 1);
+    (row, column.min(length(&text[row])))
+  }
+
+  fn moved(&self, (row, column): Position, direction: Direction) -> Position {
+    let text = self.text.borrow();
+    let last = text.len().saturating_sub(1);
+    match direction {
+      Direction::Up if row == 0 => (0, 0),
+      Direction::Up => (row - 1, column.min(length(&text[row - 1]))),
+      Direction::Down if row == last => (row, length(&text[row])),
+      Direction::Down => (row + 1, column.min(length(&text[row + 1]))),
+      Direction::Left if column > 0 => (row, column - 1),
+      Direction::Left if row > 0 => (row - 1, length(&text[row - 1])),
+      Direction::Left => (row, column),
+      Direction::Right if column < length(&text[row]) => (row, column + 1),
+      Direction::Right if row < last => (row + 1, 0),
+      Direction::Right => (row, column),
+    }
+  }
+
+  fn add_cursor(&mut self, direction: Direction) -> Change {
+    let carets = self.carets();
+    let (row, column) = match direction {
+      Direction::Up => carets.iter().min(),
+      Direction::Down => carets.iter().max(),
+      Direction::Left | Direction::Right => return Change::Nothing,
+    }
+    .copied()
+    .unwrap_or(self.cursor);
+    let row = match direction {
+      Direction::Up if row > 0 => row - 1,
+      Direction::Down if row + 1 < self.text.borrow().len() => row + 1,
+      _ => return Change::Nothing,
+    };
+    self.clear_selection();
+    let caret = self.clamp((row, column));
+    self.set_carets(carets.into_iter().chain(Some(caret)).collect());
+    Change::Cursor
+  }
+
+  /// The text each cursor replaces, the selection if there is one.
+  fn spans(&self, span: impl Fn(Position) -> Span) -> Vec<Span> {
+    match self.selection() {
+      Some(selection) => vec![selection],
+      None => self.carets().into_iter().map(span).collect(),
+    }
+  }
+
+  /// Replaces the text of `spans`, one per cursor, with `inserted`, leaving
+  /// each cursor after it. Typing `joins` the last step to undo.
+  fn edit(&mut self, spans: Vec<Span>, inserted: &str, joins: bool) {
+    let before = self.carets();
+    let first = spans.iter().map(|span| span.start.0).min().unwrap_or(0);
+    let last = spans.iter().map(|span| span.end.0).max().unwrap_or(0);
+    let mut text = self.text.borrow_mut();
+    let old = text[first..=last].to_vec();
+    let count = text.len();
+
+    // from the last one, so the spans before stay where they were
+    let mut order = (0..spans.len()).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|i| std::cmp::Reverse(spans[*i].start));
+    let mut carets = vec![(0, 0); spans.len()];
+    for (done, i) in order.iter().enumerate() {
+      let end = replace(&mut text, spans[*i], inserted);
+      for j in &order[..done] {
+        carets[*j] = shift(carets[*j], spans[*i], end);
+      }
+      carets[*i] = end;
+    }
+    let len = old.len() + text.len() - count;
+    drop(text);
+
+    self.clear_selection();
+    self.set_carets(carets);
+    let edit = Edit {
+      first,
+      old,
+      len,
+      before,
+      after: self.carets(),
+    };
+    self.history.record(edit, joins);
+  }
+
+  /// Puts back the lines `edit` replaced, returning the edit undoing that.
+  fn restore(&mut self, edit: Edit) -> Edit {
+    let len = edit.old.len();
+    let old = self
+      .text
+      .borrow_mut()
+      .splice(edit.first..edit.first + edit.len, edit.old)
+      .collect();
+    self.clear_selection();
+    self.set_carets(edit.before.clone());
+    Edit {
+      first: edit.first,
+      old,
+      len,
+      before: edit.after,
+      after: edit.before,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn state(text: &str) -> EditorState {
+    let lines = text.split('\n').map(String::from).collect();
+    EditorState::new(Rc::new(RefCell::new(lines)))
+  }
+
+  fn text(state: &EditorState) -> String {
+    state.text.borrow().join("\n")
+  }
+
+  fn typed(editor: &mut EditorState, text: &str) {
+    for ch in text.chars() {
+      editor.update(match ch {
+        '\n' => Message::Newline,
+        ch => Message::Insert(ch),
+      });
+    }
+  }
+
+  fn moved(editor: &mut EditorState, direction: Direction) -> Position {
+    editor.update(Message::Move(direction));
+    editor.cursor()
+  }
+
+  #[test]
+  fn edits_text() {
+    let mut editor = state("ab\ncd");
+    editor.update(Message::Move(Direction::Right));
+    typed(&mut editor, "ä\nx");
+    assert_eq!(text(&editor), "aä\nxb\ncd");
+    assert_eq!(editor.cursor(), (1, 1));
+
+    editor.update(Message::MoveTo((2, 0)));
+    assert_eq!(editor.update(Message::Backspace), Change::Text);
+    assert_eq!(text(&editor), "aä\nxbcd");
+    assert_eq!(editor.cursor(), (1, 2));
+
+    editor.update(Message::SetOverwrite(true));
+    editor.update(Message::Insert('y'));
+    assert_eq!(text(&editor), "aä\nxbyd");
+    // at the end of the line there's nothing to replace
+    editor.update(Message::MoveTo((1, 4)));
+    editor.update(Message::Insert('z'));
+    assert_eq!(text(&editor), "aä\nxbydz");
+
+    editor.update(Message::MoveTo((0, 0)));
+    assert_eq!(editor.update(Message::Backspace), Change::Nothing);
+  }
+
+  #[test]
+  fn handles_graphemes() {
+    // a combining accent, a family joined by zero width joiners and a flag
+    let mut editor = state("e\u{301}👨‍👩‍👧🇩🇪x");
+    assert_eq!(moved(&mut editor, Direction::Right), (0, 1));
+    assert_eq!(moved(&mut editor, Direction::Right), (0, 2));
+    assert_eq!(moved(&mut editor, Direction::Right), (0, 3));
+    assert_eq!(moved(&mut editor, Direction::Right), (0, 4));
+    assert_eq!(moved(&mut editor, Direction::Right), (0, 4));
+    editor.update(Message::MoveTo((0, 9)));
+    assert_eq!(editor.cursor(), (0, 4));
+
+    editor.update(Message::MoveTo((0, 3)));
+    editor.update(Message::Backspace);
+    assert_eq!(text(&editor), "e\u{301}👨‍👩‍👧x");
+    editor.update(Message::Backspace);
+    assert_eq!(text(&editor), "e\u{301}x");
+    assert_eq!(editor.cursor(), (0, 1));
+
+    // a combining character joins the grapheme before the cursor
+    editor.update(Message::MoveTo((0, 2)));
+    editor.update(Message::Insert('\u{301}'));
+    assert_eq!(text(&editor), "e\u{301}x\u{301}");
+    assert_eq!(editor.cursor(), (0, 2));
+
+    editor.update(Message::SetOverwrite(true));
+    editor.update(Message::MoveTo((0, 0)));
+    editor.update(Message::Insert('日'));
+    assert_eq!(text(&editor), "日x\u{301}");
+    editor.update(Message::Insert('本'));
+    assert_eq!(text(&editor), "日本");
+    assert_eq!(editor.cursor(), (0, 2));
+  }
+
+  #[test]
+  fn moves_cursor() {
+    let mut editor = state("long line\nab\nlonger line");
+    assert_eq!(moved(&mut editor, Direction::Left), (0, 0));
+    assert_eq!(moved(&mut editor, Direction::Up), (0, 0));
+    assert_eq!(moved(&mut editor, Direction::Down), (1, 0));
+    editor.update(Message::MoveTo((0, 6)));
+    assert_eq!(moved(&mut editor, Direction::Up), (0, 0));
+    editor.update(Message::MoveTo((0, 6)));
+    assert_eq!(moved(&mut editor, Direction::Down), (1, 2));
+    assert_eq!(moved(&mut editor, Direction::Right), (2, 0));
+    assert_eq!(moved(&mut editor, Direction::Left), (1, 2));
+    assert_eq!(moved(&mut editor, Direction::Down), (2, 2));
+    assert_eq!(moved(&mut editor, Direction::Down), (2, 11));
+    assert_eq!(moved(&mut editor, Direction::Right), (2, 11));
+    assert_eq!(moved(&mut editor, Direction::Up), (1, 2));
+
+    editor.update(Message::MoveTo((9, 20)));
+    assert_eq!(editor.cursor(), (2, 11));
+    editor.update(Message::Replace(vec![String::from("a")]));
+    assert_eq!(editor.cursor(), (0, 1));
+  }
+
+  #[test]
+  fn selects_text() {
+    let mut editor = state("foo(bar, baz)\nx");
+    editor.update(Message::Select {
+      anchor: (1, 1),
+      cursor: (0, 5),
+    });
+    assert_eq!(editor.selection(), Some(Span::new((0, 5), (1, 1))));
+    assert_eq!(editor.cursor(), (0, 5));
+    // positions past the text are clamped to it
+    editor.update(Message::Select {
+      anchor: (5, 9),
+      cursor: (0, 5),
+    });
+    assert_eq!(editor.selection(), Some(Span::new((0, 5), (1, 1))));
+    // an empty selection is none
+    editor.update(Message::Select {
+      anchor: (0, 5),
+      cursor: (0, 5),
+    });
+    assert_eq!(editor.selection(), None);
+
+    assert_eq!(editor.update(Message::ExpandSelection), Change::Cursor);
+    assert_eq!(editor.selection(), Some(Span::new((0, 4), (0, 7))));
+    editor.update(Message::ExpandSelection);
+    assert_eq!(editor.selection(), Some(Span::new((0, 4), (0, 12))));
+    assert_eq!(editor.cursor(), (0, 12));
+    editor.update(Message::ShrinkSelection);
+    assert_eq!(editor.selection(), Some(Span::new((0, 4), (0, 7))));
+    editor.update(Message::ShrinkSelection);
+    assert_eq!(editor.selection(), None);
+    assert_eq!(editor.update(Message::ShrinkSelection), Change::Nothing);
+
+    // moving drops the selection and what it grew from
+    editor.update(Message::ExpandSelection);
+    editor.update(Message::Move(Direction::Left));
+    assert_eq!(editor.selection(), None);
+    assert_eq!(editor.update(Message::ShrinkSelection), Change::Nothing);
+  }
+
+  #[test]
+  fn moves_by_words() {
+    let mut editor = state("foo(bar, baz)\nx");
+    let word = |editor: &mut EditorState, forward, select| {
+      editor.update(Message::MoveWord { forward, select });
+      editor.cursor()
+    };
+    assert_eq!(word(&mut editor, true, false), (0, 3));
+    assert_eq!(word(&mut editor, true, true), (0, 7));
+    assert_eq!(editor.selected_text().as_deref(), Some("(bar"));
+    assert_eq!(word(&mut editor, true, true), (0, 12));
+    editor.update(Message::Extend(Direction::Right));
+    assert_eq!(word(&mut editor, true, true), (1, 0));
+    assert_eq!(editor.selected_text().as_deref(), Some("(bar, baz)\n"));
+    assert_eq!(word(&mut editor, false, false), (0, 13));
+    assert_eq!(editor.selection(), None);
+    assert_eq!(word(&mut editor, false, false), (0, 9));
+
+    editor.update(Message::SelectAll);
+    assert_eq!(editor.selected_text().as_deref(), Some("foo(bar, baz)\nx"));
+  }
+
+  #[test]
+  fn replaces_selection() {
+    let mut editor = state("one\ntwo\nthree");
+    editor.update(Message::Select {
+      anchor: (0, 1),
+      cursor: (2, 2),
+    });
+    editor.update(Message::Insert('x'));
+    assert_eq!(text(&editor), "oxree");
+    assert_eq!(editor.cursor(), (0, 2));
+    assert_eq!(editor.selection(), None);
+
+    editor.update(Message::Select {
+      anchor: (0, 4),
+      cursor: (0, 1),
+    });
+    editor.update(Message::Newline);
+    assert_eq!(text(&editor), "o\ne");
+    assert_eq!(editor.cursor(), (1, 0));
+
+    // backspace only removes the selection
+    editor.update(Message::Select {
+      anchor: (0, 0),
+      cursor: (1, 1),
+    });
+    editor.update(Message::Backspace);
+    assert_eq!(text(&editor), "");
+    assert_eq!(editor.cursor(), (0, 0));
+  }
+
+  #[test]
+  fn undoes_edits() {
+    let mut editor = state("");
+    typed(&mut editor, "let a = 1;\nb");
+    assert_eq!(text(&editor), "let a = 1;\nb");
+
+    // words are undone at once along with the space after them
+    let mut undone = vec![];
+    while editor.update(Message::Undo) == Change::Text {
+      undone.push(text(&editor));
+    }
+    assert_eq!(
+      undone,
+      vec![
+        "let a = 1;\n",
+        "let a = 1;",
+        "let a = ",
+        "let a ",
+        "let ",
+        ""
+      ]
+    );
+    assert_eq!(editor.cursor(), (0, 0));
+
+    editor.update(Message::Redo);
+    editor.update(Message::Redo);
+    assert_eq!(text(&editor), "let a ");
+    assert_eq!(editor.cursor(), (0, 6));
+
+    // moving starts a new step, editing drops what was undone
+    editor.update(Message::Move(Direction::Left));
+    editor.update(Message::Insert('x'));
+    editor.update(Message::Move(Direction::Right));
+    editor.update(Message::Insert('y'));
+    assert_eq!(text(&editor), "let ax y");
+    assert_eq!(editor.update(Message::Redo), Change::Nothing);
+    editor.update(Message::Undo);
+    assert_eq!(text(&editor), "let ax ");
+    editor.update(Message::Undo);
+    assert_eq!(text(&editor), "let a ");
+    assert_eq!(editor.cursor(), (0, 5));
+
+    editor.update(Message::Replace(vec![String::from("new")]));
+    editor.update(Message::Backspace);
+    assert_eq!(text(&editor), "ne");
+    editor.update(Message::Undo);
+    editor.update(Message::Undo);
+    assert_eq!(text(&editor), "let a ");
+
+    // changes made elsewhere can't be undone over
+    editor.update(Message::TextChanged);
+    assert_eq!(editor.update(Message::Undo), Change::Nothing);
+    assert_eq!(text(&editor), "let a ");
+  }
+
+  #[test]
+  fn undoes_line_edits() {
+    let mut editor = state("a\nb\nc");
+    editor.update(Message::MoveTo((2, 0)));
+    editor.update(Message::Backspace);
+    editor.update(Message::Backspace);
+    assert_eq!(text(&editor), "a\nc");
+    editor.update(Message::Select {
+      anchor: (0, 0),
+      cursor: (1, 1),
+    });
+    editor.update(Message::Newline);
+    assert_eq!(text(&editor), "\n");
+
+    editor.update(Message::Undo);
+    assert_eq!(text(&editor), "a\nc");
+    assert_eq!(editor.cursor(), (1, 1));
+    editor.update(Message::Undo);
+    editor.update(Message::Undo);
+    assert_eq!(text(&editor), "a\nb\nc");
+    assert_eq!(editor.cursor(), (2, 0));
+    editor.update(Message::Redo);
+    editor.update(Message::Redo);
+    editor.update(Message::Redo);
+    assert_eq!(text(&editor), "\n");
+    assert_eq!(editor.cursor(), (1, 0));
+  }
+
+  #[test]
+  fn pastes_in_chunks() {
+    let mut editor = state("ab\ncd");
+    editor.update(Message::Select {
+      anchor: (0, 1),
+      cursor: (1, 1),
+    });
+    let paste = |text: &str, continuing| Message::Paste {
+      text: text.to_string(),
+      continuing,
+    };
+    editor.update(paste("x\ny", false));
+    editor.update(paste("z\n", true));
+    editor.update(paste("w", true));
+    assert_eq!(text(&editor), "ax\nyz\nwd");
+    assert_eq!(editor.cursor(), (2, 1));
+
+    // the chunks are undone at once
+    editor.update(Message::Undo);
+    assert_eq!(text(&editor), "ab\ncd");
+    assert_eq!(editor.cursor(), (1, 1));
+  }
+
+  #[test]
+  fn edits_at_every_cursor() {
+    let mut editor = state("ab\ncd\nef");
+    editor.update(Message::MoveTo((0, 1)));
+    editor.update(Message::AddCursor(Direction::Down));
+    editor.update(Message::AddCursor(Direction::Down));
+    let added = editor.update(Message::AddCursor(Direction::Down));
+    assert_eq!(added, Change::Nothing);
+    assert_eq!(editor.cursors(), &[(1, 1), (2, 1)]);
+
+    typed(&mut editor, "xy");
+    assert_eq!(text(&editor), "axyb\ncxyd\nexyf");
+    assert_eq!(editor.cursor(), (0, 3));
+    assert_eq!(editor.cursors(), &[(1, 3), (2, 3)]);
+
+    editor.update(Message::Newline);
+    assert_eq!(text(&editor), "axy\nb\ncxy\nd\nexy\nf");
+    assert_eq!(editor.cursor(), (1, 0));
+    assert_eq!(editor.cursors(), &[(3, 0), (5, 0)]);
+
+    // joining lines moves the cursors after them up
+    editor.update(Message::Backspace);
+    assert_eq!(text(&editor), "axyb\ncxyd\nexyf");
+    assert_eq!(editor.cursors(), &[(1, 3), (2, 3)]);
+    editor.update(Message::Undo);
+    assert_eq!(text(&editor), "axy\nb\ncxy\nd\nexy\nf");
+    editor.update(Message::Undo);
+    editor.update(Message::Undo);
+    assert_eq!(text(&editor), "ab\ncd\nef");
+    assert_eq!(editor.cursors(), &[(1, 1), (2, 1)]);
+
+    assert_eq!(moved(&mut editor, Direction::Right), (0, 2));
+    assert_eq!(editor.cursors(), &[(1, 2), (2, 2)]);
+    editor.update(Message::ClearCursors);
+    assert!(editor.cursors().is_empty());
+    assert_eq!(editor.update(Message::ClearCursors), Change::Nothing);
+  }
+
+  #[test]
+  fn merges_cursors() {
+    let mut editor = state("ab\nc");
+    editor.update(Message::MoveTo((1, 1)));
+    editor.update(Message::AddCursor(Direction::Up));
+    assert_eq!(editor.cursors(), &[(0, 1)]);
+
+    // cursors meeting at the same place become one
+    assert_eq!(moved(&mut editor, Direction::Up), (0, 1));
+    assert_eq!(editor.cursors(), &[(0, 0)]);
+    assert_eq!(moved(&mut editor, Direction::Up), (0, 0));
+    assert!(editor.cursors().is_empty());
+
+    // or once they removed the text between them
+    let mut editor = state("abc");
+    editor.update(Message::MoveTo((0, 3)));
+    editor.cursors = vec![(0, 1)];
+    editor.update(Message::Backspace);
+    assert_eq!(text(&editor), "b");
+    assert_eq!(editor.cursor(), (0, 1));
+    assert_eq!(editor.cursors(), &[(0, 0)]);
+    editor.update(Message::Backspace);
+    assert_eq!(text(&editor), "");
+    assert!(editor.cursors().is_empty());
+
+    // selecting keeps a single cursor
+    let mut editor = state("a\nb");
+    editor.update(Message::MoveTo((1, 0)));
+    editor.update(Message::AddCursor(Direction::Up));
+    editor.update(Message::Select {
+      anchor: (0, 0),
+      cursor: (1, 1),
+    });
+    assert!(editor.cursors().is_empty());
+  }
+}
@@ -0,0 +1,93 @@
+//! Regions of lines which can be folded away, told by their indentation: a
+//! line starts one when the lines after it are indented deeper.
+
+/// Lines after `start` up to `end` hidden while the region is folded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Region {
+  pub start: usize,
+  pub end: usize,
+}
+
+/// Columns `line` is indented by, `None` if it's blank.
+fn indent(line: &str) -> Option<usize> {
+  let trimmed = line.trim_start();
+  if trimmed.is_empty() {
+    None
+  } else {
+    Some(line.chars().count() - trimmed.chars().count())
+  }
+}
+
+/// The regions of `lines`, sorted by where they start. Blank lines at the
+/// end of a region are left out of it.
+pub fn regions(lines: &[String]) -> Vec<Region> {
+  let mut regions = vec![];
+  // the lines starting regions not closed yet, with their indentation
+  let mut open: Vec<(usize, usize)> = vec![];
+  let mut last: Option<(usize, usize)> = None;
+  for (row, line) in lines.iter().enumerate() {
+    let indent = match indent(line) {
+      Some(indent) => indent,
+      None => continue,
+    };
+    while let Some(&(start, level)) = open.last() {
+      if indent > level {
+        break;
+      }
+      open.pop();
+      let end = last.map_or(start, |(end, _)| end);
+      regions.push(Region { start, end });
+    }
+    if let Some((previous, level)) = last.filter(|(_, level)| indent > *level) {
+      open.push((previous, level));
+    }
+    last = Some((row, indent));
+  }
+  if let Some((end, _)) = last {
+    regions.extend(open.into_iter().map(|(start, _)| Region { start, end }));
+  }
+  regions.sort_by_key(|region| region.start);
+  regions
+}
+
+/// The lines hidden by the regions of `regions` starting at the `folded`
+/// lines, as inclusive ranges.
+pub fn hidden(regions: &[Region], folded: &[usize]) -> Vec<(usize, usize)> {
+  regions
+    .iter()
+    .filter(|region| folded.contains(&region.start))
+    .map(|region| (region.start + 1, region.end))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(text: &str) -> Vec<String> {
+    text.lines().map(String::from).collect()
+  }
+
+  #[test]
+  fn regions_by_indentation() {
+    let text = lines(
+      "fn main() {
+  if x {
+    y();
+
+  }
+  z();
+
+}
+done",
+    );
+    let region = |start, end| Region { start, end };
+    assert_eq!(regions(&text), vec![region(0, 5), region(1, 2)]);
+    // a region left open at the end goes to the last line which isn't blank
+    assert_eq!(regions(&lines("a\n  b\n  c\n\n")), vec![region(0, 2)]);
+    assert!(regions(&lines("a\nb")).is_empty());
+
+    assert_eq!(hidden(&regions(&text), &[1]), vec![(2, 2)]);
+    assert!(hidden(&regions(&text), &[3]).is_empty());
+  }
+}
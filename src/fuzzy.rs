@@ -0,0 +1,41 @@
+//! Fuzzy matching shared by the command palette, the buffer switcher and
+//! completions.
+
+/// Scores how well `query` matches `text`, `None` if not all characters of
+/// `query` appear in `text` in order, ignoring case and whitespace in the
+/// query. Consecutive matches and matches at the start score higher.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+  let mut score = 0;
+  let mut last_match = None;
+  let mut text = text.chars().flat_map(char::to_lowercase).enumerate();
+  for ch in query
+    .chars()
+    .flat_map(char::to_lowercase)
+    .filter(|ch| !ch.is_whitespace())
+  {
+    let (i, _) = text.find(|(_, t)| *t == ch)?;
+    score += match last_match {
+      Some(last) if last + 1 == i => 10,
+      None if i == 0 => 15,
+      _ => 1,
+    };
+    last_match = Some(i);
+  }
+  Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn scores() {
+    assert_eq!(score("", "anything"), Some(0));
+    assert_eq!(score("xyz", "foo"), None);
+    assert_eq!(score("Save T", "Close Tab"), None);
+    assert!(score("cl tab", "Close Tab").is_some());
+    // consecutive matches at the start are best
+    assert!(score("mai", "main.rs") > score("mai", "src/main.rs"));
+    assert!(score("mai", "src/main.rs") > score("mai", "my_app_init.rs"));
+  }
+}
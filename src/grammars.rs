@@ -0,0 +1,170 @@
+//! Grammars are TOML files in the grammars directory, which also caches the
+//! ones downloaded for file types opened without one.
+
+use crate::config::Config;
+use crate::highlight::Grammar;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+
+pub fn grammars_dir() -> PathBuf {
+  Config::path().with_file_name("grammars")
+}
+
+pub struct Grammars {
+  grammars: Vec<Rc<Grammar>>,
+  generic: Rc<Grammar>,
+}
+
+impl Grammars {
+  pub fn load() -> Self {
+    Self::load_from(&grammars_dir())
+  }
+
+  fn load_from(dir: &Path) -> Self {
+    let mut paths = std::fs::read_dir(dir)
+      .map(|entries| {
+        entries
+          .filter_map(|entry| Some(entry.ok()?.path()))
+          .filter(|path| {
+            path.extension().and_then(|ext| ext.to_str()) == Some("toml")
+          })
+          .collect::<Vec<_>>()
+      })
+      .unwrap_or_default();
+    paths.sort();
+
+    let grammars = paths
+      .into_iter()
+      .filter_map(|path| {
+        let text = std::fs::read_to_string(&path).ok()?;
+        Grammar::parse(&text)
+          .map_err(|err| {
+            tracing::warn!("failed to parse {}: {}", path.display(), err)
+          })
+          .ok()
+      })
+      .map(Rc::new)
+      .collect();
+    Self {
+      grammars,
+      generic: Rc::new(Grammar::generic()),
+    }
+  }
+
+  /// The grammar named like `language`, or else the one for the extension
+  /// of `path`.
+  fn find(
+    &self,
+    language: Option<&str>,
+    path: Option<&Path>,
+  ) -> Option<&Rc<Grammar>> {
+    let extension = path
+      .and_then(Path::extension)
+      .and_then(|extension| extension.to_str());
+    language
+      .and_then(|language| {
+        self
+          .grammars
+          .iter()
+          .find(|grammar| grammar.name == language)
+      })
+      .or_else(|| {
+        let extension = extension?;
+        self
+          .grammars
+          .iter()
+          .find(|grammar| grammar.extensions.iter().any(|ext| ext == extension))
+      })
+  }
+
+  /// Whether there is a grammar for files in `language` or at `path`.
+  pub fn has(&self, language: Option<&str>, path: Option<&Path>) -> bool {
+    self.find(language, path).is_some()
+  }
+
+  /// The grammar for files in `language` or at `path`, the generic one if
+  /// there is none.
+  pub fn get(
+    &self,
+    language: Option<&str>,
+    path: Option<&Path>,
+  ) -> Rc<Grammar> {
+    Rc::clone(self.find(language, path).unwrap_or(&self.generic))
+  }
+
+  /// Adds `grammar`, replacing one with the same name.
+  pub fn add(&mut self, grammar: Grammar) {
+    self
+      .grammars
+      .retain(|existing| existing.name != grammar.name);
+    self.grammars.push(Rc::new(grammar));
+  }
+}
+
+/// Downloads the grammar for files ending in `extension` from `url`, where
+/// `{extension}` is replaced by the extension, and caches it in the grammars
+/// directory.
+pub fn download(url: &str, extension: &str) -> Result<Grammar, anyhow::Error> {
+  let url = url.replace("{extension}", extension);
+  let output = Command::new("curl")
+    .args(&["--fail", "--silent", "--show-error", "--location", &url])
+    .output()?;
+  if !output.status.success() {
+    anyhow::bail!(
+      "downloading {} failed: {}",
+      url,
+      String::from_utf8_lossy(&output.stderr).trim()
+    );
+  }
+  let text = String::from_utf8(output.stdout)?;
+  let grammar = Grammar::parse(&text)?;
+  if !grammar.extensions.iter().any(|ext| ext == extension) {
+    anyhow::bail!("{} isn't a grammar for .{} files", url, extension);
+  }
+  cache(&grammars_dir(), &grammar.name, &text)?;
+  Ok(grammar)
+}
+
+fn cache(dir: &Path, name: &str, text: &str) -> Result<(), anyhow::Error> {
+  // the name comes from the downloaded file
+  if name.is_empty() || name.contains(&['/', '\\', '.'][..]) {
+    anyhow::bail!("invalid grammar name '{}'", name);
+  }
+  std::fs::create_dir_all(dir)?;
+  std::fs::write(dir.join(format!("{}.toml", name)), text)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cached_grammars() {
+    let dir = std::env::temp_dir().join("devcode-grammars-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    cache(&dir, "zig", "name = \"zig\"\nextensions = [\"zig\"]\n").unwrap();
+    assert!(cache(&dir, "../zig", "").is_err());
+
+    let mut grammars = Grammars::load_from(&dir);
+    assert!(grammars.has(None, Some(Path::new("main.zig"))));
+    assert!(!grammars.has(None, Some(Path::new("main.rs"))));
+    assert!(!grammars.has(None, None));
+    assert_eq!(grammars.get(None, Some(Path::new("main.rs"))).name, "text");
+    // the language a file was detected as comes before its extension
+    assert!(grammars.has(Some("zig"), Some(Path::new("build"))));
+    assert_eq!(
+      grammars.get(Some("rust"), Some(Path::new("main.zig"))).name,
+      "zig"
+    );
+
+    grammars.add(Grammar {
+      extensions: vec![String::from("zig"), String::from("zon")],
+      ..Grammar::clone(&grammars.get(None, Some(Path::new("a.zig"))))
+    });
+    assert_eq!(grammars.grammars.len(), 1);
+    assert!(grammars.has(None, Some(Path::new("build.zon"))));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
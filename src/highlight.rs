@@ -0,0 +1,596 @@
+use crate::text::is_word;
+use serde::Deserialize;
+use std::ops::Range;
+use std::rc::Rc;
+use unicode_segmentation::UnicodeSegmentation;
+
+const KEYWORDS: &[&str] = &[
+  "as",
+  "async",
+  "await",
+  "break",
+  "case",
+  "class",
+  "const",
+  "continue",
+  "def",
+  "default",
+  "do",
+  "else",
+  "enum",
+  "export",
+  "extends",
+  "false",
+  "fn",
+  "for",
+  "from",
+  "func",
+  "function",
+  "if",
+  "impl",
+  "import",
+  "in",
+  "interface",
+  "let",
+  "loop",
+  "match",
+  "mod",
+  "module",
+  "mut",
+  "new",
+  "null",
+  "pub",
+  "return",
+  "self",
+  "static",
+  "struct",
+  "switch",
+  "trait",
+  "true",
+  "type",
+  "use",
+  "var",
+  "where",
+  "while",
+  "yield",
+];
+
+/// The syntax of a language as far as highlighting is concerned, loaded from
+/// a TOML file such as
+///
+/// ```toml
+/// name = "lua"
+/// extensions = ["lua"]
+/// keywords = ["local", "function", "end"]
+/// line-comments = ["--"]
+/// block-comment = ["--[[", "]]"]
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Grammar {
+  pub name: String,
+  /// File extensions without the leading dot.
+  pub extensions: Vec<String>,
+  #[serde(default)]
+  pub keywords: Vec<String>,
+  /// Tokens starting a comment which ends with the line. A trailing space
+  /// means the token has to be followed by whitespace or the end of the
+  /// line, so `"# "` leaves out `#[attribute]`.
+  #[serde(default)]
+  pub line_comments: Vec<String>,
+  /// Tokens opening and closing a comment which can span lines.
+  #[serde(default)]
+  pub block_comment: Option<(String, String)>,
+  /// Tokens both starting and ending a string, backslashes escape them.
+  #[serde(default = "default_quotes")]
+  pub quotes: Vec<String>,
+}
+
+fn default_quotes() -> Vec<String> {
+  vec![String::from("\""), String::from("'")]
+}
+
+impl Grammar {
+  /// Used for files without a grammar, covering the most common syntax of
+  /// C-like languages and scripting languages at once.
+  pub fn generic() -> Self {
+    Self {
+      name: String::from("text"),
+      extensions: vec![],
+      keywords: KEYWORDS.iter().map(|keyword| keyword.to_string()).collect(),
+      line_comments: vec![String::from("//"), String::from("# ")],
+      block_comment: Some((String::from("/*"), String::from("*/"))),
+      quotes: default_quotes(),
+    }
+  }
+
+  pub fn parse(text: &str) -> Result<Self, anyhow::Error> {
+    Ok(toml::from_str(text)?)
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Kind {
+  Text,
+  Keyword,
+  String,
+  Comment,
+  Number,
+}
+
+/// A highlighted range of graphemes in a line.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+  pub kind: Kind,
+}
+
+/// What a line starts in, carried over from the line before it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum State {
+  Normal,
+  BlockComment,
+}
+
+/// Whether `graphemes` start with the non-empty `token`.
+fn starts_with(graphemes: &[&str], token: &str) -> bool {
+  let mut graphemes = graphemes.iter();
+  !token.is_empty()
+    && token
+      .graphemes(true)
+      .all(|grapheme| graphemes.next() == Some(&grapheme))
+}
+
+fn length(token: &str) -> usize {
+  token.graphemes(true).count()
+}
+
+fn starts_line_comment(graphemes: &[&str], token: &str) -> bool {
+  match token.strip_suffix(' ') {
+    Some(token) => {
+      starts_with(graphemes, token)
+        && !matches!(
+          graphemes.get(length(token)),
+          Some(next) if !next.trim().is_empty()
+        )
+    }
+    None => starts_with(graphemes, token),
+  }
+}
+
+/// Whether the `'` `graphemes` start with begins a lifetime or label like
+/// `'a`, rather than a char literal like `'a'` or a string like `'a b'`.
+/// The quote closing such a string isn't followed by a name, while the one
+/// of another lifetime is.
+fn starts_lifetime(graphemes: &[&str]) -> bool {
+  let name = graphemes[1..].iter().take_while(|g| is_word(g)).count();
+  let rest = &graphemes[1 + name..];
+  name != 0
+    && rest.first() != Some(&"'")
+    && !rest.iter().enumerate().any(|(i, grapheme)| {
+      *grapheme == "'" && !rest.get(i + 1).map_or(false, |next| is_word(next))
+    })
+}
+
+/// Splits `line` into spans, leaving out whitespace. Returns the state the
+/// next line starts in.
+pub fn highlight_line(
+  line: &str,
+  mut state: State,
+  grammar: &Grammar,
+) -> (Vec<Span>, State) {
+  let graphemes = line.graphemes(true).collect::<Vec<_>>();
+  let block_comment = grammar
+    .block_comment
+    .as_ref()
+    .filter(|(open, close)| !open.is_empty() && !close.is_empty());
+  let mut spans = vec![];
+  let mut i = 0;
+  while i < graphemes.len() {
+    let start = i;
+    let rest = |i: usize| &graphemes[i..];
+    let kind = if let Some((open, close)) = block_comment.filter(|(open, _)| {
+      state == State::BlockComment || starts_with(rest(i), open)
+    }) {
+      if state == State::Normal {
+        state = State::BlockComment;
+        i += length(open);
+      }
+      while i < graphemes.len() && !starts_with(rest(i), close) {
+        i += 1;
+      }
+      if i < graphemes.len() {
+        i += length(close);
+        state = State::Normal;
+      }
+      Kind::Comment
+    } else if grammar
+      .line_comments
+      .iter()
+      .any(|token| starts_line_comment(rest(i), token))
+    {
+      i = graphemes.len();
+      Kind::Comment
+    } else if graphemes[i] == "'"
+      && grammar.quotes.iter().any(|quote| quote == "'")
+      && starts_lifetime(rest(i))
+    {
+      i += 1;
+      while i < graphemes.len() && is_word(graphemes[i]) {
+        i += 1;
+      }
+      Kind::Text
+    } else if let Some(quote) = grammar
+      .quotes
+      .iter()
+      .find(|quote| starts_with(rest(i), quote))
+    {
+      i += length(quote);
+      while i < graphemes.len() && !starts_with(rest(i), quote) {
+        i += if graphemes[i] == "\\" { 2 } else { 1 };
+      }
+      i = (i + length(quote)).min(graphemes.len());
+      Kind::String
+    } else if is_word(graphemes[i]) {
+      while i < graphemes.len() && is_word(graphemes[i]) {
+        i += 1;
+      }
+      let word = graphemes[start..i].concat();
+      if word.starts_with(|ch: char| ch.is_ascii_digit()) {
+        Kind::Number
+      } else if grammar.keywords.contains(&word) {
+        Kind::Keyword
+      } else {
+        Kind::Text
+      }
+    } else {
+      i += 1;
+      if graphemes[start].trim().is_empty() {
+        continue;
+      }
+      Kind::Text
+    };
+
+    match spans.last_mut() {
+      Some(Span {
+        end, kind: last, ..
+      }) if *end == start && *last == kind => *end = i,
+      _ => spans.push(Span {
+        start,
+        end: i,
+        kind,
+      }),
+    }
+  }
+  (spans, state)
+}
+
+struct Line {
+  /// `None` until the line is highlighted.
+  text: Option<String>,
+  state: State,
+  spans: Vec<Span>,
+  next: State,
+}
+
+impl Line {
+  fn unhighlighted() -> Self {
+    Self {
+      text: None,
+      state: State::Normal,
+      spans: vec![],
+      next: State::Normal,
+    }
+  }
+}
+
+/// Highlights a buffer, remembering the spans of each line so that only
+/// lines which changed are highlighted again.
+pub struct Highlighter {
+  grammar: Rc<Grammar>,
+  lines: Vec<Line>,
+  /// Whether changes are reported with [`Highlighter::edit`], set by the
+  /// first one. Otherwise every line is compared with the text it was
+  /// highlighted for.
+  reported: bool,
+  /// Whether every change since the last update of all lines was reported,
+  /// so the lines outside the edits don't have to be compared.
+  exact: bool,
+}
+
+impl Default for Highlighter {
+  fn default() -> Self {
+    Self {
+      grammar: Rc::new(Grammar::generic()),
+      lines: vec![],
+      reported: false,
+      exact: false,
+    }
+  }
+}
+
+impl Highlighter {
+  /// Switches to `grammar`, highlighting all lines again on the next update.
+  pub fn set_grammar(&mut self, grammar: Rc<Grammar>) {
+    if grammar != self.grammar {
+      self.grammar = grammar;
+      self.lines.clear();
+    }
+  }
+
+  /// Reports that the lines `start..start + removed` were replaced by
+  /// `inserted` lines. The new lines are highlighted on the next update, the
+  /// lines after them move along with their spans instead of being
+  /// highlighted again.
+  pub fn edit(&mut self, start: usize, removed: usize, inserted: usize) {
+    self.reported = true;
+    if start > self.lines.len() {
+      return;
+    }
+    let end = (start + removed).min(self.lines.len());
+    self
+      .lines
+      .splice(start..end, (0..inserted).map(|_| Line::unhighlighted()));
+  }
+
+  /// Reports a change which wasn't given to [`Highlighter::edit`], the lines
+  /// are compared with the text they were highlighted for on the next update.
+  pub fn invalidate(&mut self) {
+    self.exact = false;
+  }
+
+  /// Brings the spans up to date with `lines`, returning the indices of the
+  /// lines whose spans changed.
+  pub fn update(&mut self, lines: &[String]) -> Vec<usize> {
+    self.update_range(lines, 0..lines.len())
+  }
+
+  /// Like [`Highlighter::update`], but only for the lines in `range`. The
+  /// first of them starts in the state the line before was last left in,
+  /// the spans of the lines outside are kept even if they are outdated.
+  pub fn update_range(
+    &mut self,
+    lines: &[String],
+    range: Range<usize>,
+  ) -> Vec<usize> {
+    let end = range.end.min(lines.len());
+    let start = range.start.min(end);
+    self.lines.resize_with(lines.len(), Line::unhighlighted);
+
+    let mut changed = vec![];
+    let mut state = match start {
+      0 => State::Normal,
+      _ => self.lines[start - 1].next,
+    };
+    for (i, text) in lines.iter().enumerate().take(end).skip(start) {
+      let line = &self.lines[i];
+      let same = match &line.text {
+        Some(highlighted) => self.exact || highlighted == text,
+        None => false,
+      };
+      if line.state != state || !same {
+        let (spans, next) = highlight_line(text, state, &self.grammar);
+        self.lines[i] = Line {
+          text: Some(text.clone()),
+          state,
+          spans,
+          next,
+        };
+        changed.push(i);
+      }
+      state = self.lines[i].next;
+    }
+    if start == 0 && end == lines.len() {
+      self.exact = self.reported;
+    }
+    changed
+  }
+
+  pub fn spans(&self, line: usize) -> &[Span] {
+    self.lines.get(line).map_or(&[], |line| &line.spans)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn kinds(line: &str) -> Vec<(usize, usize, Kind)> {
+    highlight_line(line, State::Normal, &Grammar::generic())
+      .0
+      .into_iter()
+      .map(|span| (span.start, span.end, span.kind))
+      .collect()
+  }
+
+  #[test]
+  fn spans() {
+    assert_eq!(
+      kinds("let x = \"a b\"; // hi"),
+      vec![
+        (0, 3, Kind::Keyword),
+        (4, 5, Kind::Text),
+        (6, 7, Kind::Text),
+        (8, 13, Kind::String),
+        (13, 14, Kind::Text),
+        (15, 20, Kind::Comment),
+      ]
+    );
+    assert_eq!(
+      kinds("f(12)"),
+      vec![(0, 2, Kind::Text), (2, 4, Kind::Number), (4, 5, Kind::Text),]
+    );
+    assert_eq!(kinds("'\\''"), vec![(0, 4, Kind::String)]);
+    assert_eq!(
+      kinds("'a' 'ab c'"),
+      vec![(0, 3, Kind::String), (4, 10, Kind::String)]
+    );
+    // lifetimes aren't strings running to the end of the line
+    assert_eq!(
+      kinds("&'a str"),
+      vec![(0, 3, Kind::Text), (4, 7, Kind::Text)]
+    );
+    assert_eq!(
+      kinds("<'a, 'b>"),
+      vec![(0, 4, Kind::Text), (5, 8, Kind::Text)]
+    );
+    assert_eq!(kinds("# a"), vec![(0, 3, Kind::Comment)]);
+    assert_eq!(kinds("#[a]"), vec![(0, 4, Kind::Text)]);
+  }
+
+  #[test]
+  fn block_comments() {
+    let grammar = Grammar::generic();
+    let (spans, state) = highlight_line("a /* b", State::Normal, &grammar);
+    assert_eq!(
+      spans[1],
+      Span {
+        start: 2,
+        end: 6,
+        kind: Kind::Comment,
+      }
+    );
+    assert_eq!(state, State::BlockComment);
+    let (spans, state) =
+      highlight_line("c */ d", State::BlockComment, &grammar);
+    assert_eq!(
+      spans,
+      vec![
+        Span {
+          start: 0,
+          end: 4,
+          kind: Kind::Comment,
+        },
+        Span {
+          start: 5,
+          end: 6,
+          kind: Kind::Text,
+        },
+      ]
+    );
+    assert_eq!(state, State::Normal);
+  }
+
+  #[test]
+  fn grammars() {
+    let grammar = Grammar::parse(
+      r#"
+      name = "lua"
+      extensions = ["lua"]
+      keywords = ["local"]
+      line-comments = ["--"]
+      block-comment = ["--[[", "]]"]
+      quotes = ['"""', '"']
+      "#,
+    )
+    .unwrap();
+    let kinds = |line: &str| {
+      highlight_line(line, State::Normal, &grammar)
+        .0
+        .into_iter()
+        .map(|span| (span.start, span.end, span.kind))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+      kinds("local x -- y"),
+      vec![
+        (0, 5, Kind::Keyword),
+        (6, 7, Kind::Text),
+        (8, 12, Kind::Comment)
+      ]
+    );
+    assert_eq!(
+      kinds("\"\"\"a\"\"\" // b"),
+      vec![
+        (0, 7, Kind::String),
+        (8, 10, Kind::Text),
+        (11, 12, Kind::Text),
+      ]
+    );
+    assert_eq!(
+      highlight_line("--[[ a", State::Normal, &grammar).1,
+      State::BlockComment
+    );
+  }
+
+  #[test]
+  fn only_changed_lines() {
+    let mut lines = vec![
+      "fn a() {}".to_string(),
+      "/*".to_string(),
+      "x".to_string(),
+      "*/".to_string(),
+    ];
+    let mut highlighter = Highlighter::default();
+    assert_eq!(highlighter.update(&lines), vec![0, 1, 2, 3]);
+    assert_eq!(highlighter.update(&lines), Vec::<usize>::new());
+
+    lines[0].push('x');
+    assert_eq!(highlighter.update(&lines), vec![0]);
+
+    // closing the comment early changes the lines after it
+    lines[1].push_str("*/");
+    assert_eq!(highlighter.update(&lines), vec![1, 2, 3]);
+    assert_eq!(highlighter.spans(2)[0].kind, Kind::Text);
+
+    lines.pop();
+    assert_eq!(highlighter.update(&lines), Vec::<usize>::new());
+    assert_eq!(highlighter.spans(3), &[]);
+  }
+
+  #[test]
+  fn lines_in_range() {
+    let mut lines = vec![
+      "/*".to_string(),
+      "x".to_string(),
+      "*/".to_string(),
+      "y".to_string(),
+    ];
+    let mut highlighter = Highlighter::default();
+    assert_eq!(highlighter.update_range(&lines, 2..10), vec![2, 3]);
+    assert_eq!(highlighter.spans(1), &[]);
+    // nothing before it was highlighted yet
+    assert_eq!(highlighter.spans(2)[0].kind, Kind::Text);
+
+    assert_eq!(highlighter.update_range(&lines, 0..2), vec![0, 1]);
+    assert_eq!(highlighter.update_range(&lines, 1..4), vec![2]);
+    assert_eq!(highlighter.spans(2)[0].kind, Kind::Comment);
+
+    lines.insert(0, String::new());
+    assert_eq!(highlighter.update_range(&lines, 0..1), vec![0]);
+    assert_eq!(highlighter.spans(4), &[]);
+  }
+
+  #[test]
+  fn edits() {
+    let mut lines = (0..1000).map(|i| format!("x{}", i)).collect::<Vec<_>>();
+    let mut highlighter = Highlighter::default();
+    assert_eq!(highlighter.update(&lines).len(), 1000);
+
+    // x10 split in two
+    lines[10] = String::from("x1");
+    lines.insert(11, String::from("0"));
+    highlighter.edit(10, 1, 2);
+    assert_eq!(highlighter.update(&lines), vec![10, 11]);
+    assert_eq!(highlighter.spans(11)[0].kind, Kind::Number);
+
+    // and joined again
+    lines[10] = String::from("x10");
+    lines.remove(11);
+    highlighter.edit(10, 2, 1);
+    assert_eq!(highlighter.update(&lines), vec![10]);
+
+    // a comment running until the end
+    lines[10] = String::from("/* x10");
+    highlighter.edit(10, 1, 1);
+    assert_eq!(highlighter.update(&lines).len(), 990);
+    assert_eq!(highlighter.spans(999)[0].kind, Kind::Comment);
+
+    // a change which wasn't reported
+    lines[10] = String::from("let");
+    highlighter.invalidate();
+    assert_eq!(highlighter.update(&lines).len(), 990);
+    assert_eq!(highlighter.spans(10)[0].kind, Kind::Keyword);
+  }
+}
@@ -0,0 +1,315 @@
+use crate::cli::FileArg;
+use crate::config::Config;
+use serde_json::{json, Value};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use winit::event_loop::EventLoopProxy;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// Sent back once a running instance took the files, so a port reused by
+/// another program isn't mistaken for an instance.
+const ACK: &str = "devcode-ok";
+
+/// Directory with a file per running instance, holding its port, token and
+/// workspace.
+fn instances_dir() -> PathBuf {
+  Config::path().with_file_name("instances")
+}
+
+/// A random token which has to come first in every request. Only the user
+/// can read the instance files, so other users' processes can't send files
+/// even though anyone can connect to the port.
+fn token() -> String {
+  // the hashers are keyed with random keys
+  (0..2)
+    .map(|_| format!("{:016x}", RandomState::new().build_hasher().finish()))
+    .collect()
+}
+
+/// Writes `text` to a new file at `path` which only the user can read.
+fn write_private(path: &Path, text: &str) -> std::io::Result<()> {
+  // the mode only applies to new files
+  let _ = std::fs::remove_file(path);
+  let mut options = std::fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+  options.open(path)?.write_all(text.as_bytes())
+}
+
+/// The directory to use as workspace for a file opened from the OS: the
+/// closest ancestor that is a git repository, otherwise the file's
+/// directory.
+pub fn workspace_for(path: &Path) -> PathBuf {
+  let dir = path.parent().unwrap_or(path);
+  dir
+    .ancestors()
+    .find(|ancestor| ancestor.join(".git").exists())
+    .unwrap_or(dir)
+    .to_path_buf()
+}
+
+fn encode(files: &[FileArg]) -> String {
+  Value::from(
+    files
+      .iter()
+      .map(|file| {
+        json!({
+          "path": file.path.to_string_lossy(),
+          "position": file.position,
+        })
+      })
+      .collect::<Vec<_>>(),
+  )
+  .to_string()
+}
+
+fn decode(message: &str) -> Result<Vec<FileArg>, anyhow::Error> {
+  let value = serde_json::from_str::<Value>(message)?;
+  value
+    .as_array()
+    .ok_or_else(|| anyhow::anyhow!("expected a list of files"))?
+    .iter()
+    .map(|file| {
+      let path = file["path"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("missing path: {}", file))?;
+      let position = match file["position"].as_array().map(Vec::as_slice) {
+        Some([line, column]) => line
+          .as_u64()
+          .zip(column.as_u64())
+          .map(|(line, column)| (line as usize, column as usize)),
+        _ => None,
+      };
+      Ok(FileArg {
+        path: PathBuf::from(path),
+        position,
+      })
+    })
+    .collect()
+}
+
+/// The port, token and workspace of an instance file.
+fn parse_instance(text: &str) -> Option<(u16, String, PathBuf)> {
+  let mut lines = text.lines();
+  let port = lines.next()?.parse().ok()?;
+  let token = lines.next()?.to_string();
+  let workspace = PathBuf::from(lines.next()?);
+  Some((port, token, workspace))
+}
+
+/// A running instance as the file registering it, its port, its token and
+/// its workspace.
+type Entry = (PathBuf, u16, String, PathBuf);
+
+fn instances() -> Vec<Entry> {
+  let entries = match std::fs::read_dir(instances_dir()) {
+    Ok(entries) => entries,
+    Err(_) => return vec![],
+  };
+  entries
+    .filter_map(|entry| {
+      let path = entry.ok()?.path();
+      let text = std::fs::read_to_string(&path).ok()?;
+      let (port, token, workspace) = parse_instance(&text)?;
+      Some((path, port, token, workspace))
+    })
+    .collect()
+}
+
+fn send_to(port: u16, token: &str, message: &str) -> Result<(), anyhow::Error> {
+  let mut stream =
+    TcpStream::connect_timeout(&(Ipv4Addr::LOCALHOST, port).into(), TIMEOUT)?;
+  stream.set_read_timeout(Some(TIMEOUT))?;
+  writeln!(stream, "{}\n{}", token, message)?;
+  let mut reply = String::new();
+  BufReader::new(stream).read_line(&mut reply)?;
+  if reply.trim_end() != ACK {
+    anyhow::bail!("not a devcode instance");
+  }
+  Ok(())
+}
+
+/// Hands `files` to the running instance with the innermost workspace
+/// containing the first of them. Returns whether one took them.
+pub fn send(files: &[FileArg]) -> bool {
+  let files = files
+    .iter()
+    .map(|file| FileArg {
+      path: file
+        .path
+        .canonicalize()
+        .unwrap_or_else(|_| file.path.clone()),
+      position: file.position,
+    })
+    .collect::<Vec<_>>();
+  let first = match files.first() {
+    Some(file) => &file.path,
+    None => return false,
+  };
+
+  let mut candidates = instances()
+    .into_iter()
+    .filter(|(_, _, _, workspace)| first.starts_with(workspace))
+    .collect::<Vec<_>>();
+  candidates.sort_by_key(|(_, _, _, workspace)| {
+    std::cmp::Reverse(workspace.components().count())
+  });
+
+  let message = encode(&files);
+  for (path, port, token, workspace) in candidates {
+    match send_to(port, &token, &message) {
+      Ok(()) => return true,
+      Err(err) => {
+        // most likely the instance exited without unregistering
+        tracing::debug!(
+          "instance for {} didn't respond: {}",
+          workspace.display(),
+          err
+        );
+        let _ = std::fs::remove_file(path);
+      }
+    }
+  }
+  false
+}
+
+/// Registration of this process as a running instance.
+pub struct Instance {
+  path: PathBuf,
+}
+
+impl Instance {
+  /// Accepts files from [`send`] on a background thread, turning them into
+  /// events for the event loop with `event`.
+  pub fn listen<T>(
+    workspace: &Path,
+    proxy: EventLoopProxy<T>,
+    event: fn(Vec<FileArg>) -> T,
+  ) -> Result<Self, anyhow::Error>
+  where
+    T: Send + 'static,
+  {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let port = listener.local_addr()?.port();
+    let token = token();
+    std::fs::create_dir_all(instances_dir())?;
+    let path = instances_dir().join(std::process::id().to_string());
+    write_private(
+      &path,
+      &format!("{}\n{}\n{}\n", port, token, workspace.display()),
+    )?;
+
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let stream = match stream {
+          Ok(stream) => stream,
+          Err(_) => continue,
+        };
+        // a client which never writes only holds up its own thread
+        let token = token.clone();
+        let proxy = proxy.clone();
+        std::thread::spawn(move || {
+          if let Err(err) = Self::accept(&stream, &token, &proxy, event) {
+            tracing::warn!("invalid open request: {}", err);
+          }
+        });
+      }
+    });
+
+    Ok(Self { path })
+  }
+
+  /// Reads a request from `stream` and passes its files on, if it starts
+  /// with `token`.
+  fn accept<T>(
+    mut stream: &TcpStream,
+    token: &str,
+    proxy: &EventLoopProxy<T>,
+    event: fn(Vec<FileArg>) -> T,
+  ) -> Result<(), anyhow::Error> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end() != token {
+      anyhow::bail!("wrong token");
+    }
+    line.clear();
+    reader.read_line(&mut line)?;
+    let files = decode(&line)?;
+    proxy
+      .send_event(event(files))
+      .map_err(|_| anyhow::anyhow!("the event loop is gone"))?;
+    writeln!(stream, "{}", ACK)?;
+    Ok(())
+  }
+
+  /// Stops other processes from sending files here.
+  pub fn close(&self) {
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn messages() {
+    let files = vec![
+      FileArg {
+        path: PathBuf::from("/a.rs"),
+        position: Some((3, 4)),
+      },
+      FileArg {
+        path: PathBuf::from("/b c.rs"),
+        position: None,
+      },
+    ];
+    assert_eq!(decode(&encode(&files)).unwrap(), files);
+    assert!(decode("{}").is_err());
+  }
+
+  #[test]
+  fn instance_files() {
+    let token = token();
+    assert_eq!(token.len(), 32);
+    assert_ne!(token, super::token());
+
+    let path = std::env::temp_dir()
+      .join(format!("devcode-instance-{}", std::process::id()));
+    let text = format!("4000\n{}\n/a b\n", token);
+    write_private(&path, &text).unwrap();
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+      assert_eq!(mode & 0o777, 0o600);
+    }
+    let read = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+      parse_instance(&read),
+      Some((4000, token, PathBuf::from("/a b")))
+    );
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(parse_instance("4000\n/a b\n"), None);
+  }
+
+  #[test]
+  fn workspaces() {
+    let root = std::env::temp_dir().join("devcode-workspace-test");
+    std::fs::create_dir_all(root.join(".git")).unwrap();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    assert_eq!(workspace_for(&root.join("src/main.rs")), root);
+    std::fs::remove_dir_all(&root).unwrap();
+    assert_eq!(
+      workspace_for(Path::new("/no/repository/file.rs")),
+      Path::new("/no/repository")
+    );
+  }
+}
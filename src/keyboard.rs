@@ -0,0 +1,151 @@
+//! Keyboard layouts, telling the character each key types by where it is on
+//! the keyboard. Shortcuts are matched by the character on the key rather
+//! than what the platform reports for it, which is a US key on some
+//! platforms whatever the layout. Scancodes are those of PC keyboards, as
+//! Linux and Windows report them, so layouts are only used there.
+
+use crate::config::Config;
+use std::collections::HashMap;
+
+/// The scancodes of the rows of keys typing characters, from the left.
+const ROWS: [&[u32]; 4] = [
+  &[41, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13],
+  &[16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 43],
+  &[30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40],
+  &[44, 45, 46, 47, 48, 49, 50, 51, 52, 53],
+];
+
+/// The layouts which come built in, by the characters of their rows typed
+/// without shift.
+const BUILT_IN: &[(&str, [&str; 4])] = &[
+  (
+    "us",
+    [
+      "`1234567890-=",
+      "qwertyuiop[]\\",
+      "asdfghjkl;'",
+      "zxcvbnm,./",
+    ],
+  ),
+  (
+    "azerty",
+    [
+      "²&é\"'(-è_çà)=",
+      "azertyuiop^$*",
+      "qsdfghjklmù",
+      "wxcvbn,;:!",
+    ],
+  ),
+  (
+    "qwertz",
+    [
+      "^1234567890ß´",
+      "qwertzuiopü+#",
+      "asdfghjklöä",
+      "yxcvbnm,.-",
+    ],
+  ),
+  (
+    "dvorak",
+    [
+      "`1234567890[]",
+      "',.pyfgcrl/=\\",
+      "aoeuidhtns-",
+      ";qjkxbmwvz",
+    ],
+  ),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+  keys: HashMap<u32, char>,
+}
+
+impl Layout {
+  /// A layout from the characters of the rows of keys, the digits first.
+  /// Spaces mark keys typing nothing and rows can leave out keys at their
+  /// end.
+  pub fn from_rows(rows: &[impl AsRef<str>]) -> anyhow::Result<Self> {
+    if rows.len() > ROWS.len() {
+      anyhow::bail!("a layout has {} rows, not {}", ROWS.len(), rows.len());
+    }
+    let mut keys = HashMap::new();
+    for (row, scancodes) in rows.iter().zip(ROWS.iter()) {
+      let row = row.as_ref();
+      if row.chars().count() > scancodes.len() {
+        anyhow::bail!("'{}' has more than {} keys", row, scancodes.len());
+      }
+      for (ch, scancode) in row.chars().zip(scancodes.iter()) {
+        if ch != ' ' {
+          keys.insert(*scancode, ch.to_lowercase().next().unwrap_or(ch));
+        }
+      }
+    }
+    Ok(Self { keys })
+  }
+
+  /// The layout called `name`, one of `custom` or a built in one.
+  pub fn named(
+    name: &str,
+    custom: &HashMap<String, Vec<String>>,
+  ) -> anyhow::Result<Self> {
+    if let Some(rows) = custom.get(name) {
+      return Self::from_rows(rows);
+    }
+    match BUILT_IN.iter().find(|(built_in, _)| *built_in == name) {
+      Some((_, rows)) => Self::from_rows(rows),
+      None => anyhow::bail!("unknown keyboard layout '{}'", name),
+    }
+  }
+
+  /// The layout set in `config`, if any. An invalid one is left unset, as
+  /// is any on platforms with other scancodes.
+  pub fn configured(config: &Config) -> Option<Self> {
+    let name = config.keyboard_layout.as_ref()?;
+    if !cfg!(any(target_os = "linux", target_os = "windows")) {
+      tracing::warn!(
+        "keyboard layouts only work on Linux and Windows, ignoring '{}'",
+        name
+      );
+      return None;
+    }
+    Self::named(name, &config.keyboard_layouts)
+      .map_err(|err| tracing::warn!("{}", err))
+      .ok()
+  }
+
+  /// The US layout, to name keys by when no layout is set.
+  pub fn us() -> Self {
+    Self::named("us", &HashMap::new()).unwrap()
+  }
+
+  /// The character the key at `scancode` types without shift.
+  pub fn char(&self, scancode: u32) -> Option<char> {
+    self.keys.get(&scancode).copied()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn layouts() {
+    let azerty = Layout::named("azerty", &HashMap::new()).unwrap();
+    assert_eq!(azerty.char(16), Some('a'));
+    assert_eq!(azerty.char(26), Some('^'));
+    assert_eq!(azerty.char(50), Some(','));
+    assert_eq!(Layout::us().char(26), Some('['));
+    assert_eq!(Layout::us().char(57), None);
+
+    let mut custom = HashMap::new();
+    custom.insert("mine".to_string(), vec![" 1".to_string(), "Qw".to_string()]);
+    let mine = Layout::named("mine", &custom).unwrap();
+    assert_eq!((mine.char(41), mine.char(2)), (None, Some('1')));
+    assert_eq!((mine.char(16), mine.char(18)), (Some('q'), None));
+    for rows in &[vec!["12345678901234"], vec![""; 5]] {
+      assert!(Layout::from_rows(rows).is_err());
+    }
+    assert!(Layout::named("colemak", &custom).is_err());
+  }
+}
@@ -0,0 +1,533 @@
+use crate::commands::Command;
+use crate::keyboard::Layout;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+/// A key of a key combination.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+  /// A key which doesn't type a character, like the arrows.
+  Named(VirtualKeyCode),
+  /// The key typing the character without shift in the keyboard layout.
+  Char(char),
+  /// The key at a place on the keyboard whatever the layout, written like
+  /// `"scancode:26"`.
+  Scancode(u32),
+}
+
+impl From<VirtualKeyCode> for Key {
+  fn from(key: VirtualKeyCode) -> Self {
+    let name = KEY_NAMES.iter().find(|(_, named)| *named == key);
+    let mut chars = name.map(|(name, _)| name.chars()).into_iter().flatten();
+    match (chars.next(), chars.next()) {
+      (Some(ch), None) => Self::Char(ch),
+      _ => Self::Named(key),
+    }
+  }
+}
+
+/// A key together with the modifiers that have to be held, parsed from
+/// strings like `"ctrl+shift+p"`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+  modifiers: ModifiersState,
+  key: Key,
+}
+
+impl KeyCombo {
+  pub fn new(modifiers: ModifiersState, key: VirtualKeyCode) -> Self {
+    Self {
+      modifiers,
+      key: key.into(),
+    }
+  }
+
+  /// Writes the modifiers like `"ctrl+shift+"`.
+  fn fmt_modifiers(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (modifier, name) in [
+      (ModifiersState::CTRL, "ctrl"),
+      (ModifiersState::SHIFT, "shift"),
+      (ModifiersState::ALT, "alt"),
+      (ModifiersState::LOGO, "super"),
+    ]
+    .iter()
+    {
+      if self.modifiers.contains(*modifier) {
+        write!(f, "{}+", name)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for KeyCombo {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut modifiers = ModifiersState::empty();
+    let mut key = None;
+    for part in s.split('+').map(str::trim) {
+      match part.to_lowercase().as_str() {
+        "ctrl" | "control" => modifiers |= ModifiersState::CTRL,
+        "shift" => modifiers |= ModifiersState::SHIFT,
+        "alt" | "option" => modifiers |= ModifiersState::ALT,
+        "super" | "cmd" | "meta" | "logo" => modifiers |= ModifiersState::LOGO,
+        name if key.is_none() => {
+          key = Some(
+            key_from_name(name)
+              .ok_or_else(|| anyhow::anyhow!("unknown key '{}'", part))?,
+          );
+        }
+        _ => anyhow::bail!("'{}' contains more than one key", s),
+      }
+    }
+
+    Ok(Self {
+      modifiers,
+      key: key.ok_or_else(|| anyhow::anyhow!("'{}' has no key", s))?,
+    })
+  }
+}
+
+impl fmt::Display for KeyCombo {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.fmt_modifiers(f)?;
+    match self.key {
+      Key::Named(key) => {
+        match KEY_NAMES.iter().find(|(_, named)| *named == key) {
+          Some((name, _)) => f.write_str(name),
+          None => write!(f, "{:?}", key),
+        }
+      }
+      Key::Char(ch) => write!(f, "{}", ch),
+      Key::Scancode(scancode) => write!(f, "scancode:{}", scancode),
+    }
+  }
+}
+
+/// Shows a combination with its key as the keyboard layout labels it.
+struct Described<'a>(&'a KeyCombo, &'a Layout);
+
+impl fmt::Display for Described<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let Described(combo, layout) = self;
+    match combo.key {
+      Key::Scancode(scancode) => match layout.char(scancode) {
+        Some(ch) => {
+          combo.fmt_modifiers(f)?;
+          write!(f, "{}", ch)
+        }
+        None => write!(f, "{}", combo),
+      },
+      _ => write!(f, "{}", combo),
+    }
+  }
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+  if let Some(scancode) = name.strip_prefix("scancode:") {
+    return scancode.parse().ok().map(Key::Scancode);
+  }
+  let mut chars = name.chars();
+  if let (Some(ch), None) = (chars.next(), chars.next()) {
+    return Some(Key::Char(ch));
+  }
+  KEY_NAMES
+    .iter()
+    .find(|(key_name, _)| *key_name == name)
+    .map(|(_, key)| (*key).into())
+}
+
+macro_rules! keys {
+  ($($name: literal => $key: ident),* $(,)?) => {
+    &[$(($name, VirtualKeyCode::$key),)*]
+  };
+}
+
+/// The names of the keys, a key is shown with the first of its names.
+const KEY_NAMES: &[(&str, VirtualKeyCode)] = keys! {
+    "a" => A, "b" => B, "c" => C, "d" => D, "e" => E, "f" => F, "g" => G,
+    "h" => H, "i" => I, "j" => J, "k" => K, "l" => L, "m" => M, "n" => N,
+    "o" => O, "p" => P, "q" => Q, "r" => R, "s" => S, "t" => T, "u" => U,
+    "v" => V, "w" => W, "x" => X, "y" => Y, "z" => Z,
+    "0" => Key0, "1" => Key1, "2" => Key2, "3" => Key3, "4" => Key4,
+    "5" => Key5, "6" => Key6, "7" => Key7, "8" => Key8, "9" => Key9,
+    "f1" => F1, "f2" => F2, "f3" => F3, "f4" => F4, "f5" => F5, "f6" => F6,
+    "f7" => F7, "f8" => F8, "f9" => F9, "f10" => F10, "f11" => F11,
+    "f12" => F12,
+    "up" => Up, "down" => Down, "left" => Left, "right" => Right,
+    "escape" => Escape, "esc" => Escape, "tab" => Tab, "space" => Space,
+    "enter" => Return, "return" => Return, "backspace" => Back,
+    "delete" => Delete, "insert" => Insert, "home" => Home, "end" => End,
+    "pageup" => PageUp, "pagedown" => PageDown,
+    "-" => Minus, "=" => Equals, "," => Comma, "." => Period, "/" => Slash,
+    "\\" => Backslash, ";" => Semicolon, "'" => Apostrophe,
+    "[" => LBracket, "]" => RBracket, "`" => Grave,
+};
+
+/// Which command a key combination triggers.
+pub struct Keymap {
+  bindings: HashMap<KeyCombo, Command>,
+  /// Tells the keys typing characters apart, the keys the platform reports
+  /// are taken as they are if unset.
+  layout: Option<Layout>,
+}
+
+impl Keymap {
+  fn defaults() -> HashMap<KeyCombo, Command> {
+    let ctrl_shift = ModifiersState::CTRL | ModifiersState::SHIFT;
+    let mut bindings = HashMap::new();
+    bindings.insert(
+      KeyCombo::new(ctrl_shift, VirtualKeyCode::P),
+      Command::ShowCommandPalette,
+    );
+    bindings.insert(
+      KeyCombo::new(ctrl_shift, VirtualKeyCode::I),
+      Command::ToggleDebugOverlay,
+    );
+    bindings.insert(
+      KeyCombo::new(ctrl_shift, VirtualKeyCode::M),
+      Command::ToggleMaximizePanel,
+    );
+    bindings.insert(
+      KeyCombo::new(
+        ModifiersState::CTRL | ModifiersState::ALT,
+        VirtualKeyCode::Z,
+      ),
+      Command::ToggleZenMode,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::empty(), VirtualKeyCode::F11),
+      Command::ToggleFullscreen,
+    );
+    bindings.insert(
+      KeyCombo::new(ctrl_shift, VirtualKeyCode::N),
+      Command::NewWindow,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::B),
+      Command::ToggleSidebar,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::P),
+      Command::QuickOpen,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::Tab),
+      Command::SwitchBuffer,
+    );
+    for (key, command) in [
+      (VirtualKeyCode::E, Command::ShowExplorer),
+      (VirtualKeyCode::O, Command::ShowOutline),
+      (VirtualKeyCode::F, Command::ShowSearch),
+      (VirtualKeyCode::G, Command::ShowSourceControl),
+      (VirtualKeyCode::D, Command::ShowDebug),
+      (VirtualKeyCode::X, Command::ShowExtensions),
+    ]
+    .iter()
+    {
+      bindings.insert(KeyCombo::new(ctrl_shift, *key), *command);
+    }
+    bindings.insert(
+      KeyCombo::new(ctrl_shift, VirtualKeyCode::B),
+      Command::RunLastTask,
+    );
+    bindings.insert(
+      KeyCombo::new(ctrl_shift, VirtualKeyCode::U),
+      Command::ToggleOutput,
+    );
+    bindings.insert(
+      KeyCombo::new(ctrl_shift, VirtualKeyCode::H),
+      Command::ReplaceInFiles,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::K),
+      Command::ResizeMode,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::empty(), VirtualKeyCode::F2),
+      Command::RenameSymbol,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::Period),
+      Command::ShowCodeActions,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::empty(), VirtualKeyCode::Insert),
+      Command::ToggleOverwrite,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::S),
+      Command::Save,
+    );
+    bindings.insert(
+      KeyCombo::new(ctrl_shift, VirtualKeyCode::S),
+      Command::SaveAs,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::N),
+      Command::NewFile,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::W),
+      Command::CloseTab,
+    );
+    let ctrl_alt = ModifiersState::CTRL | ModifiersState::ALT;
+    for (key, command) in [
+      (VirtualKeyCode::K, Command::ToggleBookmark),
+      (VirtualKeyCode::L, Command::NextBookmark),
+      (VirtualKeyCode::J, Command::PreviousBookmark),
+    ]
+    .iter()
+    {
+      bindings.insert(KeyCombo::new(ctrl_alt, *key), *command);
+    }
+    for (modifiers, key, command) in [
+      (
+        ModifiersState::empty(),
+        VirtualKeyCode::F8,
+        Command::NextProblem,
+      ),
+      (
+        ModifiersState::SHIFT,
+        VirtualKeyCode::F8,
+        Command::PreviousProblem,
+      ),
+      (ModifiersState::ALT, VirtualKeyCode::F5, Command::NextChange),
+      (
+        ModifiersState::ALT | ModifiersState::SHIFT,
+        VirtualKeyCode::F5,
+        Command::PreviousChange,
+      ),
+      (ModifiersState::ALT, VirtualKeyCode::F3, Command::NextMatch),
+      (
+        ModifiersState::ALT | ModifiersState::SHIFT,
+        VirtualKeyCode::F3,
+        Command::PreviousMatch,
+      ),
+    ]
+    .iter()
+    {
+      bindings.insert(KeyCombo::new(*modifiers, *key), *command);
+    }
+    let alt_shift = ModifiersState::ALT | ModifiersState::SHIFT;
+    bindings.insert(
+      KeyCombo::new(alt_shift, VirtualKeyCode::Up),
+      Command::ExpandSelection,
+    );
+    bindings.insert(
+      KeyCombo::new(alt_shift, VirtualKeyCode::Down),
+      Command::ShrinkSelection,
+    );
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::Z),
+      Command::Undo,
+    );
+    bindings
+      .insert(KeyCombo::new(ctrl_shift, VirtualKeyCode::Z), Command::Redo);
+    bindings.insert(
+      KeyCombo::new(ModifiersState::CTRL, VirtualKeyCode::V),
+      Command::Paste,
+    );
+    for (modifiers, key, command) in [
+      (ModifiersState::CTRL, VirtualKeyCode::C, Command::Copy),
+      (ModifiersState::CTRL, VirtualKeyCode::X, Command::Cut),
+      (ModifiersState::CTRL, VirtualKeyCode::A, Command::SelectAll),
+      (
+        ModifiersState::SHIFT,
+        VirtualKeyCode::Left,
+        Command::SelectLeft,
+      ),
+      (
+        ModifiersState::SHIFT,
+        VirtualKeyCode::Right,
+        Command::SelectRight,
+      ),
+      (
+        ModifiersState::CTRL,
+        VirtualKeyCode::Left,
+        Command::CursorWordLeft,
+      ),
+      (
+        ModifiersState::CTRL,
+        VirtualKeyCode::Right,
+        Command::CursorWordRight,
+      ),
+      (ctrl_shift, VirtualKeyCode::Left, Command::SelectWordLeft),
+      (ctrl_shift, VirtualKeyCode::Right, Command::SelectWordRight),
+    ]
+    .iter()
+    {
+      bindings.insert(KeyCombo::new(*modifiers, *key), *command);
+    }
+    bindings.insert(
+      KeyCombo::new(ctrl_alt, VirtualKeyCode::Up),
+      Command::AddCursorAbove,
+    );
+    bindings.insert(
+      KeyCombo::new(ctrl_alt, VirtualKeyCode::Down),
+      Command::AddCursorBelow,
+    );
+    bindings
+  }
+
+  /// Builds the default keymap with the user's bindings from the config
+  /// applied on top. Invalid bindings are skipped.
+  pub fn new(
+    overrides: &HashMap<String, String>,
+    layout: Option<Layout>,
+  ) -> Self {
+    let mut bindings = Self::defaults();
+    for (combo, id) in overrides {
+      let combo = match combo.parse::<KeyCombo>() {
+        Ok(combo) => combo,
+        Err(err) => {
+          tracing::warn!("invalid keybinding: {}", err);
+          continue;
+        }
+      };
+      if id.is_empty() {
+        bindings.remove(&combo);
+      } else if let Some(command) = Command::from_id(id) {
+        bindings.insert(combo, command);
+      } else {
+        tracing::warn!("unknown command '{}'", id);
+      }
+    }
+    Self { bindings, layout }
+  }
+
+  /// The command bound to the key at `scancode`, which the platform reports
+  /// as `key`, with `modifiers` held. Bindings to the place of the key come
+  /// before those to the character it types.
+  pub fn get(
+    &self,
+    modifiers: ModifiersState,
+    scancode: u32,
+    key: Option<VirtualKeyCode>,
+  ) -> Option<Command> {
+    let combo = |key| KeyCombo { modifiers, key };
+    if let Some(command) = self.bindings.get(&combo(Key::Scancode(scancode))) {
+      return Some(*command);
+    }
+    let key = match key.map(Key::from) {
+      Some(Key::Named(key)) => Some(Key::Named(key)),
+      reported => self
+        .layout
+        .as_ref()
+        .and_then(|layout| layout.char(scancode))
+        .map(Key::Char)
+        .or(reported),
+    }?;
+    self.bindings.get(&combo(key)).copied()
+  }
+
+  /// How `combo` is shown to the user, keys bound by their place named by
+  /// what they type in the keyboard layout, or the US one if unset.
+  pub fn describe(&self, combo: KeyCombo) -> String {
+    match &self.layout {
+      Some(layout) => Described(&combo, layout).to_string(),
+      None => Described(&combo, &Layout::us()).to_string(),
+    }
+  }
+
+  /// The shortest key combination triggering `command`, if any.
+  pub fn combo_for(&self, command: Command) -> Option<KeyCombo> {
+    self
+      .bindings
+      .iter()
+      .filter(|(_, bound)| **bound == command)
+      .map(|(combo, _)| *combo)
+      .min_by_key(|combo| {
+        let name = combo.to_string();
+        (name.len(), name)
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_key_combo() {
+    assert_eq!(
+      "ctrl+shift+p".parse::<KeyCombo>().unwrap(),
+      KeyCombo::new(
+        ModifiersState::CTRL | ModifiersState::SHIFT,
+        VirtualKeyCode::P
+      )
+    );
+    assert_eq!(
+      "Alt + F3".parse::<KeyCombo>().unwrap(),
+      KeyCombo::new(ModifiersState::ALT, VirtualKeyCode::F3)
+    );
+    assert!("ctrl+shift".parse::<KeyCombo>().is_err());
+    assert!("ctrl+a+b".parse::<KeyCombo>().is_err());
+    assert!("ctrl+foo".parse::<KeyCombo>().is_err());
+  }
+
+  #[test]
+  fn display_key_combo() {
+    for combo in &["ctrl+shift+p", "alt+up", "f12", "ctrl+super+escape"] {
+      assert_eq!(combo.parse::<KeyCombo>().unwrap().to_string(), *combo);
+    }
+    assert_eq!("Esc".parse::<KeyCombo>().unwrap().to_string(), "escape");
+  }
+
+  #[test]
+  fn overrides() {
+    let mut overrides = HashMap::new();
+    overrides.insert("ctrl+p".to_string(), "show-command-palette".to_string());
+    overrides.insert("ctrl+shift+i".to_string(), String::new());
+    let keymap = Keymap::new(&overrides, None);
+
+    assert_eq!(
+      keymap.get(ModifiersState::CTRL, 25, Some(VirtualKeyCode::P)),
+      Some(Command::ShowCommandPalette)
+    );
+    assert_eq!(
+      keymap.get(
+        ModifiersState::CTRL | ModifiersState::SHIFT,
+        23,
+        Some(VirtualKeyCode::I)
+      ),
+      None
+    );
+  }
+
+  #[test]
+  fn keys_by_layout() {
+    let mut overrides = HashMap::new();
+    overrides.insert("ctrl+ù".to_string(), "toggle-sidebar".to_string());
+    overrides.insert("alt+scancode:26".to_string(), "undo".to_string());
+    let azerty = Layout::named("azerty", &HashMap::new()).unwrap();
+    let keymap = Keymap::new(&overrides, Some(azerty));
+
+    // the key right of A types Z in AZERTY, whatever the platform says
+    assert_eq!(
+      keymap.get(ModifiersState::CTRL, 17, Some(VirtualKeyCode::W)),
+      Some(Command::Undo)
+    );
+    assert_eq!(
+      keymap.get(ModifiersState::CTRL, 40, None),
+      Some(Command::ToggleSidebar)
+    );
+    assert_eq!(
+      keymap.get(ModifiersState::ALT, 26, None),
+      Some(Command::Undo)
+    );
+    // named keys are found wherever they are
+    assert_eq!(
+      keymap.get(ModifiersState::empty(), 0, Some(VirtualKeyCode::F2)),
+      Some(Command::RenameSymbol)
+    );
+
+    let combo = "alt+scancode:26".parse::<KeyCombo>().unwrap();
+    assert_eq!(combo.to_string(), "alt+scancode:26");
+    assert_eq!(keymap.describe(combo), "alt+^");
+    assert_eq!(Keymap::new(&overrides, None).describe(combo), "alt+[");
+  }
+}
@@ -0,0 +1,325 @@
+//! Which language a file is written in, told by a modeline, its name or
+//! extension, or the interpreter its shebang runs. The language picks the
+//! grammar, the language servers and the settings of `[languages.<id>]`.
+
+use crate::config::LanguageConfig;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Lines at the start and the end of a file searched for a modeline.
+const MODELINE_LINES: usize = 5;
+
+/// The languages known without being configured, with their extensions,
+/// file names and interpreters.
+#[allow(clippy::type_complexity)]
+const BUILTIN: &[(&str, &[&str], &[&str], &[&str])] = &[
+  ("rust", &["rs"], &[], &[]),
+  ("python", &["py", "pyw", "pyi"], &[], &["python"]),
+  ("javascript", &["js", "mjs", "cjs"], &[], &["node"]),
+  (
+    "typescript",
+    &["ts", "mts", "cts"],
+    &[],
+    &["deno", "ts-node"],
+  ),
+  (
+    "shell",
+    &["sh", "bash", "zsh"],
+    &[".bashrc", ".bash_profile", ".zshrc", ".profile"],
+    &["sh", "bash", "zsh", "dash"],
+  ),
+  ("c", &["c", "h"], &[], &[]),
+  ("cpp", &["cpp", "cc", "cxx", "hpp", "hh", "hxx"], &[], &[]),
+  ("go", &["go"], &[], &[]),
+  ("java", &["java"], &[], &[]),
+  ("lua", &["lua"], &[], &["lua", "luajit"]),
+  ("ruby", &["rb"], &["Gemfile", "Rakefile"], &["ruby"]),
+  ("perl", &["pl", "pm"], &[], &["perl"]),
+  ("markdown", &["md", "markdown"], &[], &[]),
+  ("toml", &["toml"], &["Cargo.lock"], &[]),
+  ("json", &["json"], &[], &[]),
+  ("yaml", &["yml", "yaml"], &[], &[]),
+  ("html", &["html", "htm"], &[], &[]),
+  ("css", &["css"], &[], &[]),
+  (
+    "makefile",
+    &["mk"],
+    &["Makefile", "makefile", "GNUmakefile"],
+    &["make"],
+  ),
+  ("dockerfile", &[], &["Dockerfile"], &[]),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+struct Language {
+  id: String,
+  /// Without the leading dot.
+  extensions: Vec<String>,
+  file_names: Vec<String>,
+  /// Programs in the shebang, without a version like the `3` of `python3`.
+  interpreters: Vec<String>,
+}
+
+fn strings(list: &[&str]) -> Vec<String> {
+  list.iter().map(|item| item.to_string()).collect()
+}
+
+/// The language named by a vim or emacs modeline in `line`, as in
+/// `# vim: set ft=python:` or `// -*- mode: rust -*-`.
+fn modeline(line: &str) -> Option<String> {
+  if let Some(start) = line.find("-*-") {
+    let rest = &line[start + 3..];
+    let content = rest[..rest.find("-*-")?].trim();
+    let mode = if content.contains(':') {
+      content.split(';').find_map(|variable| {
+        let mut parts = variable.splitn(2, ':');
+        let name = parts.next()?;
+        Some(parts.next()?.trim()).filter(|_| name.trim() == "mode")
+      })?
+    } else {
+      content
+    };
+    return Some(mode.to_lowercase()).filter(|mode| !mode.is_empty());
+  }
+
+  let words = line.split_whitespace().collect::<Vec<_>>();
+  let start = words.iter().position(|word| {
+    ["vim:", "vi:", "ex:"].contains(word)
+      || ["vim:", "vi:", "ex:"]
+        .iter()
+        .any(|marker| word.starts_with(marker) && word.contains('='))
+  })?;
+  words[start..]
+    .iter()
+    .flat_map(|word| word.split(':'))
+    .find_map(|option| {
+      option
+        .strip_prefix("ft=")
+        .or_else(|| option.strip_prefix("filetype="))
+        .or_else(|| option.strip_prefix("syntax="))
+    })
+    .map(str::to_lowercase)
+    .filter(|name| !name.is_empty())
+}
+
+/// The program a `#!` line runs, looking through `env` and without a
+/// version at the end.
+fn interpreter(line: &str) -> Option<&str> {
+  let mut words = line.strip_prefix("#!")?.split_whitespace();
+  let mut program = words.next()?.rsplit('/').next()?;
+  if program == "env" {
+    program =
+      words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+  }
+  Some(program.trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == '.'))
+}
+
+/// The languages files can be detected as.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Languages {
+  /// The configured ones first so they take precedence.
+  languages: Vec<Language>,
+}
+
+impl Languages {
+  /// The built in languages along with the `configured` ones, which extend
+  /// a built in language of the same id.
+  pub fn new(configured: &HashMap<String, LanguageConfig>) -> Self {
+    let mut builtin = BUILTIN
+      .iter()
+      .map(|(id, extensions, file_names, interpreters)| Language {
+        id: id.to_string(),
+        extensions: strings(extensions),
+        file_names: strings(file_names),
+        interpreters: strings(interpreters),
+      })
+      .collect::<Vec<_>>();
+    let mut ids = configured.keys().collect::<Vec<_>>();
+    ids.sort();
+
+    let mut languages = vec![];
+    for id in ids {
+      let config = &configured[id];
+      let mut language = Language {
+        id: id.clone(),
+        extensions: config.extensions.clone(),
+        file_names: config.file_names.clone(),
+        interpreters: config.interpreters.clone(),
+      };
+      if let Some(i) = builtin.iter().position(|builtin| builtin.id == *id) {
+        let builtin = builtin.remove(i);
+        language.extensions.extend(builtin.extensions);
+        language.file_names.extend(builtin.file_names);
+        language.interpreters.extend(builtin.interpreters);
+      }
+      languages.push(language);
+    }
+    languages.extend(builtin);
+    Self { languages }
+  }
+
+  /// The ids of the languages, sorted.
+  pub fn ids(&self) -> Vec<&str> {
+    let mut ids = self
+      .languages
+      .iter()
+      .map(|language| language.id.as_str())
+      .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids
+  }
+
+  /// The id of the language a modeline names, which can also be one of
+  /// its extensions or interpreters like `sh` or `py`.
+  fn resolve(&self, name: String) -> String {
+    self
+      .languages
+      .iter()
+      .find(|language| language.id == name)
+      .or_else(|| {
+        self.languages.iter().find(|language| {
+          language.extensions.contains(&name)
+            || language.interpreters.contains(&name)
+        })
+      })
+      .map_or(name, |language| language.id.clone())
+  }
+
+  /// The language of the file at `path` starting with `lines`, which can
+  /// be all of its lines. A modeline comes first, then the file name, the
+  /// extension and the shebang.
+  pub fn detect(
+    &self,
+    path: Option<&Path>,
+    lines: &[String],
+  ) -> Option<String> {
+    let head = &lines[..lines.len().min(MODELINE_LINES)];
+    let tail = &lines[lines.len().saturating_sub(MODELINE_LINES)..];
+    if let Some(name) = head.iter().chain(tail).find_map(|line| modeline(line))
+    {
+      return Some(self.resolve(name));
+    }
+
+    let file_name = path
+      .and_then(Path::file_name)
+      .and_then(|name| name.to_str());
+    let extension = path
+      .and_then(Path::extension)
+      .and_then(|extension| extension.to_str());
+    let interpreter = lines.first().and_then(|line| interpreter(line));
+    let find = |matches: &dyn Fn(&Language) -> bool| {
+      self.languages.iter().find(|language| matches(language))
+    };
+    file_name
+      .and_then(|name| {
+        find(&|language| language.file_names.iter().any(|n| n == name))
+      })
+      .or_else(|| {
+        extension.and_then(|extension| {
+          find(&|language| language.extensions.iter().any(|e| e == extension))
+        })
+      })
+      .or_else(|| {
+        interpreter.and_then(|program| {
+          find(&|language| {
+            language.interpreters.iter().any(|name| name == program)
+          })
+        })
+      })
+      .map(|language| language.id.clone())
+  }
+
+  /// The language of the file at `path`, reading only its first lines.
+  pub fn detect_file(&self, path: &Path) -> Option<String> {
+    let lines = std::fs::File::open(path)
+      .map(|file| {
+        BufReader::new(file)
+          .lines()
+          .take(MODELINE_LINES)
+          .filter_map(Result::ok)
+          .collect::<Vec<_>>()
+      })
+      .unwrap_or_default();
+    self.detect(Some(path), &lines)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(text: &str) -> Vec<String> {
+    text.lines().map(String::from).collect()
+  }
+
+  #[test]
+  fn detects_languages() {
+    let languages = Languages::new(&HashMap::new());
+    let detect = |path: &str, text: &str| {
+      languages.detect(Some(Path::new(path)), &lines(text))
+    };
+    assert_eq!(detect("src/main.rs", "").as_deref(), Some("rust"));
+    assert_eq!(detect("Makefile", "").as_deref(), Some("makefile"));
+    assert_eq!(
+      detect("run", "#!/usr/bin/env python3\n").as_deref(),
+      Some("python")
+    );
+    assert_eq!(
+      detect("build", "#!/bin/bash -e\n").as_deref(),
+      Some("shell")
+    );
+    assert_eq!(detect("notes", "plain text"), None);
+    assert_eq!(languages.detect(None, &[]), None);
+
+    // modelines win over the extension, at either end of the file
+    assert_eq!(
+      detect("a.txt", "x\n# vim: set ft=sh:\n").as_deref(),
+      Some("shell")
+    );
+    assert_eq!(
+      detect("a.rs", "// vim:ft=python").as_deref(),
+      Some("python")
+    );
+    assert_eq!(
+      detect("a.h", "/* -*- indent-tabs-mode: nil; mode: cpp -*- */")
+        .as_deref(),
+      Some("cpp")
+    );
+    assert_eq!(detect("a", "# -*- ruby -*-").as_deref(), Some("ruby"));
+    let long = format!("{}\n# vim: ft=zig", "\n".repeat(20));
+    assert_eq!(detect("a", &long).as_deref(), Some("zig"));
+    // only a word on its own starts a vim modeline
+    assert_eq!(
+      detect("a.rs", "let novim: u8 = ft=1;").as_deref(),
+      Some("rust")
+    );
+  }
+
+  #[test]
+  fn configured_languages() {
+    let config = |extensions: &[&str], interpreters: &[&str]| LanguageConfig {
+      extensions: strings(extensions),
+      interpreters: strings(interpreters),
+      ..LanguageConfig::default()
+    };
+    let mut configured = HashMap::new();
+    configured.insert(String::from("cpp"), config(&["h", "ipp"], &[]));
+    configured.insert(String::from("nix"), config(&["nix"], &["nix-shell"]));
+    let languages = Languages::new(&configured);
+    let detect = |path: &str, text: &str| {
+      languages.detect(Some(Path::new(path)), &lines(text))
+    };
+    // configured extensions take precedence and extend the built in ones
+    assert_eq!(detect("a.h", "").as_deref(), Some("cpp"));
+    assert_eq!(detect("a.hpp", "").as_deref(), Some("cpp"));
+    assert_eq!(detect("a.c", "").as_deref(), Some("c"));
+    let ids = languages.ids();
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(ids.contains(&"nix") && ids.contains(&"rust"));
+    assert_eq!(
+      detect("shell", "#!/usr/bin/env nix-shell").as_deref(),
+      Some("nix")
+    );
+  }
+}
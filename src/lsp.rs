@@ -0,0 +1,741 @@
+use crate::config::LanguageServerConfig;
+use crate::processes::{Kind, Processes};
+use crate::text;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A server running at least this long starts over with the initial backoff
+/// when it crashes.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+const MAX_LOG_LINES: usize = 5000;
+
+/// Called from the server threads whenever the status of a server changed.
+pub type Notify = Arc<dyn Fn() + Send + Sync>;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Status {
+  Starting,
+  Ready,
+  Crashed,
+}
+
+impl Status {
+  pub fn name(self) -> &'static str {
+    match self {
+      Status::Starting => "starting",
+      Status::Ready => "ready",
+      Status::Crashed => "crashed",
+    }
+  }
+}
+
+/// Reads a single message, `None` once the stream ended.
+pub fn read_message(
+  reader: &mut impl BufRead,
+) -> Result<Option<Value>, anyhow::Error> {
+  let mut length = None;
+  loop {
+    let mut header = String::new();
+    if reader.read_line(&mut header)? == 0 {
+      return Ok(None);
+    }
+    let header = header.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some(value) = header.strip_prefix("Content-Length:") {
+      length = Some(value.trim().parse::<usize>()?);
+    }
+  }
+
+  let length =
+    length.ok_or_else(|| anyhow::anyhow!("message without Content-Length"))?;
+  let mut content = vec![0; length];
+  reader.read_exact(&mut content)?;
+  Ok(Some(serde_json::from_slice(&content)?))
+}
+
+pub fn write_message(
+  writer: &mut impl Write,
+  message: &Value,
+) -> Result<(), anyhow::Error> {
+  let content = serde_json::to_string(message)?;
+  write!(
+    writer,
+    "Content-Length: {}\r\n\r\n{}",
+    content.len(),
+    content
+  )?;
+  writer.flush()?;
+  Ok(())
+}
+
+struct Shared {
+  status: Status,
+  log: VecDeque<String>,
+  child: Option<Child>,
+  /// Id of the running process, until it was waited for.
+  pid: Option<u32>,
+  stdin: Option<ChildStdin>,
+  /// Id of the next request, the `initialize` request uses 0.
+  next_id: u64,
+  /// Responses to requests which weren't picked up yet.
+  responses: HashMap<u64, Result<Value, String>>,
+  /// The ids of the `workspace/applyEdit` requests with their
+  /// `WorkspaceEdit`, which weren't picked up yet.
+  applied_edits: Vec<(Value, Value)>,
+  /// Set when the server got killed on purpose and should be started again
+  /// right away.
+  restart: bool,
+  /// Set once the server isn't needed anymore.
+  stopped: bool,
+}
+
+impl Shared {
+  fn log(&mut self, line: String) {
+    if self.log.len() == MAX_LOG_LINES {
+      self.log.pop_front();
+    }
+    self.log.push_back(line);
+  }
+
+  fn send(&mut self, message: &Value) -> Result<(), anyhow::Error> {
+    match &mut self.stdin {
+      Some(stdin) => write_message(stdin, message),
+      None => anyhow::bail!("server isn't running"),
+    }
+  }
+}
+
+/// The URI of the file at `path`.
+pub fn path_to_uri(path: &Path) -> String {
+  let mut uri = String::from("file://");
+  for byte in path.to_string_lossy().bytes() {
+    match byte {
+      b'A'..=b'Z'
+      | b'a'..=b'z'
+      | b'0'..=b'9'
+      | b'/'
+      | b'-'
+      | b'_'
+      | b'.'
+      | b'~' => uri.push(byte as char),
+      _ => uri.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  uri
+}
+
+/// The path of a `file://` URI.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+  let path = text::percent_decode(uri.strip_prefix("file://")?)?;
+  String::from_utf8(path).ok().map(PathBuf::from)
+}
+
+/// A language server process that is restarted with an exponential backoff
+/// whenever it exits unexpectedly.
+pub struct LanguageServer {
+  pub name: String,
+  shared: Arc<Mutex<Shared>>,
+  /// Wakes up the supervisor waiting to start a crashed server again.
+  wake: Arc<Condvar>,
+}
+
+impl LanguageServer {
+  fn start(
+    name: String,
+    config: LanguageServerConfig,
+    root: PathBuf,
+    notify: Notify,
+    processes: Processes,
+  ) -> Self {
+    let shared = Arc::new(Mutex::new(Shared {
+      status: Status::Starting,
+      log: VecDeque::new(),
+      child: None,
+      pid: None,
+      stdin: None,
+      next_id: 1,
+      responses: HashMap::new(),
+      applied_edits: vec![],
+      restart: false,
+      stopped: false,
+    }));
+
+    let wake = Arc::new(Condvar::new());
+
+    let thread_shared = Arc::clone(&shared);
+    let thread_wake = Arc::clone(&wake);
+    let thread_name = name.clone();
+    std::thread::spawn(move || {
+      supervise(
+        &thread_name,
+        &config,
+        &root,
+        &thread_shared,
+        &thread_wake,
+        &notify,
+        &processes,
+      )
+    });
+
+    Self { name, shared, wake }
+  }
+
+  pub fn status(&self) -> Status {
+    self.shared.lock().unwrap().status
+  }
+
+  pub fn log(&self) -> String {
+    self.shared.lock().unwrap().log.make_contiguous().join("\n")
+  }
+
+  /// Sends a request once the server is ready, returning its id to pick up
+  /// the response with [`LanguageServer::take_response`].
+  pub fn request(
+    &self,
+    method: &str,
+    params: Value,
+  ) -> Result<u64, anyhow::Error> {
+    let mut shared = self.shared.lock().unwrap();
+    if shared.status != Status::Ready {
+      anyhow::bail!("{} is {}", self.name, shared.status.name());
+    }
+    let id = shared.next_id;
+    shared.next_id += 1;
+    shared.send(&json!({
+      "jsonrpc": "2.0",
+      "id": id,
+      "method": method,
+      "params": params,
+    }))?;
+    Ok(id)
+  }
+
+  pub fn notification(
+    &self,
+    method: &str,
+    params: Value,
+  ) -> Result<(), anyhow::Error> {
+    let mut shared = self.shared.lock().unwrap();
+    if shared.status != Status::Ready {
+      anyhow::bail!("{} is {}", self.name, shared.status.name());
+    }
+    shared.send(&json!({
+      "jsonrpc": "2.0",
+      "method": method,
+      "params": params,
+    }))
+  }
+
+  /// The result of the request with `id`, or its error message.
+  pub fn take_response(&self, id: u64) -> Option<Result<Value, String>> {
+    self.shared.lock().unwrap().responses.remove(&id)
+  }
+
+  /// The edits the server asked to apply since last called, like those of
+  /// a command it ran, with the ids to answer them with
+  /// [`LanguageServer::answer_applied_edit`].
+  pub fn take_applied_edits(&self) -> Vec<(Value, Value)> {
+    std::mem::take(&mut self.shared.lock().unwrap().applied_edits)
+  }
+
+  /// Tells the server whether the edit it asked to apply with `id` was
+  /// applied, or why not.
+  pub fn answer_applied_edit(&self, id: Value, result: Result<(), String>) {
+    let result = match result {
+      Ok(()) => json!({ "applied": true }),
+      Err(reason) => json!({ "applied": false, "failureReason": reason }),
+    };
+    let mut shared = self.shared.lock().unwrap();
+    let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+    if let Err(err) = shared.send(&response) {
+      shared.log(format!("error: {}", err));
+    }
+  }
+
+  pub fn restart(&self) {
+    let mut shared = self.shared.lock().unwrap();
+    shared.log("restart requested".to_string());
+    shared.restart = true;
+    if let Some(child) = &mut shared.child {
+      let _ = child.kill();
+    }
+    self.wake.notify_all();
+  }
+}
+
+impl LanguageServer {
+  /// Asks the server to exit, without starting it again.
+  pub fn shut_down(&self) {
+    let mut shared = self.shared.lock().unwrap();
+    shared.stopped = true;
+    if shared.status == Status::Ready {
+      let id = shared.next_id;
+      shared.next_id += 1;
+      let shutdown = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "shutdown",
+      });
+      let exit = json!({ "jsonrpc": "2.0", "method": "exit" });
+      if let Err(err) = shared.send(&shutdown).and_then(|_| shared.send(&exit))
+      {
+        shared.log(format!("error: {}", err));
+      }
+    }
+  }
+}
+
+impl Drop for LanguageServer {
+  fn drop(&mut self) {
+    let mut shared = self.shared.lock().unwrap();
+    shared.stopped = true;
+    if let Some(child) = &mut shared.child {
+      let _ = child.kill();
+    }
+    self.wake.notify_all();
+  }
+}
+
+fn supervise(
+  name: &str,
+  config: &LanguageServerConfig,
+  root: &Path,
+  shared: &Arc<Mutex<Shared>>,
+  wake: &Condvar,
+  notify: &Notify,
+  processes: &Processes,
+) {
+  let set_status = |status: Status, message: String| {
+    let mut shared = shared.lock().unwrap();
+    shared.status = status;
+    shared.log(message);
+    drop(shared);
+    notify();
+  };
+
+  let mut backoff = INITIAL_BACKOFF;
+  while !shared.lock().unwrap().stopped {
+    set_status(Status::Starting, format!("starting {}", config.command));
+    let started = Instant::now();
+
+    match run(name, config, root, shared, &set_status, notify, processes) {
+      Ok(Some(status)) => {
+        shared
+          .lock()
+          .unwrap()
+          .log(format!("server exited: {}", status));
+      }
+      Ok(None) => {}
+      Err(err) => shared.lock().unwrap().log(format!("error: {}", err)),
+    }
+
+    let mut state = shared.lock().unwrap();
+    state.stdin = None;
+    state.responses.clear();
+    // the server is left running if talking to it failed
+    if let Some(mut child) = state.child.take() {
+      let _ = child.kill();
+      let _ = child.wait();
+    }
+    if let Some(pid) = state.pid.take() {
+      processes.exited(pid);
+    }
+    if state.stopped {
+      break;
+    }
+    if std::mem::replace(&mut state.restart, false) {
+      backoff = INITIAL_BACKOFF;
+      continue;
+    }
+    drop(state);
+
+    if started.elapsed() > STABLE_AFTER {
+      backoff = INITIAL_BACKOFF;
+    }
+    set_status(
+      Status::Crashed,
+      format!("restarting in {:.1}s", backoff.as_secs_f32()),
+    );
+    let state = shared.lock().unwrap();
+    let (mut state, _) = wake
+      .wait_timeout_while(state, backoff, |state| {
+        !state.restart && !state.stopped
+      })
+      .unwrap();
+    backoff = if std::mem::replace(&mut state.restart, false) {
+      INITIAL_BACKOFF
+    } else {
+      (backoff * 2).min(MAX_BACKOFF)
+    };
+  }
+}
+
+/// Runs the server until it exits, returning its exit status.
+fn run(
+  name: &str,
+  config: &LanguageServerConfig,
+  root: &Path,
+  shared: &Arc<Mutex<Shared>>,
+  set_status: &impl Fn(Status, String),
+  notify: &Notify,
+  processes: &Processes,
+) -> Result<Option<std::process::ExitStatus>, anyhow::Error> {
+  let mut child = processes.spawn(
+    Command::new(&config.command)
+      .args(&config.args)
+      .current_dir(root)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped()),
+    Kind::LanguageServer,
+    name,
+  )?;
+  let stdin = child.stdin.take().unwrap();
+  let mut stdout = BufReader::new(child.stdout.take().unwrap());
+  let stderr = child.stderr.take().unwrap();
+  {
+    let mut shared = shared.lock().unwrap();
+    shared.pid = Some(child.id());
+    shared.child = Some(child);
+    shared.stdin = Some(stdin);
+  }
+  let send = |message: &Value| shared.lock().unwrap().send(message);
+
+  let stderr_shared = Arc::clone(shared);
+  std::thread::spawn(move || {
+    for line in BufReader::new(stderr).lines() {
+      match line {
+        Ok(line) => stderr_shared.lock().unwrap().log(line),
+        Err(_) => break,
+      }
+    }
+  });
+
+  send(&json!({
+    "jsonrpc": "2.0",
+    "id": 0,
+    "method": "initialize",
+    "params": {
+      "processId": std::process::id(),
+      "rootUri": path_to_uri(root),
+      "capabilities": {
+        "workspace": {
+          "applyEdit": true,
+          "workspaceEdit": { "documentChanges": true },
+        },
+        "textDocument": {
+          "codeAction": {
+            "codeActionLiteralSupport": {
+              "codeActionKind": {
+                "valueSet": [
+                  "quickfix",
+                  "refactor",
+                  "refactor.extract",
+                  "refactor.inline",
+                  "refactor.rewrite",
+                  "source",
+                  "source.organizeImports",
+                ],
+              },
+            },
+            "isPreferredSupport": true,
+            "disabledSupport": true,
+            "dataSupport": true,
+            "resolveSupport": { "properties": ["edit"] },
+          },
+        },
+      },
+    },
+  }))?;
+
+  while let Some(message) = read_message(&mut stdout)? {
+    if message["id"] == 0 && message.get("result").is_some() {
+      send(&json!({
+        "jsonrpc": "2.0",
+        "method": "initialized",
+        "params": {},
+      }))?;
+      set_status(Status::Ready, "initialized".to_string());
+    } else if let (Some(id), None) =
+      (message["id"].as_u64(), message.get("method"))
+    {
+      let response = match message.get("error") {
+        Some(error) => Err(
+          error["message"]
+            .as_str()
+            .map_or_else(|| error.to_string(), String::from),
+        ),
+        None => Ok(message["result"].clone()),
+      };
+      let mut shared = shared.lock().unwrap();
+      if let Err(error) = &response {
+        shared.log(format!("error: {}", error));
+      }
+      shared.responses.insert(id, response);
+      drop(shared);
+      notify();
+    } else if let Some(error) = message.get("error") {
+      shared.lock().unwrap().log(format!("error: {}", error));
+    } else if message["method"] == "workspace/applyEdit" {
+      // answered by the main thread once it applied the edit
+      shared
+        .lock()
+        .unwrap()
+        .applied_edits
+        .push((message["id"].clone(), message["params"]["edit"].clone()));
+      notify();
+    } else if message["method"] == "window/logMessage" {
+      if let Some(text) = message["params"]["message"].as_str() {
+        shared.lock().unwrap().log(text.to_string());
+      }
+    } else if message.get("id").is_some() {
+      // the server waits for an answer to every request
+      send(&unhandled_response(&message))?;
+    }
+  }
+
+  let child = shared.lock().unwrap().child.take();
+  match child {
+    Some(mut child) => Ok(Some(child.wait()?)),
+    None => Ok(None),
+  }
+}
+
+/// The answer to a request of the server which isn't handled otherwise,
+/// a `null` result where that is a valid answer.
+fn unhandled_response(request: &Value) -> Value {
+  let id = &request["id"];
+  match request["method"].as_str().unwrap_or_default() {
+    "workspace/configuration" => {
+      let items = request["params"]["items"].as_array().map_or(0, Vec::len);
+      json!({ "jsonrpc": "2.0", "id": id, "result": vec![Value::Null; items] })
+    }
+    "client/registerCapability"
+    | "client/unregisterCapability"
+    | "window/workDoneProgress/create"
+    | "window/showMessageRequest" => {
+      json!({ "jsonrpc": "2.0", "id": id, "result": null })
+    }
+    method => json!({
+      "jsonrpc": "2.0",
+      "id": id,
+      "error": {
+        "code": -32601,
+        "message": format!("unhandled method {}", method),
+      },
+    }),
+  }
+}
+
+/// A symbol a `workspace/symbol` request found, at a zero based line and
+/// UTF-16 column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkspaceSymbol {
+  pub name: String,
+  /// The symbol it is declared in.
+  pub container: Option<String>,
+  pub path: PathBuf,
+  pub line: usize,
+  pub character: usize,
+}
+
+/// The symbols of a `workspace/symbol` response which are in a file.
+pub fn parse_workspace_symbols(result: &Value) -> Vec<WorkspaceSymbol> {
+  let symbols = match result.as_array() {
+    Some(symbols) => symbols,
+    None => return vec![],
+  };
+  symbols
+    .iter()
+    .filter_map(|symbol| {
+      let location = &symbol["location"];
+      let path = uri_to_path(location["uri"].as_str()?)?;
+      // a workspace symbol may leave out the range, to be resolved later
+      let start = &location["range"]["start"];
+      let number = |value: &Value| value.as_u64().unwrap_or(0) as usize;
+      Some(WorkspaceSymbol {
+        name: symbol["name"].as_str()?.to_string(),
+        container: symbol["containerName"]
+          .as_str()
+          .filter(|name| !name.is_empty())
+          .map(String::from),
+        path,
+        line: number(&start["line"]),
+        character: number(&start["character"]),
+      })
+    })
+    .collect()
+}
+
+/// The language servers for the files which are open.
+pub struct LanguageServers {
+  configs: HashMap<String, LanguageServerConfig>,
+  root: PathBuf,
+  notify: Notify,
+  processes: Processes,
+  pub servers: Vec<LanguageServer>,
+}
+
+impl LanguageServers {
+  pub fn new(
+    configs: HashMap<String, LanguageServerConfig>,
+    root: PathBuf,
+    notify: Notify,
+    processes: Processes,
+  ) -> Self {
+    Self {
+      configs,
+      root,
+      notify,
+      processes,
+      servers: vec![],
+    }
+  }
+
+  /// The names and configs of the servers handling `path`, a file in
+  /// `language`.
+  fn configs_for<'a>(
+    &'a self,
+    path: &Path,
+    language: Option<&str>,
+  ) -> impl Iterator<Item = (&'a String, &'a LanguageServerConfig)> {
+    let extension = path
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .unwrap_or_default()
+      .to_string();
+    let language = language.map(String::from);
+    self.configs.iter().filter(move |(_, config)| {
+      config.extensions.contains(&extension)
+        || matches!(&language, Some(language) if config.languages.contains(language))
+    })
+  }
+
+  /// Starts the server responsible for `path`, a file in `language`,
+  /// unless it is already running.
+  pub fn start_for(&mut self, path: &Path, language: Option<&str>) {
+    let missing = self
+      .configs_for(path, language)
+      .filter(|(name, _)| {
+        !self.servers.iter().any(|server| &server.name == *name)
+      })
+      .map(|(name, config)| (name.clone(), config.clone()))
+      .collect::<Vec<_>>();
+    for (name, config) in missing {
+      self.servers.push(LanguageServer::start(
+        name,
+        config,
+        self.root.clone(),
+        Arc::clone(&self.notify),
+        self.processes.clone(),
+      ));
+    }
+  }
+
+  /// The running server responsible for `path`, a file in `language`.
+  pub fn server_for(
+    &self,
+    path: &Path,
+    language: Option<&str>,
+  ) -> Option<&LanguageServer> {
+    self.configs_for(path, language).find_map(|(name, _)| {
+      self.servers.iter().find(|server| &server.name == name)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn message_framing() {
+    let message = json!({ "jsonrpc": "2.0", "id": 1, "result": null });
+    let mut buffer = vec![];
+    write_message(&mut buffer, &message).unwrap();
+    write_message(&mut buffer, &message).unwrap();
+
+    let mut reader = std::io::Cursor::new(buffer);
+    assert_eq!(read_message(&mut reader).unwrap(), Some(message.clone()));
+    assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+  }
+
+  #[test]
+  fn unhandled_requests() {
+    let request = |method: &str, params: Value| json!({ "jsonrpc": "2.0", "id": 3, "method": method, "params": params });
+    let items = json!({ "items": [{ "section": "a" }, { "section": "b" }] });
+    assert_eq!(
+      unhandled_response(&request("workspace/configuration", items)),
+      json!({ "jsonrpc": "2.0", "id": 3, "result": [null, null] })
+    );
+    assert_eq!(
+      unhandled_response(&request("window/workDoneProgress/create", json!({}))),
+      json!({ "jsonrpc": "2.0", "id": 3, "result": null })
+    );
+    let response = unhandled_response(&request("workspace/foo", json!({})));
+    assert_eq!(response["error"]["code"], -32601);
+    assert_eq!(response.get("result"), None);
+  }
+
+  #[test]
+  fn uris() {
+    let path = Path::new("/tmp/a b.rs");
+    assert_eq!(path_to_uri(path), "file:///tmp/a%20b.rs");
+    assert_eq!(uri_to_path(&path_to_uri(path)).as_deref(), Some(path));
+    assert_eq!(
+      uri_to_path("file:///tmp/a%20b%C3%A4.rs"),
+      Some(PathBuf::from("/tmp/a bä.rs"))
+    );
+    assert_eq!(uri_to_path("untitled:1"), None);
+  }
+
+  #[test]
+  fn workspace_symbols() {
+    let result = json!([
+      {
+        "name": "Config",
+        "kind": 23,
+        "containerName": "config",
+        "location": {
+          "uri": "file:///tmp/config.rs",
+          "range": {
+            "start": { "line": 4, "character": 11 },
+            "end": { "line": 4, "character": 17 },
+          },
+        },
+      },
+      { "name": "main", "kind": 12, "location": { "uri": "untitled:1" } },
+      { "name": "run", "kind": 12, "location": { "uri": "file:///tmp/a.rs" } },
+    ]);
+    assert_eq!(
+      parse_workspace_symbols(&result),
+      vec![
+        WorkspaceSymbol {
+          name: String::from("Config"),
+          container: Some(String::from("config")),
+          path: PathBuf::from("/tmp/config.rs"),
+          line: 4,
+          character: 11,
+        },
+        WorkspaceSymbol {
+          name: String::from("run"),
+          container: None,
+          path: PathBuf::from("/tmp/a.rs"),
+          line: 0,
+          character: 0,
+        },
+      ]
+    );
+    assert!(parse_workspace_symbols(&Value::Null).is_empty());
+  }
+}
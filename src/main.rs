@@ -1,92 +1,424 @@
 #![deny(warnings)]
 
+mod brackets;
+mod calc;
+mod cli;
+mod code_actions;
+mod colors;
+mod commands;
+mod completion;
+mod config;
+mod diff;
+mod documents;
+mod editor;
+mod folding;
+mod fuzzy;
+mod grammars;
+mod highlight;
+mod instance;
+mod keyboard;
+mod keymap;
+mod languages;
+mod lsp;
+mod markers;
+mod momentum;
+mod open_with;
+mod outline;
+mod pinch;
+mod plugins;
+mod power;
+mod processes;
+mod recent;
+mod recovery;
+mod rename;
 mod renderer;
+mod scm;
+mod search;
+mod segments;
+mod session;
+mod shaping;
+mod structure;
+mod tasks;
+mod text;
+mod transform;
+mod viewport;
+mod watcher;
+mod wrap;
 
-use crate::renderer::input::TextInput;
+use crate::config::Config;
 use std::collections::HashMap;
+use std::io::Read;
 use std::path::PathBuf;
 use winit::dpi::PhysicalPosition;
-use winit::event::{ElementState, MouseScrollDelta, WindowEvent};
+use winit::event::{
+  ElementState, Event, ModifiersState, MouseScrollDelta, StartCause,
+  WindowEvent,
+};
+use winit::event_loop::ControlFlow;
+use winit::window::WindowId;
+
+/// Events sent to the event loop from other threads.
+#[derive(Clone, Debug)]
+enum UserEvent {
+  ConfigChanged,
+  /// A language server or task changed, redraw to show it.
+  BackgroundChanged,
+  /// Files sent by another process, see [`instance::send`].
+  OpenFiles(Vec<cli::FileArg>),
+}
 
 fn main() -> Result<(), anyhow::Error> {
-  let args: Vec<String> = std::env::args().collect();
-
-  let file = args
-    .get(1)
-    .ok_or_else(|| anyhow::anyhow!("no file provided"))?;
-  let filepath = std::path::PathBuf::from(file);
-  if !filepath.exists() {
-    anyhow::bail!("path doesn't exist");
+  // Spans are emitted for layout and rendering; set DEVCODE_LOG=trace to see
+  // how long each of them takes.
+  tracing_subscriber::fmt()
+    .with_env_filter(tracing_subscriber::EnvFilter::from_env("DEVCODE_LOG"))
+    .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+    .init();
+
+  let args = match cli::Args::parse(std::env::args().skip(1)) {
+    Ok(args) => args,
+    Err(err) => {
+      eprintln!("{}", err);
+      std::process::exit(2);
+    }
+  };
+
+  if args.help {
+    println!("{}", cli::USAGE);
+    return Ok(());
+  }
+  if args.register {
+    return open_with::register();
   }
-  if !filepath.is_file() {
-    anyhow::bail!("path isn't a file");
+  if args.open && instance::send(&args.files) {
+    return Ok(());
   }
+  // files opened from the OS get the workspace of their repository, as the
+  // working directory is meaningless then
+  let workspace = match args.files.first() {
+    Some(file) if args.open => {
+      instance::workspace_for(&file.path.canonicalize()?)
+    }
+    _ => std::path::Path::new("./").canonicalize()?,
+  };
 
-  let font = get_font(args.get(2))?;
+  let buffers = if let Some((left, right)) = &args.diff {
+    let text = diff::unified(
+      &std::fs::read_to_string(left)?,
+      &std::fs::read_to_string(right)?,
+    );
+    let name = format!("{} <-> {}", left.display(), right.display());
+    vec![renderer::Buffer::Diff { name, text }]
+  } else {
+    args
+      .files
+      .iter()
+      .map(|file| open_buffer(file, args.language.as_ref()))
+      .collect::<Result<_, _>>()?
+  };
+
+  let mut config = Config::load().unwrap_or_else(|err| {
+    tracing::warn!("failed to load config: {}", err);
+    Config::default()
+  });
+  if let Some(font) = &args.font {
+    config.font = Some(font.clone());
+  }
+  let (font, ligatures) = get_font(config.font.as_ref())?;
+  config::set_gamma_correct(config.text.gamma_correct);
 
-  let event_loop = winit::event_loop::EventLoop::new();
-  let mut ren = futures::executor::block_on(async {
-    renderer::Renderer::new(&event_loop, font, filepath).await
+  let event_loop = winit::event_loop::EventLoop::with_user_event();
+  watcher::watch(
+    Config::path(),
+    event_loop.create_proxy(),
+    UserEvent::ConfigChanged,
+  );
+  let proxy = std::sync::Mutex::new(event_loop.create_proxy());
+  let notify: lsp::Notify = std::sync::Arc::new(move || {
+    let _ = proxy
+      .lock()
+      .unwrap()
+      .send_event(UserEvent::BackgroundChanged);
+  });
+  let ren = futures::executor::block_on(async {
+    renderer::Renderer::new(
+      &event_loop,
+      font,
+      std::rc::Rc::new(ligatures),
+      buffers,
+      workspace.clone(),
+      config,
+      notify,
+      documents::Documents::default(),
+      true,
+    )
+    .await
   })?;
+  let instance = instance::Instance::listen(
+    &workspace,
+    event_loop.create_proxy(),
+    UserEvent::OpenFiles,
+  )
+  .map_err(|err| tracing::warn!("failed to listen for files: {}", err))
+  .ok();
 
   ren.window.request_redraw();
 
+  // files sent by other processes open in the window focused last
+  let mut focused = ren.window.id();
+  let mut windows = HashMap::<WindowId, renderer::Renderer>::new();
+  windows.insert(focused, ren);
   let mut mouse_pos = PhysicalPosition::new(0.0f64, 0.0f64);
+  let mut modifiers = ModifiersState::empty();
+  // whether the last key pressed ran a command, the characters it types
+  // aren't text then
+  let mut shortcut_pressed = false;
 
-  event_loop.run(move |event, _, control_flow| match event {
-    winit::event::Event::WindowEvent { event, .. } => match event {
-      WindowEvent::Resized(size) => {
-        ren.resize(size.cast());
+  event_loop.run(move |event, target, control_flow| match event {
+    Event::WindowEvent { event, window_id } => {
+      let ren = match windows.get_mut(&window_id) {
+        Some(ren) => ren,
+        None => return,
+      };
+      match event {
+        WindowEvent::Resized(size) => {
+          ren.resize(size.cast());
+          ren.window.request_redraw();
+        }
+        WindowEvent::ScaleFactorChanged {
+          scale_factor,
+          new_inner_size,
+        } => ren.set_scale_factor(scale_factor, *new_inner_size),
+        WindowEvent::CloseRequested => ren.request_quit(),
+        WindowEvent::MouseWheel { delta, phase, .. } => {
+          match delta {
+            MouseScrollDelta::LineDelta(_, y) if modifiers.ctrl() => {
+              ren.zoom_lines(y, mouse_pos);
+            }
+            MouseScrollDelta::PixelDelta(delta) if modifiers.ctrl() => {
+              ren.zoom_lines(delta.y as f32 / ren.font_height, mouse_pos);
+            }
+            MouseScrollDelta::LineDelta(x, y) => {
+              // shift turns the wheel sideways, scrolling down goes right
+              let (x, y) = if modifiers.shift() { (-y, x) } else { (x, y) };
+              ren.scroll_lines(x, y, mouse_pos);
+            }
+            MouseScrollDelta::PixelDelta(delta) => {
+              ren.scroll_pixels(delta, phase, mouse_pos);
+            }
+          }
+          ren.window.request_redraw();
+        }
+        WindowEvent::ModifiersChanged(state) => modifiers = state,
+        WindowEvent::KeyboardInput { input, .. }
+          if input.state == ElementState::Pressed =>
+        {
+          ren.input_received();
+          let key = input.virtual_keycode;
+          shortcut_pressed = false;
+          // AltGr is reported as ctrl+alt and types characters instead
+          if modifiers.alt()
+            && !modifiers.ctrl()
+            && key.map_or(false, |key| ren.input_mnemonic(key))
+          {
+            shortcut_pressed = true;
+          } else if let Some(command) =
+            ren.keymap.get(modifiers, input.scancode, key)
+          {
+            shortcut_pressed = true;
+            ren.execute(command);
+          } else if let Some(key) = key {
+            ren.input_special(key);
+          }
+        }
+        // ctrl combinations are shortcuts and produce control characters which
+        // shouldn't end up in the text, AltGr is reported as ctrl+alt though.
+        // Text typed by dead keys and input methods comes as characters too
+        WindowEvent::ReceivedCharacter(_)
+          if shortcut_pressed || (modifiers.ctrl() && !modifiers.alt()) => {}
+        WindowEvent::ReceivedCharacter(ch) => {
+          ren.input_received();
+          ren.input_char(ch);
+        }
+        WindowEvent::CursorMoved { position, .. } => {
+          mouse_pos = position;
+          ren.mouse_moved(position);
+        }
+        WindowEvent::CursorLeft { .. } => ren.mouse_left(),
+        WindowEvent::Touch(touch) => ren.touch(touch),
+        WindowEvent::Focused(true) => {
+          focused = window_id;
+          ren.focus_gained();
+          ren.check_files();
+        }
+        WindowEvent::Focused(false) => ren.focus_lost(),
+        WindowEvent::MouseInput { state, .. } => {
+          ren.click(mouse_pos, state);
+          ren.window.request_redraw();
+        }
+        _ => {}
+      }
+    }
+    Event::UserEvent(UserEvent::ConfigChanged) => match Config::load() {
+      Ok(mut new_config) => {
+        // a font given on the command line takes precedence
+        if let Some(font) = &args.font {
+          new_config.font = Some(font.clone());
+        }
+        for ren in windows.values_mut() {
+          ren.set_config(new_config.clone());
+        }
+      }
+      Err(err) => {
+        for ren in windows.values_mut() {
+          ren.notify_user(
+            renderer::notifications::Level::Error,
+            format!("Failed to reload the config: {}", err),
+          );
+        }
+      }
+    },
+    Event::UserEvent(UserEvent::BackgroundChanged) => {
+      for ren in windows.values() {
         ren.window.request_redraw();
       }
-      WindowEvent::CloseRequested => {
-        *control_flow = winit::event_loop::ControlFlow::Exit;
+    }
+    Event::UserEvent(UserEvent::OpenFiles(files)) => {
+      if let Some(ren) = windows.get_mut(&focused) {
+        ren.open_files(files);
       }
-      WindowEvent::MouseWheel { delta, .. } => {
-        match delta {
-          MouseScrollDelta::LineDelta(x, y) => {
-            let dy = if y > 0.0 {
-              y + ren.font_height
-            } else {
-              y - ren.font_height
-            };
-            ren.scroll(
-              winit::dpi::PhysicalPosition {
-                x: x as f64,
-                y: dy as f64,
-              },
-              mouse_pos,
-            );
-          }
-          MouseScrollDelta::PixelDelta(delta) => {
-            ren.scroll(delta, mouse_pos);
+    }
+    Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+      for ren in windows.values_mut() {
+        ren.tick();
+      }
+    }
+    // typing shows up right after the keys waiting are handled, together
+    Event::MainEventsCleared => {
+      for ren in windows.values_mut() {
+        let drawn = ren.redraw_input();
+        recover_redraw(ren, drawn);
+      }
+    }
+    Event::RedrawRequested(window_id) => {
+      if let Some(ren) = windows.get_mut(&window_id) {
+        if !ren.drawn_for_input() {
+          let drawn = ren.redraw();
+          recover_redraw(ren, drawn);
+        }
+      }
+    }
+    Event::RedrawEventsCleared => {
+      let requests = windows
+        .values_mut()
+        .filter_map(|ren| Some((ren.window.id(), ren.new_window.take()?)))
+        .collect::<Vec<_>>();
+      for (id, buffers) in requests {
+        let opened = futures::executor::block_on(
+          windows[&id].open_window(target, buffers),
+        );
+        match opened {
+          Ok(ren) => {
+            ren.window.request_redraw();
+            windows.insert(ren.window.id(), ren);
           }
+          Err(err) => windows.get_mut(&id).unwrap().notify_user(
+            renderer::notifications::Level::Error,
+            format!("Failed to open a window: {}", err),
+          ),
         }
-        ren.window.request_redraw();
       }
-      WindowEvent::KeyboardInput { input, .. } => {
-        if input.state == ElementState::Pressed {
-          ren
-            .code_views
-            .input_special(ren.size.cast(), input.virtual_keycode.unwrap());
-          ren.window.request_redraw();
+
+      let closed = windows
+        .iter()
+        .filter(|(_, ren)| ren.quit)
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+      for id in closed {
+        let mut ren = windows.remove(&id).unwrap();
+        ren.save_session();
+        ren.clear_backups();
+        ren.shut_down();
+      }
+      if args.wait && windows.values().all(|ren| ren.code_views.is_empty()) {
+        for ren in windows.values_mut() {
+          ren.save_session();
+          ren.clear_backups();
+          ren.shut_down();
         }
+        windows.clear();
       }
-      WindowEvent::ReceivedCharacter(ch) => {
-        ren.code_views.input_char(ren.size.cast(), ch);
+
+      // windows with scroll lock on scroll along with each other
+      let scrolled = windows
+        .iter_mut()
+        .filter_map(|(id, ren)| Some((*id, ren.take_locked_scroll()?)))
+        .collect::<Vec<_>>();
+      for (id, lines) in scrolled {
+        for (_, ren) in windows.iter_mut().filter(|(other, _)| **other != id) {
+          ren.follow_scroll(lines);
+        }
       }
-      WindowEvent::CursorMoved { position, .. } => mouse_pos = position,
-      WindowEvent::MouseInput { state, .. } => {
-        ren.click(mouse_pos, state);
-        ren.window.request_redraw();
+
+      // edits show up in the other windows of the file
+      for ren in windows.values() {
+        if ren.is_behind() {
+          ren.window.request_redraw();
+        }
       }
-      _ => {}
-    },
-    winit::event::Event::RedrawRequested(_) => ren.redraw().unwrap(),
-    _ => *control_flow = winit::event_loop::ControlFlow::Wait,
+      *control_flow = if windows.is_empty() {
+        if let Some(instance) = &instance {
+          instance.close();
+        }
+        ControlFlow::Exit
+      } else {
+        match windows.values().filter_map(|ren| ren.deadline()).min() {
+          Some(deadline) => ControlFlow::WaitUntil(deadline),
+          None => ControlFlow::Wait,
+        }
+      }
+    }
+    _ => {}
   });
 }
 
+fn recover_redraw(
+  ren: &mut renderer::Renderer,
+  drawn: Result<(), anyhow::Error>,
+) {
+  if let Err(err) = drawn {
+    // usually an outdated swap chain, which resizing recreates
+    tracing::warn!("failed to redraw: {}", err);
+    ren.resize(ren.size.cast());
+  }
+}
+
+fn open_buffer(
+  file: &cli::FileArg,
+  language: Option<&String>,
+) -> Result<renderer::Buffer, anyhow::Error> {
+  if file.is_stdin() {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    let name = match language {
+      Some(language) => format!("stdin ({})", language),
+      None => String::from("stdin"),
+    };
+    return Ok(renderer::Buffer::Unsaved { name, text });
+  }
+
+  if !file.path.exists() {
+    anyhow::bail!("path doesn't exist: {}", file.path.display());
+  }
+  if !file.path.is_file() {
+    anyhow::bail!("path isn't a file: {}", file.path.display());
+  }
+  Ok(renderer::Buffer::File {
+    path: file.path.clone(),
+    position: file.position,
+  })
+}
+
 macro_rules! extend_fonts {
   ($e: expr, $p: expr) => {
     match std::fs::read_dir($p) {
@@ -144,9 +476,11 @@ fn get_font_map() -> HashMap<String, PathBuf> {
     .collect()
 }
 
+/// The font called `name` and its ligatures.
 fn get_font(
   name: Option<&String>,
-) -> Result<wgpu_glyph::ab_glyph::FontArc, anyhow::Error> {
+) -> Result<(wgpu_glyph::ab_glyph::FontArc, shaping::Ligatures), anyhow::Error>
+{
   let fonts = get_font_map();
   let font = name
     .and_then(|font| fonts.get(font))
@@ -154,7 +488,11 @@ fn get_font(
     .transpose()?
     .unwrap_or_else(|| include_bytes!("./JetBrainsMono-Regular.ttf").to_vec());
 
-  Ok(wgpu_glyph::ab_glyph::FontArc::try_from_vec(font)?)
+  let ligatures = shaping::Ligatures::parse(&font);
+  Ok((
+    wgpu_glyph::ab_glyph::FontArc::try_from_vec(font)?,
+    ligatures,
+  ))
 }
 
 #[cfg(test)]
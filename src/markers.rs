@@ -0,0 +1,132 @@
+//! Places the cursor can be moved between from the keyboard, a kind at a
+//! time: the problems the last task reported, the changes of a diff, the
+//! occurrences of the text searched for and the bookmarks.
+
+use std::path::PathBuf;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MarkerKind {
+  Problem,
+  Change,
+  Match,
+  Bookmark,
+}
+
+impl MarkerKind {
+  /// Told when there is no marker of the kind to go to.
+  pub fn none_message(self) -> &'static str {
+    match self {
+      MarkerKind::Problem => "No problems",
+      MarkerKind::Change => "No changes",
+      MarkerKind::Match => "No matches",
+      MarkerKind::Bookmark => "No bookmarks",
+    }
+  }
+}
+
+/// Where a marker is. The open tabs sort before the files which aren't.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Target {
+  Tab(usize),
+  File(PathBuf),
+}
+
+/// A marker at a zero based line and column of its target.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Marker {
+  pub target: Target,
+  pub line: usize,
+  pub column: usize,
+}
+
+/// The marker after `current` in sorted `markers`, or the one before it.
+/// Wraps around at either end.
+pub fn next<T: Ord + Clone>(
+  markers: &[T],
+  current: &T,
+  forward: bool,
+) -> Option<T> {
+  if forward {
+    markers
+      .iter()
+      .find(|marker| *marker > current)
+      .or_else(|| markers.first())
+      .cloned()
+  } else {
+    markers
+      .iter()
+      .rev()
+      .find(|marker| *marker < current)
+      .or_else(|| markers.last())
+      .cloned()
+  }
+}
+
+/// Lines and grapheme columns where `find` occurs in `lines`.
+pub fn matches(lines: &[String], find: &str) -> Vec<(usize, usize)> {
+  if find.is_empty() {
+    return vec![];
+  }
+  lines
+    .iter()
+    .enumerate()
+    .flat_map(|(row, line)| {
+      line
+        .match_indices(find)
+        .map(move |(start, _)| (row, line[..start].graphemes(true).count()))
+    })
+    .collect()
+}
+
+/// The first of each run of consecutive `lines`, which are sorted.
+pub fn hunks(lines: &[usize]) -> Vec<usize> {
+  lines
+    .iter()
+    .enumerate()
+    .filter(|(i, line)| *i == 0 || lines[i - 1] + 1 != **line)
+    .map(|(_, line)| *line)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bookmarks_across_tabs() {
+    let bookmarks = [(0, 3), (0, 10), (2, 0)];
+    assert_eq!(next(&bookmarks, &(0, 3), true), Some((0, 10)));
+    assert_eq!(next(&bookmarks, &(1, 50), true), Some((2, 0)));
+    assert_eq!(next(&bookmarks, &(2, 0), true), Some((0, 3)));
+    assert_eq!(next(&bookmarks, &(0, 5), false), Some((0, 3)));
+    assert_eq!(next(&bookmarks, &(0, 3), false), Some((2, 0)));
+    assert_eq!(next::<(usize, usize)>(&[], &(0, 0), true), None);
+  }
+
+  #[test]
+  fn open_tabs_first() {
+    let marker = |target, line| Marker {
+      target,
+      line,
+      column: 0,
+    };
+    let mut markers = vec![
+      marker(Target::File(PathBuf::from("a.rs")), 1),
+      marker(Target::Tab(1), 4),
+      marker(Target::Tab(0), 9),
+    ];
+    markers.sort();
+    assert_eq!(markers[0].target, Target::Tab(0));
+    let current = marker(Target::Tab(1), 4);
+    assert_eq!(next(&markers, &current, true), Some(markers[2].clone()));
+  }
+
+  #[test]
+  fn finds_matches_and_hunks() {
+    let lines = vec![String::from("é foo foo"), String::from("bar")];
+    assert_eq!(matches(&lines, "foo"), vec![(0, 2), (0, 6)]);
+    assert!(matches(&lines, "").is_empty());
+    assert_eq!(hunks(&[2, 3, 4, 8, 10, 11]), vec![2, 8, 10]);
+  }
+}
@@ -0,0 +1,129 @@
+//! Scrolling which goes on after the fingers leave a touchpad, slowing down
+//! like it does natively.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use winit::dpi::PhysicalPosition;
+
+/// How far back the deltas of a gesture count towards its speed.
+const SAMPLE_WINDOW: Duration = Duration::from_millis(100);
+/// How quickly the scrolling slows down, in seconds to lose 63% of the
+/// speed.
+const TIME_CONSTANT: f64 = 0.325;
+/// Pixels per second below which the scrolling stops.
+const MIN_SPEED: f64 = 20.0;
+/// How often the scrolling moves on.
+pub const FRAME: Duration = Duration::from_millis(16);
+
+#[derive(Default)]
+pub struct Momentum {
+  /// Pixels per second.
+  velocity: PhysicalPosition<f64>,
+  /// When the scrolling last moved on, `None` while it isn't moving.
+  last: Option<Instant>,
+  /// The recent deltas of the gesture and when they came in.
+  samples: VecDeque<(Instant, PhysicalPosition<f64>)>,
+}
+
+impl Momentum {
+  /// Stops scrolling, such as when a new gesture starts.
+  pub fn stop(&mut self) {
+    self.last = None;
+    self.samples.clear();
+  }
+
+  /// Records a delta of the ongoing gesture.
+  pub fn track(&mut self, now: Instant, delta: PhysicalPosition<f64>) {
+    self.last = None;
+    self.samples.push_back((now, delta));
+    self.forget_before(now);
+  }
+
+  fn forget_before(&mut self, now: Instant) {
+    while matches!(
+      self.samples.front(),
+      Some((time, _)) if now.duration_since(*time) > SAMPLE_WINDOW
+    ) {
+      self.samples.pop_front();
+    }
+  }
+
+  /// Keeps scrolling at the speed of the gesture which just ended.
+  pub fn release(&mut self, now: Instant) {
+    self.forget_before(now);
+    let first = match self.samples.front() {
+      Some((time, _)) => *time,
+      None => return self.stop(),
+    };
+    let seconds = now.duration_since(first).max(FRAME).as_secs_f64();
+    let (x, y) = self
+      .samples
+      .drain(..)
+      .fold((0.0, 0.0), |(x, y), (_, delta)| (x + delta.x, y + delta.y));
+    self.velocity = PhysicalPosition::new(x / seconds, y / seconds);
+    self.last = Some(now);
+  }
+
+  /// How far to scroll since the last step, `None` once it stopped.
+  pub fn step(&mut self, now: Instant) -> Option<PhysicalPosition<f64>> {
+    let last = self.last?;
+    let seconds = now.duration_since(last).as_secs_f64();
+    let decay = (-seconds / TIME_CONSTANT).exp();
+    // the distance covered while slowing down over the interval
+    let distance = TIME_CONSTANT * (1.0 - decay);
+    let delta = PhysicalPosition::new(
+      self.velocity.x * distance,
+      self.velocity.y * distance,
+    );
+    self.velocity.x *= decay;
+    self.velocity.y *= decay;
+    self.last = if self.velocity.x.hypot(self.velocity.y) < MIN_SPEED {
+      None
+    } else {
+      Some(now)
+    };
+    Some(delta)
+  }
+
+  /// When to scroll on next, if still scrolling.
+  pub fn deadline(&self) -> Option<Instant> {
+    Some(self.last? + FRAME)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn slows_down_after_release() {
+    let start = Instant::now();
+    let mut momentum = Momentum::default();
+    for i in 0..10 {
+      momentum.track(start + FRAME * i, PhysicalPosition::new(0.0, -20.0));
+    }
+    let end = start + FRAME * 10;
+    momentum.release(end);
+    assert_eq!(momentum.deadline(), Some(end + FRAME));
+
+    let mut total = 0.0;
+    let mut previous = f64::INFINITY;
+    let mut now = end;
+    while let Some(delta) = momentum.step(now + FRAME) {
+      now += FRAME;
+      assert_eq!(delta.x, 0.0);
+      assert!(delta.y < 0.0 && -delta.y < previous);
+      previous = -delta.y;
+      total += delta.y;
+    }
+    // 1250 pixels per second times the time constant, a bit less as it
+    // stops early
+    assert!(total < -390.0 && total > -1250.0 * TIME_CONSTANT);
+    assert_eq!(momentum.deadline(), None);
+
+    // a pause before lifting the fingers doesn't throw the view
+    momentum.track(now, PhysicalPosition::new(0.0, -20.0));
+    momentum.release(now + Duration::from_millis(300));
+    assert_eq!(momentum.step(now + Duration::from_millis(316)), None);
+  }
+}
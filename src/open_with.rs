@@ -0,0 +1,183 @@
+//! Registers devcode with the OS file manager and as handler of
+//! `devcode://` links, which then start it with `--open` so files go to a
+//! running instance where possible.
+
+use std::path::Path;
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+use std::process::Command;
+
+/// Text types offered to the file manager, other files can still be opened
+/// with devcode explicitly.
+#[cfg(target_os = "linux")]
+const MIME_TYPES: &[&str] = &[
+  "text/plain",
+  "text/markdown",
+  "text/x-csrc",
+  "text/x-chdr",
+  "text/x-c++src",
+  "text/x-python",
+  "text/x-rust",
+  "text/x-go",
+  "text/x-toml",
+  "text/javascript",
+  "application/json",
+  "application/x-shellscript",
+  "application/xml",
+  "x-scheme-handler/devcode",
+];
+
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+fn run(command: &mut Command) -> Result<(), anyhow::Error> {
+  let status = command.status()?;
+  if !status.success() {
+    anyhow::bail!("{:?} failed with {}", command, status);
+  }
+  Ok(())
+}
+
+/// `arg` quoted for the Exec key of a desktop entry. Quoted arguments escape
+/// `"`, `` ` ``, `$` and `\`, `%` starts a field code, and the value of the
+/// key escapes backslashes once more.
+#[cfg(target_os = "linux")]
+fn desktop_exec_arg(arg: &str) -> String {
+  let mut quoted = String::from("\"");
+  for ch in arg.chars() {
+    match ch {
+      '"' | '`' | '$' | '\\' => {
+        quoted.push('\\');
+        quoted.push(ch);
+      }
+      '%' => quoted.push_str("%%"),
+      _ => quoted.push(ch),
+    }
+  }
+  quoted.push('"');
+  quoted.replace('\\', "\\\\")
+}
+
+/// Adds a desktop entry, which file managers list under "Open With". It takes
+/// URLs so it also handles links, files are passed as `file://` URIs.
+#[cfg(target_os = "linux")]
+fn register_for(exe: &Path) -> Result<(), anyhow::Error> {
+  let applications = std::path::PathBuf::from(
+    shellexpand::tilde("~/.local/share/applications").to_string(),
+  );
+  std::fs::create_dir_all(&applications)?;
+  let entry = format!(
+    "[Desktop Entry]\n\
+     Type=Application\n\
+     Name=devcode\n\
+     Exec={} --open %U\n\
+     Terminal=false\n\
+     Categories=Development;TextEditor;\n\
+     MimeType={};\n",
+    desktop_exec_arg(&exe.to_string_lossy()),
+    MIME_TYPES.join(";")
+  );
+  std::fs::write(applications.join("devcode.desktop"), entry)?;
+
+  // only refreshes the cache, file managers pick up the entry without it
+  if let Err(err) =
+    run(Command::new("update-desktop-database").arg(applications))
+  {
+    tracing::debug!("failed to update the desktop database: {}", err);
+  }
+  // being listed isn't enough for links, browsers ask for the default
+  run(Command::new("xdg-mime").args(&[
+    "default",
+    "devcode.desktop",
+    "x-scheme-handler/devcode",
+  ]))
+}
+
+/// Adds "Open with devcode" to the context menu of all files and the
+/// `devcode` URL protocol for the current user.
+#[cfg(target_os = "windows")]
+fn register_for(exe: &Path) -> Result<(), anyhow::Error> {
+  let command = format!("\"{}\" --open \"%1\"", exe.display());
+  let protocol = r"HKCU\Software\Classes\devcode";
+  run(Command::new("reg").args(&[
+    "add",
+    protocol,
+    "/ve",
+    "/d",
+    "URL:devcode",
+    "/f",
+  ]))?;
+  run(Command::new("reg").args(&[
+    "add",
+    protocol,
+    "/v",
+    "URL Protocol",
+    "/d",
+    "",
+    "/f",
+  ]))?;
+  run(Command::new("reg").args(&[
+    "add",
+    format!(r"{}\shell\open\command", protocol).as_str(),
+    "/ve",
+    "/d",
+    command.as_str(),
+    "/f",
+  ]))?;
+
+  let key = r"HKCU\Software\Classes\*\shell\devcode";
+  run(Command::new("reg").args(&[
+    "add",
+    key,
+    "/ve",
+    "/d",
+    "Open with devcode",
+    "/f",
+  ]))?;
+  run(Command::new("reg").args(&[
+    "add",
+    key,
+    "/v",
+    "Icon",
+    "/d",
+    exe.display().to_string().as_str(),
+    "/f",
+  ]))?;
+  run(Command::new("reg").args(&[
+    "add",
+    format!(r"{}\command", key).as_str(),
+    "/ve",
+    "/d",
+    command.as_str(),
+    "/f",
+  ]))
+}
+
+/// Finder hands files to applications through Apple events instead of
+/// arguments, which the windowing library doesn't report.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn register_for(_exe: &Path) -> Result<(), anyhow::Error> {
+  anyhow::bail!(
+    "registering file associations isn't supported on this platform, use \
+     `devcode --open <file>` instead"
+  )
+}
+
+/// Registers the running executable as an application to open files with.
+pub fn register() -> Result<(), anyhow::Error> {
+  register_for(&std::env::current_exe()?)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn desktop_exec_args() {
+    assert_eq!(
+      desktop_exec_arg("/usr/bin/devcode"),
+      r#""/usr/bin/devcode""#
+    );
+    assert_eq!(
+      desktop_exec_arg(r#"/a "b" $c `d` \e 100%"#),
+      r#""/a \\"b\\" \\$c \\`d\\` \\\\e 100%%""#
+    );
+  }
+}
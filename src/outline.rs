@@ -0,0 +1,272 @@
+/// Keywords introducing a symbol, in the languages we know about.
+const KEYWORDS: &[&str] = &[
+  "fn",
+  "def",
+  "function",
+  "func",
+  "struct",
+  "class",
+  "enum",
+  "trait",
+  "interface",
+  "impl",
+  "mod",
+  "module",
+  "namespace",
+  "type",
+  "macro_rules!",
+];
+
+/// Words which may precede the keyword of a symbol.
+const MODIFIERS: &[&str] = &[
+  "pub",
+  "export",
+  "default",
+  "async",
+  "unsafe",
+  "const",
+  "extern",
+  "static",
+  "abstract",
+  "public",
+  "private",
+  "protected",
+];
+
+/// A function, type or other named item of a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+  /// The keyword it was declared with, such as `fn` or `class`.
+  pub kind: &'static str,
+  pub name: String,
+  /// Zero based position of the name.
+  pub row: usize,
+  pub column: usize,
+  /// Last row belonging to the symbol.
+  pub end_row: usize,
+  pub children: Vec<Symbol>,
+}
+
+fn indentation(line: &str) -> usize {
+  line.len() - line.trim_start().len()
+}
+
+/// Strips a balanced `open`..`close` group from the start of `text`.
+fn skip_group(text: &str, open: char, close: char) -> &str {
+  if !text.starts_with(open) {
+    return text;
+  }
+  let mut depth = 0;
+  for (i, ch) in text.char_indices() {
+    if ch == open {
+      depth += 1;
+    } else if ch == close {
+      depth -= 1;
+      if depth == 0 {
+        return text[i + 1..].trim_start();
+      }
+    }
+  }
+  ""
+}
+
+/// The keyword, name and name column of a symbol declared on `line`.
+fn parse_declaration(line: &str) -> Option<(&'static str, String, usize)> {
+  let mut rest = line.trim_start();
+  loop {
+    if rest.starts_with("pub(") {
+      rest = skip_group(&rest[3..], '(', ')');
+      continue;
+    }
+    match MODIFIERS.iter().find(|modifier| {
+      rest.starts_with(*modifier)
+        && rest[modifier.len()..].starts_with(char::is_whitespace)
+    }) {
+      Some(modifier) => rest = rest[modifier.len()..].trim_start(),
+      None => break,
+    }
+  }
+
+  let kind = KEYWORDS.iter().find(|keyword| {
+    rest.starts_with(*keyword)
+      && rest[keyword.len()..]
+        .starts_with(|ch: char| ch.is_whitespace() || ch == '<')
+  })?;
+  rest = rest[kind.len()..].trim_start();
+
+  let name = if *kind == "impl" {
+    let rest = skip_group(rest, '<', '>');
+    let end = rest.find(" where").or_else(|| rest.find('{'));
+    rest[..end.unwrap_or(rest.len())].trim().to_string()
+  } else {
+    // receivers of Go methods
+    rest = skip_group(rest, '(', ')');
+    rest
+      .chars()
+      .take_while(|ch| ch.is_alphanumeric() || *ch == '_' || *ch == '$')
+      .collect()
+  };
+  if name.is_empty() {
+    return None;
+  }
+  let column = line.len() - rest.len();
+  Some((kind, name, line[..column].chars().count()))
+}
+
+/// Finds the symbols declared in `lines`, nested by their indentation.
+pub fn symbols(lines: &[String]) -> Vec<Symbol> {
+  // symbols which might still get children, with their indentation
+  let mut stack: Vec<(usize, Symbol)> = vec![];
+  let mut roots = vec![];
+
+  let close = |stack: &mut Vec<(usize, Symbol)>, roots: &mut Vec<Symbol>| {
+    let (_, symbol) = stack.pop().unwrap();
+    match stack.last_mut() {
+      Some((_, parent)) => parent.children.push(symbol),
+      None => roots.push(symbol),
+    }
+  };
+
+  for (row, line) in lines.iter().enumerate() {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() {
+      continue;
+    }
+
+    let indent = indentation(line);
+    // closing brackets still belong to the symbol they close
+    let closing = trimmed.starts_with(&['}', ')', ']'][..]);
+    while let Some((symbol_indent, _)) = stack.last() {
+      if indent > *symbol_indent || (indent == *symbol_indent && closing) {
+        break;
+      }
+      close(&mut stack, &mut roots);
+    }
+    for (_, symbol) in &mut stack {
+      symbol.end_row = row;
+    }
+
+    if let Some((kind, name, column)) = parse_declaration(line) {
+      stack.push((
+        indent,
+        Symbol {
+          kind,
+          name,
+          row,
+          column,
+          end_row: row,
+          children: vec![],
+        },
+      ));
+    }
+  }
+
+  while !stack.is_empty() {
+    close(&mut stack, &mut roots);
+  }
+  roots
+}
+
+/// The symbols containing `row`, outermost first.
+pub fn path_at(symbols: &[Symbol], row: usize) -> Vec<&Symbol> {
+  let mut path = vec![];
+  let mut symbols = symbols;
+  while let Some(symbol) = symbols
+    .iter()
+    .find(|symbol| symbol.row <= row && row <= symbol.end_row)
+  {
+    path.push(symbol);
+    symbols = &symbol.children;
+  }
+  path
+}
+
+/// All `symbols` and their children, each followed by its children.
+pub fn flatten(symbols: &[Symbol]) -> Vec<&Symbol> {
+  let mut flat = vec![];
+  for symbol in symbols {
+    flat.push(symbol);
+    flat.extend(flatten(&symbol.children));
+  }
+  flat
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(text: &str) -> Vec<String> {
+    text.lines().map(String::from).collect()
+  }
+
+  #[test]
+  fn rust_symbols() {
+    let text = lines(
+      "use std::io;
+
+pub struct Foo {
+  bar: u32,
+}
+
+impl<T> Display for Foo<T> {
+  pub(crate) async fn fmt(&self) {
+    let x = 1;
+  }
+
+  const fn id() {}
+}
+
+fn main() {}",
+    );
+    let symbols = symbols(&text);
+
+    let names = symbols
+      .iter()
+      .map(|symbol| (symbol.kind, symbol.name.as_str(), symbol.end_row))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      vec![
+        ("struct", "Foo", 4),
+        ("impl", "Display for Foo<T>", 12),
+        ("fn", "main", 14),
+      ]
+    );
+
+    let methods = &symbols[1].children;
+    assert_eq!(methods.len(), 2);
+    assert_eq!((methods[0].name.as_str(), methods[0].row), ("fmt", 7));
+    assert_eq!((methods[0].column, methods[0].end_row), (22, 9));
+    assert_eq!((methods[1].name.as_str(), methods[1].end_row), ("id", 11));
+
+    let path = path_at(&symbols, 8)
+      .into_iter()
+      .map(|symbol| symbol.name.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(path, vec!["Display for Foo<T>", "fmt"]);
+    assert!(path_at(&symbols, 5).is_empty());
+
+    let flat = flatten(&symbols)
+      .into_iter()
+      .map(|symbol| symbol.name.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(flat, vec!["Foo", "Display for Foo<T>", "fmt", "id", "main"]);
+  }
+
+  #[test]
+  fn python_symbols() {
+    let text = lines(
+      "class Foo:
+    def bar(self):
+        pass
+
+def baz():
+    pass",
+    );
+    let symbols = symbols(&text);
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols[0].children[0].name, "bar");
+    assert_eq!(symbols[0].end_row, 2);
+    assert_eq!(symbols[1].name, "baz");
+  }
+}
@@ -0,0 +1,84 @@
+//! Zooming by pinching two fingers on a touch screen.
+
+use std::collections::HashMap;
+use winit::dpi::PhysicalPosition;
+use winit::event::TouchPhase;
+
+#[derive(Default)]
+pub struct Pinch {
+  /// Where the fingers on the screen are, by id.
+  touches: HashMap<u64, PhysicalPosition<f64>>,
+}
+
+fn distance(a: PhysicalPosition<f64>, b: PhysicalPosition<f64>) -> f64 {
+  (a.x - b.x).hypot(a.y - b.y)
+}
+
+impl Pinch {
+  /// Follows a finger, returns how much to scale by and around which point
+  /// when it moved while exactly two are down.
+  pub fn touch(
+    &mut self,
+    id: u64,
+    phase: TouchPhase,
+    location: PhysicalPosition<f64>,
+  ) -> Option<(f32, PhysicalPosition<f64>)> {
+    match phase {
+      TouchPhase::Started => {
+        self.touches.insert(id, location);
+        None
+      }
+      TouchPhase::Ended | TouchPhase::Cancelled => {
+        self.touches.remove(&id);
+        None
+      }
+      TouchPhase::Moved => {
+        let previous = self.touches.insert(id, location)?;
+        if self.touches.len() != 2 {
+          return None;
+        }
+        let other = *self
+          .touches
+          .iter()
+          .find(|(other, _)| **other != id)
+          .map(|(_, position)| position)?;
+        let before = distance(previous, other);
+        if before < 1.0 {
+          return None;
+        }
+        let center = PhysicalPosition::new(
+          (location.x + other.x) / 2.0,
+          (location.y + other.y) / 2.0,
+        );
+        Some(((distance(location, other) / before) as f32, center))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn scales_by_the_distance_between_two_fingers() {
+    let mut pinch = Pinch::default();
+    let at = |x, y| PhysicalPosition::new(x, y);
+    assert_eq!(pinch.touch(1, TouchPhase::Started, at(100.0, 100.0)), None);
+    // one finger scrolls
+    assert_eq!(pinch.touch(1, TouchPhase::Moved, at(100.0, 110.0)), None);
+
+    pinch.touch(2, TouchPhase::Started, at(100.0, 210.0));
+    assert_eq!(
+      pinch.touch(2, TouchPhase::Moved, at(100.0, 310.0)),
+      Some((2.0, at(100.0, 210.0)))
+    );
+    assert_eq!(
+      pinch.touch(1, TouchPhase::Moved, at(100.0, 210.0)),
+      Some((0.5, at(100.0, 260.0)))
+    );
+
+    pinch.touch(2, TouchPhase::Ended, at(100.0, 310.0));
+    assert_eq!(pinch.touch(1, TouchPhase::Moved, at(100.0, 0.0)), None);
+  }
+}
@@ -0,0 +1,340 @@
+//! Plugins are directories in the plugin directory with a `plugin.toml`
+//! manifest, contributing language servers and language settings which are
+//! merged into the config.
+
+use crate::config::{Config, LanguageConfig, LanguageServerConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const MANIFEST: &str = "plugin.toml";
+
+/// Numbers the staging directories of the installs of this process.
+static INSTALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// What a plugin is allowed to do beyond changing settings.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Permission {
+  /// Starting programs, which language servers need.
+  Process,
+}
+
+impl Permission {
+  pub fn description(self) -> &'static str {
+    match self {
+      Permission::Process => "runs programs",
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Manifest {
+  pub name: String,
+  pub version: String,
+  #[serde(default)]
+  pub description: String,
+  #[serde(default)]
+  pub permissions: Vec<Permission>,
+  /// Added to the configured language servers, which win on conflicts.
+  #[serde(default)]
+  pub language_servers: HashMap<String, LanguageServerConfig>,
+  /// Added to the configured languages, which win on conflicts.
+  #[serde(default)]
+  pub languages: HashMap<String, LanguageConfig>,
+}
+
+impl Manifest {
+  fn read(dir: &Path) -> Result<Self, anyhow::Error> {
+    let manifest: Self =
+      toml::from_str(&std::fs::read_to_string(dir.join(MANIFEST))?)?;
+    // the name becomes a directory name when installing
+    if manifest.name.is_empty()
+      || manifest.name.starts_with('.')
+      || manifest.name.contains(&['/', '\\'][..])
+    {
+      anyhow::bail!("invalid plugin name '{}'", manifest.name);
+    }
+    if !manifest.language_servers.is_empty()
+      && !manifest.permissions.contains(&Permission::Process)
+    {
+      anyhow::bail!(
+        "{} starts language servers without the process permission",
+        manifest.name
+      );
+    }
+    Ok(manifest)
+  }
+}
+
+pub struct Plugin {
+  pub dir: PathBuf,
+  pub manifest: Manifest,
+  pub enabled: bool,
+}
+
+/// Plugin names to not load, kept apart from the plugins so reinstalling
+/// one doesn't enable it again.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct State {
+  disabled: Vec<String>,
+}
+
+/// The installed plugins, loaded again to pick up changes.
+pub struct PluginHost {
+  dir: PathBuf,
+  pub plugins: Vec<Plugin>,
+  /// Plugins which failed to load, by directory name.
+  pub errors: Vec<(String, String)>,
+}
+
+pub fn plugins_dir() -> PathBuf {
+  Config::path().with_file_name("plugins")
+}
+
+impl PluginHost {
+  pub fn load() -> Self {
+    Self::load_from(plugins_dir())
+  }
+
+  fn load_from(dir: PathBuf) -> Self {
+    let state = Self::read_state(&dir);
+    let mut entries = std::fs::read_dir(&dir)
+      .map(|entries| {
+        entries
+          .filter_map(|entry| Some(entry.ok()?.path()))
+          .filter(|path| path.is_dir())
+          .collect::<Vec<_>>()
+      })
+      .unwrap_or_default();
+    entries.sort();
+
+    let mut plugins = vec![];
+    let mut errors = vec![];
+    for path in entries {
+      let name = path.file_name().unwrap().to_string_lossy().to_string();
+      // staging directories of installs
+      if name.starts_with('.') {
+        continue;
+      }
+      match Manifest::read(&path) {
+        Ok(manifest) => plugins.push(Plugin {
+          enabled: !state.disabled.contains(&manifest.name),
+          dir: path,
+          manifest,
+        }),
+        Err(err) => errors.push((name, err.to_string())),
+      }
+    }
+
+    Self {
+      dir,
+      plugins,
+      errors,
+    }
+  }
+
+  fn state_path(dir: &Path) -> PathBuf {
+    dir.join("state.toml")
+  }
+
+  fn read_state(dir: &Path) -> State {
+    std::fs::read_to_string(Self::state_path(dir))
+      .ok()
+      .and_then(|text| toml::from_str(&text).ok())
+      .unwrap_or_default()
+  }
+
+  /// Enables or disables the plugin `name`, taking effect on the next load.
+  pub fn set_enabled(
+    &self,
+    name: &str,
+    enabled: bool,
+  ) -> Result<(), anyhow::Error> {
+    let mut state = Self::read_state(&self.dir);
+    state.disabled.retain(|disabled| disabled != name);
+    if !enabled {
+      state.disabled.push(name.to_string());
+    }
+    std::fs::create_dir_all(&self.dir)?;
+    std::fs::write(Self::state_path(&self.dir), toml::to_string(&state)?)?;
+    Ok(())
+  }
+
+  /// `config` with the contributions of the enabled plugins added.
+  pub fn apply(&self, config: &Config) -> Config {
+    let mut config = config.clone();
+    for plugin in self.plugins.iter().filter(|plugin| plugin.enabled) {
+      for (name, server) in &plugin.manifest.language_servers {
+        let mut server = server.clone();
+        // programs shipped with the plugin
+        if server.command.starts_with("./") {
+          server.command = plugin
+            .dir
+            .join(&server.command)
+            .to_string_lossy()
+            .to_string();
+        }
+        config
+          .language_servers
+          .entry(name.clone())
+          .or_insert(server);
+      }
+      for (name, language) in &plugin.manifest.languages {
+        config
+          .languages
+          .entry(name.clone())
+          .or_insert_with(|| language.clone());
+      }
+    }
+    config
+  }
+}
+
+fn is_url(source: &str) -> bool {
+  source.contains("://") || source.starts_with("git@")
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), anyhow::Error> {
+  std::fs::create_dir_all(to)?;
+  for entry in std::fs::read_dir(from)? {
+    let entry = entry?;
+    let target = to.join(entry.file_name());
+    if entry.file_type()?.is_dir() {
+      if entry.file_name() != ".git" {
+        copy_dir(&entry.path(), &target)?;
+      }
+    } else {
+      std::fs::copy(entry.path(), target)?;
+    }
+  }
+  Ok(())
+}
+
+/// Installs the plugin at `source`, a local directory or a git URL, into the
+/// plugin directory. A plugin with the same name is replaced. Returns the
+/// name of the plugin.
+pub fn install(source: &str) -> Result<String, anyhow::Error> {
+  install_into(&plugins_dir(), source)
+}
+
+fn install_into(dir: &Path, source: &str) -> Result<String, anyhow::Error> {
+  // installs can run at the same time, each needs its own directory
+  let staging = dir.join(format!(
+    ".install-{}-{}",
+    std::process::id(),
+    INSTALLS.fetch_add(1, Ordering::Relaxed)
+  ));
+  if staging.exists() {
+    std::fs::remove_dir_all(&staging)?;
+  }
+  std::fs::create_dir_all(dir)?;
+
+  let result = (|| {
+    if is_url(source) {
+      let output = Command::new("git")
+        // `--` keeps a source starting with a dash from passing options
+        .args(&["clone", "--depth", "1", "--", source])
+        .arg(&staging)
+        .output()?;
+      if !output.status.success() {
+        anyhow::bail!(
+          "git clone failed: {}",
+          String::from_utf8_lossy(&output.stderr).trim()
+        );
+      }
+    } else {
+      let source = PathBuf::from(shellexpand::tilde(source).to_string());
+      copy_dir(&source, &staging)?;
+    }
+    let manifest = Manifest::read(&staging)?;
+    let target = dir.join(&manifest.name);
+    if target.exists() {
+      std::fs::remove_dir_all(&target)?;
+    }
+    std::fs::rename(&staging, &target)?;
+    Ok(manifest.name)
+  })();
+
+  if result.is_err() {
+    let _ = std::fs::remove_dir_all(&staging);
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn write_plugin(dir: &Path, manifest: &str) {
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(dir.join(MANIFEST), manifest).unwrap();
+  }
+
+  #[test]
+  fn load_and_apply() {
+    let dir = std::env::temp_dir().join("devcode-plugins-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    write_plugin(
+      &dir.join("zig"),
+      r#"
+      name = "zig"
+      version = "0.2.0"
+      permissions = ["process"]
+
+      [language-servers.zls]
+      command = "./bin/zls"
+      extensions = ["zig"]
+      "#,
+    );
+    write_plugin(
+      &dir.join("sneaky"),
+      r#"
+      name = "sneaky"
+      version = "1.0.0"
+
+      [language-servers.sh]
+      command = "sh"
+      extensions = ["txt"]
+      "#,
+    );
+
+    let host = PluginHost::load_from(dir.clone());
+    assert_eq!(host.plugins.len(), 1);
+    assert_eq!(host.errors[0].0, "sneaky");
+    let config = host.apply(&Config::default());
+    assert_eq!(
+      Path::new(&config.language_servers["zls"].command),
+      dir.join("zig").join("./bin/zls")
+    );
+
+    host.set_enabled("zig", false).unwrap();
+    let host = PluginHost::load_from(dir.clone());
+    assert!(!host.plugins[0].enabled);
+    assert!(host.apply(&Config::default()).language_servers.is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn install_local() {
+    let root = std::env::temp_dir().join("devcode-install-test");
+    let _ = std::fs::remove_dir_all(&root);
+    let source = root.join("source");
+    write_plugin(&source, "name = \"md\"\nversion = \"1.0.0\"\n");
+    std::fs::create_dir_all(source.join(".git")).unwrap();
+
+    let dir = root.join("plugins");
+    let name = install_into(&dir, source.to_str().unwrap()).unwrap();
+    assert_eq!(name, "md");
+    assert!(dir.join("md").join(MANIFEST).is_file());
+    assert!(!dir.join("md").join(".git").exists());
+
+    assert!(install_into(&dir, root.join("missing").to_str().unwrap()).is_err());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+}
@@ -0,0 +1,60 @@
+//! Whether the computer runs on battery, drawing fewer frames then.
+
+use std::path::Path;
+
+/// Whether the computer is running on battery. Only known on Linux, where
+/// the power supplies are listed in sysfs, elsewhere it's taken as plugged
+/// in.
+pub fn on_battery() -> bool {
+  on_battery_in(Path::new("/sys/class/power_supply"))
+}
+
+/// Whether a battery in `dir` is discharging with no charger online.
+fn on_battery_in(dir: &Path) -> bool {
+  let entries = match std::fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(_) => return false,
+  };
+  let mut discharging = false;
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let read =
+      |name: &str| std::fs::read_to_string(path.join(name)).unwrap_or_default();
+    match read("type").trim() {
+      "Mains" | "USB" if read("online").trim() == "1" => return false,
+      "Battery" => discharging |= read("status").trim() == "Discharging",
+      _ => {}
+    }
+  }
+  discharging
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_power_supplies() {
+    let dir = std::env::temp_dir()
+      .join(format!("devcode-power-{}", std::process::id()));
+    let supply = |name: &str, files: &[(&str, &str)]| {
+      std::fs::create_dir_all(dir.join(name)).unwrap();
+      for (file, text) in files {
+        std::fs::write(dir.join(name).join(file), text).unwrap();
+      }
+    };
+    assert!(!on_battery_in(&dir));
+
+    supply(
+      "BAT0",
+      &[("type", "Battery\n"), ("status", "Discharging\n")],
+    );
+    supply("AC", &[("type", "Mains\n"), ("online", "0\n")]);
+    assert!(on_battery_in(&dir));
+    // plugged in while the battery still reports discharging
+    supply("AC", &[("online", "1\n")]);
+    assert!(!on_battery_in(&dir));
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
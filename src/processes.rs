@@ -0,0 +1,146 @@
+//! The processes a window starts, language servers and tasks, kept track of
+//! so none of them outlive it.
+
+use std::collections::HashMap;
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long processes get to exit on their own before they're killed.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Kind {
+  LanguageServer,
+  Task,
+}
+
+/// The running processes by id. Cloning it shares them.
+#[derive(Clone, Default)]
+pub struct Processes {
+  running: Arc<Mutex<HashMap<u32, (Kind, String)>>>,
+}
+
+impl Processes {
+  /// Starts `command` in a process group of its own, so the processes it
+  /// starts in turn are stopped along with it.
+  pub fn spawn(
+    &self,
+    command: &mut Command,
+    kind: Kind,
+    name: &str,
+  ) -> std::io::Result<Child> {
+    #[cfg(unix)]
+    unsafe {
+      std::os::unix::process::CommandExt::pre_exec(command, || {
+        if libc::setpgid(0, 0) == 0 {
+          Ok(())
+        } else {
+          Err(std::io::Error::last_os_error())
+        }
+      });
+    }
+    let child = command.spawn()?;
+    self
+      .running
+      .lock()
+      .unwrap()
+      .insert(child.id(), (kind, name.to_string()));
+    Ok(child)
+  }
+
+  /// Forgets the process with `id` once it was waited for.
+  pub fn exited(&self, id: u32) {
+    self.running.lock().unwrap().remove(&id);
+  }
+
+  /// The ids and names of the running processes of `kind`, sorted by id.
+  pub fn running(&self, kind: Kind) -> Vec<(u32, String)> {
+    let mut running = self
+      .running
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|(_, (running, _))| *running == kind)
+      .map(|(id, (_, name))| (*id, name.clone()))
+      .collect::<Vec<_>>();
+    running.sort();
+    running
+  }
+
+  /// Asks the processes of `kind` and the ones they started to exit.
+  pub fn terminate(&self, kind: Kind) {
+    for (id, _) in self.running(kind) {
+      signal(id, false);
+    }
+  }
+
+  /// Kills the process with `id` and the ones it started.
+  pub fn kill(&self, id: u32) {
+    signal(id, true);
+  }
+
+  /// Waits up to `timeout` for all processes to exit, then kills the ones
+  /// left and those they started.
+  pub fn shut_down(&self, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    while !self.running.lock().unwrap().is_empty() && Instant::now() < deadline
+    {
+      std::thread::sleep(POLL_INTERVAL);
+    }
+    // processes started by the exited ones can be left in their groups
+    let ids = self.running.lock().unwrap().drain().collect::<Vec<_>>();
+    for (id, (_, name)) in ids {
+      tracing::warn!("killing {} which didn't exit in time", name);
+      signal(id, true);
+    }
+  }
+}
+
+#[cfg(unix)]
+fn signal(id: u32, kill: bool) {
+  let signal = if kill { libc::SIGKILL } else { libc::SIGTERM };
+  // a negative id signals the whole process group
+  unsafe {
+    libc::kill(-(id as libc::pid_t), signal);
+  }
+}
+
+#[cfg(not(unix))]
+fn signal(id: u32, kill: bool) {
+  let mut command = Command::new("taskkill");
+  command.args(&["/T", "/PID", &id.to_string()]);
+  if kill {
+    command.arg("/F");
+  }
+  let _ = command.output();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn shuts_down_process_groups() {
+    let processes = Processes::default();
+    let mut child = processes
+      .spawn(
+        Command::new("sh").args(&["-c", "sleep 30 & wait"]),
+        Kind::Task,
+        "sleep",
+      )
+      .unwrap();
+    assert_eq!(
+      processes.running(Kind::Task),
+      vec![(child.id(), String::from("sleep"))]
+    );
+    assert!(processes.running(Kind::LanguageServer).is_empty());
+
+    processes.terminate(Kind::Task);
+    assert!(child.wait().unwrap().code().is_none());
+    processes.exited(child.id());
+    processes.shut_down(Duration::from_millis(100));
+    assert!(processes.running(Kind::Task).is_empty());
+  }
+}
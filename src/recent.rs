@@ -0,0 +1,96 @@
+//! Files and workspaces opened lately, listed on the start screen. They are
+//! shared by all workspaces, stored in `~/.config/devcode/recent.toml`.
+
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Most files and workspaces remembered.
+const MAX_ENTRIES: usize = 10;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Recent {
+  /// Most recently opened first.
+  pub files: Vec<PathBuf>,
+  /// Most recently opened first.
+  pub workspaces: Vec<PathBuf>,
+}
+
+fn push(list: &mut Vec<PathBuf>, path: &Path) {
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  list.retain(|other| *other != path);
+  list.insert(0, path);
+  list.truncate(MAX_ENTRIES);
+}
+
+impl Recent {
+  pub fn path() -> PathBuf {
+    Config::path().with_file_name("recent.toml")
+  }
+
+  pub fn load(path: &Path) -> Self {
+    match std::fs::read_to_string(path) {
+      Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+        tracing::warn!("failed to parse {}: {}", path.display(), err);
+        Self::default()
+      }),
+      Err(_) => Self::default(),
+    }
+  }
+
+  pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, toml::to_string(self)?)?;
+    Ok(())
+  }
+
+  pub fn add_file(&mut self, path: &Path) {
+    push(&mut self.files, path);
+  }
+
+  pub fn add_workspace(&mut self, path: &Path) {
+    push(&mut self.workspaces, path);
+  }
+}
+
+/// Applies `update` to the stored list. It's loaded again each time as other
+/// windows and processes change it too.
+pub fn remember(update: impl FnOnce(&mut Recent)) {
+  let path = Recent::path();
+  let mut recent = Recent::load(&path);
+  update(&mut recent);
+  if let Err(err) = recent.save(&path) {
+    tracing::warn!("failed to save recent files: {}", err);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn most_recent_first() {
+    let path = std::env::temp_dir()
+      .join(format!("devcode-recent-{}.toml", std::process::id()));
+    assert_eq!(Recent::load(&path), Recent::default());
+
+    let mut recent = Recent::default();
+    for i in 0..12 {
+      recent.add_file(Path::new(&format!("/nonexistent/{}.rs", i)));
+    }
+    recent.add_file(Path::new("/nonexistent/5.rs"));
+    recent.add_workspace(Path::new("/nonexistent"));
+    // opened again it moves to the front, the oldest ones are dropped
+    assert_eq!(recent.files.len(), MAX_ENTRIES);
+    assert_eq!(recent.files[0], Path::new("/nonexistent/5.rs"));
+    assert_eq!(recent.files[1], Path::new("/nonexistent/11.rs"));
+    assert!(!recent.files.contains(&PathBuf::from("/nonexistent/1.rs")));
+
+    recent.save(&path).unwrap();
+    assert_eq!(Recent::load(&path), recent);
+    std::fs::remove_file(path).unwrap();
+  }
+}
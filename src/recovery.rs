@@ -0,0 +1,238 @@
+//! Copies of buffers with unsaved changes, kept in `.devcode/recovery` so
+//! they can be restored after the editor didn't exit cleanly.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often changed buffers are backed up at most.
+const INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Backup {
+  /// The file the text belongs to, `None` for unsaved buffers.
+  pub path: Option<PathBuf>,
+  /// Name of the tab.
+  pub name: String,
+  pub text: String,
+}
+
+pub struct Recovery {
+  dir: PathBuf,
+  /// The version backed up of each buffer by id.
+  written: HashMap<usize, usize>,
+  last_write: Option<Instant>,
+  /// Whether changes wait for the interval to pass.
+  pending: bool,
+  /// Backups left behind by a previous run and their files.
+  leftovers: Vec<(PathBuf, Backup)>,
+}
+
+impl Recovery {
+  /// Reads the backups left behind in `workspace`, before any are written.
+  pub fn new(workspace: &Path) -> Self {
+    let dir = workspace.join(".devcode").join("recovery");
+    let mut paths = std::fs::read_dir(&dir)
+      .map(|entries| {
+        entries
+          .filter_map(|entry| Some(entry.ok()?.path()))
+          .filter(|path| {
+            path.extension().and_then(|ext| ext.to_str()) == Some("toml")
+              && !is_live(path)
+          })
+          .collect::<Vec<_>>()
+      })
+      .unwrap_or_default();
+    paths.sort();
+    let leftovers = paths
+      .into_iter()
+      .filter_map(|path| {
+        let text = std::fs::read_to_string(&path).ok()?;
+        match toml::from_str(&text) {
+          Ok(backup) => Some((path, backup)),
+          Err(err) => {
+            tracing::warn!("failed to parse {}: {}", path.display(), err);
+            None
+          }
+        }
+      })
+      .collect();
+    Self {
+      dir,
+      written: HashMap::new(),
+      last_write: None,
+      pending: false,
+      leftovers,
+    }
+  }
+
+  /// Backups of a previous run which didn't exit cleanly.
+  pub fn leftovers(&self) -> Vec<&Backup> {
+    self.leftovers.iter().map(|(_, backup)| backup).collect()
+  }
+
+  /// Removes the backups of the previous run once they were restored or
+  /// discarded.
+  pub fn clear_leftovers(&mut self) {
+    for (path, _) in self.leftovers.drain(..) {
+      let _ = std::fs::remove_file(path);
+    }
+    self.remove_dir();
+  }
+
+  fn file(&self, id: usize) -> PathBuf {
+    // ids are only unique within a process
+    self.dir.join(format!("{}-{}.toml", std::process::id(), id))
+  }
+
+  /// Backs up the buffers with unsaved changes, given by id and version,
+  /// whose backup is out of date if the interval passed. Backups of other
+  /// buffers, which were saved or closed, are removed right away.
+  pub fn sync(
+    &mut self,
+    modified: &[(usize, usize)],
+    backup: impl Fn(usize) -> Option<Backup>,
+  ) -> Result<(), anyhow::Error> {
+    let gone = self
+      .written
+      .keys()
+      .filter(|id| !modified.iter().any(|(modified, _)| modified == *id))
+      .copied()
+      .collect::<Vec<_>>();
+    for id in &gone {
+      self.written.remove(id);
+      let _ = std::fs::remove_file(self.file(*id));
+    }
+    if !gone.is_empty() && self.written.is_empty() {
+      self.remove_dir();
+    }
+
+    let changed = modified
+      .iter()
+      .filter(|(id, version)| self.written.get(id) != Some(version))
+      .collect::<Vec<_>>();
+    self.pending = !changed.is_empty();
+    if changed.is_empty()
+      || matches!(self.last_write, Some(last) if last.elapsed() < INTERVAL)
+    {
+      return Ok(());
+    }
+
+    self.pending = false;
+    self.last_write = Some(Instant::now());
+    std::fs::create_dir_all(&self.dir)?;
+    for (id, version) in changed {
+      if let Some(backup) = backup(*id) {
+        std::fs::write(self.file(*id), toml::to_string(&backup)?)?;
+        self.written.insert(*id, *version);
+      }
+    }
+    Ok(())
+  }
+
+  /// When changes which wait for the interval can be backed up.
+  pub fn deadline(&self) -> Option<Instant> {
+    if self.pending {
+      Some(self.last_write? + INTERVAL)
+    } else {
+      None
+    }
+  }
+
+  /// Removes the backups of this run, called when exiting cleanly.
+  pub fn clear(&mut self) {
+    for id in self.written.keys() {
+      let _ = std::fs::remove_file(self.file(*id));
+    }
+    self.written.clear();
+    self.remove_dir();
+  }
+
+  fn remove_dir(&self) {
+    // fails while backups are left
+    let _ = std::fs::remove_dir(&self.dir);
+  }
+}
+
+/// Whether the backup at `path` belongs to another instance which is still
+/// running, going by the process id its name starts with.
+fn is_live(path: &Path) -> bool {
+  let pid = path
+    .file_stem()
+    .and_then(|stem| stem.to_str()?.split('-').next()?.parse::<u32>().ok());
+  match pid {
+    // this process didn't write any yet, so they are left from one before
+    Some(pid) => pid != std::process::id() && is_running(pid),
+    None => false,
+  }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+  // signal 0 only checks whether the process exists
+  let exists = unsafe { libc::kill(pid as libc::pid_t, 0) == 0 };
+  exists || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+  false
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn backups_outlive_a_crash() {
+    let workspace = std::env::temp_dir()
+      .join(format!("devcode-recovery-{}", std::process::id()));
+    let backup = |id| {
+      Some(Backup {
+        path: None,
+        name: format!("untitled-{}", id),
+        text: String::from("fn main() {}"),
+      })
+    };
+
+    let mut recovery = Recovery::new(&workspace);
+    assert!(recovery.leftovers().is_empty());
+    recovery.sync(&[(1, 3), (2, 1)], backup).unwrap();
+    // buffer 2 was saved, the change to buffer 1 waits for the interval
+    recovery.sync(&[(1, 4)], backup).unwrap();
+    assert!(recovery.deadline().is_some());
+
+    // the editor crashed
+    let mut recovery = Recovery::new(&workspace);
+    let leftovers = recovery.leftovers();
+    assert_eq!(leftovers.len(), 1);
+    assert_eq!(leftovers[0].name, "untitled-1");
+    recovery.clear_leftovers();
+    assert!(Recovery::new(&workspace).leftovers().is_empty());
+
+    // backups of another instance are left to it while it runs
+    #[cfg(unix)]
+    {
+      let mut other = std::process::Command::new("sleep")
+        .arg("30")
+        .spawn()
+        .unwrap();
+      let dir = workspace.join(".devcode").join("recovery");
+      std::fs::create_dir_all(&dir).unwrap();
+      let file = dir.join(format!("{}-1.toml", other.id()));
+      std::fs::write(&file, toml::to_string(&backup(1)).unwrap()).unwrap();
+      assert!(Recovery::new(&workspace).leftovers().is_empty());
+      other.kill().unwrap();
+      other.wait().unwrap();
+      assert_eq!(Recovery::new(&workspace).leftovers().len(), 1);
+      std::fs::remove_file(file).unwrap();
+    }
+
+    recovery.sync(&[(5, 1)], backup).unwrap();
+    recovery.clear();
+    assert!(!workspace.join(".devcode").join("recovery").exists());
+    std::fs::remove_dir_all(workspace).unwrap();
+  }
+}
@@ -0,0 +1,298 @@
+use crate::lsp::uri_to_path;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A replacement in a file. Positions are zero based lines and UTF-16
+/// columns, as sent by language servers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+  pub start: (usize, usize),
+  pub end: (usize, usize),
+  pub new_text: String,
+  /// Whether the edit is applied, users can exclude single occurrences.
+  pub included: bool,
+}
+
+/// The edits a rename makes to a single file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileEdits {
+  pub path: PathBuf,
+  pub edits: Vec<Edit>,
+  /// The version of the open file the edits were made for, if known.
+  pub version: Option<usize>,
+}
+
+fn position(value: &Value) -> Result<(usize, usize), anyhow::Error> {
+  match (value["line"].as_u64(), value["character"].as_u64()) {
+    (Some(line), Some(character)) => Ok((line as usize, character as usize)),
+    _ => anyhow::bail!("invalid position: {}", value),
+  }
+}
+
+/// A list of `TextEdit`s, sorted by position.
+pub fn text_edits(value: &Value) -> Result<Vec<Edit>, anyhow::Error> {
+  let mut edits = value
+    .as_array()
+    .ok_or_else(|| anyhow::anyhow!("expected a list of edits"))?
+    .iter()
+    .map(|edit| {
+      Ok(Edit {
+        start: position(&edit["range"]["start"])?,
+        end: position(&edit["range"]["end"])?,
+        new_text: edit["newText"].as_str().unwrap_or_default().to_string(),
+        included: true,
+      })
+    })
+    .collect::<Result<Vec<_>, anyhow::Error>>()?;
+  edits.sort_by_key(|edit| edit.start);
+  Ok(edits)
+}
+
+/// The text edits of a `WorkspaceEdit`, grouped by file and sorted by path
+/// and position.
+pub fn parse_workspace_edit(
+  value: &Value,
+) -> Result<Vec<FileEdits>, anyhow::Error> {
+  let path = |uri: &Value| {
+    uri
+      .as_str()
+      .and_then(uri_to_path)
+      .ok_or_else(|| anyhow::anyhow!("unsupported uri: {}", uri))
+  };
+
+  let mut files = vec![];
+  if let Some(changes) = value["documentChanges"].as_array() {
+    for change in changes {
+      if change.get("kind").is_some() {
+        anyhow::bail!("edits which create, move or delete files");
+      }
+      files.push(FileEdits {
+        path: path(&change["textDocument"]["uri"])?,
+        edits: text_edits(&change["edits"])?,
+        version: change["textDocument"]["version"]
+          .as_u64()
+          .map(|version| version as usize),
+      });
+    }
+  } else if let Some(changes) = value["changes"].as_object() {
+    for (uri, edits) in changes {
+      files.push(FileEdits {
+        path: path(&Value::from(uri.as_str()))?,
+        edits: text_edits(edits)?,
+        version: None,
+      });
+    }
+  }
+  files.retain(|file| !file.edits.is_empty());
+  files.sort_by(|a, b| a.path.cmp(&b.path));
+  Ok(files)
+}
+
+/// Byte offset of the UTF-16 `column` in `line`, clamped to its length.
+pub fn utf16_to_byte(line: &str, column: usize) -> usize {
+  let mut units = 0;
+  for (i, ch) in line.char_indices() {
+    if units >= column {
+      return i;
+    }
+    units += ch.len_utf16();
+  }
+  line.len()
+}
+
+/// UTF-16 column of the grapheme `column` in `line`.
+pub fn grapheme_to_utf16(line: &str, column: usize) -> usize {
+  line
+    .graphemes(true)
+    .take(column)
+    .map(|grapheme| grapheme.encode_utf16().count())
+    .sum()
+}
+
+/// Byte offset of `position` in `lines` joined by newlines.
+fn offset(lines: &[String], (line, column): (usize, usize)) -> usize {
+  if line >= lines.len() {
+    let len = lines.iter().map(|line| line.len() + 1).sum::<usize>();
+    return len.saturating_sub(1);
+  }
+  let before = lines[..line]
+    .iter()
+    .map(|line| line.len() + 1)
+    .sum::<usize>();
+  before + utf16_to_byte(&lines[line], column)
+}
+
+/// `lines` with the included `edits` applied.
+pub fn apply(lines: &[String], edits: &[Edit]) -> Vec<String> {
+  let mut text = lines.join("\n");
+  let mut edits = edits
+    .iter()
+    .filter(|edit| edit.included)
+    .collect::<Vec<_>>();
+  edits.sort_by_key(|edit| edit.start);
+  // back to front so earlier offsets stay valid
+  for edit in edits.into_iter().rev() {
+    let start = offset(lines, edit.start);
+    let end = offset(lines, edit.end).max(start);
+    text.replace_range(start..end, &edit.new_text);
+  }
+  text.split('\n').map(String::from).collect()
+}
+
+fn staged(path: &Path) -> PathBuf {
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!(".{}.devcode-edit", name))
+}
+
+/// Writes the texts to their files, none of them unless all could be
+/// written next to the files first.
+pub fn write_all(files: &[(PathBuf, String)]) -> Result<(), anyhow::Error> {
+  for (i, (path, text)) in files.iter().enumerate() {
+    if let Err(err) = std::fs::write(staged(path), text) {
+      for (path, _) in &files[..=i] {
+        let _ = std::fs::remove_file(staged(path));
+      }
+      return Err(anyhow::anyhow!("{}: {}", path.display(), err));
+    }
+  }
+  for (path, _) in files {
+    std::fs::rename(staged(path), path)?;
+  }
+  Ok(())
+}
+
+/// The line `edit` starts on before and after applying it, trimmed.
+pub fn preview(lines: &[String], edit: &Edit) -> (String, String) {
+  let line = match lines.get(edit.start.0) {
+    Some(line) => line,
+    None => return (String::new(), edit.new_text.clone()),
+  };
+  let start = utf16_to_byte(line, edit.start.1);
+  let end = if edit.end.0 == edit.start.0 {
+    utf16_to_byte(line, edit.end.1).max(start)
+  } else {
+    line.len()
+  };
+  let after = format!("{}{}{}", &line[..start], edit.new_text, &line[end..]);
+  (line.trim().to_string(), after.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn edit(start: (usize, usize), end: (usize, usize), text: &str) -> Edit {
+    Edit {
+      start,
+      end,
+      new_text: text.to_string(),
+      included: true,
+    }
+  }
+
+  fn range(line: usize, start: usize, end: usize) -> Value {
+    json!({
+      "start": { "line": line, "character": start },
+      "end": { "line": line, "character": end },
+    })
+  }
+
+  #[test]
+  fn workspace_edits() {
+    let changes = json!({
+      "changes": {
+        "file:///b.rs": [
+          { "range": range(3, 0, 3), "newText": "bar" },
+          { "range": range(1, 4, 7), "newText": "bar" },
+        ],
+        "file:///a.rs": [{ "range": range(0, 0, 3), "newText": "bar" }],
+        "file:///c.rs": [],
+      }
+    });
+    let files = parse_workspace_edit(&changes).unwrap();
+    assert_eq!(
+      files
+        .iter()
+        .map(|file| (file.path.to_str().unwrap(), file.edits.len()))
+        .collect::<Vec<_>>(),
+      vec![("/a.rs", 1), ("/b.rs", 2)]
+    );
+    assert_eq!(files[1].edits[0], edit((1, 4), (1, 7), "bar"));
+
+    let document_changes = json!({
+      "documentChanges": [{
+        "textDocument": { "uri": "file:///a.rs", "version": 1 },
+        "edits": [{ "range": range(0, 0, 3), "newText": "bar" }],
+      }]
+    });
+    let versioned = parse_workspace_edit(&document_changes).unwrap();
+    assert_eq!(versioned[0].edits, files[0].edits);
+    assert_eq!(versioned[0].version, Some(1));
+
+    let create = json!({
+      "documentChanges": [{ "kind": "create", "uri": "file:///d.rs" }]
+    });
+    assert!(parse_workspace_edit(&create).is_err());
+  }
+
+  #[test]
+  fn applying() {
+    let lines = vec![
+      "let foo = 1;".to_string(),
+      "/* ä😀 */ foo + foo".to_string(),
+    ];
+    let mut edits = vec![
+      edit((0, 4), (0, 7), "bar"),
+      // the emoji takes up two UTF-16 units
+      edit((1, 10), (1, 13), "bar"),
+      edit((1, 16), (1, 19), "bar"),
+    ];
+    assert_eq!(
+      apply(&lines, &edits),
+      vec!["let bar = 1;", "/* ä😀 */ bar + bar"]
+    );
+
+    edits[1].included = false;
+    assert_eq!(
+      apply(&lines, &edits),
+      vec!["let bar = 1;", "/* ä😀 */ foo + bar"]
+    );
+
+    // edits spanning lines
+    assert_eq!(
+      apply(&lines, &[edit((0, 11), (1, 9), "")]),
+      vec!["let foo = 1 foo + foo"]
+    );
+  }
+
+  #[test]
+  fn writing_all_or_nothing() {
+    let dir = std::env::temp_dir()
+      .join(format!("devcode-rename-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let a = dir.join("a.rs");
+    std::fs::write(&a, "foo").unwrap();
+
+    let missing = dir.join("missing").join("b.rs");
+    let files = vec![(a.clone(), "bar".to_string()), (missing, String::new())];
+    assert!(write_all(&files).is_err());
+    assert_eq!(std::fs::read_to_string(&a).unwrap(), "foo");
+
+    write_all(&files[..1]).unwrap();
+    assert_eq!(std::fs::read_to_string(&a).unwrap(), "bar");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn previews() {
+    let lines = vec!["  let foo = foo;".to_string()];
+    assert_eq!(
+      preview(&lines, &edit((0, 12), (0, 15), "bar")),
+      ("let foo = foo;".to_string(), "let foo = bar;".to_string())
+    );
+    assert_eq!(grapheme_to_utf16("ä😀x", 2), 3);
+  }
+}
@@ -0,0 +1,289 @@
+use crate::config::Theme;
+use crate::renderer::input::line_length;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use serde::{Deserialize, Serialize};
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+pub const ACTIVITY_BAR_WIDTH: f32 = 40.0;
+const ITEM_HEIGHT: f32 = 40.0;
+const INDICATOR_WIDTH: f32 = 2.0;
+const BADGE_SIZE: f32 = 16.0;
+const VIEW_COUNT: usize = 6;
+
+/// What the sidebar shows.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SidebarView {
+  Explorer,
+  Outline,
+  Search,
+  SourceControl,
+  Debug,
+  Extensions,
+}
+
+impl SidebarView {
+  pub const ALL: [SidebarView; VIEW_COUNT] = [
+    SidebarView::Explorer,
+    SidebarView::Outline,
+    SidebarView::Search,
+    SidebarView::SourceControl,
+    SidebarView::Debug,
+    SidebarView::Extensions,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      SidebarView::Explorer => "Explorer",
+      SidebarView::Outline => "Outline",
+      SidebarView::Search => "Search",
+      SidebarView::SourceControl => "Source Control",
+      SidebarView::Debug => "Debug",
+      SidebarView::Extensions => "Extensions",
+    }
+  }
+
+  fn icon(self) -> &'static str {
+    match self {
+      SidebarView::Explorer => "E",
+      SidebarView::Outline => "O",
+      SidebarView::Search => "S",
+      SidebarView::SourceControl => "G",
+      SidebarView::Debug => "D",
+      SidebarView::Extensions => "X",
+    }
+  }
+}
+
+/// The strip of sidebar toggles on the left edge of the window.
+pub struct ActivityBar {
+  font: FontArc,
+  font_height: f32,
+  theme: Theme,
+  pub active: SidebarView,
+  badges: [Option<usize>; VIEW_COUNT],
+  rect: Rectangle,
+  indicator: Rectangle,
+  badge_rects: Vec<Rectangle>,
+  dimensions: Dimensions,
+}
+
+impl ActivityBar {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    let badge_rects = SidebarView::ALL
+      .iter()
+      .map(|_| {
+        Rectangle::new(
+          device,
+          screen_size,
+          dimensions,
+          theme.selection.rgb(),
+          None,
+        )
+      })
+      .collect();
+
+    Self {
+      font,
+      font_height,
+      theme,
+      active: SidebarView::Explorer,
+      badges: [None; VIEW_COUNT],
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.activity_bar.rgb(),
+        None,
+      ),
+      indicator: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.selection.rgb(),
+        None,
+      ),
+      badge_rects,
+      dimensions,
+    }
+  }
+
+  fn index(view: SidebarView) -> usize {
+    SidebarView::ALL.iter().position(|v| *v == view).unwrap()
+  }
+
+  fn item_y(&self, index: usize) -> f32 {
+    self.dimensions.y + (index as f32 * ITEM_HEIGHT)
+  }
+
+  /// Shows a number on the icon of `view`, or removes it for `None` or zero.
+  pub fn set_badge(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    view: SidebarView,
+    count: Option<usize>,
+  ) {
+    let count = count.filter(|count| *count != 0);
+    let badge = &mut self.badges[Self::index(view)];
+    if *badge != count {
+      *badge = count;
+      self.layout(screen_size);
+    }
+  }
+
+  pub fn set_active(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    view: SidebarView,
+  ) {
+    self.active = view;
+    self.layout(screen_size);
+  }
+
+  /// The view whose icon is at `position`, relative to the bar.
+  pub fn item_at(
+    &self,
+    position: PhysicalPosition<f32>,
+  ) -> Option<SidebarView> {
+    SidebarView::ALL
+      .get((position.y / ITEM_HEIGHT).floor() as usize)
+      .copied()
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.layout(screen_size);
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    self.rect.resize(screen_size, self.dimensions);
+    self.indicator.resize(
+      screen_size,
+      Dimensions {
+        y: self.item_y(Self::index(self.active)),
+        width: INDICATOR_WIDTH,
+        height: ITEM_HEIGHT,
+        ..self.dimensions
+      },
+    );
+
+    for (i, rect) in self.badge_rects.iter_mut().enumerate() {
+      let size = if self.badges[i].is_some() {
+        BADGE_SIZE
+      } else {
+        0.0
+      };
+      rect.resize(
+        screen_size,
+        Dimensions {
+          x: self.dimensions.x + self.dimensions.width - BADGE_SIZE - 2.0,
+          y: self.dimensions.y + (i as f32 * ITEM_HEIGHT) + 2.0,
+          width: size,
+          height: size,
+        },
+      );
+    }
+  }
+}
+
+impl super::RenderElement for ActivityBar {
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.activity_bar.rgb());
+    self.indicator.set_color(theme.selection.rgb());
+    for rect in &mut self.badge_rects {
+      rect.set_color(theme.selection.rgb());
+    }
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    for (i, view) in SidebarView::ALL.iter().enumerate() {
+      let icon = view.icon();
+      let icon_width = line_length(icon, self.font.clone(), self.font_height);
+      let color = if *view == self.active {
+        self.theme.text.rgba()
+      } else {
+        self.theme.activity_bar_text.rgba()
+      };
+      glyph_brush.queue(Section {
+        screen_position: (
+          self.dimensions.x + ((self.dimensions.width - icon_width) / 2.0),
+          self.item_y(i) + ((ITEM_HEIGHT - self.font_height) / 2.0),
+        ),
+        text: vec![Text::new(icon)
+          .with_color(color)
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+
+      if let Some(count) = self.badges[i] {
+        let badge = if count > 99 {
+          String::from("99+")
+        } else {
+          count.to_string()
+        };
+        let rect = &self.badge_rects[i].dimensions;
+        glyph_brush.queue(Section {
+          screen_position: (rect.x + 2.0, rect.y),
+          text: vec![Text::new(&badge)
+            .with_color(self.theme.text.rgba())
+            .with_scale(BADGE_SIZE * 0.8)],
+          ..Section::default()
+        });
+      }
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    let mut vec = vec![&self.rect, &self.indicator];
+    vec.extend(self.badge_rects.iter());
+    vec
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
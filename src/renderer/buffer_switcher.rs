@@ -0,0 +1,614 @@
+use crate::config::{Color, Theme};
+use crate::fuzzy;
+use crate::renderer::code_view_tabs::canonical;
+use crate::renderer::input::line_length;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::PhysicalSize;
+use winit::event::VirtualKeyCode;
+
+const SWITCHER_TOP: f32 = 60.0;
+const SWITCHER_MAX_WIDTH: f32 = 600.0;
+const SWITCHER_PADDING: f32 = 8.0;
+const ROW_PADDING: f32 = 6.0;
+const MAX_VISIBLE_ENTRIES: usize = 10;
+
+/// An open tab listed by the switcher.
+pub struct OpenTab {
+  pub index: usize,
+  pub name: String,
+  pub path: Option<PathBuf>,
+  pub modified: bool,
+}
+
+/// What accepting an entry of the switcher should do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SwitcherAction {
+  /// Activate the tab at the index.
+  Show(usize),
+  Open(PathBuf),
+  /// Move the cursor of the active tab to a zero based line and column.
+  Goto(usize, usize),
+  /// Open a file, or switch to its tab, at a zero based line and column.
+  OpenAt(PathBuf, usize, usize),
+}
+
+/// A symbol listed by the switcher.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolEntry {
+  pub name: String,
+  /// Its kind, or where it is for the symbols of the workspace.
+  pub detail: String,
+  pub action: SwitcherAction,
+}
+
+/// What the query asks for, told by its first character like in the quick
+/// open of VS Code.
+#[derive(Debug, PartialEq)]
+enum Query<'a> {
+  Files(&'a str),
+  /// `@`, the symbols of the active tab.
+  Symbols(&'a str),
+  /// `#`, the symbols of the workspace.
+  WorkspaceSymbols(&'a str),
+  /// `:`, a one based line of the active tab and maybe a column, as in
+  /// `:12:5`.
+  Line(Option<(usize, Option<usize>)>),
+}
+
+fn parse_query(query: &str) -> Query<'_> {
+  if let Some(query) = query.strip_prefix('@') {
+    Query::Symbols(query)
+  } else if let Some(query) = query.strip_prefix('#') {
+    Query::WorkspaceSymbols(query)
+  } else if let Some(position) = query.strip_prefix(':') {
+    let mut parts = position.trim().splitn(2, ':');
+    let line = parts.next().and_then(|line| line.parse().ok());
+    let column = parts.next().and_then(|column| column.parse().ok());
+    Query::Line(line.map(|line| (line, column)))
+  } else {
+    Query::Files(query)
+  }
+}
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+  name: String,
+  /// Where the file is, relative to the workspace.
+  location: String,
+  modified: bool,
+  action: SwitcherAction,
+}
+
+/// The tabs, most recently used first, and the files which aren't open
+/// matching `query`, best first.
+fn entries(
+  query: &str,
+  tabs: &[OpenTab],
+  files: &[PathBuf],
+  workspace: &Path,
+) -> Vec<Entry> {
+  let location = |path: &Path| {
+    let path = canonical(path);
+    path
+      .strip_prefix(workspace)
+      .unwrap_or(&path)
+      .display()
+      .to_string()
+  };
+  let open = tabs
+    .iter()
+    .filter_map(|tab| Some(canonical(tab.path.as_deref()?)))
+    .collect::<Vec<_>>();
+
+  let mut entries = tabs
+    .iter()
+    .map(|tab| Entry {
+      name: tab.name.clone(),
+      location: tab.path.as_deref().map(location).unwrap_or_default(),
+      modified: tab.modified,
+      action: SwitcherAction::Show(tab.index),
+    })
+    .collect::<Vec<_>>();
+  entries.extend(
+    files
+      .iter()
+      .filter(|path| !open.contains(&canonical(path)))
+      .map(|path| Entry {
+        name: path
+          .file_name()
+          .map(|name| name.to_string_lossy().into_owned())
+          .unwrap_or_default(),
+        location: location(path),
+        modified: false,
+        action: SwitcherAction::Open(path.clone()),
+      }),
+  );
+
+  let mut scored = entries
+    .into_iter()
+    .filter_map(|entry| {
+      let text = if entry.location.is_empty() {
+        &entry.name
+      } else {
+        &entry.location
+      };
+      Some((fuzzy::score(query, text)?, entry))
+    })
+    .collect::<Vec<_>>();
+  // stable, so equal matches keep tabs ahead of files
+  scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+  scored.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// The `symbols` whose name matches `query`, best first.
+fn symbol_entries(query: &str, symbols: &[SymbolEntry]) -> Vec<Entry> {
+  let mut scored = symbols
+    .iter()
+    .filter_map(|symbol| Some((fuzzy::score(query, &symbol.name)?, symbol)))
+    .collect::<Vec<_>>();
+  // stable, so equal matches stay in the order they are declared in
+  scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+  scored
+    .into_iter()
+    .map(|(_, symbol)| Entry {
+      name: symbol.name.clone(),
+      location: symbol.detail.clone(),
+      modified: false,
+      action: symbol.action.clone(),
+    })
+    .collect()
+}
+
+/// Going to a one based `line` and `column` of the active tab.
+fn line_entry(line: usize, column: Option<usize>) -> Entry {
+  let name = match column {
+    Some(column) => format!("Go to Line {}, Column {}", line, column),
+    None => format!("Go to Line {}", line),
+  };
+  Entry {
+    name,
+    location: String::new(),
+    modified: false,
+    action: SwitcherAction::Goto(
+      line.saturating_sub(1),
+      column.unwrap_or(1).saturating_sub(1),
+    ),
+  }
+}
+
+/// Lists the open tabs and the files of the workspace to switch to, or
+/// symbols and lines to go to depending on how the query starts.
+pub struct BufferSwitcher {
+  pub visible: bool,
+  font: FontArc,
+  font_height: f32,
+  theme: Theme,
+  workspace: PathBuf,
+  query: String,
+  tabs: Vec<OpenTab>,
+  files: Vec<PathBuf>,
+  /// The symbols of the active tab.
+  symbols: Vec<SymbolEntry>,
+  /// The symbols of the open tabs and the ones language servers found.
+  workspace_symbols: Vec<SymbolEntry>,
+  entries: Vec<Entry>,
+  selected: usize,
+  first_visible: usize,
+  rect: Rectangle,
+  selection_rect: Rectangle,
+  cursor_rect: Rectangle,
+  dimensions: Dimensions,
+}
+
+impl BufferSwitcher {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    workspace: PathBuf,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    let rect = |color: Color| {
+      Rectangle::new(device, screen_size, dimensions, color.rgb(), None)
+    };
+    Self {
+      visible: false,
+      font,
+      font_height,
+      theme,
+      workspace,
+      query: String::new(),
+      tabs: vec![],
+      files: vec![],
+      symbols: vec![],
+      workspace_symbols: vec![],
+      entries: vec![],
+      selected: 0,
+      first_visible: 0,
+      rect: rect(theme.overlay),
+      selection_rect: rect(theme.selection),
+      cursor_rect: rect(theme.cursor),
+      dimensions,
+    }
+  }
+
+  fn row_height(&self) -> f32 {
+    self.font_height + ROW_PADDING
+  }
+
+  /// Lists `tabs`, most recently used first, and `files`. The previous tab
+  /// is selected when `previous` is set, to switch back to it right away.
+  pub fn open(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    tabs: Vec<OpenTab>,
+    files: Vec<PathBuf>,
+    previous: bool,
+  ) {
+    self.visible = true;
+    self.tabs = tabs;
+    self.files = files;
+    self.query.clear();
+    self.update_entries();
+    if previous && self.tabs.len() > 1 {
+      self.selected = 1;
+    }
+    self.layout(screen_size);
+  }
+
+  pub fn close(&mut self) {
+    self.visible = false;
+  }
+
+  /// Sets the `symbols` of the active tab and those of the `workspace`,
+  /// listed for queries starting with `@` and `#`.
+  pub fn set_symbols(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    symbols: Vec<SymbolEntry>,
+    workspace: Vec<SymbolEntry>,
+  ) {
+    self.symbols = symbols;
+    self.workspace_symbols = workspace;
+    self.update_entries();
+    self.layout(screen_size);
+  }
+
+  /// Adds the symbols of the workspace a language server found.
+  pub fn add_workspace_symbols(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    symbols: Vec<SymbolEntry>,
+  ) {
+    // the open tabs already listed their own, at the name rather than the
+    // start of the declaration
+    let place = |symbol: &SymbolEntry| match &symbol.action {
+      SwitcherAction::OpenAt(path, line, _) => {
+        Some((symbol.name.clone(), canonical(path), *line))
+      }
+      _ => None,
+    };
+    let mut listed = self
+      .workspace_symbols
+      .iter()
+      .map(place)
+      .collect::<HashSet<_>>();
+    for symbol in symbols {
+      if listed.insert(place(&symbol)) {
+        self.workspace_symbols.push(symbol);
+      }
+    }
+    let selected = self.selected;
+    self.update_entries();
+    // the results come in while looking through the list
+    self.selected = selected.min(self.entries.len().saturating_sub(1));
+    self.scroll_to_selected();
+    self.layout(screen_size);
+  }
+
+  /// The query for the symbols of the workspace, if it asks for them.
+  pub fn workspace_query(&self) -> Option<&str> {
+    match parse_query(&self.query) {
+      Query::WorkspaceSymbols(query) if self.visible => Some(query),
+      _ => None,
+    }
+  }
+
+  fn update_entries(&mut self) {
+    self.entries = match parse_query(&self.query) {
+      Query::Files(query) => {
+        entries(query, &self.tabs, &self.files, &self.workspace)
+      }
+      Query::Symbols(query) => symbol_entries(query, &self.symbols),
+      Query::WorkspaceSymbols(query) => {
+        symbol_entries(query, &self.workspace_symbols)
+      }
+      Query::Line(Some((line, column))) => vec![line_entry(line, column)],
+      Query::Line(None) => vec![],
+    };
+    self.selected = 0;
+    self.first_visible = 0;
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let width = (screen_size.width * 0.6).min(SWITCHER_MAX_WIDTH);
+    let rows = 1 + self.entries.len().min(MAX_VISIBLE_ENTRIES);
+    self.dimensions = Dimensions {
+      x: (screen_size.width - width) / 2.0,
+      y: SWITCHER_TOP,
+      width,
+      height: (SWITCHER_PADDING * 2.0) + (rows as f32 * self.row_height()),
+    };
+    self.rect.resize(screen_size, self.dimensions);
+
+    let row = self.selected - self.first_visible + 1;
+    self.selection_rect.resize(
+      screen_size,
+      Dimensions {
+        y: self.dimensions.y
+          + SWITCHER_PADDING
+          + row as f32 * self.row_height(),
+        height: if self.entries.is_empty() {
+          0.0
+        } else {
+          self.row_height()
+        },
+        ..self.dimensions
+      },
+    );
+
+    let query_width =
+      line_length(&self.query, self.font.clone(), self.font_height);
+    self.cursor_rect.resize(
+      screen_size,
+      Dimensions {
+        x: self.dimensions.x + SWITCHER_PADDING + query_width + 1.0,
+        y: self.dimensions.y + SWITCHER_PADDING + (ROW_PADDING / 2.0),
+        width: 2.0,
+        height: self.font_height,
+      },
+    );
+  }
+
+  /// Selects the entry below the selected one, wrapping around at the end.
+  pub fn select_next(&mut self, screen_size: PhysicalSize<f32>) {
+    if self.entries.is_empty() {
+      return;
+    }
+    self.selected = (self.selected + 1) % self.entries.len();
+    self.scroll_to_selected();
+    self.layout(screen_size);
+  }
+
+  fn scroll_to_selected(&mut self) {
+    self.first_visible = self.first_visible.min(self.selected);
+    if self.selected >= self.first_visible + MAX_VISIBLE_ENTRIES {
+      self.first_visible = self.selected + 1 - MAX_VISIBLE_ENTRIES;
+    }
+  }
+
+  pub fn input_special(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) {
+    match key {
+      VirtualKeyCode::Escape => self.close(),
+      VirtualKeyCode::Up => self.selected = self.selected.saturating_sub(1),
+      VirtualKeyCode::Down => {
+        if self.selected + 1 < self.entries.len() {
+          self.selected += 1;
+        }
+      }
+      _ => return,
+    }
+    self.scroll_to_selected();
+    self.layout(screen_size);
+  }
+
+  /// Returns the action of the selected entry when it got accepted.
+  pub fn input_char(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    ch: char,
+  ) -> Option<SwitcherAction> {
+    match ch {
+      '\r' => {
+        let action = self
+          .entries
+          .get(self.selected)
+          .map(|entry| entry.action.clone());
+        if action.is_some() {
+          self.close();
+        }
+        return action;
+      }
+      // backspace
+      '\u{7f}' | '\u{8}' => {
+        self.query.pop();
+      }
+      _ if ch.is_control() => return None,
+      _ => self.query.push(ch),
+    }
+    self.update_entries();
+    self.layout(screen_size);
+    None
+  }
+}
+
+impl super::RenderElement for BufferSwitcher {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.layout(screen_size);
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.overlay.rgb());
+    self.selection_rect.set_color(theme.selection.rgb());
+    self.cursor_rect.set_color(theme.cursor.rgb());
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let x = self.dimensions.x + SWITCHER_PADDING;
+    let y = self.dimensions.y + SWITCHER_PADDING + (ROW_PADDING / 2.0);
+    let row_height = self.row_height();
+
+    glyph_brush.queue(Section {
+      screen_position: (x, y),
+      text: vec![Text::new(&self.query)
+        .with_color(self.theme.overlay_text.rgba())
+        .with_scale(self.font_height)],
+      ..Section::default()
+    });
+
+    for (i, entry) in self
+      .entries
+      .iter()
+      .skip(self.first_visible)
+      .take(MAX_VISIBLE_ENTRIES)
+      .enumerate()
+    {
+      let marker = if entry.modified { "\u{25cf} " } else { "  " };
+      glyph_brush.queue(Section {
+        screen_position: (x, y + ((i + 1) as f32 * row_height)),
+        text: vec![
+          Text::new(marker)
+            .with_color(self.theme.warning.rgba())
+            .with_scale(self.font_height),
+          Text::new(&entry.name)
+            .with_color(self.theme.overlay_text.rgba())
+            .with_scale(self.font_height),
+          Text::new("  ").with_scale(self.font_height),
+          Text::new(&entry.location)
+            .with_color(self.theme.gutter_text.rgba())
+            .with_scale(self.font_height),
+        ],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect, &self.selection_rect, &self.cursor_rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tabs_then_files() {
+    let workspace = Path::new("/nonexistent/workspace");
+    let tab = |index: usize, name: &str| OpenTab {
+      index,
+      name: name.to_string(),
+      path: Some(workspace.join("src").join(name)),
+      modified: index == 2,
+    };
+    let tabs = vec![tab(2, "main.rs"), tab(0, "lib.rs")];
+    let files = vec![
+      workspace.join("Cargo.toml"),
+      workspace.join("src").join("lib.rs"),
+      workspace.join("src").join("main.rs"),
+    ];
+    let actions = |query| {
+      entries(query, &tabs, &files, workspace)
+        .into_iter()
+        .map(|entry| entry.action)
+        .collect::<Vec<_>>()
+    };
+
+    // the files which are open are only listed once
+    assert_eq!(
+      actions(""),
+      vec![
+        SwitcherAction::Show(2),
+        SwitcherAction::Show(0),
+        SwitcherAction::Open(workspace.join("Cargo.toml")),
+      ]
+    );
+    // the best match comes first, even if it isn't open
+    assert_eq!(
+      actions("car"),
+      vec![
+        SwitcherAction::Open(workspace.join("Cargo.toml")),
+        SwitcherAction::Show(2),
+      ]
+    );
+    assert_eq!(actions("srcl"), vec![SwitcherAction::Show(0)]);
+
+    let entries = entries("", &tabs, &[], workspace);
+    assert_eq!(
+      entries[0].location,
+      Path::new("src").join("main.rs").display().to_string()
+    );
+    assert!(entries[0].modified);
+  }
+
+  #[test]
+  fn query_prefixes() {
+    assert_eq!(parse_query("main"), Query::Files("main"));
+    assert_eq!(parse_query("@new"), Query::Symbols("new"));
+    assert_eq!(parse_query("#Config"), Query::WorkspaceSymbols("Config"));
+    assert_eq!(parse_query(":12"), Query::Line(Some((12, None))));
+    assert_eq!(parse_query(": 12:5"), Query::Line(Some((12, Some(5)))));
+    assert_eq!(parse_query(":"), Query::Line(None));
+    assert_eq!(line_entry(12, Some(5)).action, SwitcherAction::Goto(11, 4));
+
+    let symbol = |name: &str, line| SymbolEntry {
+      name: name.to_string(),
+      detail: String::from("fn"),
+      action: SwitcherAction::Goto(line, 0),
+    };
+    let symbols = [symbol("new", 1), symbol("render", 5), symbol("run", 9)];
+    let names = |query| {
+      symbol_entries(query, &symbols)
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(names(""), vec!["new", "render", "run"]);
+    assert_eq!(names("run"), vec!["run"]);
+  }
+}
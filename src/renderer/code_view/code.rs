@@ -1,43 +1,219 @@
-use super::super::input::{max_line_length, Cursor};
+use super::super::input::{self, max_line_length};
 use super::super::rectangle::Rectangle;
+use super::decorations::{
+  self, Decoration, Decorations, Source, Style, Underline,
+};
+use super::gutter;
+use crate::brackets::{self, Brackets};
+use crate::colors::{self, ColorLiteral};
+use crate::config::{BracketsConfig, Color, Theme};
+use crate::diff;
+use crate::editor::{Change, EditorState, Message};
+use crate::highlight::Grammar;
 use crate::renderer::Dimensions;
-use std::cell::{Ref, RefCell};
+use crate::segments::{Segments, Start, LONG_LINE};
+use crate::shaping::{Ligatures, Shaped};
+use crate::structure::{Position, Span};
+use crate::viewport::{self, Overscroll};
+use crate::wrap::{self, VisualRow, WrapConfig};
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
-use wgpu_glyph::ab_glyph::FontArc;
-use wgpu_glyph::{GlyphPositioner, Layout, Section, SectionGeometry, Text};
+use unicode_segmentation::UnicodeSegmentation;
+use wgpu_glyph::ab_glyph::{Font, FontArc, ScaleFont};
+use wgpu_glyph::{Section, Text};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::VirtualKeyCode;
+use winit::window::CursorIcon;
+
+const RULER_WIDTH: f32 = 1.0;
+const GUIDE_WIDTH: f32 = 1.0;
+/// How much of its bracket color a guide takes, over the background.
+const GUIDE_SHADE: f32 = 0.5;
+/// Width of the cursor while inserting.
+const CURSOR_WIDTH: f32 = 4.0;
+/// How much of the diff colors shows behind changed lines, words that
+/// changed getting all of it.
+const DIFF_LINE_SHADE: f32 = 0.4;
+/// How much of the selection color shows behind the lines of the cursors.
+const CURRENT_LINE_SHADE: f32 = 0.25;
+/// How much of the warning color shows behind search matches.
+const MATCH_SHADE: f32 = 0.35;
+/// Graphemes of long lines measured a frame at most.
+const LAYOUT_BUDGET: usize = 1 << 18;
+/// Spaces between the end of a line and its virtual text.
+const VIRTUAL_TEXT_GAP: f32 = 2.0;
 
 pub struct Code {
   font: FontArc,
   font_height: f32,
-  text: Rc<RefCell<Vec<String>>>,
+  theme: Theme,
+  /// The text, cursor and selection drawn.
+  state: EditorState,
+  wrap: WrapConfig,
+  /// The rows the lines are wrapped into, `None` if wrapping is off and no
+  /// lines are folded.
+  rows: Rc<RefCell<Option<Vec<VisualRow>>>>,
+  /// The folded lines, as inclusive ranges.
+  folds: Vec<(usize, usize)>,
   scroll_offset: PhysicalPosition<f64>,
-  cursor: Cursor,
+  cursor: Rectangle,
+  /// A rectangle per cursor besides the main one, only grown.
+  cursor_rects: Vec<Rectangle>,
   max_line_length: f32,
+  /// How far the long lines were measured, by line.
+  segments: RefCell<HashMap<usize, Segments>>,
+  /// Graphemes of long lines left to measure this frame.
+  layout_budget: Cell<usize>,
+  /// Whether measuring ran out of budget this frame, and the last one.
+  layout_cut: Cell<bool>,
+  layout_behind: bool,
+  /// The first color literal of each line in view and the line it's in.
+  swatches: Vec<(usize, ColorLiteral)>,
+  /// The removed and added lines, `None` unless the text is a diff.
+  diff: Option<Vec<diff::Changed>>,
+  /// Registered by everything drawn along with the text.
+  pub decorations: Decorations,
+  /// The backgrounds of the decorations in view, only grown.
+  background_rects: Vec<Rectangle>,
+  background_count: usize,
+  /// Their underlines, only grown.
+  mark_rects: Vec<Rectangle>,
+  mark_count: usize,
+  /// The virtual text after the lines in view, where it's drawn.
+  virtual_texts: Vec<((f32, f32), String, Color)>,
+  brackets: Brackets,
+  brackets_config: BracketsConfig,
+  /// A line per bracket pair guide in view, only grown.
+  guide_rects: Vec<Rectangle>,
+  guide_count: usize,
+  /// Height of the top covered by pinned declarations.
+  pub covered: f32,
+  /// Columns to draw a vertical line at.
+  pub rulers: Vec<usize>,
+  /// The ligatures drawn in place of the glyphs they join.
+  ligatures: Rc<Ligatures>,
+  /// Whether glyphs are placed on whole pixels.
+  pub hinting: bool,
+  overscroll: Overscroll,
+  /// Whether the cursor is kept in the middle of the view as it moves.
+  typewriter: bool,
+  /// A line per ruler, only grown.
+  ruler_rects: Vec<Rectangle>,
   pub dimensions: Dimensions,
 }
 
+/// Places a rectangle of `rects` at each of `placed`, growing them as
+/// needed, clipped to `region`.
+fn place_rects(
+  rects: &mut Vec<Rectangle>,
+  device: &wgpu::Device,
+  screen_size: PhysicalSize<f32>,
+  placed: &[(Dimensions, Color)],
+  region: Dimensions,
+) {
+  for (i, (dimensions, color)) in placed.iter().enumerate() {
+    let color = color.rgb();
+    match rects.get_mut(i) {
+      Some(rect) => {
+        rect.resize(screen_size, *dimensions);
+        rect.set_color(color);
+      }
+      None => rects.push(Rectangle::new(
+        device,
+        screen_size,
+        *dimensions,
+        color,
+        None,
+      )),
+    }
+    rects[i].region = Some(region.into());
+  }
+}
+
+/// The graphemes `start..end` of `line`.
+fn grapheme_slice(line: &str, start: usize, end: usize) -> &str {
+  let offset = |index| {
+    line
+      .grapheme_indices(true)
+      .nth(index)
+      .map_or(line.len(), |(i, _)| i)
+  };
+  &line[offset(start)..offset(end.max(start))]
+}
+
 impl Code {
+  /// The texts of `text`, the lines from `first` on.
   fn generate_glyph_text<'r>(
     &self,
     text: &'r Ref<'_, [String]>,
+    first: usize,
   ) -> Vec<Text<'r>> {
     text
       .iter()
-      .flat_map(|s| {
-        std::iter::once(
-          Text::new(s)
-            .with_color([0.9, 0.9, 0.9, 1.0])
-            .with_scale(self.font_height),
-        )
-        .chain(std::iter::once(
+      .enumerate()
+      .flat_map(|(i, s)| {
+        // long lines are queued a segment at a time
+        let texts = if s.len() > LONG_LINE {
+          vec![]
+        } else {
+          self.line_texts(s, first + i, 0)
+        };
+        texts.into_iter().chain(std::iter::once(
           Text::new("\n").with_scale(self.font_height),
         ))
       })
       .collect()
   }
 
+  /// `slice` of `line`, starting at its grapheme `start`, in the colors its
+  /// decorations give it.
+  fn line_texts<'r>(
+    &self,
+    slice: &'r str,
+    line: usize,
+    start: usize,
+  ) -> Vec<Text<'r>> {
+    let text = |text, color: Color| {
+      Text::new(text)
+        .with_color(color.rgba())
+        .with_scale(self.font_height)
+    };
+    let colors = self.decorations.foreground(line);
+    if !colors.iter().any(|(_, end, _)| *end > start) {
+      return vec![text(slice, self.theme.text)];
+    }
+
+    let mut texts = vec![];
+    let mut from = 0;
+    let mut current = self.theme.text;
+    for (column, (offset, _)) in slice.grapheme_indices(true).enumerate() {
+      let column = start + column;
+      let color = colors
+        .iter()
+        .rev()
+        .find(|(start, end, _)| (*start..*end).contains(&column))
+        .map_or(self.theme.text, |(_, _, color)| *color);
+      if color != current {
+        texts.push(text(&slice[from..offset], current));
+        from = offset;
+        current = color;
+      }
+    }
+    texts.push(text(&slice[from..], current));
+    texts
+  }
+
+  /// The color of brackets nested `depth` pairs deep, the error color for
+  /// those without a partner.
+  fn bracket_color(&self, depth: Option<usize>) -> Color {
+    match depth {
+      Some(depth) => self.theme.brackets[depth % self.theme.brackets.len()],
+      None => self.theme.error,
+    }
+  }
+
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     device: &wgpu::Device,
     screen_size: PhysicalSize<f32>,
@@ -45,16 +221,18 @@ impl Code {
     font_height: f32,
     dimensions: Dimensions,
     text: Rc<RefCell<Vec<String>>>,
+    rows: Rc<RefCell<Option<Vec<VisualRow>>>>,
+    theme: Theme,
   ) -> Self {
-    let cursor = Cursor::new(
+    let cursor = Rectangle::new(
       device,
       screen_size,
       Dimensions {
-        width: 4.0,
+        width: CURSOR_WIDTH,
         height: font_height,
         ..dimensions
       },
-      [0.7, 0.0, 0.0],
+      theme.cursor.rgb(),
       Some(dimensions.into()),
     );
 
@@ -64,128 +242,1326 @@ impl Code {
     Self {
       font,
       font_height,
-      text,
+      theme,
+      state: EditorState::new(text),
+      wrap: WrapConfig::default(),
+      rows,
+      folds: vec![],
       scroll_offset: PhysicalPosition { x: 0.0, y: 0.0 },
       cursor,
+      cursor_rects: vec![],
       max_line_length,
+      segments: RefCell::default(),
+      layout_budget: Cell::new(LAYOUT_BUDGET),
+      layout_cut: Cell::new(false),
+      layout_behind: false,
+      swatches: vec![],
+      diff: None,
+      decorations: Decorations::default(),
+      background_rects: vec![],
+      background_count: 0,
+      mark_rects: vec![],
+      mark_count: 0,
+      virtual_texts: vec![],
+      brackets: Brackets::default(),
+      brackets_config: BracketsConfig::default(),
+      guide_rects: vec![],
+      guide_count: 0,
+      covered: 0.0,
+      rulers: vec![],
+      ruler_rects: vec![],
+      ligatures: Rc::default(),
+      hinting: true,
+      overscroll: Overscroll::default(),
+      typewriter: false,
       dimensions,
     }
   }
-}
 
-impl super::super::input::TextInput for Code {
-  fn input_special(
+  pub fn set_dimensions(
     &mut self,
     screen_size: PhysicalSize<f32>,
-    key: VirtualKeyCode,
+    dimensions: Dimensions,
   ) {
-    super::super::input::input_special(
-      screen_size,
-      key,
-      &mut self.text.borrow_mut(),
-      &mut self.cursor,
-      self.font.clone(),
-      self.font_height,
-      PhysicalPosition {
-        x: self.dimensions.x,
-        y: 0.0,
-      },
-      self.scroll_offset.cast(),
-    );
+    self.dimensions = dimensions;
+    self.cursor.region = Some(dimensions.into());
+    self.rewrap();
+    self.clamp_scroll();
+    self.position_cursor(screen_size);
   }
 
-  fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char) {
-    self.max_line_length = super::super::input::input_char(
-      screen_size,
-      ch,
-      &mut self.text.borrow_mut(),
-      &mut self.cursor,
-      self.font.clone(),
+  /// Sets how far the text can be scrolled past its last line.
+  pub fn set_overscroll(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    overscroll: Overscroll,
+  ) {
+    self.overscroll = overscroll;
+    self.clamp_scroll();
+    self.position_cursor(screen_size);
+  }
+
+  /// Sets whether the cursor is kept in the middle of the view.
+  pub fn set_typewriter(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    typewriter: bool,
+  ) {
+    self.typewriter = typewriter;
+    if typewriter {
+      self.center_cursor();
+    }
+    self.clamp_scroll();
+    self.position_cursor(screen_size);
+  }
+
+  /// Scrolls the row of the cursor to the middle of the view.
+  fn center_cursor(&mut self) {
+    let (_, y) = self.cursor_offset();
+    let row = ((y as f64 - self.scroll_offset.y) / self.font_height as f64)
+      .round()
+      .max(0.0) as usize;
+    self.scroll_offset.y =
+      viewport::centered(row, self.font_height, self.dimensions.height);
+  }
+
+  /// Keeps the text in view after it or the view changed size, returning
+  /// whether it moved.
+  fn clamp_scroll(&mut self) -> bool {
+    // wrapped lines always fit
+    let width = if self.wrap.soft_wrap {
+      0.0
+    } else {
+      viewport::horizontal_limit(self.max_line_length, self.dimensions.width)
+    };
+    let range = viewport::vertical_range(
+      self.row_count(),
       self.font_height,
-      PhysicalPosition {
-        x: self.dimensions.x,
-        y: 0.0,
-      },
-      self.scroll_offset.cast(),
+      self.dimensions.height,
+      self.overscroll,
+      self.typewriter,
     );
+    let clamped = PhysicalPosition {
+      x: viewport::clamp(self.scroll_offset.x, width),
+      y: viewport::clamp_to(self.scroll_offset.y, range),
+    };
+    let moved = clamped != self.scroll_offset;
+    self.scroll_offset = clamped;
+    moved
   }
-}
 
-impl super::super::RenderElement for Code {
-  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
-    self.dimensions.width = screen_size.width - self.dimensions.x;
+  /// How far the text is scrolled down.
+  pub fn scroll_y(&self) -> f64 {
+    self.scroll_offset.y
+  }
 
-    self.cursor.rect.resize(
-      screen_size.cast(),
-      Dimensions {
-        y: self.font_height - (self.cursor.row as f32 * self.font_height),
-        ..self.cursor.rect.dimensions
+  pub fn set_wrap(&mut self, screen_size: PhysicalSize<f32>, wrap: WrapConfig) {
+    self.wrap = wrap;
+    self.rewrap();
+    self.clamp_scroll();
+    self.position_cursor(screen_size);
+  }
+
+  /// Width of `text` when drawn.
+  fn advance(&self, text: &str) -> f32 {
+    let font = self.font.as_scaled(self.font_height);
+    text
+      .chars()
+      .map(|ch| font.h_advance(font.glyph_id(ch)))
+      .sum()
+  }
+
+  /// Recomputes the rows the lines are wrapped into, leaving out the folded
+  /// ones.
+  fn rewrap(&mut self) {
+    let text = self.state.text.borrow();
+    let shown = |line: usize| {
+      !self
+        .folds
+        .iter()
+        .any(|(start, end)| (*start..=*end).contains(&line))
+    };
+    let rows = if self.wrap.soft_wrap {
+      let width = self.dimensions.width - self.cursor.dimensions.width;
+      let mut rows =
+        wrap::wrap(&text, width, &self.wrap, |text| self.advance(text));
+      rows.retain(|row| shown(row.line));
+      Some(rows)
+    } else if self.folds.is_empty() {
+      None
+    } else {
+      // a row per line shown, to its end
+      let rows =
+        (0..text.len())
+          .filter(|line| shown(*line))
+          .map(|line| VisualRow {
+            line,
+            start: 0,
+            end: usize::MAX,
+            continuation: None,
+          });
+      Some(rows.collect())
+    };
+    drop(text);
+    *self.rows.borrow_mut() = rows;
+  }
+
+  /// Hides the lines of `folds`, inclusive ranges.
+  pub fn set_folds(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    folds: Vec<(usize, usize)>,
+  ) {
+    if folds == self.folds {
+      return;
+    }
+    self.folds = folds;
+    self.rewrap();
+    self.clamp_scroll();
+    self.position_cursor(screen_size);
+  }
+
+  /// Where the text of `row` starts, relative to the left edge.
+  fn row_x(&self, row: &VisualRow) -> f32 {
+    match row.continuation {
+      Some(indent) => {
+        (indent as f32 * self.advance(" ")) + self.advance(&self.wrap.indicator)
+      }
+      None => 0.0,
+    }
+  }
+
+  /// Position of the cursor relative to the top left corner.
+  fn cursor_offset(&self) -> (f32, f32) {
+    let (row, column) = self.state.cursor();
+    self.offset_of(row, column)
+  }
+
+  /// Position of `column` of `line` relative to the top left corner.
+  fn offset_of(&self, row: usize, column: usize) -> (f32, f32) {
+    let rows = self.rows.borrow();
+    let rows = match rows.as_ref() {
+      Some(rows) => rows,
+      None => {
+        return (
+          self.scroll_offset.x as f32 + self.x_of(row, column),
+          self.scroll_offset.y as f32 + (row as f32 * self.font_height),
+        );
+      }
+    };
+
+    // a folded line is where the line its fold starts at is
+    let index = rows
+      .iter()
+      .position(|visual| visual.line == row && column < visual.end)
+      .or_else(|| rows.iter().rposition(|visual| visual.line <= row))
+      .unwrap_or(0);
+    let x = match rows.get(index) {
+      Some(_) if !self.wrap.soft_wrap => {
+        self.scroll_offset.x as f32 + self.x_of(row, column)
+      }
+      Some(visual) => {
+        let text = self.state.text.borrow();
+        let before = grapheme_slice(&text[row], visual.start, column);
+        self.row_x(visual) + self.advance(before)
+      }
+      None => 0.0,
+    };
+    (
+      x,
+      self.scroll_offset.y as f32 + (index as f32 * self.font_height),
+    )
+  }
+
+  /// The cursors and the start of the selection, where edits start.
+  pub fn carets(&self) -> Vec<Position> {
+    let mut carets = vec![self.state.cursor()];
+    carets.extend(self.state.selection().map(|span| span.start));
+    carets.extend_from_slice(self.state.cursors());
+    carets
+  }
+
+  /// Runs `f` with the segments of the long `line`, which is `text`,
+  /// measured until `reached` holds for the last one or the budget of the
+  /// frame runs out.
+  fn with_segments<T>(
+    &self,
+    line: usize,
+    text: &str,
+    reached: impl Fn(&Start) -> bool,
+    f: impl FnOnce(&Segments) -> T,
+  ) -> T {
+    let mut cache = self.segments.borrow_mut();
+    let segments = cache.entry(line).or_default();
+    let mut budget = self.layout_budget.get();
+    let advance = |grapheme: &str| self.advance(grapheme);
+    if !segments.measure(text, &mut budget, reached, advance) {
+      self.layout_cut.set(true);
+    }
+    self.layout_budget.set(budget);
+    f(segments)
+  }
+
+  /// Distance of `column` of `line` from where it starts. Where a long line
+  /// wasn't measured yet it's estimated from the part that was.
+  fn x_of(&self, line: usize, column: usize) -> f32 {
+    let text = self.state.text.borrow();
+    let text = &text[line];
+    if text.len() <= LONG_LINE {
+      return self.advance(grapheme_slice(text, 0, column));
+    }
+    self.with_segments(
+      line,
+      text,
+      |start| start.column >= column,
+      |segments| {
+        let start = segments.before_column(column);
+        let rest = &text[start.offset..];
+        if segments.is_complete() || start != segments.last() {
+          return start.x
+            + self.advance(grapheme_slice(rest, 0, column - start.column));
+        }
+        let average = match start.column {
+          0 => self.advance(" "),
+          columns => start.x / columns as f32,
+        };
+        // there are no more graphemes left than bytes
+        start.x + (column - start.column).min(rest.len()) as f32 * average
       },
+    )
+  }
+
+  /// The column of `line`, which is `text`, closest to `x`.
+  fn column_of(&self, line: usize, text: &str, x: f32) -> usize {
+    if text.len() <= LONG_LINE {
+      return self.column_at(text, x);
+    }
+    let start = self.with_segments(
+      line,
+      text,
+      |start| start.x > x,
+      |segments| segments.before_x(x),
     );
+    start.column + self.column_at(&text[start.offset..], x - start.x)
+  }
 
-    self.cursor.rect.region = Some(self.dimensions.into());
+  /// Whether long lines in view are still being measured, over the next
+  /// frames.
+  pub fn is_laying_out(&self) -> bool {
+    self.layout_behind
   }
 
-  fn scroll(
+  /// Applies `message` to the state and catches up with what it changed.
+  pub fn update(
     &mut self,
-    offset: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+    message: Message,
+  ) -> Change {
+    let before = self.carets();
+    let count = self.state.text.borrow().len();
+    let replaces =
+      matches!(message, Message::Replace(_) | Message::TextChanged);
+    let change = self.state.update(message);
+    if change == Change::Text {
+      let mut segments = self.segments.borrow_mut();
+      if replaces || count != self.state.text.borrow().len() {
+        segments.clear();
+      } else {
+        // an edit starts at a caret from before or after it
+        for (row, column) in before.into_iter().chain(self.carets()) {
+          if let Some(line) = segments.get_mut(&row) {
+            line.truncate(column);
+          }
+        }
+      }
+      drop(segments);
+      self.max_line_length = max_line_length(
+        &self.state.text.borrow(),
+        self.font.clone(),
+        self.font_height,
+      );
+      self.rewrap();
+      self.clamp_scroll();
+      if self.diff.is_some() {
+        self.diff = Some(diff::changed_lines(&self.state.text.borrow()));
+      }
+    }
+    if change != Change::Nothing {
+      if self.typewriter {
+        self.center_cursor();
+        self.clamp_scroll();
+      }
+      self.position_cursor(screen_size);
+    }
+    change
+  }
+
+  /// Replaces all of the text, keeping the cursor in place where possible.
+  pub fn replace_text(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    lines: Vec<String>,
+  ) {
+    self.update(screen_size, Message::Replace(lines));
+  }
+
+  /// Catches up with a change to the text made elsewhere, keeping the cursor
+  /// in place where possible.
+  pub fn text_changed(&mut self, screen_size: PhysicalSize<f32>) {
+    self.update(screen_size, Message::TextChanged);
+  }
+
+  /// The first line in view and the number of lines shown.
+  pub fn visible_lines(&self) -> (usize, usize) {
+    let first =
+      ((-self.scroll_offset.y) / self.font_height as f64).floor() as usize;
+    let count = (self.dimensions.height / self.font_height).ceil() as usize;
+    match self.rows.borrow().as_ref() {
+      Some(rows) => {
+        let line = |row: usize| {
+          rows
+            .get(row)
+            .or_else(|| rows.last())
+            .map_or(0, |row| row.line)
+        };
+        let first_line = line(first);
+        (first_line, line(first + count) - first_line + 1)
+      }
+      None => (first, count),
+    }
+  }
+
+  /// Finds the color literals in view and shows a swatch of the first one
+  /// of each line in the gutter, where it doesn't cover any text.
+  pub fn decorate_swatches(&mut self) {
+    let (first, count) = self.visible_lines();
+    let text = self.state.text.borrow();
+    let end = (first + count + 1).min(text.len());
+    // long lines would be searched all along every frame
+    let swatches = (first.min(end)..end)
+      .filter(|line| text[*line].len() <= LONG_LINE)
+      .filter_map(|line| {
+        colors::find(&text[line])
+          .into_iter()
+          .next()
+          .map(|literal| (line, literal))
+      })
+      .collect::<Vec<_>>();
+    drop(text);
+
+    let decorations = swatches
+      .iter()
+      .map(|(line, literal)| {
+        let span = Span::new((*line, literal.start), (*line, literal.end));
+        let style = Style {
+          gutter: Some((gutter::SWATCH, Color::from_srgb(literal.rgb))),
+          ..Style::default()
+        };
+        Decoration::new(span, style)
+      })
+      .collect();
+    self.decorations.set(Source::Swatches, decorations);
+    self.swatches = swatches;
+  }
+
+  /// The color literal whose swatch is shown next to `line`.
+  pub fn swatch_on(&self, line: usize) -> Option<(usize, ColorLiteral)> {
+    self.swatches.iter().find(|(at, _)| *at == line).copied()
+  }
+
+  /// Sets whether brackets are colored and their pairs have guides.
+  pub fn set_brackets_config(&mut self, config: BracketsConfig) {
+    self.brackets_config = config;
+    if !config.colorize && !config.guides {
+      self.brackets = Brackets::default();
+    }
+  }
+
+  /// Whether the brackets of the text are shown in any way.
+  pub fn shows_brackets(&self) -> bool {
+    self.brackets_config.colorize || self.brackets_config.guides
+  }
+
+  /// Finds the bracket pairs of the text again, leaving out those in the
+  /// strings and comments of `grammar`.
+  pub fn find_brackets(&mut self, grammar: &Grammar) {
+    self.brackets = brackets::find(&self.state.text.borrow(), grammar);
+  }
+
+  /// Colors the brackets in view by how deeply they are nested.
+  pub fn decorate_brackets(&mut self) {
+    if !self.brackets_config.colorize {
+      self.decorations.clear(Source::Brackets);
+      return;
+    }
+    let (first, count) = self.visible_lines();
+    let decorations = (first..=first + count + 1)
+      .flat_map(|line| self.brackets.on_line(line))
+      .map(|bracket| {
+        let (line, column) = (bracket.line, bracket.column);
+        let style = Style {
+          foreground: Some(self.bracket_color(bracket.depth)),
+          ..Style::default()
+        };
+        Decoration::new(Span::new((line, column), (line, column + 1)), style)
+      })
+      .collect();
+    self.decorations.set(Source::Brackets, decorations);
+  }
+
+  /// Places a line through the lines between each bracket pair in view, at
+  /// the indentation of the line the pair opens on.
+  pub fn update_guides(
+    &mut self,
+    device: &wgpu::Device,
     screen_size: PhysicalSize<f32>,
   ) {
-    if offset.x.abs() > offset.y.abs() {
-      self.scroll_offset.x = (self.scroll_offset.x - offset.x)
-        .max((screen_size.width - self.max_line_length) as f64) // TODO
-        .min(0.0);
+    if !self.brackets_config.guides {
+      self.guide_count = 0;
+      return;
+    }
+    let (first, count) = self.visible_lines();
+    let guides = self
+      .brackets
+      .guides
+      .iter()
+      .filter(|guide| guide.open < first + count && guide.close > first)
+      .map(|guide| {
+        let (x, _) = self.offset_of(guide.open, guide.column);
+        let (_, top) = self.offset_of(guide.open + 1, 0);
+        let (_, bottom) = self.offset_of(guide.close, 0);
+        let dimensions = Dimensions {
+          x: self.dimensions.x + x,
+          y: self.dimensions.y + top,
+          width: GUIDE_WIDTH,
+          height: bottom - top,
+        };
+        let color = self
+          .theme
+          .background
+          .mix(self.bracket_color(Some(guide.depth)), GUIDE_SHADE);
+        (dimensions, color)
+      })
+      .collect::<Vec<_>>();
+
+    for (i, (dimensions, color)) in guides.iter().enumerate() {
+      let color = color.rgb();
+      match self.guide_rects.get_mut(i) {
+        Some(rect) => {
+          rect.resize(screen_size, *dimensions);
+          rect.set_color(color);
+        }
+        None => self.guide_rects.push(Rectangle::new(
+          device,
+          screen_size,
+          *dimensions,
+          color,
+          None,
+        )),
+      }
+      self.guide_rects[i].region = Some(self.dimensions.into());
+    }
+    self.guide_count = guides.len();
+  }
+
+  /// Highlights the text as a unified diff.
+  pub fn set_diff(&mut self, diff: bool) {
+    self.diff = if diff {
+      Some(diff::changed_lines(&self.state.text.borrow()))
     } else {
-      self.scroll_offset.y = (self.scroll_offset.y + offset.y).min(0.0).max(
-        -((self.text.borrow().len() - 3) as f32 * self.font_height) as f64,
-      );
+      None
+    };
+  }
+
+  /// The removed and added lines when the text is a diff.
+  pub fn changed_lines(&self) -> Vec<usize> {
+    self
+      .diff
+      .iter()
+      .flatten()
+      .map(|changed| changed.line)
+      .collect()
+  }
+
+  /// The removed and added lines along with whether they were added.
+  pub fn changes(&self) -> Vec<(usize, bool)> {
+    self
+      .diff
+      .iter()
+      .flatten()
+      .map(|changed| (changed.line, changed.added))
+      .collect()
+  }
+
+  /// Covers the graphemes `start..end` of `line`, along with its end if
+  /// `newline` is set, a rectangle per row they are wrapped into.
+  fn span_dimensions(
+    &self,
+    text: &[String],
+    line: usize,
+    start: usize,
+    end: usize,
+    newline: bool,
+  ) -> Vec<Dimensions> {
+    let mut segments = vec![];
+    let wrapped = self.wrap.soft_wrap;
+    match self.rows.borrow().as_ref() {
+      Some(rows) if wrapped => {
+        let length = text[line].graphemes(true).count();
+        for row in rows.iter().filter(|row| row.line == line) {
+          let (a, b) = (start.max(row.start), end.min(row.end));
+          let ends_line = newline && row.end == length;
+          if a < b || ends_line {
+            segments.push((a, b, ends_line));
+          }
+        }
+      }
+      // folded lines aren't shown
+      Some(rows)
+        if rows.binary_search_by_key(&line, |row| row.line).is_err() => {}
+      _ => segments.push((start, end, newline)),
     }
 
-    self.cursor.rect.resize(
+    segments
+      .into_iter()
+      .map(|(start, end, newline)| {
+        let (x, y) = self.offset_of(line, start);
+        let mut width = if wrapped {
+          self.advance(grapheme_slice(&text[line], start, end))
+        } else {
+          self.x_of(line, end) - self.x_of(line, start)
+        };
+        if newline {
+          width += self.advance(" ");
+        }
+        Dimensions {
+          x: self.dimensions.x + x,
+          y: self.dimensions.y + y,
+          width,
+          height: self.font_height,
+        }
+      })
+      .collect()
+  }
+
+  /// Shades the removed and added lines in view, and the words that
+  /// changed in them fully.
+  pub fn decorate_diff(&mut self) {
+    let changed = match &self.diff {
+      Some(changed) => changed,
+      None => {
+        self.decorations.clear(Source::Diff);
+        return;
+      }
+    };
+    let (first, count) = self.visible_lines();
+    let mut lines = vec![];
+    let mut words = vec![];
+    for changed in changed
+      .iter()
+      .filter(|changed| (first..=first + count).contains(&changed.line))
+    {
+      let color = if changed.added {
+        self.theme.added
+      } else {
+        self.theme.removed
+      };
+      let shade = self.theme.background.mix(color, DIFF_LINE_SHADE);
+      let background = |color| Style {
+        background: Some(color),
+        ..Style::default()
+      };
+      lines.push(Decoration::line(changed.line, background(shade)));
+      words.extend(changed.words.iter().map(|word| {
+        let span =
+          Span::new((changed.line, word.start), (changed.line, word.end));
+        Decoration::new(span, background(color))
+      }));
+    }
+    // the words go on top of the lines
+    lines.extend(words);
+    self.decorations.set(Source::Diff, lines);
+  }
+
+  /// Shades the selected text.
+  pub fn decorate_selection(&mut self) {
+    let decorations = self
+      .selection()
+      .map(|span| {
+        let style = Style {
+          background: Some(self.theme.selection),
+          ..Style::default()
+        };
+        Decoration::new(span, style)
+      })
+      .into_iter()
+      .collect();
+    self.decorations.set(Source::Selection, decorations);
+  }
+
+  /// Shades the lines with a cursor while nothing is selected.
+  pub fn decorate_current_line(&mut self) {
+    if self.selection().is_some() {
+      self.decorations.clear(Source::CurrentLine);
+      return;
+    }
+    let style = Style {
+      background: Some(
+        self
+          .theme
+          .background
+          .mix(self.theme.selection, CURRENT_LINE_SHADE),
+      ),
+      ..Style::default()
+    };
+    let mut lines = std::iter::once(self.state.cursor())
+      .chain(self.state.cursors().iter().copied())
+      .map(|(line, _)| line)
+      .collect::<Vec<_>>();
+    lines.sort_unstable();
+    lines.dedup();
+    let decorations = lines
+      .into_iter()
+      .map(|line| Decoration::line(line, style.clone()))
+      .collect();
+    self.decorations.set(Source::CurrentLine, decorations);
+  }
+
+  /// Shades the occurrences of `find` in view.
+  pub fn decorate_matches(&mut self, find: Option<&str>) {
+    let find = match find.filter(|find| !find.is_empty()) {
+      Some(find) => find,
+      None => {
+        self.decorations.clear(Source::Search);
+        return;
+      }
+    };
+    let style = Style {
+      background: Some(
+        self.theme.background.mix(self.theme.warning, MATCH_SHADE),
+      ),
+      ..Style::default()
+    };
+    let length = find.graphemes(true).count();
+    let (first, count) = self.visible_lines();
+    let text = self.state.text.borrow();
+    let end = (first + count + 1).min(text.len());
+    let decorations = (first.min(end)..end)
+      .filter(|line| text[*line].len() <= LONG_LINE)
+      .flat_map(|line| {
+        let text = &text[line];
+        let style = &style;
+        text.match_indices(find).map(move |(offset, _)| {
+          let start = text[..offset].graphemes(true).count();
+          Decoration::new(
+            Span::new((line, start), (line, start + length)),
+            style.clone(),
+          )
+        })
+      })
+      .collect();
+    drop(text);
+    self.decorations.set(Source::Search, decorations);
+  }
+
+  /// Underlines the word at each of `problems` with a squiggle and marks
+  /// their lines in the gutter.
+  pub fn decorate_problems(&mut self, problems: &[Position]) {
+    let text = self.state.text.borrow();
+    let decorations = problems
+      .iter()
+      .filter(|(line, _)| *line < text.len())
+      .map(|(line, column)| {
+        let graphemes = text[*line].graphemes(true).collect::<Vec<_>>();
+        let mut start = *column;
+        let mut end = start
+          + graphemes
+            .iter()
+            .skip(start)
+            .take_while(|grapheme| !grapheme.trim().is_empty())
+            .count();
+        // a problem with no word at its column is about the whole line
+        if end == start {
+          start = graphemes
+            .iter()
+            .take_while(|grapheme| grapheme.trim().is_empty())
+            .count();
+          end = graphemes.len();
+        }
+        let style = Style {
+          underline: Some((Underline::Wavy, self.theme.error)),
+          gutter: Some((gutter::PROBLEM, self.theme.error)),
+          ..Style::default()
+        };
+        Decoration::new(Span::new((*line, start), (*line, end)), style)
+      })
+      .collect();
+    drop(text);
+    self.decorations.set(Source::Problems, decorations);
+  }
+
+  /// Marks the line of `cursor` when there are code actions for it.
+  pub fn decorate_lightbulb(&mut self, cursor: Option<Position>) {
+    let decorations = cursor
+      .map(|cursor| {
+        let style = Style {
+          gutter: Some((gutter::LIGHTBULB, self.theme.warning)),
+          ..Style::default()
+        };
+        Decoration::new(Span::new(cursor, cursor), style)
+      })
+      .into_iter()
+      .collect();
+    self.decorations.set(Source::CodeActions, decorations);
+  }
+
+  /// How far backgrounds covering whole lines go: to the first ruler if
+  /// there is one.
+  fn line_width(&self) -> f32 {
+    let advance = self.advance(" ");
+    match self.rulers.iter().min() {
+      Some(column) => (self.scroll_offset.x as f32 + *column as f32 * advance)
+        .max(0.0)
+        .min(self.dimensions.width),
+      None => self.dimensions.width,
+    }
+  }
+
+  /// Covers the rows `line` is wrapped into, edge to edge.
+  fn line_dimensions(&self, line: usize) -> Vec<Dimensions> {
+    let starts = match self.rows.borrow().as_ref() {
+      Some(rows) => rows
+        .iter()
+        .filter(|row| row.line == line)
+        .map(|row| row.start)
+        .collect(),
+      None => vec![0],
+    };
+    let width = self.line_width();
+    starts
+      .into_iter()
+      .map(|start| {
+        let (_, y) = self.offset_of(line, start);
+        Dimensions {
+          x: self.dimensions.x,
+          y: self.dimensions.y + y,
+          width,
+          height: self.font_height,
+        }
+      })
+      .collect()
+  }
+
+  /// Resolves the decorations in view into the rectangles behind the text,
+  /// the underlines under it and the virtual text after it.
+  pub fn update_decorations(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    let (first, count) = self.visible_lines();
+    let text = self.state.text.borrow();
+    let last = (first + count + 1).min(text.len() - 1);
+    let mut backgrounds = vec![];
+    let mut marks = vec![];
+    let mut virtual_texts: Vec<(usize, &str, Color)> = vec![];
+    for decoration in self.decorations.in_lines(first, last) {
+      let style = &decoration.style;
+      let Span { start, end } = decoration.span;
+      for line in start.0.max(first)..=end.0.min(last) {
+        let (from, to, newline) = decoration.columns(line);
+        // measuring the text isn't needed to cover whole lines
+        let covers_text = style.underline.is_some()
+          || (style.background.is_some() && !decoration.whole_lines);
+        let rows = if covers_text {
+          self.span_dimensions(&text, line, from, to, newline)
+        } else {
+          vec![]
+        };
+        if let Some(color) = style.background {
+          let rows = if decoration.whole_lines {
+            self.line_dimensions(line)
+          } else {
+            rows.clone()
+          };
+          backgrounds.extend(rows.into_iter().map(|row| (row, color)));
+        }
+        if let Some((kind, color)) = style.underline {
+          for row in &rows {
+            let pieces = decorations::underline(kind, *row);
+            marks.extend(pieces.into_iter().map(|piece| (piece, color)));
+          }
+        }
+      }
+      if end.0 > last || end.0 < first {
+        continue;
+      }
+      if let Some((virtual_text, color)) = &style.virtual_text {
+        virtual_texts.push((end.0, virtual_text, *color));
+      }
+    }
+
+    // the virtual text of a line goes after its end, one after the other
+    let gap = self.advance(" ") * VIRTUAL_TEXT_GAP;
+    let mut placed = vec![];
+    let mut previous: Option<(usize, (f32, f32))> = None;
+    for (line, virtual_text, color) in virtual_texts {
+      let (x, y) = match previous {
+        Some((at, end)) if at == line => end,
+        _ => self.offset_of(line, text[line].graphemes(true).count()),
+      };
+      let x = x + gap;
+      previous = Some((line, (x + self.advance(virtual_text), y)));
+      placed.push(((x, y), virtual_text.to_string(), color));
+    }
+    drop(text);
+
+    let region = self.dimensions;
+    place_rects(
+      &mut self.background_rects,
+      device,
       screen_size,
-      Dimensions {
+      &backgrounds,
+      region,
+    );
+    place_rects(&mut self.mark_rects, device, screen_size, &marks, region);
+    self.background_count = backgrounds.len();
+    self.mark_count = marks.len();
+    self.virtual_texts = placed;
+  }
+
+  /// Places a rectangle at each cursor besides the main one.
+  pub fn update_cursors(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    let dimensions = self
+      .state
+      .cursors()
+      .iter()
+      .map(|(row, column)| {
+        let (x, y) = self.offset_of(*row, *column);
+        Dimensions {
+          x: self.dimensions.x + x,
+          y: self.dimensions.y + y,
+          width: CURSOR_WIDTH,
+          height: self.font_height,
+        }
+      })
+      .collect::<Vec<_>>();
+    let color = self.theme.cursor.rgb();
+    for (i, dimensions) in dimensions.iter().enumerate() {
+      match self.cursor_rects.get_mut(i) {
+        Some(rect) => {
+          rect.resize(screen_size, *dimensions);
+          rect.set_color(color);
+        }
+        None => self.cursor_rects.push(Rectangle::new(
+          device,
+          screen_size,
+          *dimensions,
+          color,
+          None,
+        )),
+      }
+      self.cursor_rects[i].region = Some(self.dimensions.into());
+    }
+  }
+
+  /// Places a line at each ruler column, measured in the advance of a space
+  /// so they follow the font size and horizontal scrolling.
+  pub fn update_rulers(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    let advance = self.advance(" ");
+    let color = self.theme.ruler.rgb();
+    for (i, column) in self.rulers.iter().enumerate() {
+      let dimensions = Dimensions {
         x: self.dimensions.x
           + self.scroll_offset.x as f32
-          + self.cursor.x_offset,
-        y: self.dimensions.y
-          + self.scroll_offset.y as f32
-          + (self.cursor.row as f32 * self.font_height),
-        ..self.cursor.rect.dimensions
+          + (*column as f32 * advance),
+        width: RULER_WIDTH,
+        ..self.dimensions
+      };
+      match self.ruler_rects.get_mut(i) {
+        Some(rect) => {
+          rect.resize(screen_size, dimensions);
+          rect.set_color(color);
+        }
+        None => self.ruler_rects.push(Rectangle::new(
+          device,
+          screen_size,
+          dimensions,
+          color,
+          None,
+        )),
+      }
+      self.ruler_rects[i].region = Some(self.dimensions.into());
+    }
+  }
+
+  /// The selected text, `None` if nothing is selected.
+  pub fn selection(&self) -> Option<Span> {
+    self.state.selection()
+  }
+
+  pub fn selected_text(&self) -> Option<String> {
+    self.state.selected_text()
+  }
+
+  /// Selects `span` with the cursor at its end.
+  pub fn select(&mut self, screen_size: PhysicalSize<f32>, span: Span) {
+    self.select_from(screen_size, span.start, span.end);
+  }
+
+  /// Selects the text from `anchor` to `cursor`, leaving the cursor there.
+  pub fn select_from(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    anchor: Position,
+    cursor: Position,
+  ) {
+    self.update(screen_size, Message::Select { anchor, cursor });
+  }
+
+  /// Grows the selection to the structure around it. Returns whether there
+  /// was any.
+  pub fn expand_selection(&mut self, screen_size: PhysicalSize<f32>) -> bool {
+    self.update(screen_size, Message::ExpandSelection) != Change::Nothing
+  }
+
+  /// Goes back to the selection last grown from. Returns whether there was
+  /// one.
+  pub fn shrink_selection(&mut self, screen_size: PhysicalSize<f32>) -> bool {
+    self.update(screen_size, Message::ShrinkSelection) != Change::Nothing
+  }
+
+  /// Zero based row and column of the cursor.
+  pub fn cursor_position(&self) -> (usize, usize) {
+    self.state.cursor()
+  }
+
+  /// The first and the last line with a cursor.
+  pub fn cursor_lines(&self) -> (usize, usize) {
+    let (row, _) = self.state.cursor();
+    self
+      .state
+      .cursors()
+      .iter()
+      .fold((row, row), |(first, last), (row, _)| {
+        (first.min(*row), last.max(*row))
+      })
+  }
+
+  /// Where the cursor is on screen.
+  pub fn cursor_dimensions(&self) -> Dimensions {
+    let (x, y) = self.cursor_offset();
+    Dimensions {
+      x: self.dimensions.x + x,
+      y: self.dimensions.y + y,
+      width: self.cursor.dimensions.width,
+      height: self.font_height,
+    }
+  }
+
+  /// Moves the cursor to `row` and `column`, clamped to the text. Returns
+  /// how far to scroll vertically to center the cursor.
+  pub fn set_cursor(&mut self, row: usize, column: usize) -> f64 {
+    self.state.update(Message::MoveTo((row, column)));
+    let (row, _) = self.state.cursor();
+    let visible_rows = self.dimensions.height / self.font_height;
+    let first_row = (row as f32 - (visible_rows / 2.0)).max(0.0);
+    -(first_row * self.font_height) as f64 - self.scroll_offset.y
+  }
+
+  fn position_cursor(&mut self, screen_size: PhysicalSize<f32>) {
+    let (x, y) = self.cursor_offset();
+    let width = if self.state.overwrite() {
+      let text = self.state.text.borrow();
+      let (row, column) = self.state.cursor();
+      match grapheme_slice(&text[row], column, column + 1) {
+        "" => self.advance("0"),
+        grapheme => self.advance(grapheme),
+      }
+    } else {
+      CURSOR_WIDTH
+    };
+    self.cursor.resize(
+      screen_size,
+      Dimensions {
+        x: self.dimensions.x + x,
+        y: self.dimensions.y + y,
+        width,
+        ..self.cursor.dimensions
       },
     );
   }
 
-  fn click(
+  /// Draws `ligatures` in place of the glyphs they join.
+  pub fn set_ligatures(&mut self, ligatures: Rc<Ligatures>) {
+    self.ligatures = ligatures;
+  }
+
+  /// Switches between replacing and inserting what is typed.
+  pub fn set_overwrite(
     &mut self,
-    position: PhysicalPosition<f64>,
-    _screen_size: PhysicalSize<f32>,
-  ) {
-    let line = ((position.y - self.scroll_offset.y) / self.font_height as f64)
-      .floor() as usize;
-    let vec = Ref::map(self.text.borrow(), |v| v[line..line + 1].as_ref());
-    let text = self.generate_glyph_text(&vec)[0];
-    let layout = Layout::default_wrap();
-
-    let section_glyphs = &layout.calculate_glyphs(
-      &[self.font.clone()],
-      &SectionGeometry {
-        ..Default::default()
+    screen_size: PhysicalSize<f32>,
+    overwrite: bool,
+  ) {
+    self.update(screen_size, Message::SetOverwrite(overwrite));
+  }
+
+  /// Number of rows the text takes up.
+  fn row_count(&self) -> usize {
+    match self.rows.borrow().as_ref() {
+      Some(rows) => rows.len(),
+      None => self.state.text.borrow().len(),
+    }
+  }
+
+  /// The region text is drawn in, below the pinned declarations.
+  fn text_region(&self) -> Dimensions {
+    Dimensions {
+      y: self.dimensions.y + self.covered,
+      height: self.dimensions.height - self.covered,
+      ..self.dimensions
+    }
+  }
+
+  /// Queues the visible lines when none are wrapped or folded.
+  fn queue_lines(&self, glyph_brush: &mut wgpu_glyph::GlyphBrush<()>) {
+    let (upper_bound, top) =
+      viewport::first_row(self.scroll_offset.y, self.font_height);
+    let lower_bound = (upper_bound
+      + (self.dimensions.height / self.font_height).ceil() as usize)
+      .min(self.state.text.borrow().len());
+
+    let vec = Ref::map(self.state.text.borrow(), |v| {
+      v[upper_bound..lower_bound].as_ref()
+    });
+    let y = self.dimensions.y + top;
+    glyph_brush.queue_custom_layout(
+      Section {
+        screen_position: (self.dimensions.x + self.scroll_offset.x as f32, y),
+        text: self.generate_glyph_text(&vec, upper_bound),
+        ..Section::default()
+      },
+      &Shaped {
+        ligatures: &self.ligatures,
+        hinting: self.hinting,
       },
-      &[text],
     );
+    for (i, text) in vec.iter().enumerate() {
+      if text.len() > LONG_LINE {
+        let y = y + i as f32 * self.font_height;
+        self.queue_segment(glyph_brush, upper_bound + i, text, y);
+      }
+    }
+  }
 
-    let mut c = 0;
-    for section_glyph in section_glyphs {
-      c += 1;
-      self.cursor.x_offset = section_glyph.glyph.position.x;
-      if (position.x as f32) < section_glyph.glyph.position.x {
-        c -= 1;
-        break;
+  /// Queues the visible rows when lines are wrapped or folded.
+  fn queue_wrapped(&self, glyph_brush: &mut wgpu_glyph::GlyphBrush<()>) {
+    let rows = self.rows.borrow();
+    let rows = match rows.as_ref() {
+      Some(rows) => rows,
+      None => return,
+    };
+    let text = self.state.text.borrow();
+    let first =
+      ((-self.scroll_offset.y) / self.font_height as f64).floor() as usize;
+    let last =
+      (first + (self.dimensions.height / self.font_height).ceil() as usize + 1)
+        .min(rows.len());
+
+    for (i, row) in rows.iter().enumerate().take(last).skip(first) {
+      let y = self.dimensions.y
+        + self.scroll_offset.y as f32
+        + (i as f32 * self.font_height);
+      if !self.wrap.soft_wrap && text[row.line].len() > LONG_LINE {
+        self.queue_segment(glyph_brush, row.line, &text[row.line], y);
+        continue;
+      }
+      if let Some(indent) = row.continuation {
+        glyph_brush.queue(Section {
+          screen_position: (
+            self.dimensions.x + (indent as f32 * self.advance(" ")),
+            y,
+          ),
+          text: vec![Text::new(&self.wrap.indicator)
+            .with_color(self.theme.gutter_text.rgba())
+            .with_scale(self.font_height)],
+          ..Section::default()
+        });
       }
+      glyph_brush.queue_custom_layout(
+        Section {
+          screen_position: (
+            self.dimensions.x + self.scroll_offset.x as f32 + self.row_x(row),
+            y,
+          ),
+          text: self.line_texts(
+            grapheme_slice(&text[row.line], row.start, row.end),
+            row.line,
+            row.start,
+          ),
+          ..Section::default()
+        },
+        &Shaped {
+          ligatures: &self.ligatures,
+          hinting: self.hinting,
+        },
+      );
+    }
+  }
+
+  /// Queues the segments in view of the long `line`, which is `text`, at
+  /// `y`.
+  fn queue_segment(
+    &self,
+    glyph_brush: &mut wgpu_glyph::GlyphBrush<()>,
+    line: usize,
+    text: &str,
+    y: f32,
+  ) {
+    let left = -self.scroll_offset.x as f32;
+    let right = left + self.dimensions.width;
+    let (start, end) = self.with_segments(
+      line,
+      text,
+      |start| start.x > right,
+      |segments| {
+        let end = segments.after_x(right).unwrap_or_else(|| segments.last());
+        (segments.before_x(left), end)
+      },
+    );
+    glyph_brush.queue_custom_layout(
+      Section {
+        screen_position: (
+          self.dimensions.x + self.scroll_offset.x as f32 + start.x,
+          y,
+        ),
+        text: self.line_texts(
+          &text[start.offset..end.offset.max(start.offset)],
+          line,
+          start.column,
+        ),
+        ..Section::default()
+      },
+      &Shaped {
+        ligatures: &self.ligatures,
+        hinting: self.hinting,
+      },
+    );
+  }
+
+  /// The column of `line` closest to `x`, measured from where it starts.
+  fn column_at(&self, line: &str, x: f32) -> usize {
+    let mut left = 0.0;
+    for (i, grapheme) in line.graphemes(true).enumerate() {
+      let width = self.advance(grapheme);
+      if x < left + (width / 2.0) {
+        return i;
+      }
+      left += width;
+    }
+    line.graphemes(true).count()
+  }
+
+  /// The line and column at `position` relative to the top left corner.
+  fn position_at(&self, position: PhysicalPosition<f64>) -> Option<Position> {
+    let index = ((position.y - self.scroll_offset.y) / self.font_height as f64)
+      .floor()
+      .max(0.0) as usize;
+    let text = self.state.text.borrow();
+    let row = match self.rows.borrow().as_ref() {
+      Some(rows) => *rows.get(index).or_else(|| rows.last())?,
+      None => {
+        let line = index.min(text.len().checked_sub(1)?);
+        let x = (position.x - self.scroll_offset.x) as f32;
+        return Some((line, self.column_of(line, &text[line], x)));
+      }
+    };
+    if !self.wrap.soft_wrap {
+      let x = (position.x - self.scroll_offset.x) as f32;
+      return Some((row.line, self.column_of(row.line, &text[row.line], x)));
+    }
+    let line = grapheme_slice(&text[row.line], row.start, row.end);
+    let x = position.x as f32 - self.row_x(&row);
+    Some((row.line, row.start + self.column_at(line, x)))
+  }
+}
+
+impl input::TextInput for Code {
+  fn input_special(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) {
+    let message = input::key_message(key).unwrap_or(Message::ClearSelection);
+    self.update(screen_size, message);
+  }
+
+  fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char) {
+    self.update(screen_size, input::char_message(ch));
+  }
+}
+
+impl super::super::RenderElement for Code {
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    self.scroll_offset.x -= offset.x;
+    self.scroll_offset.y += offset.y;
+    self.clamp_scroll();
+    self.position_cursor(screen_size);
+  }
+
+  fn click(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    let _span = tracing::trace_span!("layout").entered();
+    self.state.update(Message::ClearSelection);
+    if let Some(cursor) = self.position_at(position) {
+      self.update(screen_size, Message::MoveTo(cursor));
     }
+  }
 
-    self.cursor.row = line;
-    self.cursor.column = c;
+  fn hover(
+    &mut self,
+    position: PhysicalPosition<f32>,
+  ) -> (Option<CursorIcon>, bool) {
+    let icon = self
+      .text_region()
+      .contains(position)
+      .map(|_| CursorIcon::Text);
+    (icon, false)
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.cursor.set_color(theme.cursor.rgb());
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    // keeps the first line in view
+    let ratio = (font_height / self.font_height) as f64;
+    self.scroll_offset.x *= ratio;
+    self.scroll_offset.y *= ratio;
+    self.font = font.clone();
+    self.font_height = font_height;
+    self.max_line_length =
+      max_line_length(&self.state.text.borrow(), font.clone(), font_height);
+    self.segments.borrow_mut().clear();
+    self.cursor.dimensions.height = font_height;
+    self.rewrap();
+    self.clamp_scroll();
   }
 
   fn redraw(
@@ -197,38 +1573,46 @@ impl super::super::RenderElement for Code {
     target: &wgpu::TextureView,
     size: PhysicalSize<u32>,
   ) {
-    let upper_bound =
-      ((-self.scroll_offset.y) / self.font_height as f64).floor() as usize;
-    let lower_bound = (upper_bound
-      + (self.dimensions.height / self.font_height).ceil() as usize)
-      .min(self.text.borrow().len());
-
-    let vec =
-      Ref::map(self.text.borrow(), |v| v[upper_bound..lower_bound].as_ref());
-    glyph_brush.queue(Section {
-      screen_position: (
-        self.dimensions.x + self.scroll_offset.x as f32,
-        -(((-self.scroll_offset.y as f32) % self.font_height)
-          - self.dimensions.y),
-      ),
-      text: self.generate_glyph_text(&vec),
-      ..Section::default()
-    });
+    if self.rows.borrow().is_some() {
+      self.queue_wrapped(glyph_brush);
+    } else {
+      self.queue_lines(glyph_brush);
+    }
+    for ((x, y), text, color) in &self.virtual_texts {
+      glyph_brush.queue(Section {
+        screen_position: (self.dimensions.x + x, self.dimensions.y + y),
+        text: vec![Text::new(text)
+          .with_color(color.rgba())
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+    self.layout_behind = self.layout_cut.replace(false);
+    self.layout_budget.set(LAYOUT_BUDGET);
 
-    glyph_brush
-      .draw_queued_with_transform_and_scissoring(
-        device,
-        staging_belt,
-        encoder,
-        target,
-        wgpu_glyph::orthographic_projection(size.width, size.height),
-        self.dimensions.into(),
-      )
-      .unwrap();
+    super::super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.text_region()),
+    );
   }
 
   fn get_rects(&self) -> Vec<&Rectangle> {
-    vec![&self.cursor.rect]
+    let mut rects = self
+      .background_rects
+      .iter()
+      .take(self.background_count)
+      .collect::<Vec<_>>();
+    rects.extend(self.ruler_rects.iter().take(self.rulers.len()));
+    rects.extend(self.guide_rects.iter().take(self.guide_count));
+    rects.extend(self.mark_rects.iter().take(self.mark_count));
+    rects.extend(self.cursor_rects.iter().take(self.state.cursors().len()));
+    rects.push(&self.cursor);
+    rects
   }
 
   fn get_elements(&mut self) -> Vec<&mut dyn super::super::RenderElement> {
@@ -0,0 +1,266 @@
+//! Styles anchored to ranges of the text: the selection, search matches,
+//! problems, color swatches and anything else drawn along with the text.
+//! Each source registers its decorations, replacing the ones it registered
+//! before, and the code view resolves those in view into rectangles and
+//! glyph runs every frame.
+
+use crate::config::Color;
+use crate::renderer::Dimensions;
+use crate::structure::Span;
+use std::collections::BTreeMap;
+
+/// Thickness of an underline, and the length of the dots and the steps of
+/// the waves of the others.
+const UNDERLINE: f32 = 1.0;
+const DOT: f32 = 2.0;
+const WAVE: f32 = 2.0;
+
+/// What registers decorations, in the order they are drawn so the later
+/// ones go on top.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+  Diff,
+  CurrentLine,
+  Search,
+  Selection,
+  Brackets,
+  Swatches,
+  Problems,
+  CodeActions,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Underline {
+  Straight,
+  Dotted,
+  /// Like the squiggles under problems.
+  Wavy,
+}
+
+/// How a range of the text is decorated, each part is optional.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Style {
+  pub background: Option<Color>,
+  /// Of the text itself.
+  pub foreground: Option<Color>,
+  pub underline: Option<(Underline, Color)>,
+  /// A mark in the diagnostics column of the gutter, next to the first line.
+  pub gutter: Option<(&'static str, Color)>,
+  /// Text which isn't part of the document, after the end of the last line.
+  pub virtual_text: Option<(String, Color)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoration {
+  pub span: Span,
+  /// Whether the background covers the rows of the lines from edge to edge
+  /// rather than the text in the span.
+  pub whole_lines: bool,
+  pub style: Style,
+}
+
+impl Decoration {
+  pub fn new(span: Span, style: Style) -> Self {
+    Self {
+      span,
+      whole_lines: false,
+      style,
+    }
+  }
+
+  /// Decorates all of `line`.
+  pub fn line(line: usize, style: Style) -> Self {
+    Self {
+      span: Span::new((line, 0), (line, usize::MAX)),
+      whole_lines: true,
+      style,
+    }
+  }
+
+  /// The graphemes of `line` it covers, up to `usize::MAX` for the rest of
+  /// the line, and whether the line end is covered too.
+  pub fn columns(&self, line: usize) -> (usize, usize, bool) {
+    let Span { start, end } = self.span;
+    let from = if line == start.0 { start.1 } else { 0 };
+    let to = if line == end.0 { end.1 } else { usize::MAX };
+    (from, to, line != end.0)
+  }
+
+  fn touches(&self, first: usize, last: usize) -> bool {
+    self.span.start.0 <= last && self.span.end.0 >= first
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Decorations {
+  sources: BTreeMap<Source, Vec<Decoration>>,
+}
+
+impl Decorations {
+  /// Replaces the decorations of `source`.
+  pub fn set(&mut self, source: Source, decorations: Vec<Decoration>) {
+    if decorations.is_empty() {
+      self.sources.remove(&source);
+    } else {
+      self.sources.insert(source, decorations);
+    }
+  }
+
+  pub fn clear(&mut self, source: Source) {
+    self.sources.remove(&source);
+  }
+
+  /// The decorations on any of the lines `first..=last`, in the order they
+  /// are drawn.
+  pub fn in_lines(
+    &self,
+    first: usize,
+    last: usize,
+  ) -> impl Iterator<Item = &Decoration> {
+    self
+      .sources
+      .values()
+      .flatten()
+      .filter(move |decoration| decoration.touches(first, last))
+  }
+
+  /// The colors the text of `line` is drawn in, as grapheme ranges. Later
+  /// ranges go over the earlier ones.
+  pub fn foreground(&self, line: usize) -> Vec<(usize, usize, Color)> {
+    self
+      .in_lines(line, line)
+      .filter_map(|decoration| {
+        let color = decoration.style.foreground?;
+        let (start, end, _) = decoration.columns(line);
+        Some((start, end, color))
+      })
+      .collect()
+  }
+
+  /// The gutter marks by line, the one drawn last for each line.
+  pub fn gutter(&self) -> Vec<(usize, &'static str, Color)> {
+    let mut marks = self
+      .sources
+      .values()
+      .rev()
+      .flatten()
+      .filter_map(|decoration| {
+        let (mark, color) = decoration.style.gutter?;
+        Some((decoration.span.start.0, mark, color))
+      })
+      .collect::<Vec<_>>();
+    // the sort is stable, so the first of a line is the one drawn last
+    marks.sort_by_key(|(line, _, _)| *line);
+    marks.dedup_by_key(|(line, _, _)| *line);
+    marks
+  }
+}
+
+/// The rectangles drawing an underline of `kind` along `row`, the
+/// dimensions of the text it's under.
+pub fn underline(kind: Underline, row: Dimensions) -> Vec<Dimensions> {
+  let y = row.y + row.height - UNDERLINE;
+  let piece = |x: f32, width: f32, y: f32| Dimensions {
+    x,
+    y,
+    width: width.min(row.x + row.width - x),
+    height: UNDERLINE,
+  };
+  match kind {
+    Underline::Straight => vec![piece(row.x, row.width, y)],
+    Underline::Dotted => (0..(row.width / (DOT * 2.0)).ceil() as usize)
+      .map(|i| piece(row.x + i as f32 * DOT * 2.0, DOT, y))
+      .collect(),
+    Underline::Wavy => (0..(row.width / WAVE).ceil() as usize)
+      .map(|i| {
+        let up = if i % 2 == 0 { 0.0 } else { UNDERLINE };
+        piece(row.x + i as f32 * WAVE, WAVE, y - up)
+      })
+      .collect(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn resolves_decorations() {
+    let color = |shade| Color([shade, shade, shade]);
+    let mut decorations = Decorations::default();
+    let selection = Decoration::new(
+      Span::new((1, 4), (3, 2)),
+      Style {
+        background: Some(color(0.1)),
+        ..Style::default()
+      },
+    );
+    decorations.set(Source::Selection, vec![selection.clone()]);
+    let problem = |line, mark| {
+      Decoration::new(
+        Span::new((line, 0), (line, 3)),
+        Style {
+          foreground: Some(color(0.2)),
+          gutter: Some((mark, color(0.3))),
+          ..Style::default()
+        },
+      )
+    };
+    decorations.set(Source::Problems, vec![problem(2, "a"), problem(5, "a")]);
+    decorations.set(Source::CodeActions, vec![problem(2, "b")]);
+    decorations.set(Source::Diff, vec![Decoration::line(0, Style::default())]);
+
+    // across lines the line ends are covered, but the last one's
+    assert_eq!(selection.columns(1), (4, usize::MAX, true));
+    assert_eq!(selection.columns(2), (0, usize::MAX, true));
+    assert_eq!(selection.columns(3), (0, 2, false));
+
+    let order = decorations
+      .in_lines(0, 2)
+      .map(|decoration| decoration.span.start.0)
+      .collect::<Vec<_>>();
+    assert_eq!(order, vec![0, 1, 2, 2]);
+    assert_eq!(decorations.in_lines(4, 4).count(), 0);
+    assert_eq!(
+      decorations.foreground(2),
+      vec![(0, 3, color(0.2)), (0, 3, color(0.2))]
+    );
+    let marks = decorations
+      .gutter()
+      .into_iter()
+      .map(|(line, mark, _)| (line, mark))
+      .collect::<Vec<_>>();
+    assert_eq!(marks, vec![(2, "b"), (5, "a")]);
+
+    decorations.set(Source::CodeActions, vec![]);
+    decorations.clear(Source::Problems);
+    assert_eq!(decorations.gutter(), vec![]);
+  }
+
+  #[test]
+  fn underlines() {
+    let row = Dimensions {
+      x: 10.0,
+      y: 0.0,
+      width: 7.0,
+      height: 20.0,
+    };
+    assert_eq!(
+      underline(Underline::Straight, row),
+      vec![Dimensions {
+        y: 19.0,
+        height: 1.0,
+        ..row
+      }]
+    );
+    let dots = underline(Underline::Dotted, row);
+    assert_eq!(dots.len(), 2);
+    assert_eq!((dots[1].x, dots[1].width), (14.0, 2.0));
+    // the waves alternate heights and stop at the end of the row
+    let waves = underline(Underline::Wavy, row);
+    assert_eq!(waves.len(), 4);
+    assert_eq!((waves[0].y, waves[1].y), (19.0, 18.0));
+    assert_eq!((waves[3].x, waves[3].width), (16.0, 1.0));
+  }
+}
@@ -1,26 +1,222 @@
+use crate::config::{Color, GutterComponent, GutterConfig, Theme};
 use crate::renderer::input::max_line_length;
 use crate::renderer::rectangle::Rectangle;
 use crate::renderer::Dimensions;
+use crate::structure::Position;
+use crate::viewport;
+use crate::wrap::VisualRow;
 use std::cell::RefCell;
 use std::rc::Rc;
+use unicode_segmentation::UnicodeSegmentation;
 use wgpu::util::StagingBelt;
 use wgpu::{CommandEncoder, Device, TextureView};
 use wgpu_glyph::ab_glyph::FontArc;
 use wgpu_glyph::{GlyphBrush, HorizontalAlign, Layout, Section, Text};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::window::CursorIcon;
 
 const GUTTER_MARGIN: f32 = 10.0;
 const GUTTER_PADDING: f32 = 10.0;
+/// Space between two columns.
+const COLUMN_GAP: f32 = 2.0;
+/// Shown instead of a line number next to continuation rows.
+const WRAP_MARK: &str = "\u{b7}";
+const BOOKMARK: &str = "\u{25cf}";
+const BREAKPOINT: &str = "\u{25cf}";
+/// Next to an added or removed line of a diff.
+const CHANGE: &str = "\u{258e}";
+pub const PROBLEM: &str = "\u{25b2}";
+/// In the color of the first color literal of a line.
+pub const SWATCH: &str = "\u{25a0}";
+/// Next to the line of the cursor when there are code actions for it.
+pub const LIGHTBULB: &str = "\u{26a1}";
+/// Next to a line starting a folded region, or one which can be folded
+/// while the gutter is hovered.
+const FOLDED: &str = "\u{25b8}";
+const UNFOLDED: &str = "\u{25be}";
+/// How much of the breakpoint color a breakpoint which could be set on the
+/// hovered line takes.
+const GHOST_SHADE: f32 = 0.4;
+
+/// What a click in the gutter is on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GutterClick {
+  /// The breakpoint column of a line.
+  Breakpoint(usize),
+  /// The bookmark column of a line.
+  Bookmark(usize),
+  /// The fold marker of a line starting a region which can be folded.
+  Fold(usize),
+  /// The diagnostics column of a line.
+  Diagnostics(usize),
+  /// The number of a line.
+  Line(usize),
+}
+
+/// Where a component is placed in the gutter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Column {
+  component: GutterComponent,
+  /// Of its left edge, from the left of the gutter.
+  x: f32,
+  width: f32,
+}
+
+/// Places the columns of `components` side by side, each as wide as
+/// `width` measures it. Returns them and the width they take with the
+/// padding.
+fn layout(
+  components: &[GutterComponent],
+  width: impl Fn(GutterComponent) -> f32,
+) -> (Vec<Column>, f32) {
+  let mut columns: Vec<Column> = vec![];
+  let mut x = GUTTER_PADDING / 2.0;
+  for component in components {
+    if columns.iter().any(|column| column.component == *component) {
+      continue;
+    }
+    if !columns.is_empty() {
+      x += COLUMN_GAP;
+    }
+    let width = width(*component);
+    columns.push(Column {
+      component: *component,
+      x,
+      width,
+    });
+    x += width;
+  }
+  (columns, x + (GUTTER_PADDING / 2.0))
+}
+
+/// Adds the `row`th row to the layers of a column, one of them with `mark`
+/// in its color.
+fn push_row(
+  layers: &mut Vec<(Color, String)>,
+  row: usize,
+  mark: Option<(String, Color)>,
+) {
+  if let Some((text, color)) = mark {
+    let i = match layers.iter().position(|(layer, _)| *layer == color) {
+      Some(i) => i,
+      None => {
+        layers.push((color, "\n".repeat(row)));
+        layers.len() - 1
+      }
+    };
+    layers[i].1 += &text;
+  }
+  for (_, rows) in layers.iter_mut() {
+    rows.push('\n');
+  }
+}
 
 pub struct Gutter {
+  font: FontArc,
   text: Rc<RefCell<Vec<String>>>,
+  rows: Rc<RefCell<Option<Vec<VisualRow>>>>,
   rect: Rectangle,
   pub dimensions: Dimensions,
-  scroll_offset_y: f64,
+  /// Follows how far the code is scrolled down.
+  pub scroll_offset_y: f64,
   font_height: f32,
+  /// Digits of the last line number, which the width fits.
+  digits: usize,
+  components: Vec<GutterComponent>,
+  columns: Vec<Column>,
+  theme: Theme,
+  /// Zero based bookmarked lines, sorted.
+  pub bookmarks: Vec<usize>,
+  /// Zero based lines with a breakpoint, sorted.
+  pub breakpoints: Vec<usize>,
+  /// The lines starting a region which can be folded, and those folded,
+  /// sorted.
+  pub foldable: Vec<usize>,
+  pub folded: Vec<usize>,
+  /// The added and removed lines of a diff, sorted.
+  pub changes: Vec<(usize, bool)>,
+  /// The marks of the decorations shown in the diagnostics column, sorted
+  /// by line.
+  pub icons: Vec<(usize, &'static str, Color)>,
+  /// Whether the mouse is over the gutter, and the line it's next to.
+  hovered: bool,
+  hovered_line: Option<usize>,
+  /// Height of the top covered by pinned declarations.
+  pub covered: f32,
+}
+
+fn digits(line_count: usize) -> usize {
+  line_count.max(1).to_string().len()
+}
+
+/// Width of line numbers with `digits` digits.
+fn numbers_width(digits: usize, font: FontArc, font_height: f32) -> f32 {
+  let widest = (0..10)
+    .map(|digit| digit.to_string().repeat(digits))
+    .collect::<Vec<_>>();
+  max_line_length(&widest, font, font_height)
+}
+
+/// Adds `line` to sorted `lines` or removes it.
+pub fn toggle(lines: &mut Vec<usize>, line: usize) {
+  match lines.binary_search(&line) {
+    Ok(i) => {
+      lines.remove(i);
+    }
+    Err(i) => lines.insert(i, line),
+  }
+}
+
+/// The selection of the whole lines from `anchor` to `line`, as the
+/// position it's anchored at and the one the cursor goes to. Dragging up
+/// puts the cursor at the start of the top line.
+pub fn line_selection(
+  lines: &[String],
+  anchor: usize,
+  line: usize,
+) -> (Position, Position) {
+  let after = |line: usize| {
+    if line + 1 < lines.len() {
+      (line + 1, 0)
+    } else {
+      (line, lines[line].graphemes(true).count())
+    }
+  };
+  if line < anchor {
+    (after(anchor), (line, 0))
+  } else {
+    ((anchor, 0), after(line))
+  }
+}
+
+/// Moves `bookmarks` along with their lines after `delta` lines were
+/// inserted below `anchor`, or removed below it when negative. Bookmarks on
+/// removed lines move to `anchor`.
+pub fn shift_bookmarks(
+  bookmarks: &mut Vec<usize>,
+  anchor: usize,
+  delta: isize,
+) {
+  for line in bookmarks.iter_mut() {
+    *line = shift_line(*line, anchor, delta);
+  }
+  bookmarks.dedup();
+}
+
+/// Where `line` moves after `delta` lines were inserted or removed below
+/// `anchor`.
+pub fn shift_line(line: usize, anchor: usize, delta: isize) -> usize {
+  if line <= anchor {
+    line
+  } else if delta < 0 && line <= anchor + (-delta) as usize {
+    anchor
+  } else {
+    (line as isize + delta) as usize
+  }
 }
 
 impl Gutter {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     device: &wgpu::Device,
     font: FontArc,
@@ -28,60 +224,293 @@ impl Gutter {
     screen_size: PhysicalSize<f32>,
     dimensions: Dimensions,
     text: Rc<RefCell<Vec<String>>>,
+    rows: Rc<RefCell<Option<Vec<VisualRow>>>>,
+    theme: Theme,
   ) -> Self {
-    let line_numbers = text
-      .borrow()
-      .iter()
-      .enumerate()
-      .map(|(i, _)| i.to_string())
-      .collect::<Vec<String>>();
-    let line_numbers_width = max_line_length(&line_numbers, font, font_height);
+    let rect =
+      Rectangle::new(device, screen_size, dimensions, theme.gutter.rgb(), None);
 
-    let rect_size = line_numbers_width + GUTTER_PADDING;
-
-    let rect = Rectangle::new(
-      device,
-      screen_size,
-      Dimensions {
-        width: rect_size,
-        ..dimensions
-      },
-      [0.5, 0.05, 0.05],
-      None,
-    );
-
-    Self {
+    let digits = digits(text.borrow().len());
+    let mut gutter = Self {
+      font,
+      digits,
       text,
-      dimensions: Dimensions {
-        width: rect_size + GUTTER_MARGIN,
-        ..dimensions
-      },
+      rows,
+      dimensions,
       rect,
       font_height,
+      components: GutterConfig::default().components,
+      columns: vec![],
+      theme,
       scroll_offset_y: 0.0,
+      bookmarks: vec![],
+      breakpoints: vec![],
+      foldable: vec![],
+      folded: vec![],
+      changes: vec![],
+      icons: vec![],
+      hovered: false,
+      hovered_line: None,
+      covered: 0.0,
+    };
+    gutter.measure();
+    gutter.set_dimensions(screen_size, dimensions);
+    gutter
+  }
+
+  /// Shows the columns of `components`, returns whether they changed.
+  pub fn set_components(&mut self, components: &[GutterComponent]) -> bool {
+    if components == self.components.as_slice() {
+      return false;
     }
+    self.components = components.to_vec();
+    self.measure();
+    true
   }
-}
 
-impl super::super::RenderElement for Gutter {
-  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+  fn column(&self, component: GutterComponent) -> Option<&Column> {
+    self
+      .columns
+      .iter()
+      .find(|column| column.component == component)
+  }
+
+  /// Adds a bookmark to `line` or removes it.
+  pub fn toggle_bookmark(&mut self, line: usize) {
+    toggle(&mut self.bookmarks, line);
+  }
+
+  /// Widens or narrows the gutter to the numbers of the lines, returns
+  /// whether it did.
+  pub fn fit(&mut self) -> bool {
+    let digits = digits(self.text.borrow().len());
+    if digits == self.digits {
+      return false;
+    }
+    self.digits = digits;
+    self.measure();
+    true
+  }
+
+  /// Places the columns for the font and the digits of the line numbers
+  /// and sets the width to theirs. Without any the gutter isn't shown.
+  fn measure(&mut self) {
+    let measure = |marks: &[&str]| {
+      let marks = marks
+        .iter()
+        .map(|mark| mark.to_string())
+        .collect::<Vec<_>>();
+      max_line_length(&marks, self.font.clone(), self.font_height)
+    };
+    let (columns, width) =
+      layout(&self.components, |component| match component {
+        GutterComponent::LineNumbers => {
+          numbers_width(self.digits, self.font.clone(), self.font_height)
+        }
+        GutterComponent::Changes => measure(&[CHANGE]),
+        GutterComponent::Diagnostics => measure(&[PROBLEM, SWATCH, LIGHTBULB]),
+        GutterComponent::Folding => measure(&[FOLDED, UNFOLDED]),
+        GutterComponent::Bookmarks => measure(&[BOOKMARK]),
+        GutterComponent::Breakpoints => measure(&[BREAKPOINT]),
+      });
+    self.dimensions.width = if columns.is_empty() {
+      0.0
+    } else {
+      width + GUTTER_MARGIN
+    };
+    self.columns = columns;
+  }
+
+  /// What the click at `position` relative to the top left corner is on.
+  pub fn click_at(
+    &self,
+    position: PhysicalPosition<f64>,
+  ) -> Option<GutterClick> {
+    let line = self.line_at(position.y)?;
+    let x = position.x as f32;
+    let column = self
+      .columns
+      .iter()
+      .find(|column| x < column.x + column.width + (COLUMN_GAP / 2.0));
+    Some(match column.map(|column| column.component) {
+      Some(GutterComponent::Breakpoints) => GutterClick::Breakpoint(line),
+      Some(GutterComponent::Bookmarks) => GutterClick::Bookmark(line),
+      Some(GutterComponent::Diagnostics) => GutterClick::Diagnostics(line),
+      Some(GutterComponent::Folding)
+        if self.foldable.binary_search(&line).is_ok() =>
+      {
+        GutterClick::Fold(line)
+      }
+      _ => GutterClick::Line(line),
+    })
+  }
+
+  /// Whether the diagnostics column shows a swatch next to `line`, which
+  /// problems and code actions are shown over.
+  pub fn shows_swatch(&self, line: usize) -> bool {
+    self
+      .icons
+      .iter()
+      .any(|(at, icon, _)| *at == line && *icon == SWATCH)
+  }
+
+  /// Where the line numbers end, if they are shown.
+  pub fn numbers_x(&self) -> Option<f32> {
+    self
+      .column(GutterComponent::LineNumbers)
+      .map(|column| self.dimensions.x + column.x + column.width)
+  }
+
+  /// What `component` shows next to `line` and in which color, `line` is
+  /// `None` for the rows it's wrapped onto.
+  fn mark(
+    &self,
+    component: GutterComponent,
+    line: Option<usize>,
+  ) -> Option<(String, Color)> {
+    let theme = &self.theme;
+    let line = match (component, line) {
+      (GutterComponent::LineNumbers, None) => {
+        return Some((WRAP_MARK.to_string(), theme.gutter_text))
+      }
+      (_, None) => return None,
+      (_, Some(line)) => line,
+    };
+    let marked = |lines: &[usize]| lines.binary_search(&line).is_ok();
+    let (mark, color) = match component {
+      GutterComponent::LineNumbers => {
+        return Some(((line + 1).to_string(), theme.gutter_text))
+      }
+      GutterComponent::Changes => {
+        let i = self
+          .changes
+          .binary_search_by_key(&line, |(line, _)| *line)
+          .ok()?;
+        let color = if self.changes[i].1 {
+          theme.added
+        } else {
+          theme.removed
+        };
+        (CHANGE, color)
+      }
+      GutterComponent::Diagnostics => {
+        let i = self
+          .icons
+          .binary_search_by_key(&line, |(line, _, _)| *line)
+          .ok()?;
+        let (_, icon, color) = self.icons[i];
+        (icon, color)
+      }
+      GutterComponent::Folding if marked(&self.folded) => {
+        (FOLDED, theme.gutter_text)
+      }
+      GutterComponent::Folding if self.hovered && marked(&self.foldable) => {
+        (UNFOLDED, theme.gutter_text)
+      }
+      GutterComponent::Bookmarks if marked(&self.bookmarks) => {
+        (BOOKMARK, theme.bookmark)
+      }
+      GutterComponent::Breakpoints if marked(&self.breakpoints) => {
+        (BREAKPOINT, theme.error)
+      }
+      GutterComponent::Breakpoints if self.hovered_line == Some(line) => {
+        (BREAKPOINT, theme.gutter.mix(theme.error, GHOST_SHADE))
+      }
+      _ => return None,
+    };
+    Some((mark.to_string(), color))
+  }
+
+  /// The line shown at `y`, relative to the top.
+  pub fn line_at(&self, y: f64) -> Option<usize> {
+    let row = ((y - self.scroll_offset_y) / self.font_height as f64).floor();
+    if row < 0.0 {
+      return None;
+    }
+    match self.rows.borrow().as_ref() {
+      Some(rows) => rows.get(row as usize).map(|row| row.line),
+      None => Some(row as usize).filter(|row| *row < self.text.borrow().len()),
+    }
+  }
+
+  /// Moves the gutter, keeping its width.
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = Dimensions {
+      width: self.dimensions.width,
+      ..dimensions
+    };
     self.rect.resize(
-      screen_size.cast(),
+      screen_size,
       Dimensions {
-        width: self.dimensions.width - GUTTER_MARGIN,
+        width: (self.dimensions.width - GUTTER_MARGIN).max(0.0),
         ..self.dimensions
       },
     );
   }
+}
 
-  fn scroll(
+impl super::super::RenderElement for Gutter {
+  fn click(
     &mut self,
-    offset: PhysicalPosition<f64>,
+    position: PhysicalPosition<f64>,
     _screen_size: PhysicalSize<f32>,
   ) {
-    self.scroll_offset_y = (self.scroll_offset_y + offset.y)
-      .min(0.0)
-      .max(-((self.text.borrow().len() - 3) as f32 * self.font_height) as f64);
+    match self.click_at(position) {
+      Some(GutterClick::Breakpoint(line)) => {
+        toggle(&mut self.breakpoints, line)
+      }
+      Some(GutterClick::Bookmark(line)) => self.toggle_bookmark(line),
+      Some(GutterClick::Fold(line)) => toggle(&mut self.folded, line),
+      _ => {}
+    }
+  }
+
+  fn hover(
+    &mut self,
+    position: PhysicalPosition<f32>,
+  ) -> (Option<CursorIcon>, bool) {
+    let inside = self
+      .dimensions
+      .contains(position)
+      .filter(|position| position.y >= self.covered);
+    let click = inside.and_then(|position| self.click_at(position.cast()));
+    let line = match click {
+      Some(GutterClick::Breakpoint(line))
+      | Some(GutterClick::Bookmark(line))
+      | Some(GutterClick::Fold(line))
+      | Some(GutterClick::Diagnostics(line))
+      | Some(GutterClick::Line(line)) => Some(line),
+      None => None,
+    };
+    let changed = inside.is_some() != self.hovered || line != self.hovered_line;
+    self.hovered = inside.is_some();
+    self.hovered_line = line;
+    let icon = match click {
+      Some(GutterClick::Diagnostics(line)) if self.shows_swatch(line) => {
+        Some(CursorIcon::Hand)
+      }
+      Some(GutterClick::Line(_)) | Some(GutterClick::Diagnostics(_)) | None => {
+        None
+      }
+      Some(_) => Some(CursorIcon::Hand),
+    };
+    (icon, changed)
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.gutter.rgb());
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    // keeps the first line in view
+    self.font = font.clone();
+    self.font_height = font_height;
+    self.measure();
   }
 
   fn redraw(
@@ -93,43 +522,65 @@ impl super::super::RenderElement for Gutter {
     target: &TextureView,
     size: PhysicalSize<u32>,
   ) {
-    let upper_bound =
-      ((-self.scroll_offset_y) / self.font_height as f64).floor() as usize;
-    let lower_bound = (upper_bound
-      + (self.dimensions.height / self.font_height).ceil() as usize)
-      .min(self.text.borrow().len());
-
-    let mut line_count = upper_bound;
-    let mut line_numbers = String::new();
-    for _ in &self.text.borrow()[upper_bound..lower_bound] {
-      line_count += 1;
-      line_numbers += &format!("{}\n", line_count);
+    let (upper_bound, top) =
+      viewport::first_row(self.scroll_offset_y, self.font_height);
+    let visible = (self.dimensions.height / self.font_height).ceil() as usize;
+
+    // the marks of each column, in a layer for each color
+    let mut columns = vec![vec![]; self.columns.len()];
+    let mut row = 0;
+    let mut push = |line: Option<usize>| {
+      for (column, layers) in self.columns.iter().zip(&mut columns) {
+        push_row(layers, row, self.mark(column.component, line));
+      }
+      row += 1;
+    };
+    if let Some(rows) = self.rows.borrow().as_ref() {
+      for row in rows.iter().skip(upper_bound).take(visible) {
+        push(Some(row.line).filter(|_| row.continuation.is_none()));
+      }
+    } else {
+      let lower_bound = (upper_bound + visible).min(self.text.borrow().len());
+      for line in upper_bound..lower_bound {
+        push(Some(line));
+      }
     }
 
-    glyph_brush.queue(Section {
-      screen_position: (
-        (self.dimensions.x
-          + (self.dimensions.width - (GUTTER_PADDING + GUTTER_MARGIN))),
-        -(((-self.scroll_offset_y as f32) % self.font_height)
-          - self.dimensions.y),
-      ),
-      text: vec![Text::new(&line_numbers)
-        .with_color([0.9, 0.9, 0.9, 1.0])
-        .with_scale(self.font_height)],
-      layout: Layout::default_wrap().h_align(HorizontalAlign::Right),
-      ..Section::default()
-    });
+    let y = self.dimensions.y + top;
+    for (column, layers) in self.columns.iter().zip(&columns) {
+      // the line numbers are aligned to the right
+      let (x, layout) = match column.component {
+        GutterComponent::LineNumbers => (
+          column.x + column.width,
+          Layout::default_wrap().h_align(HorizontalAlign::Right),
+        ),
+        _ => (column.x, Layout::default_wrap()),
+      };
+      for (color, rows) in layers {
+        glyph_brush.queue(Section {
+          screen_position: (self.dimensions.x + x, y),
+          text: vec![Text::new(rows)
+            .with_color(color.rgba())
+            .with_scale(self.font_height)],
+          layout,
+          ..Section::default()
+        });
+      }
+    }
 
-    glyph_brush
-      .draw_queued_with_transform_and_scissoring(
-        device,
-        staging_belt,
-        encoder,
-        target,
-        wgpu_glyph::orthographic_projection(size.width, size.height),
-        self.dimensions.into(),
-      )
-      .unwrap();
+    super::super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(Dimensions {
+        y: self.dimensions.y + self.covered,
+        height: self.dimensions.height - self.covered,
+        ..self.dimensions
+      }),
+    );
   }
 
   fn get_rects(&self) -> Vec<&Rectangle> {
@@ -144,3 +595,75 @@ impl super::super::RenderElement for Gutter {
     self.dimensions
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bookmarks_follow_lines() {
+    let mut bookmarks = vec![1, 4, 6];
+    // a line split at line 4
+    shift_bookmarks(&mut bookmarks, 4, 1);
+    assert_eq!(bookmarks, vec![1, 4, 7]);
+    // lines 2 and 3 joined into line 1
+    shift_bookmarks(&mut bookmarks, 1, -2);
+    assert_eq!(bookmarks, vec![1, 2, 5]);
+    shift_bookmarks(&mut bookmarks, 1, -1);
+    assert_eq!(bookmarks, vec![1, 4]);
+
+    toggle(&mut bookmarks, 2);
+    assert_eq!(bookmarks, vec![1, 2, 4]);
+    toggle(&mut bookmarks, 1);
+    assert_eq!(bookmarks, vec![2, 4]);
+  }
+
+  #[test]
+  fn lays_out_components() {
+    use GutterComponent::*;
+    let width = |component| if component == LineNumbers { 20.0 } else { 8.0 };
+    let (columns, total) = layout(&[Bookmarks, LineNumbers, Bookmarks], width);
+    let x = |component| {
+      columns
+        .iter()
+        .find(|column| column.component == component)
+        .map(|column| column.x)
+    };
+    // a component listed twice is shown once
+    assert_eq!(columns.len(), 2);
+    assert_eq!(x(Bookmarks), Some(GUTTER_PADDING / 2.0));
+    assert_eq!(
+      x(LineNumbers),
+      Some(GUTTER_PADDING / 2.0 + 8.0 + COLUMN_GAP)
+    );
+    assert_eq!(x(Folding), None);
+    assert_eq!(total, GUTTER_PADDING + 28.0 + COLUMN_GAP);
+    assert_eq!(layout(&[], width).1, GUTTER_PADDING);
+
+    let mut layers = vec![];
+    push_row(&mut layers, 0, None);
+    push_row(&mut layers, 1, Some((String::from("1"), Color([1.0; 3]))));
+    push_row(&mut layers, 2, Some((String::from("2"), Color([0.0; 3]))));
+    assert_eq!(
+      layers,
+      vec![
+        (Color([1.0; 3]), String::from("\n1\n\n")),
+        (Color([0.0; 3]), String::from("\n\n2\n")),
+      ]
+    );
+  }
+
+  #[test]
+  fn selects_whole_lines() {
+    let lines =
+      vec![String::from("a"), String::from("bc"), String::from("äöü")];
+    assert_eq!(line_selection(&lines, 0, 0), ((0, 0), (1, 0)));
+    assert_eq!(line_selection(&lines, 0, 1), ((0, 0), (2, 0)));
+    // the last line has no next one to end at
+    assert_eq!(line_selection(&lines, 1, 2), ((1, 0), (2, 3)));
+    assert_eq!(line_selection(&lines, 2, 0), ((2, 3), (0, 0)));
+    assert_eq!(digits(0), 1);
+    assert_eq!(digits(99), 2);
+    assert_eq!(digits(100), 3);
+  }
+}
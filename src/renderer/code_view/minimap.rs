@@ -0,0 +1,509 @@
+use crate::config::{Color, Theme};
+use crate::highlight::{Grammar, Highlighter, Kind};
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use bytemuck::{Pod, Zeroable};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+use wgpu::util::{DeviceExt, StagingBelt};
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::GlyphBrush;
+use winit::dpi::PhysicalSize;
+
+pub const MINIMAP_WIDTH: f32 = 100.0;
+const PADDING: f32 = 5.0;
+const LINE_HEIGHT: f32 = 3.0;
+const BLOCK_HEIGHT: f32 = 2.0;
+const CHAR_WIDTH: f32 = 1.0;
+
+/// A colored quad, positioned relative to the start of its line until the
+/// lines are joined for upload.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct Instance {
+  position: [f32; 2],
+  size: [f32; 2],
+  color: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct Uniforms {
+  screen_size: [f32; 2],
+  origin: [f32; 2],
+}
+
+/// Draws the quads of a minimap, shared between all of them.
+pub struct MinimapPipeline {
+  pipeline: wgpu::RenderPipeline,
+  bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl MinimapPipeline {
+  pub fn new(device: &wgpu::Device) -> Self {
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+      label: Some("Minimap Shader Module"),
+      source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!(
+        "./minimap_shader.wgsl"
+      ))),
+      flags: wgpu::ShaderFlags::VALIDATION,
+    });
+
+    let bind_group_layout =
+      device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Minimap Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+          binding: 0,
+          visibility: wgpu::ShaderStage::VERTEX,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+          },
+          count: None,
+        }],
+      });
+
+    let render_pipeline_layout =
+      device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Minimap Render Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+      });
+
+    let pipeline =
+      device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Minimap Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+          module: &shader,
+          entry_point: "vs_main",
+          buffers: &[wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>()
+              as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![
+              0 => Float32x2,
+              1 => Float32x2,
+              2 => Float32x3
+            ],
+          }],
+        },
+        fragment: Some(wgpu::FragmentState {
+          module: &shader,
+          entry_point: "fs_main",
+          targets: &[super::super::render_format().into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+          topology: wgpu::PrimitiveTopology::TriangleStrip,
+          ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: Default::default(),
+      });
+
+    Self {
+      pipeline,
+      bind_group_layout,
+    }
+  }
+}
+
+/// A zoomed out view of the whole text, drawn from the highlight spans as
+/// one quad per span.
+pub struct Minimap {
+  pipeline: Rc<MinimapPipeline>,
+  text: Rc<RefCell<Vec<String>>>,
+  highlighter: Highlighter,
+  /// Whether only the lines visible in the code view are highlighted.
+  viewport_only: bool,
+  /// Version of the text the quads were built for.
+  version: Option<usize>,
+  lines: Vec<Vec<Instance>>,
+  /// The quads of all lines joined, as uploaded.
+  instances: Vec<Instance>,
+  /// Index of the first quad of each line in `instances`.
+  line_starts: Vec<usize>,
+  /// The lines whose quads changed since they were uploaded, up to the end
+  /// when lines were inserted or removed.
+  dirty: Option<Range<usize>>,
+  instance_buffer: wgpu::Buffer,
+  /// Number of quads `instance_buffer` has room for.
+  capacity: usize,
+  uniform_buffer: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+  /// First line and number of lines visible in the code view.
+  viewport: (usize, usize),
+  /// How far the minimap is scrolled, in pixels.
+  offset: f32,
+  theme: Theme,
+  rect: Rectangle,
+  slider: Rectangle,
+  screen_size: PhysicalSize<f32>,
+  pub dimensions: Dimensions,
+}
+
+fn create_instance_buffer(
+  device: &wgpu::Device,
+  capacity: usize,
+) -> wgpu::Buffer {
+  device.create_buffer(&wgpu::BufferDescriptor {
+    label: Some("Minimap Instance Buffer"),
+    size: (capacity.max(1) * std::mem::size_of::<Instance>())
+      as wgpu::BufferAddress,
+    usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+    mapped_at_creation: false,
+  })
+}
+
+impl Minimap {
+  pub fn new(
+    device: &wgpu::Device,
+    pipeline: Rc<MinimapPipeline>,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+    text: Rc<RefCell<Vec<String>>>,
+    theme: Theme,
+  ) -> Self {
+    let uniform_buffer =
+      device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Minimap Uniform Buffer"),
+        contents: bytemuck::bytes_of(&Uniforms {
+          screen_size: [screen_size.width, screen_size.height],
+          origin: [dimensions.x, dimensions.y],
+        }),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+      });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("Minimap Bind Group"),
+      layout: &pipeline.bind_group_layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: uniform_buffer.as_entire_binding(),
+      }],
+    });
+
+    Self {
+      pipeline,
+      text,
+      highlighter: Highlighter::default(),
+      viewport_only: false,
+      version: None,
+      lines: vec![],
+      instances: vec![],
+      line_starts: vec![],
+      dirty: None,
+      instance_buffer: create_instance_buffer(device, 0),
+      capacity: 0,
+      uniform_buffer,
+      bind_group,
+      viewport: (0, 0),
+      offset: 0.0,
+      theme,
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.sidebar.rgb(),
+        None,
+      ),
+      slider: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.overlay.rgb(),
+        None,
+      ),
+      screen_size,
+      dimensions,
+    }
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.screen_size = screen_size;
+    self.dimensions = dimensions;
+    self.rect.resize(screen_size, dimensions);
+    self.layout();
+  }
+
+  pub fn set_grammar(&mut self, grammar: Rc<Grammar>) {
+    self.highlighter.set_grammar(grammar);
+    self.version = None;
+  }
+
+  /// Reports that the lines `start..start + removed` were replaced by
+  /// `inserted` lines, so only those are highlighted again.
+  pub fn edit(&mut self, start: usize, removed: usize, inserted: usize) {
+    self.highlighter.edit(start, removed, inserted);
+    if start <= self.lines.len() {
+      let end = (start + removed).min(self.lines.len());
+      self.lines.splice(start..end, (0..inserted).map(|_| vec![]));
+      // the lines after an insertion or removal move
+      let end = match removed == inserted {
+        true => start + inserted,
+        false => self.lines.len(),
+      };
+      self.mark_dirty(start..end);
+    }
+  }
+
+  fn mark_dirty(&mut self, lines: Range<usize>) {
+    self.dirty = Some(match self.dirty.take() {
+      Some(dirty) => dirty.start.min(lines.start)..dirty.end.max(lines.end),
+      None => lines,
+    });
+  }
+
+  /// Reports a change to the text which wasn't given to
+  /// [`Minimap::edit`].
+  pub fn invalidate(&mut self) {
+    self.highlighter.invalidate();
+  }
+
+  /// Highlights only the lines visible in the code view from now on, the
+  /// rest keep their colors until scrolled into view.
+  pub fn limit_to_viewport(&mut self) {
+    self.viewport_only = true;
+  }
+
+  /// Highlights all lines again, catching up with the ones changed out of
+  /// view on the next update.
+  pub fn highlight_all(&mut self) {
+    self.viewport_only = false;
+    self.version = None;
+  }
+
+  /// Brings the quads up to date with `version` of the text and follows the
+  /// lines visible in the code view.
+  pub fn update(&mut self, version: usize, viewport: (usize, usize)) {
+    let scrolled = viewport != self.viewport;
+    if self.version != Some(version) || (self.viewport_only && scrolled) {
+      let text = self.text.borrow();
+      let changed = if self.viewport_only {
+        let (first, visible) = viewport;
+        self.highlighter.update_range(&text, first..first + visible)
+      } else {
+        self.highlighter.update(&text)
+      };
+      drop(text);
+      let count = self.text.borrow().len();
+      if count != self.lines.len() {
+        self.mark_dirty(count.min(self.lines.len())..count);
+        self.lines.resize(count, vec![]);
+      }
+      for line in changed {
+        self.lines[line] = self.build_line(line);
+        self.mark_dirty(line..line + 1);
+      }
+      self.version = Some(version);
+    }
+    if scrolled {
+      self.viewport = viewport;
+      self.layout();
+    }
+  }
+
+  fn color(&self, kind: Kind) -> Color {
+    match kind {
+      Kind::Text => self.theme.text,
+      Kind::Keyword => self.theme.keyword,
+      Kind::String => self.theme.string,
+      Kind::Comment => self.theme.comment,
+      Kind::Number => self.theme.number,
+    }
+  }
+
+  fn build_line(&self, line: usize) -> Vec<Instance> {
+    let max_width = MINIMAP_WIDTH - (PADDING * 2.0);
+    self
+      .highlighter
+      .spans(line)
+      .iter()
+      .map(|span| {
+        let x = span.start as f32 * CHAR_WIDTH;
+        let width = (span.end - span.start) as f32 * CHAR_WIDTH;
+        (x, width.min(max_width - x), span.kind)
+      })
+      .take_while(|(_, width, _)| *width > 0.0)
+      .map(|(x, width, kind)| Instance {
+        position: [x, 0.0],
+        size: [width, BLOCK_HEIGHT],
+        color: self.color(kind).rgb(),
+      })
+      .collect()
+  }
+
+  /// Scrolls the minimap so that the part visible in the code view is shown,
+  /// and moves the slider marking it.
+  fn layout(&mut self) {
+    let (first, visible) = self.viewport;
+    let content = self.lines.len() as f32 * LINE_HEIGHT;
+    let max_offset = (content - self.dimensions.height).max(0.0);
+    let scrollable = self.lines.len().saturating_sub(visible).max(1);
+    self.offset =
+      (max_offset * (first as f32 / scrollable as f32)).min(max_offset);
+    self.slider.resize(
+      self.screen_size,
+      Dimensions {
+        y: self.dimensions.y + (first as f32 * LINE_HEIGHT) - self.offset,
+        height: visible as f32 * LINE_HEIGHT,
+        ..self.dimensions
+      },
+    );
+  }
+
+  /// Joins the quads of the dirty lines into the others and writes the
+  /// quads which changed to the instance buffer, all of them if it had to
+  /// grow.
+  fn upload(
+    &mut self,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+  ) {
+    let dirty = match self.dirty.take() {
+      Some(dirty) => dirty,
+      None => return,
+    };
+    let old_starts = std::mem::take(&mut self.line_starts);
+    // the quads of the lines before the first dirty one stay in place
+    let first = dirty.start.min(old_starts.len().saturating_sub(1));
+    let offset = old_starts.get(first).copied().unwrap_or(0);
+    self.line_starts.extend_from_slice(&old_starts[..first]);
+
+    // without lines inserted or removed, the lines after the dirty ones
+    // only move when the dirty ones got a different number of quads
+    let same_lines = old_starts.len() == self.lines.len() + 1;
+    let mut instances = vec![];
+    let mut old_end = self.instances.len();
+    for i in first..=self.lines.len() {
+      let start = offset + instances.len();
+      if same_lines && i >= dirty.end && old_starts[i] == start {
+        self.line_starts.extend_from_slice(&old_starts[i..]);
+        old_end = start;
+        break;
+      }
+      self.line_starts.push(start);
+      if let Some(line) = self.lines.get(i) {
+        let y = i as f32 * LINE_HEIGHT;
+        instances.extend(line.iter().map(|instance| Instance {
+          position: [instance.position[0], instance.position[1] + y],
+          ..*instance
+        }));
+      }
+    }
+    let written = offset..offset + instances.len();
+    self.instances.splice(offset..old_end, instances);
+
+    let written = if self.instances.len() > self.capacity {
+      self.capacity = self.instances.len().next_power_of_two();
+      self.instance_buffer = create_instance_buffer(device, self.capacity);
+      0..self.instances.len()
+    } else {
+      written
+    };
+    let bytes: &[u8] = bytemuck::cast_slice(&self.instances[written.clone()]);
+    if let Some(size) = wgpu::BufferSize::new(bytes.len() as u64) {
+      let at = (written.start * std::mem::size_of::<Instance>())
+        as wgpu::BufferAddress;
+      staging_belt
+        .write_buffer(encoder, &self.instance_buffer, at, size, device)
+        .copy_from_slice(bytes);
+    }
+  }
+}
+
+impl super::super::RenderElement for Minimap {
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.sidebar.rgb());
+    self.slider.set_color(theme.overlay.rgb());
+    self.lines = (0..self.lines.len()).map(|i| self.build_line(i)).collect();
+    self.mark_dirty(0..self.lines.len());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, _font_height: f32) {}
+
+  fn redraw(
+    &mut self,
+    _glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    self.upload(device, staging_belt, encoder);
+
+    let uniforms = Uniforms {
+      screen_size: [size.width as f32, size.height as f32],
+      origin: [self.dimensions.x + PADDING, self.dimensions.y - self.offset],
+    };
+    let bytes = bytemuck::bytes_of(&uniforms);
+    staging_belt
+      .write_buffer(
+        encoder,
+        &self.uniform_buffer,
+        0,
+        wgpu::BufferSize::new(bytes.len() as u64).unwrap(),
+        device,
+      )
+      .copy_from_slice(bytes);
+
+    // only the lines in view are drawn
+    let first = (self.offset / LINE_HEIGHT) as usize;
+    let last = first + (self.dimensions.height / LINE_HEIGHT).ceil() as usize;
+    let quads = match (
+      self.line_starts.get(first.min(self.lines.len())),
+      self.line_starts.get(last.min(self.lines.len())),
+    ) {
+      (Some(start), Some(end)) if start < end => *start as u32..*end as u32,
+      _ => return,
+    };
+
+    let x = (self.dimensions.x.max(0.0) as u32).min(size.width);
+    let y = (self.dimensions.y.max(0.0) as u32).min(size.height);
+    let width = (self.dimensions.width.max(0.0) as u32).min(size.width - x);
+    let height = (self.dimensions.height.max(0.0) as u32).min(size.height - y);
+    if width == 0 || height == 0 {
+      return;
+    }
+
+    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Minimap"),
+      color_attachments: &[wgpu::RenderPassColorAttachment {
+        view: target,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Load,
+          store: true,
+        },
+      }],
+      depth_stencil_attachment: None,
+    });
+    rpass.set_pipeline(&self.pipeline.pipeline);
+    rpass.set_bind_group(0, &self.bind_group, &[]);
+    rpass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+    rpass.set_scissor_rect(x, y, width, height);
+    rpass.draw(0..4, quads);
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect, &self.slider]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
@@ -0,0 +1,26 @@
+[[block]]
+struct Uniforms {
+  screen_size: vec2<f32>;
+  origin: vec2<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+  [[builtin(position)]] out_pos: vec4<f32>;
+  [[location(0)]] out_color: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32, [[location(0)]] in_pos: vec2<f32>, [[location(1)]] in_size: vec2<f32>, [[location(2)]] in_color: vec3<f32>) -> VertexOutput {
+  let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+  let pos = uniforms.origin + in_pos + (corner * in_size);
+  let ndc = vec2<f32>(((pos.x / uniforms.screen_size.x) * 2.0) - 1.0, 1.0 - ((pos.y / uniforms.screen_size.y) * 2.0));
+  return VertexOutput(vec4<f32>(ndc, 0.0, 1.0), in_color);
+}
+
+[[stage(fragment)]]
+fn fs_main([[location(0)]] in_color: vec3<f32>) -> [[location(0)]] vec4<f32> {
+  return vec4<f32>(in_color, 1.0);
+}
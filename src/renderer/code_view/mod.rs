@@ -1,38 +1,117 @@
+use crate::colors::ColorLiteral;
+use crate::completion::{self, Item};
+use crate::config::{BracketsConfig, GutterConfig, Theme};
+use crate::documents::{self, Document};
+use crate::editor::{Change, Direction, Message};
+use crate::folding::{self, Region};
+use crate::highlight::Grammar;
+use crate::outline::{self, Symbol};
 use crate::renderer::rectangle::Rectangle;
 use crate::renderer::Dimensions;
-use std::cell::RefCell;
+use crate::shaping::Ligatures;
+use crate::structure::{Position, Span};
+use crate::transform::{self, Transform};
+use crate::viewport::Overscroll;
+use crate::wrap::WrapConfig;
+use std::cell::{Ref, RefCell};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
 use wgpu_glyph::ab_glyph::FontArc;
-use winit::dpi::PhysicalSize;
+use wgpu_glyph::GlyphBrush;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::VirtualKeyCode;
+use winit::window::CursorIcon;
 
 mod code;
+mod decorations;
 mod gutter;
+mod minimap;
+mod sticky;
+mod watchdog;
+
+pub use minimap::MinimapPipeline;
+pub use watchdog::{Degradation, Reduction};
+
+/// How long typing has to pause before a deferred minimap is updated.
+const MINIMAP_DELAY: Duration = Duration::from_millis(500);
+/// Bytes of a paste inserted a frame.
+const PASTE_CHUNK: usize = 256 * 1024;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 
 pub struct CodeView {
-  #[allow(dead_code)]
-  text: Rc<RefCell<Vec<String>>>,
+  document: Rc<Document>,
+  id: usize,
+  /// The version of the document the view was last updated for, it's behind
+  /// when the text was edited in another window.
+  version: usize,
+  /// The symbols of the text and the version they were found in.
+  symbols: (Vec<Symbol>, Option<usize>),
+  /// The words of the text as completions and the version they were found
+  /// in.
+  words: (Vec<Item>, Option<usize>),
+  /// The version of the text and the cursor position code actions were
+  /// found for, shown while neither changed.
+  lightbulb: Option<(usize, (usize, usize))>,
+  /// Where the last task reported problems.
+  problems: Vec<Position>,
+  /// The text whose occurrences are highlighted.
+  search: Option<String>,
+  /// The regions which can be folded and the version they were found in.
+  regions: (Vec<Region>, Option<usize>),
+  /// The file the text is saved to, `None` for unsaved buffers.
+  path: Option<PathBuf>,
+  /// The id of the language the text is in, if it's known.
+  language: Option<String>,
+  /// Whether the language was picked by hand rather than detected, so it
+  /// isn't detected again.
+  language_chosen: bool,
+  /// Whether the tab is replaced by the next file opened from the explorer,
+  /// cleared once the text is edited.
+  pub preview: bool,
+  /// Pinned tabs are never closed automatically.
+  pub pinned: bool,
+  watchdog: watchdog::Watchdog,
+  /// Reductions made but not reported yet.
+  degradations: Vec<Degradation>,
+  last_edit: Option<Instant>,
+  /// A large paste inserted a chunk a frame, and how much of it was.
+  paste: Option<(String, usize)>,
+  /// The version of the text shown in the minimap.
+  minimap_version: usize,
+  grammar: Rc<Grammar>,
+  /// The version of the text its brackets were found in.
+  brackets_version: Option<usize>,
+  /// The line a selection of lines dragged in the gutter started at.
+  line_drag: Option<usize>,
   gutter: gutter::Gutter,
+  /// Whether only the text is shown, without the gutter and minimap.
+  zen: bool,
   code: code::Code,
+  minimap: minimap::Minimap,
+  sticky: sticky::StickyScroll,
   pub dimensions: Dimensions,
 }
 
 impl CodeView {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     device: &wgpu::Device,
+    minimap_pipeline: Rc<MinimapPipeline>,
     screen_size: PhysicalSize<f32>,
     font: FontArc,
     font_height: f32,
     dimensions: Dimensions,
-    text: String,
+    document: Rc<Document>,
+    theme: Theme,
   ) -> Self {
-    let mut split_text =
-      text.lines().map(|s| s.to_string()).collect::<Vec<String>>();
-    if text.ends_with('\n') {
-      split_text.push(String::from(""));
-    }
-
-    let text = Rc::new(RefCell::new(split_text));
+    let text = Rc::clone(&document.text);
+    let rows = Rc::new(RefCell::new(None));
 
     let gutter = gutter::Gutter::new(
       device,
@@ -41,6 +120,8 @@ impl CodeView {
       screen_size,
       dimensions,
       Rc::clone(&text),
+      Rc::clone(&rows),
+      theme,
     );
 
     let code = code::Code::new(
@@ -50,19 +131,845 @@ impl CodeView {
       font_height,
       Dimensions {
         x: dimensions.x + gutter.dimensions.width,
-        width: dimensions.width - gutter.dimensions.width,
+        width: dimensions.width
+          - gutter.dimensions.width
+          - minimap::MINIMAP_WIDTH,
         ..dimensions
       },
       Rc::clone(&text),
+      rows,
+      theme,
+    );
+
+    let sticky = sticky::StickyScroll::new(
+      device,
+      screen_size,
+      font_height,
+      Rc::clone(&text),
+      theme,
+    );
+
+    let minimap = minimap::Minimap::new(
+      device,
+      minimap_pipeline,
+      screen_size,
+      Dimensions {
+        x: dimensions.x + dimensions.width - minimap::MINIMAP_WIDTH,
+        width: minimap::MINIMAP_WIDTH,
+        ..dimensions
+      },
+      Rc::clone(&text),
+      theme,
     );
 
     Self {
-      text,
+      id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+      version: document.version(),
+      document,
+      symbols: (vec![], None),
+      words: (vec![], None),
+      regions: (vec![], None),
+      lightbulb: None,
+      problems: vec![],
+      search: None,
+      path: None,
+      language: None,
+      language_chosen: false,
+      preview: false,
+      pinned: false,
+      watchdog: Default::default(),
+      degradations: vec![],
+      last_edit: None,
+      paste: None,
+      minimap_version: 0,
+      grammar: Rc::new(Grammar::generic()),
+      brackets_version: None,
+      line_drag: None,
       gutter,
+      zen: false,
       code,
+      minimap,
+      sticky,
       dimensions,
     }
   }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.gutter.set_dimensions(screen_size, dimensions);
+    let (gutter_width, minimap_width) = self.side_widths();
+    self.code.set_dimensions(
+      screen_size,
+      Dimensions {
+        x: dimensions.x + gutter_width,
+        width: dimensions.width - gutter_width - minimap_width,
+        ..dimensions
+      },
+    );
+    self.minimap.set_dimensions(
+      screen_size,
+      Dimensions {
+        x: dimensions.x + dimensions.width - minimap_width,
+        width: minimap_width,
+        ..dimensions
+      },
+    );
+  }
+
+  /// Widths of the gutter and the minimap, which zen mode hides.
+  fn side_widths(&self) -> (f32, f32) {
+    if self.zen {
+      (0.0, 0.0)
+    } else {
+      (self.gutter.dimensions.width, minimap::MINIMAP_WIDTH)
+    }
+  }
+
+  /// Highlights the changed lines and words of a unified diff.
+  pub fn set_diff(&mut self, diff: bool) {
+    self.code.set_diff(diff);
+  }
+
+  /// The removed and added lines when the text is a diff.
+  pub fn changed_lines(&self) -> Vec<usize> {
+    self.code.changed_lines()
+  }
+
+  /// Shows only the text, without the gutter and minimap.
+  pub fn set_zen(&mut self, screen_size: PhysicalSize<f32>, zen: bool) {
+    self.zen = zen;
+    self.set_dimensions(screen_size, self.dimensions);
+  }
+
+  /// The elements drawn and clicked, all of them unless in zen mode.
+  fn shown(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    if self.zen {
+      vec![&mut self.code, &mut self.sticky]
+    } else {
+      super::RenderElement::get_elements(self)
+    }
+  }
+
+  pub fn set_wrap(&mut self, screen_size: PhysicalSize<f32>, wrap: WrapConfig) {
+    self.code.set_wrap(screen_size, wrap);
+  }
+
+  /// Sets whether glyphs are placed on whole pixels.
+  pub fn set_hinting(&mut self, hinting: bool) {
+    self.code.hinting = hinting;
+  }
+
+  /// Sets how far the text can be scrolled past its last line.
+  pub fn set_overscroll(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    overscroll: Overscroll,
+  ) {
+    self.code.set_overscroll(screen_size, overscroll);
+  }
+
+  /// Sets whether the cursor is kept in the middle of the view.
+  pub fn set_typewriter(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    typewriter: bool,
+  ) {
+    self.code.set_typewriter(screen_size, typewriter);
+  }
+
+  /// Draws `ligatures` in place of the glyphs they join.
+  pub fn set_ligatures(&mut self, ligatures: Rc<Ligatures>) {
+    self.code.set_ligatures(ligatures);
+  }
+
+  /// Switches between replacing and inserting what is typed.
+  pub fn set_overwrite(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    overwrite: bool,
+  ) {
+    self.code.set_overwrite(screen_size, overwrite);
+  }
+
+  /// Sets the columns to draw a vertical line at.
+  pub fn set_rulers(&mut self, rulers: Vec<usize>) {
+    self.code.rulers = rulers;
+  }
+
+  pub fn text(&self) -> Ref<'_, Vec<String>> {
+    self.document.text.borrow()
+  }
+
+  pub fn replace_text(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    lines: Vec<String>,
+  ) {
+    self.sync(screen_size);
+    self.version = self.document.changed();
+    self.preview = false;
+    self.code.replace_text(screen_size, lines);
+    self.minimap.invalidate();
+    self.clamp_bookmarks();
+  }
+
+  /// Replaces the text with `text`, which then counts as unsaved.
+  pub fn set_text(&mut self, screen_size: PhysicalSize<f32>, text: &str) {
+    self.replace_text(screen_size, documents::split_lines(text));
+  }
+
+  /// Whether the text was edited in another window since the view was last
+  /// updated.
+  pub fn is_behind(&self) -> bool {
+    self.version != self.document.version()
+  }
+
+  /// Catches up with edits made to the text in another window.
+  pub fn sync(&mut self, screen_size: PhysicalSize<f32>) {
+    if !self.is_behind() {
+      return;
+    }
+    self.version = self.document.version();
+    self.code.text_changed(screen_size);
+    self.minimap.invalidate();
+    self.clamp_bookmarks();
+  }
+
+  /// Whether the text is shown in another window too.
+  pub fn is_shared(&self) -> bool {
+    Rc::strong_count(&self.document) > 1
+  }
+
+  /// The text shared with the other views of it.
+  pub fn document(&self) -> &Rc<Document> {
+    &self.document
+  }
+
+  /// Identifies the current text, changes whenever the text or the view does.
+  pub fn revision(&self) -> (usize, usize) {
+    (self.id, self.version)
+  }
+
+  pub fn symbols(&mut self) -> &[Symbol] {
+    if self.symbols.1 != Some(self.version) {
+      self.symbols = (
+        outline::symbols(&self.document.text.borrow()),
+        Some(self.version),
+      );
+    }
+    &self.symbols.0
+  }
+
+  /// The words of the text as completions. Without `refresh` the words last
+  /// found are kept even if the text changed since, like while the word
+  /// being completed is typed.
+  pub fn words(&mut self, refresh: bool) -> &[Item] {
+    let found = self.words.1;
+    if found.is_none() || (refresh && found != Some(self.version)) {
+      self.words = (
+        completion::buffer_words(&self.document.text.borrow()),
+        Some(self.version),
+      );
+    }
+    &self.words.0
+  }
+
+  pub fn cursor_position(&self) -> (usize, usize) {
+    self.code.cursor_position()
+  }
+
+  /// How far the text is scrolled, see [`Code::scroll_y`].
+  pub fn scroll_y(&self) -> f64 {
+    self.code.scroll_y()
+  }
+
+  pub fn selection(&self) -> Option<Span> {
+    self.code.selection()
+  }
+
+  /// The selected text if it's within a line.
+  pub fn selected_text(&self) -> Option<String> {
+    let span = self.code.selection()?;
+    let (line, start) = span.start;
+    let (end_line, end) = span.end;
+    if line != end_line || start == end {
+      return None;
+    }
+    let text = self.text();
+    let selected = text[line].graphemes(true).skip(start).take(end - start);
+    Some(selected.collect())
+  }
+
+  pub fn cursor_dimensions(&self) -> Dimensions {
+    self.code.cursor_dimensions()
+  }
+
+  /// Moves the cursor to the given zero based position and scrolls it into
+  /// view.
+  pub fn goto(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    row: usize,
+    column: usize,
+  ) {
+    let scroll_y = self.code.set_cursor(row, column);
+    super::RenderElement::scroll(
+      self,
+      PhysicalPosition {
+        x: 0.0,
+        y: scroll_y,
+      },
+      screen_size,
+    );
+  }
+
+  /// Scrolls the text at `position` back under it after the font was scaled
+  /// by `ratio`, which kept the first line in view.
+  pub fn zoom_at(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    position: PhysicalPosition<f32>,
+    ratio: f32,
+  ) {
+    if let Some(at) = self.code.dimensions.contains(position) {
+      let shift = 1.0 - ratio;
+      super::RenderElement::scroll(
+        self,
+        PhysicalPosition::new((-at.x * shift) as f64, (at.y * shift) as f64),
+        screen_size,
+      );
+    }
+  }
+
+  pub fn path(&self) -> Option<&Path> {
+    self.path.as_deref()
+  }
+
+  /// Sets the file the text was read from.
+  pub fn set_path(&mut self, path: Option<PathBuf>) {
+    self.path = path;
+  }
+
+  pub fn language(&self) -> Option<&str> {
+    self.language.as_deref()
+  }
+
+  pub fn set_language(&mut self, language: Option<String>) {
+    self.language = language;
+  }
+
+  pub fn language_chosen(&self) -> bool {
+    self.language_chosen
+  }
+
+  /// Sets the language picked by hand, `None` for plain text.
+  pub fn choose_language(&mut self, language: Option<String>) {
+    self.language = language;
+    self.language_chosen = true;
+  }
+
+  /// Whether another program changed the file since it was read or saved.
+  /// Only reported once per change.
+  pub fn changed_on_disk(&mut self) -> bool {
+    let current = match self.path.as_deref() {
+      Some(path) => documents::modified(path),
+      None => return false,
+    };
+    if current == self.document.disk_modified() {
+      return false;
+    }
+    self.document.set_disk_modified(current);
+    true
+  }
+
+  /// Replaces the text with the contents of the file.
+  pub fn reload(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+  ) -> Result<(), anyhow::Error> {
+    self.sync(screen_size);
+    let path = self
+      .path
+      .as_ref()
+      .ok_or_else(|| anyhow::anyhow!("buffer isn't backed by a file"))?;
+    let text = std::fs::read_to_string(path)?;
+    self.version = self.document.changed();
+    self.document.saved(path);
+    self
+      .code
+      .replace_text(screen_size, documents::split_lines(&text));
+    self.minimap.invalidate();
+    self.clamp_bookmarks();
+    Ok(())
+  }
+
+  /// Sets the grammar the minimap is highlighted with.
+  pub fn set_grammar(&mut self, grammar: Rc<Grammar>) {
+    self.minimap.set_grammar(Rc::clone(&grammar));
+    self.grammar = grammar;
+    self.brackets_version = None;
+  }
+
+  /// Shows the columns of `config` in the gutter.
+  pub fn set_gutter(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    config: &GutterConfig,
+  ) {
+    if self.gutter.set_components(&config.components) {
+      self.set_dimensions(screen_size, self.dimensions);
+    }
+  }
+
+  /// Shows that there are code actions for the cursor at `cursor` in the
+  /// text of `version`.
+  pub fn set_lightbulb(&mut self, version: usize, cursor: (usize, usize)) {
+    self.lightbulb = Some((version, cursor));
+  }
+
+  /// Marks the zero based positions the last task reported a problem at.
+  pub fn set_problems(&mut self, mut problems: Vec<Position>) {
+    problems.sort_unstable();
+    problems.dedup();
+    self.problems = problems;
+  }
+
+  /// Sets the text whose occurrences are highlighted, if any.
+  pub fn set_search(&mut self, find: Option<&str>) {
+    if self.search.as_deref() != find {
+      self.search = find.map(String::from);
+    }
+  }
+
+  pub fn set_brackets(&mut self, config: BracketsConfig) {
+    self.code.set_brackets_config(config);
+    self.brackets_version = None;
+  }
+
+  /// Decorates the text in view and places the decorations, the rulers,
+  /// the bracket pair guides and the pinned declarations, has to be called
+  /// before drawing.
+  pub fn prepare(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    self.sync(screen_size);
+    self.paste_chunk(screen_size);
+    if self.gutter.fit() {
+      self.set_dimensions(screen_size, self.dimensions);
+    }
+    self.update_folds(screen_size);
+    self.gutter.changes = self.code.changes();
+    self.gutter.scroll_offset_y = self.code.scroll_y();
+    if self.code.shows_brackets() && self.brackets_version != Some(self.version)
+    {
+      self.code.find_brackets(&self.grammar);
+      self.brackets_version = Some(self.version);
+    }
+    self.decorate();
+    self.gutter.icons = self.code.decorations.gutter();
+    self.code.update_decorations(device, screen_size);
+    self.code.update_rulers(device, screen_size);
+    self.code.update_guides(device, screen_size);
+    self.code.update_cursors(device, screen_size);
+
+    let (first, _) = self.code.visible_lines();
+    self.symbols();
+    self.sticky.update(
+      screen_size,
+      &self.symbols.0,
+      first,
+      Dimensions {
+        width: self.dimensions.width - self.side_widths().1,
+        ..self.dimensions
+      },
+      self.gutter.numbers_x(),
+      self.code.dimensions.x,
+    );
+    let covered = self.sticky.height();
+    self.gutter.covered = covered;
+    self.code.covered = covered;
+  }
+
+  /// Registers the decorations of the text in view.
+  fn decorate(&mut self) {
+    self.code.decorate_diff();
+    self.code.decorate_current_line();
+    self.code.decorate_matches(self.search.as_deref());
+    self.code.decorate_selection();
+    self.code.decorate_brackets();
+    self.code.decorate_problems(&self.problems);
+    self.code.decorate_swatches();
+    let cursor = self.cursor_position();
+    let lightbulb = self
+      .lightbulb
+      .filter(|lightbulb| *lightbulb == (self.version, cursor))
+      .map(|(_, cursor)| cursor);
+    self.code.decorate_lightbulb(lightbulb);
+  }
+
+  /// The color literal whose swatch is at `position` in the gutter, and its
+  /// line.
+  pub fn swatch_at(
+    &self,
+    position: PhysicalPosition<f32>,
+  ) -> Option<(usize, ColorLiteral)> {
+    let pos = self
+      .gutter
+      .dimensions
+      .contains(position)
+      .filter(|_| !self.zen)?;
+    match self.gutter.click_at(pos.cast())? {
+      gutter::GutterClick::Diagnostics(line)
+        if self.gutter.shows_swatch(line) =>
+      {
+        self.code.swatch_on(line)
+      }
+      _ => None,
+    }
+  }
+
+  /// Replaces the graphemes `start..end` of `line` with `text`.
+  pub fn replace_range(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    line: usize,
+    start: usize,
+    end: usize,
+    text: &str,
+  ) {
+    let mut lines = self.text().clone();
+    let current = match lines.get(line) {
+      Some(current) => current,
+      None => return,
+    };
+    let offset = |index| {
+      current
+        .grapheme_indices(true)
+        .nth(index)
+        .map_or(current.len(), |(i, _)| i)
+    };
+    let (start, end) = (offset(start), offset(end));
+    lines[line].replace_range(start..end, text);
+    self.replace_text(screen_size, lines);
+  }
+
+  /// Transforms the selected text, or all of it if nothing is selected, as
+  /// one change, selecting the result.
+  pub fn transform(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    transform: Transform,
+  ) -> Result<(), anyhow::Error> {
+    self.sync(screen_size);
+    let span = self.code.selection().unwrap_or_else(|| {
+      let text = self.text();
+      let last = text.len() - 1;
+      Span::new((0, 0), (last, text[last].graphemes(true).count()))
+    });
+    let (lines, span) = transform::apply(&self.text(), span, transform)?;
+    if lines != *self.text() {
+      self.replace_text(screen_size, lines);
+    }
+    self.code.select(screen_size, span);
+    Ok(())
+  }
+
+  /// Grows the selection to the enclosing structure, from the word at the
+  /// cursor up to the whole text.
+  pub fn expand_selection(&mut self, screen_size: PhysicalSize<f32>) -> bool {
+    self.code.expand_selection(screen_size)
+  }
+
+  /// Undoes the last expansion of the selection.
+  pub fn shrink_selection(&mut self, screen_size: PhysicalSize<f32>) -> bool {
+    self.code.shrink_selection(screen_size)
+  }
+
+  /// Undoes the last edit, or redoes the last one undone. Returns whether
+  /// there was one.
+  pub fn undo(&mut self, screen_size: PhysicalSize<f32>, redo: bool) -> bool {
+    self.sync(screen_size);
+    self.finish_paste(screen_size);
+    let message = if redo { Message::Redo } else { Message::Undo };
+    if self.code.update(screen_size, message) == Change::Nothing {
+      return false;
+    }
+    self.version = self.document.changed();
+    self.preview = false;
+    self.minimap.invalidate();
+    self.clamp_bookmarks();
+    true
+  }
+
+  /// Moves the cursor or extends the selection by `message`, like by a
+  /// word.
+  pub fn move_cursor(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    message: Message,
+  ) {
+    self.code.update(screen_size, message);
+  }
+
+  /// The selected text, across lines.
+  pub fn copy(&self) -> Option<String> {
+    self.code.selected_text()
+  }
+
+  /// Removes the selected text, returning it.
+  pub fn cut(&mut self, screen_size: PhysicalSize<f32>) -> Option<String> {
+    self.sync(screen_size);
+    self.finish_paste(screen_size);
+    let text = self.code.selected_text()?;
+    self.edit(screen_size, Message::Backspace);
+    Some(text)
+  }
+
+  /// Adds a cursor on the line above or below the others.
+  pub fn add_cursor(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    direction: Direction,
+  ) {
+    self.code.update(screen_size, Message::AddCursor(direction));
+  }
+
+  /// Selects the lines from `anchor` to `line`.
+  fn select_lines(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    anchor: usize,
+    line: usize,
+  ) {
+    let (from, to) = gutter::line_selection(&self.text(), anchor, line);
+    self.code.select_from(screen_size, from, to);
+  }
+
+  /// Extends the lines selected by dragging over the gutter to the one at
+  /// `position`, returns whether a selection is being dragged.
+  pub fn drag_lines(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    position: PhysicalPosition<f32>,
+  ) -> bool {
+    let anchor = match self.line_drag {
+      Some(anchor) => anchor,
+      None => return false,
+    };
+    let y = (position.y - self.gutter.dimensions.y).max(0.0) as f64;
+    let line = self
+      .gutter
+      .line_at(y)
+      .unwrap_or_else(|| self.text().len() - 1);
+    self.select_lines(screen_size, anchor, line);
+    true
+  }
+
+  /// Stops dragging a selection of lines.
+  pub fn end_drag(&mut self) {
+    self.line_drag = None;
+  }
+
+  /// Zero based bookmarked lines, sorted.
+  pub fn bookmarks(&self) -> &[usize] {
+    &self.gutter.bookmarks
+  }
+
+  pub fn set_bookmarks(&mut self, mut bookmarks: Vec<usize>) {
+    let count = self.document.text.borrow().len();
+    bookmarks.retain(|line| *line < count);
+    bookmarks.sort_unstable();
+    bookmarks.dedup();
+    self.gutter.bookmarks = bookmarks;
+  }
+
+  /// Adds a bookmark to the line of the cursor or removes it.
+  pub fn toggle_bookmark(&mut self) {
+    let (row, _) = self.cursor_position();
+    self.gutter.toggle_bookmark(row);
+  }
+
+  /// Drops the bookmarks and breakpoints past the end of the text.
+  fn clamp_bookmarks(&mut self) {
+    let count = self.document.text.borrow().len();
+    self.gutter.bookmarks.retain(|line| *line < count);
+    self.gutter.breakpoints.retain(|line| *line < count);
+  }
+
+  /// Finds the regions of the text if it changed and hides the folded
+  /// ones. A fold which is no region anymore or which a caret moved into is
+  /// opened.
+  fn update_folds(&mut self, screen_size: PhysicalSize<f32>) {
+    if self.regions.1 != Some(self.version) {
+      self.regions = (
+        folding::regions(&self.document.text.borrow()),
+        Some(self.version),
+      );
+      self.gutter.foldable =
+        self.regions.0.iter().map(|region| region.start).collect();
+    }
+    let carets = self.code.carets();
+    let regions = &self.regions.0;
+    self.gutter.folded.retain(|start| {
+      regions.iter().any(|region| {
+        region.start == *start
+          && !carets
+            .iter()
+            .any(|(line, _)| (region.start + 1..=region.end).contains(line))
+      })
+    });
+    let hidden = folding::hidden(regions, &self.gutter.folded);
+    self.code.set_folds(screen_size, hidden);
+  }
+
+  /// Folds the region starting at `line` or unfolds it, moving the cursor
+  /// out of it first.
+  fn toggle_fold(&mut self, screen_size: PhysicalSize<f32>, line: usize) {
+    if self.gutter.folded.binary_search(&line).is_err() {
+      let (row, _) = self.cursor_position();
+      let inside = self.regions.0.iter().any(|region| {
+        region.start == line && (region.start + 1..=region.end).contains(&row)
+      });
+      if inside {
+        self
+          .code
+          .update(screen_size, Message::MoveTo((line, usize::MAX)));
+      }
+    }
+    gutter::toggle(&mut self.gutter.folded, line);
+    self.update_folds(screen_size);
+  }
+
+  /// Takes the features reduced to keep typing responsive since last called.
+  pub fn take_degradations(&mut self) -> Vec<Degradation> {
+    std::mem::take(&mut self.degradations)
+  }
+
+  /// When the minimap has to be updated, if it was deferred, or right away
+  /// while a paste or the layout of long lines goes on over the next frames.
+  pub fn deadline(&self) -> Option<Instant> {
+    if self.paste.is_some() || self.code.is_laying_out() {
+      return Some(Instant::now());
+    }
+    if self.minimap_version == self.version {
+      return None;
+    }
+    Some(self.last_edit? + MINIMAP_DELAY)
+  }
+
+  fn minimap_deferred(&self) -> bool {
+    self.watchdog.has(Reduction::DeferMinimap)
+      && matches!(self.last_edit, Some(edit) if edit.elapsed() < MINIMAP_DELAY)
+  }
+
+  /// Whether the file can't be written to.
+  pub fn is_read_only(&self) -> bool {
+    match self.path.as_deref() {
+      Some(path) => matches!(
+        std::fs::metadata(path),
+        Ok(metadata) if metadata.permissions().readonly()
+      ),
+      None => false,
+    }
+  }
+
+  /// Whether the text changed since it was last saved.
+  pub fn is_modified(&self) -> bool {
+    self.document.is_modified()
+  }
+
+  pub fn save(&mut self) -> Result<(), anyhow::Error> {
+    let path = self
+      .path
+      .as_ref()
+      .ok_or_else(|| anyhow::anyhow!("buffer isn't backed by a file"))?;
+    std::fs::write(path, self.document.text.borrow().join("\n"))?;
+    self.document.saved(path);
+    Ok(())
+  }
+
+  /// Inserts `text` at the cursors. A large one is inserted a chunk a frame
+  /// so the editor stays responsive, as a single step to undo.
+  pub fn paste(&mut self, screen_size: PhysicalSize<f32>, text: String) {
+    self.sync(screen_size);
+    self.finish_paste(screen_size);
+    self.paste = Some((text.replace("\r\n", "\n"), 0));
+    self.paste_chunk(screen_size);
+  }
+
+  /// Inserts the next chunk of the paste going on, if any.
+  fn paste_chunk(&mut self, screen_size: PhysicalSize<f32>) {
+    let (text, done) = match self.paste.take() {
+      Some(paste) => paste,
+      None => return,
+    };
+    let mut end = (done + PASTE_CHUNK).min(text.len());
+    while !text.is_char_boundary(end) {
+      end += 1;
+    }
+    let message = Message::Paste {
+      text: text[done..end].to_string(),
+      continuing: done > 0,
+    };
+    if end < text.len() {
+      self.paste = Some((text, end));
+    }
+    self.edit(screen_size, message);
+  }
+
+  /// Inserts the rest of the paste going on before the text is edited
+  /// otherwise.
+  fn finish_paste(&mut self, screen_size: PhysicalSize<f32>) {
+    while self.paste.is_some() {
+      self.paste_chunk(screen_size);
+    }
+  }
+
+  /// Applies `message` editing the text where the cursors are and catches
+  /// up with the lines it touched.
+  fn edit(&mut self, screen_size: PhysicalSize<f32>, message: Message) {
+    let start = Instant::now();
+    self.version = self.document.changed();
+    self.preview = false;
+    self.last_edit = Some(start);
+    let (row, _) = self.code.cursor_lines();
+    let count = self.document.text.borrow().len();
+    self.code.update(screen_size, message);
+    let delta = self.document.text.borrow().len() as isize - count as isize;
+    // the lines from the first one touched to the last cursor replaced the
+    // old ones, which were `delta` more or less
+    let (first, after) = self.code.cursor_lines();
+    let anchor = row.min(first);
+    let inserted = after - anchor + 1;
+    let removed = (inserted as isize - delta).max(0) as usize;
+    self.minimap.edit(anchor, removed, inserted);
+    if delta != 0 {
+      gutter::shift_bookmarks(&mut self.gutter.bookmarks, anchor, delta);
+      gutter::shift_bookmarks(&mut self.gutter.breakpoints, anchor, delta);
+      gutter::shift_bookmarks(&mut self.gutter.folded, anchor, delta);
+      for (line, _) in &mut self.problems {
+        *line = gutter::shift_line(*line, anchor, delta);
+      }
+    }
+    match self.watchdog.keystroke(start.elapsed()) {
+      Some(watchdog::Change::Reduced(degradation)) => {
+        if degradation.reduction == Reduction::ViewportHighlight {
+          self.minimap.limit_to_viewport();
+        }
+        self.degradations.push(degradation);
+      }
+      Some(watchdog::Change::Restored(Reduction::ViewportHighlight)) => {
+        self.minimap.highlight_all();
+      }
+      Some(watchdog::Change::Restored(Reduction::DeferMinimap)) | None => {}
+    }
+  }
 }
 
 impl super::input::TextInput for CodeView {
@@ -71,24 +978,126 @@ impl super::input::TextInput for CodeView {
     screen_size: PhysicalSize<f32>,
     key: VirtualKeyCode,
   ) {
+    self.sync(screen_size);
+    self.finish_paste(screen_size);
     self.code.input_special(screen_size, key);
   }
 
   fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char) {
-    self.code.input_char(screen_size, ch);
+    self.sync(screen_size);
+    self.finish_paste(screen_size);
+    self.edit(screen_size, super::input::char_message(ch));
   }
 }
 
 impl super::RenderElement for CodeView {
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let version = if self.minimap_deferred() {
+      self.minimap_version
+    } else {
+      self.version
+    };
+    let start = Instant::now();
+    self.minimap.update(version, self.code.visible_lines());
+    if version != self.minimap_version {
+      self.watchdog.highlighted(start.elapsed());
+      self.minimap_version = version;
+    }
+    for element in self.shown() {
+      element.redraw(glyph_brush, device, staging_belt, encoder, target, size);
+    }
+  }
+
   fn get_rects(&self) -> Vec<&Rectangle> {
     let mut vec = vec![];
-    vec.extend(self.gutter.get_rects());
+    if !self.zen {
+      vec.extend(self.gutter.get_rects());
+    }
     vec.extend(self.code.get_rects());
+    if !self.zen {
+      vec.extend(self.minimap.get_rects());
+    }
+    // covers the cursor and selection scrolled under it
+    vec.extend(self.sticky.get_rects());
     vec
   }
 
+  fn click(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    self.sync(screen_size);
+    self.finish_paste(screen_size);
+    // the pinned declarations go to their line
+    if let Some(pos) = self.sticky.dimensions.contains(position.cast()) {
+      if let Some(line) = self.sticky.line_at(pos) {
+        return self.goto(screen_size, line, 0);
+      }
+    }
+    // clicking a line number selects the line, dragging more of them
+    if let Some(pos) = self
+      .gutter
+      .dimensions
+      .contains(position.cast())
+      .filter(|_| !self.zen)
+    {
+      match self.gutter.click_at(pos.cast()) {
+        Some(gutter::GutterClick::Breakpoint(line)) => {
+          gutter::toggle(&mut self.gutter.breakpoints, line)
+        }
+        Some(gutter::GutterClick::Bookmark(line)) => {
+          self.gutter.toggle_bookmark(line)
+        }
+        Some(gutter::GutterClick::Fold(line)) => {
+          self.toggle_fold(screen_size, line)
+        }
+        Some(gutter::GutterClick::Line(line))
+        | Some(gutter::GutterClick::Diagnostics(line)) => {
+          self.line_drag = Some(line);
+          self.select_lines(screen_size, line, line);
+        }
+        None => {}
+      }
+      return;
+    }
+    for element in self.shown() {
+      if let Some(pos) = element.get_dimensions().contains(position.cast()) {
+        element.click(pos.cast(), screen_size);
+        break;
+      }
+    }
+  }
+
+  fn hover(
+    &mut self,
+    position: PhysicalPosition<f32>,
+  ) -> (Option<CursorIcon>, bool) {
+    let mut icon = None;
+    let mut changed = false;
+    for element in self.shown() {
+      let (wanted, redraw) = element.hover(position);
+      icon = icon.or(wanted);
+      changed |= redraw;
+    }
+    (icon, changed)
+  }
+
   fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
-    vec![&mut self.gutter, &mut self.code]
+    vec![
+      &mut self.gutter,
+      &mut self.code,
+      &mut self.minimap,
+      &mut self.sticky,
+    ]
   }
 
   fn get_dimensions(&self) -> Dimensions {
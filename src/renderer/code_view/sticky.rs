@@ -0,0 +1,212 @@
+use crate::config::Theme;
+use crate::outline::{self, Symbol};
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, HorizontalAlign, Layout, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+/// Most declarations pinned at once, the innermost are left out.
+const MAX_LINES: usize = 5;
+
+/// The lines declaring the symbols scrolled into, outermost first, when
+/// `first` is the first line in view. Each pinned line hides the line below
+/// it, so a symbol is only pinned while it contains the first line still
+/// shown.
+pub fn pinned(symbols: &[Symbol], first: usize) -> Vec<usize> {
+  let at = |count: usize| {
+    outline::path_at(symbols, first + count)
+      .into_iter()
+      .enumerate()
+      .take_while(|(i, symbol)| symbol.row < first + i)
+      .map(|(_, symbol)| symbol.row)
+      .take(MAX_LINES)
+      .collect::<Vec<_>>()
+  };
+  let mut count = 0;
+  loop {
+    let lines = at(count);
+    if lines.len() <= count {
+      return lines;
+    }
+    count += 1;
+  }
+}
+
+/// The declarations of the symbols scrolled into, pinned to the top of the
+/// view.
+pub struct StickyScroll {
+  text: Rc<RefCell<Vec<String>>>,
+  font_height: f32,
+  theme: Theme,
+  lines: Vec<usize>,
+  /// Where the line numbers end, if the gutter shows them.
+  numbers_x: Option<f32>,
+  /// Where the text starts.
+  text_x: f32,
+  rect: Rectangle,
+  pub dimensions: Dimensions,
+}
+
+impl StickyScroll {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    text: Rc<RefCell<Vec<String>>>,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      text,
+      font_height,
+      theme,
+      lines: vec![],
+      numbers_x: None,
+      text_x: 0.0,
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.overlay.rgb(),
+        None,
+      ),
+      dimensions,
+    }
+  }
+
+  /// Pins the declarations of `symbols` scrolled into over `dimensions`,
+  /// the gutter and the text of the view.
+  pub fn update(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    symbols: &[Symbol],
+    first: usize,
+    dimensions: Dimensions,
+    numbers_x: Option<f32>,
+    text_x: f32,
+  ) {
+    self.lines = pinned(symbols, first);
+    self.numbers_x = numbers_x;
+    self.text_x = text_x;
+    self.dimensions = Dimensions {
+      height: self.height(),
+      ..dimensions
+    };
+    self.rect.resize(screen_size, self.dimensions);
+  }
+
+  /// Height of the top of the view covered.
+  pub fn height(&self) -> f32 {
+    self.lines.len() as f32 * self.font_height
+  }
+
+  /// The pinned line at `position`, relative to the top.
+  pub fn line_at(&self, position: PhysicalPosition<f32>) -> Option<usize> {
+    let index = (position.y / self.font_height).floor() as usize;
+    self.lines.get(index).copied()
+  }
+}
+
+impl super::super::RenderElement for StickyScroll {
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.overlay.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    if self.lines.is_empty() {
+      return;
+    }
+    let text = self.text.borrow();
+    let mut numbers = String::new();
+    let mut declarations = String::new();
+    for line in &self.lines {
+      numbers += &format!("{}\n", line + 1);
+      declarations += text.get(*line).map_or("", String::as_str);
+      declarations.push('\n');
+    }
+    if let Some(numbers_x) = self.numbers_x {
+      glyph_brush.queue(Section {
+        screen_position: (numbers_x, self.dimensions.y),
+        text: vec![Text::new(&numbers)
+          .with_color(self.theme.gutter_text.rgba())
+          .with_scale(self.font_height)],
+        layout: Layout::default_wrap().h_align(HorizontalAlign::Right),
+        ..Section::default()
+      });
+    }
+    glyph_brush.queue(Section {
+      screen_position: (self.text_x, self.dimensions.y),
+      text: vec![Text::new(&declarations)
+        .with_color(self.theme.text.rgba())
+        .with_scale(self.font_height)],
+      ..Section::default()
+    });
+    drop(text);
+
+    super::super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    if self.lines.is_empty() {
+      vec![]
+    } else {
+      vec![&self.rect]
+    }
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pins_enclosing_declarations() {
+    let text = "impl Foo {\n  fn bar() {\n    a();\n    b();\n    c();\n  }\n}"
+      .lines()
+      .map(String::from)
+      .collect::<Vec<_>>();
+    let symbols = outline::symbols(&text);
+    // the declarations are in view
+    assert!(pinned(&symbols, 0).is_empty());
+    // fn bar is hidden by the pinned impl
+    assert_eq!(pinned(&symbols, 1), vec![0, 1]);
+    assert_eq!(pinned(&symbols, 2), vec![0, 1]);
+    // the closing bracket of fn bar would be hidden
+    assert_eq!(pinned(&symbols, 4), vec![0]);
+    assert!(pinned(&symbols, 6).is_empty());
+  }
+}
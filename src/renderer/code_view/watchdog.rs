@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Time a keystroke may take on average before features are reduced.
+const BUDGET: Duration = Duration::from_millis(12);
+/// Number of keystrokes the average is computed over.
+const HISTORY: usize = 8;
+/// Number of keystrokes in a row taking at most half the budget after which
+/// the last reduction is undone. Being this far below the budget keeps it
+/// from being made and undone over and over.
+const RECOVERY: usize = 64;
+
+/// A feature given up to keep typing responsive, in the order they are.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Reduction {
+  /// Only the lines in view are highlighted.
+  ViewportHighlight,
+  /// The minimap is only updated once typing pauses.
+  DeferMinimap,
+}
+
+impl Reduction {
+  const ALL: [Reduction; 2] =
+    [Reduction::ViewportHighlight, Reduction::DeferMinimap];
+
+  pub fn description(self) -> &'static str {
+    match self {
+      Reduction::ViewportHighlight => "only the lines in view are highlighted",
+      Reduction::DeferMinimap => {
+        "the minimap is only updated when typing pauses"
+      }
+    }
+  }
+}
+
+/// Why a reduction was made, the average times of the keystrokes before it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Degradation {
+  pub reduction: Reduction,
+  pub layout: Duration,
+  pub highlight: Duration,
+}
+
+/// A change to the features made by the watchdog.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Change {
+  Reduced(Degradation),
+  /// Typing stayed fast long enough to bring a reduced feature back.
+  Restored(Reduction),
+}
+
+#[derive(Copy, Clone, Default)]
+struct Sample {
+  layout: Duration,
+  highlight: Duration,
+}
+
+/// Measures the time spent in layout and highlighting per keystroke, and
+/// reduces features one at a time while it stays over budget.
+#[derive(Default)]
+pub struct Watchdog {
+  samples: VecDeque<Sample>,
+  /// Keystrokes in a row taking at most half the budget.
+  fast: usize,
+  pub reductions: Vec<Reduction>,
+}
+
+impl Watchdog {
+  pub fn has(&self, reduction: Reduction) -> bool {
+    self.reductions.contains(&reduction)
+  }
+
+  /// Records a keystroke which took `layout`, returns the reduction to make
+  /// if the keystrokes before it were too slow, or the one to undo if they
+  /// were fast for long enough.
+  pub fn keystroke(&mut self, layout: Duration) -> Option<Change> {
+    let change = match self.recover() {
+      Some(reduction) => Some(Change::Restored(reduction)),
+      None => self.check().map(Change::Reduced),
+    };
+    if self.samples.len() == HISTORY {
+      self.samples.pop_front();
+    }
+    self.samples.push_back(Sample {
+      layout,
+      highlight: Duration::default(),
+    });
+    change
+  }
+
+  /// Adds the time highlighting took to the last keystroke.
+  pub fn highlighted(&mut self, highlight: Duration) {
+    if let Some(sample) = self.samples.back_mut() {
+      sample.highlight += highlight;
+    }
+  }
+
+  /// Undoes the last reduction once enough keystrokes in a row were fast.
+  fn recover(&mut self) -> Option<Reduction> {
+    let last = self.samples.back()?;
+    if last.layout + last.highlight > BUDGET / 2 {
+      self.fast = 0;
+      return None;
+    }
+    self.fast += 1;
+    if self.fast < RECOVERY {
+      return None;
+    }
+    let reduction = self.reductions.pop()?;
+    self.fast = 0;
+    // a new reduction needs keystrokes made without this one
+    self.samples.clear();
+    Some(reduction)
+  }
+
+  fn check(&mut self) -> Option<Degradation> {
+    if self.samples.len() < HISTORY {
+      return None;
+    }
+    let count = self.samples.len() as u32;
+    let layout = self.samples.iter().map(|s| s.layout).sum::<Duration>();
+    let highlight = self.samples.iter().map(|s| s.highlight).sum::<Duration>();
+    if (layout + highlight) / count <= BUDGET {
+      return None;
+    }
+    let reduction = *Reduction::ALL
+      .iter()
+      .find(|reduction| !self.has(**reduction))?;
+    self.reductions.push(reduction);
+    self.fast = 0;
+    // the next reduction needs keystrokes made with this one
+    self.samples.clear();
+    Some(Degradation {
+      reduction,
+      layout: layout / count,
+      highlight: highlight / count,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn type_keys(
+    watchdog: &mut Watchdog,
+    count: usize,
+    highlight: u64,
+  ) -> Vec<Degradation> {
+    (0..count)
+      .filter_map(|_| {
+        let change = watchdog.keystroke(Duration::from_millis(1));
+        watchdog.highlighted(Duration::from_millis(highlight));
+        match change {
+          Some(Change::Reduced(degradation)) => Some(degradation),
+          _ => None,
+        }
+      })
+      .collect()
+  }
+
+  #[test]
+  fn reduces_one_at_a_time() {
+    let mut watchdog = Watchdog::default();
+    assert!(type_keys(&mut watchdog, 20, 2).is_empty());
+
+    // the fourth keystroke finds three slow ones before it
+    let degradations = type_keys(&mut watchdog, 4, 30);
+    assert_eq!(degradations.len(), 1);
+    assert_eq!(degradations[0].reduction, Reduction::ViewportHighlight);
+    assert_eq!(degradations[0].layout, Duration::from_millis(1));
+    assert_eq!(degradations[0].highlight, Duration::from_micros(12_500));
+    assert!(watchdog.has(Reduction::ViewportHighlight));
+
+    // fast enough again after the first reduction
+    assert!(type_keys(&mut watchdog, 20, 5).is_empty());
+
+    let degradations = type_keys(&mut watchdog, 30, 30);
+    assert_eq!(degradations.len(), 1);
+    assert_eq!(degradations[0].reduction, Reduction::DeferMinimap);
+    assert_eq!(watchdog.reductions.len(), 2);
+  }
+
+  #[test]
+  fn restores_once_fast_again() {
+    let mut watchdog = Watchdog::default();
+    assert_eq!(type_keys(&mut watchdog, 20, 30).len(), 2);
+
+    // just under budget isn't enough to bring anything back
+    type_keys(&mut watchdog, 200, 10);
+    assert_eq!(watchdog.reductions.len(), 2);
+
+    let restored = (0..RECOVERY + 1)
+      .filter_map(|_| {
+        let change = watchdog.keystroke(Duration::from_millis(1));
+        watchdog.highlighted(Duration::from_millis(2));
+        change
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(restored, vec![Change::Restored(Reduction::DeferMinimap)]);
+    assert_eq!(watchdog.reductions, vec![Reduction::ViewportHighlight]);
+
+    // a slow keystroke starts the count over
+    type_keys(&mut watchdog, RECOVERY / 2, 2);
+    type_keys(&mut watchdog, 1, 30);
+    type_keys(&mut watchdog, RECOVERY / 2 + 2, 2);
+    assert_eq!(watchdog.reductions, vec![Reduction::ViewportHighlight]);
+  }
+}
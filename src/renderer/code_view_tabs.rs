@@ -1,24 +1,67 @@
-use crate::renderer::code_view::CodeView;
+use crate::config::{Config, Theme};
+use crate::documents::{Document, Documents};
+use crate::grammars::Grammars;
+use crate::highlight::Grammar;
+use crate::languages::Languages;
+use crate::markers::{self, Marker, MarkerKind, Target};
+use crate::outline::{self, Symbol};
+use crate::recovery::Backup;
+use crate::renderer::buffer_switcher::OpenTab;
+use crate::renderer::code_view::{CodeView, MinimapPipeline};
 use crate::renderer::input::line_length;
 use crate::renderer::rectangle::Rectangle;
 use crate::renderer::Dimensions;
-use std::path::PathBuf;
+use crate::shaping::Ligatures;
+use crate::tasks::Location;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use wgpu::util::StagingBelt;
 use wgpu::{CommandEncoder, TextureView};
-use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::ab_glyph::{Font, FontArc, ScaleFont};
 use wgpu_glyph::{GlyphBrush, HorizontalAlign, Layout, Section, Text};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::VirtualKeyCode;
 
 const TAB_HEIGHT: f32 = 50.0;
 const TAB_PADDING: f32 = 15.0;
+const BREADCRUMBS_PADDING: f32 = 4.0;
 
 pub struct CodeViewTabs {
   font: FontArc,
   font_height: f32,
+  theme: Theme,
+  /// Used for the per-language settings of newly opened files.
+  config: Config,
+  /// Tells which language the files are in, from the config.
+  languages: Languages,
+  grammars: Grammars,
+  /// The open files, shared with the other windows.
+  documents: Documents,
+  /// Whether typing replaces the character under the cursor in every tab.
+  overwrite: bool,
+  /// Whether every tab keeps its cursor in the middle of the view.
+  typewriter: bool,
+  /// The ligatures drawn in every tab.
+  ligatures: Rc<Ligatures>,
+  /// Bookmarks of the files which aren't open, by canonical path.
+  bookmarks: BTreeMap<PathBuf, Vec<usize>>,
+  /// Where the last task reported problems, by canonical path.
+  problems: Vec<Location>,
+  /// How many new files were created, to number their tabs.
+  untitled: usize,
+  minimap_pipeline: Rc<MinimapPipeline>,
   pub code_views: Vec<(String, Rectangle, CodeView)>,
   active: Option<usize>,
+  /// When each tab was last activated, counted in activations.
+  last_used: Vec<usize>,
+  activations: usize,
   tabs_container: Rectangle,
+  /// Shows the symbols containing the cursor, below the tabs.
+  breadcrumbs: Rectangle,
+  /// Width of the code column in zen mode, which hides the tabs,
+  /// breadcrumbs, gutter and minimap.
+  zen: Option<f32>,
   dimensions: Dimensions,
 }
 
@@ -29,7 +72,10 @@ impl CodeViewTabs {
     font: FontArc,
     font_height: f32,
     dimensions: Dimensions,
+    config: &Config,
+    documents: Documents,
   ) -> Self {
+    let theme = config.theme;
     let rect = Rectangle::new(
       device,
       screen_size,
@@ -37,16 +83,43 @@ impl CodeViewTabs {
         height: TAB_HEIGHT,
         ..dimensions
       },
-      [0.12, 0.2, 0.89],
+      theme.tab_bar.rgb(),
+      None,
+    );
+    let breadcrumbs = Rectangle::new(
+      device,
+      screen_size,
+      Dimensions {
+        y: dimensions.y + TAB_HEIGHT,
+        height: font_height + (BREADCRUMBS_PADDING * 2.0),
+        ..dimensions
+      },
+      theme.tab.rgb(),
       None,
     );
 
     Self {
       font,
       font_height,
+      theme,
+      config: config.clone(),
+      languages: Languages::new(&config.languages),
+      grammars: Grammars::load(),
+      documents,
+      overwrite: false,
+      typewriter: config.typewriter,
+      ligatures: Rc::default(),
+      bookmarks: BTreeMap::new(),
+      problems: vec![],
+      untitled: 0,
+      minimap_pipeline: Rc::new(MinimapPipeline::new(device)),
       active: None,
       code_views: vec![],
+      last_used: vec![],
+      activations: 0,
       tabs_container: rect,
+      breadcrumbs,
+      zen: None,
       dimensions,
     }
   }
@@ -56,6 +129,17 @@ impl CodeViewTabs {
     device: &wgpu::Device,
     screen_size: PhysicalSize<f32>,
     filepath: PathBuf,
+  ) -> Result<(), anyhow::Error> {
+    self.open(device, screen_size, filepath)?;
+    self.close_stale(screen_size);
+    Ok(())
+  }
+
+  fn open(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    filepath: PathBuf,
   ) -> Result<(), anyhow::Error> {
     if !filepath.exists() {
       anyhow::bail!("path doesn't exist");
@@ -63,40 +147,867 @@ impl CodeViewTabs {
     if !filepath.is_file() {
       anyhow::bail!("path isn't a file");
     }
-    let text = std::fs::read_to_string(&filepath)?;
+    let document = self.documents.open(&filepath)?;
 
     let filename = filepath.file_name().unwrap().to_str().unwrap();
-    let name_width = line_length(filename, self.font.clone(), self.font_height);
+    self.push(
+      device,
+      screen_size,
+      filename.to_string(),
+      document,
+      Some(filepath),
+    );
+    Ok(())
+  }
+
+  /// Switches to the tab of `filepath`, opening it if it isn't yet.
+  pub fn show(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    filepath: PathBuf,
+  ) -> Result<(), anyhow::Error> {
+    match self.index_of(&filepath) {
+      Some(i) => {
+        self.activate(i);
+        Ok(())
+      }
+      None => self.add(device, screen_size, filepath),
+    }
+  }
+
+  /// Opens a file from the explorer. Unless it's open already, it replaces
+  /// the current preview tab and becomes the preview tab itself.
+  pub fn open_preview(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    filepath: PathBuf,
+  ) -> Result<(), anyhow::Error> {
+    if let Some(i) = self.index_of(&filepath) {
+      self.activate(i);
+      return Ok(());
+    }
+    if !self.config.tabs.preview {
+      return self.add(device, screen_size, filepath);
+    }
+
+    let previous = self
+      .code_views
+      .iter()
+      .position(|(_, _, code_view)| code_view.preview);
+    self.open(device, screen_size, filepath)?;
+    let (_, _, code_view) = self.code_views.last_mut().unwrap();
+    code_view.preview = true;
+    if let Some(previous) = previous {
+      // the new tab takes the place of the previous preview
+      let tab = self.code_views.pop().unwrap();
+      self.last_used.pop();
+      self.keep_bookmarks(previous);
+      self.code_views[previous] = tab;
+      self.activate(previous);
+      self.set_dimensions(screen_size, self.dimensions);
+    }
+    self.close_stale(screen_size);
+    Ok(())
+  }
+
+  /// Keeps the active tab open, it stops being the preview tab and is never
+  /// closed automatically.
+  pub fn pin_active(&mut self) {
+    if let Some(active) = self.get_active() {
+      active.preview = false;
+      active.pinned = true;
+    }
+  }
+
+  /// The tabs, most recently used first.
+  pub fn recent(&self) -> Vec<OpenTab> {
+    let mut tabs = self
+      .code_views
+      .iter()
+      .enumerate()
+      .map(|(index, (name, _, code_view))| OpenTab {
+        index,
+        name: name.clone(),
+        path: code_view.path().map(Path::to_path_buf),
+        modified: code_view.is_modified(),
+      })
+      .collect::<Vec<_>>();
+    tabs.sort_by_key(|tab| std::cmp::Reverse(self.last_used[tab.index]));
+    tabs
+  }
+
+  /// The outline of each tab, with its path if it's backed by a file and
+  /// whether it is the active one.
+  pub fn outlines(&mut self) -> Vec<(bool, Option<PathBuf>, Vec<Symbol>)> {
+    let active = self.active;
+    self
+      .code_views
+      .iter_mut()
+      .enumerate()
+      .map(|(i, (_, _, code_view))| {
+        (
+          active == Some(i),
+          code_view.path().map(Path::to_path_buf),
+          code_view.symbols().to_vec(),
+        )
+      })
+      .collect()
+  }
+
+  /// Switches to the tab at `i`.
+  pub fn show_tab(&mut self, i: usize) {
+    if i < self.code_views.len() {
+      self.activate(i);
+    }
+  }
+
+  fn activate(&mut self, i: usize) {
+    self.activations += 1;
+    self.last_used[i] = self.activations;
+    self.active = Some(i);
+  }
+
+  /// Closes the least recently used tabs beyond the configured limit. The
+  /// active tab, pinned tabs and tabs with unsaved changes are kept.
+  fn close_stale(&mut self, screen_size: PhysicalSize<f32>) {
+    let limit = match self.config.tabs.limit {
+      Some(limit) => limit,
+      None => return,
+    };
+    let tabs = self
+      .code_views
+      .iter()
+      .enumerate()
+      .map(|(i, (_, _, code_view))| {
+        let closable = Some(i) != self.active
+          && !code_view.pinned
+          && !code_view.is_modified()
+          && code_view.path().is_some();
+        (closable, self.last_used[i])
+      })
+      .collect::<Vec<_>>();
+    let mut stale = stale_tabs(&tabs, limit);
+    if stale.is_empty() {
+      return;
+    }
+
+    stale.sort_unstable();
+    self.active = self
+      .active
+      .map(|active| active - stale.iter().filter(|i| **i < active).count());
+    for i in stale.into_iter().rev() {
+      self.remove(i);
+    }
+    self.set_dimensions(screen_size, self.dimensions);
+  }
+
+  /// Opens a buffer that isn't backed by a file, such as piped stdin.
+  pub fn add_unsaved(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    name: String,
+    text: String,
+  ) {
+    let document = Rc::new(Document::new(&text));
+    self.push(device, screen_size, name, document, None);
+  }
+
+  /// Opens an empty buffer which asks where to save it the first time it's
+  /// saved.
+  pub fn add_untitled(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    self.untitled += 1;
+    let name = format!("Untitled-{}", self.untitled);
+    self.add_unsaved(device, screen_size, name, String::new());
+  }
+
+  /// Opens a tab moved from another window, sharing its text.
+  pub fn add_document(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    name: String,
+    document: Rc<Document>,
+    path: Option<PathBuf>,
+  ) {
+    self.push(device, screen_size, name, document, path);
+  }
 
+  fn push(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    name: String,
+    document: Rc<Document>,
+    path: Option<PathBuf>,
+  ) {
+    let tabs_end = self
+      .code_views
+      .last()
+      .map(|(_, rect, _)| rect.dimensions.x + rect.dimensions.width)
+      .unwrap_or(self.tabs_container.dimensions.x);
     let rect = Rectangle::new(
       device,
       screen_size,
       Dimensions {
-        width: TAB_PADDING + name_width + TAB_PADDING,
+        x: tabs_end,
+        width: self.tab_width(&name),
         ..self.tabs_container.dimensions
       },
-      [0.04, 0.12, 0.81],
+      self.theme.tab.rgb(),
       None,
     );
 
-    let code_view = CodeView::new(
+    let mut code_view = CodeView::new(
       &device,
+      Rc::clone(&self.minimap_pipeline),
       screen_size,
       self.font.clone(),
       self.font_height,
-      Dimensions {
-        y: self.dimensions.y + TAB_HEIGHT,
-        height: self.dimensions.height - TAB_HEIGHT,
+      self.code_view_dimensions(),
+      document,
+      self.theme,
+    );
+    code_view.set_hinting(self.config.text.hinting);
+    code_view.set_overscroll(screen_size, self.config.overscroll);
+    code_view.set_brackets(self.config.brackets);
+    code_view.set_gutter(screen_size, &self.config.gutter);
+    code_view.set_overwrite(screen_size, self.overwrite);
+    code_view.set_typewriter(screen_size, self.typewriter);
+    code_view.set_ligatures(self.ligatures.clone());
+    code_view.set_zen(screen_size, self.zen.is_some());
+    if let Some(bookmarks) = path
+      .as_deref()
+      .and_then(|path| self.bookmarks.remove(&canonical(path)))
+    {
+      code_view.set_bookmarks(bookmarks);
+    }
+    code_view.set_problems(self.problem_lines(path.as_deref()));
+    code_view.set_path(path);
+    self.set_language(screen_size, &mut code_view);
+
+    self.code_views.push((name, rect, code_view));
+    self.last_used.push(0);
+    self.activate(self.code_views.len() - 1);
+  }
+
+  /// Closes the tab at `i`, keeping its bookmarks.
+  fn remove(&mut self, i: usize) -> (String, CodeView) {
+    self.keep_bookmarks(i);
+    self.last_used.remove(i);
+    let (name, _, code_view) = self.code_views.remove(i);
+    (name, code_view)
+  }
+
+  fn keep_bookmarks(&mut self, i: usize) {
+    let code_view = &self.code_views[i].2;
+    if let Some(path) = code_view.path() {
+      if !code_view.bookmarks().is_empty() {
+        self
+          .bookmarks
+          .insert(canonical(path), code_view.bookmarks().to_vec());
+      }
+    }
+  }
+
+  /// Sets the bookmarks of files by canonical path, such as the ones of the
+  /// last session.
+  pub fn restore_bookmarks(
+    &mut self,
+    bookmarks: BTreeMap<PathBuf, Vec<usize>>,
+  ) {
+    self.bookmarks = bookmarks;
+    let saved = &mut self.bookmarks;
+    for (_, _, code_view) in &mut self.code_views {
+      let bookmarks = code_view
+        .path()
+        .and_then(|path| saved.remove(&canonical(path)));
+      if let Some(bookmarks) = bookmarks {
+        code_view.set_bookmarks(bookmarks);
+      }
+    }
+  }
+
+  /// The bookmarks of all files by canonical path, open or not.
+  pub fn bookmarks(&self) -> BTreeMap<PathBuf, Vec<usize>> {
+    let mut bookmarks = self.bookmarks.clone();
+    for (_, _, code_view) in &self.code_views {
+      if let Some(path) = code_view.path() {
+        if !code_view.bookmarks().is_empty() {
+          bookmarks.insert(canonical(path), code_view.bookmarks().to_vec());
+        }
+      }
+    }
+    bookmarks
+  }
+
+  /// The markers of `kind` in the open tabs, sorted. `find` is the text
+  /// whose occurrences are matches and `problems` the locations reported
+  /// by the last task, which can be in files that aren't open.
+  pub fn markers(
+    &self,
+    kind: MarkerKind,
+    find: &str,
+    problems: &[Location],
+  ) -> Vec<Marker> {
+    let mut found = vec![];
+    if kind == MarkerKind::Problem {
+      found.extend(problems.iter().map(|location| Marker {
+        target: match self.index_of(&location.path) {
+          Some(i) => Target::Tab(i),
+          None => Target::File(location.path.clone()),
+        },
+        line: location.line,
+        column: location.column,
+      }));
+    }
+    for (i, (_, _, code_view)) in self.code_views.iter().enumerate() {
+      let positions = match kind {
+        MarkerKind::Problem => vec![],
+        MarkerKind::Change => markers::hunks(&code_view.changed_lines())
+          .into_iter()
+          .map(|line| (line, 0))
+          .collect(),
+        MarkerKind::Match => markers::matches(&code_view.text(), find),
+        MarkerKind::Bookmark => code_view
+          .bookmarks()
+          .iter()
+          .map(|line| (*line, 0))
+          .collect(),
+      };
+      found.extend(positions.into_iter().map(|(line, column)| Marker {
+        target: Target::Tab(i),
+        line,
+        column,
+      }));
+    }
+    found.sort();
+    found.dedup();
+    found
+  }
+
+  /// Where the selection starts in the active tab, or the cursor is if
+  /// nothing is selected, so a selected marker is stepped over both ways.
+  pub fn current_marker(&self) -> Marker {
+    let (i, (line, column)) = match self.active {
+      Some(i) => {
+        let code_view = &self.code_views[i].2;
+        let position = match code_view.selection() {
+          Some(span) => span.start,
+          None => code_view.cursor_position(),
+        };
+        (i, position)
+      }
+      None => (0, (0, 0)),
+    };
+    Marker {
+      target: Target::Tab(i),
+      line,
+      column,
+    }
+  }
+
+  /// Activates the tab at `i` and moves its cursor, see [`CodeView::goto`].
+  pub fn goto_tab(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    i: usize,
+    row: usize,
+    column: usize,
+  ) {
+    self.activate(i);
+    self.code_views[i].2.goto(screen_size, row, column);
+  }
+
+  /// Detects the language of `code_view`, unless it was picked by hand,
+  /// and applies its grammar and settings.
+  fn set_language(
+    &self,
+    screen_size: PhysicalSize<f32>,
+    code_view: &mut CodeView,
+  ) {
+    let language = if code_view.language_chosen() {
+      code_view.language().map(String::from)
+    } else {
+      self
+        .languages
+        .detect(code_view.path(), &code_view.document().text.borrow())
+    };
+    let language = language.as_deref();
+    code_view.set_wrap(screen_size, self.config.wrap_for(language).clone());
+    code_view.set_rulers(self.config.rulers_for(language).to_vec());
+    code_view.set_grammar(self.grammars.get(language, code_view.path()));
+    code_view.set_language(language.map(String::from));
+  }
+
+  /// The ids of the languages a tab can be set to.
+  pub fn language_ids(&self) -> Vec<String> {
+    self.languages.ids().into_iter().map(String::from).collect()
+  }
+
+  /// Sets the language of the active tab by hand, `None` for plain text.
+  pub fn choose_language(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    language: Option<String>,
+  ) {
+    let i = match self.active {
+      Some(i) => i,
+      None => return,
+    };
+    let mut code_view = self.code_views.remove(i);
+    code_view.2.choose_language(language);
+    self.set_language(screen_size, &mut code_view.2);
+    self.code_views.insert(i, code_view);
+  }
+
+  /// Whether there is a grammar for the file at `path`.
+  pub fn has_grammar(&self, path: &Path) -> bool {
+    let language = match self.index_of(path) {
+      Some(i) => self.code_views[i].2.language().map(String::from),
+      None => self.languages.detect_file(path),
+    };
+    self.grammars.has(language.as_deref(), Some(path))
+  }
+
+  /// Adds `grammar`, applying it to the open files it is for.
+  pub fn add_grammar(&mut self, grammar: Grammar) {
+    self.grammars.add(grammar);
+    for (_, _, code_view) in &mut self.code_views {
+      let grammar = self.grammars.get(code_view.language(), code_view.path());
+      code_view.set_grammar(grammar);
+    }
+  }
+
+  /// Re-applies the per-language settings of `config` to all open files.
+  pub fn set_config(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    config: &Config,
+  ) {
+    self.config = config.clone();
+    self.languages = Languages::new(&config.languages);
+    let mut code_views = std::mem::take(&mut self.code_views);
+    for (_, _, code_view) in &mut code_views {
+      self.set_language(screen_size, code_view);
+      code_view.set_hinting(config.text.hinting);
+      code_view.set_overscroll(screen_size, config.overscroll);
+      code_view.set_typewriter(screen_size, config.typewriter);
+      code_view.set_brackets(config.brackets);
+      code_view.set_gutter(screen_size, &config.gutter);
+    }
+    self.code_views = code_views;
+    self.close_stale(screen_size);
+  }
+
+  /// Switches all tabs between replacing and inserting what is typed.
+  pub fn set_overwrite(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    overwrite: bool,
+  ) {
+    self.overwrite = overwrite;
+    for (_, _, code_view) in &mut self.code_views {
+      code_view.set_overwrite(screen_size, overwrite);
+    }
+  }
+
+  pub fn typewriter(&self) -> bool {
+    self.typewriter
+  }
+
+  /// Switches all tabs between keeping the cursor in the middle of the view
+  /// and scrolling freely.
+  pub fn set_typewriter(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    typewriter: bool,
+  ) {
+    self.typewriter = typewriter;
+    for (_, _, code_view) in &mut self.code_views {
+      code_view.set_typewriter(screen_size, typewriter);
+    }
+  }
+
+  /// Draws `ligatures` in place of the glyphs they join in all tabs.
+  pub fn set_ligatures(&mut self, ligatures: Rc<Ligatures>) {
+    self.ligatures = ligatures;
+    for (_, _, code_view) in &mut self.code_views {
+      code_view.set_ligatures(self.ligatures.clone());
+    }
+  }
+
+  /// Shows only the code in a centered column up to `width` wide, or
+  /// everything again if `None`.
+  pub fn set_zen(&mut self, screen_size: PhysicalSize<f32>, zen: Option<f32>) {
+    if zen == self.zen {
+      return;
+    }
+    self.zen = zen;
+    for (_, _, code_view) in &mut self.code_views {
+      code_view.set_zen(screen_size, zen.is_some());
+    }
+    self.set_dimensions(screen_size, self.dimensions);
+  }
+
+  /// The area below the tabs and breadcrumbs.
+  fn code_view_dimensions(&self) -> Dimensions {
+    if let Some(width) = self.zen {
+      let width = width.min(self.dimensions.width);
+      return Dimensions {
+        x: self.dimensions.x + (self.dimensions.width - width) / 2.0,
+        width,
         ..self.dimensions
+      };
+    }
+    let top = TAB_HEIGHT + self.breadcrumbs.dimensions.height;
+    Dimensions {
+      y: self.dimensions.y + top,
+      height: self.dimensions.height - top,
+      ..self.dimensions
+    }
+  }
+
+  fn tab_label(&self, i: usize, y: f32) -> Section<'_> {
+    let (name, rect, _) = &self.code_views[i];
+    Section {
+      screen_position: (rect.dimensions.x + TAB_PADDING, y),
+      text: vec![Text::new(name)
+        .with_color(self.theme.tab_text.rgba())
+        .with_scale(self.font_height)],
+      layout: Layout::default_wrap().h_align(HorizontalAlign::Left),
+      ..Section::default()
+    }
+  }
+
+  fn tab_width(&self, name: &str) -> f32 {
+    TAB_PADDING
+      + line_length(name, self.font.clone(), self.font_height)
+      + TAB_PADDING
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.tabs_container.resize(
+      screen_size,
+      Dimensions {
+        height: TAB_HEIGHT,
+        ..dimensions
+      },
+    );
+    self.breadcrumbs.resize(
+      screen_size,
+      Dimensions {
+        y: dimensions.y + TAB_HEIGHT,
+        height: self.font_height + (BREADCRUMBS_PADDING * 2.0),
+        ..dimensions
       },
-      text,
     );
 
+    let code_view_dimensions = self.code_view_dimensions();
+    let mut x = dimensions.x;
+    for (_, rect, code_view) in &mut self.code_views {
+      rect.resize(
+        screen_size,
+        Dimensions {
+          x,
+          ..rect.dimensions
+        },
+      );
+      x += rect.dimensions.width;
+
+      code_view.set_dimensions(screen_size, code_view_dimensions);
+    }
+  }
+
+  /// Paths of all tabs backed by a file.
+  pub fn paths(&self) -> Vec<PathBuf> {
     self
       .code_views
-      .push((filename.to_string(), rect, code_view));
-    self.active = Some(self.code_views.len() - 1);
-    Ok(())
+      .iter()
+      .filter_map(|(_, _, code_view)| code_view.path().map(Path::to_path_buf))
+      .collect()
+  }
+
+  /// Paths of all tabs backed by a file, with the language of each.
+  pub fn languages(&self) -> Vec<(PathBuf, Option<String>)> {
+    self
+      .code_views
+      .iter()
+      .filter_map(|(_, _, code_view)| {
+        let path = code_view.path()?.to_path_buf();
+        Some((path, code_view.language().map(String::from)))
+      })
+      .collect()
+  }
+
+  /// Index of the tab showing the file at `path`.
+  /// Marks the lines of the open files the last task reported a problem
+  /// on, and those of the files opened later.
+  pub fn set_problems(&mut self, problems: &[Location]) {
+    self.problems = problems
+      .iter()
+      .map(|location| Location {
+        path: canonical(&location.path),
+        ..location.clone()
+      })
+      .collect();
+    let mut code_views = std::mem::take(&mut self.code_views);
+    for (_, _, code_view) in &mut code_views {
+      code_view.set_problems(self.problem_lines(code_view.path()));
+    }
+    self.code_views = code_views;
+  }
+
+  /// Where the problems of the last task are in the file at `path`.
+  fn problem_lines(&self, path: Option<&Path>) -> Vec<(usize, usize)> {
+    let path = match path {
+      Some(path) if !self.problems.is_empty() => canonical(path),
+      _ => return vec![],
+    };
+    self
+      .problems
+      .iter()
+      .filter(|location| location.path == path)
+      .map(|location| (location.line, location.column))
+      .collect()
+  }
+
+  fn index_of(&self, path: &Path) -> Option<usize> {
+    let path = canonical(path);
+    self.code_views.iter().position(|(_, _, code_view)| {
+      code_view.path().map(canonical) == Some(path.clone())
+    })
+  }
+
+  /// The tab showing the file at `path`.
+  pub fn view_for_path_mut(&mut self, path: &Path) -> Option<&mut CodeView> {
+    let i = self.index_of(path)?;
+    Some(&mut self.code_views[i].2)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.code_views.is_empty()
+  }
+
+  /// The name of the active tab followed by the symbols containing the
+  /// cursor.
+  fn breadcrumbs(&mut self) -> Option<String> {
+    let (name, _, code_view) = &mut self.code_views[self.active?];
+    let (row, _) = code_view.cursor_position();
+    let mut crumbs = vec![name.as_str()];
+    crumbs.extend(
+      outline::path_at(code_view.symbols(), row)
+        .into_iter()
+        .map(|symbol| symbol.name.as_str()),
+    );
+    Some(crumbs.join(" > "))
+  }
+
+  pub fn active_view_mut(&mut self) -> Option<&mut CodeView> {
+    self.get_active()
+  }
+
+  /// The tab whose document has the id `id`, see [`CodeView::revision`].
+  pub fn view_for_id_mut(&mut self, id: usize) -> Option<&mut CodeView> {
+    self
+      .code_views
+      .iter_mut()
+      .map(|(_, _, code_view)| code_view)
+      .find(|code_view| code_view.revision().0 == id)
+  }
+
+  pub fn active_view(&self) -> Option<&CodeView> {
+    self.active.map(|i| &self.code_views[i].2)
+  }
+
+  /// Zero based row and column of the cursor in the active tab.
+  pub fn cursor_position(&self) -> Option<(usize, usize)> {
+    self.active_view().map(CodeView::cursor_position)
+  }
+
+  /// Moves the cursor of the active tab, see [`CodeView::goto`].
+  pub fn goto(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    row: usize,
+    column: usize,
+  ) {
+    if let Some(active) = self.get_active() {
+      active.goto(screen_size, row, column);
+    }
+  }
+
+  pub fn save_active(&mut self) -> Result<(), anyhow::Error> {
+    match self.get_active() {
+      Some(active) => active.save(),
+      None => Ok(()),
+    }
+  }
+
+  /// Saves the active tab to `path`, which backs it from then on. The tab
+  /// is named after the file and its language detected again.
+  pub fn save_active_as(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    path: PathBuf,
+  ) -> Result<(), anyhow::Error> {
+    let i = match self.active {
+      Some(i) => i,
+      None => return Ok(()),
+    };
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+      Some(name) => name.to_string(),
+      None => anyhow::bail!("path isn't a file"),
+    };
+    let (mut tab_name, mut rect, mut code_view) = self.code_views.remove(i);
+    let previous = code_view.path().map(Path::to_path_buf);
+    code_view.set_path(Some(path.clone()));
+    let saved = code_view
+      .save()
+      .and_then(|()| self.documents.add(&path, code_view.document()));
+    if saved.is_err() {
+      code_view.set_path(previous);
+    } else {
+      rect.resize(
+        screen_size,
+        Dimensions {
+          width: self.tab_width(&name),
+          ..rect.dimensions
+        },
+      );
+      code_view.set_problems(self.problem_lines(Some(&path)));
+      self.set_language(screen_size, &mut code_view);
+      tab_name = name;
+    }
+    self.code_views.insert(i, (tab_name, rect, code_view));
+    self.set_dimensions(screen_size, self.dimensions);
+    saved
+  }
+
+  pub fn active_name(&self) -> Option<String> {
+    self.active.map(|i| self.code_views[i].0.clone())
+  }
+
+  /// Names of the tabs with unsaved changes, leaving out the ones which are
+  /// open in another window too.
+  pub fn modified(&self) -> Vec<String> {
+    self
+      .code_views
+      .iter()
+      .filter(|(_, _, code_view)| {
+        code_view.is_modified() && !code_view.is_shared()
+      })
+      .map(|(name, _, _)| name.clone())
+      .collect()
+  }
+
+  /// The ids and versions of the tabs with unsaved changes.
+  pub fn modified_revisions(&self) -> Vec<(usize, usize)> {
+    self
+      .code_views
+      .iter()
+      .filter(|(_, _, code_view)| code_view.is_modified())
+      .map(|(_, _, code_view)| code_view.revision())
+      .collect()
+  }
+
+  /// A copy of the tab with the id `id` to restore after a crash.
+  pub fn backup(&self, id: usize) -> Option<Backup> {
+    let (name, _, code_view) = self
+      .code_views
+      .iter()
+      .find(|(_, _, code_view)| code_view.revision().0 == id)?;
+    Some(Backup {
+      path: code_view.path().map(Path::to_path_buf),
+      name: name.clone(),
+      text: code_view.text().join("\n"),
+    })
+  }
+
+  /// The ids and versions of the tabs with unsaved changes which can be
+  /// saved without asking, skipping unsaved buffers and read-only files.
+  pub fn auto_saveable(&self) -> Vec<(usize, usize)> {
+    self
+      .code_views
+      .iter()
+      .map(|(_, _, code_view)| code_view)
+      .filter(|code_view| {
+        code_view.is_modified()
+          && code_view.path().is_some()
+          && !code_view.is_read_only()
+      })
+      .map(CodeView::revision)
+      .collect()
+  }
+
+  /// Saves the tabs with the ids `ids`. Returns the errors with the names
+  /// of the tabs.
+  pub fn save_ids(&mut self, ids: &[usize]) -> Vec<String> {
+    let mut errors = vec![];
+    for (name, _, code_view) in &mut self.code_views {
+      if ids.contains(&code_view.revision().0) {
+        if let Err(err) = code_view.save() {
+          errors.push(format!("{}: {}", name, err));
+        }
+      }
+    }
+    errors
+  }
+
+  /// Saves all tabs with unsaved changes, failing with the first error once
+  /// all were tried.
+  pub fn save_modified(&mut self) -> Result<(), anyhow::Error> {
+    let mut result = Ok(());
+    for (name, _, code_view) in &mut self.code_views {
+      if code_view.is_modified() {
+        if let Err(err) = code_view.save() {
+          if result.is_ok() {
+            result = Err(anyhow::anyhow!("{}: {}", name, err));
+          }
+        }
+      }
+    }
+    result
+  }
+
+  pub fn close_active(&mut self, screen_size: PhysicalSize<f32>) {
+    self.take_active(screen_size);
+  }
+
+  /// Closes the active tab, returning its name and view to open it somewhere
+  /// else.
+  pub fn take_active(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+  ) -> Option<(String, CodeView)> {
+    let i = self.active?;
+    let tab = self.remove(i);
+    self.active = None;
+    if !self.code_views.is_empty() {
+      self.activate(i.min(self.code_views.len() - 1));
+    }
+    self.set_dimensions(screen_size, self.dimensions);
+    Some(tab)
+  }
+
+  /// Whether there is a tab at `position`.
+  pub fn tab_at(&self, position: PhysicalPosition<f32>) -> bool {
+    self.zen.is_none()
+      && self
+        .code_views
+        .iter()
+        .any(|(_, rect, _)| rect.dimensions.contains(position).is_some())
   }
 
   fn get_active(&mut self) -> Option<&mut CodeView> {
@@ -109,14 +1020,25 @@ impl CodeViewTabs {
 }
 
 impl super::RenderElement for CodeViewTabs {
-  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
-    self.tabs_container.resize(
-      screen_size.cast(),
-      Dimensions {
-        width: screen_size.width,
-        ..self.tabs_container.dimensions
-      },
-    );
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.tabs_container.set_color(theme.tab_bar.rgb());
+    self.breadcrumbs.set_color(theme.tab.rgb());
+    for (_, rect, code_view) in &mut self.code_views {
+      rect.set_color(theme.tab.rgb());
+      code_view.set_theme(theme);
+    }
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+    for i in 0..self.code_views.len() {
+      let width = self.tab_width(&self.code_views[i].0);
+      let (_, rect, code_view) = &mut self.code_views[i];
+      rect.dimensions.width = width;
+      code_view.set_font(font, font_height);
+    }
   }
 
   fn scroll(
@@ -134,13 +1056,18 @@ impl super::RenderElement for CodeViewTabs {
     position: PhysicalPosition<f64>,
     screen_size: PhysicalSize<f32>,
   ) {
-    if let Some(pos) = self.tabs_container.dimensions.contains(position.cast())
+    if let Some(pos) = self
+      .tabs_container
+      .dimensions
+      .contains(position.cast())
+      .filter(|_| self.zen.is_none())
     {
-      for (i, (_, rect, _)) in self.code_views.iter().enumerate() {
-        if rect.dimensions.contains(pos).is_some() {
-          self.active = Some(i);
-          break;
-        }
+      let clicked = self
+        .code_views
+        .iter()
+        .position(|(_, rect, _)| rect.dimensions.contains(pos).is_some());
+      if let Some(i) = clicked {
+        self.activate(i);
       }
     } else if let Some(active) = self.get_active() {
       active.click(position, screen_size);
@@ -156,30 +1083,57 @@ impl super::RenderElement for CodeViewTabs {
     target: &TextureView,
     size: PhysicalSize<u32>,
   ) {
-    for (name, rect, _) in &self.code_views {
+    if self.zen.is_some() {
+      if let Some(active) = self.get_active() {
+        active.redraw(glyph_brush, device, staging_belt, encoder, target, size);
+      }
+      return;
+    }
+    let label_y = self.tabs_container.dimensions.y
+      + ((TAB_HEIGHT - self.font_height) / 2.0);
+    let mut preview = None;
+    for (i, (_, _, code_view)) in self.code_views.iter().enumerate() {
+      if code_view.preview {
+        preview = Some(i);
+      } else {
+        glyph_brush.queue(self.tab_label(i, label_y));
+      }
+    }
+
+    if let Some(breadcrumbs) = self.breadcrumbs() {
+      let rect = &self.breadcrumbs.dimensions;
       glyph_brush.queue(Section {
-        screen_position: (
-          (rect.dimensions.x + TAB_PADDING),
-          (TAB_HEIGHT - self.font_height) / 2.0,
-        ),
-        text: vec![Text::new(&name)
-          .with_color([0.9, 0.9, 0.9, 1.0])
+        screen_position: (rect.x + TAB_PADDING, rect.y + BREADCRUMBS_PADDING),
+        text: vec![Text::new(&breadcrumbs)
+          .with_color(self.theme.tab_text.rgba())
           .with_scale(self.font_height)],
-        layout: Layout::default_wrap().h_align(HorizontalAlign::Left),
         ..Section::default()
       });
     }
 
-    glyph_brush
-      .draw_queued(
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      None,
+    );
+
+    if let Some(preview) = preview {
+      glyph_brush.queue(self.tab_label(preview, label_y));
+      let ascent = self.font.as_scaled(self.font_height).ascent();
+      super::draw_glyphs_slanted(
+        glyph_brush,
         device,
         staging_belt,
         encoder,
         target,
-        size.width,
-        size.height,
-      )
-      .unwrap();
+        size,
+        label_y + ascent,
+      );
+    }
 
     if let Some(active) = self.get_active() {
       active.redraw(glyph_brush, device, staging_belt, encoder, target, size);
@@ -187,8 +1141,12 @@ impl super::RenderElement for CodeViewTabs {
   }
 
   fn get_rects(&self) -> Vec<&Rectangle> {
-    let mut vec = vec![&self.tabs_container];
-    vec.extend(self.code_views.iter().map(|(_, rect, _)| rect));
+    let mut vec = vec![];
+    if self.zen.is_none() {
+      vec.push(&self.tabs_container);
+      vec.push(&self.breadcrumbs);
+      vec.extend(self.code_views.iter().map(|(_, rect, _)| rect));
+    }
     if let Some(i) = self.active {
       vec.extend(self.code_views[i].2.get_rects());
     }
@@ -225,3 +1183,38 @@ impl super::input::TextInput for CodeViewTabs {
     }
   }
 }
+
+/// `path` with symlinks and relative parts resolved, as it is if it doesn't
+/// exist.
+pub(crate) fn canonical(path: &Path) -> PathBuf {
+  path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Indices of the tabs to close so that at most `limit` remain, least
+/// recently used first. Each tab is given as whether it may be closed and
+/// when it was last used.
+fn stale_tabs(tabs: &[(bool, usize)], limit: usize) -> Vec<usize> {
+  let excess = tabs.len().saturating_sub(limit);
+  let mut closable = tabs
+    .iter()
+    .enumerate()
+    .filter(|(_, (closable, _))| *closable)
+    .map(|(i, (_, last_used))| (*last_used, i))
+    .collect::<Vec<_>>();
+  closable.sort_unstable();
+  closable.into_iter().take(excess).map(|(_, i)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn least_recently_used() {
+    let tabs = [(true, 4), (true, 1), (false, 0), (true, 3), (true, 5)];
+    assert_eq!(stale_tabs(&tabs, 5), Vec::<usize>::new());
+    assert_eq!(stale_tabs(&tabs, 3), vec![1, 3]);
+    // tabs which have to stay open can leave more than `limit`
+    assert_eq!(stale_tabs(&tabs, 0), vec![1, 3, 0, 4]);
+  }
+}
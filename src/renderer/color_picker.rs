@@ -0,0 +1,319 @@
+use crate::colors::{self, Format};
+use crate::config::{Color, Theme};
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::VirtualKeyCode;
+
+const PICKER_TOP: f32 = 120.0;
+const PICKER_WIDTH: f32 = 320.0;
+const PADDING: f32 = 12.0;
+const PREVIEW_HEIGHT: f32 = 40.0;
+const ROW_SPACING: f32 = 6.0;
+/// Room for the channel names and values left of the tracks.
+const LABEL_WIDTH: f32 = 60.0;
+/// How much the arrow keys change a channel.
+const STEP: u8 = 5;
+const CHANNELS: [&str; 3] = ["R", "G", "B"];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PickerAction {
+  /// Rewrite the literal with the sRGB channels.
+  Apply([u8; 3]),
+  Cancel,
+}
+
+/// Edits the color of a literal with a slider per channel.
+pub struct ColorPicker {
+  pub visible: bool,
+  font_height: f32,
+  theme: Theme,
+  rgb: [u8; 3],
+  format: Format,
+  selected: usize,
+  rect: Rectangle,
+  preview: Rectangle,
+  tracks: Vec<Rectangle>,
+  fills: Vec<Rectangle>,
+  dimensions: Dimensions,
+}
+
+impl ColorPicker {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    let rect = |color: Color| {
+      Rectangle::new(device, screen_size, dimensions, color.rgb(), None)
+    };
+    Self {
+      visible: false,
+      font_height,
+      theme,
+      rgb: [0; 3],
+      format: Format::Hex,
+      selected: 0,
+      rect: rect(theme.overlay),
+      preview: rect(theme.overlay),
+      tracks: (0..3).map(|_| rect(theme.tab)).collect(),
+      fills: (0..3)
+        .map(|i| {
+          let mut rgb = [0; 3];
+          rgb[i] = 255;
+          rect(Color::from_srgb(rgb))
+        })
+        .collect(),
+      dimensions,
+    }
+  }
+
+  /// Shows `rgb`, to be written in `format` once applied.
+  pub fn open(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    rgb: [u8; 3],
+    format: Format,
+  ) {
+    self.visible = true;
+    self.rgb = rgb;
+    self.format = format;
+    self.selected = 0;
+    self.layout(screen_size);
+  }
+
+  pub fn close(&mut self) {
+    self.visible = false;
+  }
+
+  fn inner_width(&self) -> f32 {
+    self.dimensions.width - (PADDING * 2.0)
+  }
+
+  /// Top of the row of channel `i`.
+  fn row_y(&self, i: usize) -> f32 {
+    self.dimensions.y
+      + PADDING
+      + PREVIEW_HEIGHT
+      + PADDING
+      + (i as f32 * (self.font_height + ROW_SPACING))
+  }
+
+  fn track_bounds(&self, i: usize) -> Dimensions {
+    let height = (self.font_height * 0.5).round();
+    Dimensions {
+      x: self.dimensions.x + PADDING + LABEL_WIDTH,
+      y: self.row_y(i) + ((self.font_height - height) / 2.0),
+      width: self.inner_width() - LABEL_WIDTH,
+      height,
+    }
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let width = PICKER_WIDTH.min(screen_size.width);
+    self.dimensions = Dimensions {
+      x: (screen_size.width - width) / 2.0,
+      y: PICKER_TOP,
+      width,
+      height: 0.0,
+    };
+    self.dimensions.height = self.row_y(CHANNELS.len()) + self.font_height
+      - self.dimensions.y
+      + PADDING;
+    self.rect.resize(screen_size, self.dimensions);
+
+    self.preview.resize(
+      screen_size,
+      Dimensions {
+        x: self.dimensions.x + PADDING,
+        y: self.dimensions.y + PADDING,
+        width: self.inner_width(),
+        height: PREVIEW_HEIGHT,
+      },
+    );
+    self.preview.set_color(Color::from_srgb(self.rgb).rgb());
+
+    for i in 0..CHANNELS.len() {
+      let track = self.track_bounds(i);
+      let color = if i == self.selected {
+        self.theme.selection
+      } else {
+        self.theme.tab
+      };
+      self.tracks[i].resize(screen_size, track);
+      self.tracks[i].set_color(color.rgb());
+      self.fills[i].resize(
+        screen_size,
+        Dimensions {
+          width: track.width * (self.rgb[i] as f32 / 255.0),
+          ..track
+        },
+      );
+    }
+  }
+
+  /// Returns what to do with the literal, if anything. The arrow keys
+  /// select a channel and change it.
+  pub fn input_special(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) -> Option<PickerAction> {
+    let channel = &mut self.rgb[self.selected];
+    match key {
+      VirtualKeyCode::Escape => {
+        self.close();
+        return Some(PickerAction::Cancel);
+      }
+      VirtualKeyCode::Up => self.selected = (self.selected + 2) % 3,
+      VirtualKeyCode::Down => self.selected = (self.selected + 1) % 3,
+      VirtualKeyCode::Left => *channel = channel.saturating_sub(STEP),
+      VirtualKeyCode::Right => *channel = channel.saturating_add(STEP),
+      _ => return None,
+    }
+    self.layout(screen_size);
+    None
+  }
+
+  /// Returns what to do with the literal, if anything. Enter applies the
+  /// color.
+  pub fn input_char(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    ch: char,
+  ) -> Option<PickerAction> {
+    match ch {
+      '\r' => {
+        self.close();
+        Some(PickerAction::Apply(self.rgb))
+      }
+      '\t' => {
+        self.selected = (self.selected + 1) % 3;
+        self.layout(screen_size);
+        None
+      }
+      _ => None,
+    }
+  }
+
+  /// Sets the channel whose track is at `position`, relative to the picker.
+  /// Clicking the preview applies the color.
+  pub fn click_at(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    position: PhysicalPosition<f32>,
+  ) -> Option<PickerAction> {
+    let position = PhysicalPosition {
+      x: position.x + self.dimensions.x,
+      y: position.y + self.dimensions.y,
+    };
+    if self.preview.dimensions.contains(position).is_some() {
+      self.close();
+      return Some(PickerAction::Apply(self.rgb));
+    }
+    for i in 0..CHANNELS.len() {
+      let track = self.track_bounds(i);
+      let row = Dimensions {
+        y: self.row_y(i),
+        height: self.font_height,
+        ..track
+      };
+      if let Some(offset) = row.contains(position) {
+        let value = (offset.x / track.width).clamp(0.0, 1.0) * 255.0;
+        self.rgb[i] = value.round() as u8;
+        self.selected = i;
+        self.layout(screen_size);
+        break;
+      }
+    }
+    None
+  }
+}
+
+impl super::RenderElement for ColorPicker {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    if self.visible {
+      self.layout(screen_size);
+    }
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.overlay.rgb());
+    for (i, track) in self.tracks.iter_mut().enumerate() {
+      let color = if i == self.selected {
+        theme.selection
+      } else {
+        theme.tab
+      };
+      track.set_color(color.rgb());
+    }
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let color = self.theme.overlay_text.rgba();
+    let x = self.dimensions.x + PADDING;
+    for (i, name) in CHANNELS.iter().enumerate() {
+      glyph_brush.queue(Section {
+        screen_position: (x, self.row_y(i)),
+        text: vec![Text::new(&format!("{} {}", name, self.rgb[i]))
+          .with_color(color)
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+    glyph_brush.queue(Section {
+      screen_position: (x, self.row_y(CHANNELS.len())),
+      text: vec![Text::new(&format!(
+        "{}  Enter to apply",
+        colors::format(self.rgb, self.format)
+      ))
+      .with_color(color)
+      .with_scale(self.font_height)],
+      ..Section::default()
+    });
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    let mut rects = vec![&self.rect, &self.preview];
+    rects.extend(self.tracks.iter());
+    rects.extend(self.fills.iter());
+    rects
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
@@ -0,0 +1,633 @@
+use crate::calc;
+use crate::commands::Command;
+use crate::config::Theme;
+use crate::editor::Message;
+use crate::fuzzy;
+use crate::renderer::input::{TextArea, TextInput};
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::PhysicalSize;
+use winit::event::VirtualKeyCode;
+
+const PALETTE_TOP: f32 = 60.0;
+const PALETTE_MAX_WIDTH: f32 = 600.0;
+const PALETTE_PADDING: f32 = 8.0;
+const ROW_PADDING: f32 = 6.0;
+const MAX_VISIBLE_ENTRIES: usize = 10;
+
+/// What accepting an entry of the palette should do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaletteAction {
+  Run(Command),
+  Copy(String),
+  Insert(String),
+  ApplyLayout(String),
+  SaveLayout(String),
+  Rename(String),
+  RunTask(String),
+  InstallPlugin(String),
+  OpenFolder(String),
+  /// Asking what to replace the text with in all files.
+  FindInFiles(String),
+  /// Replacing the first text with the second in all files.
+  ReplaceInFiles(String, String),
+  SaveAs(String),
+  /// Setting the language of the active tab, `None` for plain text.
+  SetLanguage(Option<String>),
+}
+
+/// What the query is used for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Mode {
+  /// Searching commands, tasks and layouts, or calculating with a leading `=`.
+  Commands,
+  /// Entering the new name of the symbol at the cursor.
+  Rename,
+  /// Entering the directory or git URL of a plugin to install.
+  InstallPlugin,
+  /// Entering the directory to open in a new window.
+  OpenFolder,
+  /// Entering the text to replace in all files.
+  FindInFiles,
+  /// Entering what to replace the text found with.
+  ReplaceInFiles,
+  /// Entering the path to save the active tab to.
+  SaveAs,
+  /// Searching the languages to set the active tab to.
+  Language,
+}
+
+impl Mode {
+  /// Shown while nothing is entered.
+  fn placeholder(self) -> &'static str {
+    match self {
+      Mode::Commands => "Search commands, or calculate after =",
+      Mode::Rename => "New name",
+      Mode::InstallPlugin => "Plugin directory or git URL",
+      Mode::OpenFolder => "Directory",
+      Mode::FindInFiles => "Text to find",
+      Mode::ReplaceInFiles => "Replacement",
+      Mode::SaveAs => "Path",
+      Mode::Language => "Search languages",
+    }
+  }
+}
+
+struct Entry {
+  label: String,
+  action: Option<PaletteAction>,
+}
+
+pub struct CommandPalette {
+  pub visible: bool,
+  font: FontArc,
+  font_height: f32,
+  theme: Theme,
+  mode: Mode,
+  input: TextArea,
+  /// Names of the saved layout presets.
+  layouts: Vec<String>,
+  /// Names of the tasks of the workspace.
+  tasks: Vec<String>,
+  /// The key combinations of the commands, shown after their names.
+  shortcuts: Vec<(Command, String)>,
+  /// The text to replace in all files, entered before its replacement.
+  find: String,
+  /// Ids of the languages a tab can be set to.
+  languages: Vec<String>,
+  entries: Vec<Entry>,
+  selected: usize,
+  first_visible: usize,
+  rect: Rectangle,
+  selection_rect: Rectangle,
+  dimensions: Dimensions,
+}
+
+impl CommandPalette {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    let mut palette = Self {
+      visible: false,
+      font: font.clone(),
+      font_height,
+      theme,
+      mode: Mode::Commands,
+      input: TextArea::new(
+        device,
+        screen_size,
+        font,
+        font_height,
+        theme,
+        false,
+      ),
+      layouts: vec![],
+      tasks: vec![],
+      shortcuts: vec![],
+      find: String::new(),
+      languages: vec![],
+      entries: vec![],
+      selected: 0,
+      first_visible: 0,
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.overlay.rgb(),
+        None,
+      ),
+      selection_rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.selection.rgb(),
+        None,
+      ),
+      dimensions,
+    };
+    palette.update_entries();
+    palette.layout(screen_size);
+    palette
+  }
+
+  fn row_height(&self) -> f32 {
+    self.font_height + ROW_PADDING
+  }
+
+  pub fn open(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    layouts: Vec<String>,
+    tasks: Vec<String>,
+    shortcuts: Vec<(Command, String)>,
+  ) {
+    self.layouts = layouts;
+    self.tasks = tasks;
+    self.shortcuts = shortcuts;
+    self.prompt(screen_size, Mode::Commands, "");
+  }
+
+  /// Asks for the new name of the symbol currently named `name`.
+  pub fn open_rename(&mut self, screen_size: PhysicalSize<f32>, name: &str) {
+    self.prompt(screen_size, Mode::Rename, name);
+  }
+
+  /// Asks where to install a plugin from.
+  pub fn open_install_plugin(&mut self, screen_size: PhysicalSize<f32>) {
+    self.prompt(screen_size, Mode::InstallPlugin, "");
+  }
+
+  /// Asks for a directory to open.
+  pub fn open_folder(&mut self, screen_size: PhysicalSize<f32>) {
+    self.prompt(screen_size, Mode::OpenFolder, "");
+  }
+
+  /// Asks for the text to replace in all files, starting with `find`.
+  pub fn open_find_in_files(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    find: &str,
+  ) {
+    self.prompt(screen_size, Mode::FindInFiles, find);
+  }
+
+  /// Asks what to replace `find` with in all files.
+  pub fn open_replace_in_files(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    find: String,
+  ) {
+    self.find = find;
+    self.prompt(screen_size, Mode::ReplaceInFiles, "");
+  }
+
+  /// Asks for the path to save the active tab to, starting with `path`.
+  pub fn open_save_as(&mut self, screen_size: PhysicalSize<f32>, path: &str) {
+    self.prompt(screen_size, Mode::SaveAs, path);
+  }
+
+  /// Asks which of `languages` the active tab is in.
+  pub fn open_language(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    languages: Vec<String>,
+  ) {
+    self.languages = languages;
+    self.prompt(screen_size, Mode::Language, "");
+  }
+
+  pub fn close(&mut self) {
+    self.visible = false;
+  }
+
+  /// Shows the palette in `mode`, with `query` entered.
+  fn prompt(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    mode: Mode,
+    query: &str,
+  ) {
+    self.visible = true;
+    self.mode = mode;
+    self.input.set_placeholder(mode.placeholder());
+    self.input.set_text(screen_size, query);
+    self.update_entries();
+    self.layout(screen_size);
+  }
+
+  /// The selected part of the query, if any.
+  pub fn selected_text(&self) -> Option<String> {
+    self.input.selected_text()
+  }
+
+  /// Removes the selected part of the query, returning it.
+  pub fn cut(&mut self, screen_size: PhysicalSize<f32>) -> Option<String> {
+    let query = self.input.text();
+    let cut = self.input.cut(screen_size);
+    self.query_edited(screen_size, query);
+    cut
+  }
+
+  pub fn paste(&mut self, screen_size: PhysicalSize<f32>, text: &str) {
+    let query = self.input.text();
+    self.input.paste(screen_size, text);
+    self.query_edited(screen_size, query);
+  }
+
+  /// Applies `message` to the query, like moving by words or undoing.
+  pub fn update_query(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    message: Message,
+  ) {
+    let query = self.input.text();
+    self.input.update(screen_size, message);
+    self.query_edited(screen_size, query);
+  }
+
+  /// Finds the entries again if the query isn't `before` anymore.
+  fn query_edited(&mut self, screen_size: PhysicalSize<f32>, before: String) {
+    if self.input.text() != before {
+      self.update_entries();
+      self.layout(screen_size);
+    }
+  }
+
+  fn update_entries(&mut self) {
+    let query = self.input.text();
+    self.entries = if self.mode == Mode::Rename {
+      let name = query.trim();
+      vec![if name.is_empty() {
+        Entry {
+          label: String::from("Enter the new name"),
+          action: None,
+        }
+      } else {
+        Entry {
+          label: format!("Rename Symbol to \"{}\"", name),
+          action: Some(PaletteAction::Rename(name.to_string())),
+        }
+      }]
+    } else if self.mode == Mode::InstallPlugin {
+      let source = query.trim();
+      vec![if source.is_empty() {
+        Entry {
+          label: String::from("Enter a plugin directory or git URL"),
+          action: None,
+        }
+      } else {
+        Entry {
+          label: format!("Install Plugin from \"{}\"", source),
+          action: Some(PaletteAction::InstallPlugin(source.to_string())),
+        }
+      }]
+    } else if self.mode == Mode::OpenFolder {
+      let dir = query.trim();
+      vec![if dir.is_empty() {
+        Entry {
+          label: String::from("Enter the directory to open"),
+          action: None,
+        }
+      } else {
+        Entry {
+          label: format!("Open Folder \"{}\" in a New Window", dir),
+          action: Some(PaletteAction::OpenFolder(dir.to_string())),
+        }
+      }]
+    } else if self.mode == Mode::FindInFiles {
+      vec![if query.is_empty() {
+        Entry {
+          label: String::from("Enter the text to replace in all files"),
+          action: None,
+        }
+      } else {
+        Entry {
+          label: format!("Find \"{}\" in All Files", query),
+          action: Some(PaletteAction::FindInFiles(query.clone())),
+        }
+      }]
+    } else if self.mode == Mode::ReplaceInFiles {
+      vec![Entry {
+        label: format!(
+          "Replace \"{}\" with \"{}\" in All Files",
+          self.find, query
+        ),
+        action: Some(PaletteAction::ReplaceInFiles(
+          self.find.clone(),
+          query.clone(),
+        )),
+      }]
+    } else if self.mode == Mode::SaveAs {
+      let path = query.trim();
+      vec![if path.is_empty() {
+        Entry {
+          label: String::from("Enter the path to save to"),
+          action: None,
+        }
+      } else {
+        Entry {
+          label: format!("Save as \"{}\"", path),
+          action: Some(PaletteAction::SaveAs(path.to_string())),
+        }
+      }]
+    } else if self.mode == Mode::Language {
+      let plain_text = Entry {
+        label: String::from("Plain Text"),
+        action: Some(PaletteAction::SetLanguage(None)),
+      };
+      std::iter::once(plain_text)
+        .chain(self.languages.iter().map(|id| Entry {
+          label: id.clone(),
+          action: Some(PaletteAction::SetLanguage(Some(id.clone()))),
+        }))
+        .filter(|entry| fuzzy::score(&query, &entry.label).is_some())
+        .collect()
+    } else if let Some(expression) = query.strip_prefix('=') {
+      match calc::evaluate(expression) {
+        Ok(answer) => {
+          let result = answer.display();
+          let mut entries = vec![
+            Entry {
+              label: format!("Copy result: {}", result),
+              action: Some(PaletteAction::Copy(result.clone())),
+            },
+            Entry {
+              label: format!("Insert at cursor: {}", result),
+              action: Some(PaletteAction::Insert(result)),
+            },
+          ];
+          entries.extend(answer.alternatives().into_iter().map(|result| {
+            Entry {
+              label: format!("Copy {}", result),
+              action: Some(PaletteAction::Copy(result)),
+            }
+          }));
+          entries
+        }
+        Err(err) => vec![Entry {
+          label: err.to_string(),
+          action: None,
+        }],
+      }
+    } else {
+      let mut entries = Command::ALL
+        .iter()
+        .filter(|command| **command != Command::ShowCommandPalette)
+        .filter(|command| fuzzy::score(&query, command.name()).is_some())
+        .map(|command| Entry {
+          label: command.name().to_string(),
+          action: Some(PaletteAction::Run(*command)),
+        })
+        .collect::<Vec<_>>();
+      entries.extend(
+        self
+          .tasks
+          .iter()
+          .map(|name| (format!("Run Task: {}", name), name))
+          .filter(|(label, _)| fuzzy::score(&query, label).is_some())
+          .map(|(label, name)| Entry {
+            label,
+            action: Some(PaletteAction::RunTask(name.clone())),
+          }),
+      );
+      entries.extend(
+        self
+          .layouts
+          .iter()
+          .map(|name| (format!("Switch to Layout: {}", name), name))
+          .filter(|(label, _)| fuzzy::score(&query, label).is_some())
+          .map(|(label, name)| Entry {
+            label,
+            action: Some(PaletteAction::ApplyLayout(name.clone())),
+          }),
+      );
+      let name = query.trim();
+      if !name.is_empty() {
+        entries.push(Entry {
+          label: format!("Save Current Layout as \"{}\"", name),
+          action: Some(PaletteAction::SaveLayout(name.to_string())),
+        });
+      }
+      entries
+    };
+    self.selected = 0;
+    self.first_visible = 0;
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let width = (screen_size.width * 0.6).min(PALETTE_MAX_WIDTH);
+    let rows = 1 + self.entries.len().min(MAX_VISIBLE_ENTRIES);
+    self.dimensions = Dimensions {
+      x: (screen_size.width - width) / 2.0,
+      y: PALETTE_TOP,
+      width,
+      height: (PALETTE_PADDING * 2.0) + (rows as f32 * self.row_height()),
+    };
+    self.rect.resize(screen_size, self.dimensions);
+
+    let row = self.selected - self.first_visible + 1;
+    self.selection_rect.resize(
+      screen_size,
+      Dimensions {
+        y: self.dimensions.y + PALETTE_PADDING + row as f32 * self.row_height(),
+        height: if self.entries.is_empty() {
+          0.0
+        } else {
+          self.row_height()
+        },
+        ..self.dimensions
+      },
+    );
+
+    self.input.set_dimensions(
+      screen_size,
+      Dimensions {
+        x: self.dimensions.x + PALETTE_PADDING,
+        y: self.dimensions.y + PALETTE_PADDING + (ROW_PADDING / 2.0),
+        width: self.dimensions.width - (PALETTE_PADDING * 2.0),
+        height: self.font_height,
+      },
+    );
+  }
+
+  pub fn input_special(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) {
+    match key {
+      VirtualKeyCode::Escape => self.close(),
+      VirtualKeyCode::Up => {
+        self.selected = self.selected.saturating_sub(1);
+        self.first_visible = self.first_visible.min(self.selected);
+      }
+      VirtualKeyCode::Down => {
+        if self.selected + 1 < self.entries.len() {
+          self.selected += 1;
+        }
+        if self.selected >= self.first_visible + MAX_VISIBLE_ENTRIES {
+          self.first_visible = self.selected + 1 - MAX_VISIBLE_ENTRIES;
+        }
+      }
+      key => return self.input.input_special(screen_size, key),
+    }
+    self.layout(screen_size);
+  }
+
+  /// Returns the action of the selected entry when it got accepted.
+  pub fn input_char(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    ch: char,
+  ) -> Option<PaletteAction> {
+    match ch {
+      '\r' => {
+        let action = self
+          .entries
+          .get(self.selected)
+          .and_then(|entry| entry.action.clone());
+        if action.is_some() {
+          self.close();
+        }
+        return action;
+      }
+      _ => {
+        let query = self.input.text();
+        self.input.input_char(screen_size, ch);
+        self.query_edited(screen_size, query);
+      }
+    }
+    None
+  }
+}
+
+impl super::RenderElement for CommandPalette {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.layout(screen_size);
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.overlay.rgb());
+    self.selection_rect.set_color(theme.selection.rgb());
+    super::RenderElement::set_theme(&mut self.input, theme);
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+    super::RenderElement::set_font(&mut self.input, font, font_height);
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let x = self.dimensions.x + PALETTE_PADDING;
+    let y = self.dimensions.y + PALETTE_PADDING + (ROW_PADDING / 2.0);
+    let row_height = self.row_height();
+
+    super::RenderElement::redraw(
+      &mut self.input,
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+    );
+
+    for (i, entry) in self
+      .entries
+      .iter()
+      .skip(self.first_visible)
+      .take(MAX_VISIBLE_ENTRIES)
+      .enumerate()
+    {
+      let color = if entry.action.is_some() {
+        self.theme.overlay_text.rgba()
+      } else {
+        self.theme.error.rgba()
+      };
+      let shortcut = match entry.action {
+        Some(PaletteAction::Run(command)) => self
+          .shortcuts
+          .iter()
+          .find(|(bound, _)| *bound == command)
+          .map(|(_, shortcut)| shortcut.as_str()),
+        _ => None,
+      };
+      glyph_brush.queue(Section {
+        screen_position: (x, y + ((i + 1) as f32 * row_height)),
+        text: vec![
+          Text::new(&entry.label)
+            .with_color(color)
+            .with_scale(self.font_height),
+          Text::new("  ").with_scale(self.font_height),
+          Text::new(shortcut.unwrap_or_default())
+            .with_color(self.theme.gutter_text.rgba())
+            .with_scale(self.font_height),
+        ],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    let mut rects = vec![&self.rect, &self.selection_rect];
+    rects.extend(super::RenderElement::get_rects(&self.input));
+    rects
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
@@ -0,0 +1,286 @@
+use crate::completion::Item;
+use crate::config::Theme;
+use crate::renderer::input::line_length;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::PhysicalSize;
+use winit::event::VirtualKeyCode;
+
+const POPUP_PADDING: f32 = 4.0;
+const ROW_PADDING: f32 = 4.0;
+const MIN_WIDTH: f32 = 150.0;
+const DOCUMENTATION_WIDTH: f32 = 250.0;
+const MAX_VISIBLE_ITEMS: usize = 8;
+
+/// The list of completions shown below the cursor.
+pub struct CompletionPopup {
+  pub visible: bool,
+  font: FontArc,
+  font_height: f32,
+  theme: Theme,
+  items: Vec<Item>,
+  selected: usize,
+  first_visible: usize,
+  show_documentation: bool,
+  /// The cursor the popup belongs to.
+  anchor: Dimensions,
+  rect: Rectangle,
+  selection_rect: Rectangle,
+  documentation_rect: Rectangle,
+  dimensions: Dimensions,
+  documentation_dimensions: Dimensions,
+}
+
+impl CompletionPopup {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      visible: false,
+      font,
+      font_height,
+      theme,
+      items: vec![],
+      selected: 0,
+      first_visible: 0,
+      show_documentation: true,
+      anchor: dimensions,
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.overlay.rgb(),
+        None,
+      ),
+      selection_rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.selection.rgb(),
+        None,
+      ),
+      documentation_rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.overlay.rgb(),
+        None,
+      ),
+      dimensions,
+      documentation_dimensions: dimensions,
+    }
+  }
+
+  fn row_height(&self) -> f32 {
+    self.font_height + ROW_PADDING
+  }
+
+  /// Shows `items` next to the cursor at `anchor`.
+  pub fn open(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    anchor: Dimensions,
+    items: Vec<Item>,
+    show_documentation: bool,
+  ) {
+    self.visible = true;
+    self.anchor = anchor;
+    self.items = items;
+    self.selected = 0;
+    self.first_visible = 0;
+    self.show_documentation = show_documentation;
+    self.layout(screen_size);
+  }
+
+  pub fn close(&mut self) {
+    self.visible = false;
+    self.items.clear();
+  }
+
+  pub fn selected_item(&self) -> Option<&Item> {
+    self.items.get(self.selected)
+  }
+
+  pub fn selected_index(&self) -> usize {
+    self.selected
+  }
+
+  fn documentation(&self) -> Option<&str> {
+    if !self.show_documentation {
+      return None;
+    }
+    self.selected_item()?.documentation.as_deref()
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let label_width = self
+      .items
+      .iter()
+      .map(|item| line_length(&item.label, self.font.clone(), self.font_height))
+      .fold(0.0, f32::max);
+    let rows = self.items.len().min(MAX_VISIBLE_ITEMS);
+    let width = (label_width + (POPUP_PADDING * 2.0)).max(MIN_WIDTH);
+    let height = (rows as f32 * self.row_height()) + (POPUP_PADDING * 2.0);
+
+    // below the cursor, or above it if there is no room
+    let below = self.anchor.y + self.anchor.height;
+    let y = if below + height > screen_size.height {
+      (self.anchor.y - height).max(0.0)
+    } else {
+      below
+    };
+    self.dimensions = Dimensions {
+      x: self.anchor.x.min(screen_size.width - width).max(0.0),
+      y,
+      width,
+      height,
+    };
+    self.rect.resize(screen_size, self.dimensions);
+
+    let row = self.selected - self.first_visible;
+    self.selection_rect.resize(
+      screen_size,
+      Dimensions {
+        y: self.dimensions.y + POPUP_PADDING + row as f32 * self.row_height(),
+        height: self.row_height(),
+        ..self.dimensions
+      },
+    );
+
+    self.documentation_dimensions = if self.documentation().is_some() {
+      Dimensions {
+        x: self.dimensions.x + self.dimensions.width,
+        width: DOCUMENTATION_WIDTH,
+        ..self.dimensions
+      }
+    } else {
+      Dimensions::default()
+    };
+    self
+      .documentation_rect
+      .resize(screen_size, self.documentation_dimensions);
+  }
+
+  /// Moves the selection, returns whether `key` was handled.
+  pub fn input_special(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) -> bool {
+    match key {
+      VirtualKeyCode::Up => {
+        self.selected = self.selected.saturating_sub(1);
+        self.first_visible = self.first_visible.min(self.selected);
+      }
+      VirtualKeyCode::Down => {
+        if self.selected + 1 < self.items.len() {
+          self.selected += 1;
+        }
+        if self.selected >= self.first_visible + MAX_VISIBLE_ITEMS {
+          self.first_visible = self.selected + 1 - MAX_VISIBLE_ITEMS;
+        }
+      }
+      _ => return false,
+    }
+    self.layout(screen_size);
+    true
+  }
+}
+
+impl super::RenderElement for CompletionPopup {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.layout(screen_size);
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.overlay.rgb());
+    self.selection_rect.set_color(theme.selection.rgb());
+    self.documentation_rect.set_color(theme.overlay.rgb());
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let x = self.dimensions.x + POPUP_PADDING;
+    let y = self.dimensions.y + POPUP_PADDING + (ROW_PADDING / 2.0);
+    let row_height = self.row_height();
+
+    for (i, item) in self
+      .items
+      .iter()
+      .skip(self.first_visible)
+      .take(MAX_VISIBLE_ITEMS)
+      .enumerate()
+    {
+      glyph_brush.queue(Section {
+        screen_position: (x, y + (i as f32 * row_height)),
+        text: vec![Text::new(&item.label)
+          .with_color(self.theme.overlay_text.rgba())
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+
+    if let Some(documentation) = self.documentation() {
+      glyph_brush.queue(Section {
+        screen_position: (self.documentation_dimensions.x + POPUP_PADDING, y),
+        bounds: (DOCUMENTATION_WIDTH - (POPUP_PADDING * 2.0), f32::INFINITY),
+        text: vec![Text::new(documentation)
+          .with_color(self.theme.overlay_text.rgba())
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+      super::draw_glyphs(
+        glyph_brush,
+        device,
+        staging_belt,
+        encoder,
+        target,
+        size,
+        Some(self.documentation_dimensions),
+      );
+    }
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect, &self.selection_rect, &self.documentation_rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
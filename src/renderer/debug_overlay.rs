@@ -0,0 +1,185 @@
+use crate::config::Theme;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::PhysicalSize;
+
+const OVERLAY_WIDTH: f32 = 260.0;
+const OVERLAY_PADDING: f32 = 8.0;
+const OVERLAY_LINES: f32 = 4.0;
+/// Number of frames the average frame time is computed over.
+const FRAME_HISTORY: usize = 120;
+/// Number of inputs the average input latency is computed over.
+const INPUT_HISTORY: usize = 30;
+
+/// What happened during a single frame.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct FrameStats {
+  pub frame_time: Duration,
+  pub rect_draws: usize,
+  pub text_draws: usize,
+}
+
+pub struct DebugOverlay {
+  pub visible: bool,
+  rect: Rectangle,
+  font_height: f32,
+  theme: Theme,
+  frame_times: VecDeque<Duration>,
+  frame_starts: VecDeque<Instant>,
+  last: FrameStats,
+  /// From receiving inputs to presenting the frame showing them.
+  latencies: VecDeque<Duration>,
+}
+
+fn average(durations: &VecDeque<Duration>) -> Duration {
+  if durations.is_empty() {
+    Duration::default()
+  } else {
+    durations.iter().sum::<Duration>() / durations.len() as u32
+  }
+}
+
+impl DebugOverlay {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let rect = Rectangle::new(
+      device,
+      screen_size,
+      Self::dimensions(screen_size, font_height),
+      theme.overlay.rgb(),
+      None,
+    );
+
+    Self {
+      visible: false,
+      rect,
+      font_height,
+      theme,
+      frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+      frame_starts: VecDeque::new(),
+      last: FrameStats::default(),
+      latencies: VecDeque::with_capacity(INPUT_HISTORY),
+    }
+  }
+
+  fn dimensions(
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+  ) -> Dimensions {
+    Dimensions {
+      x: screen_size.width - OVERLAY_WIDTH,
+      y: 0.0,
+      width: OVERLAY_WIDTH,
+      height: (OVERLAY_PADDING * 2.0) + (font_height * OVERLAY_LINES),
+    }
+  }
+
+  pub fn record(&mut self, start: Instant, stats: FrameStats) {
+    if self.frame_times.len() == FRAME_HISTORY {
+      self.frame_times.pop_front();
+    }
+    self.frame_times.push_back(stats.frame_time);
+
+    self.frame_starts.push_back(start);
+    while let Some(first) = self.frame_starts.front() {
+      if start.duration_since(*first) > Duration::from_secs(1) {
+        self.frame_starts.pop_front();
+      } else {
+        break;
+      }
+    }
+
+    self.last = stats;
+  }
+
+  /// Counts the time from receiving an input to presenting it.
+  pub fn record_latency(&mut self, latency: Duration) {
+    if self.latencies.len() == INPUT_HISTORY {
+      self.latencies.pop_front();
+    }
+    self.latencies.push_back(latency);
+  }
+}
+
+impl super::RenderElement for DebugOverlay {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self
+      .rect
+      .resize(screen_size, Self::dimensions(screen_size, self.font_height));
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.overlay.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    let text = format!(
+      "frame {:.2} ms (avg {:.2} ms)\nfps {}\ndraw calls {} ({} rects, {} text)\ninput {:.2} ms (avg {:.2} ms)",
+      millis(self.last.frame_time),
+      millis(average(&self.frame_times)),
+      self.frame_starts.len(),
+      self.last.rect_draws + self.last.text_draws,
+      self.last.rect_draws,
+      self.last.text_draws,
+      millis(self.latencies.back().copied().unwrap_or_default()),
+      millis(average(&self.latencies)),
+    );
+
+    glyph_brush.queue(Section {
+      screen_position: (
+        self.rect.dimensions.x + OVERLAY_PADDING,
+        self.rect.dimensions.y + OVERLAY_PADDING,
+      ),
+      text: vec![Text::new(&text)
+        .with_color(self.theme.overlay_text.rgba())
+        .with_scale(self.font_height)],
+      ..Section::default()
+    });
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      None,
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.rect.dimensions
+  }
+}
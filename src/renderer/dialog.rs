@@ -0,0 +1,326 @@
+use crate::config::Theme;
+use crate::renderer::input::line_length;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::VirtualKeyCode;
+
+const DIALOG_TOP: f32 = 120.0;
+const DIALOG_MAX_WIDTH: f32 = 500.0;
+const PADDING: f32 = 12.0;
+const BUTTON_PADDING: f32 = 8.0;
+const BUTTON_SPACING: f32 = 8.0;
+
+/// A question with a row of buttons, which has to be answered before
+/// anything else can be done.
+pub struct Dialog {
+  pub visible: bool,
+  font: FontArc,
+  font_height: f32,
+  theme: Theme,
+  message: String,
+  buttons: Vec<String>,
+  /// The button escape answers with.
+  cancel: usize,
+  selected: usize,
+  rect: Rectangle,
+  button_rects: Vec<Rectangle>,
+  dimensions: Dimensions,
+}
+
+impl Dialog {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      visible: false,
+      font,
+      font_height,
+      theme,
+      message: String::new(),
+      buttons: vec![],
+      cancel: 0,
+      selected: 0,
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.overlay.rgb(),
+        None,
+      ),
+      button_rects: vec![],
+      dimensions,
+    }
+  }
+
+  /// Asks `message`, answered with the index of one of `buttons`. The first
+  /// button is selected, escape answers `cancel`.
+  pub fn open(
+    &mut self,
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    message: String,
+    buttons: &[&str],
+    cancel: usize,
+  ) {
+    self.visible = true;
+    self.message = message;
+    self.buttons = buttons.iter().map(|button| button.to_string()).collect();
+    self.cancel = cancel;
+    self.selected = 0;
+    self.button_rects = buttons
+      .iter()
+      .map(|_| {
+        Rectangle::new(
+          device,
+          screen_size,
+          Dimensions::default(),
+          self.theme.tab.rgb(),
+          None,
+        )
+      })
+      .collect();
+    self.layout(screen_size);
+  }
+
+  pub fn close(&mut self) {
+    self.visible = false;
+  }
+
+  fn text_width(&self) -> f32 {
+    self.dimensions.width - (PADDING * 2.0)
+  }
+
+  fn message_lines(&self) -> usize {
+    let width = self.text_width().max(1.0);
+    self
+      .message
+      .lines()
+      .map(|line| {
+        let length = line_length(line, self.font.clone(), self.font_height);
+        ((length / width).ceil() as usize).max(1)
+      })
+      .sum()
+  }
+
+  fn button_height(&self) -> f32 {
+    self.font_height + (BUTTON_PADDING * 2.0)
+  }
+
+  /// Where the buttons are, right aligned below the message.
+  fn button_bounds(&self) -> Vec<Dimensions> {
+    let y = self.dimensions.y + self.dimensions.height
+      - PADDING
+      - self.button_height();
+    let mut x = self.dimensions.x + self.dimensions.width - PADDING;
+    let mut bounds = self
+      .buttons
+      .iter()
+      .rev()
+      .map(|button| {
+        let width = line_length(button, self.font.clone(), self.font_height)
+          + (BUTTON_PADDING * 2.0);
+        x -= width;
+        let dimensions = Dimensions {
+          x,
+          y,
+          width,
+          height: self.button_height(),
+        };
+        x -= BUTTON_SPACING;
+        dimensions
+      })
+      .collect::<Vec<_>>();
+    bounds.reverse();
+    bounds
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let width = (screen_size.width * 0.6).min(DIALOG_MAX_WIDTH);
+    self.dimensions = Dimensions {
+      x: (screen_size.width - width) / 2.0,
+      y: DIALOG_TOP,
+      width,
+      height: 0.0,
+    };
+    self.dimensions.height = (self.message_lines() as f32 * self.font_height)
+      + self.button_height()
+      + (PADDING * 3.0);
+    self.rect.resize(screen_size, self.dimensions);
+
+    for (i, bounds) in self.button_bounds().into_iter().enumerate() {
+      let color = if i == self.selected {
+        self.theme.selection
+      } else {
+        self.theme.tab
+      };
+      self.button_rects[i].resize(screen_size, bounds);
+      self.button_rects[i].set_color(color.rgb());
+    }
+  }
+
+  fn select(&mut self, screen_size: PhysicalSize<f32>, selected: usize) {
+    self.selected = selected;
+    self.layout(screen_size);
+  }
+
+  fn answer(&mut self, button: usize) -> Option<usize> {
+    self.close();
+    Some(button)
+  }
+
+  /// Returns the button answered with, if any.
+  pub fn input_special(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) -> Option<usize> {
+    let count = self.buttons.len();
+    match key {
+      VirtualKeyCode::Escape => return self.answer(self.cancel),
+      VirtualKeyCode::Left | VirtualKeyCode::Up => {
+        self.select(screen_size, (self.selected + count - 1) % count)
+      }
+      VirtualKeyCode::Right | VirtualKeyCode::Down => {
+        self.select(screen_size, (self.selected + 1) % count)
+      }
+      _ => {}
+    }
+    None
+  }
+
+  /// Returns the button answered with, if any. Enter answers with the
+  /// selected button, tab selects the next one.
+  pub fn input_char(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    ch: char,
+  ) -> Option<usize> {
+    match ch {
+      '\r' => self.answer(self.selected),
+      '\t' => {
+        self.select(screen_size, (self.selected + 1) % self.buttons.len());
+        None
+      }
+      _ => None,
+    }
+  }
+
+  /// Answers with the button starting with `ch`, typed with alt held.
+  pub fn input_mnemonic(&mut self, ch: char) -> Option<usize> {
+    let ch = ch.to_lowercase().next()?;
+    let button = self.buttons.iter().position(|button| {
+      button
+        .chars()
+        .next()
+        .and_then(|first| first.to_lowercase().next())
+        == Some(ch)
+    })?;
+    self.answer(button)
+  }
+
+  /// The button at `position`, relative to the dialog.
+  pub fn click_at(&mut self, position: PhysicalPosition<f32>) -> Option<usize> {
+    let position = PhysicalPosition {
+      x: position.x + self.dimensions.x,
+      y: position.y + self.dimensions.y,
+    };
+    let button = self
+      .button_bounds()
+      .iter()
+      .position(|bounds| bounds.contains(position).is_some())?;
+    self.answer(button)
+  }
+}
+
+impl super::RenderElement for Dialog {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    if self.visible {
+      self.layout(screen_size);
+    }
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.overlay.rgb());
+    for (i, rect) in self.button_rects.iter_mut().enumerate() {
+      let color = if i == self.selected {
+        theme.selection
+      } else {
+        theme.tab
+      };
+      rect.set_color(color.rgb());
+    }
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let color = self.theme.overlay_text.rgba();
+    glyph_brush.queue(Section {
+      screen_position: (
+        self.dimensions.x + PADDING,
+        self.dimensions.y + PADDING,
+      ),
+      bounds: (self.text_width(), f32::INFINITY),
+      text: vec![Text::new(&self.message)
+        .with_color(color)
+        .with_scale(self.font_height)],
+      ..Section::default()
+    });
+
+    for (button, bounds) in self.buttons.iter().zip(self.button_bounds()) {
+      glyph_brush.queue(Section {
+        screen_position: (bounds.x + BUTTON_PADDING, bounds.y + BUTTON_PADDING),
+        text: vec![Text::new(button)
+          .with_color(self.theme.tab_text.rgba())
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    let mut rects = vec![&self.rect];
+    rects.extend(self.button_rects.iter());
+    rects
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
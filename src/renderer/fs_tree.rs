@@ -1,17 +1,21 @@
+use crate::config::Theme;
 use crate::renderer::rectangle::Rectangle;
 use crate::renderer::Dimensions;
 use std::collections::HashSet;
 use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 use wgpu::util::StagingBelt;
 use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
 use wgpu_glyph::{GlyphBrush, Section, Text};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+
 struct TreeEntry {
   name: String,
-  #[allow(dead_code)]
   path: PathBuf,
   inset: usize,
   sub_entry: Option<Vec<TreeEntry>>,
@@ -114,10 +118,13 @@ impl TreeEntry {
 pub struct FsTree {
   rect: Rectangle,
   font_height: f32,
+  theme: Theme,
   pub dimensions: Dimensions,
   scroll_offset: PhysicalPosition<f64>,
   tree: TreeEntry,
   counter: i32,
+  /// The row and time of the last click, to detect double clicks.
+  last_click: Option<(usize, Instant)>,
 }
 
 impl FsTree {
@@ -127,9 +134,15 @@ impl FsTree {
     font_height: f32,
     dimensions: Dimensions,
     path: PathBuf,
+    theme: Theme,
   ) -> Self {
-    let rect =
-      Rectangle::new(device, screen_size, dimensions, [0.04, 0.04, 0.04], None);
+    let rect = Rectangle::new(
+      device,
+      screen_size,
+      dimensions,
+      theme.sidebar.rgb(),
+      None,
+    );
 
     let mut ignore_set = HashSet::new();
     ignore_set.insert(OsString::from_str(".DS_Store").unwrap());
@@ -137,45 +150,88 @@ impl FsTree {
     Self {
       rect,
       font_height,
+      theme,
       dimensions,
       scroll_offset: PhysicalPosition { x: 0.0, y: 0.0 },
       tree: TreeEntry::new(path, ignore_set),
       counter: 0,
+      last_click: None,
     }
   }
-}
-
-impl super::RenderElement for FsTree {
-  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
-    self.dimensions.height = screen_size.height;
-    self.rect.resize(screen_size.cast(), self.dimensions);
-  }
 
-  fn scroll(
+  pub fn set_dimensions(
     &mut self,
-    offset: PhysicalPosition<f64>,
-    _screen_size: PhysicalSize<f32>,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
   ) {
-    self.scroll_offset.y = (self.scroll_offset.y + offset.y)
-      .min(0.0)
-      .max(-((self.counter - 3) as f32 * self.font_height) as f64);
+    self.dimensions = dimensions;
+    self.rect.resize(screen_size, dimensions);
   }
 
-  fn click(
+  /// Folds or unfolds the directory at `position`, relative to the tree.
+  /// Returns the path of a clicked file and whether it was double clicked.
+  pub fn click_at(
     &mut self,
-    position: PhysicalPosition<f64>,
-    _screen_size: PhysicalSize<f32>,
-  ) {
-    let index = ((position.y - self.scroll_offset.y) / self.font_height as f64)
+    position: PhysicalPosition<f32>,
+  ) -> Option<(PathBuf, bool)> {
+    let index = ((position.y as f64 - self.scroll_offset.y)
+      / self.font_height as f64)
       .floor() as usize;
+    let now = Instant::now();
+    let double = matches!(
+      self.last_click,
+      Some((row, time)) if row == index && now - time < DOUBLE_CLICK_INTERVAL
+    );
+    self.last_click = Some((index, now));
+
+    let mut file = None;
     let mut i = 0;
     self.counter = self.tree.walk(&mut |entry| {
-      if index == i && entry.sub_entry.is_some() {
-        entry.folded = !entry.folded;
+      if index == i {
+        if entry.sub_entry.is_some() {
+          entry.folded = !entry.folded;
+        } else {
+          file = Some(entry.path.clone());
+        }
       }
       i += 1;
       !entry.folded
     });
+    file.map(|path| (path, double))
+  }
+
+  /// The files in the workspace, leaving out hidden directories such as
+  /// `.git`.
+  pub fn files(&mut self) -> Vec<PathBuf> {
+    let mut files = vec![];
+    self.tree.walk(&mut |entry| {
+      if entry.sub_entry.is_none() {
+        files.push(entry.path.clone());
+      }
+      entry.inset == 0 || !entry.name.starts_with('.')
+    });
+    files
+  }
+}
+
+impl super::RenderElement for FsTree {
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    _screen_size: PhysicalSize<f32>,
+  ) {
+    self.scroll_offset.y = (self.scroll_offset.y + offset.y)
+      .min(0.0)
+      .max(-((self.counter - 3) as f32 * self.font_height) as f64);
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.sidebar.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
   }
 
   fn redraw(
@@ -188,38 +244,36 @@ impl super::RenderElement for FsTree {
     screen_size: PhysicalSize<u32>,
   ) {
     let font_height = self.font_height;
+    let color = self.theme.sidebar_text.rgba();
+    let dimensions = self.dimensions;
     let y_offset = self.scroll_offset.y;
     let mut index = 0;
     self.counter = self.tree.walk(&mut |entry| {
       glyph_brush.queue(Section {
         screen_position: (
-          entry.inset as f32 * font_height,
-          (index as f32 * font_height) + y_offset as f32,
+          dimensions.x + (entry.inset as f32 * font_height),
+          dimensions.y + (index as f32 * font_height) + y_offset as f32,
         ),
         bounds: (f32::INFINITY, f32::INFINITY),
         layout: Default::default(),
         text: vec![Text::new(&entry.name)
           .with_scale(font_height)
-          .with_color([0.0, 0.9, 0.0, 1.0])],
+          .with_color(color)],
       });
       index += 1;
 
       !entry.folded
     });
 
-    glyph_brush
-      .draw_queued_with_transform_and_scissoring(
-        device,
-        staging_belt,
-        encoder,
-        target,
-        wgpu_glyph::orthographic_projection(
-          screen_size.width,
-          screen_size.height,
-        ),
-        self.dimensions.into(),
-      )
-      .unwrap();
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      screen_size,
+      Some(self.dimensions),
+    );
   }
 
   fn get_rects(&self) -> Vec<&Rectangle> {
@@ -0,0 +1,81 @@
+//! The caches of rasterized glyphs text is drawn from. The code, which can
+//! show thousands of distinct characters, has a page of its own so the
+//! glyphs of the rest of the window don't push them out, sized to hold a
+//! window full of glyphs so they aren't rasterized again every frame.
+
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, GlyphBrushBuilder};
+use winit::dpi::PhysicalSize;
+
+/// Side of the smallest page, the size glyph_brush starts out with.
+const MIN_PAGE: u32 = 256;
+
+/// Side of a square page fitting glyphs covering a window of `size`, with
+/// room for the gaps between them.
+fn page_size(size: PhysicalSize<u32>, max: u32) -> u32 {
+  let area = size.width as f64 * size.height as f64;
+  let side = (area.sqrt() * 1.25) as u32;
+  side.next_power_of_two().clamp(MIN_PAGE, max)
+}
+
+fn build(device: &wgpu::Device, font: FontArc, side: u32) -> GlyphBrush<()> {
+  GlyphBrushBuilder::using_font(font)
+    .initial_cache_size((side, side))
+    .build(device, super::render_format())
+}
+
+pub struct GlyphPages {
+  /// Draws the text of everything but the code.
+  pub ui: GlyphBrush<()>,
+  /// Draws the code with its tabs and gutter.
+  pub code: GlyphBrush<()>,
+  /// Side of the code page.
+  code_size: u32,
+  /// Side of the largest texture the device supports.
+  max_size: u32,
+}
+
+impl GlyphPages {
+  pub fn new(
+    device: &wgpu::Device,
+    font: FontArc,
+    size: PhysicalSize<u32>,
+  ) -> Self {
+    let max_size = device.limits().max_texture_dimension_2d;
+    let code_size = page_size(size, max_size);
+    Self {
+      ui: build(device, font.clone(), MIN_PAGE),
+      code: build(device, font, code_size),
+      code_size,
+      max_size,
+    }
+  }
+
+  /// Grows the code page to fit a window resized to `size`. The glyphs it
+  /// had are rasterized again.
+  pub fn resize(
+    &mut self,
+    device: &wgpu::Device,
+    font: FontArc,
+    size: PhysicalSize<u32>,
+  ) {
+    let code_size = page_size(size, self.max_size);
+    if code_size > self.code_size {
+      self.code = build(device, font, code_size);
+      self.code_size = code_size;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fits_a_window_of_glyphs() {
+    assert_eq!(page_size(PhysicalSize::new(1920, 1080), 8192), 2048);
+    assert_eq!(page_size(PhysicalSize::new(3840, 2160), 8192), 4096);
+    assert_eq!(page_size(PhysicalSize::new(3840, 2160), 2048), 2048);
+    assert_eq!(page_size(PhysicalSize::new(100, 50), 8192), MIN_PAGE);
+  }
+}
@@ -1,36 +1,22 @@
-use crate::renderer::rectangle::{Rectangle, Region};
+use crate::config::{Color, Theme};
+use crate::editor::{Change, Direction, EditorState, Message};
+use crate::renderer::rectangle::Rectangle;
 use crate::renderer::Dimensions;
+use crate::segments::LONG_LINE;
+use crate::structure::{Position, Span};
+use crate::viewport::{self, Overscroll};
+use std::cell::RefCell;
+use std::rc::Rc;
 use unicode_segmentation::UnicodeSegmentation;
-use wgpu_glyph::ab_glyph::{Font, FontArc};
-use wgpu_glyph::{GlyphPositioner, Layout, SectionGeometry, Text};
-use winit::dpi::{PhysicalPosition, PhysicalSize};
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::{Font, FontArc, ScaleFont};
+use wgpu_glyph::{
+  GlyphBrush, GlyphPositioner, Layout, Section, SectionGeometry, Text,
+};
+use winit::dpi::PhysicalSize;
 use winit::event::VirtualKeyCode;
 
-#[derive(Debug)]
-pub struct Cursor {
-  pub rect: Rectangle,
-  pub row: usize,
-  pub column: usize,
-  pub x_offset: f32,
-}
-
-impl Cursor {
-  pub fn new(
-    device: &wgpu::Device,
-    screen_size: PhysicalSize<f32>,
-    dimensions: Dimensions,
-    color: [f32; 3],
-    region: Option<Region>,
-  ) -> Self {
-    Self {
-      rect: Rectangle::new(device, screen_size, dimensions, color, region),
-      row: 0,
-      column: 0,
-      x_offset: 0.0,
-    }
-  }
-}
-
 pub trait TextInput {
   fn input_special(
     &mut self,
@@ -40,71 +26,318 @@ pub trait TextInput {
   fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char);
 }
 
+/// The message `key` sends to an editor, if any.
+pub fn key_message(key: VirtualKeyCode) -> Option<Message> {
+  let direction = match key {
+    VirtualKeyCode::Up => Direction::Up,
+    VirtualKeyCode::Down => Direction::Down,
+    VirtualKeyCode::Left => Direction::Left,
+    VirtualKeyCode::Right => Direction::Right,
+    VirtualKeyCode::Escape => return Some(Message::ClearCursors),
+    _ => return None,
+  };
+  Some(Message::Move(direction))
+}
+
+/// The message typing `ch` sends to an editor.
+pub fn char_message(ch: char) -> Message {
+  match ch {
+    '\u{7f}' => Message::Backspace,
+    '\r' => Message::Newline,
+    _ => Message::Insert(ch),
+  }
+}
+
+/// Width of the cursor of a text area.
+const CURSOR_WIDTH: f32 = 2.0;
+
+fn editor_state(text: &str) -> EditorState {
+  let lines = text.split('\n').map(String::from).collect();
+  EditorState::new(Rc::new(RefCell::new(lines)))
+}
+
+/// Editable text in a box, a line or several, for the prompts asking for
+/// names, paths and queries. Text which doesn't fit is scrolled to follow
+/// the cursor.
 pub struct TextArea {
-  cursor: Cursor,
   font: FontArc,
   font_height: f32,
-  max_line_length: f32,
-  text: Vec<String>,
-  _multiline: Option<f32>,
+  theme: Theme,
+  state: EditorState,
+  multiline: bool,
+  /// Shown dimmed while there is no text.
+  placeholder: String,
+  dimensions: Dimensions,
+  /// Offsets of the text, negative as it scrolls left and up.
+  scroll: (f64, f64),
+  cursor: Rectangle,
+  /// The selection on its first line, the lines in between and its last
+  /// line.
+  selection: [Rectangle; 3],
 }
 
 impl TextArea {
-  pub fn _new(
-    font: FontArc,
-    text: String,
-    font_height: f32,
+  pub fn new(
     device: &wgpu::Device,
     screen_size: PhysicalSize<f32>,
-    multiline: Option<f32>,
+    font: FontArc,
+    font_height: f32,
+    theme: Theme,
+    multiline: bool,
   ) -> Self {
-    let mut split_text =
-      text.lines().map(|s| s.to_string()).collect::<Vec<String>>();
-    if multiline.is_some() && text.ends_with('\n') {
-      split_text.push(String::from(""));
+    let rect = |color: Color| {
+      Rectangle::new(
+        device,
+        screen_size,
+        Dimensions::default(),
+        color.rgb(),
+        None,
+      )
+    };
+    Self {
+      font,
+      font_height,
+      theme,
+      state: editor_state(""),
+      multiline,
+      placeholder: String::new(),
+      dimensions: Dimensions::default(),
+      scroll: (0.0, 0.0),
+      cursor: rect(theme.cursor),
+      selection: [
+        rect(theme.selection),
+        rect(theme.selection),
+        rect(theme.selection),
+      ],
     }
+  }
+
+  pub fn text(&self) -> String {
+    self.state.text.borrow().join("\n")
+  }
+
+  /// Replaces the text, with the cursor at its end and nothing to undo.
+  pub fn set_text(&mut self, screen_size: PhysicalSize<f32>, text: &str) {
+    self.state = if self.multiline {
+      editor_state(text)
+    } else {
+      editor_state(&text.replace('\n', " "))
+    };
+    self.state.update(Message::MoveTo((usize::MAX, usize::MAX)));
+    self.scroll = (0.0, 0.0);
+    self.layout(screen_size);
+  }
 
-    if multiline.is_none() {
-      assert_eq!(split_text.len(), 1);
+  pub fn set_placeholder(&mut self, placeholder: &str) {
+    self.placeholder = placeholder.to_string();
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.layout(screen_size);
+  }
+
+  pub fn selected_text(&self) -> Option<String> {
+    self.state.selected_text()
+  }
+
+  /// Removes the selected text, returning it.
+  pub fn cut(&mut self, screen_size: PhysicalSize<f32>) -> Option<String> {
+    let text = self.state.selected_text()?;
+    self.update(screen_size, Message::Backspace);
+    Some(text)
+  }
+
+  /// Inserts `text` in place of the selection, its lines joined by spaces
+  /// if only one line can be typed.
+  pub fn paste(&mut self, screen_size: PhysicalSize<f32>, text: &str) {
+    let text = text.replace("\r\n", "\n");
+    let text = if self.multiline {
+      text
+    } else {
+      text.replace('\n', " ")
+    };
+    let message = Message::Paste {
+      text,
+      continuing: false,
+    };
+    self.update(screen_size, message);
+  }
+
+  /// Applies `message` to the text and scrolls the cursor into view.
+  pub fn update(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    message: Message,
+  ) -> Change {
+    let change = self.state.update(message);
+    if change != Change::Nothing {
+      self.layout(screen_size);
     }
+    change
+  }
 
-    // TODO: bounding rect
+  /// Distance of the grapheme `column` of `row` from the start of the line.
+  fn x(&self, (row, column): Position) -> f32 {
+    let scaled = self.font.as_scaled(self.font_height);
+    self.state.text.borrow()[row]
+      .graphemes(true)
+      .take(column)
+      .flat_map(str::chars)
+      .map(|ch| scaled.h_advance(self.font.glyph_id(ch)))
+      .sum()
+  }
 
-    let cursor = Cursor::new(
-      device,
+  /// Where the start of the text is on the screen.
+  fn origin(&self) -> (f32, f32) {
+    (
+      self.dimensions.x + self.scroll.0 as f32,
+      self.dimensions.y + self.scroll.1 as f32,
+    )
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let rows = self.state.text.borrow().len();
+    let longest = (0..rows)
+      .map(|row| self.x((row, usize::MAX)))
+      .fold(0.0, f32::max);
+    let cursor = self.state.cursor();
+    let (x, y) = (self.x(cursor), cursor.0 as f32 * self.font_height);
+    let Dimensions { width, height, .. } = self.dimensions;
+    let scroll_x =
+      viewport::revealing(self.scroll.0, x, x + CURSOR_WIDTH, width);
+    let scroll_y =
+      viewport::revealing(self.scroll.1, y, y + self.font_height, height);
+    let limit_y = viewport::vertical_limit(
+      rows,
+      self.font_height,
+      height,
+      Overscroll::None,
+    );
+    self.scroll = (
+      viewport::clamp(
+        scroll_x,
+        viewport::horizontal_limit(longest + CURSOR_WIDTH, width),
+      ),
+      viewport::clamp(scroll_y, limit_y),
+    );
+
+    let (left, top) = self.origin();
+    self.cursor.resize(
       screen_size,
       Dimensions {
-        x: 0.0,
-        y: screen_size.height as f32 - font_height,
-        width: 1.0,
-        height: font_height,
+        x: left + x,
+        y: top + y,
+        width: CURSOR_WIDTH,
+        height: self.font_height,
       },
-      [0.7, 0.0, 0.0],
-      Some(Region {
-        x: 0,
-        y: 0,
-        width: screen_size.width as u32,
-        height: screen_size.height as u32,
-      }),
     );
 
-    let max_line_length =
-      max_line_length(&split_text, font.clone(), font_height);
-
-    Self {
-      text: split_text,
-      cursor,
-      font,
-      font_height,
-      max_line_length,
-      _multiline: multiline,
+    let row = |row: usize| top + row as f32 * self.font_height;
+    let line = |x: f32, y: f32, width: f32| Dimensions {
+      x,
+      y,
+      width: width.max(0.0),
+      height: self.font_height,
+    };
+    let selection = match self.state.selection() {
+      None => [Dimensions::default(); 3],
+      Some(Span { start, end }) => {
+        let (from, to) = (left + self.x(start), left + self.x(end));
+        if start.0 == end.0 {
+          [
+            line(from, row(start.0), to - from),
+            Dimensions::default(),
+            Dimensions::default(),
+          ]
+        } else {
+          let right = self.dimensions.x + width;
+          [
+            line(from, row(start.0), right - from),
+            Dimensions {
+              height: (end.0 - start.0 - 1) as f32 * self.font_height,
+              ..line(self.dimensions.x, row(start.0 + 1), width)
+            },
+            line(self.dimensions.x, row(end.0), to - self.dimensions.x),
+          ]
+        }
+      }
+    };
+    for (rect, dimensions) in self.selection.iter_mut().zip(selection.iter()) {
+      rect.resize(screen_size, *dimensions);
+    }
+    for rect in self.selection.iter_mut().chain(Some(&mut self.cursor)) {
+      rect.region = Some(self.dimensions.into());
     }
   }
 }
 
 impl super::RenderElement for TextArea {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.layout(screen_size);
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.cursor.set_color(theme.cursor.rgb());
+    for rect in &mut self.selection {
+      rect.set_color(theme.selection.rgb());
+    }
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let (left, top) = self.origin();
+    let text = self.state.text.borrow();
+    if text.len() == 1 && text[0].is_empty() {
+      glyph_brush.queue(Section {
+        screen_position: (left, top),
+        text: vec![Text::new(&self.placeholder)
+          .with_color(self.theme.gutter_text.rgba())
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+    for (row, line) in text.iter().enumerate() {
+      glyph_brush.queue(Section {
+        screen_position: (left, top + row as f32 * self.font_height),
+        text: vec![Text::new(line)
+          .with_color(self.theme.overlay_text.rgba())
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
   fn get_rects(&self) -> Vec<&Rectangle> {
-    vec![&self.cursor.rect]
+    let mut rects = self.selection.iter().collect::<Vec<_>>();
+    rects.push(&self.cursor);
+    rects
   }
 
   fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
@@ -112,7 +345,7 @@ impl super::RenderElement for TextArea {
   }
 
   fn get_dimensions(&self) -> Dimensions {
-    todo!()
+    self.dimensions
   }
 }
 
@@ -122,29 +355,30 @@ impl TextInput for TextArea {
     screen_size: PhysicalSize<f32>,
     key: VirtualKeyCode,
   ) {
-    input_special(
-      screen_size,
-      key,
-      &mut self.text,
-      &mut self.cursor,
-      self.font.clone(),
-      self.font_height,
-      PhysicalPosition { x: 0.0, y: 0.0 },
-      PhysicalPosition { x: 0.0, y: 0.0 },
-    );
+    let (row, _) = self.state.cursor();
+    let position = match key {
+      VirtualKeyCode::Home => (row, 0),
+      VirtualKeyCode::End => (row, usize::MAX),
+      key => {
+        if let Some(message) = key_message(key) {
+          self.update(screen_size, message);
+        }
+        return;
+      }
+    };
+    self.state.update(Message::ClearSelection);
+    self.update(screen_size, Message::MoveTo(position));
   }
 
   fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char) {
-    self.max_line_length = input_char(
-      screen_size,
-      ch,
-      &mut self.text,
-      &mut self.cursor,
-      self.font.clone(),
-      self.font_height,
-      PhysicalPosition { x: 0.0, y: 0.0 },
-      PhysicalPosition { x: 0.0, y: 0.0 },
-    );
+    let message = match ch {
+      '\u{8}' => Message::Backspace,
+      '\r' if self.multiline => Message::Newline,
+      '\u{7f}' => char_message(ch),
+      _ if ch.is_control() => return,
+      _ => char_message(ch),
+    };
+    self.update(screen_size, message);
   }
 }
 
@@ -167,14 +401,22 @@ pub fn line_length(line: &str, font: FontArc, font_height: f32) -> f32 {
   }
 }
 
+/// Width of the widest of `lines`. Those too long to lay out in one go are
+/// estimated from their characters and the width of a space.
 pub fn max_line_length(
   lines: &[String],
   font: FontArc,
   font_height: f32,
 ) -> f32 {
+  let _span = tracing::trace_span!("layout", lines = lines.len()).entered();
+  let space = font.as_scaled(font_height).h_advance(font.glyph_id(' '));
   let mut max_line_width = 0.0;
   for line in lines {
-    let width = line_length(line, font.clone(), font_height);
+    let width = if line.len() > LONG_LINE {
+      line.chars().count() as f32 * space
+    } else {
+      line_length(line, font.clone(), font_height)
+    };
 
     if width > max_line_width {
       max_line_width = width;
@@ -183,198 +425,3 @@ pub fn max_line_length(
 
   max_line_width
 }
-
-pub fn cursor_x_position(
-  row: usize,
-  column: usize,
-  text: &[String],
-  font: FontArc,
-  font_height: f32,
-  offset: PhysicalPosition<f32>,
-) -> Option<f32> {
-  let text = Text::new(&text[row]).with_scale(font_height);
-  let layout = Layout::default_wrap();
-
-  let section_glyphs = layout.calculate_glyphs(
-    &[font.clone()],
-    &SectionGeometry {
-      screen_position: (offset.x, offset.y),
-      ..Default::default()
-    },
-    &[text],
-  );
-
-  if let Some(section_glyph) = section_glyphs.get(column) {
-    Some(section_glyph.glyph.position.x)
-  } else if column != 0 {
-    section_glyphs.get(column - 1).map(|section_glyph| {
-      section_glyph.glyph.position.x
-        + font.glyph_bounds(&section_glyph.glyph).width()
-    })
-  } else {
-    None
-  }
-}
-
-#[allow(clippy::too_many_arguments)]
-pub fn input_special(
-  screen_size: PhysicalSize<f32>,
-  key: VirtualKeyCode,
-  text: &mut Vec<String>,
-  cursor: &mut Cursor,
-  font: FontArc,
-  font_height: f32,
-  offset: PhysicalPosition<f32>,
-  scroll_offset: PhysicalPosition<f32>,
-) {
-  let cursor_x_position2 = |row: usize, column: usize| {
-    cursor_x_position(
-      row,
-      column,
-      text,
-      font.clone(),
-      font_height,
-      scroll_offset,
-    )
-  };
-
-  match key {
-    VirtualKeyCode::Up => {
-      if cursor.row != 0 {
-        cursor.row -= 1;
-        if let Some(offset) = cursor_x_position2(cursor.row, cursor.column) {
-          cursor.x_offset = offset;
-        } else {
-          cursor.column = text[cursor.row].len();
-          cursor.x_offset =
-            cursor_x_position2(cursor.row, cursor.column).unwrap_or(0.0);
-        }
-      } else {
-        cursor.x_offset = 0.0;
-        cursor.column = 0;
-      }
-    }
-    VirtualKeyCode::Left => {
-      if cursor.column != 0 {
-        cursor.column -= 1;
-        cursor.x_offset =
-          cursor_x_position2(cursor.row, cursor.column).unwrap();
-      } else if cursor.row != 0 {
-        cursor.row -= 1;
-        cursor.column = text[cursor.row].len();
-        cursor.x_offset =
-          cursor_x_position2(cursor.row, cursor.column).unwrap_or(0.0);
-      }
-    }
-    VirtualKeyCode::Down => {
-      if cursor.row != (text.len() - 1) {
-        cursor.row += 1;
-        if let Some(offset) = cursor_x_position2(cursor.row, cursor.column) {
-          cursor.x_offset = offset;
-        } else {
-          cursor.column = text[cursor.row].len();
-          cursor.x_offset =
-            cursor_x_position2(cursor.row, cursor.column).unwrap_or(0.0);
-        }
-      } else {
-        cursor.column = text[cursor.row].len();
-        cursor.x_offset =
-          cursor_x_position2(cursor.row, cursor.column).unwrap_or(0.0);
-      }
-    }
-    VirtualKeyCode::Right => {
-      if cursor.row != (text.len() - 1) {
-        if let Some(offset) = cursor_x_position2(cursor.row, cursor.column + 1)
-        {
-          cursor.column += 1;
-          cursor.x_offset = offset;
-        } else {
-          cursor.x_offset = 0.0;
-          cursor.column = 0;
-          cursor.row += 1;
-        }
-      } else if let Some(offset) =
-        cursor_x_position2(cursor.row, cursor.column + 1)
-      {
-        cursor.column += 1;
-        cursor.x_offset = offset;
-      }
-    }
-    _ => return,
-  }
-
-  cursor.rect.resize(
-    screen_size,
-    Dimensions {
-      x: offset.x + scroll_offset.x + cursor.x_offset,
-      y: scroll_offset.y + font_height + (cursor.row as f32 * font_height),
-      ..cursor.rect.dimensions
-    },
-  );
-}
-
-#[allow(clippy::too_many_arguments)]
-pub fn input_char(
-  screen_size: PhysicalSize<f32>,
-  ch: char,
-  text: &mut Vec<String>,
-  cursor: &mut Cursor,
-  font: FontArc,
-  font_height: f32,
-  offset: PhysicalPosition<f32>,
-  scroll_offset: PhysicalPosition<f32>,
-) -> f32 {
-  let input_spc =
-    |key: VirtualKeyCode, text: &mut Vec<String>, cursor: &mut Cursor| {
-      input_special(
-        screen_size,
-        key,
-        text,
-        cursor,
-        font.clone(),
-        font_height,
-        offset,
-        scroll_offset,
-      );
-    };
-
-  match ch {
-    // backspace
-    '\u{7f}' => {
-      if cursor.column != 0 {
-        let mut graphemes_indices = text[cursor.row].grapheme_indices(true);
-        let index = graphemes_indices.nth(cursor.column - 1).unwrap().0;
-        text[cursor.row].remove(index);
-        input_spc(VirtualKeyCode::Left, text, cursor);
-      } else if cursor.row != 0 {
-        let removed = text.remove(cursor.row);
-        cursor.row -= 1;
-        cursor.column = text[cursor.row].len() + 1;
-        text[cursor.row] += &removed;
-        input_spc(VirtualKeyCode::Left, text, cursor);
-      }
-    }
-    // enter
-    '\r' => {
-      let mut graphemes_indices = text[cursor.row].grapheme_indices(true);
-      let index = graphemes_indices
-        .nth(cursor.column)
-        .map(|(i, _)| i)
-        .unwrap_or_else(|| text[cursor.row].len());
-      let after_enter = text[cursor.row].split_off(index);
-      text.insert(cursor.row + 1, after_enter);
-      input_spc(VirtualKeyCode::Right, text, cursor);
-    }
-    _ => {
-      let mut graphemes_indices = text[cursor.row].grapheme_indices(true);
-      let index = graphemes_indices
-        .nth(cursor.column)
-        .map(|(i, _)| i)
-        .unwrap_or_else(|| text[cursor.row].len());
-      text[cursor.row].insert(index, ch);
-      input_spc(VirtualKeyCode::Right, text, cursor);
-    }
-  }
-
-  max_line_length(&text, font, font_height)
-}
@@ -0,0 +1,103 @@
+use super::activity_bar::SidebarView;
+use super::Panel;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The arrangement of the panels, without anything they show.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LayoutPreset {
+  pub sidebar_width: f32,
+  #[serde(default = "default_sidebar_visible")]
+  pub sidebar_visible: bool,
+  #[serde(default = "default_sidebar_view")]
+  pub sidebar_view: SidebarView,
+  #[serde(default)]
+  pub maximized: Option<Panel>,
+}
+
+fn default_sidebar_visible() -> bool {
+  true
+}
+
+fn default_sidebar_view() -> SidebarView {
+  SidebarView::Explorer
+}
+
+/// Named layouts of a workspace, stored in `.devcode/layouts.toml`.
+pub struct LayoutPresets {
+  path: PathBuf,
+  presets: BTreeMap<String, LayoutPreset>,
+}
+
+impl LayoutPresets {
+  pub fn load(workspace: &Path) -> Self {
+    let path = workspace.join(".devcode").join("layouts.toml");
+    let presets = match std::fs::read_to_string(&path) {
+      Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+        tracing::warn!("failed to parse {}: {}", path.display(), err);
+        BTreeMap::new()
+      }),
+      Err(_) => BTreeMap::new(),
+    };
+    Self { path, presets }
+  }
+
+  pub fn names(&self) -> Vec<String> {
+    self.presets.keys().cloned().collect()
+  }
+
+  pub fn get(&self, name: &str) -> Option<LayoutPreset> {
+    self.presets.get(name).copied()
+  }
+
+  /// Adds or replaces a preset and writes all presets to disk.
+  pub fn save(
+    &mut self,
+    name: String,
+    preset: LayoutPreset,
+  ) -> Result<(), anyhow::Error> {
+    self.presets.insert(name, preset);
+    if let Some(dir) = self.path.parent() {
+      std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&self.path, toml::to_string(&self.presets)?)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn save_and_load() {
+    let workspace = std::env::temp_dir()
+      .join(format!("devcode-layouts-{}", std::process::id()));
+    let mut presets = LayoutPresets::load(&workspace);
+    assert!(presets.names().is_empty());
+
+    let writing = LayoutPreset {
+      sidebar_width: 0.0,
+      sidebar_visible: false,
+      sidebar_view: SidebarView::Explorer,
+      maximized: Some(Panel::Editor),
+    };
+    let debugging = LayoutPreset {
+      sidebar_width: 300.0,
+      sidebar_visible: true,
+      sidebar_view: SidebarView::Debug,
+      maximized: None,
+    };
+    presets.save("writing".to_string(), writing).unwrap();
+    presets.save("debugging".to_string(), debugging).unwrap();
+
+    let presets = LayoutPresets::load(&workspace);
+    assert_eq!(presets.names(), vec!["debugging", "writing"]);
+    assert_eq!(presets.get("writing"), Some(writing));
+    assert_eq!(presets.get("debugging"), Some(debugging));
+
+    std::fs::remove_dir_all(workspace).unwrap();
+  }
+}
@@ -1,19 +1,245 @@
+mod activity_bar;
+mod buffer_switcher;
 mod code_view;
 mod code_view_tabs;
+mod color_picker;
+mod command_palette;
+mod completion_popup;
+mod debug_overlay;
+mod dialog;
 mod fs_tree;
+mod glyph_pages;
 pub mod input;
+mod layout_presets;
+pub mod notifications;
+mod outline_view;
+mod output_panel;
+mod placeholder;
+mod plugin_panel;
 mod rectangle;
+mod rename_preview;
+mod status_bar;
+mod welcome;
 
+use crate::cli::FileArg;
+use crate::code_actions::{self, CodeAction};
+use crate::colors::{self, ColorLiteral};
+use crate::commands::Command;
+use crate::completion::{self, Item};
+use crate::config::{AutoSave, Config, Theme};
+use crate::documents::{Document, Documents};
+use crate::editor::{Direction, Message};
+use crate::grammars;
+use crate::highlight::Grammar;
+use crate::keyboard::Layout;
+use crate::keymap::{Key, Keymap};
+use crate::lsp::{self, LanguageServers, Notify};
+use crate::markers::{self, Marker, MarkerKind, Target};
+use crate::momentum::Momentum;
+use crate::outline;
+use crate::pinch::Pinch;
+use crate::plugins::{self, PluginHost};
+use crate::power;
+use crate::processes::{self, Processes};
+use crate::recent::{self, Recent};
+use crate::recovery::Recovery;
+use crate::rename::{self, FileEdits};
+use crate::scm;
+use crate::search;
+use crate::session::Session;
+use crate::shaping::Ligatures;
+use crate::tasks::{self, Location, Task};
+use crate::text;
+use crate::transform::Transform;
+use activity_bar::{SidebarView, ACTIVITY_BAR_WIDTH};
+use buffer_switcher::{SwitcherAction, SymbolEntry};
+use code_view::CodeView;
+use color_picker::PickerAction;
+use command_palette::PaletteAction;
 use futures::task::SpawnExt;
-use std::path::PathBuf;
+use input::TextInput;
+use layout_presets::{LayoutPreset, LayoutPresets};
+use notifications::Level;
+use plugin_panel::PluginAction;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use status_bar::{Alignment, Segment};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+use welcome::WelcomeAction;
 use wgpu::util::StagingBelt;
 use wgpu::{CommandEncoder, Device, TextureView};
-use wgpu_glyph::ab_glyph::Font;
+use wgpu_glyph::ab_glyph::{Font, FontArc};
 use wgpu_glyph::GlyphBrush;
 use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::event::ElementState;
+use winit::event::{ElementState, Touch, TouchPhase, VirtualKeyCode};
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::{CursorIcon, Fullscreen, UserAttentionType};
 
-const RENDER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+/// The format of the surface, an sRGB one blends in linear space.
+fn render_format() -> wgpu::TextureFormat {
+  if crate::config::gamma_correct() {
+    wgpu::TextureFormat::Bgra8UnormSrgb
+  } else {
+    wgpu::TextureFormat::Bgra8Unorm
+  }
+}
+
+/// Share of the editor's height taken by the output panel.
+const OUTPUT_PANEL_HEIGHT: f32 = 0.3;
+
+/// Pixels a panel grows or shrinks by per keypress while resizing.
+const PANEL_RESIZE_STEP: f32 = 20.0;
+const MIN_PANEL_WIDTH: f32 = 50.0;
+
+/// How long saving waits for a language server to format the file.
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long the cursor has to rest before the code actions for it are
+/// looked for.
+const LIGHTBULB_DELAY: Duration = Duration::from_millis(500);
+/// How much a line of ctrl+scrolling scales the font by.
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 4.0;
+
+/// Number of glyph brush draws issued during the current frame.
+static TEXT_DRAWS: AtomicUsize = AtomicUsize::new(0);
+
+/// A rename request waiting for the language server to answer.
+struct PendingRename {
+  server: String,
+  id: u64,
+  name: String,
+  /// The file renamed in and the version of it the server was sent.
+  path: PathBuf,
+  version: usize,
+}
+
+/// What code actions were asked for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum CodeActionRequest {
+  /// Whether there are any, to show the lightbulb.
+  Lightbulb,
+  /// To pick one from a menu at the cursor.
+  Menu,
+  /// To apply the first one organizing the imports.
+  OrganizeImports,
+  /// The edit of an action picked.
+  Resolve,
+}
+
+/// A code action request waiting for the language server to answer.
+struct PendingCodeActions {
+  server: String,
+  id: u64,
+  request: CodeActionRequest,
+  /// The tab, the version of its text and the cursor position the actions
+  /// are for, they don't fit anymore once they changed.
+  revision: (usize, usize),
+  cursor: (usize, usize),
+}
+
+/// A formatting request to save the tab with the id `id` after.
+struct PendingFormat {
+  server: String,
+  request: u64,
+  /// The tab is saved as it is if it changed before the server answered.
+  revision: (usize, usize),
+  /// When to give up on the server and save without formatting.
+  deadline: Instant,
+}
+
+/// What to show in a tab opened at startup.
+pub enum Buffer {
+  File {
+    path: PathBuf,
+    /// Zero based row and column to put the cursor at.
+    position: Option<(usize, usize)>,
+  },
+  Unsaved {
+    name: String,
+    text: String,
+  },
+  /// A unified diff, its changed lines and words highlighted.
+  Diff {
+    name: String,
+    text: String,
+  },
+  /// A tab moved from another window, sharing its text.
+  Moved {
+    name: String,
+    path: Option<PathBuf>,
+    document: Rc<Document>,
+    /// Zero based row and column of the cursor.
+    position: (usize, usize),
+    bookmarks: Vec<usize>,
+  },
+}
+
+/// The panels which can be focused and resized.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Panel {
+  Sidebar,
+  Editor,
+}
+
+/// What a dialog asked the user to confirm.
+#[derive(Clone, Debug, PartialEq)]
+enum Confirm {
+  CloseTab,
+  Quit,
+  /// Downloading a grammar for files with the extension.
+  DownloadGrammar(String),
+  /// Restoring the backups of a run which didn't exit cleanly.
+  Restore,
+}
+
+/// Height in pixels of a line of text at `font_size` points.
+fn font_height(font: &FontArc, font_size: f32, scale_factor: f32) -> f32 {
+  let px_per_em = (font_size / 72.0) * (96.0 * scale_factor);
+  let units_per_em = font.units_per_em().unwrap();
+  let height = font.height_unscaled();
+  let scale = (px_per_em / units_per_em) * height;
+
+  font
+    .glyph_bounds(&font.glyph_id('0').with_scale(scale))
+    .height()
+}
+
+/// Sends the text of `code_view`, the file at `path`, to `server`, which
+/// would only see the saved file otherwise.
+fn open_document(
+  server: &lsp::LanguageServer,
+  code_view: &CodeView,
+  path: &Path,
+) -> Result<(), anyhow::Error> {
+  let language = code_view
+    .language()
+    .or_else(|| path.extension().and_then(|ext| ext.to_str()));
+  server.notification(
+    "textDocument/didOpen",
+    json!({
+      "textDocument": {
+        "uri": lsp::path_to_uri(path),
+        "languageId": language,
+        "version": code_view.revision().1,
+        "text": code_view.text().join("\n"),
+      },
+    }),
+  )
+}
+
+/// 20% of the window for the file tree
+fn default_sidebar_width(screen_width: f32) -> f32 {
+  (screen_width / 100.0) * 20.0
+}
 
 pub struct Renderer {
   pub window: winit::window::Window,
@@ -25,21 +251,173 @@ pub struct Renderer {
   staging_belt: wgpu::util::StagingBelt,
   local_spawner: futures::executor::LocalSpawner,
   local_pool: futures::executor::LocalPool,
-  glyph_brush: wgpu_glyph::GlyphBrush<()>,
+  glyph_pages: glyph_pages::GlyphPages,
   rectangle_render_pipeline: wgpu::RenderPipeline,
+  activity_bar: activity_bar::ActivityBar,
   fs_tree: fs_tree::FsTree,
+  outline: outline_view::OutlineView,
+  /// Shown in the sidebar for views which aren't implemented.
+  placeholder: placeholder::Placeholder,
+  /// Shown in place of the code while no tab is open.
+  welcome: welcome::Welcome,
+  /// Whether the start screen was shown last frame, its entries are
+  /// refreshed when it's shown again.
+  welcome_shown: bool,
+  plugin_panel: plugin_panel::PluginPanel,
+  plugins: PluginHost,
+  /// The number of changed files git is asked for in the background, see
+  /// [`Renderer::count_changes`].
+  pending_changes: Option<Receiver<Result<usize, anyhow::Error>>>,
+  /// Plugins installed in the background, see [`Renderer::install_plugin`].
+  pending_installs: Vec<Receiver<Result<String, anyhow::Error>>>,
+  sidebar_visible: bool,
+  sidebar_width: f32,
+  focused: Panel,
+  /// The panel filling the whole window, if any.
+  maximized: Option<Panel>,
+  /// Whether only the code is shown, centered in the window. The panels
+  /// keep their state to be shown as they were when it ends.
+  zen: bool,
+  /// Whether the arrows resize the focused panel, until another key, a
+  /// click or losing focus ends it.
+  resize_mode: bool,
+  layout_presets: LayoutPresets,
+  language_servers: LanguageServers,
+  /// The language servers and tasks started for the window.
+  processes: Processes,
+  /// Called when the status of a language server or task changed.
+  notify: Notify,
+  workspace: PathBuf,
+  /// The session as this window last loaded or saved it, to tell its
+  /// changes from those of other windows of the workspace.
+  session: Session,
+  recovery: Recovery,
+  /// The tabs waiting to be saved automatically with their versions.
+  auto_save_pending: Vec<(usize, usize)>,
+  /// When to save them, reset whenever they change.
+  auto_save_deadline: Option<Instant>,
+  status_bar: status_bar::StatusBar,
+  output_panel: output_panel::OutputPanel,
+  /// Name of the task run last, run again by `Command::RunLastTask`.
+  last_task: Option<String>,
+  /// The text whose occurrences are gone through by `Command::NextMatch`,
+  /// found in all files or selected when it was run.
+  search: Option<String>,
+  command_palette: command_palette::CommandPalette,
+  buffer_switcher: buffer_switcher::BufferSwitcher,
+  completion: completion_popup::CompletionPopup,
+  /// When to show completions for what was typed last.
+  completion_deadline: Option<Instant>,
+  /// Keeps scrolling after a touchpad gesture, at the position it ended.
+  momentum: Momentum,
+  momentum_position: PhysicalPosition<f64>,
+  pinch: Pinch,
+  /// Scale of the configured font size, changed by pinching or
+  /// ctrl+scrolling.
+  zoom: f32,
+  /// Physical pixels per logical pixel of the monitor the window is on.
+  scale_factor: f32,
+  /// Whether typing replaces the character under the cursor.
+  overwrite: bool,
+  /// When the first input not drawn yet was received. The window is drawn
+  /// as soon as the inputs waiting are handled, not when the system gets
+  /// around to asking for it.
+  input_at: Option<Instant>,
+  /// Whether the window has the keyboard focus. Nothing is drawn
+  /// continuously while it doesn't.
+  has_focus: bool,
+  /// Whether the window is minimized, which is reported as a size of
+  /// zero. Nothing is drawn then.
+  hidden: bool,
+  /// Whether the computer ran on battery when last checked, at startup and
+  /// whenever the window gets focused.
+  on_battery: bool,
+  last_frame: Option<Instant>,
+  /// When the frame held back by the frame rate cap on battery is due.
+  frame_deadline: Option<Instant>,
+  /// Whether the window was drawn for input after the events were last
+  /// handled, making the redraw the system asks for then redundant.
+  drawn_for_input: bool,
+  /// Whether the active tab scrolls along with the active tabs of the
+  /// other windows which have it on too.
+  scroll_lock: bool,
+  /// The document of the active tab and how many lines it was scrolled
+  /// down when last checked, to tell scrolling from switching tabs.
+  locked_scroll: Option<(Weak<Document>, f64)>,
+  rename_preview: rename_preview::RenamePreview,
+  pending_rename: Option<PendingRename>,
+  pending_format: Option<PendingFormat>,
+  pending_code_actions: Option<PendingCodeActions>,
+  /// The code actions listed in the completion popup, and the server
+  /// offering them.
+  code_action_menu: Option<(String, Vec<CodeAction>)>,
+  /// The tab revision and cursor position the lightbulb was last looked
+  /// for at, and when to look for it once the cursor rests.
+  lightbulb_at: Option<((usize, usize), (usize, usize))>,
+  lightbulb_deadline: Option<Instant>,
+  /// The query last sent to the language servers for the symbols of the
+  /// workspace and the requests they haven't answered yet.
+  workspace_symbol_query: Option<String>,
+  pending_workspace_symbols: Vec<(String, u64)>,
+  debug_overlay: debug_overlay::DebugOverlay,
+  color_picker: color_picker::ColorPicker,
+  /// The line of the active tab and the literal the color picker edits.
+  picking: Option<(usize, ColorLiteral)>,
+  dialog: dialog::Dialog,
+  /// What the open dialog asks about.
+  confirm: Option<Confirm>,
+  /// Set once the window should close, checked by the event loop.
+  pub quit: bool,
+  /// Tabs to open in a new window, taken by the event loop.
+  pub new_window: Option<Vec<Buffer>>,
+  /// Whether a tab is held, it's moved to a new window when dropped outside
+  /// of this one.
+  dragging_tab: bool,
+  /// The icon of the mouse cursor over the window.
+  cursor_icon: CursorIcon,
+  /// Extensions to not offer downloading a grammar for again.
+  declined_grammars: HashSet<String>,
+  /// The extension a grammar is being downloaded for, with the download.
+  pending_grammar: Option<(String, Receiver<Result<Grammar, anyhow::Error>>)>,
+  notifications: notifications::Notifications,
+  /// Language servers known to have crashed, to notify about each crash once.
+  crashed_servers: HashSet<String>,
+  clipboard: Option<arboard::Clipboard>,
+  font: FontArc,
+  /// The ligatures of the font, drawn unless turned off in the config.
+  ligatures: Rc<Ligatures>,
+  /// The open files, shared with the other windows.
+  documents: Documents,
+  /// The config as loaded, before plugins added to it.
+  user_config: Config,
+  config: Config,
+  pub keymap: Keymap,
   pub font_height: f32,
   pub code_views: code_view_tabs::CodeViewTabs,
 }
 
 impl Renderer {
-  pub async fn new(
-    event_loop: &winit::event_loop::EventLoop<()>,
-    font: wgpu_glyph::ab_glyph::FontArc,
-    filepath: PathBuf,
+  /// Opens a window showing `buffers`. Unless it's the first one, `restore`
+  /// is false as the backups of a previous run were offered already.
+  #[allow(clippy::too_many_arguments)]
+  pub async fn new<T>(
+    event_loop: &EventLoopWindowTarget<T>,
+    font: FontArc,
+    ligatures: Rc<Ligatures>,
+    buffers: Vec<Buffer>,
+    workspace: PathBuf,
+    config: Config,
+    notify: Notify,
+    documents: Documents,
+    restore: bool,
   ) -> Result<Self, anyhow::Error> {
+    let plugins = PluginHost::load();
+    let user_config = config;
+    let config = plugins.apply(&user_config);
+
     let window = winit::window::WindowBuilder::new()
       .with_title(env!("CARGO_CRATE_NAME"))
+      .with_transparent(config.window.opacity() < 1.0)
       .build(event_loop)
       .unwrap();
     let instance = wgpu::Instance::new(wgpu::BackendBit::all());
@@ -66,32 +444,25 @@ impl Renderer {
       &surface,
       &wgpu::SwapChainDescriptor {
         usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
-        format: RENDER_FORMAT,
+        format: render_format(),
         width: size.width,
         height: size.height,
         present_mode: wgpu::PresentMode::Mailbox,
       },
     );
 
-    let px_per_em = (10.0 / 72.0) * (96.0 * window.scale_factor() as f32);
-    let units_per_em = font.units_per_em().unwrap();
-    let height = font.height_unscaled();
-    let scale = (px_per_em / units_per_em) * height;
-
-    let font_height = font
-      .glyph_bounds(&font.glyph_id('0').with_scale(scale))
-      .height();
+    let scale_factor = window.scale_factor() as f32;
+    let font_height = font_height(&font, config.font_size, scale_factor);
+    let theme = config.theme;
 
-    let glyph_brush = wgpu_glyph::GlyphBrushBuilder::using_font(font.clone())
-      .build(&device, RENDER_FORMAT);
+    let glyph_pages = glyph_pages::GlyphPages::new(&device, font.clone(), size);
 
-    // 20% for window for file tree
-    let tree_width = (size.width as f32 / 100.0) * 20.0;
+    let tree_width = default_sidebar_width(size.width as f32);
 
     let mut code_views = code_view_tabs::CodeViewTabs::new(
       &device,
       size.cast(),
-      font,
+      font.clone(),
       font_height,
       Dimensions {
         x: tree_width,
@@ -99,10 +470,63 @@ impl Renderer {
         width: size.width as f32 - tree_width,
         height: size.height as f32,
       },
+      &config,
+      documents.clone(),
     );
-    code_views.add(&device, size.cast(), filepath)?;
+    if config.ligatures {
+      code_views.set_ligatures(ligatures.clone());
+    }
+    let session = Session::load(&workspace);
+    code_views.restore_bookmarks(session.bookmarks.clone());
+    for buffer in buffers {
+      match buffer {
+        Buffer::File { path, position } => {
+          recent::remember(|recent| recent.add_file(&path));
+          code_views.add(&device, size.cast(), path)?;
+          if let Some((row, column)) = position {
+            code_views.goto(size.cast(), row, column);
+          }
+        }
+        Buffer::Unsaved { name, text } => {
+          code_views.add_unsaved(&device, size.cast(), name, text)
+        }
+        Buffer::Diff { name, text } => {
+          code_views.add_unsaved(&device, size.cast(), name, text);
+          if let Some(active) = code_views.active_view_mut() {
+            active.set_diff(true);
+          }
+        }
+        Buffer::Moved {
+          name,
+          path,
+          document,
+          position: (row, column),
+          bookmarks,
+        } => {
+          code_views.add_document(&device, size.cast(), name, document, path);
+          if let Some(active) = code_views.active_view_mut() {
+            active.set_bookmarks(bookmarks);
+          }
+          code_views.goto(size.cast(), row, column);
+        }
+      }
+    }
 
-    let path = std::path::Path::new("./").canonicalize()?;
+    let path = workspace;
+    let layout_presets = LayoutPresets::load(&path);
+    let processes = Processes::default();
+    let mut language_servers = LanguageServers::new(
+      config.language_servers.clone(),
+      path.clone(),
+      Arc::clone(&notify),
+      processes.clone(),
+    );
+    for (path, language) in code_views.languages() {
+      language_servers.start_for(&path, language.as_deref());
+    }
+    let workspace = path.clone();
+    recent::remember(|recent| recent.add_workspace(&workspace));
+    let recovery = Recovery::new(&workspace);
     let fs_tree = fs_tree::FsTree::new(
       &device,
       size.cast(),
@@ -114,10 +538,97 @@ impl Renderer {
         height: size.height as f32,
       },
       path,
+      theme,
+    );
+
+    let activity_bar = activity_bar::ActivityBar::new(
+      &device,
+      size.cast(),
+      font.clone(),
+      font_height,
+      theme,
+    );
+    let outline =
+      outline_view::OutlineView::new(&device, size.cast(), font_height, theme);
+    let placeholder =
+      placeholder::Placeholder::new(&device, size.cast(), font_height, theme);
+    let welcome = welcome::Welcome::new(
+      &device,
+      size.cast(),
+      font_height,
+      workspace.clone(),
+      theme,
+    );
+    let mut plugin_panel =
+      plugin_panel::PluginPanel::new(&device, size.cast(), font_height, theme);
+    plugin_panel.update(&plugins);
+
+    let status_bar = status_bar::StatusBar::new(
+      &device,
+      size.cast(),
+      font.clone(),
+      font_height,
+      theme,
+    );
+
+    let output_panel =
+      output_panel::OutputPanel::new(&device, size.cast(), font_height, theme);
+    let command_palette = command_palette::CommandPalette::new(
+      &device,
+      size.cast(),
+      font.clone(),
+      font_height,
+      theme,
+    );
+    let buffer_switcher = buffer_switcher::BufferSwitcher::new(
+      &device,
+      size.cast(),
+      font.clone(),
+      font_height,
+      workspace.clone(),
+      theme,
+    );
+    let notifications = notifications::Notifications::new(
+      size.cast(),
+      font.clone(),
+      font_height,
+      theme,
+    );
+    let completion = completion_popup::CompletionPopup::new(
+      &device,
+      size.cast(),
+      font.clone(),
+      font_height,
+      theme,
+    );
+    let rename_preview = rename_preview::RenamePreview::new(
+      &device,
+      size.cast(),
+      font_height,
+      theme,
+    );
+    let debug_overlay = debug_overlay::DebugOverlay::new(
+      &device,
+      size.cast(),
+      font_height,
+      theme,
+    );
+    let color_picker =
+      color_picker::ColorPicker::new(&device, size.cast(), font_height, theme);
+    let dialog = dialog::Dialog::new(
+      &device,
+      size.cast(),
+      font.clone(),
+      font_height,
+      theme,
     );
 
+    let clipboard = arboard::Clipboard::new()
+      .map_err(|err| tracing::warn!("clipboard unavailable: {}", err))
+      .ok();
+
     let rectangle_render_pipeline = rectangle::Rectangle::pipeline(&device);
-    Ok(Self {
+    let mut renderer = Self {
       window,
       size,
       surface,
@@ -127,184 +638,3624 @@ impl Renderer {
       staging_belt,
       local_spawner,
       local_pool,
-      glyph_brush,
+      glyph_pages,
       rectangle_render_pipeline,
+      activity_bar,
       fs_tree,
+      outline,
+      placeholder,
+      welcome,
+      welcome_shown: false,
+      plugin_panel,
+      plugins,
+      pending_changes: None,
+      pending_installs: vec![],
+      sidebar_visible: true,
+      sidebar_width: tree_width,
+      focused: Panel::Editor,
+      maximized: None,
+      zen: false,
+      resize_mode: false,
+      layout_presets,
+      language_servers,
+      processes,
+      notify,
+      workspace,
+      session,
+      recovery,
+      auto_save_pending: vec![],
+      auto_save_deadline: None,
+      status_bar,
+      output_panel,
+      last_task: None,
+      search: None,
+      command_palette,
+      buffer_switcher,
+      completion,
+      completion_deadline: None,
+      momentum: Momentum::default(),
+      momentum_position: PhysicalPosition::new(0.0, 0.0),
+      pinch: Pinch::default(),
+      zoom: 1.0,
+      scale_factor,
+      overwrite: false,
+      input_at: None,
+      drawn_for_input: false,
+      has_focus: true,
+      hidden: false,
+      on_battery: power::on_battery(),
+      last_frame: None,
+      frame_deadline: None,
+      scroll_lock: false,
+      locked_scroll: None,
+      rename_preview,
+      pending_rename: None,
+      pending_format: None,
+      pending_code_actions: None,
+      code_action_menu: None,
+      lightbulb_at: None,
+      lightbulb_deadline: None,
+      workspace_symbol_query: None,
+      pending_workspace_symbols: vec![],
+      debug_overlay,
+      color_picker,
+      picking: None,
+      dialog,
+      confirm: None,
+      quit: false,
+      new_window: None,
+      dragging_tab: false,
+      cursor_icon: CursorIcon::Default,
+      declined_grammars: HashSet::new(),
+      pending_grammar: None,
+      notifications,
+      crashed_servers: HashSet::new(),
+      clipboard,
+      font,
+      ligatures,
+      documents,
+      keymap: Keymap::new(&config.keybindings, Layout::configured(&config)),
+      user_config,
+      config,
       font_height,
       code_views,
-    })
+    };
+    renderer.layout();
+    renderer.count_changes();
+    renderer.report_plugin_errors();
+    if restore {
+      renderer.offer_restore();
+    }
+    if let Some(path) = renderer.code_views.paths().first() {
+      renderer.offer_grammar(path);
+    }
+    Ok(renderer)
+  }
+
+  /// Opens another window of the workspace showing `buffers`, sharing the
+  /// open files with this one.
+  pub async fn open_window<T>(
+    &self,
+    event_loop: &EventLoopWindowTarget<T>,
+    buffers: Vec<Buffer>,
+  ) -> Result<Self, anyhow::Error> {
+    Self::new(
+      event_loop,
+      self.font.clone(),
+      self.ligatures.clone(),
+      buffers,
+      self.workspace.clone(),
+      self.user_config.clone(),
+      Arc::clone(&self.notify),
+      self.documents.clone(),
+      false,
+    )
+    .await
   }
 
   pub fn resize(&mut self, size: PhysicalSize<f32>) {
+    // there's no swap chain of size zero, the old one is kept until the
+    // window is restored
+    self.hidden = size.width < 1.0 || size.height < 1.0;
+    if self.hidden {
+      return;
+    }
     self.size = size.cast();
 
     self.swap_chain = self.device.create_swap_chain(
       &self.surface,
       &wgpu::SwapChainDescriptor {
         usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
-        format: RENDER_FORMAT,
+        format: render_format(),
         width: self.size.width,
         height: self.size.height,
         present_mode: wgpu::PresentMode::Mailbox,
       },
     );
+    self
+      .glyph_pages
+      .resize(&self.device, self.font.clone(), self.size);
+
+    self.layout();
+    self.command_palette.resize(size);
+    self.buffer_switcher.resize(size);
+    self.completion.resize(size);
+    self.rename_preview.resize(size);
+    self.debug_overlay.resize(size);
+    self.color_picker.resize(size);
+    self.dialog.resize(size);
+    self.notifications.resize(size);
+  }
+
+  /// Positions the panels according to the current window size and sidebar
+  /// width.
+  fn layout(&mut self) {
+    let size: PhysicalSize<f32> = self.size.cast();
+    self.sidebar_width = self
+      .sidebar_width
+      .min(size.width - ACTIVITY_BAR_WIDTH - MIN_PANEL_WIDTH)
+      .max(MIN_PANEL_WIDTH);
+
+    let status_bar_height = if self.zen {
+      0.0
+    } else {
+      self.status_bar.height()
+    };
+    self.status_bar.set_dimensions(
+      size,
+      Dimensions {
+        x: 0.0,
+        y: size.height - status_bar_height,
+        width: size.width,
+        height: status_bar_height,
+      },
+    );
+
+    let full = Dimensions {
+      x: 0.0,
+      y: 0.0,
+      width: size.width,
+      height: size.height - status_bar_height,
+    };
+    let zen_width =
+      input::line_length(" ", self.font.clone(), self.font_height)
+        * self.config.zen.max_width as f32;
+    self
+      .code_views
+      .set_zen(size, Some(zen_width).filter(|_| self.zen));
+    // a hidden panel keeps its old dimensions, it isn't drawn anyways
+    let (sidebar, editor) = match self.maximized {
+      _ if self.zen => (None, Some(full)),
+      Some(Panel::Sidebar) => (Some(full), None),
+      Some(Panel::Editor) => (None, Some(full)),
+      None => {
+        self.activity_bar.set_dimensions(
+          size,
+          Dimensions {
+            width: ACTIVITY_BAR_WIDTH,
+            ..full
+          },
+        );
+        let sidebar_width = if self.sidebar_visible {
+          self.sidebar_width
+        } else {
+          0.0
+        };
+        (
+          Some(Dimensions {
+            x: ACTIVITY_BAR_WIDTH,
+            width: sidebar_width,
+            ..full
+          }),
+          Some(Dimensions {
+            x: ACTIVITY_BAR_WIDTH + sidebar_width,
+            width: size.width - ACTIVITY_BAR_WIDTH - sidebar_width,
+            ..full
+          }),
+        )
+      }
+    };
+    if let Some(editor) = editor {
+      let output_height = if self.output_shown() {
+        (editor.height * OUTPUT_PANEL_HEIGHT).round()
+      } else {
+        0.0
+      };
+      let code = Dimensions {
+        height: editor.height - output_height,
+        ..editor
+      };
+      self.code_views.set_dimensions(size, code);
+      self.welcome.set_dimensions(size, code);
+      self.output_panel.set_dimensions(
+        size,
+        Dimensions {
+          y: editor.y + editor.height - output_height,
+          height: output_height,
+          ..editor
+        },
+      );
+    }
+    if let Some(dimensions) = sidebar {
+      self.fs_tree.set_dimensions(size, dimensions);
+      self.outline.set_dimensions(size, dimensions);
+      self.placeholder.set_dimensions(size, dimensions);
+      self.plugin_panel.set_dimensions(size, dimensions);
+    }
 
+    // re-clamp the scroll offsets to the new sizes
     for element in self.get_elements() {
-      element.resize(size);
       element.scroll(PhysicalPosition { x: 0.0, y: 0.0 }, size);
     }
   }
 
-  pub fn scroll(
-    &mut self,
-    offset: PhysicalPosition<f64>,
-    mouse_pos: PhysicalPosition<f64>,
-  ) {
-    let self_size = self.size.cast();
-    for element in self.get_elements() {
-      if element
-        .get_dimensions()
-        .contains(mouse_pos.cast())
-        .is_some()
-      {
-        element.scroll(offset, self_size);
-        break;
-      }
+  /// Lays out the text with `font` at `font_size` points, zoomed and scaled
+  /// to the monitor.
+  fn set_font(&mut self, font: FontArc, font_size: f32) {
+    self.font_height =
+      font_height(&font, font_size * self.zoom, self.scale_factor);
+    // drops the glyphs cached at the previous size
+    self.glyph_pages =
+      glyph_pages::GlyphPages::new(&self.device, font.clone(), self.size);
+    self.font = font.clone();
+    let font_height = self.font_height;
+    for element in self.get_all_elements() {
+      element.set_font(&font, font_height);
     }
   }
 
-  pub fn click(
+  /// Re-renders for a monitor with a different density the window moved to,
+  /// which resized it to `size`.
+  pub fn set_scale_factor(
     &mut self,
-    position: PhysicalPosition<f64>,
-    state: ElementState,
+    scale_factor: f64,
+    size: PhysicalSize<u32>,
   ) {
-    if state == ElementState::Pressed {
-      let size = self.size.cast();
-      for element in self.get_elements() {
-        if let Some(pos) = element.get_dimensions().contains(position.cast()) {
-          element.click(pos.cast(), size);
-          self.window.request_redraw();
-          break;
+    self.scale_factor = scale_factor as f32;
+    self.set_font(self.font.clone(), self.config.font_size);
+    self.resize(size.cast());
+    self.window.request_redraw();
+  }
+
+  /// Applies a reloaded config, re-rendering with the new theme and font.
+  pub fn set_config(&mut self, config: Config) {
+    self.user_config = config;
+    self.apply_config(self.plugins.apply(&self.user_config));
+  }
+
+  fn apply_config(&mut self, config: Config) {
+    if config.theme != self.config.theme {
+      for element in self.get_all_elements() {
+        element.set_theme(&config.theme);
+      }
+    }
+
+    if config.font != self.config.font
+      || config.font_size != self.config.font_size
+    {
+      match crate::get_font(config.font.as_ref()) {
+        Ok((font, ligatures)) => {
+          self.ligatures = Rc::new(ligatures);
+          self.set_font(font, config.font_size);
         }
+        Err(err) => self
+          .notify_user(Level::Error, format!("Failed to load font: {}", err)),
+      }
+    }
+
+    if config.language_servers != self.config.language_servers {
+      for server in &self.language_servers.servers {
+        self.status_bar.remove(&format!("lsp-{}", server.name));
+      }
+      self.language_servers = LanguageServers::new(
+        config.language_servers.clone(),
+        self.workspace.clone(),
+        Arc::clone(&self.notify),
+        self.processes.clone(),
+      );
+      for (path, language) in self.code_views.languages() {
+        self.language_servers.start_for(&path, language.as_deref());
       }
     }
+
+    self.code_views.set_ligatures(if config.ligatures {
+      self.ligatures.clone()
+    } else {
+      Rc::default()
+    });
+    self.code_views.set_config(self.size.cast(), &config);
+    self.keymap = Keymap::new(&config.keybindings, Layout::configured(&config));
+    // lists the new keybindings
+    self.welcome_shown = false;
+    self.config = config;
+    self.resize(self.size.cast());
+    self.window.request_redraw();
   }
 
-  pub fn redraw(&mut self) -> Result<(), anyhow::Error> {
-    let mut encoder =
-      self
-        .device
-        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-          label: Some("Redraw"),
-        });
+  /// Shows `message` in the corner of the window, and logs it.
+  pub fn notify_user(&mut self, level: Level, message: String) {
+    match level {
+      Level::Info => tracing::info!("{}", message),
+      Level::Warning | Level::Error => tracing::warn!("{}", message),
+    }
+    self.notifications.push(&self.device, level, message);
+    self.window.request_redraw();
+  }
 
-    let frame = self.swap_chain.get_current_frame()?.output;
+  fn report_plugin_errors(&mut self) {
+    for (name, err) in self.plugins.errors.clone() {
+      self.notify_user(
+        Level::Warning,
+        format!("Failed to load plugin {}: {}", name, err),
+      );
+    }
+  }
 
-    {
-      let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: None,
-        color_attachments: &[wgpu::RenderPassColorAttachment {
-          view: &frame.view,
-          resolve_target: None,
-          ops: wgpu::Operations {
-            load: wgpu::LoadOp::Clear(wgpu::Color {
-              r: 0.01,
-              g: 0.01,
-              b: 0.01,
-              a: 1.0,
-            }),
-            store: true,
-          },
-        }],
-        depth_stencil_attachment: None,
-      });
+  /// Loads the plugins again and applies what they contribute, restarting
+  /// language servers where that changed.
+  fn reload_plugins(&mut self) {
+    self.plugins = PluginHost::load();
+    self.plugin_panel.update(&self.plugins);
+    self.set_config(self.user_config.clone());
+    self.report_plugin_errors();
+  }
 
-      rpass.set_pipeline(&self.rectangle_render_pipeline);
-      for rect in self.get_rects() {
-        rect.write_buffer(&self.queue);
-        rpass.set_vertex_buffer(0, rect.vertex_buffer.slice(..));
-        if let Some(ref region) = rect.region {
-          rpass.set_scissor_rect(
-            region.x,
-            region.y,
-            region.width,
-            region.height,
-          );
-        } else {
-          rpass.set_scissor_rect(0, 0, self.size.width, self.size.height);
+  /// Installs a plugin from a directory or git URL on a background thread,
+  /// as cloning can take a while.
+  fn install_plugin(&mut self, source: String) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let notify = Arc::clone(&self.notify);
+    std::thread::spawn(move || {
+      let _ = sender.send(plugins::install(&source));
+      notify();
+    });
+    self.pending_installs.push(receiver);
+    self.notify_user(Level::Info, String::from("Installing plugin..."));
+  }
+
+  fn poll_install(&mut self) {
+    let mut results = vec![];
+    self.pending_installs.retain(|receiver| {
+      let result = match receiver.try_recv() {
+        Ok(result) => result,
+        Err(TryRecvError::Empty) => return true,
+        Err(TryRecvError::Disconnected) => {
+          Err(anyhow::anyhow!("the install was interrupted"))
+        }
+      };
+      results.push(result);
+      false
+    });
+    if results.iter().any(Result::is_ok) {
+      self.reload_plugins();
+    }
+    for result in results {
+      match result {
+        Ok(name) => {
+          self.notify_user(Level::Info, format!("Installed plugin {}", name))
         }
-        rpass.draw(0..4, 0..1);
+        Err(err) => self.notify_user(
+          Level::Error,
+          format!("Failed to install plugin: {}", err),
+        ),
       }
     }
+  }
 
-    self.code_views.redraw(
-      &mut self.glyph_brush,
-      &self.device,
-      &mut self.staging_belt,
-      &mut encoder,
-      &frame.view,
-      self.size,
-    );
+  fn set_plugin_enabled(&mut self, name: &str, enabled: bool) {
+    match self.plugins.set_enabled(name, enabled) {
+      Ok(()) => self.reload_plugins(),
+      Err(err) => self.notify_user(
+        Level::Error,
+        format!("Failed to change plugin {}: {}", name, err),
+      ),
+    }
+  }
 
-    self.fs_tree.redraw(
-      &mut self.glyph_brush,
-      &self.device,
-      &mut self.staging_belt,
-      &mut encoder,
-      &frame.view,
-      self.size,
-    );
+  /// Reloads open files changed by other programs, unless they have unsaved
+  /// changes which would be lost.
+  pub fn check_files(&mut self) {
+    let size = self.size.cast();
+    let mut messages = vec![];
+    for (name, _, code_view) in &mut self.code_views.code_views {
+      if !code_view.changed_on_disk() {
+        continue;
+      }
+      if code_view.is_modified() {
+        messages.push((
+          Level::Warning,
+          format!(
+            "{} changed on disk, saving will overwrite those changes",
+            name
+          ),
+        ));
+      } else if let Err(err) = code_view.reload(size) {
+        messages
+          .push((Level::Error, format!("Failed to reload {}: {}", name, err)));
+      }
+    }
+    for (level, message) in messages {
+      self.notify_user(level, message);
+    }
+    self.window.request_redraw();
+  }
 
-    self.staging_belt.finish();
-    self.queue.submit(Some(encoder.finish()));
-    self.local_spawner.spawn(self.staging_belt.recall())?;
-    self.local_pool.run_until_stalled();
+  /// Opens files sent by another process and asks for the user's attention.
+  pub fn open_files(&mut self, files: Vec<FileArg>) {
+    for file in files {
+      self.open_file(file.path, file.position);
+    }
+    self
+      .window
+      .request_user_attention(Some(UserAttentionType::Informational));
+    self.window.request_redraw();
+  }
 
-    Ok(())
+  /// Switches to the tab of `path`, opening it if needed, and moves the
+  /// cursor to `position`.
+  fn open_file(&mut self, path: PathBuf, position: Option<(usize, usize)>) {
+    let size = self.size.cast();
+    if let Err(err) = self.code_views.show(&self.device, size, path.clone()) {
+      self.notify_user(
+        Level::Error,
+        format!("Failed to open {}: {}", path.display(), err),
+      );
+      return;
+    }
+    recent::remember(|recent| recent.add_file(&path));
+    self.start_language_servers(&path);
+    self.offer_grammar(&path);
+    if let Some((row, column)) = position {
+      self.code_views.goto(size, row, column);
+    }
+    self.focused = Panel::Editor;
   }
 
-  fn get_rects(&self) -> Vec<&rectangle::Rectangle> {
-    let mut vec = vec![];
-    vec.extend(self.code_views.get_rects());
-    vec.extend(self.fs_tree.get_rects());
-    vec
+  /// The language of the active tab.
+  fn language(&self) -> Option<&str> {
+    self.code_views.active_view().and_then(CodeView::language)
   }
 
-  fn get_elements(&mut self) -> Vec<&mut dyn RenderElement> {
-    let mut vec: Vec<&mut dyn RenderElement> = vec![&mut self.fs_tree];
-    vec.extend(self.code_views.get_elements());
-    vec
+  /// Starts the language servers for the file at `path`, which was just
+  /// opened in the active tab.
+  fn start_language_servers(&mut self, path: &Path) {
+    let language = self.code_views.active_view().and_then(CodeView::language);
+    self.language_servers.start_for(path, language);
   }
-}
 
-trait RenderElement {
-  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
-    for element in self.get_elements() {
-      element.resize(screen_size);
+  /// Moves the cursor to the next marker of `kind` or the previous one,
+  /// opening its file if it isn't open. Matches are of the selected text,
+  /// or the text searched for last if nothing is selected.
+  fn goto_marker(&mut self, kind: MarkerKind, forward: bool) {
+    if kind == MarkerKind::Match {
+      if let Some(text) = self
+        .code_views
+        .active_view()
+        .and_then(CodeView::selected_text)
+      {
+        self.search = Some(text);
+      }
+    }
+    let find = self.search.clone().unwrap_or_default();
+    self.poll_problems();
+    let problems = self.output_panel.locations();
+    let markers = self.code_views.markers(kind, &find, problems);
+    let current = self.code_views.current_marker();
+    match markers::next(&markers, &current, forward) {
+      Some(Marker {
+        target: Target::Tab(i),
+        line,
+        column,
+      }) => {
+        self.code_views.goto_tab(self.size.cast(), i, line, column);
+        self.focused = Panel::Editor;
+      }
+      Some(Marker {
+        target: Target::File(path),
+        line,
+        column,
+      }) => self.open_file(path, Some((line, column))),
+      None => self.notify_user(Level::Info, kind.none_message().to_string()),
     }
   }
 
-  fn scroll(
-    &mut self,
-    offset: PhysicalPosition<f64>,
-    screen_size: PhysicalSize<f32>,
-  ) {
-    for element in self.get_elements() {
-      element.scroll(offset, screen_size);
+  /// Starts another instance of the editor with `dir` as its workspace,
+  /// relative to the current one.
+  fn open_folder(&mut self, dir: &str) {
+    let dir = self
+      .workspace
+      .join(shellexpand::tilde(dir).as_ref())
+      .canonicalize()
+      .ok()
+      .filter(|dir| dir.is_dir());
+    let dir = match dir {
+      Some(dir) => dir,
+      None => {
+        return self.notify_user(Level::Error, String::from("Not a directory"));
+      }
+    };
+    let spawned = std::env::current_exe().and_then(|exe| {
+      std::process::Command::new(exe).current_dir(&dir).spawn()
+    });
+    if let Err(err) = spawned {
+      self.notify_user(
+        Level::Error,
+        format!("Failed to open {}: {}", dir.display(), err),
+      );
     }
   }
 
-  fn click(
-    &mut self,
-    position: PhysicalPosition<f64>,
-    screen_size: PhysicalSize<f32>,
-  ) {
-    for element in self.get_elements() {
-      if let Some(pos) = element.get_dimensions().contains(position.cast()) {
-        element.click(pos.cast(), screen_size);
-        break;
+  /// Lists the commands and recent files on the start screen.
+  fn update_welcome(&mut self) {
+    let commands = welcome::COMMANDS
+      .iter()
+      .map(|command| {
+        let combo = self.keymap.combo_for(*command);
+        (*command, combo.map(|combo| self.keymap.describe(combo)))
+      })
+      .collect::<Vec<_>>();
+    let recent = Recent::load(&Recent::path());
+    self.welcome.update(self.size.cast(), &commands, &recent);
+  }
+
+  fn welcome_action(&mut self, action: WelcomeAction) {
+    match action {
+      WelcomeAction::Run(command) => self.execute(command),
+      WelcomeAction::Open(path) => self.open_file(path, None),
+      WelcomeAction::OpenWorkspace(dir) => {
+        self.open_folder(&dir.to_string_lossy())
+      }
+    }
+  }
+
+  /// Runs the task `name` of the workspace in the output panel.
+  fn run_task(&mut self, name: &str) {
+    let tasks = tasks::load(&self.workspace);
+    let config = match tasks.get(name) {
+      Some(config) => config,
+      None => {
+        self.notify_user(Level::Warning, format!("No task named {}", name));
+        return;
+      }
+    };
+    let notify = Arc::clone(&self.notify);
+    match Task::spawn(name, config, &self.workspace, notify, &self.processes) {
+      Ok(task) => {
+        self.output_panel.run(task);
+        self.last_task = Some(name.to_string());
+        self.layout();
       }
+      Err(err) => self.notify_user(
+        Level::Error,
+        format!("Failed to run task {}: {}", name, err),
+      ),
+    }
+  }
+
+  fn output_shown(&self) -> bool {
+    self.output_panel.visible
+      && self.maximized != Some(Panel::Sidebar)
+      && !self.zen
+  }
+
+  fn editor_shown(&self) -> bool {
+    self.zen || self.maximized != Some(Panel::Sidebar)
+  }
+
+  fn activity_bar_shown(&self) -> bool {
+    self.maximized.is_none() && !self.zen
+  }
+
+  /// Opens a file clicked in the explorer in the preview tab, double clicks
+  /// keep it open.
+  fn open_from_explorer(&mut self, path: PathBuf, double: bool) {
+    let size = self.size.cast();
+    match self
+      .code_views
+      .open_preview(&self.device, size, path.clone())
+    {
+      Ok(()) => {
+        recent::remember(|recent| recent.add_file(&path));
+        self.start_language_servers(&path);
+        self.offer_grammar(&path);
+        if double {
+          self.code_views.pin_active();
+          self.focused = Panel::Editor;
+        }
+      }
+      Err(err) => self.notify_user(
+        Level::Error,
+        format!("Failed to open {}: {}", path.display(), err),
+      ),
+    }
+  }
+
+  /// Updates the built-in status bar segments to the current state.
+  fn update_status(&mut self) {
+    match self.code_views.cursor_position() {
+      Some((row, column)) => self.status_bar.set(
+        "cursor",
+        Segment {
+          text: format!("Ln {}, Col {}", row + 1, column + 1),
+          alignment: Alignment::Right,
+          priority: 100,
+          command: None,
+        },
+      ),
+      None => self.status_bar.remove("cursor"),
+    }
+
+    let language = self.code_views.active_view().and_then(CodeView::language);
+    let tab_width = self.config.tab_width_for(language);
+    self.status_bar.set(
+      "indentation",
+      Segment {
+        text: if self.config.insert_spaces_for(language) {
+          format!("Spaces: {}", tab_width)
+        } else {
+          format!("Tab Size: {}", tab_width)
+        },
+        alignment: Alignment::Right,
+        priority: 90,
+        command: None,
+      },
+    );
+    match language {
+      Some(language) => self.status_bar.set(
+        "language",
+        Segment {
+          text: language.to_string(),
+          alignment: Alignment::Right,
+          priority: 89,
+          command: None,
+        },
+      ),
+      None => self.status_bar.remove("language"),
+    }
+
+    let mut crashed = vec![];
+    for server in &self.language_servers.servers {
+      if server.status() == lsp::Status::Crashed {
+        if self.crashed_servers.insert(server.name.clone()) {
+          crashed.push(server.name.clone());
+        }
+      } else {
+        self.crashed_servers.remove(&server.name);
+      }
+      self.status_bar.set(
+        &format!("lsp-{}", server.name),
+        Segment {
+          text: format!("{}: {}", server.name, server.status().name()),
+          alignment: Alignment::Right,
+          priority: 50,
+          command: Some(Command::OpenServerLog),
+        },
+      );
+    }
+    for name in crashed {
+      self.notify_user(
+        Level::Error,
+        format!("Language server {} crashed, see its log for details", name),
+      );
+    }
+
+    if self.resize_mode {
+      self.status_bar.set(
+        "mode",
+        Segment {
+          text: String::from("-- RESIZE --"),
+          alignment: Alignment::Left,
+          priority: 100,
+          command: Some(Command::EqualizePanels),
+        },
+      );
+    } else {
+      self.status_bar.remove("mode");
+    }
+
+    if self.overwrite {
+      self.status_bar.set(
+        "overwrite",
+        Segment {
+          text: String::from("Overwrite"),
+          alignment: Alignment::Right,
+          priority: 95,
+          command: Some(Command::ToggleOverwrite),
+        },
+      );
+    } else {
+      self.status_bar.remove("overwrite");
+    }
+
+    if self.code_views.typewriter() {
+      self.status_bar.set(
+        "typewriter",
+        Segment {
+          text: String::from("Typewriter"),
+          alignment: Alignment::Right,
+          priority: 93,
+          command: Some(Command::ToggleTypewriter),
+        },
+      );
+    } else {
+      self.status_bar.remove("typewriter");
+    }
+
+    if self.scroll_lock {
+      self.status_bar.set(
+        "scroll-lock",
+        Segment {
+          text: String::from("Scroll Lock"),
+          alignment: Alignment::Right,
+          priority: 94,
+          command: Some(Command::ToggleScrollLock),
+        },
+      );
+    } else {
+      self.status_bar.remove("scroll-lock");
+    }
+
+    if self.maximized.is_some() {
+      self.status_bar.set(
+        "maximized",
+        Segment {
+          text: String::from("Maximized"),
+          alignment: Alignment::Left,
+          priority: 90,
+          command: Some(Command::ToggleMaximizePanel),
+        },
+      );
+    } else {
+      self.status_bar.remove("maximized");
+    }
+  }
+
+  /// Shows the symbols of the active tab in the outline if they changed.
+  fn update_outline(&mut self) {
+    let active = self.code_views.active_view_mut();
+    let revision = active.as_ref().map(|active| active.revision());
+    if revision != self.outline.revision {
+      let symbols = active.map_or(vec![], |active| active.symbols().to_vec());
+      self.outline.set_symbols(revision, symbols);
+    }
+  }
+
+  /// Asks the language server of the active file to rename the symbol at the
+  /// cursor to `name`, the edits are previewed once it answered.
+  fn request_rename(&mut self, name: String) -> Result<(), anyhow::Error> {
+    let active = self
+      .code_views
+      .active_view()
+      .ok_or_else(|| anyhow::anyhow!("no file is open"))?;
+    let path = active
+      .path()
+      .ok_or_else(|| anyhow::anyhow!("buffer isn't backed by a file"))?
+      .canonicalize()?;
+    let server = self
+      .language_servers
+      .server_for(&path, active.language())
+      .ok_or_else(|| {
+        anyhow::anyhow!("no language server for {}", path.display())
+      })?;
+
+    let (row, column) = active.cursor_position();
+    let version = active.document().version();
+    let text = active.text();
+    let uri = lsp::path_to_uri(&path);
+    open_document(server, active, &path)?;
+    let id = server.request(
+      "textDocument/rename",
+      json!({
+        "textDocument": { "uri": uri },
+        "position": {
+          "line": row,
+          "character": rename::grapheme_to_utf16(&text[row], column),
+        },
+        "newName": name,
+      }),
+    )?;
+    server.notification(
+      "textDocument/didClose",
+      json!({ "textDocument": { "uri": uri } }),
+    )?;
+
+    self.pending_rename = Some(PendingRename {
+      server: server.name.clone(),
+      id,
+      name,
+      path,
+      version,
+    });
+    Ok(())
+  }
+
+  /// Asks the language server of the active file for the code actions at
+  /// the cursor, the answer is used as `request` says.
+  fn request_code_actions(
+    &mut self,
+    request: CodeActionRequest,
+  ) -> Result<(), anyhow::Error> {
+    let active = self
+      .code_views
+      .active_view()
+      .ok_or_else(|| anyhow::anyhow!("no file is open"))?;
+    let path = active
+      .path()
+      .ok_or_else(|| anyhow::anyhow!("buffer isn't backed by a file"))?
+      .canonicalize()?;
+    let server = self
+      .language_servers
+      .server_for(&path, active.language())
+      .ok_or_else(|| {
+        anyhow::anyhow!("no language server for {}", path.display())
+      })?;
+
+    let (row, column) = active.cursor_position();
+    let character = rename::grapheme_to_utf16(&active.text()[row], column);
+    let only = match request {
+      CodeActionRequest::OrganizeImports => {
+        Some(code_actions::ORGANIZE_IMPORTS)
+      }
+      _ => None,
+    };
+    let uri = lsp::path_to_uri(&path);
+    open_document(server, active, &path)?;
+    let id = server.request(
+      "textDocument/codeAction",
+      code_actions::request_params(&uri, row, character, character, only),
+    )?;
+    server.notification(
+      "textDocument/didClose",
+      json!({ "textDocument": { "uri": uri } }),
+    )?;
+
+    self.pending_code_actions = Some(PendingCodeActions {
+      server: server.name.clone(),
+      id,
+      request,
+      revision: active.revision(),
+      cursor: (row, column),
+    });
+    Ok(())
+  }
+
+  /// Looks for the code actions at the cursor once it rested after moving
+  /// or editing, to show the lightbulb.
+  fn update_lightbulb(&mut self) {
+    let at = self
+      .code_views
+      .active_view()
+      .map(|active| (active.revision(), active.cursor_position()));
+    if at != self.lightbulb_at {
+      self.lightbulb_at = at;
+      self.lightbulb_deadline = at.map(|_| Instant::now() + LIGHTBULB_DELAY);
+    }
+  }
+
+  /// Uses the code actions once the language server answered.
+  fn poll_code_actions(&mut self) {
+    let edits = self
+      .language_servers
+      .servers
+      .iter()
+      .enumerate()
+      .flat_map(|(i, server)| {
+        server
+          .take_applied_edits()
+          .into_iter()
+          .map(move |(id, edit)| (i, id, edit))
+      })
+      .collect::<Vec<_>>();
+    for (i, id, edit) in edits {
+      let result = rename::parse_workspace_edit(&edit)
+        .map_err(|err| anyhow::anyhow!("Failed to apply an edit: {}", err))
+        .and_then(|files| self.write_edits(files))
+        .map_err(|err| err.to_string());
+      if let Err(message) = &result {
+        self.notify_user(Level::Error, message.clone());
+      }
+      self.language_servers.servers[i].answer_applied_edit(id, result);
+    }
+
+    let pending = match &self.pending_code_actions {
+      Some(pending) => pending,
+      None => return,
+    };
+    let server = self
+      .language_servers
+      .servers
+      .iter()
+      .find(|server| server.name == pending.server);
+    let response = match server {
+      Some(server) if server.status() == lsp::Status::Ready => {
+        match server.take_response(pending.id) {
+          Some(response) => response,
+          None => return,
+        }
+      }
+      _ => Err(format!("{} stopped", pending.server)),
+    };
+    let pending = self.pending_code_actions.take().unwrap();
+
+    let result = match response {
+      Ok(result) => result,
+      // asked for without the user noticing
+      Err(_) if pending.request == CodeActionRequest::Lightbulb => return,
+      Err(err) => {
+        let message = format!("Failed to get code actions: {}", err);
+        return self.notify_user(Level::Error, message);
+      }
+    };
+    let (id, version) = pending.revision;
+    let current = self
+      .code_views
+      .view_for_id_mut(id)
+      .map(|code_view| (code_view.revision().1, code_view.cursor_position()));
+    // moved or typed into while waiting, the actions don't fit anymore
+    if pending.request != CodeActionRequest::Resolve
+      && current != Some((version, pending.cursor))
+    {
+      return;
+    }
+
+    match pending.request {
+      CodeActionRequest::Lightbulb => {
+        if !code_actions::parse_actions(&result).is_empty() {
+          if let Some(code_view) = self.code_views.view_for_id_mut(id) {
+            code_view.set_lightbulb(version, pending.cursor);
+          }
+        }
+      }
+      CodeActionRequest::Menu => {
+        let actions = code_actions::parse_actions(&result);
+        if actions.is_empty() {
+          return self
+            .notify_user(Level::Info, String::from("No code actions"));
+        }
+        let anchor = match self.code_views.active_view() {
+          Some(active) => active.cursor_dimensions(),
+          None => return,
+        };
+        let items = actions
+          .iter()
+          .map(|action| Item {
+            label: action.title.clone(),
+            insert_text: String::new(),
+            is_snippet: false,
+            sort_text: None,
+            documentation: None,
+          })
+          .collect();
+        self.completion.open(self.size.cast(), anchor, items, false);
+        self.code_action_menu = Some((pending.server, actions));
+      }
+      CodeActionRequest::OrganizeImports => {
+        match code_actions::parse_actions(&result).into_iter().next() {
+          Some(action) => self.apply_code_action(&pending.server, action),
+          None => self
+            .notify_user(Level::Info, String::from("No imports to organize")),
+        }
+      }
+      CodeActionRequest::Resolve => {
+        match code_actions::parse_action(&result) {
+          // resolved already, so it isn't resolved over and over
+          Some(action) if !action.needs_resolve() => {
+            self.apply_code_action(&pending.server, action)
+          }
+          _ => self.notify_user(
+            Level::Error,
+            format!("{} didn't resolve the code action", pending.server),
+          ),
+        }
+      }
+    }
+  }
+
+  /// Applies the edit of `action` offered by `server`, resolving it first
+  /// if needed, then runs its command on the server.
+  fn apply_code_action(&mut self, server: &str, action: CodeAction) {
+    if action.needs_resolve() {
+      return self.resolve_code_action(server, action);
+    }
+    if let Some(edit) = &action.edit {
+      match rename::parse_workspace_edit(edit) {
+        Ok(files) => self.apply_edits(files),
+        Err(err) => {
+          let message = format!("Failed to apply {}: {}", action.title, err);
+          return self.notify_user(Level::Error, message);
+        }
+      }
+    }
+    if let Some(command) = &action.command {
+      let params = json!({
+        "command": command["command"],
+        "arguments": command.get("arguments").cloned().unwrap_or_else(|| json!([])),
+      });
+      let sent = self
+        .server_named(server)
+        .and_then(|server| server.request("workspace/executeCommand", params));
+      if let Err(err) = sent {
+        let message = format!("Failed to run {}: {}", action.title, err);
+        self.notify_user(Level::Error, message);
+      }
+    }
+  }
+
+  /// Asks `server` for the edit of `action`, which is applied once it
+  /// answered.
+  fn resolve_code_action(&mut self, server: &str, action: CodeAction) {
+    let sent = self.server_named(server).and_then(|server| {
+      server.request("codeAction/resolve", action.raw.clone())
+    });
+    match sent {
+      Ok(id) => {
+        let active = self.code_views.active_view();
+        self.pending_code_actions = Some(PendingCodeActions {
+          server: server.to_string(),
+          id,
+          request: CodeActionRequest::Resolve,
+          revision: active.map_or((0, 0), CodeView::revision),
+          cursor: active.map_or((0, 0), CodeView::cursor_position),
+        });
+      }
+      Err(err) => self.notify_user(
+        Level::Error,
+        format!("Failed to resolve {}: {}", action.title, err),
+      ),
+    }
+  }
+
+  fn server_named(
+    &self,
+    name: &str,
+  ) -> Result<&lsp::LanguageServer, anyhow::Error> {
+    self
+      .language_servers
+      .servers
+      .iter()
+      .find(|server| server.name == name)
+      .ok_or_else(|| anyhow::anyhow!("{} stopped", name))
+  }
+
+  /// Saves the active tab, formatting it first if that's configured for its
+  /// language and a language server is there to do it. A buffer which isn't
+  /// backed by a file asks where to save it.
+  fn save_active(&mut self) {
+    if self.code_views.active_view().map(CodeView::path) == Some(None) {
+      self.command_palette.open_save_as(self.size.cast(), "");
+      return;
+    }
+    match self.request_format() {
+      Ok(true) => return,
+      Ok(false) => {}
+      Err(err) => {
+        self.notify_user(Level::Error, format!("Failed to format: {}", err))
+      }
+    }
+    if let Err(err) = self.code_views.save_active() {
+      self.notify_user(Level::Error, format!("Failed to save: {}", err));
+    }
+    self.count_changes();
+  }
+
+  /// Saves the active tab to `path`, relative to the workspace, and starts
+  /// the language servers for it.
+  fn save_active_as(&mut self, path: &str) {
+    let path = self.workspace.join(path);
+    if let Err(err) = self
+      .code_views
+      .save_active_as(self.size.cast(), path.clone())
+    {
+      self.notify_user(Level::Error, format!("Failed to save: {}", err));
+      return;
+    }
+    recent::remember(|recent| recent.add_file(&path));
+    self.start_language_servers(&path);
+  }
+
+  /// Asks the language server of the active tab to format it, if its
+  /// language is formatted on save. The tab is saved once the server
+  /// answered, which is the case when it returns `true`.
+  fn request_format(&mut self) -> Result<bool, anyhow::Error> {
+    let active = match self.code_views.active_view() {
+      Some(active) => active,
+      None => return Ok(false),
+    };
+    let path = match active.path() {
+      Some(path) => path.canonicalize()?,
+      None => return Ok(false),
+    };
+    let language = active.language();
+    if self.pending_format.is_some()
+      || !self.config.format_on_save_for(language)
+    {
+      return Ok(false);
+    }
+    let server = match self.language_servers.server_for(&path, language) {
+      Some(server) => server,
+      None => return Ok(false),
+    };
+
+    let uri = lsp::path_to_uri(&path);
+    open_document(server, active, &path)?;
+    let request = server.request(
+      "textDocument/formatting",
+      json!({
+        "textDocument": { "uri": uri },
+        "options": {
+          "tabSize": self.config.tab_width_for(language),
+          "insertSpaces": self.config.insert_spaces_for(language),
+        },
+      }),
+    )?;
+    server.notification(
+      "textDocument/didClose",
+      json!({ "textDocument": { "uri": uri } }),
+    )?;
+
+    self.pending_format = Some(PendingFormat {
+      server: server.name.clone(),
+      request,
+      revision: active.revision(),
+      deadline: Instant::now() + FORMAT_TIMEOUT,
+    });
+    Ok(true)
+  }
+
+  /// Applies the formatting once the language server answered and saves
+  /// the tab, also when the server failed or took too long.
+  fn poll_format(&mut self) {
+    let pending = match &self.pending_format {
+      Some(pending) => pending,
+      None => return,
+    };
+    let server = self
+      .language_servers
+      .servers
+      .iter()
+      .find(|server| server.name == pending.server);
+    let response = match server {
+      Some(server) if server.status() == lsp::Status::Ready => {
+        match server.take_response(pending.request) {
+          Some(response) => response,
+          None if Instant::now() < pending.deadline => return,
+          None => Err(format!("{} took too long", pending.server)),
+        }
+      }
+      _ => Err(format!("{} stopped", pending.server)),
+    };
+    let pending = self.pending_format.take().unwrap();
+
+    let (id, version) = pending.revision;
+    let size = self.size.cast();
+    let code_view = match self.code_views.view_for_id_mut(id) {
+      Some(code_view) => code_view,
+      None => return,
+    };
+    let edits =
+      response
+        .map_err(anyhow::Error::msg)
+        .and_then(|result| match result {
+          Value::Null => Ok(vec![]),
+          result => rename::text_edits(&result),
+        });
+    match edits {
+      // typed into while waiting, the edits don't fit anymore
+      Ok(_) if code_view.revision().1 != version => {}
+      Ok(edits) if edits.is_empty() => {}
+      Ok(edits) => {
+        let lines = rename::apply(&code_view.text(), &edits);
+        code_view.replace_text(size, lines);
+      }
+      Err(err) => {
+        self.notify_user(Level::Error, format!("Failed to format: {}", err))
+      }
+    }
+    for error in self.code_views.save_ids(&[id]) {
+      self.notify_user(Level::Error, format!("Failed to save {}", error));
+    }
+  }
+
+  /// Opens the rename preview once the language server answered.
+  fn poll_rename(&mut self) {
+    let pending = match &self.pending_rename {
+      Some(pending) => pending,
+      None => return,
+    };
+    let server = self
+      .language_servers
+      .servers
+      .iter()
+      .find(|server| server.name == pending.server);
+    let response = match server {
+      Some(server) if server.status() == lsp::Status::Ready => {
+        match server.take_response(pending.id) {
+          Some(response) => response,
+          None => return,
+        }
+      }
+      _ => Err(format!("{} stopped", pending.server)),
+    };
+    let pending = self.pending_rename.take().unwrap();
+
+    let files = response
+      .map_err(anyhow::Error::msg)
+      .and_then(|result| rename::parse_workspace_edit(&result))
+      .map(|mut files| {
+        // the edits to the file renamed in were made for the text sent
+        for file in &mut files {
+          if file.path == pending.path && file.version.is_none() {
+            file.version = Some(pending.version);
+          }
+        }
+        files
+      });
+    match files {
+      Ok(files) if files.is_empty() => {
+        self.notify_user(Level::Info, String::from("Nothing to rename"))
+      }
+      Ok(files) => {
+        self.preview_edits(&format!("Rename to \"{}\"", pending.name), files)
+      }
+      Err(err) => {
+        self.notify_user(Level::Error, format!("Failed to rename: {}", err))
+      }
+    }
+  }
+
+  /// Asks the language servers which are ready for the symbols of the
+  /// workspace matching the query of the switcher, unless they were asked
+  /// for it already.
+  fn request_workspace_symbols(&mut self) {
+    let query = match self.buffer_switcher.workspace_query() {
+      Some(query) => query.to_string(),
+      None => return,
+    };
+    if self.workspace_symbol_query.as_ref() == Some(&query) {
+      return;
+    }
+    self.pending_workspace_symbols = self
+      .language_servers
+      .servers
+      .iter()
+      .filter(|server| server.status() == lsp::Status::Ready)
+      .filter_map(|server| {
+        let id = server
+          .request("workspace/symbol", json!({ "query": query }))
+          .ok()?;
+        Some((server.name.clone(), id))
+      })
+      .collect();
+    self.workspace_symbol_query = Some(query);
+  }
+
+  /// Lists the symbols of the workspace the language servers found in the
+  /// switcher.
+  fn poll_workspace_symbols(&mut self) {
+    if self.pending_workspace_symbols.is_empty() {
+      return;
+    }
+    if !self.buffer_switcher.visible {
+      self.pending_workspace_symbols.clear();
+      return;
+    }
+    let mut found = vec![];
+    let servers = &self.language_servers.servers;
+    self.pending_workspace_symbols.retain(|(name, id)| {
+      let server = servers.iter().find(|server| &server.name == name);
+      match server {
+        Some(server) if server.status() == lsp::Status::Ready => {
+          match server.take_response(*id) {
+            Some(Ok(result)) => {
+              found.extend(lsp::parse_workspace_symbols(&result));
+              false
+            }
+            Some(Err(err)) => {
+              tracing::warn!("{} failed to find symbols: {}", name, err);
+              false
+            }
+            None => true,
+          }
+        }
+        _ => false,
+      }
+    });
+    if found.is_empty() {
+      return;
+    }
+
+    let mut files = HashMap::new();
+    let symbols = found
+      .into_iter()
+      .map(|symbol| {
+        let lines = files
+          .entry(symbol.path.clone())
+          .or_insert_with(|| self.file_lines(&symbol.path).unwrap_or_default());
+        let column = lines.get(symbol.line).map_or(0, |line| {
+          let end = rename::utf16_to_byte(line, symbol.character);
+          line[..end].graphemes(true).count()
+        });
+        let location = self.relative_path(&symbol.path);
+        SymbolEntry {
+          name: symbol.name,
+          detail: match symbol.container {
+            Some(container) => format!("{} · {}", container, location),
+            None => location,
+          },
+          action: SwitcherAction::OpenAt(symbol.path, symbol.line, column),
+        }
+      })
+      .collect();
+    self
+      .buffer_switcher
+      .add_workspace_symbols(self.size.cast(), symbols);
+  }
+
+  /// `path` relative to the workspace, for display.
+  fn relative_path(&self, path: &Path) -> String {
+    path
+      .strip_prefix(&self.workspace)
+      .unwrap_or(path)
+      .display()
+      .to_string()
+  }
+
+  /// Lists `files` to be edited as `change` describes, to apply the edits
+  /// which are left in.
+  fn preview_edits(&mut self, change: &str, mut files: Vec<FileEdits>) {
+    for file in &mut files {
+      if file.version.is_none() {
+        file.version = self
+          .code_views
+          .view_for_path_mut(&file.path)
+          .map(|view| view.document().version());
+      }
+    }
+    let previews = files
+      .iter()
+      .map(|file| {
+        let lines = self.file_lines(&file.path).unwrap_or_default();
+        file
+          .edits
+          .iter()
+          .map(|edit| rename::preview(&lines, edit))
+          .collect()
+      })
+      .collect();
+    self.rename_preview.open(
+      self.size.cast(),
+      &self.workspace,
+      change,
+      files,
+      previews,
+    );
+  }
+
+  /// Previews replacing `find` with `replacement` in all files of the
+  /// workspace.
+  fn replace_in_files(&mut self, find: &str, replacement: &str) {
+    let paths = self.fs_tree.files();
+    let files = search::replace_in_files(
+      &paths,
+      |path| self.file_lines(path).ok(),
+      find,
+      replacement,
+    );
+    if files.is_empty() {
+      let message = format!("\"{}\" wasn't found in any file", find);
+      return self.notify_user(Level::Info, message);
+    }
+    let change = format!("Replace \"{}\" with \"{}\"", find, replacement);
+    self.preview_edits(&change, files);
+  }
+
+  /// The lines of the file at `path`, from its tab if it is open.
+  fn file_lines(&mut self, path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    match self.code_views.view_for_path_mut(path) {
+      Some(code_view) => Ok(code_view.text().clone()),
+      None => Ok(
+        std::fs::read_to_string(path)?
+          .split('\n')
+          .map(String::from)
+          .collect(),
+      ),
+    }
+  }
+
+  /// Applies the edits of `files` with [`Renderer::write_edits`], showing
+  /// why if that fails.
+  fn apply_edits(&mut self, files: Vec<FileEdits>) {
+    if let Err(err) = self.write_edits(files) {
+      self.notify_user(Level::Error, err.to_string());
+    }
+  }
+
+  /// Applies the edits which are still included, to the tabs of open files
+  /// as one change each and to the other files on disk. Nothing is changed
+  /// unless all files can be, or if an open file was edited since the
+  /// edits were made for it.
+  fn write_edits(
+    &mut self,
+    files: Vec<FileEdits>,
+  ) -> Result<(), anyhow::Error> {
+    let mut open = vec![];
+    let mut closed = vec![];
+    for file in files {
+      if !file.edits.iter().any(|edit| edit.included) {
+        continue;
+      }
+      let view = self.code_views.view_for_path_mut(&file.path);
+      let version = view.map(|view| view.document().version());
+      if matches!((file.version, version), (Some(a), Some(b)) if a != b) {
+        anyhow::bail!(
+          "{} changed since the edits were made, nothing was changed",
+          file.path.display()
+        );
+      }
+      let lines = match self.file_lines(&file.path) {
+        Ok(lines) => rename::apply(&lines, &file.edits),
+        Err(err) => {
+          anyhow::bail!("Failed to read {}: {}", file.path.display(), err)
+        }
+      };
+      if self.code_views.view_for_path_mut(&file.path).is_some() {
+        open.push((file.path, lines));
+      } else {
+        closed.push((file.path, lines.join("\n")));
+      }
+    }
+    if let Err(err) = rename::write_all(&closed) {
+      anyhow::bail!("Failed to write {}", err);
+    }
+
+    let size = self.size.cast();
+    for (path, lines) in open {
+      if let Some(code_view) = self.code_views.view_for_path_mut(&path) {
+        code_view.replace_text(size, lines);
+      }
+    }
+    Ok(())
+  }
+
+  /// Closes the active tab, asking whether to save it first if it has
+  /// unsaved changes which aren't open in another window.
+  fn close_tab(&mut self) {
+    let name = match self.code_views.active_view() {
+      Some(active) if active.is_modified() && !active.is_shared() => {
+        self.code_views.active_name().unwrap_or_default()
+      }
+      _ => return self.code_views.close_active(self.size.cast()),
+    };
+    self.dialog.open(
+      &self.device,
+      self.size.cast(),
+      format!("Save changes to {} before closing?", name),
+      &["Save", "Discard", "Cancel"],
+      2,
+    );
+    self.confirm = Some(Confirm::CloseTab);
+  }
+
+  /// Remembers the bookmarks for the next time the workspace is opened,
+  /// keeping the changes other windows saved since this one loaded them.
+  pub fn save_session(&mut self) {
+    let ours = Session {
+      bookmarks: self.code_views.bookmarks(),
+    };
+    let mut session = Session::load(&self.workspace);
+    session.merge(&self.session, &ours);
+    match session.save(&self.workspace) {
+      Ok(()) => self.session = ours,
+      Err(err) => tracing::warn!("failed to save the session: {}", err),
+    }
+  }
+
+  /// Stops the language servers and tasks of the window, called when it
+  /// closes. Servers are asked to exit and tasks to terminate, whatever is
+  /// left after the grace period is killed.
+  pub fn shut_down(&mut self) {
+    for server in &self.language_servers.servers {
+      server.shut_down();
+    }
+    self.processes.terminate(processes::Kind::Task);
+    self.processes.shut_down(processes::GRACE_PERIOD);
+  }
+
+  /// Removes the backups of unsaved changes, called when exiting cleanly.
+  pub fn clear_backups(&mut self) {
+    self.recovery.clear();
+  }
+
+  /// Backs up the buffers with unsaved changes now and then.
+  fn back_up(&mut self) {
+    let code_views = &self.code_views;
+    let modified = code_views.modified_revisions();
+    if let Err(err) = self.recovery.sync(&modified, |id| code_views.backup(id))
+    {
+      tracing::warn!("failed to back up unsaved changes: {}", err);
+    }
+  }
+
+  /// Notices changes to save automatically, and when to save them.
+  fn update_auto_save(&mut self) {
+    let mode = self.config.auto_save.mode;
+    let pending = if mode == AutoSave::Off {
+      vec![]
+    } else {
+      self.code_views.auto_saveable()
+    };
+    if pending != self.auto_save_pending {
+      self.auto_save_deadline = match mode {
+        AutoSave::AfterDelay if !pending.is_empty() => Some(
+          Instant::now() + Duration::from_millis(self.config.auto_save.delay),
+        ),
+        _ => None,
+      };
+      self.auto_save_pending = pending;
+    }
+
+    if mode == AutoSave::Off {
+      return self.status_bar.remove("auto-save");
+    }
+    let text = if self.auto_save_pending.is_empty() {
+      "Auto Save"
+    } else {
+      "Auto Save: Pending"
+    };
+    self.status_bar.set(
+      "auto-save",
+      Segment {
+        text: String::from(text),
+        alignment: Alignment::Right,
+        priority: 60,
+        command: None,
+      },
+    );
+  }
+
+  /// Saves the tabs waiting to be saved automatically.
+  fn auto_save(&mut self) {
+    self.auto_save_deadline = None;
+    let ids = self
+      .auto_save_pending
+      .iter()
+      .map(|(id, _)| *id)
+      .collect::<Vec<_>>();
+    for error in self.code_views.save_ids(&ids) {
+      self.notify_user(Level::Error, format!("Failed to auto save {}", error));
+    }
+    self.count_changes();
+    self.window.request_redraw();
+  }
+
+  /// Checks again whether the computer runs on battery, and how many files
+  /// changed, as other programs may have changed them.
+  pub fn focus_gained(&mut self) {
+    self.has_focus = true;
+    self.on_battery = power::on_battery();
+    self.count_changes();
+    self.window.request_redraw();
+  }
+
+  /// Saves the files with unsaved changes if they are saved on focus loss,
+  /// and leaves resize mode so the arrows move the cursor when coming back.
+  pub fn focus_lost(&mut self) {
+    self.has_focus = false;
+    if std::mem::take(&mut self.resize_mode) {
+      self.window.request_redraw();
+    }
+    self.update_auto_save();
+    if self.config.auto_save.mode == AutoSave::OnFocusLoss {
+      self.auto_save();
+    }
+  }
+
+  /// Asks whether to restore the unsaved changes of a run which didn't exit
+  /// cleanly.
+  fn offer_restore(&mut self) {
+    let names = self
+      .recovery
+      .leftovers()
+      .iter()
+      .map(|backup| backup.name.clone())
+      .collect::<Vec<_>>();
+    let message = match names.len() {
+      0 => return,
+      1 => format!(
+        "The editor didn't exit cleanly. Restore the unsaved changes to {}?",
+        names[0]
+      ),
+      count => format!(
+        "The editor didn't exit cleanly. Restore the unsaved changes to {} \
+         files: {}?",
+        count,
+        names.join(", ")
+      ),
+    };
+    self.dialog.open(
+      &self.device,
+      self.size.cast(),
+      message,
+      &["Restore", "Discard"],
+      1,
+    );
+    self.confirm = Some(Confirm::Restore);
+  }
+
+  /// Opens the backed up buffers with their unsaved changes.
+  fn restore(&mut self) {
+    let size = self.size.cast();
+    let backups = self
+      .recovery
+      .leftovers()
+      .into_iter()
+      .cloned()
+      .collect::<Vec<_>>();
+    for backup in backups {
+      let shown = match &backup.path {
+        Some(path) => self.code_views.show(&self.device, size, path.clone()),
+        None => Err(anyhow::anyhow!("buffer isn't backed by a file")),
+      };
+      match shown {
+        Ok(()) => {
+          if let Some(active) = self.code_views.active_view_mut() {
+            active.set_text(size, &backup.text);
+          }
+        }
+        // keep the changes to a file which is gone
+        Err(_) => self.code_views.add_unsaved(
+          &self.device,
+          size,
+          backup.name,
+          backup.text,
+        ),
+      }
+    }
+  }
+
+  /// Lists the open tabs and the files of the workspace to switch to, with
+  /// the previous tab selected if `previous` is set.
+  fn show_buffer_switcher(&mut self, previous: bool) {
+    self.command_palette.close();
+    let files = self.fs_tree.files();
+    self.buffer_switcher.open(
+      self.size.cast(),
+      self.code_views.recent(),
+      files,
+      previous,
+    );
+
+    // the symbols of the open tabs, those of the active one for `@`
+    let mut symbols = vec![];
+    let mut workspace = vec![];
+    for (active, path, outline) in self.code_views.outlines() {
+      for symbol in outline::flatten(&outline) {
+        if active {
+          symbols.push(SymbolEntry {
+            name: symbol.name.clone(),
+            detail: symbol.kind.to_string(),
+            action: SwitcherAction::Goto(symbol.row, symbol.column),
+          });
+        }
+        if let Some(path) = &path {
+          workspace.push(SymbolEntry {
+            name: symbol.name.clone(),
+            detail: format!("{} · {}", symbol.kind, self.relative_path(path)),
+            action: SwitcherAction::OpenAt(
+              path.clone(),
+              symbol.row,
+              symbol.column,
+            ),
+          });
+        }
+      }
+    }
+    self
+      .buffer_switcher
+      .set_symbols(self.size.cast(), symbols, workspace);
+    self.workspace_symbol_query = None;
+    self.pending_workspace_symbols.clear();
+  }
+
+  /// Moves the active tab to a new window, along with its unsaved changes.
+  fn move_tab_to_new_window(&mut self) {
+    let (name, code_view) = match self.code_views.take_active(self.size.cast())
+    {
+      Some(tab) => tab,
+      None => return,
+    };
+    self.new_window = Some(vec![Buffer::Moved {
+      name,
+      path: code_view.path().map(Path::to_path_buf),
+      document: Rc::clone(code_view.document()),
+      position: code_view.cursor_position(),
+      bookmarks: code_view.bookmarks().to_vec(),
+    }]);
+    self.window.request_redraw();
+  }
+
+  /// Lines the active tab was scrolled down since last asked, if scroll
+  /// lock is on, for the other windows to follow.
+  pub fn take_locked_scroll(&mut self) -> Option<f64> {
+    if !self.scroll_lock {
+      return None;
+    }
+    let active = self.code_views.active_view()?;
+    let document = Rc::downgrade(active.document());
+    let lines = -active.scroll_y() / self.font_height as f64;
+    match self.locked_scroll.replace((document.clone(), lines)) {
+      Some((previous, previous_lines))
+        if previous.ptr_eq(&document) && previous_lines != lines =>
+      {
+        Some(lines - previous_lines)
+      }
+      _ => None,
+    }
+  }
+
+  /// Scrolls the active tab `lines` down along with another window, if
+  /// scroll lock is on.
+  pub fn follow_scroll(&mut self, lines: f64) {
+    if !self.scroll_lock {
+      return;
+    }
+    let size = self.size.cast();
+    let offset = PhysicalPosition::new(0.0, -lines * self.font_height as f64);
+    if let Some(active) = self.code_views.active_view_mut() {
+      active.scroll(offset, size);
+      self.window.request_redraw();
+    }
+    // so it isn't passed back
+    self.take_locked_scroll();
+  }
+
+  /// Notes a key press or typed character, drawn once the inputs waiting
+  /// are handled.
+  pub fn input_received(&mut self) {
+    self.input_at.get_or_insert_with(Instant::now);
+  }
+
+  /// Draws the inputs received since the last frame in one go.
+  pub fn redraw_input(&mut self) -> Result<(), anyhow::Error> {
+    self.drawn_for_input = self.input_at.is_some();
+    if !self.drawn_for_input {
+      return Ok(());
+    }
+    self.redraw()
+  }
+
+  /// Whether the redraw the system asks for can be skipped, as the window
+  /// was just drawn for input.
+  pub fn drawn_for_input(&self) -> bool {
+    self.drawn_for_input
+  }
+
+  /// Whether the active tab was edited in another window since it was last
+  /// drawn.
+  pub fn is_behind(&self) -> bool {
+    matches!(self.code_views.active_view(), Some(active) if active.is_behind())
+  }
+
+  /// Closes the window, asking whether to save files with unsaved changes
+  /// first.
+  pub fn request_quit(&mut self) {
+    let modified = self.code_views.modified();
+    if modified.is_empty() {
+      self.quit = true;
+      return;
+    }
+    let message = if modified.len() == 1 {
+      format!("Save changes to {} before quitting?", modified[0])
+    } else {
+      format!(
+        "{} files have unsaved changes: {}. Save them before quitting?",
+        modified.len(),
+        modified.join(", ")
+      )
+    };
+    self.completion.close();
+    self.command_palette.close();
+    self.dialog.open(
+      &self.device,
+      self.size.cast(),
+      message,
+      &["Save", "Discard", "Cancel"],
+      2,
+    );
+    self.confirm = Some(Confirm::Quit);
+    self.window.request_redraw();
+  }
+
+  /// Acts on the button the dialog was answered with.
+  fn answer(&mut self, button: usize) {
+    let confirm = match self.confirm.take() {
+      Some(confirm) => confirm,
+      None => return,
+    };
+    match (confirm, button) {
+      (Confirm::CloseTab, 0) => match self.code_views.save_active() {
+        Ok(()) => self.code_views.close_active(self.size.cast()),
+        Err(err) => {
+          self.notify_user(Level::Error, format!("Failed to save: {}", err))
+        }
+      },
+      (Confirm::CloseTab, 1) => self.code_views.close_active(self.size.cast()),
+      (Confirm::Quit, 0) => match self.code_views.save_modified() {
+        Ok(()) => self.quit = true,
+        Err(err) => {
+          self.notify_user(Level::Error, format!("Failed to save: {}", err))
+        }
+      },
+      (Confirm::Quit, 1) => self.quit = true,
+      (Confirm::DownloadGrammar(extension), 0) => {
+        self.download_grammar(extension)
+      }
+      (Confirm::DownloadGrammar(extension), _) => {
+        self.declined_grammars.insert(extension);
+      }
+      (Confirm::Restore, button) => {
+        if button == 0 {
+          self.restore();
+        }
+        self.recovery.clear_leftovers();
+      }
+      _ => {}
+    }
+  }
+
+  /// Rewrites the literal being picked once the color picker is done.
+  fn pick(&mut self, action: Option<PickerAction>) {
+    let rgb = match action {
+      Some(PickerAction::Apply(rgb)) => rgb,
+      Some(PickerAction::Cancel) => {
+        self.picking = None;
+        return;
+      }
+      None => return,
+    };
+    if let (Some((line, literal)), Some(active)) =
+      (self.picking.take(), self.code_views.active_view_mut())
+    {
+      active.replace_range(
+        self.size.cast(),
+        line,
+        literal.start,
+        literal.end,
+        &colors::format(rgb, literal.format),
+      );
+    }
+  }
+
+  /// Asks whether to download a grammar for the file at `path` if there is
+  /// none and a place to download them from is configured.
+  fn offer_grammar(&mut self, path: &Path) {
+    let url = match &self.config.grammar_url {
+      Some(url) => url.clone(),
+      None => return,
+    };
+    let extension = match path.extension().and_then(|ext| ext.to_str()) {
+      Some(extension) => extension.to_string(),
+      None => return,
+    };
+    if self.dialog.visible
+      || self.pending_grammar.is_some()
+      || self.declined_grammars.contains(&extension)
+      || self.code_views.has_grammar(path)
+    {
+      return;
+    }
+
+    let host = url.split('/').nth(2).unwrap_or(&url);
+    self.dialog.open(
+      &self.device,
+      self.size.cast(),
+      format!(
+        "There is no grammar for .{} files. Download one from {}?",
+        extension, host
+      ),
+      &["Download", "Not Now"],
+      1,
+    );
+    self.confirm = Some(Confirm::DownloadGrammar(extension));
+  }
+
+  fn download_grammar(&mut self, extension: String) {
+    let url = match &self.config.grammar_url {
+      Some(url) => url.clone(),
+      None => return,
+    };
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let notify = Arc::clone(&self.notify);
+    let thread_extension = extension.clone();
+    std::thread::spawn(move || {
+      let _ = sender.send(grammars::download(&url, &thread_extension));
+      notify();
+    });
+    self.pending_grammar = Some((extension, receiver));
+  }
+
+  /// Marks the problems the running task reported since last called in the
+  /// gutters.
+  fn poll_problems(&mut self) {
+    if self.output_panel.poll_locations() {
+      self.code_views.set_problems(self.output_panel.locations());
+    }
+  }
+
+  /// Asks git in the background how many files of the workspace changed,
+  /// for the badge of the source control view.
+  fn count_changes(&mut self) {
+    if self.pending_changes.is_some() {
+      return;
+    }
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let notify = Arc::clone(&self.notify);
+    let workspace = self.workspace.clone();
+    std::thread::spawn(move || {
+      let _ = sender.send(scm::changed_files(&workspace));
+      notify();
+    });
+    self.pending_changes = Some(receiver);
+  }
+
+  fn poll_changes(&mut self) {
+    let result = match &self.pending_changes {
+      Some(receiver) => match receiver.try_recv() {
+        Ok(result) => result,
+        Err(TryRecvError::Empty) => return,
+        Err(TryRecvError::Disconnected) => {
+          Err(anyhow::anyhow!("git status was interrupted"))
+        }
+      },
+      None => return,
+    };
+    self.pending_changes = None;
+    let count = match result {
+      Ok(count) => Some(count),
+      Err(err) => {
+        // like when the workspace isn't a git repository
+        tracing::debug!("failed to count the changed files: {}", err);
+        None
+      }
+    };
+    self.activity_bar.set_badge(
+      self.size.cast(),
+      SidebarView::SourceControl,
+      count,
+    );
+  }
+
+  /// Shows the problems the last task reported on the explorer, which lists
+  /// the files they are in, and whether a task is running on the debug
+  /// view.
+  fn update_badges(&mut self) {
+    let problems = self.output_panel.locations().len();
+    let running = match &self.output_panel.task {
+      Some(task) if task.status().is_none() => 1,
+      _ => 0,
+    };
+    let size = self.size.cast();
+    self
+      .activity_bar
+      .set_badge(size, SidebarView::Explorer, Some(problems));
+    self
+      .activity_bar
+      .set_badge(size, SidebarView::Debug, Some(running));
+  }
+
+  fn poll_grammar(&mut self) {
+    let result = match &self.pending_grammar {
+      Some((_, receiver)) => match receiver.try_recv() {
+        Ok(result) => result,
+        Err(TryRecvError::Empty) => return,
+        Err(TryRecvError::Disconnected) => {
+          Err(anyhow::anyhow!("the download was interrupted"))
+        }
+      },
+      None => return,
+    };
+    let extension = match self.pending_grammar.take() {
+      Some((extension, _)) => extension,
+      None => return,
+    };
+    match result {
+      Ok(grammar) => {
+        let message = format!("Installed the {} grammar", grammar.name);
+        self.code_views.add_grammar(grammar);
+        self.notify_user(Level::Info, message);
+      }
+      Err(err) => {
+        // most likely offline, files are still highlighted generically
+        self.declined_grammars.insert(extension);
+        self.notify_user(
+          Level::Warning,
+          format!("Failed to download grammar: {}", err),
+        );
+      }
+    }
+  }
+
+  /// Tells the user which features were reduced to keep typing responsive.
+  fn report_degradations(&mut self) {
+    let name = self.code_views.active_name().unwrap_or_default();
+    let degradations = match self.code_views.active_view_mut() {
+      Some(view) => view.take_degradations(),
+      None => return,
+    };
+    for degradation in degradations {
+      self.notify_user(
+        Level::Warning,
+        format!(
+          "Typing in {} is slow ({}ms layout, {}ms highlighting per \
+           keystroke), so {}.",
+          name,
+          degradation.layout.as_millis(),
+          degradation.highlight.as_millis(),
+          degradation.reduction.description()
+        ),
+      );
+    }
+  }
+
+  /// Shows `view` in the sidebar. With `toggle` the sidebar is hidden
+  /// instead if it already shows `view`.
+  fn show_view(&mut self, view: SidebarView, toggle: bool) {
+    if toggle && self.sidebar_visible && self.activity_bar.active == view {
+      self.sidebar_visible = false;
+      self.focused = Panel::Editor;
+    } else {
+      self.sidebar_visible = true;
+      self.focused = Panel::Sidebar;
+      self.activity_bar.set_active(self.size.cast(), view);
+      self.placeholder.text =
+        format!("{}\n\nNothing to show yet.", view.name());
+    }
+    self.maximized = None;
+    self.zen = false;
+    self.layout();
+  }
+
+  /// Grows the focused panel by `amount` pixels, shrinking its neighbour.
+  fn resize_focused(&mut self, amount: f32) {
+    match self.focused {
+      Panel::Sidebar => self.sidebar_width += amount,
+      Panel::Editor => self.sidebar_width -= amount,
+    }
+    self.layout();
+  }
+
+  /// When [`Renderer::tick`] has to be called next, if at all.
+  pub fn deadline(&self) -> Option<Instant> {
+    let minimap = self.code_views.active_view().and_then(CodeView::deadline);
+    [
+      self.completion_deadline,
+      self.notifications.deadline(),
+      minimap,
+      self.recovery.deadline(),
+      self.auto_save_deadline,
+      self.momentum.deadline(),
+      self.frame_deadline,
+      self.pending_format.as_ref().map(|pending| pending.deadline),
+      self.lightbulb_deadline,
+    ]
+    .iter()
+    .flatten()
+    .min()
+    .copied()
+  }
+
+  /// Runs whatever was waiting for its deadline.
+  pub fn tick(&mut self) {
+    let now = Instant::now();
+    if matches!(self.completion_deadline, Some(deadline) if deadline <= now) {
+      self.completion_deadline = None;
+      self.show_completions(true);
+      self.window.request_redraw();
+    }
+    if self.notifications.expire(now) {
+      self.window.request_redraw();
+    }
+    if matches!(self.frame_deadline, Some(deadline) if deadline <= now) {
+      self.frame_deadline = None;
+      self.window.request_redraw();
+    }
+    let minimap = self.code_views.active_view().and_then(CodeView::deadline);
+    if matches!(minimap, Some(deadline) if deadline <= now) {
+      self.window.request_redraw();
+    }
+    if matches!(self.recovery.deadline(), Some(deadline) if deadline <= now) {
+      self.back_up();
+    }
+    if matches!(self.auto_save_deadline, Some(deadline) if deadline <= now) {
+      self.auto_save();
+    }
+    if matches!(self.lightbulb_deadline, Some(deadline) if deadline <= now) {
+      self.lightbulb_deadline = None;
+      // not to drop an answer the user waits for
+      if self.pending_code_actions.is_none() {
+        let _ = self.request_code_actions(CodeActionRequest::Lightbulb);
+      }
+    }
+    if matches!(&self.pending_format, Some(pending) if pending.deadline <= now)
+    {
+      self.poll_format();
+      self.window.request_redraw();
+    }
+    if matches!(self.momentum.deadline(), Some(deadline) if deadline <= now) {
+      if let Some(delta) = self.momentum.step(now) {
+        self.scroll(delta, self.momentum_position);
+        self.window.request_redraw();
+      }
+    }
+  }
+
+  /// Completions for the word in front of the cursor, along with that word.
+  /// The words of the buffer are only looked for again when the popup
+  /// opens, not for each character typed into it.
+  fn completions(&mut self) -> Option<(String, Vec<Item>)> {
+    let refresh = !self.completion.visible;
+    let active = self.code_views.active_view_mut()?;
+    let (row, column) = active.cursor_position();
+    let prefix =
+      completion::word_before(active.text().get(row)?, column).to_string();
+    if prefix.is_empty() {
+      return None;
+    }
+
+    let items = active
+      .words(refresh)
+      .iter()
+      .filter(|item| item.label != prefix)
+      .cloned()
+      .collect();
+    let items =
+      completion::filter_and_sort(items, &prefix, self.config.completion.sort);
+    Some((prefix, items))
+  }
+
+  /// Shows the completions at the cursor. With `auto_insert` the only
+  /// completion starting with the word is inserted right away if the config
+  /// asks for it.
+  fn show_completions(&mut self, auto_insert: bool) {
+    let (prefix, mut items) = match self.completions() {
+      Some((prefix, items)) if !items.is_empty() => (prefix, items),
+      _ => return self.completion.close(),
+    };
+
+    let mut prefixed = items
+      .iter()
+      .enumerate()
+      .filter(|(_, item)| item.label.starts_with(&prefix))
+      .map(|(i, _)| i);
+    let unique = match (prefixed.next(), prefixed.next()) {
+      (Some(i), None) => Some(i),
+      _ => None,
+    };
+    match unique {
+      Some(i) if auto_insert && self.config.completion.auto_insert_single => {
+        self.insert_completion(&prefix, items.swap_remove(i));
+      }
+      _ => {
+        let anchor = match self.code_views.active_view() {
+          Some(active) => active.cursor_dimensions(),
+          None => return,
+        };
+        self.code_action_menu = None;
+        self.completion.open(
+          self.size.cast(),
+          anchor,
+          items,
+          self.config.completion.show_documentation,
+        );
+      }
+    }
+  }
+
+  /// Replaces `prefix` in front of the cursor with `item`.
+  fn insert_completion(&mut self, prefix: &str, item: Item) {
+    let text = if !item.is_snippet {
+      item.insert_text
+    } else if self.config.completion.expand_snippets {
+      completion::expand_snippet(&item.insert_text)
+    } else {
+      item.label
+    };
+
+    let size = self.size.cast();
+    for _ in prefix.graphemes(true) {
+      self.code_views.input_char(size, '\u{7f}');
+    }
+    for ch in text.chars() {
+      self.code_views.input_char(size, ch);
+    }
+    self.completion.close();
+  }
+
+  fn accept_completion(&mut self) {
+    if let Some((server, mut actions)) = self.code_action_menu.take() {
+      let i = self.completion.selected_index();
+      self.completion.close();
+      if i < actions.len() {
+        self.apply_code_action(&server, actions.swap_remove(i));
+      }
+      return;
+    }
+    let item = self.completion.selected_item().cloned();
+    let prefix = self.completions().map(|(prefix, _)| prefix);
+    if let (Some(item), Some(prefix)) = (item, prefix) {
+      self.insert_completion(&prefix, item);
+    }
+    self.completion.close();
+  }
+
+  /// Updates the completions after `ch` got typed into the editor.
+  fn complete_typed(&mut self, ch: char) {
+    if !text::is_word_char(ch) || self.code_action_menu.is_some() {
+      self.completion.close();
+      self.completion_deadline = None;
+    } else if self.completion.visible {
+      self.show_completions(false);
+    } else {
+      self.completion_deadline = Some(
+        Instant::now()
+          + Duration::from_millis(self.config.completion.trigger_delay),
+      );
+    }
+  }
+
+  pub fn execute(&mut self, command: Command) {
+    // the dialog has to be answered first
+    if self.dialog.visible {
+      return;
+    }
+    self.completion.close();
+    self.color_picker.close();
+    self.picking = None;
+    match command {
+      Command::ShowCommandPalette => {
+        self.buffer_switcher.close();
+        if self.command_palette.visible {
+          self.command_palette.close();
+        } else {
+          let tasks = tasks::load(&self.workspace).keys().cloned().collect();
+          let shortcuts = Command::ALL
+            .iter()
+            .filter_map(|command| {
+              let combo = self.keymap.combo_for(*command)?;
+              Some((*command, self.keymap.describe(combo)))
+            })
+            .collect();
+          self.command_palette.open(
+            self.size.cast(),
+            self.layout_presets.names(),
+            tasks,
+            shortcuts,
+          );
+        }
+      }
+      Command::QuickOpen => self.show_buffer_switcher(false),
+      Command::SwitchBuffer => {
+        if self.buffer_switcher.visible {
+          self.buffer_switcher.select_next(self.size.cast());
+        } else {
+          self.show_buffer_switcher(true);
+        }
+      }
+      Command::ToggleDebugOverlay => {
+        self.debug_overlay.visible = !self.debug_overlay.visible;
+      }
+      Command::ResizeMode => self.resize_mode = true,
+      Command::GrowPanel => self.resize_focused(PANEL_RESIZE_STEP),
+      Command::ShrinkPanel => self.resize_focused(-PANEL_RESIZE_STEP),
+      Command::EqualizePanels => {
+        self.sidebar_width = default_sidebar_width(self.size.width as f32);
+        self.layout();
+      }
+      Command::Save => self.save_active(),
+      Command::SaveAs => {
+        if let Some(active) = self.code_views.active_view() {
+          let path = active
+            .path()
+            .map(|path| path.strip_prefix(&self.workspace).unwrap_or(path))
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+          self.command_palette.open_save_as(self.size.cast(), &path);
+        }
+      }
+      Command::NewFile => {
+        self.code_views.add_untitled(&self.device, self.size.cast());
+        self.focused = Panel::Editor;
+      }
+      Command::ChangeLanguage => {
+        if self.code_views.active_view().is_some() {
+          let languages = self.code_views.language_ids();
+          self
+            .command_palette
+            .open_language(self.size.cast(), languages);
+        }
+      }
+      Command::CloseTab => self.close_tab(),
+      Command::NewWindow => self.new_window = Some(vec![]),
+      Command::OpenFolder => self.command_palette.open_folder(self.size.cast()),
+      Command::MoveTabToNewWindow => self.move_tab_to_new_window(),
+      Command::PinTab => self.code_views.pin_active(),
+      Command::ToggleBookmark => {
+        if let Some(active) = self.code_views.active_view_mut() {
+          active.toggle_bookmark();
+        }
+      }
+      Command::Undo | Command::Redo => {
+        let size = self.size.cast();
+        if self.command_palette.visible {
+          let message = if command == Command::Redo {
+            Message::Redo
+          } else {
+            Message::Undo
+          };
+          self.command_palette.update_query(size, message);
+        } else if let Some(active) = self.code_views.active_view_mut() {
+          active.undo(size, command == Command::Redo);
+        }
+      }
+      Command::Paste => self.paste(),
+      Command::Copy | Command::Cut => self.copy(command == Command::Cut),
+      Command::SelectAll
+      | Command::SelectLeft
+      | Command::SelectRight
+      | Command::CursorWordLeft
+      | Command::CursorWordRight
+      | Command::SelectWordLeft
+      | Command::SelectWordRight => {
+        let message = match command {
+          Command::SelectAll => Message::SelectAll,
+          Command::SelectLeft => Message::Extend(Direction::Left),
+          Command::SelectRight => Message::Extend(Direction::Right),
+          _ => Message::MoveWord {
+            forward: matches!(
+              command,
+              Command::CursorWordRight | Command::SelectWordRight
+            ),
+            select: matches!(
+              command,
+              Command::SelectWordLeft | Command::SelectWordRight
+            ),
+          },
+        };
+        let size = self.size.cast();
+        if self.command_palette.visible {
+          self.command_palette.update_query(size, message);
+        } else if let Some(active) = self.code_views.active_view_mut() {
+          // the word being completed is left behind
+          active.move_cursor(size, message);
+          self.completion.close();
+        }
+      }
+      Command::AddCursorAbove | Command::AddCursorBelow => {
+        let size = self.size.cast();
+        let direction = if command == Command::AddCursorAbove {
+          Direction::Up
+        } else {
+          Direction::Down
+        };
+        if let Some(active) = self.code_views.active_view_mut() {
+          active.add_cursor(size, direction);
+        }
+      }
+      Command::ExpandSelection | Command::ShrinkSelection => {
+        let size = self.size.cast();
+        if let Some(active) = self.code_views.active_view_mut() {
+          if command == Command::ExpandSelection {
+            active.expand_selection(size);
+          } else {
+            active.shrink_selection(size);
+          }
+        }
+      }
+      Command::SortLinesAscending
+      | Command::SortLinesDescending
+      | Command::SortLinesUnique
+      | Command::ReverseLines
+      | Command::TransformToUppercase
+      | Command::TransformToLowercase
+      | Command::TransformToTitleCase
+      | Command::Base64Encode
+      | Command::Base64Decode
+      | Command::UrlEncode
+      | Command::UrlDecode => {
+        let size = self.size.cast();
+        if let (Some(active), Some(transform)) = (
+          self.code_views.active_view_mut(),
+          Transform::from_command(command),
+        ) {
+          if let Err(err) = active.transform(size, transform) {
+            let message = format!("Failed to transform: {}", err);
+            self.notify_user(Level::Error, message);
+          }
+        }
+      }
+      Command::ToggleScrollLock => {
+        self.scroll_lock = !self.scroll_lock;
+        self.locked_scroll = None;
+        self.take_locked_scroll();
+      }
+      Command::ToggleOverwrite => {
+        self.overwrite = !self.overwrite;
+        self
+          .code_views
+          .set_overwrite(self.size.cast(), self.overwrite);
+      }
+      Command::ToggleTypewriter => {
+        let typewriter = !self.code_views.typewriter();
+        self.code_views.set_typewriter(self.size.cast(), typewriter);
+      }
+      Command::NextBookmark | Command::PreviousBookmark => {
+        self.goto_marker(MarkerKind::Bookmark, command == Command::NextBookmark)
+      }
+      Command::NextProblem | Command::PreviousProblem => {
+        self.goto_marker(MarkerKind::Problem, command == Command::NextProblem)
+      }
+      Command::NextChange | Command::PreviousChange => {
+        self.goto_marker(MarkerKind::Change, command == Command::NextChange)
+      }
+      Command::NextMatch | Command::PreviousMatch => {
+        self.goto_marker(MarkerKind::Match, command == Command::NextMatch)
+      }
+      Command::ToggleOutput => {
+        self.output_panel.visible = !self.output_panel.visible;
+        self.layout();
+      }
+      Command::RunLastTask => {
+        let name = self.last_task.clone().unwrap_or_else(|| "build".into());
+        self.run_task(&name);
+      }
+      Command::StopTask => {
+        if let Some(task) = &self.output_panel.task {
+          task.stop();
+        }
+      }
+      Command::ToggleSidebar => {
+        self.sidebar_visible = !self.sidebar_visible;
+        if !self.sidebar_visible {
+          self.focused = Panel::Editor;
+        }
+        self.layout();
+      }
+      Command::ShowExplorer => self.show_view(SidebarView::Explorer, false),
+      Command::ShowOutline => self.show_view(SidebarView::Outline, false),
+      Command::ShowSearch => self.show_view(SidebarView::Search, false),
+      Command::ShowSourceControl => {
+        self.show_view(SidebarView::SourceControl, false)
+      }
+      Command::ShowDebug => self.show_view(SidebarView::Debug, false),
+      Command::ShowExtensions => self.show_view(SidebarView::Extensions, false),
+      Command::InstallPlugin => {
+        self.command_palette.open_install_plugin(self.size.cast())
+      }
+      Command::ReloadPlugins => {
+        self.reload_plugins();
+        self.notify_user(Level::Info, String::from("Reloaded plugins"));
+      }
+      Command::RestartLanguageServer => {
+        for server in &self.language_servers.servers {
+          server.restart();
+        }
+      }
+      Command::OpenServerLog => {
+        for server in &self.language_servers.servers {
+          self.code_views.add_unsaved(
+            &self.device,
+            self.size.cast(),
+            format!("{} log", server.name),
+            server.log(),
+          );
+        }
+      }
+      Command::ToggleMaximizePanel => {
+        self.maximized = match self.maximized {
+          Some(_) => None,
+          None => Some(self.focused),
+        };
+        self.layout();
+      }
+      Command::ToggleZenMode => {
+        self.zen = !self.zen;
+        self.focused = Panel::Editor;
+        self.layout();
+      }
+      Command::ToggleFullscreen => {
+        self.window.set_fullscreen(match self.window.fullscreen() {
+          Some(_) => None,
+          None => Some(Fullscreen::Borderless(None)),
+        });
+      }
+      Command::RenameSymbol => {
+        if let Some(active) = self.code_views.active_view() {
+          let (row, column) = active.cursor_position();
+          let name = active.text().get(row).map_or(String::new(), |line| {
+            completion::word_at(line, column).to_string()
+          });
+          self.command_palette.open_rename(self.size.cast(), &name);
+        }
+      }
+      Command::ShowCodeActions | Command::OrganizeImports => {
+        let request = if command == Command::OrganizeImports {
+          CodeActionRequest::OrganizeImports
+        } else {
+          CodeActionRequest::Menu
+        };
+        if let Err(err) = self.request_code_actions(request) {
+          let message = format!("Failed to get code actions: {}", err);
+          self.notify_user(Level::Error, message);
+        }
+      }
+      Command::ReplaceInFiles => {
+        let find =
+          self
+            .code_views
+            .active_view()
+            .map_or(String::new(), |active| {
+              let (row, column) = active.cursor_position();
+              active.text().get(row).map_or(String::new(), |line| {
+                completion::word_at(line, column).to_string()
+              })
+            });
+        self
+          .command_palette
+          .open_find_in_files(self.size.cast(), &find);
+      }
+    }
+    self.window.request_redraw();
+  }
+
+  pub fn input_special(&mut self, key: VirtualKeyCode) {
+    if self.dialog.visible {
+      if let Some(button) = self.dialog.input_special(self.size.cast(), key) {
+        self.answer(button);
+      }
+      return self.window.request_redraw();
+    }
+    if self.color_picker.visible {
+      let action = self.color_picker.input_special(self.size.cast(), key);
+      self.pick(action);
+      return self.window.request_redraw();
+    }
+
+    if self.resize_mode {
+      // arrows keep resizing, anything else leaves resize mode
+      match key {
+        VirtualKeyCode::Right | VirtualKeyCode::Up => {
+          return self.execute(Command::GrowPanel);
+        }
+        VirtualKeyCode::Left | VirtualKeyCode::Down => {
+          return self.execute(Command::ShrinkPanel);
+        }
+        // the characters typed are told apart when they arrive
+        _ if matches!(Key::from(key), Key::Char(_)) => return,
+        _ => self.resize_mode = false,
+      }
+    }
+
+    if self.completion.visible
+      && !self.command_palette.visible
+      && !self.buffer_switcher.visible
+    {
+      match key {
+        VirtualKeyCode::Escape => {
+          self.completion.close();
+          return self.window.request_redraw();
+        }
+        VirtualKeyCode::Left
+        | VirtualKeyCode::Right
+        | VirtualKeyCode::Home
+        | VirtualKeyCode::End
+        | VirtualKeyCode::PageUp
+        | VirtualKeyCode::PageDown => self.completion.close(),
+        _ => {
+          if self.completion.input_special(self.size.cast(), key) {
+            return self.window.request_redraw();
+          }
+        }
+      }
+    }
+
+    if self.command_palette.visible {
+      self.command_palette.input_special(self.size.cast(), key);
+    } else if self.buffer_switcher.visible {
+      self.buffer_switcher.input_special(self.size.cast(), key);
+    } else if self.rename_preview.visible {
+      self.rename_preview.input_special(self.size.cast(), key);
+    } else if self.code_views.is_empty() {
+      self.welcome.input_special(self.size.cast(), key);
+    } else {
+      self.code_views.input_special(self.size.cast(), key);
+    }
+    self.window.request_redraw();
+  }
+
+  /// Answers the dialog with the button `key` is the mnemonic of, returns
+  /// whether it did.
+  pub fn input_mnemonic(&mut self, key: VirtualKeyCode) -> bool {
+    let button = match Key::from(key) {
+      Key::Char(ch) if self.dialog.visible => self.dialog.input_mnemonic(ch),
+      _ => None,
+    };
+    match button {
+      Some(button) => {
+        self.answer(button);
+        self.window.request_redraw();
+        true
+      }
+      None => false,
+    }
+  }
+
+  pub fn input_char(&mut self, ch: char) {
+    if self.resize_mode {
+      if ch == '=' {
+        return self.execute(Command::EqualizePanels);
+      }
+      self.resize_mode = false;
+    }
+
+    if self.dialog.visible {
+      if let Some(button) = self.dialog.input_char(self.size.cast(), ch) {
+        self.answer(button);
+      }
+      return self.window.request_redraw();
+    }
+    if self.color_picker.visible {
+      let action = self.color_picker.input_char(self.size.cast(), ch);
+      self.pick(action);
+      return self.window.request_redraw();
+    }
+
+    if self.command_palette.visible {
+      match self.command_palette.input_char(self.size.cast(), ch) {
+        Some(PaletteAction::Run(command)) => self.execute(command),
+        Some(PaletteAction::Copy(text)) => {
+          if let Some(clipboard) = &mut self.clipboard {
+            if let Err(err) = clipboard.set_text(text) {
+              let message = format!("Failed to copy to clipboard: {}", err);
+              self.notify_user(Level::Error, message);
+            }
+          }
+        }
+        Some(PaletteAction::Insert(text)) => {
+          let size = self.size.cast();
+          if let Some(active) = self.code_views.active_view_mut() {
+            active.paste(size, text);
+          }
+        }
+        Some(PaletteAction::ApplyLayout(name)) => {
+          if let Some(preset) = self.layout_presets.get(&name) {
+            self.show_view(preset.sidebar_view, false);
+            self.sidebar_width = preset.sidebar_width;
+            self.sidebar_visible = preset.sidebar_visible;
+            self.focused = Panel::Editor;
+            self.maximized = preset.maximized;
+            if let Some(panel) = preset.maximized {
+              self.focused = panel;
+            }
+            self.layout();
+          }
+        }
+        Some(PaletteAction::SaveLayout(name)) => {
+          let preset = LayoutPreset {
+            sidebar_width: self.sidebar_width,
+            sidebar_visible: self.sidebar_visible,
+            sidebar_view: self.activity_bar.active,
+            maximized: self.maximized,
+          };
+          if let Err(err) = self.layout_presets.save(name, preset) {
+            let message = format!("Failed to save layout: {}", err);
+            self.notify_user(Level::Error, message);
+          }
+        }
+        Some(PaletteAction::RunTask(name)) => self.run_task(&name),
+        Some(PaletteAction::InstallPlugin(source)) => {
+          self.install_plugin(source)
+        }
+        Some(PaletteAction::OpenFolder(dir)) => self.open_folder(&dir),
+        Some(PaletteAction::FindInFiles(find)) => {
+          self.search = Some(find.clone());
+          self
+            .command_palette
+            .open_replace_in_files(self.size.cast(), find)
+        }
+        Some(PaletteAction::ReplaceInFiles(find, replacement)) => {
+          self.replace_in_files(&find, &replacement)
+        }
+        Some(PaletteAction::SaveAs(path)) => self.save_active_as(&path),
+        Some(PaletteAction::SetLanguage(language)) => {
+          self.code_views.choose_language(self.size.cast(), language)
+        }
+        Some(PaletteAction::Rename(name)) => {
+          if let Err(err) = self.request_rename(name) {
+            self
+              .notify_user(Level::Error, format!("Failed to rename: {}", err));
+          }
+        }
+        None => {}
+      }
+    } else if self.buffer_switcher.visible {
+      match self.buffer_switcher.input_char(self.size.cast(), ch) {
+        Some(SwitcherAction::Show(i)) => self.code_views.show_tab(i),
+        Some(SwitcherAction::Open(path)) => self.open_file(path, None),
+        Some(SwitcherAction::Goto(row, column)) => {
+          self.code_views.goto(self.size.cast(), row, column)
+        }
+        Some(SwitcherAction::OpenAt(path, row, column)) => {
+          self.open_file(path, Some((row, column)))
+        }
+        None => self.request_workspace_symbols(),
+      }
+    } else if self.rename_preview.visible {
+      if let Some(files) = self.rename_preview.input_char(ch) {
+        self.apply_edits(files);
+      }
+    } else if self.code_views.is_empty() {
+      if let Some(action) = self.welcome.input_char(ch) {
+        self.welcome_action(action);
+      }
+    } else if self.completion.visible
+      && (ch == '\t'
+        || (ch == '\r'
+          && (self.config.completion.accept_on_enter
+            || self.code_action_menu.is_some())))
+    {
+      self.accept_completion();
+    } else if ch == '\t' && self.config.insert_spaces_for(self.language()) {
+      self.complete_typed(ch);
+      for _ in 0..self.config.tab_width_for(self.language()) {
+        self.code_views.input_char(self.size.cast(), ' ');
+      }
+    } else {
+      self.code_views.input_char(self.size.cast(), ch);
+      self.complete_typed(ch);
+    }
+    self.window.request_redraw();
+  }
+
+  /// Inserts the text on the clipboard at the cursors.
+  fn paste(&mut self) {
+    let text = match self
+      .clipboard
+      .as_mut()
+      .map(|clipboard| clipboard.get_text())
+    {
+      Some(Ok(text)) => text,
+      Some(Err(err)) => {
+        let message = format!("Failed to paste from clipboard: {}", err);
+        return self.notify_user(Level::Error, message);
+      }
+      None => return,
+    };
+    let size = self.size.cast();
+    if self.command_palette.visible {
+      self.command_palette.paste(size, &text);
+    } else if let Some(active) = self.code_views.active_view_mut() {
+      active.paste(size, text);
+    }
+  }
+
+  /// Puts the selected text of the palette or the active tab on the
+  /// clipboard, removing it if `cut`.
+  fn copy(&mut self, cut: bool) {
+    let size = self.size.cast();
+    let text = if self.command_palette.visible {
+      if cut {
+        self.command_palette.cut(size)
+      } else {
+        self.command_palette.selected_text()
+      }
+    } else {
+      match self.code_views.active_view_mut() {
+        Some(active) if cut => active.cut(size),
+        Some(active) => active.copy(),
+        None => None,
+      }
+    };
+    if let (Some(text), Some(clipboard)) = (text, &mut self.clipboard) {
+      if let Err(err) = clipboard.set_text(text) {
+        let message = format!("Failed to copy to clipboard: {}", err);
+        self.notify_user(Level::Error, message);
+      }
+    }
+  }
+
+  /// Scales the font by `factor`, keeping the text at `anchor` in place.
+  pub fn zoom(&mut self, factor: f32, anchor: PhysicalPosition<f64>) {
+    let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    if (zoom - self.zoom).abs() < f32::EPSILON {
+      return;
+    }
+    self.zoom = zoom;
+    let previous = self.font_height;
+    self.set_font(self.font.clone(), self.config.font_size);
+    self.resize(self.size.cast());
+
+    let size = self.size.cast();
+    let ratio = self.font_height / previous;
+    if let Some(view) = self.code_views.active_view_mut() {
+      view.zoom_at(size, anchor.cast(), ratio);
+    }
+    self.window.request_redraw();
+  }
+
+  /// Zooms by the lines a mouse wheel turned while holding ctrl.
+  pub fn zoom_lines(&mut self, lines: f32, anchor: PhysicalPosition<f64>) {
+    self.zoom(ZOOM_STEP.powf(lines), anchor);
+  }
+
+  /// Zooms while two fingers pinch on a touch screen.
+  pub fn touch(&mut self, touch: Touch) {
+    if let Some((factor, center)) =
+      self.pinch.touch(touch.id, touch.phase, touch.location)
+    {
+      self.zoom(factor, center);
+    }
+  }
+
+  /// Scrolls by the lines a mouse wheel turned.
+  pub fn scroll_lines(
+    &mut self,
+    x: f32,
+    y: f32,
+    mouse_pos: PhysicalPosition<f64>,
+  ) {
+    self.momentum.stop();
+    self.scroll(
+      PhysicalPosition::new(
+        (x * self.font_height) as f64,
+        (y * self.font_height) as f64,
+      ),
+      mouse_pos,
+    );
+  }
+
+  /// Scrolls by the pixels a touchpad gesture moved, going on and slowing
+  /// down once it ends. macOS sends that part of the gesture itself.
+  pub fn scroll_pixels(
+    &mut self,
+    delta: PhysicalPosition<f64>,
+    phase: TouchPhase,
+    mouse_pos: PhysicalPosition<f64>,
+  ) {
+    let now = Instant::now();
+    match phase {
+      TouchPhase::Started | TouchPhase::Cancelled => self.momentum.stop(),
+      TouchPhase::Moved => self.momentum.track(now, delta),
+      TouchPhase::Ended if cfg!(target_os = "macos") => self.momentum.stop(),
+      TouchPhase::Ended => {
+        self.momentum.track(now, delta);
+        self.momentum.release(now);
+        self.momentum_position = mouse_pos;
+      }
+    }
+    self.scroll(delta, mouse_pos);
+  }
+
+  pub fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    mouse_pos: PhysicalPosition<f64>,
+  ) {
+    if self.dialog.visible {
+      return;
+    }
+    self.completion.close();
+    let self_size = self.size.cast();
+    for element in self.get_elements() {
+      if element
+        .get_dimensions()
+        .contains(mouse_pos.cast())
+        .is_some()
+      {
+        element.scroll(offset, self_size);
+        break;
+      }
+    }
+  }
+
+  pub fn click(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    state: ElementState,
+  ) {
+    if state == ElementState::Pressed {
+      self.completion.close();
+      self.resize_mode = false;
+      if self.notifications.click(position.cast()) {
+        self.window.request_redraw();
+        return;
+      }
+      if self.dialog.visible {
+        if let Some(pos) =
+          self.dialog.get_dimensions().contains(position.cast())
+        {
+          if let Some(button) = self.dialog.click_at(pos) {
+            self.answer(button);
+          }
+          self.window.request_redraw();
+        }
+        return;
+      }
+      if self.color_picker.visible {
+        let action =
+          match self.color_picker.get_dimensions().contains(position.cast()) {
+            Some(pos) => self.color_picker.click_at(self.size.cast(), pos),
+            None => {
+              self.color_picker.close();
+              Some(PickerAction::Cancel)
+            }
+          };
+        self.pick(action);
+        self.window.request_redraw();
+        return;
+      }
+      if self.rename_preview.visible {
+        if let Some(pos) = self
+          .rename_preview
+          .get_dimensions()
+          .contains(position.cast())
+        {
+          self.rename_preview.click_at(self.size.cast(), pos);
+          self.window.request_redraw();
+        }
+        return;
+      }
+
+      if let Some(pos) = self
+        .status_bar
+        .get_dimensions()
+        .contains(position.cast())
+        .filter(|_| !self.zen)
+      {
+        if let Some(command) = self.status_bar.command_at(pos) {
+          self.execute(command);
+        }
+        return;
+      }
+
+      if self.activity_bar_shown() {
+        if let Some(pos) =
+          self.activity_bar.get_dimensions().contains(position.cast())
+        {
+          if let Some(view) = self.activity_bar.item_at(pos) {
+            self.show_view(view, true);
+            self.window.request_redraw();
+          }
+          return;
+        }
+
+        self.focused = if self.sidebar_shown()
+          && self
+            .fs_tree
+            .get_dimensions()
+            .contains(position.cast())
+            .is_some()
+        {
+          Panel::Sidebar
+        } else {
+          Panel::Editor
+        };
+      }
+
+      if self.sidebar_shown()
+        && self.activity_bar.active == SidebarView::Explorer
+      {
+        if let Some(pos) =
+          self.fs_tree.get_dimensions().contains(position.cast())
+        {
+          if let Some((path, double)) = self.fs_tree.click_at(pos) {
+            self.open_from_explorer(path, double);
+          }
+          self.window.request_redraw();
+          return;
+        }
+      }
+
+      if self.sidebar_shown()
+        && self.activity_bar.active == SidebarView::Outline
+      {
+        if let Some(pos) =
+          self.outline.get_dimensions().contains(position.cast())
+        {
+          if let Some((row, column)) = self.outline.click_at(pos) {
+            self.code_views.goto(self.size.cast(), row, column);
+            self.focused = Panel::Editor;
+          }
+          self.window.request_redraw();
+          return;
+        }
+      }
+
+      if self.sidebar_shown()
+        && self.activity_bar.active == SidebarView::Extensions
+      {
+        if let Some(pos) =
+          self.plugin_panel.get_dimensions().contains(position.cast())
+        {
+          match self.plugin_panel.click_at(pos) {
+            Some(PluginAction::Install) => self.execute(Command::InstallPlugin),
+            Some(PluginAction::Reload) => self.execute(Command::ReloadPlugins),
+            Some(PluginAction::SetEnabled(name, enabled)) => {
+              self.set_plugin_enabled(&name, enabled)
+            }
+            None => {}
+          }
+          self.window.request_redraw();
+          return;
+        }
+      }
+
+      if self.output_shown() {
+        if let Some(pos) =
+          self.output_panel.get_dimensions().contains(position.cast())
+        {
+          if let Some(Location { path, line, column }) =
+            self.output_panel.click_at(pos)
+          {
+            self.open_file(path, Some((line, column)));
+          }
+          self.window.request_redraw();
+          return;
+        }
+      }
+
+      if let Some(swatch) = self
+        .code_views
+        .active_view()
+        .and_then(|active| active.swatch_at(position.cast()))
+      {
+        let (_, literal) = swatch;
+        self
+          .color_picker
+          .open(self.size.cast(), literal.rgb, literal.format);
+        self.picking = Some(swatch);
+        self.window.request_redraw();
+        return;
+      }
+
+      if self.code_views.is_empty() && self.editor_shown() {
+        if let Some(pos) =
+          self.welcome.get_dimensions().contains(position.cast())
+        {
+          if let Some(action) = self.welcome.click_at(self.size.cast(), pos) {
+            self.welcome_action(action);
+          }
+          self.window.request_redraw();
+          return;
+        }
+      }
+
+      self.dragging_tab = self.code_views.tab_at(position.cast());
+      let size = self.size.cast();
+      for element in self.get_elements() {
+        if let Some(pos) = element.get_dimensions().contains(position.cast()) {
+          element.click(pos.cast(), size);
+          self.window.request_redraw();
+          break;
+        }
+      }
+    } else {
+      if let Some(active) = self.code_views.active_view_mut() {
+        active.end_drag();
+      }
+      if !std::mem::take(&mut self.dragging_tab) {
+        return;
+      }
+      // a tab dropped outside of the window
+      let inside = position.x >= 0.0
+        && position.y >= 0.0
+        && position.x < self.size.width as f64
+        && position.y < self.size.height as f64;
+      if !inside {
+        self.execute(Command::MoveTabToNewWindow);
+      }
+    }
+  }
+
+  /// Extends a selection of lines being dragged in the gutter and shows
+  /// what the mouse is over.
+  pub fn mouse_moved(&mut self, position: PhysicalPosition<f64>) {
+    let size = self.size.cast();
+    if let Some(active) = self.code_views.active_view_mut() {
+      if active.drag_lines(size, position.cast()) {
+        self.window.request_redraw();
+      }
+    }
+    self.hover(position.cast());
+  }
+
+  /// Stops showing what the mouse was over once it left the window.
+  pub fn mouse_left(&mut self) {
+    self.hover(PhysicalPosition::new(-1.0, -1.0));
+  }
+
+  /// Lets the elements follow the mouse at `position`, setting the cursor
+  /// icon the one under it wants.
+  fn hover(&mut self, position: PhysicalPosition<f32>) {
+    let mut icon = None;
+    let mut changed = false;
+    for element in self.get_elements() {
+      let (wanted, redraw) = element.hover(position);
+      icon = icon.or(wanted);
+      changed |= redraw;
+    }
+    let icon = icon.unwrap_or(CursorIcon::Default);
+    if icon != self.cursor_icon {
+      self.cursor_icon = icon;
+      self.window.set_cursor_icon(icon);
+    }
+    if changed {
+      self.window.request_redraw();
+    }
+  }
+
+  pub fn redraw(&mut self) -> Result<(), anyhow::Error> {
+    let _span = tracing::info_span!("render").entered();
+    let start = Instant::now();
+    if self.hidden {
+      return Ok(());
+    }
+    match self.next_frame() {
+      Some(due) if due > start => {
+        self.frame_deadline = Some(due);
+        return Ok(());
+      }
+      _ => self.frame_deadline = None,
+    }
+    self.last_frame = Some(start);
+    TEXT_DRAWS.store(0, Ordering::Relaxed);
+    self.update_status();
+    self.poll_rename();
+    self.poll_code_actions();
+    self.update_lightbulb();
+    self.poll_format();
+    self.poll_workspace_symbols();
+    self.poll_install();
+    self.poll_grammar();
+    self.poll_problems();
+    self.poll_changes();
+    self.update_badges();
+    self.report_degradations();
+    self.back_up();
+    self.update_auto_save();
+    if let Some(active) = self.code_views.active_view_mut() {
+      active.set_search(self.search.as_deref());
+      active.prepare(&self.device, self.size.cast());
+    }
+    // the recent files change while tabs are open
+    let empty = self.code_views.is_empty();
+    if empty && !self.welcome_shown {
+      self.update_welcome();
+    }
+    self.welcome_shown = empty;
+    if self.activity_bar.active == SidebarView::Outline {
+      self.update_outline();
+    }
+
+    let mut encoder =
+      self
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+          label: Some("Redraw"),
+        });
+
+    let frame = self.swap_chain.get_current_frame()?.output;
+
+    // premultiplied, like the rectangles blended with the opacity
+    let opacity = self.config.window.opacity();
+    let [r, g, b] = self.config.theme.background.rgb();
+    let mut rect_draws = draw_rects(
+      &mut encoder,
+      &frame.view,
+      &self.queue,
+      &self.rectangle_render_pipeline,
+      self.size,
+      &self.get_rects(),
+      wgpu::LoadOp::Clear(wgpu::Color {
+        r: (r * opacity) as f64,
+        g: (g * opacity) as f64,
+        b: (b * opacity) as f64,
+        a: opacity as f64,
+      }),
+      opacity,
+    );
+
+    let sidebar_shown = self.sidebar_shown();
+    let editor_shown = self.editor_shown();
+    let sidebar_view = self.activity_bar.active;
+    let output_shown = self.output_shown();
+    let activity_bar_shown = self.activity_bar_shown();
+    let mut panels: Vec<&mut dyn RenderElement> = vec![];
+    if !self.zen {
+      panels.push(&mut self.status_bar);
+    }
+    if activity_bar_shown {
+      panels.push(&mut self.activity_bar);
+    }
+    if output_shown {
+      panels.push(&mut self.output_panel);
+    }
+    if sidebar_shown {
+      panels.push(match sidebar_view {
+        SidebarView::Explorer => &mut self.fs_tree,
+        SidebarView::Outline => &mut self.outline,
+        SidebarView::Extensions => &mut self.plugin_panel,
+        _ => &mut self.placeholder,
+      });
+    }
+    // the code draws from a glyph page of its own
+    if editor_shown && empty {
+      panels.push(&mut self.welcome);
+    } else if editor_shown {
+      self.code_views.redraw(
+        &mut self.glyph_pages.code,
+        &self.device,
+        &mut self.staging_belt,
+        &mut encoder,
+        &frame.view,
+        self.size,
+      );
+    }
+    for element in panels {
+      element.redraw(
+        &mut self.glyph_pages.ui,
+        &self.device,
+        &mut self.staging_belt,
+        &mut encoder,
+        &frame.view,
+        self.size,
+      );
+    }
+
+    // overlays get their own passes so they are drawn on top of all text
+    let mut overlays: Vec<&mut dyn RenderElement> = vec![];
+    if self.completion.visible {
+      overlays.push(&mut self.completion);
+    }
+    if self.rename_preview.visible {
+      overlays.push(&mut self.rename_preview);
+    }
+    if self.command_palette.visible {
+      overlays.push(&mut self.command_palette);
+    }
+    if self.buffer_switcher.visible {
+      overlays.push(&mut self.buffer_switcher);
+    }
+    if self.color_picker.visible {
+      overlays.push(&mut self.color_picker);
+    }
+    if self.dialog.visible {
+      overlays.push(&mut self.dialog);
+    }
+    if !self.notifications.is_empty() {
+      overlays.push(&mut self.notifications);
+    }
+    if self.debug_overlay.visible {
+      overlays.push(&mut self.debug_overlay);
+    }
+    for overlay in overlays {
+      rect_draws += draw_rects(
+        &mut encoder,
+        &frame.view,
+        &self.queue,
+        &self.rectangle_render_pipeline,
+        self.size,
+        &overlay.get_rects(),
+        wgpu::LoadOp::Load,
+        opacity,
+      );
+      overlay.redraw(
+        &mut self.glyph_pages.ui,
+        &self.device,
+        &mut self.staging_belt,
+        &mut encoder,
+        &frame.view,
+        self.size,
+      );
+    }
+
+    self.staging_belt.finish();
+    self.queue.submit(Some(encoder.finish()));
+    self.local_spawner.spawn(self.staging_belt.recall())?;
+    self.local_pool.run_until_stalled();
+    // presents it
+    drop(frame);
+    if let Some(input_at) = self.input_at.take() {
+      self.debug_overlay.record_latency(input_at.elapsed());
+    }
+
+    self.debug_overlay.record(
+      start,
+      debug_overlay::FrameStats {
+        frame_time: start.elapsed(),
+        rect_draws,
+        text_draws: TEXT_DRAWS.load(Ordering::Relaxed),
+      },
+    );
+    if self.debug_overlay.visible && self.has_focus {
+      // keep frames coming so the overlay reflects the actual frame rate
+      self.window.request_redraw();
+    }
+
+    Ok(())
+  }
+
+  /// When the next frame may be drawn, if the frame rate is capped as the
+  /// computer runs on battery.
+  fn next_frame(&self) -> Option<Instant> {
+    let fps = self
+      .config
+      .window
+      .battery_max_fps
+      .filter(|_| self.on_battery)?;
+    Some(self.last_frame? + Duration::from_secs_f64(1.0 / fps.max(1) as f64))
+  }
+
+  fn sidebar_shown(&self) -> bool {
+    if self.zen {
+      return false;
+    }
+    match self.maximized {
+      Some(panel) => panel == Panel::Sidebar,
+      None => self.sidebar_visible,
+    }
+  }
+
+  fn get_rects(&self) -> Vec<&rectangle::Rectangle> {
+    let mut vec = vec![];
+    if !self.zen {
+      vec.extend(self.status_bar.get_rects());
+    }
+    if self.activity_bar_shown() {
+      vec.extend(self.activity_bar.get_rects());
+    }
+    if self.editor_shown() {
+      if self.code_views.is_empty() {
+        vec.extend(self.welcome.get_rects());
+      } else {
+        vec.extend(self.code_views.get_rects());
+      }
+    }
+    if self.output_shown() {
+      vec.extend(self.output_panel.get_rects());
+    }
+    if self.sidebar_shown() {
+      vec.extend(match self.activity_bar.active {
+        SidebarView::Explorer => self.fs_tree.get_rects(),
+        SidebarView::Outline => self.outline.get_rects(),
+        SidebarView::Extensions => self.plugin_panel.get_rects(),
+        _ => self.placeholder.get_rects(),
+      });
+    }
+    vec
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn RenderElement> {
+    let output_shown = self.output_shown();
+    let editor_shown = self.editor_shown();
+    let mut vec: Vec<&mut dyn RenderElement> = vec![];
+    if self.sidebar_shown() {
+      vec.push(match self.activity_bar.active {
+        SidebarView::Explorer => &mut self.fs_tree,
+        SidebarView::Outline => &mut self.outline,
+        SidebarView::Extensions => &mut self.plugin_panel,
+        _ => &mut self.placeholder,
+      });
+    }
+    if output_shown {
+      vec.push(&mut self.output_panel);
+    }
+    if editor_shown {
+      vec.extend(self.code_views.get_elements());
+    }
+    vec
+  }
+
+  /// All top level elements, including hidden overlays and inactive tabs.
+  fn get_all_elements(&mut self) -> Vec<&mut dyn RenderElement> {
+    vec![
+      &mut self.status_bar,
+      &mut self.activity_bar,
+      &mut self.fs_tree,
+      &mut self.outline,
+      &mut self.placeholder,
+      &mut self.welcome,
+      &mut self.plugin_panel,
+      &mut self.code_views,
+      &mut self.output_panel,
+      &mut self.command_palette,
+      &mut self.buffer_switcher,
+      &mut self.completion,
+      &mut self.rename_preview,
+      &mut self.color_picker,
+      &mut self.dialog,
+      &mut self.notifications,
+      &mut self.debug_overlay,
+    ]
+  }
+}
+
+/// Draws `rects` at `opacity`, replacing what is below them.
+#[allow(clippy::too_many_arguments)]
+fn draw_rects(
+  encoder: &mut CommandEncoder,
+  target: &TextureView,
+  queue: &wgpu::Queue,
+  pipeline: &wgpu::RenderPipeline,
+  size: PhysicalSize<u32>,
+  rects: &[&rectangle::Rectangle],
+  load: wgpu::LoadOp<wgpu::Color>,
+  opacity: f32,
+) -> usize {
+  let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+    label: None,
+    color_attachments: &[wgpu::RenderPassColorAttachment {
+      view: target,
+      resolve_target: None,
+      ops: wgpu::Operations { load, store: true },
+    }],
+    depth_stencil_attachment: None,
+  });
+
+  rpass.set_pipeline(pipeline);
+  let opacity = opacity as f64;
+  rpass.set_blend_constant(wgpu::Color {
+    r: opacity,
+    g: opacity,
+    b: opacity,
+    a: opacity,
+  });
+  for rect in rects {
+    rect.write_buffer(queue);
+    rpass.set_vertex_buffer(0, rect.vertex_buffer.slice(..));
+    if let Some(ref region) = rect.region {
+      rpass.set_scissor_rect(region.x, region.y, region.width, region.height);
+    } else {
+      rpass.set_scissor_rect(0, 0, size.width, size.height);
+    }
+    rpass.draw(0..4, 0..1);
+  }
+
+  rects.len()
+}
+
+/// Draws everything queued on the glyph brush, scissored to `region` if one
+/// is given.
+fn draw_glyphs(
+  glyph_brush: &mut GlyphBrush<()>,
+  device: &Device,
+  staging_belt: &mut StagingBelt,
+  encoder: &mut CommandEncoder,
+  target: &TextureView,
+  size: PhysicalSize<u32>,
+  region: Option<Dimensions>,
+) {
+  TEXT_DRAWS.fetch_add(1, Ordering::Relaxed);
+  let transform = wgpu_glyph::orthographic_projection(size.width, size.height);
+  if let Some(region) = region {
+    glyph_brush.draw_queued_with_transform_and_scissoring(
+      device,
+      staging_belt,
+      encoder,
+      target,
+      transform,
+      region.into(),
+    )
+  } else {
+    glyph_brush.draw_queued_with_transform(
+      device,
+      staging_belt,
+      encoder,
+      target,
+      transform,
+    )
+  }
+  .unwrap();
+}
+
+/// How far glyphs lean to the right per pixel above the baseline.
+const SLANT: f32 = 0.2;
+
+/// Draws everything queued on the glyph brush leaning to the right around
+/// `baseline`, standing in for italics which the fonts lack.
+fn draw_glyphs_slanted(
+  glyph_brush: &mut GlyphBrush<()>,
+  device: &Device,
+  staging_belt: &mut StagingBelt,
+  encoder: &mut CommandEncoder,
+  target: &TextureView,
+  size: PhysicalSize<u32>,
+  baseline: f32,
+) {
+  TEXT_DRAWS.fetch_add(1, Ordering::Relaxed);
+  let projection = wgpu_glyph::orthographic_projection(size.width, size.height);
+  // x' = x + SLANT * (baseline - y), in column major order
+  #[rustfmt::skip]
+  let shear = [
+    1.0, 0.0, 0.0, 0.0,
+    -SLANT, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    SLANT * baseline, 0.0, 0.0, 1.0,
+  ];
+  let mut transform = [0.0; 16];
+  for column in 0..4 {
+    for row in 0..4 {
+      transform[column * 4 + row] = (0..4)
+        .map(|k| projection[k * 4 + row] * shear[column * 4 + k])
+        .sum();
+    }
+  }
+  glyph_brush
+    .draw_queued_with_transform(
+      device,
+      staging_belt,
+      encoder,
+      target,
+      transform,
+    )
+    .unwrap();
+}
+
+trait RenderElement {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    for element in self.get_elements() {
+      element.resize(screen_size);
+    }
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    for element in self.get_elements() {
+      element.set_theme(theme);
+    }
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    for element in self.get_elements() {
+      element.set_font(font, font_height);
+    }
+  }
+
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    for element in self.get_elements() {
+      element.scroll(offset, screen_size);
+    }
+  }
+
+  fn click(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    for element in self.get_elements() {
+      if let Some(pos) = element.get_dimensions().contains(position.cast()) {
+        element.click(pos.cast(), screen_size);
+        break;
+      }
+    }
+  }
+
+  /// Follows the mouse to `position` in the window, returning the cursor
+  /// icon wanted there, if any, and whether something changed to redraw.
+  fn hover(
+    &mut self,
+    position: PhysicalPosition<f32>,
+  ) -> (Option<CursorIcon>, bool) {
+    let mut icon = None;
+    let mut changed = false;
+    for element in self.get_elements() {
+      let (wanted, redraw) = element.hover(position);
+      icon = icon.or(wanted);
+      changed |= redraw;
     }
+    (icon, changed)
   }
 
   fn redraw(
@@ -326,7 +4277,7 @@ trait RenderElement {
   fn get_dimensions(&self) -> Dimensions;
 }
 
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
 pub struct Dimensions {
   x: f32,
   y: f32,
@@ -0,0 +1,272 @@
+use crate::config::Theme;
+use crate::renderer::input::line_length;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use std::time::{Duration, Instant};
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+const MARGIN: f32 = 10.0;
+const PADDING: f32 = 8.0;
+const STRIPE_WIDTH: f32 = 4.0;
+const MAX_WIDTH: f32 = 400.0;
+const MAX_SHOWN: usize = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Level {
+  Info,
+  Warning,
+  Error,
+}
+
+impl Level {
+  fn timeout(self) -> Duration {
+    match self {
+      Level::Info => Duration::from_secs(4),
+      Level::Warning => Duration::from_secs(8),
+      Level::Error => Duration::from_secs(15),
+    }
+  }
+
+  fn color(self, theme: &Theme) -> [f32; 3] {
+    match self {
+      Level::Info => theme.info.rgb(),
+      Level::Warning => theme.warning.rgb(),
+      Level::Error => theme.error.rgb(),
+    }
+  }
+}
+
+struct Toast {
+  level: Level,
+  message: String,
+  expires: Instant,
+  rect: Rectangle,
+  /// Marks the level on the left edge.
+  stripe: Rectangle,
+}
+
+/// Messages stacked in the top right corner which disappear after a while
+/// or when clicked.
+pub struct Notifications {
+  font: FontArc,
+  font_height: f32,
+  theme: Theme,
+  toasts: Vec<Toast>,
+  screen_size: PhysicalSize<f32>,
+}
+
+impl Notifications {
+  pub fn new(
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    Self {
+      font,
+      font_height,
+      theme,
+      toasts: vec![],
+      screen_size,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.toasts.is_empty()
+  }
+
+  /// Shows `message`, an identical message which is still shown only gets
+  /// its timeout reset.
+  pub fn push(&mut self, device: &Device, level: Level, message: String) {
+    let expires = Instant::now() + level.timeout();
+    if let Some(toast) = self
+      .toasts
+      .iter_mut()
+      .find(|toast| toast.level == level && toast.message == message)
+    {
+      toast.expires = expires;
+      return;
+    }
+
+    let dimensions = Dimensions::default();
+    self.toasts.push(Toast {
+      level,
+      message,
+      expires,
+      rect: Rectangle::new(
+        device,
+        self.screen_size,
+        dimensions,
+        self.theme.overlay.rgb(),
+        None,
+      ),
+      stripe: Rectangle::new(
+        device,
+        self.screen_size,
+        dimensions,
+        level.color(&self.theme),
+        None,
+      ),
+    });
+    if self.toasts.len() > MAX_SHOWN {
+      self.toasts.remove(0);
+    }
+    self.layout();
+  }
+
+  /// When the next toast disappears.
+  pub fn deadline(&self) -> Option<Instant> {
+    self.toasts.iter().map(|toast| toast.expires).min()
+  }
+
+  /// Removes the toasts which timed out, returns whether there were any.
+  pub fn expire(&mut self, now: Instant) -> bool {
+    let count = self.toasts.len();
+    self.toasts.retain(|toast| toast.expires > now);
+    if self.toasts.len() == count {
+      return false;
+    }
+    self.layout();
+    true
+  }
+
+  /// Dismisses the toast at `position`, returns whether there was one.
+  pub fn click(&mut self, position: PhysicalPosition<f32>) -> bool {
+    let clicked = self
+      .toasts
+      .iter()
+      .position(|toast| toast.rect.dimensions.contains(position).is_some());
+    match clicked {
+      Some(i) => {
+        self.toasts.remove(i);
+        self.layout();
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn width(&self) -> f32 {
+    (self.screen_size.width * 0.4).min(MAX_WIDTH)
+  }
+
+  fn text_width(&self) -> f32 {
+    self.width() - STRIPE_WIDTH - (PADDING * 2.0)
+  }
+
+  /// Lines `message` takes up once wrapped.
+  fn lines(&self, message: &str) -> usize {
+    let width = self.text_width().max(1.0);
+    message
+      .lines()
+      .map(|line| {
+        let length = line_length(line, self.font.clone(), self.font_height);
+        ((length / width).ceil() as usize).max(1)
+      })
+      .sum::<usize>()
+      .max(1)
+  }
+
+  fn layout(&mut self) {
+    let width = self.width();
+    let x = self.screen_size.width - width - MARGIN;
+    let mut y = MARGIN;
+    for i in (0..self.toasts.len()).rev() {
+      let lines = self.lines(&self.toasts[i].message);
+      let dimensions = Dimensions {
+        x,
+        y,
+        width,
+        height: (lines as f32 * self.font_height) + (PADDING * 2.0),
+      };
+      let toast = &mut self.toasts[i];
+      toast.rect.resize(self.screen_size, dimensions);
+      toast.stripe.resize(
+        self.screen_size,
+        Dimensions {
+          width: STRIPE_WIDTH,
+          ..dimensions
+        },
+      );
+      y += dimensions.height + MARGIN;
+    }
+  }
+}
+
+impl super::RenderElement for Notifications {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.screen_size = screen_size;
+    self.layout();
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    for toast in &mut self.toasts {
+      toast.rect.set_color(theme.overlay.rgb());
+      toast.stripe.set_color(toast.level.color(theme));
+    }
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+    self.layout();
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let color = self.theme.overlay_text.rgba();
+    for toast in &self.toasts {
+      let rect = toast.rect.dimensions;
+      glyph_brush.queue(Section {
+        screen_position: (rect.x + STRIPE_WIDTH + PADDING, rect.y + PADDING),
+        bounds: (self.text_width(), f32::INFINITY),
+        text: vec![Text::new(&toast.message)
+          .with_color(color)
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      None,
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    self
+      .toasts
+      .iter()
+      .flat_map(|toast| vec![&toast.rect, &toast.stripe])
+      .collect()
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    Dimensions {
+      width: self.screen_size.width,
+      height: self.screen_size.height,
+      ..Dimensions::default()
+    }
+  }
+}
@@ -0,0 +1,231 @@
+use crate::config::Theme;
+use crate::outline::Symbol;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use std::collections::HashSet;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+const PADDING: f32 = 10.0;
+
+struct Row<'a> {
+  depth: usize,
+  symbol: &'a Symbol,
+  /// Names of the symbol and its parents, identifying it across edits.
+  key: String,
+}
+
+/// The sidebar listing the symbols of the active file.
+pub struct OutlineView {
+  rect: Rectangle,
+  font_height: f32,
+  theme: Theme,
+  symbols: Vec<Symbol>,
+  /// Revision of the code view the symbols belong to.
+  pub revision: Option<(usize, usize)>,
+  folded: HashSet<String>,
+  scroll_offset: f64,
+  dimensions: Dimensions,
+}
+
+impl OutlineView {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.sidebar.rgb(),
+        None,
+      ),
+      font_height,
+      theme,
+      symbols: vec![],
+      revision: None,
+      folded: HashSet::new(),
+      scroll_offset: 0.0,
+      dimensions,
+    }
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.rect.resize(screen_size, dimensions);
+  }
+
+  pub fn set_symbols(
+    &mut self,
+    revision: Option<(usize, usize)>,
+    symbols: Vec<Symbol>,
+  ) {
+    if revision.map(|(id, _)| id) != self.revision.map(|(id, _)| id) {
+      self.folded.clear();
+      self.scroll_offset = 0.0;
+    }
+    self.revision = revision;
+    self.symbols = symbols;
+  }
+
+  fn rows(&self) -> Vec<Row<'_>> {
+    fn walk<'a>(
+      symbols: &'a [Symbol],
+      depth: usize,
+      parent: &str,
+      folded: &HashSet<String>,
+      rows: &mut Vec<Row<'a>>,
+    ) {
+      for symbol in symbols {
+        let key = format!("{}/{}", parent, symbol.name);
+        let open = !folded.contains(&key);
+        rows.push(Row {
+          depth,
+          symbol,
+          key: key.clone(),
+        });
+        if open {
+          walk(&symbol.children, depth + 1, &key, folded, rows);
+        }
+      }
+    }
+
+    let mut rows = vec![];
+    walk(&self.symbols, 0, "", &self.folded, &mut rows);
+    rows
+  }
+
+  /// Handles a click at `position`, relative to the view. Clicking the
+  /// marker of a symbol folds or unfolds it, clicking anywhere else on it
+  /// returns its position to jump to.
+  pub fn click_at(
+    &mut self,
+    position: PhysicalPosition<f32>,
+  ) -> Option<(usize, usize)> {
+    let index = ((position.y as f64 - PADDING as f64 - self.scroll_offset)
+      / self.font_height as f64)
+      .floor();
+    if index < 0.0 {
+      return None;
+    }
+
+    let rows = self.rows();
+    let row = rows.get(index as usize)?;
+    let marker = PADDING + (row.depth as f32 * self.font_height);
+    if !row.symbol.children.is_empty()
+      && position.x >= marker
+      && position.x < marker + self.font_height
+    {
+      let key = row.key.clone();
+      if !self.folded.remove(&key) {
+        self.folded.insert(key);
+      }
+      None
+    } else {
+      Some((row.symbol.row, row.symbol.column))
+    }
+  }
+}
+
+impl super::RenderElement for OutlineView {
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    _screen_size: PhysicalSize<f32>,
+  ) {
+    let rows = self.rows().len() as f64;
+    self.scroll_offset = (self.scroll_offset + offset.y)
+      .min(0.0)
+      .max(-((rows - 3.0).max(0.0) * self.font_height as f64));
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.sidebar.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let color = self.theme.sidebar_text.rgba();
+    let x = self.dimensions.x + PADDING;
+    let y = self.dimensions.y + PADDING + self.scroll_offset as f32;
+
+    let rows = self.rows();
+    if rows.is_empty() {
+      glyph_brush.queue(Section {
+        screen_position: (x, y),
+        bounds: (self.dimensions.width - (PADDING * 2.0), f32::INFINITY),
+        text: vec![Text::new("No symbols found.")
+          .with_color(color)
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+    for (i, row) in rows.iter().enumerate() {
+      let marker = if row.symbol.children.is_empty() {
+        " "
+      } else if self.folded.contains(&row.key) {
+        ">"
+      } else {
+        "v"
+      };
+      glyph_brush.queue(Section {
+        screen_position: (
+          x + (row.depth as f32 * self.font_height),
+          y + (i as f32 * self.font_height),
+        ),
+        text: vec![Text::new(&format!(
+          "{} {} {}",
+          marker, row.symbol.kind, row.symbol.name
+        ))
+        .with_color(color)
+        .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
@@ -0,0 +1,252 @@
+use crate::config::Theme;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use crate::tasks::{self, Location, Task};
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+const PADDING: f32 = 6.0;
+
+/// The panel below the editor showing the output of the last task.
+pub struct OutputPanel {
+  pub visible: bool,
+  pub task: Option<Task>,
+  /// The file locations in the output of the task, in order, and how many
+  /// of its lines they were looked for in, counting dropped ones. `None`
+  /// until looked for in its output.
+  locations: Vec<Location>,
+  parsed: Option<usize>,
+  rect: Rectangle,
+  title_rect: Rectangle,
+  font_height: f32,
+  theme: Theme,
+  /// Lines scrolled up from the end, the panel follows the output at 0.
+  scroll_lines: usize,
+  dimensions: Dimensions,
+}
+
+impl OutputPanel {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      visible: false,
+      task: None,
+      locations: vec![],
+      parsed: None,
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.sidebar.rgb(),
+        None,
+      ),
+      title_rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.tab_bar.rgb(),
+        None,
+      ),
+      font_height,
+      theme,
+      scroll_lines: 0,
+      dimensions,
+    }
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.rect.resize(screen_size, dimensions);
+    self.title_rect.resize(
+      screen_size,
+      Dimensions {
+        height: self.title_height(),
+        ..dimensions
+      },
+    );
+  }
+
+  fn title_height(&self) -> f32 {
+    self.font_height + (PADDING * 2.0)
+  }
+
+  fn visible_lines(&self) -> usize {
+    ((self.dimensions.height - self.title_height() - PADDING)
+      / self.font_height)
+      .max(0.0) as usize
+  }
+
+  /// Shows the output of `task`, stopping the previous one.
+  pub fn run(&mut self, task: Task) {
+    if let Some(previous) = &self.task {
+      previous.stop();
+    }
+    self.task = Some(task);
+    self.locations.clear();
+    self.parsed = None;
+    self.scroll_lines = 0;
+    self.visible = true;
+  }
+
+  /// Index of the first line shown and the lines shown.
+  fn window(&self, total: usize) -> (usize, usize) {
+    let count = self.visible_lines().min(total);
+    let first = total.saturating_sub(count + self.scroll_lines);
+    (first, count)
+  }
+
+  /// The file locations in the output of the last task, in order, as of
+  /// the last [`OutputPanel::poll_locations`].
+  pub fn locations(&self) -> &[Location] {
+    &self.locations
+  }
+
+  /// Looks for file locations in the lines the task printed since last
+  /// called. Returns whether the locations changed.
+  pub fn poll_locations(&mut self) -> bool {
+    let task = match &self.task {
+      Some(task) => task,
+      None => return false,
+    };
+    let output = task.output.lock().unwrap();
+    let parsed = self.parsed.unwrap_or(0);
+    let found = output
+      .lines
+      .iter()
+      .skip(parsed.saturating_sub(output.dropped))
+      .filter_map(|line| tasks::parse_location(line, &task.cwd))
+      .collect::<Vec<_>>();
+    let changed = self.parsed.is_none() || !found.is_empty();
+    self.parsed = Some(output.dropped + output.lines.len());
+    self.locations.extend(found);
+    changed
+  }
+
+  /// The file location on the line at `position`, relative to the panel.
+  pub fn click_at(&self, position: PhysicalPosition<f32>) -> Option<Location> {
+    let task = self.task.as_ref()?;
+    let index = (position.y - self.title_height()) / self.font_height;
+    if index < 0.0 {
+      return None;
+    }
+    let output = task.output.lock().unwrap();
+    let (first, count) = self.window(output.lines.len());
+    if index as usize >= count {
+      return None;
+    }
+    tasks::parse_location(&output.lines[first + index as usize], &task.cwd)
+  }
+
+  fn title(&self) -> String {
+    match &self.task {
+      Some(task) => match task.status() {
+        None => format!("Output: {} (running)", task.name),
+        Some(status) if status.success() => format!("Output: {}", task.name),
+        Some(_) => format!("Output: {} (failed)", task.name),
+      },
+      None => String::from("Output"),
+    }
+  }
+}
+
+impl super::RenderElement for OutputPanel {
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    _screen_size: PhysicalSize<f32>,
+  ) {
+    let total = match &self.task {
+      Some(task) => task.output.lock().unwrap().lines.len(),
+      None => 0,
+    };
+    let lines = (offset.y / self.font_height as f64).round() as isize;
+    let max = total.saturating_sub(self.visible_lines()) as isize;
+    self.scroll_lines =
+      (self.scroll_lines as isize + lines).max(0).min(max) as usize;
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.sidebar.rgb());
+    self.title_rect.set_color(theme.tab_bar.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let x = self.dimensions.x + PADDING;
+    glyph_brush.queue(Section {
+      screen_position: (x, self.dimensions.y + PADDING),
+      text: vec![Text::new(&self.title())
+        .with_color(self.theme.tab_text.rgba())
+        .with_scale(self.font_height)],
+      ..Section::default()
+    });
+
+    if let Some(task) = &self.task {
+      let output = task.output.lock().unwrap();
+      let (first, count) = self.window(output.lines.len());
+      let y = self.dimensions.y + self.title_height();
+      let lines = output.lines.iter().skip(first).take(count);
+      for (i, line) in lines.enumerate() {
+        // lines with a location can be clicked to jump to it
+        let color = if tasks::parse_location(line, &task.cwd).is_some() {
+          self.theme.error
+        } else {
+          self.theme.sidebar_text
+        };
+        glyph_brush.queue(Section {
+          screen_position: (x, y + (i as f32 * self.font_height)),
+          text: vec![Text::new(line)
+            .with_color(color.rgba())
+            .with_scale(self.font_height)],
+          ..Section::default()
+        });
+      }
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect, &self.title_rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
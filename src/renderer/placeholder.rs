@@ -0,0 +1,107 @@
+use crate::config::Theme;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::PhysicalSize;
+
+const PADDING: f32 = 10.0;
+
+/// A sidebar panel that only shows a message.
+pub struct Placeholder {
+  rect: Rectangle,
+  font_height: f32,
+  theme: Theme,
+  pub text: String,
+  dimensions: Dimensions,
+}
+
+impl Placeholder {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.sidebar.rgb(),
+        None,
+      ),
+      font_height,
+      theme,
+      text: String::new(),
+      dimensions,
+    }
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.rect.resize(screen_size, dimensions);
+  }
+}
+
+impl super::RenderElement for Placeholder {
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.sidebar.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    glyph_brush.queue(Section {
+      screen_position: (
+        self.dimensions.x + PADDING,
+        self.dimensions.y + PADDING,
+      ),
+      bounds: (self.dimensions.width - (PADDING * 2.0), f32::INFINITY),
+      text: vec![Text::new(&self.text)
+        .with_color(self.theme.sidebar_text.rgba())
+        .with_scale(self.font_height)],
+      ..Section::default()
+    });
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
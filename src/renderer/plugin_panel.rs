@@ -0,0 +1,245 @@
+use crate::config::{Color, Theme};
+use crate::plugins::PluginHost;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+const PADDING: f32 = 10.0;
+
+/// What clicking a row of the panel does.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PluginAction {
+  Install,
+  Reload,
+  SetEnabled(String, bool),
+}
+
+#[derive(Copy, Clone)]
+enum Style {
+  Action,
+  Text,
+  Detail,
+  Permission,
+  Error,
+}
+
+impl Style {
+  fn color(self, theme: &Theme) -> Color {
+    match self {
+      Style::Action => theme.info,
+      Style::Text => theme.sidebar_text,
+      Style::Detail => theme.comment,
+      Style::Permission => theme.warning,
+      Style::Error => theme.error,
+    }
+  }
+}
+
+struct Row {
+  text: String,
+  style: Style,
+  action: Option<PluginAction>,
+}
+
+/// The extensions sidebar, listing the installed plugins.
+pub struct PluginPanel {
+  rect: Rectangle,
+  font_height: f32,
+  theme: Theme,
+  rows: Vec<Row>,
+  scroll_offset: f64,
+  dimensions: Dimensions,
+}
+
+impl PluginPanel {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.sidebar.rgb(),
+        None,
+      ),
+      font_height,
+      theme,
+      rows: vec![],
+      scroll_offset: 0.0,
+      dimensions,
+    }
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.rect.resize(screen_size, dimensions);
+  }
+
+  /// Lists the plugins of `host`.
+  pub fn update(&mut self, host: &PluginHost) {
+    let row = |text: String, style, action| Row {
+      text,
+      style,
+      action,
+    };
+    let mut rows = vec![
+      row(
+        String::from("+ Install Plugin"),
+        Style::Action,
+        Some(PluginAction::Install),
+      ),
+      row(
+        String::from("~ Reload Plugins"),
+        Style::Action,
+        Some(PluginAction::Reload),
+      ),
+    ];
+    if host.plugins.is_empty() && host.errors.is_empty() {
+      rows.push(row(String::new(), Style::Text, None));
+      rows.push(row(
+        String::from("No plugins installed."),
+        Style::Text,
+        None,
+      ));
+    }
+
+    for plugin in &host.plugins {
+      let manifest = &plugin.manifest;
+      rows.push(row(String::new(), Style::Text, None));
+      rows.push(row(
+        format!(
+          "[{}] {} {}",
+          if plugin.enabled { "x" } else { " " },
+          manifest.name,
+          manifest.version
+        ),
+        Style::Text,
+        Some(PluginAction::SetEnabled(
+          manifest.name.clone(),
+          !plugin.enabled,
+        )),
+      ));
+      if !manifest.description.is_empty() {
+        rows.push(row(
+          format!("    {}", manifest.description),
+          Style::Detail,
+          None,
+        ));
+      }
+      if !manifest.permissions.is_empty() {
+        let permissions = manifest
+          .permissions
+          .iter()
+          .map(|permission| permission.description())
+          .collect::<Vec<_>>();
+        rows.push(row(
+          format!("    {}", permissions.join(", ")),
+          Style::Permission,
+          None,
+        ));
+      }
+    }
+
+    for (name, error) in &host.errors {
+      rows.push(row(String::new(), Style::Text, None));
+      rows.push(row(format!("[!] {}", name), Style::Error, None));
+      rows.push(row(format!("    {}", error), Style::Error, None));
+    }
+    self.rows = rows;
+  }
+
+  /// The action of the row at `position`, relative to the panel.
+  pub fn click_at(
+    &self,
+    position: PhysicalPosition<f32>,
+  ) -> Option<PluginAction> {
+    let index = ((position.y as f64 - PADDING as f64 - self.scroll_offset)
+      / self.font_height as f64)
+      .floor();
+    if index < 0.0 {
+      return None;
+    }
+    self.rows.get(index as usize)?.action.clone()
+  }
+}
+
+impl super::RenderElement for PluginPanel {
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    _screen_size: PhysicalSize<f32>,
+  ) {
+    let rows = self.rows.len() as f64;
+    self.scroll_offset = (self.scroll_offset + offset.y)
+      .min(0.0)
+      .max(-((rows - 3.0).max(0.0) * self.font_height as f64));
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.sidebar.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let y = self.dimensions.y + PADDING + self.scroll_offset as f32;
+    for (i, row) in self.rows.iter().enumerate() {
+      glyph_brush.queue(Section {
+        screen_position: (
+          self.dimensions.x + PADDING,
+          y + (i as f32 * self.font_height),
+        ),
+        text: vec![Text::new(&row.text)
+          .with_color(row.style.color(&self.theme).rgba())
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
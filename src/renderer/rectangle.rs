@@ -85,7 +85,24 @@ impl Rectangle {
       fragment: Some(wgpu::FragmentState {
         module: &shader,
         entry_point: "fs_main",
-        targets: &[super::RENDER_FORMAT.into()],
+        // the colors are scaled by the opacity set as the blend constant,
+        // premultiplied as the window is composited
+        targets: &[wgpu::ColorTargetState {
+          format: super::render_format(),
+          blend: Some(wgpu::BlendState {
+            color: wgpu::BlendComponent {
+              src_factor: wgpu::BlendFactor::Constant,
+              dst_factor: wgpu::BlendFactor::Zero,
+              operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+              src_factor: wgpu::BlendFactor::Constant,
+              dst_factor: wgpu::BlendFactor::Zero,
+              operation: wgpu::BlendOperation::Add,
+            },
+          }),
+          write_mask: wgpu::ColorWrite::ALL,
+        }],
       }),
       primitive: wgpu::PrimitiveState {
         topology: wgpu::PrimitiveTopology::TriangleStrip,
@@ -150,6 +167,13 @@ impl Rectangle {
     self.vertices = Self::create_vertices(pos, end_pos, self.color);
   }
 
+  pub fn set_color(&mut self, color: [f32; 3]) {
+    self.color = color;
+    for vertex in &mut self.vertices {
+      vertex.color = color;
+    }
+  }
+
   pub fn write_buffer(&self, queue: &wgpu::Queue) {
     queue.write_buffer(
       &self.vertex_buffer,
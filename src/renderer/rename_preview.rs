@@ -0,0 +1,349 @@
+use crate::config::Theme;
+use crate::rename::FileEdits;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use std::path::Path;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::VirtualKeyCode;
+
+const PREVIEW_TOP: f32 = 60.0;
+const PREVIEW_MAX_WIDTH: f32 = 900.0;
+const PREVIEW_PADDING: f32 = 8.0;
+const ROW_PADDING: f32 = 6.0;
+const INDENT: f32 = 30.0;
+const MAX_VISIBLE_ROWS: usize = 15;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Row {
+  File(usize),
+  Edit(usize, usize),
+}
+
+/// Lists the edits of a rename, or of replacing text in all files, before
+/// applying them, letting users leave out files or single occurrences.
+pub struct RenamePreview {
+  pub visible: bool,
+  font_height: f32,
+  theme: Theme,
+  title: String,
+  files: Vec<FileEdits>,
+  /// Paths shown for the files, relative to the workspace.
+  names: Vec<String>,
+  /// The line of each edit before and after the rename.
+  previews: Vec<Vec<(String, String)>>,
+  rows: Vec<Row>,
+  selected: usize,
+  first_visible: usize,
+  rect: Rectangle,
+  selection_rect: Rectangle,
+  dimensions: Dimensions,
+}
+
+impl RenamePreview {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      visible: false,
+      font_height,
+      theme,
+      title: String::new(),
+      files: vec![],
+      names: vec![],
+      previews: vec![],
+      rows: vec![],
+      selected: 0,
+      first_visible: 0,
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.overlay.rgb(),
+        None,
+      ),
+      selection_rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.selection.rgb(),
+        None,
+      ),
+      dimensions,
+    }
+  }
+
+  fn row_height(&self) -> f32 {
+    self.font_height + ROW_PADDING
+  }
+
+  /// Lists `files` to be edited as `change` describes, such as renaming.
+  pub fn open(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    workspace: &Path,
+    change: &str,
+    files: Vec<FileEdits>,
+    previews: Vec<Vec<(String, String)>>,
+  ) {
+    let occurrences = files.iter().map(|file| file.edits.len()).sum::<usize>();
+    self.title = format!(
+      "{}: {} occurrences in {} files. Space toggles, Enter applies.",
+      change,
+      occurrences,
+      files.len()
+    );
+    self.names = files
+      .iter()
+      .map(|file| {
+        file
+          .path
+          .strip_prefix(workspace)
+          .unwrap_or(&file.path)
+          .display()
+          .to_string()
+      })
+      .collect();
+    self.rows = files
+      .iter()
+      .enumerate()
+      .flat_map(|(i, file)| {
+        std::iter::once(Row::File(i))
+          .chain((0..file.edits.len()).map(move |j| Row::Edit(i, j)))
+      })
+      .collect();
+    self.files = files;
+    self.previews = previews;
+    self.selected = 0;
+    self.first_visible = 0;
+    self.visible = true;
+    self.layout(screen_size);
+  }
+
+  pub fn close(&mut self) {
+    self.visible = false;
+    self.files.clear();
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let width = (screen_size.width * 0.8).min(PREVIEW_MAX_WIDTH);
+    let rows = 1 + self.rows.len().min(MAX_VISIBLE_ROWS);
+    self.dimensions = Dimensions {
+      x: (screen_size.width - width) / 2.0,
+      y: PREVIEW_TOP,
+      width,
+      height: (PREVIEW_PADDING * 2.0) + (rows as f32 * self.row_height()),
+    };
+    self.rect.resize(screen_size, self.dimensions);
+
+    let row = self.selected - self.first_visible + 1;
+    self.selection_rect.resize(
+      screen_size,
+      Dimensions {
+        y: self.dimensions.y
+          + PREVIEW_PADDING
+          + (row as f32 * self.row_height()),
+        height: self.row_height(),
+        ..self.dimensions
+      },
+    );
+  }
+
+  fn toggle(&mut self, row: Row) {
+    match row {
+      Row::File(i) => {
+        let included = !self.files[i].edits.iter().any(|edit| edit.included);
+        for edit in &mut self.files[i].edits {
+          edit.included = included;
+        }
+      }
+      Row::Edit(i, j) => {
+        let edit = &mut self.files[i].edits[j];
+        edit.included = !edit.included;
+      }
+    }
+  }
+
+  fn select(&mut self, selected: usize) {
+    self.selected = selected;
+    self.first_visible = self.first_visible.min(self.selected);
+    if self.selected >= self.first_visible + MAX_VISIBLE_ROWS {
+      self.first_visible = self.selected + 1 - MAX_VISIBLE_ROWS;
+    }
+  }
+
+  pub fn input_special(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) {
+    match key {
+      VirtualKeyCode::Escape => self.close(),
+      VirtualKeyCode::Up => self.select(self.selected.saturating_sub(1)),
+      VirtualKeyCode::Down => {
+        self.select((self.selected + 1).min(self.rows.len().saturating_sub(1)))
+      }
+      VirtualKeyCode::PageUp => {
+        self.select(self.selected.saturating_sub(MAX_VISIBLE_ROWS))
+      }
+      VirtualKeyCode::PageDown => self.select(
+        (self.selected + MAX_VISIBLE_ROWS)
+          .min(self.rows.len().saturating_sub(1)),
+      ),
+      _ => return,
+    }
+    self.layout(screen_size);
+  }
+
+  /// Returns the edits to apply once the rename got accepted.
+  pub fn input_char(&mut self, ch: char) -> Option<Vec<FileEdits>> {
+    match ch {
+      ' ' => {
+        if let Some(row) = self.rows.get(self.selected) {
+          self.toggle(*row);
+        }
+        None
+      }
+      '\r' => {
+        let files = std::mem::take(&mut self.files);
+        self.close();
+        Some(files)
+      }
+      _ => None,
+    }
+  }
+
+  /// Toggles the row at `position`, relative to the preview.
+  pub fn click_at(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    position: PhysicalPosition<f32>,
+  ) {
+    let index = ((position.y - PREVIEW_PADDING) / self.row_height()).floor();
+    if index < 1.0 {
+      return;
+    }
+    let index = self.first_visible + index as usize - 1;
+    if let Some(row) = self.rows.get(index) {
+      self.toggle(*row);
+      self.select(index);
+      self.layout(screen_size);
+    }
+  }
+
+  fn label(&self, row: Row) -> String {
+    match row {
+      Row::File(i) => {
+        let edits = &self.files[i].edits;
+        let included = edits.iter().filter(|edit| edit.included).count();
+        let mark = if included == edits.len() {
+          "[x]"
+        } else if included == 0 {
+          "[ ]"
+        } else {
+          "[-]"
+        };
+        format!("{} {} ({})", mark, self.names[i], edits.len())
+      }
+      Row::Edit(i, j) => {
+        let edit = &self.files[i].edits[j];
+        let (before, after) = &self.previews[i][j];
+        format!(
+          "{} {}: {}  ->  {}",
+          if edit.included { "[x]" } else { "[ ]" },
+          edit.start.0 + 1,
+          before,
+          after
+        )
+      }
+    }
+  }
+}
+
+impl super::RenderElement for RenamePreview {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.layout(screen_size);
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.overlay.rgb());
+    self.selection_rect.set_color(theme.selection.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let x = self.dimensions.x + PREVIEW_PADDING;
+    let y = self.dimensions.y + PREVIEW_PADDING + (ROW_PADDING / 2.0);
+    let row_height = self.row_height();
+    let color = self.theme.overlay_text.rgba();
+
+    glyph_brush.queue(Section {
+      screen_position: (x, y),
+      text: vec![Text::new(&self.title)
+        .with_color(color)
+        .with_scale(self.font_height)],
+      ..Section::default()
+    });
+
+    for (i, row) in self
+      .rows
+      .iter()
+      .skip(self.first_visible)
+      .take(MAX_VISIBLE_ROWS)
+      .enumerate()
+    {
+      let indent = match row {
+        Row::File(_) => 0.0,
+        Row::Edit(..) => INDENT,
+      };
+      glyph_brush.queue(Section {
+        screen_position: (x + indent, y + ((i + 1) as f32 * row_height)),
+        text: vec![Text::new(&self.label(*row))
+          .with_color(color)
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect, &self.selection_rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
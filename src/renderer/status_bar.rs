@@ -0,0 +1,194 @@
+use crate::commands::Command;
+use crate::config::Theme;
+use crate::renderer::input::line_length;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+const STATUS_BAR_PADDING: f32 = 4.0;
+const SEGMENT_PADDING: f32 = 10.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Alignment {
+  Left,
+  Right,
+}
+
+/// A piece of text contributed to the status bar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+  pub text: String,
+  pub alignment: Alignment,
+  /// Segments with a higher priority are placed closer to the edge of
+  /// their side.
+  pub priority: i32,
+  /// Run when the segment is clicked.
+  pub command: Option<Command>,
+}
+
+/// The bar at the bottom of the window. Segments are registered under an id
+/// and can be replaced at any time to update them.
+pub struct StatusBar {
+  font: FontArc,
+  font_height: f32,
+  theme: Theme,
+  segments: Vec<(String, Segment)>,
+  /// Horizontal extent of each segment, in the same order as `segments`.
+  positions: Vec<(f32, f32)>,
+  rect: Rectangle,
+  dimensions: Dimensions,
+}
+
+impl StatusBar {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      font,
+      font_height,
+      theme,
+      segments: vec![],
+      positions: vec![],
+      rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.status_bar.rgb(),
+        None,
+      ),
+      dimensions,
+    }
+  }
+
+  pub fn height(&self) -> f32 {
+    self.font_height + (STATUS_BAR_PADDING * 2.0)
+  }
+
+  /// Adds the segment `id`, or replaces it if it already exists.
+  pub fn set(&mut self, id: &str, segment: Segment) {
+    match self.segments.iter_mut().find(|(other, _)| other == id) {
+      Some((_, existing)) if *existing == segment => return,
+      Some((_, existing)) => *existing = segment,
+      None => self.segments.push((id.to_string(), segment)),
+    }
+    self.layout();
+  }
+
+  pub fn remove(&mut self, id: &str) {
+    self.segments.retain(|(other, _)| other != id);
+    self.layout();
+  }
+
+  /// The command of the segment at `position`, relative to the bar.
+  pub fn command_at(&self, position: PhysicalPosition<f32>) -> Option<Command> {
+    let x = position.x + self.dimensions.x;
+    self
+      .segments
+      .iter()
+      .zip(&self.positions)
+      .find(|(_, (start, end))| x >= *start && x < *end)
+      .and_then(|((_, segment), _)| segment.command)
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.rect.resize(screen_size, dimensions);
+    self.layout();
+  }
+
+  fn layout(&mut self) {
+    let mut order = (0..self.segments.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| -self.segments[*i].1.priority);
+
+    self.positions = vec![(0.0, 0.0); self.segments.len()];
+    let mut left = self.dimensions.x;
+    let mut right = self.dimensions.x + self.dimensions.width;
+    for i in order {
+      let segment = &self.segments[i].1;
+      let width =
+        line_length(&segment.text, self.font.clone(), self.font_height)
+          + (SEGMENT_PADDING * 2.0);
+      self.positions[i] = match segment.alignment {
+        Alignment::Left => {
+          left += width;
+          (left - width, left)
+        }
+        Alignment::Right => {
+          right -= width;
+          (right, right + width)
+        }
+      };
+    }
+  }
+}
+
+impl super::RenderElement for StatusBar {
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.rect.set_color(theme.status_bar.rgb());
+  }
+
+  fn set_font(&mut self, font: &FontArc, font_height: f32) {
+    self.font = font.clone();
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    for ((_, segment), (start, _)) in self.segments.iter().zip(&self.positions)
+    {
+      glyph_brush.queue(Section {
+        screen_position: (
+          start + SEGMENT_PADDING,
+          self.dimensions.y + STATUS_BAR_PADDING,
+        ),
+        text: vec![Text::new(&segment.text)
+          .with_color(self.theme.status_bar_text.rgba())
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
@@ -0,0 +1,376 @@
+use crate::commands::Command;
+use crate::config::Theme;
+use crate::recent::Recent;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::Dimensions;
+use std::path::{Path, PathBuf};
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::VirtualKeyCode;
+
+const MAX_WIDTH: f32 = 600.0;
+const PADDING: f32 = 20.0;
+const ROW_PADDING: f32 = 6.0;
+/// The commands offered, with their keybindings as hints.
+pub const COMMANDS: &[Command] = &[
+  Command::NewFile,
+  Command::OpenFolder,
+  Command::QuickOpen,
+  Command::ShowCommandPalette,
+  Command::ShowExplorer,
+];
+
+/// What activating an entry of the start screen should do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WelcomeAction {
+  Run(Command),
+  Open(PathBuf),
+  OpenWorkspace(PathBuf),
+}
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+  label: String,
+  /// Shown dimmed after the label.
+  detail: String,
+  /// Headings and the gaps between sections have none.
+  action: Option<WelcomeAction>,
+}
+
+impl Entry {
+  fn heading(label: &str) -> Self {
+    Self {
+      label: label.to_string(),
+      detail: String::new(),
+      action: None,
+    }
+  }
+}
+
+/// The sections of the start screen: the `commands` with their
+/// keybindings, then the recent files and workspaces which still exist,
+/// leaving out `workspace` itself.
+fn entries(
+  commands: &[(Command, Option<String>)],
+  recent: &Recent,
+  workspace: &Path,
+) -> Vec<Entry> {
+  let mut entries = vec![Entry::heading("Start")];
+  entries.extend(commands.iter().map(|(command, combo)| Entry {
+    label: command.name().to_string(),
+    detail: combo.clone().unwrap_or_default(),
+    action: Some(WelcomeAction::Run(*command)),
+  }));
+
+  let name = |path: &Path| {
+    path
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_else(|| path.display().to_string())
+  };
+  let files = recent
+    .files
+    .iter()
+    .filter(|path| path.is_file())
+    .map(|path| Entry {
+      label: name(path),
+      detail: path
+        .parent()
+        .map(|dir| dir.strip_prefix(workspace).unwrap_or(dir))
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default(),
+      action: Some(WelcomeAction::Open(path.clone())),
+    })
+    .collect::<Vec<_>>();
+  let workspaces = recent
+    .workspaces
+    .iter()
+    .filter(|path| *path != workspace && path.is_dir())
+    .map(|path| Entry {
+      label: name(path),
+      detail: path.display().to_string(),
+      action: Some(WelcomeAction::OpenWorkspace(path.clone())),
+    })
+    .collect::<Vec<_>>();
+  for (heading, section) in
+    vec![("Recent Files", files), ("Recent Workspaces", workspaces)]
+  {
+    if !section.is_empty() {
+      entries.push(Entry::heading(""));
+      entries.push(Entry::heading(heading));
+      entries.extend(section);
+    }
+  }
+  entries
+}
+
+/// Shown in place of the code while no tab is open.
+pub struct Welcome {
+  font_height: f32,
+  theme: Theme,
+  workspace: PathBuf,
+  entries: Vec<Entry>,
+  selected: usize,
+  selection_rect: Rectangle,
+  dimensions: Dimensions,
+}
+
+impl Welcome {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    workspace: PathBuf,
+    theme: Theme,
+  ) -> Self {
+    let dimensions = Dimensions::default();
+    Self {
+      font_height,
+      theme,
+      workspace,
+      entries: vec![],
+      selected: 0,
+      selection_rect: Rectangle::new(
+        device,
+        screen_size,
+        dimensions,
+        theme.selection.rgb(),
+        None,
+      ),
+      dimensions,
+    }
+  }
+
+  fn row_height(&self) -> f32 {
+    self.font_height + ROW_PADDING
+  }
+
+  /// Where the title starts and how wide the column of entries is.
+  fn column(&self) -> (f32, f32, f32) {
+    let width = (self.dimensions.width - PADDING * 2.0).min(MAX_WIDTH);
+    let x = self.dimensions.x + (self.dimensions.width - width) / 2.0;
+    let y = self.dimensions.y + (self.dimensions.height * 0.15).max(PADDING);
+    (x, y, width)
+  }
+
+  /// Top of the first entry, below the title.
+  fn entries_top(&self) -> f32 {
+    let (_, y, _) = self.column();
+    y + self.font_height * 2.0 + PADDING
+  }
+
+  /// Lists `commands` with their keybindings and the `recent` files and
+  /// workspaces, selecting the first command.
+  pub fn update(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    commands: &[(Command, Option<String>)],
+    recent: &Recent,
+  ) {
+    self.entries = entries(commands, recent, &self.workspace);
+    self.selected = 0;
+    self.select(screen_size, 1);
+  }
+
+  pub fn set_dimensions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    dimensions: Dimensions,
+  ) {
+    self.dimensions = dimensions;
+    self.layout(screen_size);
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let (x, _, width) = self.column();
+    self.selection_rect.resize(
+      screen_size,
+      Dimensions {
+        x: x - ROW_PADDING,
+        y: self.entries_top() + self.selected as f32 * self.row_height(),
+        width: width + ROW_PADDING * 2.0,
+        height: self.row_height(),
+      },
+    );
+  }
+
+  /// Selects the closest entry with an action `step` entries away or
+  /// further, keeping the selection if there is none.
+  fn select(&mut self, screen_size: PhysicalSize<f32>, step: isize) {
+    let mut i = self.selected as isize + step;
+    while i >= 0 && (i as usize) < self.entries.len() {
+      if self.entries[i as usize].action.is_some() {
+        self.selected = i as usize;
+        break;
+      }
+      i += step.signum();
+    }
+    self.layout(screen_size);
+  }
+
+  pub fn input_special(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) {
+    match key {
+      VirtualKeyCode::Up => self.select(screen_size, -1),
+      VirtualKeyCode::Down => self.select(screen_size, 1),
+      _ => {}
+    }
+  }
+
+  /// Returns the action of the selected entry when it got accepted.
+  pub fn input_char(&self, ch: char) -> Option<WelcomeAction> {
+    match ch {
+      '\r' => self.entries.get(self.selected)?.action.clone(),
+      _ => None,
+    }
+  }
+
+  /// Selects the entry at `position`, relative to the start screen, and
+  /// returns its action.
+  pub fn click_at(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    position: PhysicalPosition<f32>,
+  ) -> Option<WelcomeAction> {
+    let y = position.y + self.dimensions.y - self.entries_top();
+    if y < 0.0 {
+      return None;
+    }
+    let i = (y / self.row_height()) as usize;
+    let action = self.entries.get(i)?.action.clone()?;
+    self.selected = i;
+    self.layout(screen_size);
+    Some(action)
+  }
+}
+
+impl super::RenderElement for Welcome {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.layout(screen_size);
+  }
+
+  fn set_theme(&mut self, theme: &Theme) {
+    self.theme = *theme;
+    self.selection_rect.set_color(theme.selection.rgb());
+  }
+
+  fn set_font(&mut self, _font: &FontArc, font_height: f32) {
+    self.font_height = font_height;
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let (x, y, _) = self.column();
+    glyph_brush.queue(Section {
+      screen_position: (x, y),
+      text: vec![Text::new(env!("CARGO_CRATE_NAME"))
+        .with_color(self.theme.text.rgba())
+        .with_scale(self.font_height * 2.0)],
+      ..Section::default()
+    });
+
+    let top = self.entries_top() + (ROW_PADDING / 2.0);
+    for (i, entry) in self.entries.iter().enumerate() {
+      let color = if entry.action.is_some() {
+        self.theme.text
+      } else {
+        self.theme.gutter_text
+      };
+      glyph_brush.queue(Section {
+        screen_position: (x, top + i as f32 * self.row_height()),
+        text: vec![
+          Text::new(&entry.label)
+            .with_color(color.rgba())
+            .with_scale(self.font_height),
+          Text::new("  ").with_scale(self.font_height),
+          Text::new(&entry.detail)
+            .with_color(self.theme.gutter_text.rgba())
+            .with_scale(self.font_height),
+        ],
+        ..Section::default()
+      });
+    }
+
+    super::draw_glyphs(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+      Some(self.dimensions),
+    );
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.selection_rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn lists_what_exists() {
+    let dir = std::env::temp_dir()
+      .join(format!("devcode-welcome-{}", std::process::id()));
+    let workspace = dir.join("workspace");
+    std::fs::create_dir_all(&workspace).unwrap();
+    let file = workspace.join("main.rs");
+    std::fs::write(&file, "").unwrap();
+
+    let recent = Recent {
+      files: vec![file.clone(), dir.join("gone.rs")],
+      workspaces: vec![workspace.clone(), dir.clone()],
+    };
+    let commands = [
+      (Command::OpenFolder, None),
+      (Command::QuickOpen, Some("ctrl+p".into())),
+    ];
+    let listed = entries(&commands, &recent, &workspace);
+    let actions = listed
+      .iter()
+      .filter_map(|entry| entry.action.clone())
+      .collect::<Vec<_>>();
+    // files which are gone and the workspace itself are left out
+    assert_eq!(
+      actions,
+      vec![
+        WelcomeAction::Run(Command::OpenFolder),
+        WelcomeAction::Run(Command::QuickOpen),
+        WelcomeAction::Open(file),
+        WelcomeAction::OpenWorkspace(dir.clone()),
+      ]
+    );
+    assert_eq!(listed[2].detail, "ctrl+p");
+    assert_eq!(listed[5].label, "main.rs");
+    assert_eq!(listed[5].detail, "");
+
+    // without recent files there are only the commands
+    assert_eq!(entries(&commands, &Recent::default(), &workspace).len(), 3);
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
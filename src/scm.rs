@@ -0,0 +1,38 @@
+use std::path::Path;
+use std::process::Command;
+
+/// The number of files with changes in the git repository of `root`,
+/// counting untracked ones.
+pub fn changed_files(root: &Path) -> Result<usize, anyhow::Error> {
+  let output = Command::new("git")
+    .args(&["status", "--porcelain"])
+    .current_dir(root)
+    .output()?;
+  if !output.status.success() {
+    anyhow::bail!(
+      "git status failed: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    );
+  }
+  Ok(count_entries(&output.stdout))
+}
+
+/// The number of files `git status --porcelain` printed.
+fn count_entries(status: &[u8]) -> usize {
+  status
+    .split(|byte| *byte == b'\n')
+    .filter(|line| !line.is_empty())
+    .count()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn entries() {
+    assert_eq!(count_entries(b""), 0);
+    assert_eq!(count_entries(b" M src/main.rs\n?? notes.txt\n"), 2);
+    assert_eq!(count_entries(b"R  a.rs -> b.rs\n"), 1);
+  }
+}
@@ -0,0 +1,106 @@
+//! Replacing text in all files of the workspace.
+
+use crate::rename::{Edit, FileEdits};
+use std::path::{Path, PathBuf};
+
+fn utf16_len(text: &str) -> usize {
+  text.encode_utf16().count()
+}
+
+/// Edits replacing each occurrence of `find` in `lines` with `replacement`,
+/// positioned like the edits of a rename.
+pub fn replace(lines: &[String], find: &str, replacement: &str) -> Vec<Edit> {
+  if find.is_empty() {
+    return vec![];
+  }
+  lines
+    .iter()
+    .enumerate()
+    .flat_map(|(row, line)| {
+      line.match_indices(find).map(move |(start, _)| {
+        let column = utf16_len(&line[..start]);
+        Edit {
+          start: (row, column),
+          end: (row, column + utf16_len(find)),
+          new_text: replacement.to_string(),
+          included: true,
+        }
+      })
+    })
+    .collect()
+}
+
+/// The edits replacing `find` in `files`, whose lines are read by `lines`,
+/// leaving out the files without it and those which can't be read.
+pub fn replace_in_files(
+  files: &[PathBuf],
+  mut lines: impl FnMut(&Path) -> Option<Vec<String>>,
+  find: &str,
+  replacement: &str,
+) -> Vec<FileEdits> {
+  files
+    .iter()
+    .filter_map(|path| {
+      let edits = replace(&lines(path)?, find, replacement);
+      if edits.is_empty() {
+        None
+      } else {
+        Some(FileEdits {
+          path: path.clone(),
+          edits,
+          version: None,
+        })
+      }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rename;
+
+  #[test]
+  fn replaces_in_every_file() {
+    let text = |text: &str| text.split('\n').map(String::from).collect();
+    let files = vec![
+      PathBuf::from("a.rs"),
+      PathBuf::from("b.rs"),
+      PathBuf::from("c.png"),
+    ];
+    let found = replace_in_files(
+      &files,
+      |path| match path.to_str() {
+        Some("a.rs") => Some(text("let foo = 1;\n/* 😀 */ foo + foofoo")),
+        Some("b.rs") => Some(text("bar")),
+        _ => None,
+      },
+      "foo",
+      "x",
+    );
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path, PathBuf::from("a.rs"));
+    // the emoji takes up two UTF-16 units
+    assert_eq!(
+      found[0]
+        .edits
+        .iter()
+        .map(|edit| (edit.start, edit.end))
+        .collect::<Vec<_>>(),
+      vec![
+        ((0, 4), (0, 7)),
+        ((1, 9), (1, 12)),
+        ((1, 15), (1, 18)),
+        ((1, 18), (1, 21)),
+      ]
+    );
+
+    let mut edits = found[0].edits.clone();
+    edits[2].included = false;
+    assert_eq!(
+      rename::apply(&text("let foo = 1;\n/* 😀 */ foo + foofoo"), &edits),
+      vec!["let x = 1;", "/* 😀 */ x + foox"]
+    );
+    assert!(replace(&text("foo"), "", "x").is_empty());
+  }
+}
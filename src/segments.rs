@@ -0,0 +1,172 @@
+//! Lines too long to lay out in one go, like the single line of a minified
+//! file. They are measured a segment at a time only as far as they are
+//! scrolled to, with a budget of graphemes a frame so the rest is measured
+//! on the frames after, and only the segments in view are drawn.
+
+use std::cmp::Ordering;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Lines of more bytes are laid out in segments.
+pub const LONG_LINE: usize = 4096;
+/// Graphemes measured in a segment.
+const SEGMENT: usize = 256;
+
+/// Where a segment starts: its grapheme column, byte offset and distance
+/// from the start of the line.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Start {
+  pub column: usize,
+  pub offset: usize,
+  pub x: f32,
+}
+
+/// The starts of the segments of a line, as far as it was measured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segments {
+  starts: Vec<Start>,
+  /// Whether the line was measured to its end, which is the last start.
+  complete: bool,
+}
+
+impl Default for Segments {
+  fn default() -> Self {
+    Self {
+      starts: vec![Start {
+        column: 0,
+        offset: 0,
+        x: 0.0,
+      }],
+      complete: false,
+    }
+  }
+}
+
+impl Segments {
+  /// The last start measured.
+  pub fn last(&self) -> Start {
+    *self.starts.last().unwrap()
+  }
+
+  pub fn is_complete(&self) -> bool {
+    self.complete
+  }
+
+  /// Measures `line` further until `reached` holds for the last start or
+  /// it ends, spending at most `budget` graphemes of it. Returns whether
+  /// `reached` holds or the line ended.
+  pub fn measure(
+    &mut self,
+    line: &str,
+    budget: &mut usize,
+    reached: impl Fn(&Start) -> bool,
+    advance: impl Fn(&str) -> f32,
+  ) -> bool {
+    while !self.complete && !reached(&self.last()) {
+      if *budget == 0 {
+        return false;
+      }
+      let last = self.last();
+      let mut next = last;
+      for (offset, grapheme) in
+        line[last.offset..].grapheme_indices(true).take(SEGMENT)
+      {
+        next.column += 1;
+        next.offset = last.offset + offset + grapheme.len();
+        next.x += advance(grapheme);
+      }
+      let count = next.column - last.column;
+      *budget = budget.saturating_sub(count.max(1));
+      if count < SEGMENT {
+        self.complete = true;
+      }
+      if count > 0 {
+        self.starts.push(next);
+      }
+    }
+    true
+  }
+
+  /// How many starts come first which are `before`.
+  fn count(&self, before: impl Fn(&Start) -> bool) -> usize {
+    self
+      .starts
+      .binary_search_by(|start| {
+        if before(start) {
+          Ordering::Less
+        } else {
+          Ordering::Greater
+        }
+      })
+      .unwrap_err()
+  }
+
+  /// The last start at or before `x`.
+  pub fn before_x(&self, x: f32) -> Start {
+    let i = self.count(|start| start.x <= x);
+    self.starts[i.saturating_sub(1)]
+  }
+
+  /// The last start at or before `column`.
+  pub fn before_column(&self, column: usize) -> Start {
+    let i = self.count(|start| start.column <= column);
+    self.starts[i.saturating_sub(1)]
+  }
+
+  /// The first start after `x`, if measured.
+  pub fn after_x(&self, x: f32) -> Option<Start> {
+    let i = self.count(|start| start.x <= x);
+    self.starts.get(i).copied()
+  }
+
+  /// Forgets the segments past `column`, where the line was edited.
+  pub fn truncate(&mut self, column: usize) {
+    let i = self.count(|start| start.column <= column);
+    self.starts.truncate(i.max(1));
+    self.complete = false;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn measures_within_budget() {
+    let line = "ab".repeat(SEGMENT);
+    let advance = |grapheme: &str| if grapheme == "a" { 1.0 } else { 2.0 };
+    let mut segments = Segments::default();
+    let mut budget = SEGMENT;
+    // a segment fits the budget, the next frame measures the rest
+    assert!(!segments.measure(&line, &mut budget, |_| false, advance));
+    assert_eq!(budget, 0);
+    assert_eq!(
+      segments.last(),
+      Start {
+        column: SEGMENT,
+        offset: SEGMENT,
+        x: SEGMENT as f32 * 1.5,
+      }
+    );
+    let mut budget = SEGMENT * 4;
+    assert!(segments.measure(&line, &mut budget, |_| false, advance));
+    assert!(segments.is_complete());
+    assert_eq!(segments.last().column, SEGMENT * 2);
+    assert_eq!(segments.last().x, SEGMENT as f32 * 3.0);
+
+    assert_eq!(segments.before_x(SEGMENT as f32 * 2.0).column, SEGMENT);
+    assert_eq!(
+      segments.after_x(SEGMENT as f32 * 2.0),
+      Some(segments.last())
+    );
+    assert_eq!(segments.before_column(SEGMENT - 1).column, 0);
+
+    // what was measured up to an edit is kept
+    segments.truncate(SEGMENT + 3);
+    assert!(!segments.is_complete());
+    assert_eq!(segments.last().column, SEGMENT);
+    let mut budget = 1;
+    let reached = |start: &Start| start.x > 0.0;
+    assert!(segments.measure(&line, &mut budget, reached, advance));
+    assert_eq!(budget, 1);
+  }
+}
@@ -0,0 +1,103 @@
+//! State of a workspace kept between runs, stored in
+//! `.devcode/session.toml`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Session {
+  /// Zero based bookmarked lines by file.
+  pub bookmarks: BTreeMap<PathBuf, Vec<usize>>,
+}
+
+impl Session {
+  fn path(workspace: &Path) -> PathBuf {
+    workspace.join(".devcode").join("session.toml")
+  }
+
+  pub fn load(workspace: &Path) -> Self {
+    let path = Self::path(workspace);
+    match std::fs::read_to_string(&path) {
+      Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+        tracing::warn!("failed to parse {}: {}", path.display(), err);
+        Self::default()
+      }),
+      Err(_) => Self::default(),
+    }
+  }
+
+  /// Takes the changes `ours` made to `base` into the session, which other
+  /// windows may have saved in the meantime. Files only they changed keep
+  /// their bookmarks.
+  pub fn merge(&mut self, base: &Session, ours: &Session) {
+    for file in base.bookmarks.keys().chain(ours.bookmarks.keys()) {
+      let bookmarks = ours.bookmarks.get(file);
+      if bookmarks == base.bookmarks.get(file) {
+        continue;
+      }
+      match bookmarks {
+        Some(bookmarks) => {
+          self.bookmarks.insert(file.clone(), bookmarks.clone());
+        }
+        None => {
+          self.bookmarks.remove(file);
+        }
+      }
+    }
+  }
+
+  pub fn save(&self, workspace: &Path) -> Result<(), anyhow::Error> {
+    let path = Self::path(workspace);
+    if self == &Self::load(workspace) {
+      return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, toml::to_string(self)?)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn save_and_load() {
+    let workspace = std::env::temp_dir()
+      .join(format!("devcode-session-{}", std::process::id()));
+    assert_eq!(Session::load(&workspace), Session::default());
+    // nothing to remember doesn't create the directory
+    Session::default().save(&workspace).unwrap();
+    assert!(!workspace.exists());
+
+    let mut session = Session::default();
+    session
+      .bookmarks
+      .insert(workspace.join("src").join("main.rs"), vec![3, 40]);
+    session.save(&workspace).unwrap();
+    assert_eq!(Session::load(&workspace), session);
+
+    std::fs::remove_dir_all(workspace).unwrap();
+  }
+
+  #[test]
+  fn merge_windows() {
+    let session = |bookmarks: &[(&str, &[usize])]| Session {
+      bookmarks: bookmarks
+        .iter()
+        .map(|(file, lines)| (PathBuf::from(file), lines.to_vec()))
+        .collect(),
+    };
+    let base = session(&[("a", &[1]), ("b", &[2]), ("c", &[3])]);
+    // another window moved a's bookmark and removed b's meanwhile
+    let mut saved = session(&[("a", &[5]), ("c", &[3])]);
+    // this one added d's and removed c's
+    let ours = session(&[("a", &[1]), ("b", &[2]), ("d", &[4])]);
+    saved.merge(&base, &ours);
+    assert_eq!(saved, session(&[("a", &[5]), ("d", &[4])]));
+  }
+}
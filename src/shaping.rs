@@ -0,0 +1,717 @@
+//! Substituting the glyphs of ligatures, like programming fonts draw for
+//! `=>` or `!=`, as the `GSUB` table of the font describes.
+
+use glyph_brush::ToSectionText;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wgpu_glyph::ab_glyph::{Font, GlyphId, Rect};
+use wgpu_glyph::{GlyphPositioner, Layout, SectionGeometry, SectionGlyph};
+
+/// The features substituting ligatures, contextual ones included.
+const FEATURES: [&[u8; 4]; 3] = [b"liga", b"clig", b"calt"];
+/// How deep contextual lookups may nest.
+const MAX_DEPTH: usize = 8;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+  let bytes = data.get(offset..offset + 2)?;
+  Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+  let bytes = data.get(offset..offset + 4)?;
+  Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn u16s_at(data: &[u8], offset: usize, count: usize) -> Option<Vec<u16>> {
+  (0..count).map(|i| u16_at(data, offset + i * 2)).collect()
+}
+
+/// The table the 16 bit offset at `offset` points to, `None` for a null
+/// offset.
+fn offset_table(data: &[u8], offset: usize) -> Option<&[u8]> {
+  match u16_at(data, offset)? {
+    0 => None,
+    table => data.get(table as usize..),
+  }
+}
+
+/// The table tagged `tag` of the first font in `font`.
+fn find_table<'a>(font: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+  let start = if font.get(..4)? == b"ttcf" {
+    u32_at(font, 12)? as usize
+  } else {
+    0
+  };
+  let directory = font.get(start..)?;
+  (0..u16_at(directory, 4)? as usize).find_map(|i| {
+    let record = directory.get(12 + i * 16..28 + i * 16)?;
+    if &record[..4] != tag {
+      return None;
+    }
+    let offset = u32_at(record, 8)? as usize;
+    font.get(offset..offset + u32_at(record, 12)? as usize)
+  })
+}
+
+/// Glyph ranges, with the coverage index of their first glyph.
+fn ranges(data: &[u8]) -> Option<Vec<(u16, u16, u16)>> {
+  (0..u16_at(data, 2)? as usize)
+    .map(|i| {
+      let at = 4 + i * 6;
+      Some((
+        u16_at(data, at)?,
+        u16_at(data, at + 2)?,
+        u16_at(data, at + 4)?,
+      ))
+    })
+    .collect()
+}
+
+fn find_range(ranges: &[(u16, u16, u16)], glyph: u16) -> Option<(u16, u16)> {
+  let index = ranges
+    .binary_search_by(|(start, end, _)| {
+      if *end < glyph {
+        std::cmp::Ordering::Less
+      } else if *start > glyph {
+        std::cmp::Ordering::Greater
+      } else {
+        std::cmp::Ordering::Equal
+      }
+    })
+    .ok()?;
+  let (start, _, value) = ranges[index];
+  Some((start, value))
+}
+
+/// The glyphs a subtable applies to.
+enum Coverage {
+  Glyphs(Vec<u16>),
+  Ranges(Vec<(u16, u16, u16)>),
+}
+
+impl Coverage {
+  fn parse(data: &[u8]) -> Option<Self> {
+    match u16_at(data, 0)? {
+      1 => Some(Self::Glyphs(u16s_at(data, 4, u16_at(data, 2)? as usize)?)),
+      2 => Some(Self::Ranges(ranges(data)?)),
+      _ => None,
+    }
+  }
+
+  fn index(&self, glyph: u16) -> Option<usize> {
+    match self {
+      Self::Glyphs(glyphs) => glyphs.binary_search(&glyph).ok(),
+      Self::Ranges(ranges) => {
+        let (start, index) = find_range(ranges, glyph)?;
+        Some(index as usize + (glyph - start) as usize)
+      }
+    }
+  }
+}
+
+/// Sorts glyphs into classes, class 0 for the ones it leaves out.
+enum ClassDef {
+  Array(u16, Vec<u16>),
+  Ranges(Vec<(u16, u16, u16)>),
+}
+
+impl ClassDef {
+  /// The class definition at the offset stored at `offset`, putting every
+  /// glyph in class 0 for a null offset.
+  fn parse_at(data: &[u8], offset: usize) -> Option<Rc<Self>> {
+    let data = match offset_table(data, offset) {
+      Some(data) => data,
+      None => return Some(Rc::new(Self::Ranges(vec![]))),
+    };
+    Some(Rc::new(match u16_at(data, 0)? {
+      1 => Self::Array(
+        u16_at(data, 2)?,
+        u16s_at(data, 6, u16_at(data, 4)? as usize)?,
+      ),
+      2 => Self::Ranges(ranges(data)?),
+      _ => return None,
+    }))
+  }
+
+  fn class(&self, glyph: u16) -> u16 {
+    match self {
+      Self::Array(start, classes) => glyph
+        .checked_sub(*start)
+        .and_then(|i| classes.get(i as usize).copied())
+        .unwrap_or(0),
+      Self::Ranges(ranges) => find_range(ranges, glyph).map_or(0, |(_, c)| c),
+    }
+  }
+}
+
+/// What a glyph in the context of a rule has to be.
+enum Matcher {
+  Glyph(u16),
+  Class(Rc<ClassDef>, u16),
+  Coverage(Coverage),
+}
+
+impl Matcher {
+  fn matches(&self, glyph: u16) -> bool {
+    match self {
+      Self::Glyph(expected) => glyph == *expected,
+      Self::Class(class_def, class) => class_def.class(glyph) == *class,
+      Self::Coverage(coverage) => coverage.index(glyph).is_some(),
+    }
+  }
+}
+
+/// A contextual substitution, applying `lookups` at positions of the input
+/// when the glyphs around the first one match.
+struct Rule {
+  /// The glyphs before the first, closest first.
+  backtrack: Vec<Matcher>,
+  /// The glyphs after the first one of the input.
+  input: Vec<Matcher>,
+  lookahead: Vec<Matcher>,
+  /// Positions in the input and the lookups applied there.
+  lookups: Vec<(usize, usize)>,
+}
+
+impl Rule {
+  fn matches(&self, glyphs: &[u16], i: usize) -> bool {
+    let after = i + 1 + self.input.len();
+    self.backtrack.len() <= i
+      && after + self.lookahead.len() <= glyphs.len()
+      && self
+        .backtrack
+        .iter()
+        .enumerate()
+        .all(|(k, matcher)| matcher.matches(glyphs[i - 1 - k]))
+      && self
+        .input
+        .iter()
+        .enumerate()
+        .all(|(k, matcher)| matcher.matches(glyphs[i + 1 + k]))
+      && self
+        .lookahead
+        .iter()
+        .enumerate()
+        .all(|(k, matcher)| matcher.matches(glyphs[after + k]))
+  }
+}
+
+/// The records of the lookups a rule applies, `count` of them at `offset`.
+fn lookup_records(
+  data: &[u8],
+  offset: usize,
+  count: usize,
+) -> Option<Vec<(usize, usize)>> {
+  (0..count)
+    .map(|i| {
+      let at = offset + i * 4;
+      Some((u16_at(data, at)? as usize, u16_at(data, at + 2)? as usize))
+    })
+    .collect()
+}
+
+/// Parses a rule of glyph ids or classes, which `matcher` turns into what
+/// the glyphs are matched against by their place: backtrack, input or
+/// lookahead.
+fn parse_rule(
+  data: &[u8],
+  chained: bool,
+  matcher: &dyn Fn(usize, u16) -> Matcher,
+) -> Option<Rule> {
+  let to_matchers = |place: usize, values: Vec<u16>| {
+    values
+      .into_iter()
+      .map(|value| matcher(place, value))
+      .collect()
+  };
+  if !chained {
+    // the count of lookups comes before the input here
+    let inputs = (u16_at(data, 0)? as usize).saturating_sub(1);
+    return Some(Rule {
+      backtrack: vec![],
+      input: to_matchers(1, u16s_at(data, 4, inputs)?),
+      lookahead: vec![],
+      lookups: lookup_records(data, 4 + inputs * 2, u16_at(data, 2)? as usize)?,
+    });
+  }
+
+  let backtracks = u16_at(data, 0)? as usize;
+  let backtrack = u16s_at(data, 2, backtracks)?;
+  let at = 2 + backtracks * 2;
+  let inputs = (u16_at(data, at)? as usize).saturating_sub(1);
+  let input = u16s_at(data, at + 2, inputs)?;
+  let at = at + 2 + inputs * 2;
+  let lookaheads = u16_at(data, at)? as usize;
+  let lookahead = u16s_at(data, at + 2, lookaheads)?;
+  let at = at + 2 + lookaheads * 2;
+  Some(Rule {
+    backtrack: to_matchers(0, backtrack),
+    input: to_matchers(1, input),
+    lookahead: to_matchers(2, lookahead),
+    lookups: lookup_records(data, at + 2, u16_at(data, at)? as usize)?,
+  })
+}
+
+/// The sets of rules whose offsets are listed at `offset`, empty for null
+/// offsets.
+fn parse_rule_sets(
+  data: &[u8],
+  offset: usize,
+  chained: bool,
+  matcher: &dyn Fn(usize, u16) -> Matcher,
+) -> Option<Vec<Vec<Rule>>> {
+  (0..u16_at(data, offset)? as usize)
+    .map(|i| match offset_table(data, offset + 2 + i * 2) {
+      Some(set) => (0..u16_at(set, 0)? as usize)
+        .map(|j| parse_rule(offset_table(set, 2 + j * 2)?, chained, matcher))
+        .collect(),
+      None => Some(vec![]),
+    })
+    .collect()
+}
+
+/// How the rules to try for a glyph are picked.
+enum Select {
+  /// By its coverage index.
+  Coverage,
+  /// By its class.
+  Class(Rc<ClassDef>),
+  /// There is a single rule.
+  Single,
+}
+
+struct Context {
+  coverage: Coverage,
+  select: Select,
+  sets: Vec<Vec<Rule>>,
+}
+
+impl Context {
+  fn parse(data: &[u8], chained: bool) -> Option<Self> {
+    match u16_at(data, 0)? {
+      1 => Some(Self {
+        coverage: Coverage::parse(offset_table(data, 2)?)?,
+        select: Select::Coverage,
+        sets: parse_rule_sets(data, 4, chained, &|_, glyph| {
+          Matcher::Glyph(glyph)
+        })?,
+      }),
+      2 if chained => {
+        let class_defs = [
+          ClassDef::parse_at(data, 4)?,
+          ClassDef::parse_at(data, 6)?,
+          ClassDef::parse_at(data, 8)?,
+        ];
+        Some(Self {
+          coverage: Coverage::parse(offset_table(data, 2)?)?,
+          select: Select::Class(class_defs[1].clone()),
+          sets: parse_rule_sets(data, 10, chained, &|place, class| {
+            Matcher::Class(class_defs[place].clone(), class)
+          })?,
+        })
+      }
+      2 => {
+        let class_def = ClassDef::parse_at(data, 4)?;
+        Some(Self {
+          coverage: Coverage::parse(offset_table(data, 2)?)?,
+          select: Select::Class(class_def.clone()),
+          sets: parse_rule_sets(data, 6, chained, &|_, class| {
+            Matcher::Class(class_def.clone(), class)
+          })?,
+        })
+      }
+      3 => {
+        let coverages = |at: usize, count: usize| {
+          (0..count)
+            .map(|i| Coverage::parse(offset_table(data, at + i * 2)?))
+            .collect::<Option<Vec<_>>>()
+        };
+        let (backtrack, mut input, lookahead, records) = if chained {
+          let backtracks = u16_at(data, 2)? as usize;
+          let at = 4 + backtracks * 2;
+          let inputs = u16_at(data, at)? as usize;
+          let at = at + 2 + inputs * 2;
+          let lookaheads = u16_at(data, at)? as usize;
+          (
+            coverages(4, backtracks)?,
+            coverages(4 + backtracks * 2 + 2, inputs)?,
+            coverages(at + 2, lookaheads)?,
+            at + 2 + lookaheads * 2,
+          )
+        } else {
+          let inputs = u16_at(data, 2)? as usize;
+          (vec![], coverages(6, inputs)?, vec![], 4)
+        };
+        if input.is_empty() {
+          return None;
+        }
+        let coverage = input.remove(0);
+        let lookups = if chained {
+          lookup_records(data, records + 2, u16_at(data, records)? as usize)?
+        } else {
+          let inputs = input.len() + 1;
+          lookup_records(data, 6 + inputs * 2, u16_at(data, 4)? as usize)?
+        };
+        let matchers = |coverages: Vec<Coverage>| {
+          coverages.into_iter().map(Matcher::Coverage).collect()
+        };
+        Some(Self {
+          coverage,
+          select: Select::Single,
+          sets: vec![vec![Rule {
+            backtrack: matchers(backtrack),
+            input: matchers(input),
+            lookahead: matchers(lookahead),
+            lookups,
+          }]],
+        })
+      }
+      _ => None,
+    }
+  }
+
+  /// The first rule matching at `i`.
+  fn rule(&self, glyphs: &[u16], i: usize) -> Option<&Rule> {
+    let index = self.coverage.index(glyphs[i])?;
+    let set = match &self.select {
+      Select::Coverage => index,
+      Select::Class(class_def) => class_def.class(glyphs[i]) as usize,
+      Select::Single => 0,
+    };
+    self
+      .sets
+      .get(set)?
+      .iter()
+      .find(|rule| rule.matches(glyphs, i))
+  }
+}
+
+enum Subtable {
+  /// Adds to the glyph id.
+  Delta(Coverage, u16),
+  /// Replaces a glyph with the one at its coverage index.
+  Single(Coverage, Vec<u16>),
+  /// Ligatures by the coverage index of their first glyph, with the glyphs
+  /// following it.
+  Ligature(Coverage, Vec<Vec<(Vec<u16>, u16)>>),
+  Context(Context),
+}
+
+impl Subtable {
+  fn parse(kind: u16, data: &[u8]) -> Option<Self> {
+    match kind {
+      1 => {
+        let coverage = Coverage::parse(offset_table(data, 2)?)?;
+        match u16_at(data, 0)? {
+          1 => Some(Self::Delta(coverage, u16_at(data, 4)?)),
+          2 => Some(Self::Single(
+            coverage,
+            u16s_at(data, 6, u16_at(data, 4)? as usize)?,
+          )),
+          _ => None,
+        }
+      }
+      4 => {
+        let coverage = Coverage::parse(offset_table(data, 2)?)?;
+        let sets = (0..u16_at(data, 4)? as usize)
+          .map(|i| {
+            let set = offset_table(data, 6 + i * 2)?;
+            (0..u16_at(set, 0)? as usize)
+              .map(|j| {
+                let ligature = offset_table(set, 2 + j * 2)?;
+                let components = u16_at(ligature, 2)? as usize;
+                Some((
+                  u16s_at(ligature, 4, components.saturating_sub(1))?,
+                  u16_at(ligature, 0)?,
+                ))
+              })
+              .collect()
+          })
+          .collect::<Option<_>>()?;
+        Some(Self::Ligature(coverage, sets))
+      }
+      5 => Some(Self::Context(Context::parse(data, false)?)),
+      6 => Some(Self::Context(Context::parse(data, true)?)),
+      // an extension points to a subtable further away
+      7 => match u16_at(data, 2)? {
+        7 => None,
+        kind => Self::parse(kind, data.get(u32_at(data, 4)? as usize..)?),
+      },
+      _ => None,
+    }
+  }
+}
+
+#[derive(Default)]
+struct Lookup {
+  subtables: Vec<Subtable>,
+}
+
+impl Lookup {
+  fn parse(data: &[u8]) -> Option<Self> {
+    let kind = u16_at(data, 0)?;
+    let subtables = (0..u16_at(data, 4)? as usize)
+      .filter_map(|i| Subtable::parse(kind, offset_table(data, 6 + i * 2)?))
+      .collect();
+    Some(Self { subtables })
+  }
+}
+
+/// The ligatures of a font. The default one has none.
+#[derive(Default)]
+pub struct Ligatures {
+  /// Tells apart the ligatures of different fonts when laid out text is
+  /// cached.
+  id: usize,
+  lookups: Vec<Lookup>,
+  /// The lookups of the ligature features, in the order they apply.
+  enabled: Vec<usize>,
+}
+
+impl Ligatures {
+  /// Reads the ligatures of the default script and language from the data
+  /// of a font, leaving out the ones it can't read.
+  pub fn parse(font: &[u8]) -> Self {
+    Self::try_parse(font).unwrap_or_default()
+  }
+
+  fn try_parse(font: &[u8]) -> Option<Self> {
+    let gsub = find_table(font, b"GSUB")?;
+    let scripts = offset_table(gsub, 4)?;
+    let features = offset_table(gsub, 6)?;
+    let lookups = offset_table(gsub, 8)?;
+
+    let script_count = u16_at(scripts, 0)? as usize;
+    let script = [b"DFLT", b"latn"]
+      .iter()
+      .find_map(|tag| {
+        (0..script_count)
+          .find(|i| scripts.get(2 + i * 6..6 + i * 6) == Some(&tag[..]))
+      })
+      .or_else(|| (0..script_count).next())?;
+    let language = offset_table(offset_table(scripts, 6 + script * 6)?, 0)?;
+    let mut enabled = vec![];
+    for index in u16s_at(language, 6, u16_at(language, 4)? as usize)? {
+      let record = 2 + index as usize * 6;
+      let tag = features.get(record..record + 4)?;
+      if FEATURES.iter().any(|feature| tag == &feature[..]) {
+        let feature = offset_table(features, record + 4)?;
+        let count = u16_at(feature, 2)? as usize;
+        enabled
+          .extend(u16s_at(feature, 4, count)?.into_iter().map(usize::from));
+      }
+    }
+    if enabled.is_empty() {
+      return None;
+    }
+    enabled.sort_unstable();
+    enabled.dedup();
+
+    let lookups = (0..u16_at(lookups, 0)? as usize)
+      .map(|i| {
+        offset_table(lookups, 2 + i * 2)
+          .and_then(Lookup::parse)
+          .unwrap_or_default()
+      })
+      .collect();
+    Some(Self {
+      id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+      lookups,
+      enabled,
+    })
+  }
+
+  /// Applies the lookup at `i`, returning how many glyphs it went over.
+  fn apply(
+    &self,
+    lookup: usize,
+    glyphs: &mut Vec<(u16, usize)>,
+    i: usize,
+    depth: usize,
+  ) -> Option<usize> {
+    let glyph = glyphs.get(i)?.0;
+    self
+      .lookups
+      .get(lookup)?
+      .subtables
+      .iter()
+      .find_map(|subtable| {
+        match subtable {
+          Subtable::Delta(coverage, delta) => {
+            coverage.index(glyph)?;
+            glyphs[i].0 = glyph.wrapping_add(*delta);
+          }
+          Subtable::Single(coverage, substitutes) => {
+            glyphs[i].0 = *substitutes.get(coverage.index(glyph)?)?;
+          }
+          Subtable::Ligature(coverage, sets) => {
+            let (components, ligature) = sets
+              .get(coverage.index(glyph)?)?
+              .iter()
+              .find(|(components, _)| {
+                glyphs.len() > i + components.len()
+                  && components
+                    .iter()
+                    .zip(&glyphs[i + 1..])
+                    .all(|(component, (glyph, _))| component == glyph)
+              })?;
+            glyphs.drain(i + 1..i + 1 + components.len());
+            glyphs[i].0 = *ligature;
+          }
+          Subtable::Context(context) => {
+            if depth == MAX_DEPTH {
+              return None;
+            }
+            let ids =
+              glyphs.iter().map(|(glyph, _)| *glyph).collect::<Vec<_>>();
+            let rule = context.rule(&ids, i)?;
+            for (position, lookup) in &rule.lookups {
+              self.apply(*lookup, glyphs, i + position, depth + 1);
+            }
+            // ligatures within the input make it shorter
+            let shortened = ids.len() - glyphs.len();
+            return Some(
+              (rule.input.len() + 1).saturating_sub(shortened).max(1),
+            );
+          }
+        }
+        Some(1)
+      })
+  }
+
+  /// The glyphs to draw in place of `glyphs`, one for each, `None` where a
+  /// glyph was joined into a ligature with the ones before it.
+  pub fn substitute(&self, glyphs: &[u16]) -> Vec<Option<u16>> {
+    let mut shaped = glyphs.iter().copied().zip(0..).collect::<Vec<_>>();
+    for lookup in &self.enabled {
+      let mut i = 0;
+      while i < shaped.len() {
+        i += self.apply(*lookup, &mut shaped, i, 0).unwrap_or(1);
+      }
+    }
+    let mut substituted = vec![None; glyphs.len()];
+    for (glyph, index) in shaped {
+      substituted[index] = Some(glyph);
+    }
+    substituted
+  }
+}
+
+/// Lays out text like the default layout, then draws the ligatures in place
+/// of the glyphs they join. A ligature takes the place of its first glyph,
+/// so the characters keep their positions for the cursor.
+pub struct Shaped<'a> {
+  pub ligatures: &'a Ligatures,
+  /// Whether glyphs are moved to whole pixels.
+  pub hinting: bool,
+}
+
+impl Hash for Shaped<'_> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    Layout::default().hash(state);
+    self.ligatures.id.hash(state);
+    self.hinting.hash(state);
+  }
+}
+
+impl GlyphPositioner for Shaped<'_> {
+  fn calculate_glyphs<F, S>(
+    &self,
+    fonts: &[F],
+    geometry: &SectionGeometry,
+    sections: &[S],
+  ) -> Vec<SectionGlyph>
+  where
+    F: Font,
+    S: ToSectionText,
+  {
+    let mut glyphs =
+      Layout::default().calculate_glyphs(fonts, geometry, sections);
+    if self.hinting {
+      for glyph in &mut glyphs {
+        glyph.glyph.position.x = glyph.glyph.position.x.round();
+        glyph.glyph.position.y = glyph.glyph.position.y.round();
+      }
+    }
+    if self.ligatures.enabled.is_empty() {
+      return glyphs;
+    }
+
+    // ligatures join glyphs of the same text on the same line
+    let same_run = |a: &SectionGlyph, b: &SectionGlyph| {
+      a.section_index == b.section_index
+        && a.font_id == b.font_id
+        && a.glyph.scale == b.glyph.scale
+        && (a.glyph.position.y - b.glyph.position.y).abs() < f32::EPSILON
+    };
+    let mut shaped = Vec::with_capacity(glyphs.len());
+    let mut start = 0;
+    while start < glyphs.len() {
+      let end = (start + 1..glyphs.len())
+        .find(|i| !same_run(&glyphs[start], &glyphs[*i]))
+        .unwrap_or(glyphs.len());
+      let run = &glyphs[start..end];
+      let ids = run.iter().map(|glyph| glyph.glyph.id.0).collect::<Vec<_>>();
+      for (glyph, id) in run.iter().zip(self.ligatures.substitute(&ids)) {
+        if let Some(id) = id {
+          let mut glyph = glyph.clone();
+          glyph.glyph.id = GlyphId(id);
+          shaped.push(glyph);
+        }
+      }
+      start = end;
+    }
+    shaped
+  }
+
+  fn bounds_rect(&self, geometry: &SectionGeometry) -> Rect {
+    Layout::default().bounds_rect(geometry)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use wgpu_glyph::ab_glyph::FontRef;
+
+  const FONT: &[u8] = include_bytes!("./JetBrainsMono-Regular.ttf");
+
+  fn ids(text: &str) -> Vec<u16> {
+    let font = FontRef::try_from_slice(FONT).unwrap();
+    text.chars().map(|ch| font.glyph_id(ch).0).collect()
+  }
+
+  #[test]
+  fn substitutes_ligatures() {
+    let ligatures = Ligatures::parse(FONT);
+    let plain = ids("a => b != c");
+    let shaped = ligatures.substitute(&plain);
+    assert_eq!(shaped.len(), plain.len());
+    // letters and spaces stay as they are
+    for &i in &[0, 1, 4, 5, 6, 9, 10] {
+      assert_eq!(shaped[i], Some(plain[i]));
+    }
+    assert_ne!(&shaped[2..4], &[Some(plain[2]), Some(plain[3])]);
+    assert_ne!(&shaped[7..9], &[Some(plain[7]), Some(plain[8])]);
+    // a lone equals sign isn't part of a ligature
+    assert_eq!(
+      ligatures.substitute(&ids("a = b")),
+      vec![
+        Some(plain[0]),
+        Some(plain[1]),
+        Some(plain[2]),
+        Some(plain[1]),
+        Some(plain[5]),
+      ]
+    );
+
+    assert_eq!(
+      Ligatures::default().substitute(&plain),
+      plain.iter().copied().map(Some).collect::<Vec<_>>()
+    );
+    assert!(Ligatures::parse(b"not a font").enabled.is_empty());
+  }
+}
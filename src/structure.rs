@@ -0,0 +1,261 @@
+//! Structural selection, growing a selection to the enclosing word,
+//! brackets, statement, block and declaration. The structure comes from the
+//! brackets and indentation of the text, so it works for any grammar.
+
+use crate::text::is_word;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A zero based line and grapheme column.
+pub type Position = (usize, usize);
+
+/// The text from `start` up to `end`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Span {
+  pub start: Position,
+  pub end: Position,
+}
+
+impl Span {
+  /// The span between two positions, in either order.
+  pub fn new(a: Position, b: Position) -> Self {
+    Self {
+      start: a.min(b),
+      end: a.max(b),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.start == self.end
+  }
+
+  fn contains(&self, other: &Span) -> bool {
+    self.start <= other.start && other.end <= self.end
+  }
+}
+
+/// Byte offset of the grapheme `column` in `line`, clamped to its length.
+pub fn byte_offset(line: &str, column: usize) -> usize {
+  line
+    .grapheme_indices(true)
+    .nth(column)
+    .map_or(line.len(), |(i, _)| i)
+}
+
+const PAIRS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
+
+fn is_space(grapheme: &str) -> bool {
+  grapheme.chars().all(char::is_whitespace)
+}
+
+struct Text<'a> {
+  lines: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Text<'a> {
+  fn new(lines: &'a [String]) -> Self {
+    Self {
+      lines: lines
+        .iter()
+        .map(|line| line.graphemes(true).collect())
+        .collect(),
+    }
+  }
+
+  /// Offset of `position` from the start, counting line ends as one.
+  fn offset(&self, position: Position) -> usize {
+    let before = self.lines[..position.0]
+      .iter()
+      .map(|line| line.len() + 1)
+      .sum::<usize>();
+    before + position.1
+  }
+
+  fn len(&self, span: &Span) -> usize {
+    self.offset(span.end) - self.offset(span.start)
+  }
+
+  fn indent(&self, line: usize) -> usize {
+    self.lines[line]
+      .iter()
+      .take_while(|grapheme| is_space(grapheme))
+      .count()
+  }
+
+  /// Length of `line` without trailing whitespace.
+  fn trimmed_len(&self, line: usize) -> usize {
+    let line = &self.lines[line];
+    line.len() - line.iter().rev().take_while(|g| is_space(g)).count()
+  }
+
+  /// Positions of the matching brackets, openers before closers.
+  fn pairs(&self) -> Vec<(Position, Position, &'static str)> {
+    let mut open = vec![];
+    let mut pairs = vec![];
+    for (row, line) in self.lines.iter().enumerate() {
+      for (column, grapheme) in line.iter().enumerate() {
+        if let Some((opener, _)) = PAIRS.iter().find(|(o, _)| o == grapheme) {
+          open.push(((row, column), *opener));
+        } else if let Some((opener, _)) =
+          PAIRS.iter().find(|(_, c)| c == grapheme)
+        {
+          // an unmatched closer is left alone
+          if matches!(open.last(), Some((_, o)) if o == opener) {
+            let (start, opener) = open.pop().unwrap();
+            pairs.push((start, (row, column), opener));
+          }
+        }
+      }
+    }
+    pairs
+  }
+
+  /// `span` without the whitespace and line ends around it.
+  fn trim(&self, mut span: Span) -> Span {
+    while span.start < span.end {
+      let (row, column) = span.start;
+      match self.lines[row].get(column) {
+        None => span.start = (row + 1, 0),
+        Some(grapheme) if is_space(grapheme) => span.start.1 += 1,
+        Some(_) => break,
+      }
+    }
+    while span.end > span.start {
+      let (row, column) = span.end;
+      if column == 0 {
+        span.end = (row - 1, self.lines[row - 1].len());
+      } else if is_space(self.lines[row][column - 1]) {
+        span.end.1 -= 1;
+      } else {
+        break;
+      }
+    }
+    span
+  }
+
+  /// The word `span` is in, if it is within one.
+  fn word(&self, span: &Span) -> Option<Span> {
+    if span.start.0 != span.end.0 {
+      return None;
+    }
+    let line = &self.lines[span.start.0];
+    if !line[span.start.1..span.end.1].iter().all(|g| is_word(g)) {
+      return None;
+    }
+    let mut start = span.start.1;
+    while start > 0 && is_word(line[start - 1]) {
+      start -= 1;
+    }
+    let mut end = span.end.1;
+    while end < line.len() && is_word(line[end]) {
+      end += 1;
+    }
+    Some(Span::new((span.start.0, start), (span.start.0, end)))
+  }
+
+  /// The lines of `span`, without their indentation.
+  fn statement(&self, span: &Span) -> Span {
+    Span::new(
+      (span.start.0, self.indent(span.start.0).min(span.start.1)),
+      (span.end.0, self.trimmed_len(span.end.0).max(span.end.1)),
+    )
+  }
+
+  /// The spans a selection can grow to: the brackets with and without their
+  /// contents, a call before parentheses, and a block with the line it
+  /// starts on.
+  fn bracket_spans(&self) -> Vec<Span> {
+    let mut spans = vec![];
+    for (open, close, opener) in self.pairs() {
+      let inner = Span::new((open.0, open.1 + 1), close);
+      let outer = Span::new(open, (close.0, close.1 + 1));
+      spans.push(self.trim(inner));
+      spans.push(outer);
+      if opener == "{" {
+        spans.push(Span::new((open.0, self.indent(open.0)), outer.end));
+      } else {
+        let line = &self.lines[open.0];
+        let mut start = open.1;
+        while start > 0
+          && (is_word(line[start - 1]) || matches!(line[start - 1], "." | ":"))
+        {
+          start -= 1;
+        }
+        spans.push(Span::new((open.0, start), outer.end));
+      }
+    }
+    spans
+  }
+}
+
+/// The smallest structure enclosing `span` in `lines`, `None` if it covers
+/// everything already.
+pub fn expand(lines: &[String], span: Span) -> Option<Span> {
+  if lines.is_empty() {
+    return None;
+  }
+  let text = Text::new(lines);
+  let last = lines.len() - 1;
+  let mut candidates = vec![];
+  candidates.extend(text.word(&span));
+  candidates.extend(text.bracket_spans());
+  candidates.push(text.statement(&span));
+  candidates.push(Span::new((0, 0), (last, text.lines[last].len())));
+  candidates
+    .into_iter()
+    .filter(|candidate| candidate.contains(&span) && *candidate != span)
+    .min_by_key(|candidate| text.len(candidate))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(text: &str) -> Vec<String> {
+    text.lines().map(String::from).collect()
+  }
+
+  fn expansions(lines: &[String], position: Position) -> Vec<Span> {
+    let mut span = Span::new(position, position);
+    let mut spans = vec![];
+    while let Some(next) = expand(lines, span) {
+      spans.push(next);
+      span = next;
+    }
+    spans
+  }
+
+  #[test]
+  fn grows_to_enclosing_structure() {
+    let text = lines("fn main() {\n  let x = foo(bar, baz);\n  x\n}");
+    assert_eq!(
+      expansions(&text, (1, 15)),
+      vec![
+        // bar
+        Span::new((1, 14), (1, 17)),
+        // bar, baz
+        Span::new((1, 14), (1, 22)),
+        // (bar, baz)
+        Span::new((1, 13), (1, 23)),
+        // foo(bar, baz)
+        Span::new((1, 10), (1, 23)),
+        // let x = foo(bar, baz);
+        Span::new((1, 2), (1, 24)),
+        // the body
+        Span::new((1, 2), (2, 3)),
+        Span::new((0, 10), (3, 1)),
+        // the function, which is all of the text
+        Span::new((0, 0), (3, 1)),
+      ]
+    );
+  }
+
+  #[test]
+  fn unbalanced_brackets() {
+    let text = lines("a) (b");
+    assert_eq!(
+      expansions(&text, (0, 4)),
+      vec![Span::new((0, 4), (0, 5)), Span::new((0, 0), (0, 5))]
+    );
+    assert_eq!(expand(&[], Span::new((0, 0), (0, 0))), None);
+  }
+}
@@ -0,0 +1,386 @@
+use crate::lsp::Notify;
+use crate::processes::{Kind, Processes};
+use serde::Deserialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_OUTPUT_LINES: usize = 10000;
+
+/// A command to run in the workspace, such as `cargo build`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TaskConfig {
+  /// Run by the shell, so it can use pipes and variables.
+  pub command: String,
+  /// Directory to run in, relative to the workspace.
+  #[serde(default)]
+  pub cwd: Option<PathBuf>,
+}
+
+impl TaskConfig {
+  fn new(command: &str) -> Self {
+    Self {
+      command: command.to_string(),
+      cwd: None,
+    }
+  }
+}
+
+/// The tasks of a workspace by name, from `.devcode/tasks.toml`. Cargo
+/// workspaces get build, test and run tasks unless they define their own.
+pub fn load(workspace: &Path) -> BTreeMap<String, TaskConfig> {
+  let path = workspace.join(".devcode").join("tasks.toml");
+  match std::fs::read_to_string(&path) {
+    Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+      tracing::warn!("failed to parse {}: {}", path.display(), err);
+      BTreeMap::new()
+    }),
+    Err(_) if workspace.join("Cargo.toml").is_file() => vec![
+      ("build", TaskConfig::new("cargo build")),
+      ("test", TaskConfig::new("cargo test")),
+      ("run", TaskConfig::new("cargo run")),
+    ]
+    .into_iter()
+    .map(|(name, task)| (name.to_string(), task))
+    .collect(),
+    Err(_) => BTreeMap::new(),
+  }
+}
+
+/// A position in a file mentioned by compiler output, zero based.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+  pub path: PathBuf,
+  pub line: usize,
+  pub column: usize,
+}
+
+/// Whether `path` looks like a file name rather than some other word
+/// followed by a number, like `localhost:8080`.
+fn is_path(path: &str) -> bool {
+  !path.is_empty()
+    && !path.contains(char::is_whitespace)
+    && (path.contains('.') || path.contains('/') || path.contains('\\'))
+}
+
+fn leading_number(text: &str) -> Option<(usize, &str)> {
+  let end = text
+    .find(|ch: char| !ch.is_ascii_digit())
+    .unwrap_or(text.len());
+  let number = text[..end].parse::<usize>().ok()?;
+  Some((number, &text[end..]))
+}
+
+/// Finds a file location in a line of output, as printed by rustc
+/// (`--> src/main.rs:3:5`), most C compilers and linters
+/// (`main.c:3:5: error`) and Python tracebacks (`File "a.py", line 3`).
+/// Relative paths are resolved against `cwd`.
+pub fn parse_location(line: &str, cwd: &Path) -> Option<Location> {
+  let line = line.trim_start();
+  let line = line
+    .strip_prefix("--> ")
+    .or_else(|| line.strip_prefix("::: "))
+    .unwrap_or(line);
+
+  let (path, number, column) = if let Some(rest) = line.strip_prefix("File \"")
+  {
+    let end = rest.find('"')?;
+    let (number, _) = leading_number(rest[end..].strip_prefix("\", line ")?)?;
+    (&rest[..end], number, None)
+  } else {
+    line.match_indices(':').find_map(|(i, _)| {
+      let path = &line[..i];
+      if !is_path(path) {
+        return None;
+      }
+      let (number, rest) = leading_number(&line[i + 1..])?;
+      let column = rest
+        .strip_prefix(':')
+        .and_then(leading_number)
+        .map(|(column, _)| column);
+      Some((path, number, column))
+    })?
+  };
+
+  Some(Location {
+    path: cwd.join(path),
+    line: number.saturating_sub(1),
+    column: column.unwrap_or(1).saturating_sub(1),
+  })
+}
+
+/// The lines a task printed, up to the last `MAX_OUTPUT_LINES`.
+#[derive(Default)]
+pub struct Output {
+  pub lines: VecDeque<String>,
+  /// How many lines were dropped from the front.
+  pub dropped: usize,
+}
+
+impl Output {
+  fn push(&mut self, line: String) {
+    if self.lines.len() == MAX_OUTPUT_LINES {
+      self.lines.pop_front();
+      self.dropped += 1;
+    }
+    self.lines.push_back(line);
+  }
+}
+
+/// A running or finished task, collecting the lines it prints to stdout and
+/// stderr.
+pub struct Task {
+  pub name: String,
+  /// Directory relative paths in the output are resolved against.
+  pub cwd: PathBuf,
+  pub output: Arc<Mutex<Output>>,
+  child: Arc<Mutex<Child>>,
+  status: Arc<Mutex<Option<ExitStatus>>>,
+  processes: Processes,
+}
+
+impl Task {
+  /// Starts the task, calling `notify` whenever it prints a line or exits.
+  pub fn spawn(
+    name: &str,
+    config: &TaskConfig,
+    workspace: &Path,
+    notify: Notify,
+    processes: &Processes,
+  ) -> Result<Self, anyhow::Error> {
+    let cwd = match &config.cwd {
+      Some(cwd) => workspace.join(cwd),
+      None => workspace.to_path_buf(),
+    };
+    let mut command = if cfg!(windows) {
+      let mut command = Command::new("cmd");
+      command.arg("/C");
+      command
+    } else {
+      let mut command = Command::new("sh");
+      command.arg("-c");
+      command
+    };
+    let mut child = processes.spawn(
+      command
+        .arg(&config.command)
+        .current_dir(&cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped()),
+      Kind::Task,
+      name,
+    )?;
+
+    let output = Arc::new(Mutex::new(Output::default()));
+    output.lock().unwrap().push(format!("> {}", config.command));
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let readers = vec![
+      read_lines(stdout, Arc::clone(&output), Arc::clone(&notify)),
+      read_lines(stderr, Arc::clone(&output), Arc::clone(&notify)),
+    ];
+
+    let pid = child.id();
+    let child = Arc::new(Mutex::new(child));
+    let status = Arc::new(Mutex::new(None));
+    {
+      let child = Arc::clone(&child);
+      let status = Arc::clone(&status);
+      let output = Arc::clone(&output);
+      let processes = processes.clone();
+      // polled instead of waited on, so the task can still be stopped
+      std::thread::spawn(move || loop {
+        match child.lock().unwrap().try_wait() {
+          Ok(Some(exit_status)) => {
+            processes.exited(pid);
+            *status.lock().unwrap() = Some(exit_status);
+            notify();
+            // processes started by the task can keep the pipes open longer
+            for reader in readers {
+              let _ = reader.join();
+            }
+            output
+              .lock()
+              .unwrap()
+              .push(format!("> finished with {}", exit_status));
+            notify();
+            break;
+          }
+          Ok(None) => {}
+          Err(err) => {
+            tracing::warn!("failed to wait for task: {}", err);
+            break;
+          }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+      });
+    }
+
+    Ok(Self {
+      name: name.to_string(),
+      cwd,
+      output,
+      child,
+      status,
+      processes: processes.clone(),
+    })
+  }
+
+  /// The exit status, `None` while the task is running.
+  pub fn status(&self) -> Option<ExitStatus> {
+    *self.status.lock().unwrap()
+  }
+
+  /// Kills the task and the processes it started.
+  pub fn stop(&self) {
+    if self.status().is_none() {
+      self.processes.kill(self.child.lock().unwrap().id());
+    }
+  }
+}
+
+fn read_lines(
+  pipe: impl Read + Send + 'static,
+  output: Arc<Mutex<Output>>,
+  notify: Notify,
+) -> std::thread::JoinHandle<()> {
+  std::thread::spawn(move || {
+    let mut reader = BufReader::new(pipe);
+    let mut line = vec![];
+    loop {
+      line.clear();
+      match reader.read_until(b'\n', &mut line) {
+        Ok(0) => break,
+        Ok(_) => {}
+        Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+        Err(_) => break,
+      }
+      // output that isn't UTF-8 is still shown, with replacement characters
+      let text = String::from_utf8_lossy(&line);
+      let text = text.strip_suffix('\n').unwrap_or(&text);
+      let text = text.strip_suffix('\r').unwrap_or(text);
+      output.lock().unwrap().push(text.to_string());
+      notify();
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn location(path: &str, line: usize, column: usize) -> Option<Location> {
+    Some(Location {
+      path: PathBuf::from("/ws").join(path),
+      line,
+      column,
+    })
+  }
+
+  #[test]
+  fn locations() {
+    let cwd = Path::new("/ws");
+    assert_eq!(
+      parse_location("  --> src/main.rs:12:5", cwd),
+      location("src/main.rs", 11, 4)
+    );
+    assert_eq!(
+      parse_location("main.c:3:10: error: expected ';'", cwd),
+      location("main.c", 2, 9)
+    );
+    assert_eq!(
+      parse_location("lib.ts:7: warning", cwd),
+      location("lib.ts", 6, 0)
+    );
+    assert_eq!(
+      parse_location("  File \"app/x.py\", line 4, in main", cwd),
+      location("app/x.py", 3, 0)
+    );
+    assert_eq!(
+      parse_location("/abs/a.rs:1:1", cwd),
+      Some(Location {
+        path: PathBuf::from("/abs/a.rs"),
+        line: 0,
+        column: 0,
+      })
+    );
+    assert_eq!(parse_location("listening on localhost:8080", cwd), None);
+    assert_eq!(parse_location("   Compiling devcode v0.1.0", cwd), None);
+  }
+
+  #[test]
+  fn running() {
+    let task = Task::spawn(
+      "echo",
+      &TaskConfig::new("echo out && echo err >&2 && exit 3"),
+      &std::env::temp_dir(),
+      Arc::new(|| {}),
+      &Processes::default(),
+    )
+    .unwrap();
+    while !task
+      .output
+      .lock()
+      .unwrap()
+      .lines
+      .back()
+      .unwrap()
+      .contains("finished")
+    {
+      std::thread::sleep(POLL_INTERVAL);
+    }
+    assert!(!task.status().unwrap().success());
+    let output = task.output.lock().unwrap();
+    assert!(output.lines.contains(&"out".to_string()));
+    assert!(output.lines.contains(&"err".to_string()));
+  }
+
+  #[test]
+  fn output_limit() {
+    let mut output = Output::default();
+    for i in 0..MAX_OUTPUT_LINES + 2 {
+      output.push(i.to_string());
+    }
+    assert_eq!(output.lines.len(), MAX_OUTPUT_LINES);
+    assert_eq!(output.dropped, 2);
+    assert_eq!(output.lines[0], "2");
+  }
+
+  #[test]
+  fn invalid_utf8() {
+    let task = Task::spawn(
+      "printf",
+      &TaskConfig::new("printf 'a\\377b\\r\\nc'"),
+      &std::env::temp_dir(),
+      Arc::new(|| {}),
+      &Processes::default(),
+    )
+    .unwrap();
+    while !task
+      .output
+      .lock()
+      .unwrap()
+      .lines
+      .back()
+      .unwrap()
+      .contains("finished")
+    {
+      std::thread::sleep(POLL_INTERVAL);
+    }
+    let output = task.output.lock().unwrap();
+    assert_eq!(output.lines[1], "a\u{fffd}b");
+    assert_eq!(output.lines[2], "c");
+  }
+
+  #[test]
+  fn cargo_defaults() {
+    let tasks = load(Path::new(env!("CARGO_MANIFEST_DIR")));
+    assert_eq!(tasks["build"].command, "cargo build");
+  }
+}
@@ -0,0 +1,52 @@
+//! Small text helpers shared by unrelated features.
+
+/// Whether `ch` can be part of a word, like an identifier.
+pub fn is_word_char(ch: char) -> bool {
+  ch.is_alphanumeric() || ch == '_'
+}
+
+/// Whether `grapheme` is made of word characters.
+pub fn is_word(grapheme: &str) -> bool {
+  grapheme.chars().all(is_word_char)
+}
+
+/// Decodes the `%XX` escapes of `text`, `None` if one of them is invalid.
+pub fn percent_decode(text: &str) -> Option<Vec<u8>> {
+  let mut bytes = vec![];
+  let mut rest = text.as_bytes();
+  while let Some((&byte, after)) = rest.split_first() {
+    rest = after;
+    if byte == b'%' {
+      // `from_str_radix` would also take a sign
+      let hex = after
+        .get(..2)
+        .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+      bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+      rest = &after[2..];
+    } else {
+      bytes.push(byte);
+    }
+  }
+  Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn words() {
+    assert!(is_word("a_1"));
+    assert!(is_word("é"));
+    assert!(!is_word("-"));
+    assert!(!is_word(" "));
+  }
+
+  #[test]
+  fn percent_decoding() {
+    assert_eq!(percent_decode("a%20b%c3%A4").unwrap(), "a bä".as_bytes());
+    assert_eq!(percent_decode("100%"), None);
+    assert_eq!(percent_decode("%+1"), None);
+    assert_eq!(percent_decode("%zz"), None);
+  }
+}
@@ -0,0 +1,285 @@
+//! Commands transforming the selected text, or all of it when nothing is
+//! selected.
+
+use crate::commands::Command;
+use crate::structure::{self, Span};
+use crate::text;
+use unicode_segmentation::UnicodeSegmentation;
+
+const BASE64: &[u8; 64] =
+  b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transform {
+  SortAscending,
+  SortDescending,
+  /// Sorting and leaving out repeated lines.
+  SortUnique,
+  Reverse,
+  Upper,
+  Lower,
+  Title,
+  Base64Encode,
+  Base64Decode,
+  UrlEncode,
+  UrlDecode,
+}
+
+impl Transform {
+  pub fn from_command(command: Command) -> Option<Self> {
+    Some(match command {
+      Command::SortLinesAscending => Self::SortAscending,
+      Command::SortLinesDescending => Self::SortDescending,
+      Command::SortLinesUnique => Self::SortUnique,
+      Command::ReverseLines => Self::Reverse,
+      Command::TransformToUppercase => Self::Upper,
+      Command::TransformToLowercase => Self::Lower,
+      Command::TransformToTitleCase => Self::Title,
+      Command::Base64Encode => Self::Base64Encode,
+      Command::Base64Decode => Self::Base64Decode,
+      Command::UrlEncode => Self::UrlEncode,
+      Command::UrlDecode => Self::UrlDecode,
+      _ => return None,
+    })
+  }
+
+  /// Whether it rearranges whole lines, so the selection is extended to the
+  /// lines it touches.
+  fn by_lines(self) -> bool {
+    matches!(
+      self,
+      Self::SortAscending
+        | Self::SortDescending
+        | Self::SortUnique
+        | Self::Reverse
+    )
+  }
+
+  fn apply(self, text: &str) -> Result<String, anyhow::Error> {
+    let mut lines = text.split('\n').collect::<Vec<_>>();
+    Ok(match self {
+      Self::SortAscending => {
+        lines.sort_unstable();
+        lines.join("\n")
+      }
+      Self::SortDescending => {
+        lines.sort_unstable_by(|a, b| b.cmp(a));
+        lines.join("\n")
+      }
+      Self::SortUnique => {
+        lines.sort_unstable();
+        lines.dedup();
+        lines.join("\n")
+      }
+      Self::Reverse => {
+        lines.reverse();
+        lines.join("\n")
+      }
+      Self::Upper => text.to_uppercase(),
+      Self::Lower => text.to_lowercase(),
+      Self::Title => title_case(text),
+      Self::Base64Encode => base64_encode(text.as_bytes()),
+      Self::Base64Decode => String::from_utf8(base64_decode(text)?)?,
+      Self::UrlEncode => url_encode(text),
+      Self::UrlDecode => String::from_utf8(
+        text::percent_decode(text)
+          .ok_or_else(|| anyhow::anyhow!("invalid escape in URL"))?,
+      )?,
+    })
+  }
+}
+
+fn title_case(text: &str) -> String {
+  text
+    .split_word_bounds()
+    .map(|word| {
+      let mut chars = word.chars();
+      match chars.next() {
+        Some(first) => first
+          .to_uppercase()
+          .chain(chars.flat_map(char::to_lowercase))
+          .collect(),
+        None => String::new(),
+      }
+    })
+    .collect()
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+  let mut encoded = String::new();
+  for chunk in bytes.chunks(3) {
+    let value = chunk.iter().enumerate().fold(0u32, |value, (i, byte)| {
+      value | (*byte as u32) << (16 - i * 8)
+    });
+    for i in 0..4 {
+      if i <= chunk.len() {
+        let index = (value >> (18 - i * 6)) & 0x3f;
+        encoded.push(BASE64[index as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+  encoded
+}
+
+fn base64_decode(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+  let digits = text
+    .chars()
+    .filter(|ch| !ch.is_whitespace())
+    .collect::<Vec<_>>();
+  let unpadded = digits
+    .iter()
+    .position(|ch| *ch == '=')
+    .unwrap_or(digits.len());
+  if digits.len() % 4 != 0
+    || digits.len() - unpadded > 2
+    || digits[unpadded..].iter().any(|ch| *ch != '=')
+  {
+    anyhow::bail!("not valid base64");
+  }
+
+  let mut bytes = vec![];
+  for chunk in digits[..unpadded].chunks(4) {
+    let mut value = 0u32;
+    for (i, ch) in chunk.iter().enumerate() {
+      let digit = BASE64
+        .iter()
+        .position(|digit| *digit as char == *ch)
+        .ok_or_else(|| anyhow::anyhow!("'{}' is not a base64 digit", ch))?;
+      value |= (digit as u32) << (18 - i * 6);
+    }
+    for i in 0..chunk.len() - 1 {
+      bytes.push((value >> (16 - i * 8)) as u8);
+    }
+  }
+  Ok(bytes)
+}
+
+fn url_encode(text: &str) -> String {
+  text
+    .bytes()
+    .map(|byte| match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+        (byte as char).to_string()
+      }
+      _ => format!("%{:02X}", byte),
+    })
+    .collect()
+}
+
+/// `lines` with the text of `span` transformed, and where the transformed
+/// text is. Line transforms extend `span` to whole lines first.
+pub fn apply(
+  lines: &[String],
+  mut span: Span,
+  transform: Transform,
+) -> Result<(Vec<String>, Span), anyhow::Error> {
+  if transform.by_lines() {
+    // a selection ending at the start of a line doesn't include it
+    if span.end.1 == 0 && span.end.0 > span.start.0 {
+      span.end.0 -= 1;
+    }
+    span.start.1 = 0;
+    span.end.1 = lines[span.end.0].graphemes(true).count();
+  }
+  let start = structure::byte_offset(&lines[span.start.0], span.start.1);
+  let end = structure::byte_offset(&lines[span.end.0], span.end.1);
+  let mut selected = lines[span.start.0..=span.end.0].join("\n");
+  let after_end = selected.len() - (lines[span.end.0].len() - end);
+  selected.truncate(after_end);
+  let transformed = transform.apply(&selected[start..])?;
+
+  let text = format!(
+    "{}{}{}",
+    &lines[span.start.0][..start],
+    transformed,
+    &lines[span.end.0][end..]
+  );
+  let replaced = text.split('\n').map(String::from).collect::<Vec<_>>();
+  let last = transformed.split('\n').count() - 1 + span.start.0;
+  let end_column = if last == span.start.0 {
+    span.start.1 + transformed.graphemes(true).count()
+  } else {
+    transformed
+      .rsplit('\n')
+      .next()
+      .unwrap_or("")
+      .graphemes(true)
+      .count()
+  };
+
+  let mut result = lines[..span.start.0].to_vec();
+  result.extend(replaced);
+  result.extend_from_slice(&lines[span.end.0 + 1..]);
+  Ok((result, Span::new(span.start, (last, end_column))))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(text: &str) -> Vec<String> {
+    text.split('\n').map(String::from).collect()
+  }
+
+  #[test]
+  fn transforms_lines() {
+    let text = lines("c\na\nb\na");
+    let all = Span::new((0, 0), (3, 1));
+    let sorted = |transform| apply(&text, all, transform).unwrap().0;
+    assert_eq!(sorted(Transform::SortAscending), lines("a\na\nb\nc"));
+    assert_eq!(sorted(Transform::SortDescending), lines("c\nb\na\na"));
+    assert_eq!(sorted(Transform::SortUnique), lines("a\nb\nc"));
+    assert_eq!(sorted(Transform::Reverse), lines("a\nb\na\nc"));
+
+    // the selection covers the lines it touches, up to where it ends
+    assert_eq!(
+      apply(&text, Span::new((0, 1), (2, 0)), Transform::SortAscending)
+        .unwrap(),
+      (lines("a\nc\nb\na"), Span::new((0, 0), (1, 1)))
+    );
+  }
+
+  #[test]
+  fn transforms_text() {
+    let text = lines("let ä = \"hello world\";");
+    let quoted = Span::new((0, 9), (0, 20));
+    let transformed =
+      |transform| apply(&text, quoted, transform).unwrap().0.join("\n");
+    assert_eq!(transformed(Transform::Upper), "let ä = \"HELLO WORLD\";");
+    assert_eq!(transformed(Transform::Title), "let ä = \"Hello World\";");
+    assert_eq!(
+      transformed(Transform::UrlEncode),
+      "let ä = \"hello%20world\";"
+    );
+    assert_eq!(
+      apply(&text, quoted, Transform::Base64Encode).unwrap(),
+      (
+        lines("let ä = \"aGVsbG8gd29ybGQ=\";"),
+        Span::new((0, 9), (0, 25))
+      )
+    );
+    assert_eq!(
+      apply(&text, Span::new((0, 0), (0, 22)), Transform::Lower)
+        .unwrap()
+        .0,
+      text
+    );
+  }
+
+  #[test]
+  fn encodings_round_trip() {
+    for text in &["", "a", "ab", "abc", "ä😀 /?&"] {
+      let encoded = Transform::Base64Encode.apply(text).unwrap();
+      assert_eq!(Transform::Base64Decode.apply(&encoded).unwrap(), *text);
+      let encoded = Transform::UrlEncode.apply(text).unwrap();
+      assert_eq!(Transform::UrlDecode.apply(&encoded).unwrap(), *text);
+    }
+    assert_eq!(base64_encode(b"ab"), "YWI=");
+    assert!(Transform::Base64Decode.apply("YW=I").is_err());
+    assert!(Transform::Base64Decode.apply("YWI").is_err());
+    assert!(Transform::UrlDecode.apply("%zz").is_err());
+    assert!(Transform::UrlDecode.apply("%C3").is_err());
+  }
+}
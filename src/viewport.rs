@@ -0,0 +1,157 @@
+//! How far the text of an editor can be scrolled. Offsets are negative, the
+//! text moving up and to the left as it is scrolled.
+
+use serde::Deserialize;
+
+/// Rows left in view when scrolling a page past the end.
+const KEPT_ROWS: usize = 3;
+
+/// How far the text can be scrolled past its last line.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overscroll {
+  /// Until only the last few rows are in view.
+  Page,
+  /// Until the last row is at the bottom.
+  None,
+  /// Until the last row is at the top.
+  Full,
+}
+
+impl Default for Overscroll {
+  fn default() -> Self {
+    Self::Page
+  }
+}
+
+/// Furthest the text of `rows` rows can be scrolled down in a view `height`
+/// high. Text that fits doesn't scroll, unless it can be scrolled fully.
+pub fn vertical_limit(
+  rows: usize,
+  row_height: f32,
+  height: f32,
+  overscroll: Overscroll,
+) -> f64 {
+  let content = rows as f32 * row_height;
+  let limit = match overscroll {
+    Overscroll::Full => rows.saturating_sub(1) as f32 * row_height,
+    _ if content <= height => 0.0,
+    Overscroll::Page => {
+      (rows.saturating_sub(KEPT_ROWS) as f32 * row_height).max(content - height)
+    }
+    Overscroll::None => content - height,
+  };
+  limit as f64
+}
+
+/// The first row in view at `offset` and where its top is, relative to the
+/// top of the view.
+pub fn first_row(offset: f64, row_height: f32) -> (usize, f32) {
+  let first = (-offset / row_height as f64).floor().max(0.0) as usize;
+  (first, offset as f32 + (first as f32 * row_height))
+}
+
+/// The offset which puts `row` in the middle of a view `height` high.
+pub fn centered(row: usize, row_height: f32, height: f32) -> f64 {
+  (((height - row_height) / 2.0) - (row as f32 * row_height)) as f64
+}
+
+/// The lowest and the highest offset the text of `rows` rows can be
+/// scrolled to. In typewriter mode every row can be scrolled to the middle,
+/// which pulls the first ones down from the top.
+pub fn vertical_range(
+  rows: usize,
+  row_height: f32,
+  height: f32,
+  overscroll: Overscroll,
+  typewriter: bool,
+) -> (f64, f64) {
+  if typewriter {
+    let last = rows.saturating_sub(1);
+    (
+      centered(last, row_height, height).min(0.0),
+      centered(0, row_height, height).max(0.0),
+    )
+  } else {
+    (-vertical_limit(rows, row_height, height, overscroll), 0.0)
+  }
+}
+
+/// Furthest lines up to `longest` wide can be scrolled right in a view
+/// `width` wide.
+pub fn horizontal_limit(longest: f32, width: f32) -> f64 {
+  (longest - width).max(0.0) as f64
+}
+
+/// The offset closest to `offset` showing the content from `start` to `end`
+/// in a view `extent` long, its start if it doesn't fit.
+pub fn revealing(offset: f64, start: f32, end: f32, extent: f32) -> f64 {
+  if (start as f64) < -offset || end - start > extent {
+    -start as f64
+  } else if (end as f64) > extent as f64 - offset {
+    (extent - end) as f64
+  } else {
+    offset
+  }
+}
+
+/// `offset` kept between the start and `limit`.
+pub fn clamp(offset: f64, limit: f64) -> f64 {
+  clamp_to(offset, (-limit, 0.0))
+}
+
+/// `offset` kept between the lowest and the highest offset of `range`.
+pub fn clamp_to(offset: f64, (lowest, highest): (f64, f64)) -> f64 {
+  offset.min(highest).max(lowest)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn limits_scrolling() {
+    // 100 rows of 10 in a view of 20 rows
+    assert_eq!(vertical_limit(100, 10.0, 200.0, Overscroll::Page), 970.0);
+    assert_eq!(vertical_limit(100, 10.0, 200.0, Overscroll::None), 800.0);
+    // a view shorter than the rows kept still reaches the last line
+    assert_eq!(vertical_limit(100, 10.0, 20.0, Overscroll::Page), 980.0);
+    assert_eq!(vertical_limit(20, 10.0, 200.0, Overscroll::Page), 0.0);
+    assert_eq!(horizontal_limit(500.0, 300.0), 200.0);
+    assert_eq!(horizontal_limit(200.0, 300.0), 0.0);
+
+    assert_eq!(clamp(-1000.0, 800.0), -800.0);
+    assert_eq!(clamp(20.0, 800.0), 0.0);
+    assert_eq!(clamp(-10.0, 0.0), 0.0);
+
+    // scrolls as little as it takes, to the start of what doesn't fit
+    assert_eq!(revealing(-50.0, 60.0, 70.0, 100.0), -50.0);
+    assert_eq!(revealing(-50.0, 20.0, 30.0, 100.0), -20.0);
+    assert_eq!(revealing(-50.0, 160.0, 170.0, 100.0), -70.0);
+    assert_eq!(revealing(0.0, 160.0, 300.0, 100.0), -160.0);
+  }
+
+  #[test]
+  fn scrolls_past_the_ends() {
+    // the last line can go to the top, even when the text fits
+    assert_eq!(vertical_limit(100, 10.0, 200.0, Overscroll::Full), 990.0);
+    assert_eq!(vertical_limit(5, 10.0, 200.0, Overscroll::Full), 40.0);
+    assert_eq!(
+      vertical_range(100, 10.0, 200.0, Overscroll::None, false),
+      (-800.0, 0.0)
+    );
+
+    // typewriter mode centers the first and the last row
+    assert_eq!(centered(0, 10.0, 210.0), 100.0);
+    assert_eq!(centered(30, 10.0, 210.0), -200.0);
+    assert_eq!(
+      vertical_range(100, 10.0, 210.0, Overscroll::None, true),
+      (-890.0, 100.0)
+    );
+    assert_eq!(clamp_to(150.0, (-890.0, 100.0)), 100.0);
+    assert_eq!(clamp_to(-50.0, (-890.0, 100.0)), -50.0);
+
+    assert_eq!(first_row(-25.0, 10.0), (2, -5.0));
+    assert_eq!(first_row(30.0, 10.0), (0, 30.0));
+  }
+}
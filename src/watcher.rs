@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use winit::event_loop::EventLoopProxy;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+  std::fs::metadata(path)
+    .and_then(|metadata| metadata.modified())
+    .ok()
+}
+
+/// Polls `path` on a background thread and sends `event` to the event loop
+/// whenever the file is created, modified or removed.
+pub fn watch<T>(path: PathBuf, proxy: EventLoopProxy<T>, event: T)
+where
+  T: Clone + Send + 'static,
+{
+  std::thread::spawn(move || {
+    let mut last_modified = modified(&path);
+    loop {
+      std::thread::sleep(POLL_INTERVAL);
+      let current = modified(&path);
+      if current != last_modified {
+        last_modified = current;
+        // the event loop is gone once this fails
+        if proxy.send_event(event.clone()).is_err() {
+          break;
+        }
+      }
+    }
+  });
+}
@@ -0,0 +1,191 @@
+use serde::Deserialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How continuation rows of a wrapped line are indented.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WrapIndent {
+  /// Start at the left edge.
+  None,
+  /// Line up with the indentation of the line.
+  Same,
+  /// Indent `hang` columns more than the line.
+  Hang,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct WrapConfig {
+  /// Whether lines wider than the editor are wrapped.
+  pub soft_wrap: bool,
+  /// Shown at the start of continuation rows.
+  pub indicator: String,
+  pub indent: WrapIndent,
+  /// Columns to indent continuation rows by for `indent = "hang"`.
+  pub hang: usize,
+}
+
+impl Default for WrapConfig {
+  fn default() -> Self {
+    Self {
+      soft_wrap: false,
+      indicator: String::from("\u{21aa}"),
+      indent: WrapIndent::Same,
+      hang: 2,
+    }
+  }
+}
+
+impl WrapConfig {
+  /// Columns to indent the continuation rows of `line` by.
+  pub fn continuation_indent(&self, line: &str) -> usize {
+    let leading = line
+      .graphemes(true)
+      .take_while(|grapheme| grapheme.trim().is_empty())
+      .count();
+    match self.indent {
+      WrapIndent::None => 0,
+      WrapIndent::Same => leading,
+      WrapIndent::Hang => leading + self.hang,
+    }
+  }
+}
+
+/// A row on screen showing part of a line.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VisualRow {
+  pub line: usize,
+  /// Range of graphemes of the line shown in the row.
+  pub start: usize,
+  pub end: usize,
+  /// Columns a continuation row is indented by, `None` for the first row of
+  /// a line.
+  pub continuation: Option<usize>,
+}
+
+/// Splits `line` into grapheme ranges no wider than `width`, breaking after
+/// whitespace where possible. Continuation rows are `indent_width` narrower.
+pub fn wrap_line(
+  line: &str,
+  width: f32,
+  indent_width: f32,
+  advance: impl Fn(&str) -> f32,
+) -> Vec<(usize, usize)> {
+  let graphemes = line.graphemes(true).collect::<Vec<_>>();
+  let mut rows = vec![];
+  let mut start = 0;
+  let mut available = width;
+  let mut x = 0.0;
+  let mut last_break = None;
+  for (i, grapheme) in graphemes.iter().enumerate() {
+    let grapheme_width = advance(grapheme);
+    if x + grapheme_width > available && i > start {
+      let end = match last_break {
+        Some(end) if end > start => end,
+        _ => i,
+      };
+      rows.push((start, end));
+      start = end;
+      available = width - indent_width;
+      x = graphemes[start..i].iter().map(|g| advance(g)).sum();
+      last_break = None;
+    }
+    x += grapheme_width;
+    if grapheme.trim().is_empty() {
+      last_break = Some(i + 1);
+    }
+  }
+  rows.push((start, graphemes.len()));
+  rows
+}
+
+/// Wraps all `lines`, see [`wrap_line`].
+pub fn wrap(
+  lines: &[String],
+  width: f32,
+  config: &WrapConfig,
+  advance: impl Fn(&str) -> f32,
+) -> Vec<VisualRow> {
+  let space = advance(" ");
+  let indicator = advance(&config.indicator);
+  let mut rows = vec![];
+  for (i, line) in lines.iter().enumerate() {
+    let indent = config.continuation_indent(line);
+    let indent_width = (indent as f32 * space) + indicator;
+    for (j, (start, end)) in wrap_line(line, width, indent_width, &advance)
+      .into_iter()
+      .enumerate()
+    {
+      rows.push(VisualRow {
+        line: i,
+        start,
+        end,
+        continuation: if j == 0 { None } else { Some(indent) },
+      });
+    }
+  }
+  rows
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn monospace(text: &str) -> f32 {
+    text.graphemes(true).count() as f32
+  }
+
+  #[test]
+  fn wrapping() {
+    assert_eq!(wrap_line("", 10.0, 0.0, monospace), vec![(0, 0)]);
+    assert_eq!(wrap_line("short", 10.0, 0.0, monospace), vec![(0, 5)]);
+    // breaks after whitespace
+    assert_eq!(
+      wrap_line("aaa bbb ccc ddd", 10.0, 0.0, monospace),
+      vec![(0, 8), (8, 15)]
+    );
+    // long words are split anywhere, continuation rows are narrower
+    assert_eq!(
+      wrap_line("abcdefghij", 4.0, 2.0, monospace),
+      vec![(0, 4), (4, 6), (6, 8), (8, 10)]
+    );
+    // an indent wider than the row still makes progress
+    assert_eq!(
+      wrap_line("abc", 1.0, 5.0, monospace),
+      vec![(0, 1), (1, 2), (2, 3)]
+    );
+  }
+
+  #[test]
+  fn continuation_indent() {
+    let mut config = WrapConfig {
+      indicator: String::from(">"),
+      ..WrapConfig::default()
+    };
+    assert_eq!(config.continuation_indent("  foo bar"), 2);
+    config.indent = WrapIndent::Hang;
+    assert_eq!(config.continuation_indent("  foo bar"), 4);
+    config.indent = WrapIndent::None;
+    assert_eq!(config.continuation_indent("  foo bar"), 0);
+
+    config.indent = WrapIndent::Same;
+    let rows = wrap(&["  aaaa bbbb".to_string()], 8.0, &config, monospace);
+    assert_eq!(
+      rows,
+      vec![
+        VisualRow {
+          line: 0,
+          start: 0,
+          end: 7,
+          continuation: None,
+        },
+        VisualRow {
+          line: 0,
+          start: 7,
+          end: 11,
+          continuation: Some(2),
+        },
+      ]
+    );
+  }
+}